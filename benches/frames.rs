@@ -0,0 +1,51 @@
+//! Benchmarks of `zip_frames` against a loop that indexes the buffers.
+//!
+//! Run with `cargo bench --bench frames` and compare the time per frame of the two loops. The
+//! indexed loop may be vectorized, which `zip_frames` does not guarantee.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const FRAMES: usize = 1024;
+const RUN_TIME: Duration = Duration::from_millis(500);
+
+/// Run `f` repeatedly for `RUN_TIME` and print the average time per frame.
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    for _ in 0..1000 {
+        f();
+    }
+    let start = Instant::now();
+    let mut iterations = 0u64;
+    while start.elapsed() < RUN_TIME {
+        for _ in 0..1000 {
+            f();
+        }
+        iterations += 1000;
+    }
+    let per_frame = start.elapsed().as_secs_f64() * 1e9 / (iterations * FRAMES as u64) as f64;
+    println!("{:<28} {:>8.3} ns/frame", name, per_frame);
+}
+
+fn mix_indexed(a: &[f32], b: &[f32], out: &mut [f32]) {
+    for i in 0..out.len() {
+        out[i] = a[i] * 0.5 + b[i] * 0.25;
+    }
+}
+
+fn mix_zipped(a: &[f32], b: &[f32], out: &mut [f32]) {
+    for ([a, b], [o]) in jack::zip_frames([a, b], [out]) {
+        *o = a * 0.5 + b * 0.25;
+    }
+}
+
+fn main() {
+    let a: Vec<f32> = (0..FRAMES).map(|i| (i as f32 * 0.01).sin()).collect();
+    let b: Vec<f32> = (0..FRAMES).map(|i| (i as f32 * 0.02).cos()).collect();
+    let mut out = vec![0.0f32; FRAMES];
+
+    bench("indexed", || {
+        mix_indexed(black_box(&a), black_box(&b), black_box(&mut out[..]));
+    });
+    bench("zip_frames", || {
+        mix_zipped(black_box(&a), black_box(&b), black_box(&mut out[..]));
+    });
+}
@@ -0,0 +1,51 @@
+//! Benchmarks of the `MixBuffer` operations, which run on every frame of every channel.
+//!
+//! Run with `cargo bench --bench mix`. The time per frame should be a fraction of a nanosecond,
+//! far less than one operation per sample, if the loops are vectorized.
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use jack::MixBuffer;
+
+const FRAMES: usize = 1024;
+const RUN_TIME: Duration = Duration::from_millis(500);
+
+/// Run `f` repeatedly for `RUN_TIME` and print the average time per frame.
+fn bench<F: FnMut()>(name: &str, mut f: F) {
+    for _ in 0..1000 {
+        f();
+    }
+    let start = Instant::now();
+    let mut iterations = 0u64;
+    while start.elapsed() < RUN_TIME {
+        for _ in 0..1000 {
+            f();
+        }
+        iterations += 1000;
+    }
+    let per_frame = start.elapsed().as_secs_f64() * 1e9 / (iterations * FRAMES as u64) as f64;
+    println!("{:<28} {:>8.3} ns/frame", name, per_frame);
+}
+
+fn main() {
+    let a: Vec<f32> = (0..FRAMES).map(|i| (i as f32 * 0.01).sin()).collect();
+    let b: Vec<f32> = (0..FRAMES).map(|i| (i as f32 * 0.02).cos()).collect();
+    let mut dst = vec![0.0f32; FRAMES];
+
+    bench("add_from", || {
+        black_box(&mut dst[..]).add_from(black_box(&a));
+    });
+    bench("mix_from", || {
+        black_box(&mut dst[..]).mix_from(black_box(&a), black_box(0.5));
+    });
+    bench("add_scaled_pair", || {
+        black_box(&mut dst[..]).add_scaled_pair(black_box(&a), 0.3, black_box(&b), 0.7);
+    });
+    bench("copy_scaled", || {
+        black_box(&mut dst[..]).copy_scaled(black_box(&a), black_box(0.5));
+    });
+    // A gain of 1 keeps the samples from decaying into denormals, which are slow.
+    bench("scale", || {
+        black_box(&mut dst[..]).scale(black_box(1.0));
+    });
+}
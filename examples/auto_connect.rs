@@ -0,0 +1,54 @@
+//! Connects every new audio output port of other clients to the system playback ports.
+//!
+//! Notifications are received as an asynchronous stream, so this works with any executor.
+use jack::Notification;
+
+fn main() {
+    let (client, _status) = jack::Client::new(
+        "rust_jack_auto_connect",
+        jack::ClientOptions::NO_START_SERVER,
+    )
+    .unwrap();
+    let (notifications, receiver) = jack::Notifications::channel(64);
+    let active_client = client.activate_async(notifications, ()).unwrap();
+    let mut stream = receiver.into_stream();
+
+    futures_executor::block_on(async {
+        while let Some(notification) = next(&mut stream).await {
+            if let Notification::PortRegistered {
+                name: Some(name), ..
+            } = notification
+            {
+                auto_connect(active_client.as_client(), &name);
+            }
+        }
+    });
+}
+
+/// Connect `port_name` to the system playback ports if it is an audio output of another client.
+fn auto_connect(client: &jack::Client, port_name: &str) {
+    let port = match client.port_by_name(port_name) {
+        Some(p) => p,
+        None => return,
+    };
+    let is_own_port = client.is_mine(&port);
+    let is_audio_out = port.flags().contains(jack::PortFlags::IS_OUTPUT)
+        && port.port_type().is_ok_and(|t| t.contains("audio"));
+    if is_own_port || !is_audio_out {
+        return;
+    }
+    let playback = client.ports(Some("system:playback_.*"), None, jack::PortFlags::IS_INPUT);
+    for dst in playback {
+        match client.connect_ports_by_name(port_name, &dst) {
+            Ok(()) => println!("Connected {} to {}", port_name, dst),
+            Err(e) => println!("Failed to connect {} to {}: {:?}", port_name, dst, e),
+        }
+    }
+}
+
+/// Wait for the next item of the stream.
+async fn next(stream: &mut jack::NotificationStream) -> Option<Notification> {
+    use futures_core::Stream;
+    use std::pin::Pin;
+    std::future::poll_fn(|cx| Pin::new(&mut *stream).poll_next(cx)).await
+}
@@ -0,0 +1,82 @@
+//! Records its audio input to `recording.f32`, as raw 32 bit floats in native byte order.
+//!
+//! The process callback must not do disk I/O, so it passes the samples to a disk writing thread
+//! through a ring buffer. The disk writing thread is created by JACK so that it has the same
+//! scheduling setup as the process thread.
+use std::fs::File;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::{mem, thread, time};
+
+/// The number of seconds of audio that the ring buffer can hold.
+const BUFFER_SECONDS: usize = 4;
+
+fn main() {
+    // Create client
+    let (client, _status) = jack::Client::new(
+        "rust_jack_disk_writer",
+        jack::ClientOptions::NO_START_SERVER,
+    )
+    .unwrap();
+    let in_port = client
+        .register_port("in", jack::AudioIn::default())
+        .unwrap();
+
+    // The ring buffer is allocated up front, the process callback must not allocate.
+    let ring_size = BUFFER_SECONDS * client.sample_rate() * mem::size_of::<f32>();
+    let mut ring = jack::RingBuffer::new(ring_size).unwrap();
+    // Keep the ring buffer from being swapped out while the process callback writes to it.
+    if let Err(e) = ring.mlock() {
+        eprintln!(
+            "Could not lock the ring buffer into memory, check `ulimit -l`: {}",
+            e
+        );
+    }
+    let (mut reader, mut writer) = ring.into_reader_writer();
+
+    // Start the disk writing thread.
+    let mut file = File::create("recording.f32").unwrap();
+    let stop = Arc::new(AtomicBool::new(false));
+    let disk_thread = client
+        .spawn_rt_thread(None, true, {
+            let stop = stop.clone();
+            move || {
+                let mut buf = vec![0; ring_size];
+                loop {
+                    // Check before reading so that everything is written after stopping.
+                    let stopping = stop.load(Ordering::Acquire);
+                    let len = reader.read_buffer(&mut buf);
+                    file.write_all(&buf[..len]).unwrap();
+                    if stopping {
+                        return;
+                    }
+                    thread::sleep(time::Duration::from_millis(50));
+                }
+            }
+        })
+        .unwrap();
+
+    // Pass the input to the disk writing thread.
+    let process = jack::ClosureProcessHandler::new(
+        move |_: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
+            for sample in in_port.as_slice(ps) {
+                // Samples are dropped if the disk writing thread can't keep up.
+                if writer.space() >= mem::size_of::<f32>() {
+                    writer.write_buffer(&sample.to_ne_bytes());
+                }
+            }
+            jack::Control::Continue
+        },
+    );
+    let active_client = client.activate_async((), process).unwrap();
+
+    // Wait for user input to quit
+    println!("Recording to recording.f32, press enter/return to stop...");
+    let mut user_input = String::new();
+    io::stdin().read_line(&mut user_input).ok();
+
+    let (_client, _, _) = active_client.deactivate().unwrap();
+    stop.store(true, Ordering::Release);
+    disk_thread.join().unwrap();
+}
@@ -0,0 +1,61 @@
+//! Delays its audio input by a fixed number of frames and reports the added latency to JACK, so
+//! that other clients can compensate for it.
+use std::io;
+
+/// The number of frames the signal is delayed by.
+const DELAY: jack::Frames = 4800;
+
+fn main() {
+    // Create client
+    let (client, _status) =
+        jack::Client::new("rust_jack_latency", jack::ClientOptions::NO_START_SERVER).unwrap();
+
+    // Register ports and allocate the delay line up front, the process callback must not
+    // allocate.
+    let in_port = client
+        .register_port("in", jack::AudioIn::default())
+        .unwrap();
+    let mut out_port = client
+        .register_port("out", jack::AudioOut::default())
+        .unwrap();
+    let mut delay_line = vec![0.0; DELAY as usize];
+    let mut position = 0;
+    let process = jack::ClosureProcessHandler::new(
+        move |_: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
+            let input = in_port.as_slice(ps);
+            let output = out_port.as_mut_slice(ps);
+            for (i, o) in input.iter().zip(output.iter_mut()) {
+                *o = delay_line[position];
+                delay_line[position] = *i;
+                position = (position + 1) % delay_line.len();
+            }
+            jack::Control::Continue
+        },
+    );
+
+    // Activate the client, which starts the processing.
+    let active_client = client
+        .activate_async(Latency { delay: DELAY }, process)
+        .unwrap();
+
+    // Wait for user input to quit
+    println!("Press enter/return to quit...");
+    let mut user_input = String::new();
+    io::stdin().read_line(&mut user_input).ok();
+
+    active_client.deactivate().unwrap();
+}
+
+/// Reports `delay` frames of latency between the input and output ports.
+struct Latency {
+    delay: jack::Frames,
+}
+
+impl jack::NotificationHandler for Latency {
+    fn latency_with_scope(&mut self, scope: &jack::LatencyScope) {
+        let (min, max) = scope.upstream_range();
+        for port in scope.ports_to_update() {
+            scope.set_range(&port, (min + self.delay, max + self.delay));
+        }
+    }
+}
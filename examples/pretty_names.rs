@@ -0,0 +1,59 @@
+//! Registers a surround output with friendly channel names that patchbays such as qjackctl or
+//! RaySession display instead of the port names.
+use std::io;
+
+const CHANNELS: [(&str, &str); 6] = [
+    ("out_1", "Front Left"),
+    ("out_2", "Front Right"),
+    ("out_3", "Center"),
+    ("out_4", "LFE"),
+    ("out_5", "Surround Left"),
+    ("out_6", "Surround Right"),
+];
+
+fn main() {
+    // Create client
+    let (client, _status) = jack::Client::new(
+        "rust_jack_pretty_names",
+        jack::ClientOptions::NO_START_SERVER,
+    )
+    .unwrap();
+    client.set_pretty_name(Some("Rust JACK Surround")).unwrap();
+
+    // Register the ports and name them
+    let mut ports = Vec::with_capacity(CHANNELS.len());
+    for (name, pretty_name) in CHANNELS.iter() {
+        let port = client
+            .register_port(name, jack::AudioOut::default())
+            .unwrap();
+        port.set_pretty_name(Some(pretty_name)).unwrap();
+        ports.push(port);
+    }
+    for port in ports.iter() {
+        println!(
+            "{} is displayed as {:?}",
+            port.name().unwrap(),
+            port.pretty_name().unwrap_or_default()
+        );
+    }
+
+    // Output silence
+    let process = jack::ClosureProcessHandler::new(
+        move |_: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
+            for port in ports.iter_mut() {
+                port.as_mut_slice(ps).iter_mut().for_each(|v| *v = 0.0);
+            }
+            jack::Control::Continue
+        },
+    );
+    let active_client = client.activate_async((), process).unwrap();
+
+    // Wait for user input to quit
+    println!("Press enter/return to quit...");
+    let mut user_input = String::new();
+    io::stdin().read_line(&mut user_input).ok();
+
+    // Remove the names that were set, they would otherwise outlive the client
+    let (client, _, _) = active_client.deactivate().unwrap();
+    client.set_pretty_name(None).unwrap();
+}
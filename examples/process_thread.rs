@@ -0,0 +1,37 @@
+//! Passes its audio input through to its output from a processing loop that is owned by the
+//! client, instead of from a process callback.
+use std::io;
+
+fn main() {
+    // Create client
+    let (client, _status) = jack::Client::new(
+        "rust_jack_process_thread",
+        jack::ClientOptions::NO_START_SERVER,
+    )
+    .unwrap();
+    let in_port = client
+        .register_port("in", jack::AudioIn::default())
+        .unwrap();
+    let mut out_port = client
+        .register_port("out", jack::AudioOut::default())
+        .unwrap();
+
+    // Run the processing loop until the client is deactivated.
+    let active_client = client
+        .activate_thread((), move |mut ctx: jack::ProcessThreadContext| {
+            while let Some(ps) = ctx.cycle_wait() {
+                out_port
+                    .as_mut_slice(&ps)
+                    .copy_from_slice(in_port.as_slice(&ps));
+                ctx.cycle_signal(jack::Control::Continue);
+            }
+        })
+        .unwrap();
+
+    // Wait for user input to quit
+    println!("Press enter/return to quit...");
+    let mut user_input = String::new();
+    io::stdin().read_line(&mut user_input).ok();
+
+    active_client.deactivate().unwrap();
+}
@@ -0,0 +1,52 @@
+//! Takes part in JACK Session by writing its state into the directory that the session manager
+//! provides.
+use std::sync::mpsc;
+use std::{fs, io, thread};
+
+fn main() {
+    // Create client
+    let (mut client, _status) =
+        jack::Client::new("rust_jack_session", jack::ClientOptions::NO_START_SERVER).unwrap();
+
+    // The handler runs on the notification thread and must not block, so the events are saved
+    // on another thread.
+    let (tx, rx) = mpsc::channel::<jack::SessionEvent>();
+    client
+        .register_session_handler(jack::ClosureSessionHandler::new(move |event| {
+            let _ = tx.send(event);
+        }))
+        .unwrap();
+    let saver = thread::spawn(move || {
+        for event in rx {
+            let path = event.session_dir().join("state.txt");
+            let state = format!("client uuid: {}\n", event.client_uuid());
+            let flags = match fs::write(&path, state) {
+                Ok(()) => {
+                    println!("Saved {:?} to {}", event.event_type(), path.display());
+                    jack::SessionFlags::empty()
+                }
+                Err(e) => {
+                    println!("Failed to save to {}: {}", path.display(), e);
+                    jack::SessionFlags::SAVE_ERROR
+                }
+            };
+            // The session manager restores the client with this command.
+            let command_line = format!(
+                "session --state {}state.txt --jack-session-uuid {}",
+                jack::SESSION_DIR_PLACEHOLDER,
+                event.client_uuid()
+            );
+            event.reply(&command_line, flags).unwrap();
+        }
+    });
+
+    let active_client = client.activate_async((), ()).unwrap();
+
+    // Wait for user input to quit
+    println!("Press enter/return to quit...");
+    let mut user_input = String::new();
+    io::stdin().read_line(&mut user_input).ok();
+
+    active_client.deactivate().unwrap();
+    saver.join().unwrap();
+}
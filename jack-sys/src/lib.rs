@@ -1102,3 +1102,203 @@ lazy_static! {
             })
     };
 }
+
+lazy_static! {
+    /// `true` if the JACK library provides the session API, which old versions of JACK lack.
+    pub static ref jack_session_available: bool = {
+        libloading::Library::new(jack_lib)
+            .ok()
+            .map(|lib| unsafe {
+                lib.get::<unsafe extern "C" fn()>(b"jack_set_session_callback\0")
+                    .is_ok()
+                    && lib
+                        .get::<unsafe extern "C" fn()>(b"jack_client_has_session_callback\0")
+                        .is_ok()
+            })
+            .unwrap_or(false)
+    };
+}
+
+lazy_static! {
+    /// `true` if the JACK library provides the metadata API, which old versions of JACK lack.
+    pub static ref jack_metadata_available: bool = {
+        libloading::Library::new(jack_lib)
+            .ok()
+            .map(|lib| unsafe {
+                lib.get::<unsafe extern "C" fn()>(b"jack_set_property\0")
+                    .is_ok()
+            })
+            .unwrap_or(false)
+    };
+}
+
+// NetJACK2 slave and master API from `jack/net.h`. It lives in its own library, libjacknet, which only JACK2
+// provides, so it is loaded at runtime.
+
+pub const DEFAULT_MULTICAST_IP: &'static str = "225.3.19.154";
+pub const DEFAULT_PORT: ::libc::c_int = 19000;
+pub const DEFAULT_MTU: ::libc::c_int = 1500;
+pub const MASTER_NAME_SIZE: usize = 256;
+
+pub type JackNetEncoder = ::libc::c_int;
+pub const JackFloatEncoder: JackNetEncoder = 0;
+pub const JackIntEncoder: JackNetEncoder = 1;
+pub const JackCeltEncoder: JackNetEncoder = 2;
+pub const JackOpusEncoder: JackNetEncoder = 3;
+
+pub enum jack_net_slave_t {}
+pub enum jack_net_master_t {}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct jack_slave_t {
+    pub audio_input: ::libc::c_int,
+    pub audio_output: ::libc::c_int,
+    pub midi_input: ::libc::c_int,
+    pub midi_output: ::libc::c_int,
+    pub mtu: ::libc::c_int,
+    pub time_out: ::libc::c_int,
+    pub encoder: ::libc::c_int,
+    pub kbps: ::libc::c_int,
+    pub latency: ::libc::c_int,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+pub struct jack_master_t {
+    pub audio_input: ::libc::c_int,
+    pub audio_output: ::libc::c_int,
+    pub midi_input: ::libc::c_int,
+    pub midi_output: ::libc::c_int,
+    pub buffer_size: jack_nframes_t,
+    pub sample_rate: jack_nframes_t,
+    pub master_name: [::libc::c_char; MASTER_NAME_SIZE],
+    pub time_out: ::libc::c_int,
+    pub partial_cycle: ::libc::c_int,
+}
+
+pub type JackNetSlaveProcessCallback = ::std::option::Option<
+    unsafe extern "C" fn(
+        buffer_size: jack_nframes_t,
+        audio_input: ::libc::c_int,
+        audio_input_buffer: *mut *mut ::libc::c_float,
+        midi_input: ::libc::c_int,
+        midi_input_buffer: *mut *mut ::libc::c_void,
+        audio_output: ::libc::c_int,
+        audio_output_buffer: *mut *mut ::libc::c_float,
+        midi_output: ::libc::c_int,
+        midi_output_buffer: *mut *mut ::libc::c_void,
+        data: *mut ::libc::c_void,
+    ) -> ::libc::c_int,
+>;
+pub type JackNetSlaveBufferSizeCallback = ::std::option::Option<
+    unsafe extern "C" fn(nframes: jack_nframes_t, arg: *mut ::libc::c_void) -> ::libc::c_int,
+>;
+pub type JackNetSlaveSampleRateCallback = ::std::option::Option<
+    unsafe extern "C" fn(nframes: jack_nframes_t, arg: *mut ::libc::c_void) -> ::libc::c_int,
+>;
+pub type JackNetSlaveShutdownCallback =
+    ::std::option::Option<unsafe extern "C" fn(arg: *mut ::libc::c_void)>;
+
+/// The functions of libjacknet, see `jack_net`.
+pub struct jack_net_t {
+    pub jack_net_slave_open: unsafe extern "C" fn(
+        ip: *const ::libc::c_char,
+        port: ::libc::c_int,
+        name: *const ::libc::c_char,
+        request: *mut jack_slave_t,
+        result: *mut jack_master_t,
+    ) -> *mut jack_net_slave_t,
+    pub jack_net_slave_close: unsafe extern "C" fn(net: *mut jack_net_slave_t) -> ::libc::c_int,
+    pub jack_net_slave_activate: unsafe extern "C" fn(net: *mut jack_net_slave_t) -> ::libc::c_int,
+    pub jack_net_slave_deactivate:
+        unsafe extern "C" fn(net: *mut jack_net_slave_t) -> ::libc::c_int,
+    pub jack_set_net_slave_process_callback: unsafe extern "C" fn(
+        net: *mut jack_net_slave_t,
+        net_callback: JackNetSlaveProcessCallback,
+        arg: *mut ::libc::c_void,
+    ) -> ::libc::c_int,
+    pub jack_set_net_slave_buffer_size_callback: unsafe extern "C" fn(
+        net: *mut jack_net_slave_t,
+        bufsize_callback: JackNetSlaveBufferSizeCallback,
+        arg: *mut ::libc::c_void,
+    ) -> ::libc::c_int,
+    pub jack_set_net_slave_sample_rate_callback: unsafe extern "C" fn(
+        net: *mut jack_net_slave_t,
+        samplerate_callback: JackNetSlaveSampleRateCallback,
+        arg: *mut ::libc::c_void,
+    ) -> ::libc::c_int,
+    pub jack_set_net_slave_shutdown_callback: unsafe extern "C" fn(
+        net: *mut jack_net_slave_t,
+        shutdown_callback: JackNetSlaveShutdownCallback,
+        arg: *mut ::libc::c_void,
+    ) -> ::libc::c_int,
+    pub jack_net_master_open: unsafe extern "C" fn(
+        ip: *const ::libc::c_char,
+        port: ::libc::c_int,
+        request: *mut jack_master_t,
+        result: *mut jack_slave_t,
+    ) -> *mut jack_net_master_t,
+    pub jack_net_master_close: unsafe extern "C" fn(net: *mut jack_net_master_t) -> ::libc::c_int,
+    pub jack_net_master_recv: unsafe extern "C" fn(
+        net: *mut jack_net_master_t,
+        audio_input: ::libc::c_int,
+        audio_input_buffer: *mut *mut ::libc::c_float,
+        midi_input: ::libc::c_int,
+        midi_input_buffer: *mut *mut ::libc::c_void,
+    ) -> ::libc::c_int,
+    pub jack_net_master_send: unsafe extern "C" fn(
+        net: *mut jack_net_master_t,
+        audio_output: ::libc::c_int,
+        audio_output_buffer: *mut *mut ::libc::c_float,
+        midi_output: ::libc::c_int,
+        midi_output_buffer: *mut *mut ::libc::c_void,
+    ) -> ::libc::c_int,
+    // The functions are only valid while the library is loaded.
+    _lib: libloading::Library,
+}
+
+#[cfg(windows)]
+const jack_net_lib: &'static str = "libjacknet.dll";
+
+#[cfg(unix)]
+const jack_net_lib: &'static str = "libjacknet.so.0";
+
+impl jack_net_t {
+    unsafe fn load() -> Option<jack_net_t> {
+        let lib = libloading::Library::new(jack_net_lib).ok()?;
+        Some(jack_net_t {
+            jack_net_slave_open: *lib.get(b"jack_net_slave_open\0").ok()?,
+            jack_net_slave_close: *lib.get(b"jack_net_slave_close\0").ok()?,
+            jack_net_slave_activate: *lib.get(b"jack_net_slave_activate\0").ok()?,
+            jack_net_slave_deactivate: *lib.get(b"jack_net_slave_deactivate\0").ok()?,
+            jack_set_net_slave_process_callback: *lib
+                .get(b"jack_set_net_slave_process_callback\0")
+                .ok()?,
+            jack_set_net_slave_buffer_size_callback: *lib
+                .get(b"jack_set_net_slave_buffer_size_callback\0")
+                .ok()?,
+            jack_set_net_slave_sample_rate_callback: *lib
+                .get(b"jack_set_net_slave_sample_rate_callback\0")
+                .ok()?,
+            jack_set_net_slave_shutdown_callback: *lib
+                .get(b"jack_set_net_slave_shutdown_callback\0")
+                .ok()?,
+            jack_net_master_open: *lib.get(b"jack_net_master_open\0").ok()?,
+            jack_net_master_close: *lib.get(b"jack_net_master_close\0").ok()?,
+            jack_net_master_recv: *lib.get(b"jack_net_master_recv\0").ok()?,
+            jack_net_master_send: *lib.get(b"jack_net_master_send\0").ok()?,
+            _lib: lib,
+        })
+    }
+}
+
+lazy_static! {
+    /// The NetJACK2 API, or `None` if libjacknet is not installed.
+    pub static ref jack_net: Option<jack_net_t> = unsafe { jack_net_t::load() };
+}
+
+// Return codes of `jack_net_master_recv` and `jack_net_master_send`.
+pub const NET_SOCKET_ERROR: ::libc::c_int = -1;
+pub const NET_SYNC_PACKET_ERROR: ::libc::c_int = -2;
+pub const NET_DATA_PACKET_ERROR: ::libc::c_int = -3;
@@ -0,0 +1,238 @@
+use crate::{
+    AsyncClient, AudioIn, AudioOut, Client, ClientOptions, Control, Error, Frames, Port, PortFlags,
+    PortSpec, ProcessHandler, ProcessScope, XrunStats,
+};
+
+/// The callback of an `AudioStream`. It receives the buffers of the input and output ports for the
+/// current cycle.
+type StreamCallback = Box<dyn FnMut(&ProcessScope, &[&[f32]], &mut [&mut [f32]]) -> Control + Send>;
+
+/// A client with a fixed number of audio inputs and outputs, processed by a single callback.
+///
+/// This covers simple tools such as tone generators and recorders without implementing
+/// `ProcessHandler` or managing ports. The client, its ports and its handler are built from the
+/// regular `Client`, `Port` and `ProcessHandler` APIs, which should be used directly for anything
+/// more involved.
+///
+/// The input ports are named `in_1`, `in_2`, ... and the output ports `out_1`, `out_2`, .... The
+/// stream is closed when it is dropped.
+///
+/// # Example
+/// ```
+/// // Pass the input through with half the volume.
+/// let stream = jack::AudioStream::open("attenuator", 1, 1, |_, ins, outs| {
+///     for (o, i) in outs[0].iter_mut().zip(ins[0].iter()) {
+///         *o = 0.5 * i;
+///     }
+///     jack::Control::Continue
+/// })
+/// .unwrap();
+/// stream.connect_to_hardware().unwrap();
+/// ```
+#[must_use = "the stream is closed when the AudioStream is dropped"]
+pub struct AudioStream {
+    client: AsyncClient<(), StreamProcess>,
+    input_names: Vec<String>,
+    output_names: Vec<String>,
+}
+
+impl AudioStream {
+    /// Open a client called `name` with `inputs` audio input ports and `outputs` audio output
+    /// ports and start processing them with `callback`.
+    ///
+    /// `callback` is called in real-time with a slice per input port and a slice per output port,
+    /// all of which hold `ProcessScope::n_frames` samples. It must follow the same rules as
+    /// `ProcessHandler::process`. Calling it does not allocate.
+    pub fn open<F>(name: &str, inputs: usize, outputs: usize, callback: F) -> Result<Self, Error>
+    where
+        F: 'static + Send + FnMut(&ProcessScope, &[&[f32]], &mut [&mut [f32]]) -> Control,
+    {
+        let (client, _status) = Client::new(name, ClientOptions::NO_START_SERVER)?;
+        let inputs = (1..=inputs)
+            .map(|i| client.register_port(&format!("in_{}", i), AudioIn))
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = (1..=outputs)
+            .map(|i| client.register_port(&format!("out_{}", i), AudioOut))
+            .collect::<Result<Vec<_>, _>>()?;
+        let input_names = inputs.iter().map(Port::name).collect::<Result<_, _>>()?;
+        let output_names = outputs.iter().map(Port::name).collect::<Result<_, _>>()?;
+        let process = StreamProcess {
+            ins: vec![&[]; inputs.len()],
+            outs: outputs.iter().map(|_| Default::default()).collect(),
+            inputs,
+            outputs,
+            callback: Box::new(callback),
+        };
+        Ok(AudioStream {
+            client: client.activate_async((), process)?,
+            input_names,
+            output_names,
+        })
+    }
+
+    /// Connect the inputs to the physical capture ports and the outputs to the physical playback
+    /// ports, in order. Ports without a physical counterpart are left unconnected.
+    pub fn connect_to_hardware(&self) -> Result<(), Error> {
+        let client = self.client.as_client();
+        let audio_type = AudioIn.jack_port_type();
+        let capture = client.ports(
+            None,
+            Some(audio_type),
+            PortFlags::IS_PHYSICAL | PortFlags::IS_OUTPUT,
+        );
+        for (src, dst) in capture.iter().zip(self.input_names.iter()) {
+            client.connect_ports_by_name(src, dst)?;
+        }
+        let playback = client.ports(
+            None,
+            Some(audio_type),
+            PortFlags::IS_PHYSICAL | PortFlags::IS_INPUT,
+        );
+        for (src, dst) in self.output_names.iter().zip(playback.iter()) {
+            client.connect_ports_by_name(src, dst)?;
+        }
+        Ok(())
+    }
+
+    /// The underlying client.
+    pub fn as_client(&self) -> &Client {
+        self.client.as_client()
+    }
+
+    /// The full names of the input ports.
+    pub fn input_names(&self) -> &[String] {
+        &self.input_names
+    }
+
+    /// The full names of the output ports.
+    pub fn output_names(&self) -> &[String] {
+        &self.output_names
+    }
+
+    /// The sample rate of the JACK system.
+    pub fn sample_rate(&self) -> usize {
+        self.client.as_client().sample_rate()
+    }
+
+    /// The maximum number of frames passed to the callback, see `Client::buffer_size`.
+    pub fn buffer_size(&self) -> Frames {
+        self.client.as_client().buffer_size()
+    }
+
+    /// Get statistics on the xruns that occurred since the stream was opened.
+    pub fn xrun_stats(&self) -> XrunStats {
+        self.client.xrun_stats()
+    }
+
+    /// Returns `false` once JACK has stopped calling the callback, see
+    /// `AsyncClient::is_processing`.
+    pub fn is_processing(&self) -> bool {
+        self.client.is_processing()
+    }
+
+    /// Stop processing and close the client.
+    pub fn close(self) -> Result<(), Error> {
+        self.client.deactivate().map(|_| ())
+    }
+}
+
+impl std::fmt::Debug for AudioStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("AudioStream")
+            .field("client", self.client.as_client())
+            .field("input_names", &self.input_names)
+            .field("output_names", &self.output_names)
+            .finish()
+    }
+}
+
+/// Hands the port buffers of an `AudioStream` to its callback.
+struct StreamProcess {
+    inputs: Vec<Port<AudioIn>>,
+    outputs: Vec<Port<AudioOut>>,
+    // Preallocated storage for the slices passed to the callback. They only refer to the port
+    // buffers for the duration of a cycle, and are reset to empty slices afterwards.
+    ins: Vec<&'static [f32]>,
+    outs: Vec<&'static mut [f32]>,
+    callback: StreamCallback,
+}
+
+impl ProcessHandler for StreamProcess {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        for (slot, port) in self.ins.iter_mut().zip(self.inputs.iter()) {
+            // The callback can't hold on to the slices since it has to accept any lifetime.
+            *slot = unsafe { std::mem::transmute::<&[f32], &'static [f32]>(port.as_slice(ps)) };
+        }
+        for (slot, port) in self.outs.iter_mut().zip(self.outputs.iter_mut()) {
+            *slot = unsafe {
+                std::mem::transmute::<&mut [f32], &'static mut [f32]>(port.as_mut_slice(ps))
+            };
+        }
+        let res = (self.callback)(ps, &self.ins, &mut self.outs);
+        self.ins.iter_mut().for_each(|slot| *slot = &[]);
+        self.outs.iter_mut().for_each(|slot| *slot = &mut []);
+        res
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::{thread, time};
+
+    #[test]
+    fn audio_stream_passes_port_buffers_to_callback() {
+        let cycles = Arc::new(AtomicUsize::new(0));
+        let mismatches = Arc::new(AtomicUsize::new(0));
+        let stream = {
+            let cycles = cycles.clone();
+            let mismatches = mismatches.clone();
+            AudioStream::open("audio_stream_ppbtc", 2, 3, move |ps, ins, outs| {
+                let n_frames = ps.n_frames() as usize;
+                if ins.len() != 2 || outs.len() != 3 {
+                    mismatches.fetch_add(1, Ordering::Relaxed);
+                }
+                for buffer in ins.iter() {
+                    if buffer.len() != n_frames {
+                        mismatches.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                for buffer in outs.iter_mut() {
+                    if buffer.len() != n_frames {
+                        mismatches.fetch_add(1, Ordering::Relaxed);
+                    }
+                    buffer.fill(0.0);
+                }
+                cycles.fetch_add(1, Ordering::Relaxed);
+                Control::Continue
+            })
+            .unwrap()
+        };
+        thread::sleep(time::Duration::from_millis(500));
+        assert!(cycles.load(Ordering::Relaxed) > 0);
+        assert_eq!(mismatches.load(Ordering::Relaxed), 0);
+        assert!(stream.is_processing());
+        assert_eq!(
+            stream.input_names(),
+            &["audio_stream_ppbtc:in_1", "audio_stream_ppbtc:in_2"]
+        );
+        assert_eq!(stream.output_names().len(), 3);
+        assert_eq!(stream.sample_rate(), stream.as_client().sample_rate());
+        assert_eq!(stream.xrun_stats().count, 0);
+        stream.close().unwrap();
+    }
+
+    #[test]
+    fn audio_stream_connects_to_hardware() {
+        let stream =
+            AudioStream::open("audio_stream_cth", 1, 1, |_, _, _| Control::Continue).unwrap();
+        stream.connect_to_hardware().unwrap();
+        let client = stream.as_client();
+        let input = client.port_by_name("audio_stream_cth:in_1").unwrap();
+        let output = client.port_by_name("audio_stream_cth:out_1").unwrap();
+        assert!(input.is_connected_to("system:capture_1").unwrap());
+        assert!(output.is_connected_to("system:playback_1").unwrap());
+    }
+}
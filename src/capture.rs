@@ -0,0 +1,522 @@
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::{fmt, thread, time};
+
+use crate::{Client, Error, Frames, RingBuffer, RingBufferReader, RingBufferWriter};
+
+/// The size of a recorded sample in bytes.
+const SAMPLE_SIZE: usize = std::mem::size_of::<f32>();
+
+/// The number of frames that are interleaved at a time before they are written to the ring buffer.
+const CHUNK_FRAMES: usize = 256;
+
+/// How long the worker sleeps at most before it checks the ring buffer, in case a wakeup was missed
+/// because the process callback could not take the lock.
+const WAKE_INTERVAL: time::Duration = time::Duration::from_millis(20);
+
+/// The statistics of a recording, see `CaptureWriter::stop`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    /// The number of frames that were written to the output.
+    pub frames_written: u64,
+    /// The number of pushes that were dropped because the ring buffer was full.
+    pub overruns: usize,
+    /// The number of frames in the dropped pushes.
+    pub frames_dropped: u64,
+}
+
+/// Records audio from the process callback to any `std::io::Write`, like `jack_capture`.
+///
+/// The process callback pushes frames into a `CaptureInput`, which copies them into a lock-free
+/// ring buffer. A worker thread drains the ring buffer and writes the frames to the output as
+/// interleaved little-endian `f32` samples, which is the data of a 32 bit float WAV file. Headers
+/// and other encoding are left to the output.
+///
+/// The worker is created with `Client::spawn_rt_thread`, so it has the same scheduling setup as
+/// the client's threads and runs one step below the process thread if the client is real-time.
+///
+/// If the worker falls behind, the pushes that don't fit are dropped whole and counted as
+/// overruns. The recording is stopped, and the remaining frames are written, with `stop`, or when
+/// the `CaptureWriter` is dropped.
+///
+/// # Example
+/// ```no_run
+/// let (client, _) = jack::Client::new("recorder", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let port = client.register_port("in", jack::AudioIn::default()).unwrap();
+/// let file = std::fs::File::create("recording.raw").unwrap();
+/// let (writer, mut input) = jack::CaptureWriter::start(&client, 1, 48000, file).unwrap();
+/// let active = client
+///     .activate_async(
+///         (),
+///         jack::ClosureProcessHandler::new(move |_, ps| {
+///             input.push_planar(&[port.as_slice(ps)]);
+///             jack::Control::Continue
+///         }),
+///     )
+///     .unwrap();
+/// std::thread::sleep(std::time::Duration::from_secs(10));
+/// active.deactivate().unwrap();
+/// let (_file, stats) = writer.stop().unwrap();
+/// println!("recorded {} frames, {} overruns", stats.frames_written, stats.overruns);
+/// ```
+pub struct CaptureWriter<W> {
+    shared: Arc<CaptureShared>,
+    result: Arc<Mutex<Option<io::Result<W>>>>,
+    thread: Option<WorkerThread>,
+}
+
+impl<W: 'static + Send + Write> CaptureWriter<W> {
+    /// Start recording `channels` channels into `output`. The ring buffer holds at least
+    /// `capacity` frames, a second or a few of audio is typical.
+    ///
+    /// The worker thread is created by JACK for `client`, except on Windows where a regular thread
+    /// is used.
+    ///
+    /// # Panics
+    /// Panics if `channels` is `0`.
+    pub fn start(
+        client: &Client,
+        channels: usize,
+        capacity: Frames,
+        output: W,
+    ) -> Result<(CaptureWriter<W>, CaptureInput), Error> {
+        #[cfg(not(target_os = "windows"))]
+        {
+            CaptureWriter::start_with(channels, capacity, output, |f| {
+                let realtime = client.is_realtime();
+                client
+                    .spawn_rt_thread(None, realtime, f)
+                    .map(WorkerThread::Jack)
+            })
+        }
+        #[cfg(target_os = "windows")]
+        {
+            let _ = client;
+            CaptureWriter::start_with(channels, capacity, output, spawn_std)
+        }
+    }
+
+    fn start_with<S>(
+        channels: usize,
+        capacity: Frames,
+        output: W,
+        spawn: S,
+    ) -> Result<(CaptureWriter<W>, CaptureInput), Error>
+    where
+        S: FnOnce(Box<dyn FnOnce() + Send>) -> Result<WorkerThread, Error>,
+    {
+        assert!(channels > 0, "a recording needs at least one channel");
+        let frame_size = channels * SAMPLE_SIZE;
+        // The ring buffer keeps a byte free to tell a full buffer from an empty one.
+        let size = (capacity as usize)
+            .checked_mul(frame_size)
+            .and_then(|size| size.checked_add(1))
+            .ok_or(Error::RingbufferCreateFailed)?;
+        let mut ring = RingBuffer::new(size)?;
+        // Recording still works if the memory can't be locked, it may just be slower.
+        let _ = ring.mlock();
+        let (reader, writer) = ring.into_reader_writer();
+        let shared = Arc::new(CaptureShared::default());
+        let result = Arc::new(Mutex::new(None));
+        let thread = spawn(Box::new({
+            let shared = shared.clone();
+            let result = result.clone();
+            move || {
+                let res = drain(reader, output, frame_size, &shared);
+                *result.lock().unwrap() = Some(res);
+            }
+        }))?;
+        let input = CaptureInput {
+            writer,
+            channels,
+            scratch: vec![0; CHUNK_FRAMES * frame_size].into_boxed_slice(),
+            shared: shared.clone(),
+        };
+        let writer = CaptureWriter {
+            shared,
+            result,
+            thread: Some(thread),
+        };
+        Ok((writer, input))
+    }
+
+    /// The statistics of the recording so far.
+    pub fn stats(&self) -> CaptureStats {
+        self.shared.stats()
+    }
+
+    /// Stop the worker after it has written the frames that were pushed so far, flush the output
+    /// and return it with the statistics of the recording.
+    ///
+    /// Frames that are pushed after this are dropped. `Err(Error::CaptureIoError(message))` is
+    /// returned if writing to the output failed, and `Err(Error::ThreadJoinError)` if the worker
+    /// could not be joined.
+    pub fn stop(mut self) -> Result<(W, CaptureStats), Error> {
+        self.stop_worker()?;
+        let res = self.result.lock().unwrap().take();
+        match res {
+            Some(Ok(output)) => Ok((output, self.stats())),
+            Some(Err(err)) => Err(Error::CaptureIoError(err.to_string())),
+            None => Err(Error::ThreadJoinError),
+        }
+    }
+}
+
+impl<W> CaptureWriter<W> {
+    fn stop_worker(&mut self) -> Result<(), Error> {
+        let thread = match self.thread.take() {
+            Some(thread) => thread,
+            None => return Ok(()),
+        };
+        self.shared.stop.store(true, Ordering::Release);
+        self.shared.wake(true);
+        thread.join()
+    }
+}
+
+/// Stops the worker after it has written the remaining frames.
+impl<W> Drop for CaptureWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.stop_worker();
+    }
+}
+
+impl<W> fmt::Debug for CaptureWriter<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("CaptureWriter")
+            .field("stats", &self.shared.stats())
+            .finish()
+    }
+}
+
+/// The real-time side of a `CaptureWriter`, which the process callback pushes frames into.
+///
+/// Pushing does not allocate or block, so it is suitable for the process callback. A push is
+/// either recorded whole or dropped whole, and dropped pushes are counted as overruns.
+pub struct CaptureInput {
+    writer: RingBufferWriter,
+    channels: usize,
+    // Frames are interleaved into this buffer before they are written to the ring buffer.
+    scratch: Box<[u8]>,
+    shared: Arc<CaptureShared>,
+}
+
+impl CaptureInput {
+    /// The number of channels that are recorded.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Push interleaved samples, `channels()` samples per frame. A trailing partial frame is
+    /// ignored.
+    ///
+    /// Returns `false` if the frames were dropped because the ring buffer was full.
+    pub fn push_interleaved(&mut self, samples: &[f32]) -> bool {
+        let channels = self.channels;
+        self.push_with(samples.len() / channels, |frame, channel| {
+            samples[frame * channels + channel]
+        })
+    }
+
+    /// Push a slice of samples per channel. The number of frames is the length of the shortest
+    /// slice, extra slices are ignored and missing channels are recorded as silence.
+    ///
+    /// Returns `false` if the frames were dropped because the ring buffer was full.
+    pub fn push_planar(&mut self, channels: &[&[f32]]) -> bool {
+        let n_frames = channels
+            .iter()
+            .take(self.channels)
+            .map(|c| c.len())
+            .min()
+            .unwrap_or(0);
+        self.push_with(n_frames, |frame, channel| {
+            channels.get(channel).map_or(0.0, |c| c[frame])
+        })
+    }
+
+    fn push_with<F: Fn(usize, usize) -> f32>(&mut self, n_frames: usize, sample: F) -> bool {
+        if n_frames == 0 {
+            return true;
+        }
+        let frame_size = self.channels * SAMPLE_SIZE;
+        if self.shared.stop.load(Ordering::Acquire) || self.writer.space() < n_frames * frame_size {
+            self.shared.overruns.fetch_add(1, Ordering::Relaxed);
+            self.shared
+                .frames_dropped
+                .fetch_add(n_frames as u64, Ordering::Relaxed);
+            return false;
+        }
+        let mut start = 0;
+        while start < n_frames {
+            let len = CHUNK_FRAMES.min(n_frames - start);
+            for frame in 0..len {
+                for channel in 0..self.channels {
+                    let offset = (frame * self.channels + channel) * SAMPLE_SIZE;
+                    self.scratch[offset..offset + SAMPLE_SIZE]
+                        .copy_from_slice(&sample(start + frame, channel).to_ne_bytes());
+                }
+            }
+            self.writer.write_buffer(&self.scratch[..len * frame_size]);
+            start += len;
+        }
+        self.shared.wake(false);
+        true
+    }
+}
+
+impl fmt::Debug for CaptureInput {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("CaptureInput")
+            .field("channels", &self.channels)
+            .finish()
+    }
+}
+
+/// The state that is shared by the process callback, the worker and the `CaptureWriter`.
+#[derive(Default)]
+struct CaptureShared {
+    frames_written: AtomicU64,
+    overruns: AtomicUsize,
+    frames_dropped: AtomicU64,
+    stop: AtomicBool,
+    // Set when there is something for the worker to do.
+    pending: Mutex<bool>,
+    wakeup: Condvar,
+}
+
+impl CaptureShared {
+    fn stats(&self) -> CaptureStats {
+        CaptureStats {
+            frames_written: self.frames_written.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+            frames_dropped: self.frames_dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Wake the worker. Unless `block` is set, nothing is done if the lock is taken, the worker
+    /// then finds the frames within `WAKE_INTERVAL`.
+    fn wake(&self, block: bool) {
+        let pending = if block {
+            Some(self.pending.lock().unwrap_or_else(|e| e.into_inner()))
+        } else {
+            self.pending.try_lock().ok()
+        };
+        if let Some(mut pending) = pending {
+            *pending = true;
+            self.wakeup.notify_one();
+        }
+    }
+
+    /// Wait until there is something to do, or `WAKE_INTERVAL` has passed.
+    fn wait(&self) {
+        let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+        if !*pending {
+            pending = self
+                .wakeup
+                .wait_timeout(pending, WAKE_INTERVAL)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        *pending = false;
+    }
+}
+
+/// The worker: writes the frames from `reader` to `output` until it is stopped.
+fn drain<W: Write>(
+    mut reader: RingBufferReader,
+    mut output: W,
+    frame_size: usize,
+    shared: &CaptureShared,
+) -> io::Result<W> {
+    let mut buf = vec![0; CHUNK_FRAMES * frame_size];
+    loop {
+        // Check before draining so that nothing is left behind after stopping.
+        let stopping = shared.stop.load(Ordering::Acquire);
+        loop {
+            let len = (reader.space() / frame_size * frame_size).min(buf.len());
+            if len == 0 {
+                break;
+            }
+            reader.read_buffer(&mut buf[..len]);
+            for sample in buf[..len].chunks_exact_mut(SAMPLE_SIZE) {
+                let mut bytes = [0; SAMPLE_SIZE];
+                bytes.copy_from_slice(sample);
+                sample.copy_from_slice(&f32::from_ne_bytes(bytes).to_le_bytes());
+            }
+            output.write_all(&buf[..len])?;
+            shared
+                .frames_written
+                .fetch_add((len / frame_size) as u64, Ordering::Relaxed);
+        }
+        if stopping {
+            output.flush()?;
+            return Ok(output);
+        }
+        shared.wait();
+    }
+}
+
+/// The thread that runs the worker.
+enum WorkerThread {
+    #[cfg(not(target_os = "windows"))]
+    Jack(crate::RtThreadHandle),
+    #[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+    Std(thread::JoinHandle<()>),
+}
+
+impl WorkerThread {
+    fn join(self) -> Result<(), Error> {
+        match self {
+            #[cfg(not(target_os = "windows"))]
+            WorkerThread::Jack(handle) => handle.join(),
+            WorkerThread::Std(handle) => handle.join().map_err(|_| Error::ThreadJoinError),
+        }
+    }
+}
+
+#[cfg_attr(not(any(test, target_os = "windows")), allow(dead_code))]
+fn spawn_std(f: Box<dyn FnOnce() + Send>) -> Result<WorkerThread, Error> {
+    thread::Builder::new()
+        .name("jack-capture".to_string())
+        .spawn(f)
+        .map(WorkerThread::Std)
+        .map_err(|_| Error::ThreadCreationError)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AudioIn, AudioOut, ClientOptions, ClosureProcessHandler, Control};
+
+    fn samples(bytes: &[u8]) -> Vec<f32> {
+        bytes
+            .chunks_exact(SAMPLE_SIZE)
+            .map(|s| f32::from_le_bytes([s[0], s[1], s[2], s[3]]))
+            .collect()
+    }
+
+    #[test]
+    fn capture_writer_interleaves_pushed_frames() {
+        let (writer, mut input) =
+            CaptureWriter::start_with(2, 1024, Vec::new(), spawn_std).unwrap();
+        assert_eq!(input.channels(), 2);
+        assert!(input.push_interleaved(&[1.0, -1.0, 2.0, -2.0, 3.0]));
+        assert!(input.push_planar(&[&[4.0, 5.0], &[-4.0, -5.0, -6.0]]));
+        assert!(input.push_planar(&[&[6.0]]));
+        let (output, stats) = writer.stop().unwrap();
+        assert_eq!(
+            samples(&output),
+            [1.0, -1.0, 2.0, -2.0, 4.0, -4.0, 5.0, -5.0, 6.0, 0.0]
+        );
+        assert_eq!(
+            stats,
+            CaptureStats {
+                frames_written: 5,
+                overruns: 0,
+                frames_dropped: 0
+            }
+        );
+        // The recording is over.
+        assert!(!input.push_interleaved(&[7.0, -7.0]));
+    }
+
+    #[test]
+    fn capture_writer_counts_overruns() {
+        let (writer, mut input) = CaptureWriter::start_with(1, 16, Vec::new(), spawn_std).unwrap();
+        let block: Vec<f32> = (0..1000).map(|s| s as f32).collect();
+        assert!(!input.push_interleaved(&block));
+        assert!(input.push_interleaved(&block[..8]));
+        let (output, stats) = writer.stop().unwrap();
+        assert_eq!(samples(&output), &block[..8]);
+        assert_eq!(
+            stats,
+            CaptureStats {
+                frames_written: 8,
+                overruns: 1,
+                frames_dropped: 1000
+            }
+        );
+    }
+
+    #[test]
+    fn capture_writer_keeps_up_with_a_busy_producer() {
+        let (writer, mut input) =
+            CaptureWriter::start_with(2, 4096, Vec::new(), spawn_std).unwrap();
+        let mut next = 0u32;
+        while next < 200_000 {
+            let block: Vec<f32> = (next..next + 64)
+                .flat_map(|s| vec![s as f32, -(s as f32)])
+                .collect();
+            if input.push_interleaved(&block) {
+                next += 64;
+            } else {
+                thread::yield_now();
+            }
+        }
+        let (output, stats) = writer.stop().unwrap();
+        assert_eq!(stats.frames_written, 200_000);
+        let expected: Vec<f32> = (0..200_000u32)
+            .flat_map(|s| vec![s as f32, -(s as f32)])
+            .collect();
+        assert_eq!(samples(&output), expected);
+    }
+
+    #[test]
+    fn capture_writer_reports_write_errors() {
+        struct Broken;
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        let (writer, mut input) = CaptureWriter::start_with(1, 16, Broken, spawn_std).unwrap();
+        assert!(input.push_interleaved(&[1.0]));
+        assert_eq!(
+            writer.stop().map(|(_, stats)| stats),
+            Err(Error::CaptureIoError("disk full".to_string()))
+        );
+    }
+
+    #[test]
+    fn capture_writer_records_loopback_signal() {
+        let (client, _) =
+            Client::new("capture_writer_rls", ClientOptions::NO_START_SERVER).unwrap();
+        let input_port = client.register_port("in", AudioIn::default()).unwrap();
+        let mut output_port = client.register_port("out", AudioOut::default()).unwrap();
+        let (writer, mut input) =
+            CaptureWriter::start(&client, 1, client.sample_rate() as Frames, Vec::new()).unwrap();
+        let mut t = 0u32;
+        let handler = ClosureProcessHandler::new(move |_, ps| {
+            for s in output_port.as_mut_slice(ps) {
+                t += 1;
+                *s = t as f32;
+            }
+            input.push_planar(&[input_port.as_slice(ps)]);
+            Control::Continue
+        });
+        let active = client.activate_async((), handler).unwrap();
+        active
+            .as_client()
+            .connect_ports_by_name("capture_writer_rls:out", "capture_writer_rls:in")
+            .unwrap();
+        thread::sleep(time::Duration::from_millis(500));
+        active.deactivate().unwrap();
+
+        let (output, stats) = writer.stop().unwrap();
+        assert_eq!(stats.overruns, 0);
+        assert_eq!(stats.frames_written as usize * SAMPLE_SIZE, output.len());
+        // Silence until the connection is made, then the signal without gaps.
+        let recorded: Vec<f32> = samples(&output)
+            .into_iter()
+            .skip_while(|&s| s == 0.0)
+            .collect();
+        assert!(recorded.len() > 1000, "{} frames", recorded.len());
+        for pair in recorded.windows(2) {
+            assert_eq!(pair[1], pair[0] + 1.0);
+        }
+    }
+}
@@ -0,0 +1,206 @@
+use jack_sys as j;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+
+use crate::{Error, RingBuffer, RingBufferReader, RingBufferWriter};
+
+/// Create a lock-free channel of `Copy` values that can hold at least `capacity` values, for
+/// example to send parameter changes to the process callback or meter values from it.
+///
+/// The channel is built on `RingBuffer`, values are copied in and out as a whole so a partially
+/// sent value is never received. `Sender::try_send` and `Receiver::try_recv` are wait-free and
+/// don't allocate, so they may be used from the process callback.
+///
+/// Zero sized types are rejected at compile time.
+///
+/// # Example
+/// ```
+/// let (mut tx, mut rx) = jack::channel::<f32>(16).unwrap();
+/// std::thread::spawn(move || tx.try_send(0.5).unwrap())
+///     .join()
+///     .unwrap();
+/// assert_eq!(rx.try_recv(), Some(0.5));
+/// assert_eq!(rx.try_recv(), None);
+/// ```
+///
+/// ```compile_fail
+/// let (tx, rx) = jack::channel::<()>(16).unwrap();
+/// ```
+pub fn channel<T: Copy>(capacity: usize) -> Result<(Sender<T>, Receiver<T>), Error> {
+    #[allow(clippy::let_unit_value)]
+    let () = Slot::<T>::CHECK_SIZE;
+    // The ring buffer keeps a byte free to tell a full buffer from an empty one.
+    let size = capacity
+        .checked_mul(Slot::<T>::SIZE)
+        .and_then(|size| size.checked_add(1))
+        .ok_or(Error::RingbufferCreateFailed)?;
+    let mut ring = RingBuffer::new(size)?;
+    // The channel still works if the memory can't be locked, it may just be slower.
+    let _ = ring.mlock();
+    let (reader, writer) = ring.into_reader_writer();
+    let sender = Sender {
+        writer,
+        _item: PhantomData,
+    };
+    let receiver = Receiver {
+        reader,
+        _item: PhantomData,
+    };
+    Ok((sender, receiver))
+}
+
+/// The size of the values of a channel.
+struct Slot<T>(PhantomData<T>);
+
+impl<T> Slot<T> {
+    const SIZE: usize = mem::size_of::<T>();
+    const CHECK_SIZE: () = assert!(
+        Self::SIZE > 0,
+        "zero sized types can't be sent over a channel"
+    );
+}
+
+/// The sending half of a channel, see `channel`.
+pub struct Sender<T> {
+    writer: RingBufferWriter,
+    // Sending values across threads requires `T: Send`.
+    _item: PhantomData<T>,
+}
+
+impl<T: Copy> Sender<T> {
+    /// Send `item`, or return it in `Err` if the channel is full. Does not block or allocate.
+    pub fn try_send(&mut self, item: T) -> Result<(), T> {
+        if self.writer.space() < Slot::<T>::SIZE {
+            return Err(item);
+        }
+        // The value is copied by the C library, including any padding bytes which may not be read
+        // as `u8` from Rust.
+        let src = &item as *const T as *const libc::c_char;
+        unsafe {
+            j::jack_ringbuffer_write(self.writer.ringbuffer_handle(), src, Slot::<T>::SIZE as _)
+        };
+        Ok(())
+    }
+}
+
+impl<T> std::fmt::Debug for Sender<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Sender").finish()
+    }
+}
+
+/// The receiving half of a channel, see `channel`.
+pub struct Receiver<T> {
+    reader: RingBufferReader,
+    _item: PhantomData<T>,
+}
+
+impl<T: Copy> Receiver<T> {
+    /// Receive the oldest value that was sent, or `None` if the channel is empty. Does not block or
+    /// allocate.
+    pub fn try_recv(&mut self) -> Option<T> {
+        // Only whole values are written, so the data that is available always starts at a value.
+        if self.reader.space() < Slot::<T>::SIZE {
+            return None;
+        }
+        let mut item = MaybeUninit::<T>::uninit();
+        let dst = item.as_mut_ptr() as *mut libc::c_char;
+        unsafe {
+            j::jack_ringbuffer_read(self.reader.ringbuffer_handle(), dst, Slot::<T>::SIZE as _);
+            // The bytes were copied from a valid `T` by `try_send`.
+            Some(item.assume_init())
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Receiver<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Receiver").finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn channel_sends_in_order() {
+        let (mut tx, mut rx) = channel::<u32>(4).unwrap();
+        assert_eq!(rx.try_recv(), None);
+        for i in 0..3 {
+            tx.try_send(i).unwrap();
+        }
+        assert_eq!(rx.try_recv(), Some(0));
+        assert_eq!(rx.try_recv(), Some(1));
+        assert_eq!(rx.try_recv(), Some(2));
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[test]
+    fn channel_returns_item_when_full() {
+        let (mut tx, mut rx) = channel::<[u8; 3]>(2).unwrap();
+        let mut sent = 0;
+        while tx.try_send([sent; 3]).is_ok() {
+            sent += 1;
+        }
+        assert!(sent >= 2);
+        assert_eq!(tx.try_send([7; 3]), Err([7; 3]));
+        assert_eq!(rx.try_recv(), Some([0; 3]));
+        tx.try_send([7; 3]).unwrap();
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    struct Pair {
+        value: u64,
+        check: u8,
+        inverse: u64,
+    }
+
+    #[test]
+    fn channel_does_not_tear_values_across_threads() {
+        const LEN: u64 = 100_000;
+        let (mut tx, mut rx) = channel::<Pair>(7).unwrap();
+        let sender = thread::spawn(move || {
+            for value in 0..LEN {
+                let mut item = Pair {
+                    value,
+                    check: value as u8,
+                    inverse: !value,
+                };
+                while let Err(i) = tx.try_send(item) {
+                    item = i;
+                    thread::yield_now();
+                }
+            }
+        });
+        let mut expected = 0;
+        while expected < LEN {
+            match rx.try_recv() {
+                Some(item) => {
+                    assert_eq!(
+                        item,
+                        Pair {
+                            value: expected,
+                            check: expected as u8,
+                            inverse: !expected,
+                        }
+                    );
+                    expected += 1;
+                }
+                None => thread::yield_now(),
+            }
+        }
+        sender.join().unwrap();
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[test]
+    fn channel_does_not_allocate() {
+        let (mut tx, mut rx) = channel::<f64>(4).unwrap();
+        let before = crate::test_alloc::allocations();
+        tx.try_send(1.0).unwrap();
+        assert_eq!(rx.try_recv(), Some(1.0));
+        assert_eq!(crate::test_alloc::allocations(), before);
+    }
+}
@@ -1,12 +1,28 @@
 use jack_sys as j;
 use std::fmt;
-use std::mem;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, MutexGuard};
+use std::{mem, thread, time};
 
-use super::callbacks::clear_callbacks;
+use super::callback_selection::CallbackSelection;
 use super::callbacks::{CallbackContext, NotificationHandler, ProcessHandler};
+use super::name_registry::NameRegistry;
+use super::pending_connection::PendingConnection;
+use super::port_watch::PortEventReceiver;
+use super::process_thread::{ProcessThread, ProcessThreadFn};
+use super::shutdown::ShutdownReceiver;
+use super::timing::TimingStats;
+use super::watchdog::CycleCounter;
+use super::xrun::XrunStats;
 use crate::client::client_impl::Client;
 use crate::client::common::{sleep_on_test, CREATE_OR_DESTROY_CLIENT_MUTEX};
-use crate::Error;
+use crate::jack_utils::to_cstring;
+use crate::logging::log_event;
+use crate::{Error, Frames, Port, PortConnections, PortSpec};
+
+/// How often `AsyncClient::wait_for_quit` checks whether processing has stopped, and
+/// `AsyncClient::set_buffer_size_sync` whether the buffer size callback was called.
+const QUIT_POLL_INTERVAL: time::Duration = time::Duration::from_millis(10);
 
 /// A JACK client that is processing data asynchronously, in real-time.
 ///
@@ -39,32 +55,91 @@ where
     /// Tell the JACK server that the program is ready to start processing audio. JACK will call the
     /// methods specified by the `NotificationHandler` and `ProcessHandler` objects.
     ///
-    /// On failure, either `Err(Error::CallbackRegistrationError)` or
+    /// On failure, either `Err(Error::CallbackRegistrationError(name))` or
     /// `Err(Error::ClientActivationError)` is returned.
     ///
     /// `notification_handler` and `process_handler` are consumed, but they are returned when
     /// `Client::deactivate` is called.
     pub fn new(client: Client, notification_handler: N, process_handler: P) -> Result<Self, Error> {
+        // The handlers are 'static, so they may be called for as long as the client is active.
+        unsafe { AsyncClient::new_unchecked(client, notification_handler, process_handler) }
+    }
+}
+
+impl<N, P> AsyncClient<N, P>
+where
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
+{
+    /// Activate `client` with handlers that may borrow data.
+    ///
+    /// # Safety
+    /// The client must be deactivated before any data borrowed by the handlers goes out of scope.
+    /// Dropping the returned `AsyncClient` is not enough since it may be leaked with
+    /// `mem::forget`.
+    pub(crate) unsafe fn new_unchecked(
+        client: Client,
+        notification_handler: N,
+        process_handler: P,
+    ) -> Result<Self, Error> {
+        let callback_context = Box::new(CallbackContext::new(
+            client,
+            notification_handler,
+            process_handler,
+        ));
+        AsyncClient::activate_context(callback_context)
+    }
+
+    /// Register the callbacks of `callback_context` and activate its client.
+    ///
+    /// # Safety
+    /// See `new_unchecked`.
+    unsafe fn activate_context(
+        mut callback_context: Box<CallbackContext<N, P>>,
+    ) -> Result<Self, Error> {
         let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
         unsafe {
             sleep_on_test();
-            let mut callback_context = Box::new(CallbackContext {
-                client,
-                notification: notification_handler,
-                process: process_handler,
-            });
-            CallbackContext::register_callbacks(&mut callback_context)?;
+            if let Err(e) = CallbackContext::register_callbacks(&mut callback_context) {
+                log_event!(
+                    error,
+                    "failed to register the callbacks of client {}: {:?}",
+                    callback_context.client.name_lossy(),
+                    e
+                );
+                return Err(e);
+            }
             sleep_on_test();
+            // JACK does not call into the client before it is activated.
+            callback_context.client.set_active(true);
             let res = j::jack_activate(callback_context.client.raw());
             for _ in 0..4 {
                 sleep_on_test();
             }
             match res {
-                0 => Ok(AsyncClient {
-                    callback: Some(callback_context),
-                }),
+                0 => {
+                    log_event!(
+                        debug,
+                        "activated client {}",
+                        callback_context.client.name_lossy()
+                    );
+                    Ok(AsyncClient {
+                        callback: Some(callback_context),
+                    })
+                }
                 _ => {
-                    mem::forget(callback_context);
+                    log_event!(
+                        error,
+                        "failed to activate client {}",
+                        callback_context.client.name_lossy()
+                    );
+                    callback_context.client.set_active(false);
+                    // The client never became active so JACK will not call into the context. If
+                    // the callbacks can't be cleared, leak the context rather than risk a dangling
+                    // pointer.
+                    if callback_context.clear_callbacks().is_err() {
+                        mem::forget(callback_context);
+                    }
                     Err(Error::ClientActivationError)
                 }
             }
@@ -72,28 +147,361 @@ where
     }
 }
 
+impl<N> AsyncClient<N, ()>
+where
+    N: 'static + Send + Sync + NotificationHandler,
+{
+    /// Activate `client` with a process thread that runs `f` instead of a process handler, see
+    /// `Client::activate_thread`.
+    pub(crate) fn with_process_thread(
+        client: Client,
+        notification_handler: N,
+        f: ProcessThreadFn,
+    ) -> Result<Self, Error> {
+        let mut callback_context = Box::new(CallbackContext::new(client, notification_handler, ()));
+        callback_context.process_thread = Some(ProcessThread::new(f));
+        // The handlers are 'static, so they may be called for as long as the client is active.
+        unsafe { AsyncClient::activate_context(callback_context) }
+    }
+}
+
 impl<N, P> AsyncClient<N, P> {
     /// Return the underlying `jack::Client`.
+    ///
+    /// JACK allows ports to be registered and connected while a client is active, so
+    /// `Client::register_port`, `Client::connect_ports_by_name` and friends may be used on the
+    /// returned client. Ports registered this way can be handed to the process handler through a
+    /// lock-free channel.
     #[inline(always)]
     pub fn as_client(&self) -> &Client {
         let callback = self.callback.as_ref().unwrap();
         &callback.client
     }
 
+    /// Get the notification handler that was passed on activation.
+    ///
+    /// Notification callbacks run on a JACK thread and need exclusive access to the handler, so
+    /// they are blocked until the returned guard is dropped. Hold the guard only briefly. The
+    /// shutdown and xrun callbacks, which may run on the process thread, are skipped instead of
+    /// blocked.
+    ///
+    /// There is no such accessor for the process handler, not even a shared one for a handler that
+    /// is `Sync`. The process callback holds `&mut P` for the whole cycle and must never wait on a
+    /// lock. Through `&mut P`, safe code may write the handler's state without synchronization, for
+    /// example with `AtomicU32::get_mut`, so a `&P` on another thread would race with it. State that
+    /// has to be observed from other threads while processing, such as meters or parameters,
+    /// should be shared with the process handler through atomics or lock-free queues like
+    /// `RingBuffer`. Alternatively, implement `SharedProcessHandler`, activate with an `Arc` of the
+    /// handler and keep a clone of the `Arc`.
+    pub fn notification_handler(&self) -> MutexGuard<'_, N> {
+        self.callback.as_ref().unwrap().notification_handler()
+    }
+
+    /// Returns `true` if the notification or process handler panicked within a JACK callback.
+    ///
+    /// Panics are caught before they reach JACK. Once a handler has panicked, neither handler is
+    /// called again and the process callback reports failure, which makes JACK remove the client
+    /// from the process graph. The client should then be deactivated.
+    pub fn is_poisoned(&self) -> bool {
+        self.callback.as_ref().unwrap().is_poisoned()
+    }
+
+    /// Returns `false` once JACK has stopped calling the process handler.
+    ///
+    /// This happens when the process handler returns `Control::Quit` or panics, or when JACK shuts
+    /// the client down. The client remains open but silent, and should be deactivated.
+    pub fn is_processing(&self) -> bool {
+        !self.callback.as_ref().unwrap().has_stopped()
+    }
+
+    /// Block until JACK has stopped calling the process handler, see `is_processing`, or until
+    /// `timeout` has passed. Returns `true` if processing stopped and `false` on timeout.
+    ///
+    /// This allows running a client until its process handler decides to stop:
+    /// ```no_run
+    /// # let (client, _status) =
+    /// #     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let mut cycles_left = 1000;
+    /// let process = jack::ClosureProcessHandler::new(
+    ///     move |_: &jack::Client, _: &jack::ProcessScope| {
+    ///         cycles_left -= 1;
+    ///         match cycles_left {
+    ///             0 => jack::Control::Quit,
+    ///             _ => jack::Control::Continue,
+    ///         }
+    ///     },
+    /// );
+    /// let active_client = client.activate_async((), process).unwrap();
+    /// while !active_client.wait_for_quit(std::time::Duration::from_secs(1)) {}
+    /// active_client.deactivate().unwrap();
+    /// ```
+    pub fn wait_for_quit(&self, timeout: time::Duration) -> bool {
+        let callback = self.callback.as_ref().unwrap();
+        poll_until(timeout, || callback.has_stopped())
+    }
+
+    /// Block until the first process cycle after activation has ended, or fail with
+    /// `Error::FirstCycleTimeout` once `timeout` has passed.
+    ///
+    /// Between activation and the first cycle, the client is not part of the processing graph
+    /// yet, so for example its ports carry no data and the timing of the cycle can't be queried.
+    /// Waiting for the first cycle takes the guesswork out of sleeping for some time after
+    /// activation. The process callback only marks the end of the first cycle and wakes up the
+    /// waiting threads, it never blocks.
+    pub fn wait_for_first_cycle(&self, timeout: time::Duration) -> Result<(), Error> {
+        match self
+            .callback
+            .as_ref()
+            .unwrap()
+            .cycles
+            .wait_for_first(timeout)
+        {
+            true => Ok(()),
+            false => Err(Error::FirstCycleTimeout),
+        }
+    }
+
+    /// The number of process cycles that have ended since the client was activated.
+    pub fn cycles_processed(&self) -> u64 {
+        self.callback.as_ref().unwrap().cycles.processed()
+    }
+
+    /// Get a receiver for the event of JACK shutting down this client.
+    ///
+    /// The status and reason passed to the shutdown callback are captured without allocating, so
+    /// this works regardless of the `NotificationHandler` that is used.
+    pub fn shutdown_receiver(&self) -> ShutdownReceiver {
+        ShutdownReceiver::new(self.callback.as_ref().unwrap().shutdown.clone())
+    }
+
+    /// The callbacks selected by the `NotificationHandler` that could not be registered with JACK
+    /// and will therefore never be called.
+    ///
+    /// Only optional callbacks are reported here, for example `CallbackSelection::LATENCY` is
+    /// unavailable on servers that don't support latency callbacks. Activation fails if any other
+    /// callback can't be registered.
+    pub fn unavailable_callbacks(&self) -> CallbackSelection {
+        self.callback.as_ref().unwrap().unavailable_callbacks
+    }
+
+    /// Get statistics on the xruns that occurred since the client was activated, or since the last
+    /// call to `reset_xrun_count`.
+    ///
+    /// The statistics are collected regardless of the `NotificationHandler` that is used. Reading
+    /// them does not block the JACK threads, so they may be polled at any rate.
+    pub fn xrun_stats(&self) -> XrunStats {
+        self.callback.as_ref().unwrap().xruns.stats()
+    }
+
+    /// Receive the connection changes of `port` as `PortEvent`s, with the full name of the port on
+    /// the other end.
+    ///
+    /// Unlike `NotificationHandler::ports_connected`, which is called for every connection change
+    /// in the graph, only the changes that involve `port` are sent, and the filtering is done
+    /// within the crate. This suits one watcher per port, for example to show whether a channel is
+    /// connected. The events end once `port` is unregistered or the client is deactivated.
+    ///
+    /// Fails with `Error::GraphNotTracked` if the graph callbacks were not registered on
+    /// activation, see `Client::set_graph_tracking_enabled`.
+    pub fn watch_port<PS: PortSpec>(&self, port: &Port<PS>) -> Result<PortEventReceiver, Error> {
+        let callback = self.callback.as_ref().unwrap();
+        if !callback.tracks_graph {
+            return Err(Error::GraphNotTracked);
+        }
+        let name = port.name().unwrap_or_default();
+        Ok(callback.port_watchers().add(port.raw(), name))
+    }
+
+    /// Connect `source_port` to `destination_port`, by their full names, once both of them are
+    /// registered. This suits restoring the connections of a session, where the peers may still be
+    /// starting up.
+    ///
+    /// If both ports exist, they are connected right away like `Client::connect_ports_by_name`.
+    /// Otherwise a thread waits for the ports to be registered, and connects them as soon as both
+    /// exist or fails with `Error::PortConnectionTimeout` once `timeout` has passed. Ports that are
+    /// already connected count as connected. Dropping the returned `PendingConnection` cancels the
+    /// connection. A `timeout` too large to compute a deadline for, like `Duration::MAX`, waits
+    /// until the connection is cancelled.
+    ///
+    /// Only the registrations while the client is active are noticed, so a connection that is
+    /// pending when the client is deactivated times out. Waiting for ports fails right away with
+    /// `Error::GraphNotTracked` if the graph callbacks were not registered on activation, see
+    /// `Client::set_graph_tracking_enabled`.
+    ///
+    /// ```no_run
+    /// let (client, _status) =
+    ///     jack::Client::new("restorer", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let active_client = client.activate_async((), ()).unwrap();
+    /// let connection = active_client.connect_when_available(
+    ///     "synth:out_l",
+    ///     "system:playback_1",
+    ///     std::time::Duration::from_secs(10),
+    /// );
+    /// connection.wait().unwrap();
+    /// ```
+    pub fn connect_when_available(
+        &self,
+        source_port: &str,
+        destination_port: &str,
+        timeout: time::Duration,
+    ) -> PendingConnection {
+        let client = self.as_client();
+        let (source, destination) = match (to_cstring(source_port), to_cstring(destination_port)) {
+            (Ok(source), Ok(destination)) => (source, destination),
+            (Err(err), _) | (_, Err(err)) => {
+                return PendingConnection::done(source_port, destination_port, Err(err))
+            }
+        };
+        if client.port_by_name(source_port).is_some()
+            && client.port_by_name(destination_port).is_some()
+        {
+            let res = match client.connect_ports_cstr(&source, &destination) {
+                Err(Error::PortAlreadyConnected(..)) => Ok(()),
+                res => res,
+            };
+            return PendingConnection::done(source_port, destination_port, res);
+        }
+        let callback = self.callback.as_ref().unwrap();
+        if !callback.tracks_graph {
+            let err = Err(Error::GraphNotTracked);
+            return PendingConnection::done(source_port, destination_port, err);
+        }
+        let subscribers = callback.port_subscribers.clone();
+        PendingConnection::start(
+            client.downgrade(),
+            subscribers,
+            source,
+            destination,
+            timeout,
+        )
+    }
+
+    /// The names of the ports by id and of the clients by uuid, kept up to date from the
+    /// registration notifications, see `NameRegistry`.
+    ///
+    /// The registry is filled with the existing ports and their clients on the first call, and
+    /// only kept up to date from then on, so clients that don't use it don't pay for it.
+    ///
+    /// Fails with `Error::GraphNotTracked` if the graph callbacks were not registered on
+    /// activation, see `Client::set_graph_tracking_enabled`.
+    pub fn name_registry(&self) -> Result<Arc<NameRegistry>, Error> {
+        let callback = self.callback.as_ref().unwrap();
+        if !callback.tracks_graph {
+            return Err(Error::GraphNotTracked);
+        }
+        let names = callback.names.clone();
+        names.enable(self.as_client());
+        Ok(names)
+    }
+
+    /// The cycle counter that `Watchdog` watches.
+    pub(crate) fn cycle_counter(&self) -> Arc<CycleCounter> {
+        self.callback.as_ref().unwrap().cycles.clone()
+    }
+
+    /// The number of xruns that occurred since the client was activated, or since the last call to
+    /// `reset_xrun_count`.
+    ///
+    /// Xruns are counted even if the `NotificationHandler` does not select
+    /// `CallbackSelection::XRUN`, see `xrun_stats`.
+    pub fn xruns_since_activation(&self) -> u64 {
+        self.callback.as_ref().unwrap().xruns.count()
+    }
+
+    /// Clear the xrun statistics, see `xrun_stats`.
+    pub fn reset_xrun_count(&self) {
+        self.callback.as_ref().unwrap().xruns.reset()
+    }
+
+    /// Get statistics on how long the process callback took since the client was activated, or
+    /// `None` if they were not enabled with `Client::set_timing_stats_enabled` before activation.
+    ///
+    /// Clients activated with `Client::activate_thread` are not measured. Reading the statistics
+    /// does not block the process thread.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # let process_handler = jack::ClosureProcessHandler::new(
+    /// #     move |_: &jack::Client, _: &jack::ProcessScope| jack::Control::Continue,
+    /// # );
+    /// let (mut client, _status) =
+    ///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// client.set_timing_stats_enabled(true);
+    /// let active_client = client.activate_async((), process_handler).unwrap();
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    /// let stats = active_client.timing_stats().unwrap();
+    /// println!("DSP load: {:.1}%", stats.dsp_load() * 100.0);
+    /// ```
+    pub fn timing_stats(&self) -> Option<TimingStats> {
+        let callback = self.callback.as_ref().unwrap();
+        let client = &callback.client;
+        let period_usecs = client.buffer_size() as f64 * 1_000_000.0 / client.sample_rate() as f64;
+        callback
+            .timing
+            .as_ref()
+            .map(|timing| timing.stats(period_usecs))
+    }
+
+    /// Change the buffer size like `Client::set_buffer_size`, and block until the buffer size
+    /// callback of this client was called with the new size, or until `timeout` has passed.
+    ///
+    /// JACK calls the buffer size callbacks after `Client::set_buffer_size` returns, so the
+    /// process handler may still be using the old size. Once this returns `Ok`, it uses the new
+    /// one. `Error::BufferSizeChangeTimeout` is returned if the callback was not called in time.
+    pub fn set_buffer_size_sync(
+        &self,
+        n_frames: Frames,
+        timeout: time::Duration,
+    ) -> Result<(), Error> {
+        let callback = self.callback.as_ref().unwrap();
+        callback.client.set_buffer_size(n_frames)?;
+        match poll_until(timeout, || {
+            callback.buffer_size.load(Ordering::Acquire) == n_frames
+        }) {
+            true => Ok(()),
+            false => Err(Error::BufferSizeChangeTimeout(n_frames)),
+        }
+    }
+
     /// Tell the JACK server to remove this client from the process graph.  Also, disconnect all
     /// ports belonging to it since inactive clients have no port connections.
     ///
     /// The `handler` that was used for `Client::activate` is returned on success. Its state may
     /// have changed due to JACK calling its methods.
     ///
+    /// The returned client may be activated again, with the same or new handlers. The callbacks
+    /// are cleared on deactivation and registered again on activation, and the ports of the client
+    /// stay registered. Their connections are gone though, use `deactivate_with_connections` to
+    /// restore them.
+    ///
     /// In the case of error, the `Client` is destroyed because its state is unknown, and it is
     /// therefore unsafe to continue using.
     pub fn deactivate(self) -> Result<(Client, N, P), Error> {
         let mut c = self;
-        unsafe {
-            c.maybe_deactivate()
-                .map(|c| (c.client, c.notification, c.process))
-        }
+        unsafe { c.maybe_deactivate().map(CallbackContext::into_parts) }
+    }
+
+    /// Like `deactivate`, but also returns the connections that the ports of the client had
+    /// before it was deactivated, see `Client::port_connections`. They may be restored with
+    /// `Client::restore_connections` once the client is active again.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let (client, _status) =
+    ///     jack::Client::new("reactivate", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let active = client.activate_async((), ()).unwrap();
+    /// let (client, (), (), connections) = active.deactivate_with_connections().unwrap();
+    /// // Reconfigure the client, for example register more ports.
+    /// let active = client.activate_async((), ()).unwrap();
+    /// active.as_client().restore_connections(&connections).unwrap();
+    /// ```
+    pub fn deactivate_with_connections(self) -> Result<(Client, N, P, PortConnections), Error> {
+        let connections = match self.callback.as_ref() {
+            Some(callback) => callback.client.port_connections(),
+            None => Vec::new(),
+        };
+        let (client, notification_handler, process_handler) = self.deactivate()?;
+        Ok((client, notification_handler, process_handler, connections))
     }
 
     // Helper function for deactivating. Any function that calls this should
@@ -104,25 +512,100 @@ impl<N, P> AsyncClient<N, P> {
             return Err(Error::ClientIsNoLongerAlive);
         }
         let client = self.callback.as_ref().unwrap().client.raw();
+        if let Some(process_thread) = self.callback.as_ref().unwrap().process_thread.as_ref() {
+            process_thread.stop();
+        }
         // Prevent the callback from being deallocated in case deactivation
-        // fails.
+        // fails. JACK may still call into it, so it is leaked in that case.
         let callback = Box::into_raw(self.callback.take().unwrap());
 
         // deactivate
         sleep_on_test();
         if j::jack_deactivate(client) != 0 {
+            log_event!(
+                error,
+                "failed to deactivate client {}",
+                (*callback).client.name_lossy()
+            );
             return Err(Error::ClientDeactivationError);
         }
+        log_event!(
+            debug,
+            "deactivated client {}",
+            (*callback).client.name_lossy()
+        );
+        (*callback).client.set_active(false);
 
-        // clear the callbacks
+        // clear the callbacks, JACK no longer calls them once deactivated so this is safe.
         sleep_on_test();
-        clear_callbacks(client)?;
+        if let Err(e) = (*callback).clear_callbacks() {
+            log_event!(
+                error,
+                "failed to clear the callbacks of client {}: {:?}",
+                (*callback).client.name_lossy(),
+                e
+            );
+            return Err(e);
+        }
 
-        // done, take ownership of callback
+        // done, take ownership of callback so it is dropped exactly once.
         Ok(*Box::from_raw(callback))
     }
 }
 
+/// Deactivates an `AsyncClient` whose handlers may borrow data when it goes out of scope, including
+/// while unwinding from a panic.
+///
+/// Unlike an `AsyncClient`, the scope is never handed out by value so it can't be leaked.
+pub(crate) struct ActiveScope<N, P>(AsyncClient<N, P>);
+
+impl<N, P> ActiveScope<N, P>
+where
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
+{
+    /// # Safety
+    /// The scope must be dropped or deactivated before any data borrowed by the handlers goes out
+    /// of scope.
+    pub(crate) unsafe fn new(
+        client: Client,
+        notification_handler: N,
+        process_handler: P,
+    ) -> Result<Self, Error> {
+        AsyncClient::new_unchecked(client, notification_handler, process_handler).map(ActiveScope)
+    }
+}
+
+impl<N, P> ActiveScope<N, P> {
+    pub(crate) fn async_client(&self) -> &AsyncClient<N, P> {
+        &self.0
+    }
+
+    pub(crate) fn deactivate(mut self) -> Result<(Client, N, P), Error> {
+        self.deactivate_or_abort().map(CallbackContext::into_parts)
+    }
+
+    // JACK may still call into the handlers if deactivation fails, and they may borrow data that is
+    // about to go away. Aborting is the only sound option left.
+    fn deactivate_or_abort(&mut self) -> Result<CallbackContext<N, P>, Error> {
+        match unsafe { self.0.maybe_deactivate() } {
+            Err(Error::ClientDeactivationError) => {
+                eprintln!("jack: failed to deactivate a scoped client, aborting");
+                std::process::abort()
+            }
+            res => res,
+        }
+    }
+}
+
+impl<N, P> Drop for ActiveScope<N, P> {
+    fn drop(&mut self) {
+        if self.0.callback.is_some() {
+            let _ = self.deactivate_or_abort();
+        }
+    }
+}
+
 /// Closes the client.
 impl<N, P> Drop for AsyncClient<N, P> {
     /// Deactivate and close the client.
@@ -136,3 +619,23 @@ impl<N, P> fmt::Debug for AsyncClient<N, P> {
         write!(f, "AsyncClient({:?})", self.as_client())
     }
 }
+
+/// Poll `done` every `QUIT_POLL_INTERVAL` until it returns `true`, or return `false` once `timeout`
+/// has passed. A timeout too large to compute a deadline for waits without one.
+fn poll_until<F: FnMut() -> bool>(timeout: time::Duration, mut done: F) -> bool {
+    let deadline = time::Instant::now().checked_add(timeout);
+    loop {
+        if done() {
+            return true;
+        }
+        let mut interval = QUIT_POLL_INTERVAL;
+        if let Some(deadline) = deadline {
+            let now = time::Instant::now();
+            if now >= deadline {
+                return false;
+            }
+            interval = interval.min(deadline.saturating_duration_since(now));
+        }
+        thread::sleep(interval);
+    }
+}
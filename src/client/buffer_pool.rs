@@ -0,0 +1,195 @@
+use std::slice;
+
+use crate::{Client, Control, Frames, ProcessHandler, ProcessScope};
+
+/// Scratch buffers that are sized to the JACK buffer size.
+///
+/// The pool holds `count` buffers of `channels` channels each. Every channel holds exactly
+/// `buffer_size` samples. Resizing allocates, so it should only be done in
+/// `ProcessHandler::buffer_size`. JACK calls it outside of the process cycle, and once before the
+/// first call to `process`. Accessing the buffers does not allocate or block, so it is suitable
+/// for the process callback.
+///
+/// `PooledProcessHandler` takes care of resizing the pool.
+///
+/// # Example
+/// ```
+/// // Two stereo scratch buffers.
+/// let mut pool = jack::BufferPool::new(2, 2);
+/// pool.set_buffer_size(1024);
+/// let mut channels = pool.channels_mut(0);
+/// let left = channels.next().unwrap();
+/// left.fill(0.5);
+/// assert_eq!(left.len(), 1024);
+/// assert_eq!(pool.buffer_mut(1).len(), 2 * 1024);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct BufferPool {
+    count: usize,
+    channels: usize,
+    buffer_size: Frames,
+    samples: Vec<f32>,
+}
+
+impl BufferPool {
+    /// Create a pool of `count` buffers with `channels` channels each. No samples are allocated
+    /// until the buffer size is set.
+    pub fn new(count: usize, channels: usize) -> BufferPool {
+        BufferPool {
+            count,
+            channels,
+            buffer_size: 0,
+            samples: Vec::new(),
+        }
+    }
+
+    /// Create a pool of `count` buffers with `channels` channels each, sized for the current
+    /// buffer size of `client`.
+    pub fn for_client(client: &Client, count: usize, channels: usize) -> BufferPool {
+        let mut pool = BufferPool::new(count, channels);
+        pool.set_buffer_size(client.buffer_size());
+        pool
+    }
+
+    /// The number of buffers in the pool.
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// The number of channels in each buffer.
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// The number of samples in each channel.
+    pub fn buffer_size(&self) -> Frames {
+        self.buffer_size
+    }
+
+    /// Resize every channel to hold `buffer_size` samples. All samples are set to `0.0`.
+    ///
+    /// # Remarks
+    /// * Allocates unless the buffer size is unchanged, so it must not be called from `process`.
+    pub fn set_buffer_size(&mut self, buffer_size: Frames) {
+        if buffer_size == self.buffer_size {
+            return;
+        }
+        self.buffer_size = buffer_size;
+        // Replace the allocation so that shrinking releases the memory as well.
+        self.samples = vec![0.0; self.count * self.channels * buffer_size as usize];
+    }
+
+    /// Get the samples of buffer `index`, with its channels laid out one after the other.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of range.
+    pub fn buffer_mut(&mut self, index: usize) -> &mut [f32] {
+        assert!(index < self.count, "buffer {} out of range", index);
+        let len = self.channels * self.buffer_size as usize;
+        &mut self.samples[index * len..(index + 1) * len]
+    }
+
+    /// Iterate over the channels of buffer `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is out of range.
+    pub fn channels_mut(&mut self, index: usize) -> slice::ChunksExactMut<'_, f32> {
+        // `chunks_exact_mut` panics on a chunk size of 0. The buffer is empty before the buffer size
+        // is set, so there are no chunks either way.
+        let channel_len = (self.buffer_size as usize).max(1);
+        self.buffer_mut(index).chunks_exact_mut(channel_len)
+    }
+
+    /// Iterate over all buffers in the pool. Each buffer has its channels laid out one after the
+    /// other.
+    pub fn buffers_mut(&mut self) -> slice::ChunksExactMut<'_, f32> {
+        let len = (self.channels * self.buffer_size as usize).max(1);
+        self.samples.chunks_exact_mut(len)
+    }
+}
+
+/// Wrap a closure that handles the `process` callback with access to a `BufferPool`. The pool is
+/// resized in the `buffer_size` callback, so it always matches the JACK buffer size within
+/// `process`.
+pub struct PooledProcessHandler<F: Send + FnMut(&Client, &ProcessScope, &mut BufferPool) -> Control>
+{
+    pub pool: BufferPool,
+    pub process_fn: F,
+}
+
+impl<F> PooledProcessHandler<F>
+where
+    F: Send + FnMut(&Client, &ProcessScope, &mut BufferPool) -> Control,
+{
+    pub fn new(pool: BufferPool, f: F) -> PooledProcessHandler<F> {
+        PooledProcessHandler {
+            pool,
+            process_fn: f,
+        }
+    }
+}
+
+impl<F> ProcessHandler for PooledProcessHandler<F>
+where
+    F: Send + FnMut(&Client, &ProcessScope, &mut BufferPool) -> Control,
+{
+    fn process(&mut self, c: &Client, ps: &ProcessScope) -> Control {
+        (self.process_fn)(c, ps, &mut self.pool)
+    }
+
+    fn buffer_size(&mut self, _: &Client, size: Frames) -> Control {
+        self.pool.set_buffer_size(size);
+        Control::Continue
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn buffer_pool_is_empty_until_sized() {
+        let mut pool = BufferPool::new(2, 2);
+        assert_eq!(pool.buffer_size(), 0);
+        assert!(pool.buffer_mut(1).is_empty());
+        assert_eq!(pool.channels_mut(0).count(), 0);
+        assert_eq!(pool.buffers_mut().count(), 0);
+    }
+
+    #[test]
+    fn buffer_pool_grows_and_shrinks() {
+        let mut pool = BufferPool::new(3, 2);
+        for &size in &[256, 1024, 64] {
+            pool.set_buffer_size(size);
+            assert_eq!(pool.buffer_size(), size);
+            assert_eq!(pool.buffers_mut().count(), 3);
+            for buffer in pool.buffers_mut() {
+                assert_eq!(buffer.len(), 2 * size as usize);
+            }
+            for channel in pool.channels_mut(2) {
+                assert_eq!(channel.len(), size as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn buffer_pool_buffers_do_not_overlap() {
+        let mut pool = BufferPool::new(2, 2);
+        pool.set_buffer_size(4);
+        for (i, channel) in pool.channels_mut(1).enumerate() {
+            channel.iter_mut().for_each(|s| *s = i as f32 + 1.0);
+        }
+        assert_eq!(pool.buffer_mut(0), &[0.0; 8][..]);
+        assert_eq!(
+            pool.buffer_mut(1),
+            &[1.0, 1.0, 1.0, 1.0, 2.0, 2.0, 2.0, 2.0][..]
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn buffer_pool_panics_on_out_of_range_buffer() {
+        let mut pool = BufferPool::new(2, 2);
+        pool.buffer_mut(2);
+    }
+}
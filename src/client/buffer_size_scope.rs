@@ -0,0 +1,60 @@
+use jack_sys as j;
+
+use crate::{Client, Frames};
+
+/// Provides the new buffer size, and the sizes of the port buffers that depend on it, within the
+/// buffer size callback, see `ProcessHandler::buffer_size_with_scope`.
+///
+/// # Example
+/// A handler that sizes its storage of outgoing MIDI bytes for a whole cycle:
+/// ```
+/// struct Sequencer {
+///     pending: Vec<u8>,
+/// }
+///
+/// impl jack::ProcessHandler for Sequencer {
+///     fn process(&mut self, _: &jack::Client, _: &jack::ProcessScope) -> jack::Control {
+///         jack::Control::Continue
+///     }
+///
+///     fn buffer_size_with_scope(&mut self, scope: &jack::BufferSizeScope) -> jack::Control {
+///         self.pending.reserve(scope.midi_buffer_size());
+///         jack::Control::Continue
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct BufferSizeScope<'a> {
+    client: &'a Client,
+    n_frames: Frames,
+}
+
+impl<'a> BufferSizeScope<'a> {
+    pub(crate) fn new(client: &'a Client, n_frames: Frames) -> BufferSizeScope<'a> {
+        BufferSizeScope { client, n_frames }
+    }
+
+    /// The client whose buffer size changes.
+    pub fn client(&self) -> &'a Client {
+        self.client
+    }
+
+    /// The number of frames that will be passed to the process callback.
+    pub fn n_frames(&self) -> Frames {
+        self.n_frames
+    }
+
+    /// The size of the buffer of an audio port in bytes.
+    pub fn audio_buffer_size(&self) -> usize {
+        // The scope only exists within the buffer size callback.
+        unsafe { self.client.type_buffer_size(j::FLOAT_MONO_AUDIO) }
+    }
+
+    /// The size of the buffer of a MIDI port in bytes. This bounds the number of bytes of MIDI
+    /// events, including the space JACK uses for each event, that can be written to a `MidiOut`
+    /// port in one cycle. It grows with the buffer size.
+    pub fn midi_buffer_size(&self) -> usize {
+        // The scope only exists within the buffer size callback.
+        unsafe { self.client.type_buffer_size(j::RAW_MIDI_TYPE) }
+    }
+}
@@ -0,0 +1,63 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Selects the `NotificationHandler` callbacks that are registered with JACK.
+    ///
+    /// JACK calls into the client for every event that has a registered callback, so handlers that
+    /// only implement a few methods can avoid the overhead of the other ones by selecting just the
+    /// methods they implement in `NotificationHandler::callbacks`.
+    ///
+    /// The shutdown, freewheel, sample rate and xrun callbacks are always registered since the
+    /// crate keeps track of them, but the handler is only called if they are selected. The client
+    /// registration, port registration and connection callbacks are only registered if they are
+    /// selected, or if `Client::set_graph_tracking_enabled` was called.
+    pub struct CallbackSelection: u32 {
+        /// `NotificationHandler::thread_init`.
+        const THREAD_INIT         = 1 << 0;
+
+        /// `NotificationHandler::shutdown`.
+        const SHUTDOWN            = 1 << 1;
+
+        /// `NotificationHandler::freewheel`.
+        const FREEWHEEL           = 1 << 2;
+
+        /// `NotificationHandler::sample_rate`.
+        const SAMPLE_RATE         = 1 << 3;
+
+        /// `NotificationHandler::client_registration`.
+        const CLIENT_REGISTRATION = 1 << 4;
+
+        /// `NotificationHandler::port_registration`.
+        const PORT_REGISTRATION   = 1 << 5;
+
+        /// `NotificationHandler::port_rename`.
+        const PORT_RENAME         = 1 << 6;
+
+        /// `NotificationHandler::ports_connected`.
+        const PORTS_CONNECTED     = 1 << 7;
+
+        /// `NotificationHandler::graph_reorder`.
+        const GRAPH_REORDER       = 1 << 8;
+
+        /// `NotificationHandler::xrun` and `NotificationHandler::xrun_with_delay`.
+        const XRUN                = 1 << 9;
+
+        /// `NotificationHandler::latency` and `NotificationHandler::latency_with_scope`.
+        ///
+        /// Registering a latency callback changes how JACK computes the latency of the client's
+        /// ports, so only select it if the handler actually sets latency ranges.
+        const LATENCY             = 1 << 10;
+
+        /// `NotificationHandler::property_change`.
+        ///
+        /// Only registered with the `metadata` feature, and unavailable if the client already has
+        /// a handler from `Client::register_property_change_handler`.
+        const PROPERTY_CHANGE     = 1 << 11;
+    }
+}
+
+impl Default for CallbackSelection {
+    fn default() -> Self {
+        CallbackSelection::all()
+    }
+}
@@ -1,7 +1,27 @@
 use jack_sys as j;
+use std::cell::UnsafeCell;
 use std::ffi;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, TryLockError};
 
+use super::buffer_size_scope::BufferSizeScope;
+use super::callback_selection::CallbackSelection;
+use super::latency::LatencyScope;
+use super::name_registry::NameRegistry;
+use super::pending_connection::RegistrationSubscribers;
+use super::port_cache::{PortCache, PortRegistrationInfo};
+use super::port_watch::PortWatchers;
+use super::process_thread::ProcessThread;
+use super::shutdown::ShutdownSlot;
+use super::timing::TimingCounter;
+use super::watchdog::CycleCounter;
+use super::xrun::XrunCounter;
 use crate::{Client, ClientStatus, Control, Error, Frames, LatencyType, PortId, ProcessScope};
+#[cfg(feature = "metadata")]
+use crate::{PropertyChangeKind, Uuid};
+#[cfg(feature = "log")]
+use crate::{RtLogThread, RtLogger};
 
 /// Specifies callbacks for JACK.
 pub trait NotificationHandler: Send {
@@ -20,6 +40,10 @@ pub trait NotificationHandler: Send {
     /// flag or write to a
     /// pipe so that the rest of the application knows that the JACK client
     /// thread has shut down.
+    ///
+    /// The call is skipped if the handler is locked at the time, for example by
+    /// `AsyncClient::notification_handler`. The shutdown is still reported by
+    /// `AsyncClient::shutdown_receiver`.
     fn shutdown(&mut self, _status: ClientStatus, _reason: &str) {}
 
     /// Called whenever "freewheel" mode is entered or leaving.
@@ -36,6 +60,19 @@ pub trait NotificationHandler: Send {
     /// Called whenever a port is registered or unregistered
     fn port_registration(&mut self, _: &Client, _port_id: PortId, _is_registered: bool) {}
 
+    /// Called whenever a port is registered or unregistered, with the information of the port
+    /// resolved by the crate.
+    ///
+    /// The default implementation calls `port_registration`.
+    fn port_registration_with_info(
+        &mut self,
+        client: &Client,
+        info: &PortRegistrationInfo,
+        is_registered: bool,
+    ) {
+        self.port_registration(client, info.id, is_registered)
+    }
+
     /// Called whenever a port is renamed.
     fn port_rename(
         &mut self,
@@ -57,6 +94,25 @@ pub trait NotificationHandler: Send {
     ) {
     }
 
+    /// Called whenever ports are connected/disconnected to/from each other, with the full names
+    /// of the ports resolved by the crate.
+    ///
+    /// If a port is already gone, for example because it was disconnected by being unregistered,
+    /// its last known name is used, or `"?"` if the port's name was never known.
+    ///
+    /// The default implementation calls `ports_connected`.
+    fn ports_connected_with_names(
+        &mut self,
+        client: &Client,
+        port_id_a: PortId,
+        _port_name_a: &str,
+        port_id_b: PortId,
+        _port_name_b: &str,
+        are_connected: bool,
+    ) {
+        self.ports_connected(client, port_id_a, port_id_b, are_connected)
+    }
+
     /// Called whenever the processing graph is reordered.
     fn graph_reorder(&mut self, _: &Client) -> Control {
         Control::Continue
@@ -70,6 +126,18 @@ pub trait NotificationHandler: Send {
         Control::Continue
     }
 
+    /// Called whenever an xrun occurs, with the delay in microseconds that caused the xrun.
+    ///
+    /// The default implementation calls `xrun`. The crate keeps track of the number of xruns and
+    /// their delays regardless, see `AsyncClient::xrun_stats`.
+    ///
+    /// JACK1 calls this from the process thread, which must not wait for a lock. The call is
+    /// skipped if the handler is locked at the time, for example by
+    /// `AsyncClient::notification_handler`.
+    fn xrun_with_delay(&mut self, client: &Client, _delayed_usecs: f32) -> Control {
+        self.xrun(client)
+    }
+
     /// Called whenever it is necessary to recompute the latencies for some or
     /// all JACK ports.
     ///
@@ -127,6 +195,38 @@ pub trait NotificationHandler: Send {
     /// will need to be
     /// passed into jack_port_set_latency_range()
     fn latency(&mut self, _: &Client, _mode: LatencyType) {}
+
+    /// Called whenever it is necessary to recompute the latencies for some or all JACK ports, with
+    /// a `LatencyScope` that provides the ports of this client that are involved.
+    ///
+    /// The default implementation calls `latency`. See `latency` on whether a client needs to
+    /// handle this callback and `LatencyScope` for an example.
+    fn latency_with_scope(&mut self, scope: &LatencyScope) {
+        self.latency(scope.client(), scope.mode())
+    }
+
+    /// Called whenever a Metadata property of `subject` is created, changed or deleted. `key` is
+    /// `None` if the change concerns all the properties of the subject.
+    ///
+    /// Only available with the `metadata` feature.
+    #[cfg(feature = "metadata")]
+    fn property_change(
+        &mut self,
+        _: &Client,
+        _subject: Uuid,
+        _key: Option<&str>,
+        _change: PropertyChangeKind,
+    ) {
+    }
+
+    /// The callbacks that should be registered with JACK. Methods whose callback is not selected
+    /// are never called.
+    ///
+    /// Called once on activation. The default selects all callbacks. Handlers that only implement
+    /// a few methods should select just those to avoid needless calls from JACK.
+    fn callbacks(&self) -> CallbackSelection {
+        CallbackSelection::all()
+    }
 }
 
 /// Specifies real-time processing.
@@ -143,6 +243,10 @@ pub trait ProcessHandler: Send {
     ///
     /// Should return `Control::Continue` on success, and
     /// `Control::Quit` on error.
+    ///
+    /// Once `Control::Quit` is returned, JACK stops calling the client. The client remains open but
+    /// silent until it is deactivated, which can be detected with `AsyncClient::is_processing` or
+    /// `AsyncClient::wait_for_quit`.
     fn process(&mut self, _: &Client, _process_scope: &ProcessScope) -> Control;
 
     /// Called whenever the size of the buffer that will be passed to `process`
@@ -154,15 +258,30 @@ pub trait ProcessHandler: Send {
     fn buffer_size(&mut self, _: &Client, _size: Frames) -> Control {
         Control::Continue
     }
+
+    /// Like `buffer_size`, with a `BufferSizeScope` that also provides the sizes of the port
+    /// buffers for the new buffer size, such as `BufferSizeScope::midi_buffer_size`.
+    ///
+    /// The default implementation calls `buffer_size`.
+    fn buffer_size_with_scope(&mut self, scope: &BufferSizeScope) -> Control {
+        self.buffer_size(scope.client(), scope.n_frames())
+    }
 }
 
 unsafe extern "C" fn thread_init_callback<N, P>(data: *mut libc::c_void)
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.thread_init(&ctx.client)
+    if let Some(config) = ctx.client.thread_init_config() {
+        config.apply();
+    }
+    if ctx.callbacks.contains(CallbackSelection::THREAD_INIT) {
+        ctx.guard_notification("thread_init", (), || {
+            ctx.notification_handler().thread_init(&ctx.client)
+        })
+    }
 }
 
 unsafe extern "C" fn shutdown<N, P>(
@@ -170,54 +289,127 @@ unsafe extern "C" fn shutdown<N, P>(
     reason: *const libc::c_char,
     data: *mut libc::c_void,
 ) where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    let cstr = ffi::CStr::from_ptr(reason);
-    let reason_str = cstr.to_str().unwrap_or("Failed to interpret error.");
-    ctx.notification.shutdown(
-        ClientStatus::from_bits(code).unwrap_or_else(ClientStatus::empty),
-        reason_str,
-    )
+    let status = ClientStatus::from_bits(code).unwrap_or_else(ClientStatus::empty);
+    let reason = ffi::CStr::from_ptr(reason);
+    ctx.shutdown.store(status, reason.to_bytes());
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        target: "jack",
+        status = ?status,
+        reason = %reason.to_string_lossy(),
+        "client was shut down"
+    );
+    if !ctx.callbacks.contains(CallbackSelection::SHUTDOWN) {
+        return;
+    }
+    // The shutdown callback may run on the process thread, so it does not wait for the handler.
+    ctx.guard((), || {
+        if let Some(mut handler) = ctx.try_notification_handler() {
+            handler.shutdown(status, &reason.to_string_lossy())
+        }
+    })
+}
+
+/// Run `f` within `assert_no_alloc` if the `assert-no-alloc` feature is enabled.
+#[inline(always)]
+pub(crate) fn forbid_alloc<T, F: FnOnce() -> T>(f: F) -> T {
+    #[cfg(feature = "assert-no-alloc")]
+    return crate::no_alloc::assert_no_alloc(f);
+    #[cfg(not(feature = "assert-no-alloc"))]
+    f()
 }
 
 unsafe extern "C" fn process<N, P>(n_frames: Frames, data: *mut libc::c_void) -> libc::c_int
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    let scope = ProcessScope::from_raw(n_frames, ctx.client.raw());
-    ctx.process.process(&ctx.client, &scope).to_ffi()
+    ctx.cycles.record(n_frames);
+    let start = ctx.timing.as_ref().map(|_| j::jack_get_time());
+    let res = ctx.guard(Control::Quit.to_ffi(), || {
+        let sample_rate = ctx.sample_rate.load(Ordering::Relaxed) as usize;
+        let scope =
+            ProcessScope::from_raw(n_frames, ctx.client.raw()).with_sample_rate(sample_rate);
+        forbid_alloc(|| ctx.process_handler().process(&ctx.client, &scope).to_ffi())
+    });
+    if let (Some(timing), Some(start)) = (ctx.timing.as_ref(), start) {
+        timing.record(start, j::jack_get_time());
+    }
+    if res != 0 {
+        ctx.quit.store(true, Ordering::Release);
+    }
+    ctx.cycles.finish();
+    res
+}
+
+unsafe extern "C" fn process_thread<N, P>(data: *mut libc::c_void) -> *mut libc::c_void
+where
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
+{
+    let ctx = CallbackContext::<N, P>::from_raw(data);
+    if let Some(thread) = ctx.process_thread.as_ref() {
+        ctx.guard((), || {
+            thread.run(&ctx.client, &ctx.quit, &ctx.cycles, &ctx.sample_rate)
+        });
+    }
+    std::ptr::null_mut()
 }
 
 unsafe extern "C" fn freewheel<N, P>(starting: libc::c_int, data: *mut libc::c_void)
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
     let is_starting = !matches!(starting, 0);
-    ctx.notification.freewheel(&ctx.client, is_starting)
+    ctx.cycles.set_freewheeling(is_starting);
+    if !ctx.callbacks.contains(CallbackSelection::FREEWHEEL) {
+        return;
+    }
+    ctx.guard_notification("freewheel", (), || {
+        ctx.notification_handler()
+            .freewheel(&ctx.client, is_starting)
+    })
 }
 
 unsafe extern "C" fn buffer_size<N, P>(n_frames: Frames, data: *mut libc::c_void) -> libc::c_int
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.process.buffer_size(&ctx.client, n_frames).to_ffi()
+    let res = ctx.guard(Control::Quit.to_ffi(), || {
+        let scope = BufferSizeScope::new(&ctx.client, n_frames);
+        ctx.process_handler()
+            .buffer_size_with_scope(&scope)
+            .to_ffi()
+    });
+    ctx.buffer_size.store(n_frames, Ordering::Release);
+    res
 }
 
 unsafe extern "C" fn sample_rate<N, P>(n_frames: Frames, data: *mut libc::c_void) -> libc::c_int
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.sample_rate(&ctx.client, n_frames).to_ffi()
+    // Stored before the next process cycle, which runs after the callback.
+    ctx.sample_rate.store(n_frames, Ordering::Relaxed);
+    if !ctx.callbacks.contains(CallbackSelection::SAMPLE_RATE) {
+        return Control::Continue.to_ffi();
+    }
+    ctx.guard_notification("sample_rate", Control::Continue.to_ffi(), || {
+        ctx.notification_handler()
+            .sample_rate(&ctx.client, n_frames)
+            .to_ffi()
+    })
 }
 
 unsafe extern "C" fn client_registration<N, P>(
@@ -225,14 +417,31 @@ unsafe extern "C" fn client_registration<N, P>(
     register: libc::c_int,
     data: *mut libc::c_void,
 ) where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    let name = ffi::CStr::from_ptr(name).to_str().unwrap();
     let register = !matches!(register, 0);
-    ctx.notification
-        .client_registration(&ctx.client, name, register)
+    let notify = ctx
+        .callbacks
+        .contains(CallbackSelection::CLIENT_REGISTRATION);
+    if !notify && !ctx.names.is_enabled() {
+        return;
+    }
+    ctx.guard_notification("client_registration", (), || {
+        let name = ffi::CStr::from_ptr(name).to_string_lossy();
+        if ctx.names.is_enabled() {
+            let now = std::time::Instant::now();
+            match register {
+                true => ctx.names.client_registered(&name),
+                false => ctx.names.client_unregistered(&name, now),
+            }
+        }
+        if notify {
+            ctx.notification_handler()
+                .client_registration(&ctx.client, &name, register)
+        }
+    })
 }
 
 unsafe extern "C" fn port_registration<N, P>(
@@ -240,13 +449,47 @@ unsafe extern "C" fn port_registration<N, P>(
     register: libc::c_int,
     data: *mut libc::c_void,
 ) where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
     let register = !matches!(register, 0);
-    ctx.notification
-        .port_registration(&ctx.client, port_id, register)
+    ctx.guard_notification("port_registration", (), || {
+        if register {
+            ctx.port_subscribers.port_registered();
+        }
+        let notify = ctx.callbacks.contains(CallbackSelection::PORT_REGISTRATION);
+        let mut port_watchers = ctx.port_watchers();
+        if !notify && port_watchers.is_empty() && !ctx.names.is_enabled() {
+            return;
+        }
+        let mut port_cache = ctx.port_cache();
+        let info = if register {
+            port_cache.register(&ctx.client, port_id)
+        } else {
+            port_cache.unregister(&ctx.client, port_id)
+        };
+        drop(port_cache);
+        if ctx.names.is_enabled() {
+            let now = std::time::Instant::now();
+            match register {
+                true if !info.name.is_empty() => {
+                    ctx.names.port_registered(port_id, &info.name, now)
+                }
+                true => (),
+                false => ctx.names.port_unregistered(port_id, &info.name, now),
+            }
+        }
+        if !register && !port_watchers.is_empty() {
+            let port = j::jack_port_by_id(ctx.client.raw(), port_id);
+            port_watchers.port_unregistered(port, &info.name);
+        }
+        drop(port_watchers);
+        if notify {
+            ctx.notification_handler()
+                .port_registration_with_info(&ctx.client, &info, register)
+        }
+    })
 }
 
 #[allow(dead_code)] // TODO: remove once it can be registered
@@ -257,15 +500,17 @@ unsafe extern "C" fn port_rename<N, P>(
     data: *mut libc::c_void,
 ) -> libc::c_int
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    let old_name = ffi::CStr::from_ptr(old_name).to_str().unwrap();
-    let new_name = ffi::CStr::from_ptr(new_name).to_str().unwrap();
-    ctx.notification
-        .port_rename(&ctx.client, port_id, old_name, new_name)
-        .to_ffi()
+    ctx.guard_notification("port_rename", Control::Continue.to_ffi(), || {
+        let old_name = ffi::CStr::from_ptr(old_name).to_string_lossy();
+        let new_name = ffi::CStr::from_ptr(new_name).to_string_lossy();
+        ctx.notification_handler()
+            .port_rename(&ctx.client, port_id, &old_name, &new_name)
+            .to_ffi()
+    })
 }
 
 unsafe extern "C" fn port_connect<N, P>(
@@ -274,45 +519,122 @@ unsafe extern "C" fn port_connect<N, P>(
     connect: libc::c_int,
     data: *mut libc::c_void,
 ) where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
     let are_connected = !matches!(connect, 0);
-    ctx.notification
-        .ports_connected(&ctx.client, port_id_a, port_id_b, are_connected)
+    ctx.guard_notification("ports_connected", (), || {
+        let notify = ctx.callbacks.contains(CallbackSelection::PORTS_CONNECTED);
+        let mut port_watchers = ctx.port_watchers();
+        if !notify && port_watchers.is_empty() {
+            return;
+        }
+        let mut port_cache = ctx.port_cache();
+        let name_a = port_cache.name(&ctx.client, port_id_a);
+        let name_b = port_cache.name(&ctx.client, port_id_b);
+        drop(port_cache);
+        if !port_watchers.is_empty() {
+            let port_a = j::jack_port_by_id(ctx.client.raw(), port_id_a);
+            let port_b = j::jack_port_by_id(ctx.client.raw(), port_id_b);
+            port_watchers.connection_changed((port_a, &name_a), (port_b, &name_b), are_connected);
+        }
+        drop(port_watchers);
+        if !notify {
+            return;
+        }
+        ctx.notification_handler().ports_connected_with_names(
+            &ctx.client,
+            port_id_a,
+            &name_a,
+            port_id_b,
+            &name_b,
+            are_connected,
+        )
+    })
 }
 
 unsafe extern "C" fn graph_order<N, P>(data: *mut libc::c_void) -> libc::c_int
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.graph_reorder(&ctx.client).to_ffi()
+    ctx.guard_notification("graph_reorder", Control::Continue.to_ffi(), || {
+        ctx.notification_handler()
+            .graph_reorder(&ctx.client)
+            .to_ffi()
+    })
 }
 
 unsafe extern "C" fn xrun<N, P>(data: *mut libc::c_void) -> libc::c_int
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
-    ctx.notification.xrun(&ctx.client).to_ffi()
+    let delayed_usecs = ctx.client.xrun_delayed_usecs();
+    ctx.xruns.record(delayed_usecs);
+    // JACK1 calls the xrun callback from the process thread, so xruns are logged through the
+    // real-time log.
+    #[cfg(feature = "log")]
+    {
+        if let Some(Ok(mut logger)) = ctx.xrun_log.as_ref().map(|(l, _)| l.try_lock()) {
+            crate::rt_log!(logger, log::Level::Warn, "xrun of {} usecs", delayed_usecs);
+        }
+    }
+    if !ctx.callbacks.contains(CallbackSelection::XRUN) {
+        return Control::Continue.to_ffi();
+    }
+    ctx.guard(Control::Continue.to_ffi(), || {
+        match ctx.try_notification_handler() {
+            Some(mut handler) => handler.xrun_with_delay(&ctx.client, delayed_usecs),
+            None => Control::Continue,
+        }
+        .to_ffi()
+    })
 }
 
 unsafe extern "C" fn latency<N, P>(mode: j::jack_latency_callback_mode_t, data: *mut libc::c_void)
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
     let ctx = CallbackContext::<N, P>::from_raw(data);
     let mode = match mode {
         j::JackCaptureLatency => LatencyType::Capture,
         j::JackPlaybackLatency => LatencyType::Playback,
-        _ => unreachable!(),
+        _ => return,
     };
-    ctx.notification.latency(&ctx.client, mode)
+    ctx.guard_notification("latency", (), || {
+        let scope = LatencyScope::new(&ctx.client, mode);
+        ctx.notification_handler().latency_with_scope(&scope)
+    })
+}
+
+#[cfg(feature = "metadata")]
+unsafe extern "C" fn property_change<N, P>(
+    subject: j::jack_uuid_t,
+    key: *const libc::c_char,
+    change: j::jack_property_change_t,
+    data: *mut libc::c_void,
+) where
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
+{
+    let ctx = CallbackContext::<N, P>::from_raw(data);
+    ctx.guard_notification("property_change", (), || {
+        let key = match key.is_null() {
+            true => None,
+            false => Some(ffi::CStr::from_ptr(key).to_string_lossy()),
+        };
+        ctx.notification_handler().property_change(
+            &ctx.client,
+            Uuid::from_raw(subject),
+            key.as_deref(),
+            PropertyChangeKind::from_ffi(change),
+        )
+    })
 }
 
 /// Unsafe ffi wrapper that clears the callbacks registered to `client`.
@@ -324,31 +646,262 @@ where
 /// # Unsafe
 ///
 /// * Uses ffi calls, be careful.
-///
-/// # TODO
-///
-/// * Implement correctly. Freezes on my system.
-pub unsafe fn clear_callbacks(_client: *mut j::jack_client_t) -> Result<(), Error> {
-    // j::jack_set_thread_init_callback(client, None, ptr::null_mut());
-    // j::jack_set_process_callback(client, None, ptr::null_mut());
-    Ok(())
+/// * `client` must not be active. JACK only stops calling the callbacks once `jack_deactivate`
+///   has returned, and callbacks can't be changed on an active client.
+pub unsafe fn clear_callbacks(client: *mut j::jack_client_t) -> Result<(), Error> {
+    let data_ptr = std::ptr::null_mut();
+    // JACK refuses to clear the process thread while a process callback is set and vice versa, so
+    // the process callback is cleared first. Clearing the process thread then always works.
+    j::jack_set_process_callback(client, None, data_ptr);
+    let res = [
+        j::jack_set_thread_init_callback(client, None, data_ptr),
+        j::jack_set_process_thread(client, None, data_ptr),
+        j::jack_set_freewheel_callback(client, None, data_ptr),
+        j::jack_set_buffer_size_callback(client, None, data_ptr),
+        j::jack_set_sample_rate_callback(client, None, data_ptr),
+        j::jack_set_client_registration_callback(client, None, data_ptr),
+        j::jack_set_port_registration_callback(client, None, data_ptr),
+        j::jack_set_port_connect_callback(client, None, data_ptr),
+        j::jack_set_graph_order_callback(client, None, data_ptr),
+        j::jack_set_xrun_callback(client, None, data_ptr),
+        j::jack_set_latency_callback(client, None, data_ptr),
+    ];
+    j::jack_on_info_shutdown(client, None, data_ptr);
+    if res.iter().all(|r| *r == 0) {
+        Ok(())
+    } else {
+        Err(Error::CallbackDeregistrationError)
+    }
 }
 
+/// The state shared between an `AsyncClient` and the JACK callback threads.
+///
+/// The notification handler is behind a lock so that it may be accessed from outside of the
+/// callbacks. The process handler is only ever accessed by the process thread while the client is
+/// active, which JACK guarantees is never concurrent with itself.
+///
+/// If any handler panics, the context is marked as poisoned and no handler is called again.
 pub struct CallbackContext<N, P> {
     pub client: Client,
-    pub notification: N,
-    pub process: P,
+    pub notification: Mutex<N>,
+    pub process: UnsafeCell<P>,
+    pub poisoned: AtomicBool,
+    /// Set once the process callback reports failure, after which JACK stops calling the client.
+    pub quit: AtomicBool,
+    pub shutdown: Arc<ShutdownSlot>,
+    pub xruns: XrunCounter,
+    /// Counts the process cycles for `Watchdog`.
+    pub cycles: Arc<CycleCounter>,
+    /// The size of the last call to the buffer size callback, `0` before the first.
+    pub buffer_size: AtomicU32,
+    /// The sample rate for `ProcessScope::sample_rate`, set on registration and updated by the
+    /// sample rate callback.
+    pub sample_rate: AtomicU32,
+    /// Measures the process callback, if enabled with `Client::set_timing_stats_enabled`.
+    pub timing: Option<TimingCounter>,
+    /// The notification callbacks selected by the handler, set on registration.
+    pub callbacks: CallbackSelection,
+    /// Whether the client, port and connection callbacks are registered for the crate, set on
+    /// registration, see `Client::set_graph_tracking_enabled`.
+    pub tracks_graph: bool,
+    pub port_cache: Mutex<PortCache>,
+    /// The ports watched with `AsyncClient::watch_port`.
+    pub port_watchers: Mutex<PortWatchers>,
+    /// The names of the ports and clients, see `AsyncClient::name_registry`.
+    pub names: Arc<NameRegistry>,
+    /// The connections of `AsyncClient::connect_when_available` that wait for ports.
+    pub port_subscribers: Arc<RegistrationSubscribers>,
+    /// The selected callbacks that could not be registered, set on registration.
+    pub unavailable_callbacks: CallbackSelection,
+    /// Runs the processing on its own thread instead of calling the process handler, see
+    /// `Client::activate_thread`.
+    pub process_thread: Option<ProcessThread>,
+    /// Logs the xruns with the `log` crate, if warnings for the `jack` target were enabled on
+    /// activation.
+    #[cfg(feature = "log")]
+    pub xrun_log: Option<(Mutex<RtLogger>, RtLogThread)>,
+}
+
+/// The callbacks that are registered for the crate if the graph is tracked, see
+/// `Client::set_graph_tracking_enabled`.
+const GRAPH_CALLBACKS: CallbackSelection = CallbackSelection::CLIENT_REGISTRATION
+    .union(CallbackSelection::PORT_REGISTRATION)
+    .union(CallbackSelection::PORTS_CONNECTED);
+
+/// The number of xruns that can be logged before the log thread forwards them.
+#[cfg(feature = "log")]
+const XRUN_LOG_CAPACITY: usize = 16;
+
+impl<N, P> CallbackContext<N, P> {
+    pub fn new(client: Client, notification: N, process: P) -> Self {
+        let timing = match client.timing_stats_enabled() {
+            true => Some(TimingCounter::default()),
+            false => None,
+        };
+        #[cfg(feature = "log")]
+        let xrun_log = match log::log_enabled!(target: "jack", log::Level::Warn) {
+            true => RtLogger::new(&client, XRUN_LOG_CAPACITY)
+                .ok()
+                .map(|(logger, reader)| (Mutex::new(logger), reader.spawn())),
+            false => None,
+        };
+        CallbackContext {
+            #[cfg(feature = "log")]
+            xrun_log,
+            process_thread: None,
+            timing,
+            client,
+            notification: Mutex::new(notification),
+            process: UnsafeCell::new(process),
+            poisoned: AtomicBool::new(false),
+            quit: AtomicBool::new(false),
+            shutdown: ShutdownSlot::new(),
+            xruns: XrunCounter::default(),
+            cycles: Arc::default(),
+            buffer_size: AtomicU32::new(0),
+            sample_rate: AtomicU32::new(0),
+            callbacks: CallbackSelection::all(),
+            tracks_graph: true,
+            port_cache: Mutex::default(),
+            port_watchers: Mutex::default(),
+            names: Arc::default(),
+            port_subscribers: Arc::default(),
+            unavailable_callbacks: CallbackSelection::empty(),
+        }
+    }
+
+    /// Lock the cache of port information. Only used by the notification callbacks.
+    fn port_cache(&self) -> MutexGuard<'_, PortCache> {
+        self.port_cache
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the watched ports. Only used by the notification callbacks and
+    /// `AsyncClient::watch_port`.
+    pub fn port_watchers(&self) -> MutexGuard<'_, PortWatchers> {
+        self.port_watchers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns `true` if a handler has panicked within a callback.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    /// Returns `true` if JACK stopped calling the client, either because the process callback
+    /// reported failure or because JACK shut the client down.
+    pub fn has_stopped(&self) -> bool {
+        self.quit.load(Ordering::Acquire) || self.shutdown.is_stored()
+    }
+
+    /// Run the callback body `f`, returning `default` instead if the context is poisoned or if `f`
+    /// panics. Unwinding into JACK is undefined behavior so panics must not escape a callback.
+    fn guard<R, F: FnOnce() -> R>(&self, default: R, f: F) -> R {
+        if self.is_poisoned() {
+            return default;
+        }
+        match panic::catch_unwind(AssertUnwindSafe(f)) {
+            Ok(r) => r,
+            Err(_) => {
+                self.poisoned.store(true, Ordering::Release);
+                default
+            }
+        }
+    }
+
+    /// Like `guard`, for the notification callbacks that don't run on the process thread. With the
+    /// `tracing` feature, `f` runs within a span for `callback`, see
+    /// `Client::set_notification_span`.
+    #[inline(always)]
+    fn guard_notification<R, F: FnOnce() -> R>(
+        &self,
+        callback: &'static str,
+        default: R,
+        f: F,
+    ) -> R {
+        #[cfg(feature = "tracing")]
+        {
+            let parent = self.client.notification_span().and_then(tracing::Span::id);
+            let span =
+                tracing::debug_span!(target: "jack", parent: parent, "notification", callback);
+            span.in_scope(|| self.guard(default, f))
+        }
+        #[cfg(not(feature = "tracing"))]
+        {
+            let _ = callback;
+            self.guard(default, f)
+        }
+    }
+
+    /// Lock the notification handler. Notification callbacks are blocked while the guard is held.
+    pub fn notification_handler(&self) -> MutexGuard<'_, N> {
+        self.notification
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Lock the notification handler if it is not locked already, for the callbacks that must not
+    /// wait.
+    pub fn try_notification_handler(&self) -> Option<MutexGuard<'_, N>> {
+        match self.notification.try_lock() {
+            Ok(handler) => Some(handler),
+            Err(TryLockError::Poisoned(err)) => Some(err.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        }
+    }
+
+    /// Get the process handler.
+    ///
+    /// # Safety
+    /// Must only be called from the process thread, or while the client is not active.
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn process_handler(&self) -> &mut P {
+        &mut *self.process.get()
+    }
+
+    /// Clear the callbacks registered to the client, see `clear_callbacks`. This also clears the
+    /// callbacks that are only registered for some contexts.
+    ///
+    /// # Safety
+    /// See `clear_callbacks`.
+    pub unsafe fn clear_callbacks(&self) -> Result<(), Error> {
+        let client = self.client.raw();
+        let res = clear_callbacks(client);
+        #[cfg(feature = "metadata")]
+        {
+            if self.callbacks.contains(CallbackSelection::PROPERTY_CHANGE)
+                && !self
+                    .unavailable_callbacks
+                    .contains(CallbackSelection::PROPERTY_CHANGE)
+                && j::jack_set_property_change_callback(client, None, std::ptr::null_mut()) != 0
+            {
+                return Err(Error::CallbackDeregistrationError);
+            }
+        }
+        res
+    }
+
+    /// Consume the context, returning the client and both handlers.
+    pub fn into_parts(self) -> (Client, N, P) {
+        let notification = self
+            .notification
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner);
+        (self.client, notification, self.process.into_inner())
+    }
 }
 
 impl<N, P> CallbackContext<N, P>
 where
-    N: 'static + Send + Sync + NotificationHandler,
-    P: 'static + Send + ProcessHandler,
+    N: Send + Sync + NotificationHandler,
+    P: Send + ProcessHandler,
 {
-    pub unsafe fn from_raw<'a>(ptr: *mut libc::c_void) -> &'a mut CallbackContext<N, P> {
+    pub unsafe fn from_raw<'a>(ptr: *mut libc::c_void) -> &'a CallbackContext<N, P> {
         debug_assert!(!ptr.is_null());
-        let obj_ptr = ptr as *mut CallbackContext<N, P>;
-        &mut *obj_ptr
+        let obj_ptr = ptr as *const CallbackContext<N, P>;
+        &*obj_ptr
     }
 
     fn raw(b: &mut Box<Self>) -> *mut libc::c_void {
@@ -360,49 +913,372 @@ where
     ///
     /// This is mostly for use within the jack crate itself.
     ///
-    /// Returns `Ok(handler_ptr)` on success, or
-    /// `Err(Error::CallbackRegistrationError)` on failure.
+    /// Returns `Ok(())` on success, or `Err(Error::CallbackRegistrationError(name))` naming the
+    /// first callback that could not be registered. On failure, the callbacks that were
+    /// registered are cleared again. Optional callbacks that could not be registered are recorded
+    /// in `unavailable_callbacks` instead.
     ///
-    /// `handler_ptr` here is a pointer to a heap-allocated pair `(T, *mut
-    /// j::jack_client_t)`.
+    /// Registers `b` with JACK. All JACK calls to the client will be handled by the handlers in
+    /// `b`. JACK only holds a pointer to the boxed context, so the box must outlive the
+    /// registration. Once the client has been deactivated, `clear_callbacks` may be called after
+    /// which the box can be dropped.
     ///
-    /// Registers `handler` with JACK. All JACK calls to `client` will be handled by
-    /// `handler`. `handler` is consumed, but it is not deallocated. `handler`
-    /// should be manually
-    /// deallocated when JACK will no longer make calls to it, such as when
-    /// registering new callbacks
-    /// with the same client, or dropping the client.
+    /// Only the notification callbacks selected by `NotificationHandler::callbacks` are registered.
+    /// The shutdown, xrun, freewheel, sample rate, port registration and port connect callbacks
+    /// are always registered since the crate keeps track of them, as is the thread init callback if the
+    /// client has a `ThreadInitConfig`.
     ///
     /// # TODO
     ///
-    /// * Handled failed registrations
     /// * Fix `jack_set_port_rename_callback`
     ///
     /// # Unsafe
     ///
     /// * makes ffi calls
-    /// * `handler` will not be automatically deallocated.
+    /// * `b` must not be dropped while the callbacks are registered.
     pub unsafe fn register_callbacks(b: &mut Box<Self>) -> Result<(), Error> {
+        let res = Self::try_register_callbacks(b);
+        if res.is_err() {
+            // Don't leave JACK with pointers into `b`, which is dropped by the caller on failure.
+            let _ = b.clear_callbacks();
+        }
+        res
+    }
+
+    unsafe fn try_register_callbacks(b: &mut Box<Self>) -> Result<(), Error> {
+        let callbacks = b.notification_handler().callbacks();
+        b.callbacks = callbacks;
+        b.tracks_graph = b.client.graph_tracking_enabled() || callbacks.contains(GRAPH_CALLBACKS);
+        let graph_callbacks = match b.tracks_graph {
+            true => GRAPH_CALLBACKS,
+            false => CallbackSelection::empty(),
+        } | callbacks;
         let data_ptr = CallbackContext::raw(b);
         let client = b.client.raw();
-        j::jack_set_thread_init_callback(client, Some(thread_init_callback::<N, P>), data_ptr);
+        match b.process_thread {
+            Some(_) => check_registration(
+                "process_thread",
+                j::jack_set_process_thread(client, Some(process_thread::<N, P>), data_ptr),
+            )?,
+            None => check_registration(
+                "process",
+                j::jack_set_process_callback(client, Some(process::<N, P>), data_ptr),
+            )?,
+        }
+        check_registration(
+            "buffer_size",
+            j::jack_set_buffer_size_callback(client, Some(buffer_size::<N, P>), data_ptr),
+        )?;
+        if callbacks.contains(CallbackSelection::THREAD_INIT)
+            || b.client.thread_init_config().is_some()
+        {
+            check_registration(
+                "thread_init",
+                j::jack_set_thread_init_callback(
+                    client,
+                    Some(thread_init_callback::<N, P>),
+                    data_ptr,
+                ),
+            )?;
+        }
         j::jack_on_info_shutdown(client, Some(shutdown::<N, P>), data_ptr);
-        j::jack_set_process_callback(client, Some(process::<N, P>), data_ptr);
-        j::jack_set_freewheel_callback(client, Some(freewheel::<N, P>), data_ptr);
-        j::jack_set_buffer_size_callback(client, Some(buffer_size::<N, P>), data_ptr);
-        j::jack_set_sample_rate_callback(client, Some(sample_rate::<N, P>), data_ptr);
-        j::jack_set_client_registration_callback(
-            client,
-            Some(client_registration::<N, P>),
-            data_ptr,
-        );
-        j::jack_set_port_registration_callback(client, Some(port_registration::<N, P>), data_ptr);
+        check_registration(
+            "freewheel",
+            j::jack_set_freewheel_callback(client, Some(freewheel::<N, P>), data_ptr),
+        )?;
+        b.sample_rate
+            .store(j::jack_get_sample_rate(client), Ordering::Relaxed);
+        check_registration(
+            "sample_rate",
+            j::jack_set_sample_rate_callback(client, Some(sample_rate::<N, P>), data_ptr),
+        )?;
+        if graph_callbacks.contains(CallbackSelection::CLIENT_REGISTRATION) {
+            check_registration(
+                "client_registration",
+                j::jack_set_client_registration_callback(
+                    client,
+                    Some(client_registration::<N, P>),
+                    data_ptr,
+                ),
+            )?;
+        }
+        if graph_callbacks.contains(CallbackSelection::PORT_REGISTRATION) {
+            check_registration(
+                "port_registration",
+                j::jack_set_port_registration_callback(
+                    client,
+                    Some(port_registration::<N, P>),
+                    data_ptr,
+                ),
+            )?;
+        }
         // doesn't compile for testing since it is a weak export
         // j::jack_set_port_rename_callback(client, Some(port_rename::<N, P), data_ptr);
-        j::jack_set_port_connect_callback(client, Some(port_connect::<N, P>), data_ptr);
-        j::jack_set_graph_order_callback(client, Some(graph_order::<N, P>), data_ptr);
-        j::jack_set_xrun_callback(client, Some(xrun::<N, P>), data_ptr);
-        j::jack_set_latency_callback(client, Some(latency::<N, P>), data_ptr);
+        if callbacks.contains(CallbackSelection::PORT_RENAME) {
+            b.unavailable_callbacks |= CallbackSelection::PORT_RENAME;
+        }
+        if graph_callbacks.contains(CallbackSelection::PORTS_CONNECTED) {
+            check_registration(
+                "ports_connected",
+                j::jack_set_port_connect_callback(client, Some(port_connect::<N, P>), data_ptr),
+            )?;
+        }
+        if callbacks.contains(CallbackSelection::GRAPH_REORDER) {
+            check_registration(
+                "graph_reorder",
+                j::jack_set_graph_order_callback(client, Some(graph_order::<N, P>), data_ptr),
+            )?;
+        }
+        check_registration(
+            "xrun",
+            j::jack_set_xrun_callback(client, Some(xrun::<N, P>), data_ptr),
+        )?;
+        // Latency callbacks are not supported by older servers. Computing latencies is optional,
+        // so carry on without it.
+        if callbacks.contains(CallbackSelection::LATENCY)
+            && j::jack_set_latency_callback(client, Some(latency::<N, P>), data_ptr) != 0
+        {
+            b.unavailable_callbacks |= CallbackSelection::LATENCY;
+        }
+        // Metadata is not supported by older servers, and JACK only keeps one property change
+        // callback per client, which may already be taken by a `PropertyChangeHandler`.
+        #[cfg(feature = "metadata")]
+        {
+            if callbacks.contains(CallbackSelection::PROPERTY_CHANGE)
+                && (!crate::metadata::is_available()
+                    || b.client.has_property_change_handler()
+                    || j::jack_set_property_change_callback(
+                        client,
+                        Some(property_change::<N, P>),
+                        data_ptr,
+                    ) != 0)
+            {
+                b.unavailable_callbacks |= CallbackSelection::PROPERTY_CHANGE;
+            }
+        }
         Ok(())
     }
 }
+
+/// Turn the return code of registering the callback `name` into a `Result`.
+fn check_registration(name: &str, res: libc::c_int) -> Result<(), Error> {
+    match res {
+        0 => Ok(()),
+        _ => Err(Error::CallbackRegistrationError(name.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::mem;
+
+    #[derive(Default)]
+    struct NameRecorder {
+        names: Vec<String>,
+    }
+
+    impl NotificationHandler for NameRecorder {
+        fn shutdown(&mut self, _: ClientStatus, reason: &str) {
+            self.names.push(reason.to_string());
+        }
+
+        fn client_registration(&mut self, _: &Client, name: &str, _: bool) {
+            self.names.push(name.to_string());
+        }
+
+        fn port_rename(
+            &mut self,
+            _: &Client,
+            _: PortId,
+            old_name: &str,
+            new_name: &str,
+        ) -> Control {
+            self.names.push(old_name.to_string());
+            self.names.push(new_name.to_string());
+            Control::Continue
+        }
+    }
+
+    #[test]
+    fn callbacks_accept_non_utf8_strings() {
+        // "caf\xe9" is "café" in Latin-1, which is not valid UTF-8.
+        let latin1 = ffi::CString::new(&b"caf\xe9"[..]).unwrap();
+        let ascii = ffi::CString::new("cafe").unwrap();
+        let ctx = CallbackContext::new(
+            unsafe { Client::from_raw(std::ptr::null_mut()) },
+            NameRecorder::default(),
+            (),
+        );
+        let data = &ctx as *const CallbackContext<NameRecorder, ()> as *mut libc::c_void;
+        unsafe {
+            client_registration::<NameRecorder, ()>(latin1.as_ptr(), 1, data);
+            port_rename::<NameRecorder, ()>(0, latin1.as_ptr(), ascii.as_ptr(), data);
+            shutdown::<NameRecorder, ()>(0, latin1.as_ptr(), data);
+        }
+        assert!(!ctx.is_poisoned());
+        let (client, recorder, _) = ctx.into_parts();
+        // The client does not refer to a JACK client so it must not be closed.
+        mem::forget(client);
+        assert_eq!(
+            recorder.names,
+            vec!["caf\u{FFFD}", "caf\u{FFFD}", "cafe", "caf\u{FFFD}"]
+        );
+    }
+
+    #[test]
+    fn callbacks_skip_shutdown_of_locked_handler() {
+        let reason = ffi::CString::new("gone").unwrap();
+        let ctx = CallbackContext::new(
+            unsafe { Client::from_raw(std::ptr::null_mut()) },
+            NameRecorder::default(),
+            (),
+        );
+        let data = &ctx as *const CallbackContext<NameRecorder, ()> as *mut libc::c_void;
+        {
+            let _locked = ctx.notification_handler();
+            unsafe { shutdown::<NameRecorder, ()>(0, reason.as_ptr(), data) };
+        }
+        assert!(ctx.shutdown.is_stored());
+        let (client, recorder, _) = ctx.into_parts();
+        // The client does not refer to a JACK client so it must not be closed.
+        mem::forget(client);
+        assert!(recorder.names.is_empty());
+    }
+
+    #[test]
+    fn callbacks_name_clients_without_calling_the_server() {
+        let name = ffi::CString::new("other").unwrap();
+        // The client is null, so asking the server for the uuid of `other` would crash.
+        let ctx = CallbackContext::new(
+            unsafe { Client::from_raw(std::ptr::null_mut()) },
+            NameRecorder::default(),
+            (),
+        );
+        ctx.names.enable_unseeded();
+        let data = &ctx as *const CallbackContext<NameRecorder, ()> as *mut libc::c_void;
+        unsafe { client_registration::<NameRecorder, ()>(name.as_ptr(), 1, data) };
+        assert!(!ctx.is_poisoned());
+        assert_eq!(ctx.names.pending_clients(), ["other"]);
+        let (client, recorder, _) = ctx.into_parts();
+        // The client does not refer to a JACK client so it must not be closed.
+        mem::forget(client);
+        assert_eq!(recorder.names, ["other"]);
+    }
+
+    /// Records the spans and events with the target `jack`.
+    #[cfg(feature = "tracing")]
+    #[derive(Default)]
+    struct TraceRecorder {
+        records: Mutex<Vec<String>>,
+    }
+
+    /// Formats the fields of a span or an event.
+    #[cfg(feature = "tracing")]
+    struct Fields(String);
+
+    #[cfg(feature = "tracing")]
+    impl tracing::field::Visit for Fields {
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.push_str(&format!(" {}={:?}", field.name(), value));
+        }
+    }
+
+    #[cfg(feature = "tracing")]
+    impl tracing::Subscriber for TraceRecorder {
+        fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+            metadata.target() == "jack"
+        }
+
+        fn new_span(&self, span: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            let mut fields = Fields(format!("span {}", span.metadata().name()));
+            span.record(&mut fields);
+            let mut records = self.records.lock().unwrap();
+            records.push(fields.0);
+            tracing::span::Id::from_u64(records.len() as u64)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut fields = Fields("event".to_string());
+            event.record(&mut fields);
+            self.records.lock().unwrap().push(fields.0);
+        }
+
+        fn enter(&self, span: &tracing::span::Id) {
+            let record = format!("enter {}", span.into_u64());
+            self.records.lock().unwrap().push(record);
+        }
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn callbacks_trace_notifications_and_shutdown() {
+        let name = ffi::CString::new("other").unwrap();
+        let reason = ffi::CString::new("gone").unwrap();
+        let ctx = CallbackContext::new(
+            unsafe { Client::from_raw(std::ptr::null_mut()) },
+            NameRecorder::default(),
+            (),
+        );
+        let data = &ctx as *const CallbackContext<NameRecorder, ()> as *mut libc::c_void;
+        let recorder = Arc::new(TraceRecorder::default());
+        tracing::subscriber::with_default(recorder.clone(), || unsafe {
+            client_registration::<NameRecorder, ()>(name.as_ptr(), 1, data);
+            shutdown::<NameRecorder, ()>(0, reason.as_ptr(), data);
+        });
+        let (client, _, _) = ctx.into_parts();
+        // The client does not refer to a JACK client so it must not be closed.
+        mem::forget(client);
+        assert_eq!(
+            *recorder.records.lock().unwrap(),
+            vec![
+                "span notification callback=\"client_registration\"",
+                "enter 1",
+                "event message=client was shut down status=(empty) reason=gone",
+            ]
+        );
+    }
+
+    /// Records the sample rate of the process scopes.
+    #[derive(Default)]
+    struct SampleRateRecorder {
+        sample_rates: Vec<usize>,
+    }
+
+    impl ProcessHandler for SampleRateRecorder {
+        fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+            // Only fills the capacity reserved by the test, so it does not allocate.
+            self.sample_rates.push(ps.sample_rate());
+            Control::Continue
+        }
+    }
+
+    #[test]
+    fn callbacks_update_sample_rate_of_process_scope() {
+        let mut recorder = SampleRateRecorder::default();
+        recorder.sample_rates.reserve(2);
+        let ctx = CallbackContext::new(
+            unsafe { Client::from_raw(std::ptr::null_mut()) },
+            (),
+            recorder,
+        );
+        ctx.sample_rate.store(48000, Ordering::Relaxed);
+        let data = &ctx as *const CallbackContext<(), SampleRateRecorder> as *mut libc::c_void;
+        unsafe {
+            process::<(), SampleRateRecorder>(16, data);
+            sample_rate::<(), SampleRateRecorder>(44100, data);
+            process::<(), SampleRateRecorder>(16, data);
+        }
+        assert!(!ctx.is_poisoned());
+        let (client, _, recorder) = ctx.into_parts();
+        // The client does not refer to a JACK client so it must not be closed.
+        mem::forget(client);
+        assert_eq!(recorder.sample_rates, [48000, 44100]);
+    }
+}
@@ -1,16 +1,36 @@
 use jack_sys as j;
+use std::any::Any;
+use std::borrow::Cow;
 use std::sync::Arc;
-use std::{ffi, fmt, ptr};
+use std::{ffi, fmt, mem, ptr, thread};
 
+use crate::client::async_client::ActiveScope;
 use crate::client::common::{sleep_on_test, CREATE_OR_DESTROY_CLIENT_MUTEX};
-use crate::jack_utils::collect_strs;
+use crate::client::retry::retry_open;
+use crate::jack_utils::{collect_strs, to_cstring};
+use crate::logging;
 use crate::properties::PropertyChangeHandler;
+use crate::test_support::{MockMidiBuffer, MockPorts};
+#[cfg(not(target_os = "windows"))]
+use crate::thread::RtThreadHandle;
+use crate::thread::ThreadInitConfig;
 use crate::transport::Transport;
+use crate::units;
+use crate::Uuid;
 use crate::{
-    AsyncClient, ClientOptions, ClientStatus, Error, Frames, NotificationHandler, Port, PortFlags,
-    PortId, PortSpec, ProcessHandler, Time, Unowned,
+    AsyncClient, ClientOptions, ClientState, ClientStatus, ConnectionGuard, Error, Frames,
+    NotificationHandler, OwnedPort, Port, PortConnections, PortErrorDetails, PortErrorKind,
+    PortFlags, PortId, PortNames, PortSpec, ProcessHandler, ProcessThreadContext, Time, Unowned,
+    WeakClient, PORT_NAME_SIZE,
 };
 
+/// The smallest buffer size that `Client::set_buffer_size` requests.
+pub const MIN_BUFFER_SIZE: Frames = 16;
+
+/// The largest buffer size that `Client::set_buffer_size` requests, which is the largest that
+/// JACK2 supports.
+pub const MAX_BUFFER_SIZE: Frames = 8192;
+
 /// A client to interact with a JACK server.
 ///
 /// # Example
@@ -26,40 +46,155 @@ use crate::{
 ///     Err(e) => println!("Failed to open client because of error: {:?}", e),
 /// };
 /// ```
-pub struct Client(
-    *mut j::jack_client_t,
-    Arc<()>,
-    Option<Box<dyn PropertyChangeHandler>>,
-);
+pub struct Client {
+    raw: *mut j::jack_client_t,
+    // Kept alive by queries of `WeakClient`s, which the client waits for when it is dropped.
+    life: Arc<()>,
+    // The property change handler, if any, see `Client::register_property_change_handler`.
+    #[cfg_attr(not(feature = "metadata"), allow(dead_code))]
+    property_change_handler: Option<Box<dyn PropertyChangeHandler>>,
+    thread_init_config: Option<ThreadInitConfig>,
+    // The parent of the spans around the notification callbacks, see
+    // `Client::set_notification_span`.
+    #[cfg(feature = "tracing")]
+    notification_span: Option<tracing::Span>,
+    timing_stats_enabled: bool,
+    graph_tracking_enabled: bool,
+    // The session handler, if any, see `Client::register_session_handler`. It is only kept to be
+    // dropped with the client.
+    #[cfg_attr(not(feature = "session"), allow(dead_code))]
+    session_handler: Option<Box<dyn Any + Send>>,
+    // Whether the client is active, set by `AsyncClient`.
+    active: bool,
+    // The timebase handler, if any, see `Client::register_timebase_handler`. It is only kept to be
+    // dropped with the client.
+    timebase_handler: Option<Box<dyn Any + Send>>,
+}
 
 unsafe impl Send for Client {}
 
 impl Client {
     /// Opens a JACK client with the given name and options. If the client is successfully opened,
     /// then `Ok(client)` is returned. If there is a failure, then `Err(Error::ClientError(status))`
-    /// will be returned.
+    /// will be returned, `ClientStatus::describe` explains the failure.
     ///
     /// Although the client may be successful in opening, there still may be some errors minor
     /// errors when attempting to opening. To access these, check the returned `ClientStatus`.
+    ///
+    /// `ClientOptions::SERVER_NAME` and `ClientOptions::SESSION_ID` are ignored, clients are opened
+    /// on a named server with `Client::new_with_server_name`, and session clients with
+    /// `Client::new_with_session_uuid` when the `session` feature is enabled.
     pub fn new(client_name: &str, options: ClientOptions) -> Result<(Self, ClientStatus), Error> {
+        Client::open(client_name, options, None, None)
+    }
+
+    /// Open a client like `Client::new`, on the JACK server named `server_name` instead of the
+    /// default server.
+    pub fn new_with_server_name(
+        client_name: &str,
+        server_name: &str,
+        options: ClientOptions,
+    ) -> Result<(Self, ClientStatus), Error> {
+        let server_name = to_cstring(server_name)?;
+        Client::open(client_name, options, Some(&server_name), None)
+    }
+
+    /// Open a client like `Client::new`, trying again every `interval` while the failure is
+    /// transient, such as the JACK server not running yet, for up to `deadline`. See
+    /// `ClientStatus::is_transient`.
+    ///
+    /// Failures that won't improve, like a name conflict with `ClientOptions::USE_EXACT_NAME`, are
+    /// returned right away. If the deadline passes, the error of the last attempt is returned with
+    /// its status. Use `ClientOptions::NO_START_SERVER` to wait for a server that is started
+    /// elsewhere, such as at login.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// let (client, _status) = jack::Client::open_with_retry(
+    ///     "autostarted",
+    ///     jack::ClientOptions::NO_START_SERVER,
+    ///     Duration::from_secs(30),
+    ///     Duration::from_millis(500),
+    /// )
+    /// .unwrap();
+    /// ```
+    pub fn open_with_retry(
+        client_name: &str,
+        options: ClientOptions,
+        deadline: std::time::Duration,
+        interval: std::time::Duration,
+    ) -> Result<(Self, ClientStatus), Error> {
+        retry_open(deadline, interval, || {
+            Client::open(client_name, options, None, None)
+        })
+    }
+
+    /// Open a client, passing `server_name` and `session_uuid` to JACK along with
+    /// `ClientOptions::SERVER_NAME` and `ClientOptions::SESSION_ID`.
+    pub(crate) fn open(
+        client_name: &str,
+        mut options: ClientOptions,
+        server_name: Option<&ffi::CStr>,
+        session_uuid: Option<&ffi::CStr>,
+    ) -> Result<(Self, ClientStatus), Error> {
         let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
         sleep_on_test();
         let mut status_bits = 0;
+        logging::init();
+        // JACK reads the server name and the uuid from the arguments whenever the options are set.
+        options.remove(ClientOptions::SERVER_NAME | ClientOptions::SESSION_ID);
+        let client_name_c = to_cstring(client_name)?;
+        let name = client_name_c.as_ptr();
         let client = unsafe {
-            let client_name = ffi::CString::new(client_name).unwrap();
-            j::jack_client_open(client_name.as_ptr(), options.bits(), &mut status_bits)
+            match (server_name, session_uuid) {
+                (None, None) => j::jack_client_open(name, options.bits(), &mut status_bits),
+                (Some(server), None) => j::jack_client_open(
+                    name,
+                    (options | ClientOptions::SERVER_NAME).bits(),
+                    &mut status_bits,
+                    server.as_ptr(),
+                ),
+                (None, Some(uuid)) => j::jack_client_open(
+                    name,
+                    (options | ClientOptions::SESSION_ID).bits(),
+                    &mut status_bits,
+                    uuid.as_ptr(),
+                ),
+                // JACK reads the server name before the uuid.
+                (Some(server), Some(uuid)) => j::jack_client_open(
+                    name,
+                    (options | ClientOptions::SERVER_NAME | ClientOptions::SESSION_ID).bits(),
+                    &mut status_bits,
+                    server.as_ptr(),
+                    uuid.as_ptr(),
+                ),
+            }
         };
         sleep_on_test();
-        let status = ClientStatus::from_bits(status_bits).unwrap_or_else(ClientStatus::empty);
+        // Keep the known flags even if JACK sets flags that this crate does not know about.
+        let status = ClientStatus::from_bits_truncate(status_bits);
         if client.is_null() {
+            logging::log_event!(
+                error,
+                "failed to open client {}: {}",
+                client_name,
+                status.describe()
+            );
             Err(Error::ClientError(status))
         } else {
-            Ok((Client(client, Arc::default(), None), status))
+            logging::log_event!(debug, "opened client {}", client_name);
+            Ok((unsafe { Client::from_raw(client) }, status))
         }
     }
 
     /// Begin processing in real-time using the specified `NotificationHandler` and
     /// `ProcessHandler`.
+    ///
+    /// The handlers may be boxed trait objects, such as `Box<dyn NotificationHandler + Sync>` and
+    /// `Box<dyn ProcessHandler>`, to choose them at runtime. This costs an extra indirect call per
+    /// callback.
     pub fn activate_async<N, P>(
         self,
         notification_handler: N,
@@ -72,6 +207,86 @@ impl Client {
         AsyncClient::new(self, notification_handler, process_handler)
     }
 
+    /// Begin processing in real-time on a thread that runs `f`, which drives the process cycles
+    /// itself with `ProcessThreadContext::cycle_wait` and `ProcessThreadContext::cycle_signal`.
+    ///
+    /// This is an alternative to the process handler for engines that own their processing loop,
+    /// the two can't be combined. The thread is created by JACK and has the same scheduling setup
+    /// as a process callback. The `NotificationHandler` is used as with `activate_async`.
+    ///
+    /// `f` should keep processing cycles until `cycle_wait` returns `None`, which happens when the
+    /// client is deactivated. JACK stops processing the client once `f` returns. Deactivation
+    /// waits up to a second for `f` to return.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let (client, _status) =
+    ///     jack::Client::new("pull", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let active_client = client
+    ///     .activate_thread((), |mut ctx: jack::ProcessThreadContext| {
+    ///         while let Some(ps) = ctx.cycle_wait() {
+    ///             let _n_frames = ps.n_frames();
+    ///             ctx.cycle_signal(jack::Control::Continue);
+    ///         }
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn activate_thread<N, F>(
+        self,
+        notification_handler: N,
+        f: F,
+    ) -> Result<AsyncClient<N, ()>, Error>
+    where
+        N: 'static + Send + Sync + NotificationHandler,
+        F: 'static + Send + FnOnce(ProcessThreadContext),
+    {
+        AsyncClient::with_process_thread(self, notification_handler, Box::new(f))
+    }
+
+    /// Begin processing in real-time with handlers that may borrow data, for the duration of `f`.
+    ///
+    /// This works like `std::thread::scope`: the client is deactivated and its callbacks are torn
+    /// down before `activate_scoped` returns, even if `f` panics, so the handlers may borrow from
+    /// the caller's stack, for example a large sample bank that is allocated up front. `f` gets
+    /// access to the active client, but can't deactivate it.
+    ///
+    /// Returns the client along with the result of `f` once it is deactivated. Activation fails in
+    /// the same way as with `activate_async`. If the client can't be deactivated JACK may still
+    /// call into the handlers, so the process is aborted.
+    ///
+    /// # Example
+    /// ```
+    /// let (client, _status) =
+    ///     jack::Client::new("scoped", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let samples = vec![0.0f32; 48000];
+    /// let process = jack::ClosureProcessHandler::new(|_: &jack::Client, _: &jack::ProcessScope| {
+    ///     let _loudest = samples.iter().fold(0.0f32, |a, b| a.max(b.abs()));
+    ///     jack::Control::Continue
+    /// });
+    /// let (_client, ()) = client
+    ///     .activate_scoped((), process, |active| {
+    ///         println!("{} is running", active.as_client().name());
+    ///     })
+    ///     .unwrap();
+    /// ```
+    pub fn activate_scoped<N, P, F, R>(
+        self,
+        notification_handler: N,
+        process_handler: P,
+        f: F,
+    ) -> Result<(Client, R), Error>
+    where
+        N: Send + Sync + NotificationHandler,
+        P: Send + ProcessHandler,
+        F: FnOnce(&AsyncClient<N, P>) -> R,
+    {
+        // The scope is dropped before returning, which deactivates the client even if `f` panics.
+        let scope = unsafe { ActiveScope::new(self, notification_handler, process_handler)? };
+        let res = f(scope.async_client());
+        let (client, _, _) = scope.deactivate()?;
+        Ok((client, res))
+    }
+
     /// The sample rate of the JACK system, as set by the user when jackd was
     /// started.
     pub fn sample_rate(&self) -> usize {
@@ -89,20 +304,115 @@ impl Client {
         load as f32
     }
 
+    /// The id of the thread that runs the process callback, or `None` if the client is not active.
+    #[cfg(not(target_os = "windows"))]
+    pub fn thread_id(&self) -> Option<libc::pthread_t> {
+        let id = unsafe { j::jack_client_thread_id(self.raw()) };
+        let none: libc::pthread_t = unsafe { mem::zeroed() };
+        if id == none {
+            None
+        } else {
+            Some(id)
+        }
+    }
+
+    /// Returns `true` if the JACK server runs the process thread with real-time scheduling.
+    pub fn is_realtime(&self) -> bool {
+        unsafe { j::jack_is_realtime(self.raw()) != 0 }
+    }
+
+    /// The priority of the process thread, or `None` if the client is not running in real-time.
+    pub fn real_time_priority(&self) -> Option<i32> {
+        match unsafe { j::jack_client_real_time_priority(self.raw()) } {
+            p if p < 0 => None,
+            p => Some(p),
+        }
+    }
+
+    /// The maximum priority that threads of this client may use, or `None` if the client is not
+    /// running in real-time.
+    pub fn max_real_time_priority(&self) -> Option<i32> {
+        match unsafe { j::jack_client_max_real_time_priority(self.raw()) } {
+            p if p < 0 => None,
+            p => Some(p),
+        }
+    }
+
+    /// Run `f` on a new thread that is created by JACK, with the same scheduling setup as the
+    /// client's own threads. This is the conventional way of running helper threads, such as disk
+    /// streaming threads, that have to keep up with the process thread.
+    ///
+    /// If `realtime` is `true`, the thread runs with real-time scheduling at `priority`. If
+    /// `priority` is `None`, it runs one step below the process thread so that it never preempts
+    /// it.
+    ///
+    /// `Err(Error::ThreadCreationError)` is returned if the thread could not be created, in which
+    /// case `f` is dropped.
+    #[cfg(not(target_os = "windows"))]
+    pub fn spawn_rt_thread<F>(
+        &self,
+        priority: Option<i32>,
+        realtime: bool,
+        f: F,
+    ) -> Result<RtThreadHandle, Error>
+    where
+        F: 'static + Send + FnOnce(),
+    {
+        let priority = priority
+            .or_else(|| self.real_time_priority().map(|p| (p - 1).max(1)))
+            .unwrap_or(0);
+        crate::thread::spawn_rt_thread(self, Arc::downgrade(&self.life), priority, realtime, f)
+    }
+
+    /// The delay in microseconds that caused the most recent xrun.
+    pub fn xrun_delayed_usecs(&self) -> f32 {
+        unsafe { j::jack_get_xrun_delayed_usecs(self.raw()) }
+    }
+
+    /// The maximum delay in microseconds reported by the backend since the last call to
+    /// `reset_max_delayed_usecs`.
+    pub fn max_delayed_usecs(&self) -> f32 {
+        unsafe { j::jack_get_max_delayed_usecs(self.raw()) }
+    }
+
+    /// Reset the maximum delay reported by `max_delayed_usecs`.
+    pub fn reset_max_delayed_usecs(&self) {
+        unsafe { j::jack_reset_max_delayed_usecs(self.raw()) }
+    }
+
     /// Get the name of the current client. This may differ from the name requested by `Client::new`
     /// as JACK will may rename a client if necessary (ie: name collision, name too long). The name
     /// will only the be different than the one passed to `Client::new` if the `ClientStatus` was
     /// `NAME_NOT_UNIQUE`.
+    ///
+    /// The name is requested as a `&str`, so it is valid UTF-8 unless JACK renamed the client to
+    /// something that is not. In that case only the valid start of the name is returned, see
+    /// `Client::name_lossy`.
     pub fn name(&self) -> &str {
-        unsafe {
-            let ptr = j::jack_get_client_name(self.raw());
-            let cstr = ffi::CStr::from_ptr(ptr);
-            cstr.to_str().unwrap()
+        let name = self.name_cstr().to_bytes();
+        match std::str::from_utf8(name) {
+            Ok(name) => name,
+            Err(err) => unsafe { std::str::from_utf8_unchecked(&name[..err.valid_up_to()]) },
         }
     }
 
+    /// Get the name of the current client like `Client::name`, with invalid UTF-8 sequences
+    /// replaced by `U+FFFD REPLACEMENT CHARACTER`.
+    pub fn name_lossy(&self) -> Cow<'_, str> {
+        self.name_cstr().to_string_lossy()
+    }
+
+    fn name_cstr(&self) -> &ffi::CStr {
+        unsafe { ffi::CStr::from_ptr(j::jack_get_client_name(self.raw())) }
+    }
+
     /// The current maximum size that will every be passed to the process
     /// callback.
+    ///
+    /// With some JACK implementations, such as PipeWire, this is only an upper bound, and process
+    /// cycles may be smaller without a call to the buffer size callback. See
+    /// `ServerQuirks::buffer_size_can_change_without_callback`, and use `ProcessScope::n_frames`
+    /// in the process callback.
     pub fn buffer_size(&self) -> Frames {
         unsafe { j::jack_get_buffer_size(self.raw()) }
     }
@@ -112,7 +422,20 @@ impl Client {
     /// This operation stops the JACK engine process cycle, then calls all registered buffer size
     /// callback functions before restarting the process cycle. This will cause a gap in the audio
     /// flow, so it should only be done at appropriate stopping points.
+    ///
+    /// `n_frames` must be a power of two from `MIN_BUFFER_SIZE` to `MAX_BUFFER_SIZE`, otherwise
+    /// `Error::InvalidBufferSize` is returned without asking the server. The server may still
+    /// reject sizes that its driver does not support, which results in
+    /// `Error::SetBufferSizeError`. The buffer size callbacks run after this returns, see
+    /// `AsyncClient::set_buffer_size_sync` to wait for them.
     pub fn set_buffer_size(&self, n_frames: Frames) -> Result<(), Error> {
+        if !n_frames.is_power_of_two() || !(MIN_BUFFER_SIZE..=MAX_BUFFER_SIZE).contains(&n_frames) {
+            return Err(Error::InvalidBufferSize(
+                n_frames,
+                MIN_BUFFER_SIZE,
+                MAX_BUFFER_SIZE,
+            ));
+        }
         let res = unsafe { j::jack_set_buffer_size(self.raw(), n_frames) };
         match res {
             0 => Ok(()),
@@ -120,21 +443,14 @@ impl Client {
         }
     }
 
-    /// Get the numeric `uuid` of this client.
+    /// Get the `uuid` of this client.
     ///
     /// # Remarks
     ///
-    /// * Deallocates, not realtime safe.
+    /// * Allocates & deallocates, not realtime safe.
     #[cfg(feature = "metadata")]
-    pub fn uuid(&self) -> j::jack_uuid_t {
-        unsafe {
-            let mut uuid: j::jack_uuid_t = Default::default();
-            let uuid_s = j::jack_client_get_uuid(self.raw());
-            assert!(!uuid_s.is_null());
-            assert_eq!(0, j::jack_uuid_parse(uuid_s, &mut uuid));
-            j::jack_free(uuid_s as _);
-            uuid
-        }
+    pub fn uuid(&self) -> Uuid {
+        Uuid::parse(&self.uuid_string()).expect("JACK returned an invalid client uuid")
     }
 
     /// Get a String representation of the `uuid` of this client.
@@ -146,10 +462,7 @@ impl Client {
         unsafe {
             let uuid_s = j::jack_client_get_uuid(self.raw());
             assert!(!uuid_s.is_null());
-            let uuid = ffi::CStr::from_ptr(uuid_s)
-                .to_str()
-                .expect("uuid is valid string")
-                .to_string();
+            let uuid = ffi::CStr::from_ptr(uuid_s).to_string_lossy().into_owned();
             j::jack_free(uuid_s as _);
             uuid
         }
@@ -161,28 +474,19 @@ impl Client {
         if name_ptr.is_null() {
             None
         } else {
-            Some(
-                ffi::CStr::from_ptr(name_ptr)
-                    .to_str()
-                    .expect("name convert to a valid str")
-                    .to_string(),
-            )
+            Some(ffi::CStr::from_ptr(name_ptr).to_string_lossy().into_owned())
         }
     }
 
-    /// Get the name of a client by its numeric uuid.
+    /// Get the name of a client by its `uuid`.
     #[cfg(feature = "metadata")]
-    pub fn name_by_uuid(&self, uuid: j::jack_uuid_t) -> Option<String> {
-        let mut uuid_s = ['\0' as _; 37]; //jack_uuid_unparse expects an array of length 37
-        unsafe {
-            j::jack_uuid_unparse(uuid, uuid_s.as_mut_ptr());
-            self.name_by_uuid_raw(uuid_s.as_ptr())
-        }
+    pub fn name_by_uuid(&self, uuid: Uuid) -> Option<String> {
+        self.name_by_uuid_str(&uuid.to_string())
     }
 
     /// Get the name of a client by its `&str` uuid.
     pub fn name_by_uuid_str(&self, uuid: &str) -> Option<String> {
-        let uuid = ffi::CString::new(uuid).unwrap();
+        let uuid = to_cstring(uuid).ok()?;
         unsafe { self.name_by_uuid_raw(uuid.as_ptr()) }
     }
 
@@ -198,14 +502,21 @@ impl Client {
     ///
     /// `flags` - A value used to select ports by their flags. Use `PortFlags::empty()` for no flag
     /// selection.
+    ///
+    /// Port names and types can't contain NUL bytes, so no ports match a pattern that does.
     pub fn ports(
         &self,
         port_name_pattern: Option<&str>,
         type_name_pattern: Option<&str>,
         flags: PortFlags,
     ) -> Vec<String> {
-        let pnp = ffi::CString::new(port_name_pattern.unwrap_or("")).unwrap();
-        let tnp = ffi::CString::new(type_name_pattern.unwrap_or("")).unwrap();
+        let (pnp, tnp) = match (
+            to_cstring(port_name_pattern.unwrap_or("")),
+            to_cstring(type_name_pattern.unwrap_or("")),
+        ) {
+            (Ok(pnp), Ok(tnp)) => (pnp, tnp),
+            _ => return Vec::new(),
+        };
         let flags = libc::c_ulong::from(flags.bits());
         unsafe {
             let ports = j::jack_get_ports(self.raw(), pnp.as_ptr(), tnp.as_ptr(), flags);
@@ -213,6 +524,100 @@ impl Client {
         }
     }
 
+    /// Like `ports`, but the names are borrowed from JACK's array instead of being copied into
+    /// `String`s, which saves an allocation per port when listing ports often. See `PortNames`.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let (client, _status) =
+    ///     jack::Client::new("port_names", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let physical = client.port_names(None, None, jack::PortFlags::IS_PHYSICAL);
+    /// for name in &physical {
+    ///     println!("{}", name);
+    /// }
+    /// ```
+    pub fn port_names(
+        &self,
+        port_name_pattern: Option<&str>,
+        type_name_pattern: Option<&str>,
+        flags: PortFlags,
+    ) -> PortNames {
+        let (pnp, tnp) = match (
+            to_cstring(port_name_pattern.unwrap_or("")),
+            to_cstring(type_name_pattern.unwrap_or("")),
+        ) {
+            (Ok(pnp), Ok(tnp)) => (pnp, tnp),
+            _ => return unsafe { PortNames::from_raw(ptr::null_mut()) },
+        };
+        let flags = libc::c_ulong::from(flags.bits());
+        unsafe {
+            let ports = j::jack_get_ports(self.raw(), pnp.as_ptr(), tnp.as_ptr(), flags);
+            PortNames::from_raw(ports)
+        }
+    }
+
+    /// The names of the clients that have ports, in the order of their first port. Clients
+    /// without ports can't be listed, but `is_client_registered` tells if a client exists.
+    ///
+    /// Client and port names may contain colons, so full port names are split using the short
+    /// name of the port rather than at the first colon.
+    pub fn client_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for (client_name, _) in self.ports_with_client_names() {
+            if !names.contains(&client_name) {
+                names.push(client_name);
+            }
+        }
+        names
+    }
+
+    /// The ports of the client named `client_name`, in the order JACK lists them, which is usually
+    /// the order in which they were registered. Empty if the client does not exist or has no
+    /// ports.
+    pub fn ports_of_client(&self, client_name: &str) -> Vec<Port<Unowned>> {
+        self.ports_with_client_names()
+            .into_iter()
+            .filter(|(name, _)| name == client_name)
+            .map(|(_, port)| port)
+            .collect()
+    }
+
+    /// Returns `true` if a client named `client_name` is open on the JACK server, even if it has
+    /// no ports.
+    pub fn is_client_registered(&self, client_name: &str) -> bool {
+        let client_name = match to_cstring(client_name) {
+            Ok(client_name) => client_name,
+            Err(_) => return false,
+        };
+        unsafe {
+            let uuid = j::jack_get_uuid_for_client_name(self.raw(), client_name.as_ptr());
+            if uuid.is_null() {
+                return false;
+            }
+            j::jack_free(uuid as _);
+            true
+        }
+    }
+
+    /// The uuid of the client named `client_name`, if it is open.
+    pub(crate) fn uuid_by_name(&self, client_name: &str) -> Option<Uuid> {
+        unsafe { uuid_by_name(self.raw(), client_name) }
+    }
+
+    /// All ports along with the names of their clients.
+    fn ports_with_client_names(&self) -> Vec<(String, Port<Unowned>)> {
+        self.ports(None, None, PortFlags::empty())
+            .into_iter()
+            .filter_map(|name| {
+                let port = self.port_by_name(&name)?;
+                let short_name = port.short_name().ok()?;
+                // The full name is the client name and the short name joined by a colon.
+                let client_name = name.strip_suffix(short_name.as_str())?.strip_suffix(':')?;
+                Some((client_name.to_string(), port))
+            })
+            .collect()
+    }
+
     /// Create a new port for the client. This is an object used for moving data of any type in or
     /// out of the client. Ports may be connected in various ways.
     ///
@@ -222,7 +627,7 @@ impl Client {
     /// Each port has a short name. The port's full name contains the name of the client
     /// concatenated with a colon (:) followed by its short name. `Port::name_size()` is the maximum
     /// length of the full name. Exceeding that will cause the port registration to fail and return
-    /// `Err(())`.
+    /// a `PortRegistrationError`, see `PortErrorDetails` for why it failed.
     ///
     /// The `port_name` must be unique among all ports owned by this client. If the name is not
     /// unique, the registration will fail.
@@ -231,11 +636,11 @@ impl Client {
         port_name: &str,
         port_spec: PS,
     ) -> Result<Port<PS>, Error> {
-        let port_name_c = ffi::CString::new(port_name).unwrap();
-        let port_type_c = ffi::CString::new(port_spec.jack_port_type()).unwrap();
+        let port_name_c = to_cstring(port_name)?;
+        let port_type_c = to_cstring(port_spec.jack_port_type())?;
         let port_flags = port_spec.jack_flags().bits();
         let buffer_size = port_spec.jack_buffer_size();
-        let pp = unsafe {
+        let (pp, message) = logging::capture_errors(|| unsafe {
             j::jack_port_register(
                 self.raw(),
                 port_name_c.as_ptr(),
@@ -243,32 +648,57 @@ impl Client {
                 libc::c_ulong::from(port_flags),
                 buffer_size,
             )
-        };
+        });
         if pp.is_null() {
-            Err(Error::PortRegistrationError(port_name.to_string()))
+            let details = PortErrorDetails {
+                kind: self.diagnose_registration(port_name),
+                code: None,
+                message,
+            };
+            logging::log_event!(
+                debug,
+                "failed to register port {}: {:?}",
+                port_name,
+                details
+            );
+            Err(Error::PortRegistrationError(port_name.to_string(), details))
         } else {
-            Ok(unsafe { Port::from_raw(port_spec, self.raw(), pp, Arc::downgrade(&self.1)) })
+            logging::log_event!(debug, "registered port {}", port_name);
+            Ok(unsafe { Port::from_raw(port_spec, self.raw(), pp, Arc::downgrade(&self.life)) })
         }
     }
 
+    /// Register a port like `register_port`, which is unregistered when the returned `OwnedPort`
+    /// is dropped.
+    pub fn register_port_owned<PS: PortSpec>(
+        &self,
+        port_name: &str,
+        port_spec: PS,
+    ) -> Result<OwnedPort<PS>, Error> {
+        self.register_port(port_name, port_spec).map(OwnedPort::new)
+    }
+
     /// Get a `Port` by its port id.
     pub fn port_by_id(&self, port_id: PortId) -> Option<Port<Unowned>> {
         let pp = unsafe { j::jack_port_by_id(self.raw(), port_id) };
         if pp.is_null() {
             None
         } else {
-            Some(unsafe { Port::from_raw(Unowned {}, self.raw(), pp, Arc::downgrade(&self.1)) })
+            Some(unsafe { Port::from_raw(Unowned {}, self.raw(), pp, Arc::downgrade(&self.life)) })
         }
     }
 
     /// Get a `Port` by its port name.
     pub fn port_by_name(&self, port_name: &str) -> Option<Port<Unowned>> {
-        let port_name = ffi::CString::new(port_name).unwrap();
+        self.port_by_name_cstr(&to_cstring(port_name).ok()?)
+    }
+
+    fn port_by_name_cstr(&self, port_name: &ffi::CStr) -> Option<Port<Unowned>> {
         let pp = unsafe { j::jack_port_by_name(self.raw(), port_name.as_ptr()) };
         if pp.is_null() {
             None
         } else {
-            Some(unsafe { Port::from_raw(Unowned {}, self.raw(), pp, Arc::downgrade(&self.1)) })
+            Some(unsafe { Port::from_raw(Unowned {}, self.raw(), pp, Arc::downgrade(&self.life)) })
         }
     }
 
@@ -310,6 +740,17 @@ impl Client {
         matches!(unsafe { j::jack_port_is_mine(self.raw(), port.raw()) }, 1)
     }
 
+    /// The names and ports of this client that have all of `flags`.
+    fn own_ports(&self, flags: PortFlags) -> Vec<(String, Port<Unowned>)> {
+        self.ports(None, None, flags)
+            .into_iter()
+            .filter_map(|name| {
+                let port = self.port_by_name(&name)?;
+                self.is_mine(&port).then_some((name, port))
+            })
+            .collect()
+    }
+
     /// Toggle input monitoring for the port with name `port_name`.
     ///
     /// `Err(Error::PortMonitorError)` is returned on failure.
@@ -320,7 +761,7 @@ impl Client {
         port_name: &str,
         enable_monitor: bool,
     ) -> Result<(), Error> {
-        let port_name_cstr = ffi::CString::new(port_name).unwrap();
+        let port_name_cstr = to_cstring(port_name)?;
         let res = unsafe {
             j::jack_port_request_monitor_by_name(
                 self.raw(),
@@ -334,37 +775,61 @@ impl Client {
         }
     }
 
+    /// Turn input monitoring on or off for all the ports of this client that have the
+    /// `CAN_MONITOR` flag, like `Port::ensure_monitor`. Returns the number of ports.
+    ///
+    /// All the ports are tried. If some fail, `Err(Error::PortMonitorErrors(names))` is returned
+    /// with the full names of the ports that failed.
+    pub fn set_monitoring_all(&self, enable: bool) -> Result<usize, Error> {
+        let mut count = 0;
+        let mut failed = Vec::new();
+        for (name, port) in self.own_ports(PortFlags::CAN_MONITOR) {
+            match port.ensure_monitor(enable) {
+                Ok(()) => count += 1,
+                Err(_) => failed.push(name),
+            }
+        }
+        match failed.is_empty() {
+            true => Ok(count),
+            false => Err(Error::PortMonitorErrors(failed)),
+        }
+    }
+
     // TODO implement
     // /// Start/Stop JACK's "freewheel" mode.
     // ///
-    // /// When in "freewheel" mode, JACK no longer waits for any external event to
-    // /// begin the start of the next process cycle. As a result, freewheel mode
-    // /// causes "faster than real-time" execution of a JACK graph. If possessed,
-    // /// real-time scheduling is dropped when entering freewheel mode, and if
-    // /// appropriate it is reacquired when stopping.
-    // ///
-    // /// IMPORTANT: on systems using capabilities to provide real-time scheduling
-    // /// (i.e. Linux Kernel 2.4), if enabling freewheel, this function must be
-    // /// called from the thread that originally called `self.activate()`. This
-    // /// restriction does not apply to other systems (e.g. Linux Kernel 2.6 or OS
-    // /// X).
-    // pub pub fn set_freewheel(&self, enable: bool) -> Result<(), Error> {
-    //     let onoff = match enable {
-    //         true => 0,
-    //         false => 1,
-    //     };
-    //     match unsafe { j::jack_set_freewheel(self.raw(), onoff) } {
-    //         0 => Ok(()),
-    //         _ => Err(Error::FreewheelError),
-    //     }
-    // }
+    /// When in "freewheel" mode, JACK no longer waits for any external event to
+    /// begin the start of the next process cycle. As a result, freewheel mode
+    /// causes "faster than real-time" execution of a JACK graph. If possessed,
+    /// real-time scheduling is dropped when entering freewheel mode, and if
+    /// appropriate it is reacquired when stopping.
+    ///
+    /// IMPORTANT: on systems using capabilities to provide real-time scheduling
+    /// (i.e. Linux Kernel 2.4), if enabling freewheel, this function must be
+    /// called from the thread that originally called `self.activate()`. This
+    /// restriction does not apply to other systems (e.g. Linux Kernel 2.6 or OS
+    /// X).
+    ///
+    /// Returns `Error::FreewheelError` without calling into JACK if the JACK implementation does
+    /// not support freewheel mode, see `ServerQuirks::supports_freewheel`.
+    pub fn set_freewheel(&self, enable: bool) -> Result<(), Error> {
+        if !crate::server_quirks().supports_freewheel {
+            return Err(Error::FreewheelError);
+        }
+        match unsafe { j::jack_set_freewheel(self.raw(), enable as libc::c_int) } {
+            0 => Ok(()),
+            _ => Err(Error::FreewheelError),
+        }
+    }
 
     /// Establish a connection between two ports by their full name.
     ///
     /// When a connection exists, data written to the source port will be available to be read at
     /// the destination port.
     ///
-    /// On failure, either a `PortAlreadyConnected` or `PortConnectionError` is returned.
+    /// On failure, either a `PortAlreadyConnected` or `PortConnectionError` is returned. A
+    /// `PortConnectionError` tells why it failed, see `PortErrorDetails`. If a port belongs to this
+    /// client while it is inactive, `InvalidClientState` is returned without calling into JACK.
     ///
     /// # Preconditions
     /// 1. The port types must be identical
@@ -376,20 +841,41 @@ impl Client {
         source_port: &str,
         destination_port: &str,
     ) -> Result<(), Error> {
-        let source_cstr = ffi::CString::new(source_port).unwrap();
-        let destination_cstr = ffi::CString::new(destination_port).unwrap();
+        self.connect_ports_cstr(&to_cstring(source_port)?, &to_cstring(destination_port)?)
+    }
 
-        let res =
-            unsafe { j::jack_connect(self.raw(), source_cstr.as_ptr(), destination_cstr.as_ptr()) };
+    /// Like `connect_ports_by_name`, but takes the names as `CStr`s so that they don't have to be
+    /// copied. Apart from the error, connecting does not allocate.
+    pub fn connect_ports_cstr(
+        &self,
+        source_port: &ffi::CStr,
+        destination_port: &ffi::CStr,
+    ) -> Result<(), Error> {
+        self.check_connectable(source_port, destination_port)?;
+        let (res, message) = logging::capture_errors(|| unsafe {
+            j::jack_connect(self.raw(), source_port.as_ptr(), destination_port.as_ptr())
+        });
+        let (source_port, destination_port) = (
+            source_port.to_string_lossy(),
+            destination_port.to_string_lossy(),
+        );
         match res {
-            0 => Ok(()),
+            0 => {
+                logging::log_event!(debug, "connected {} to {}", source_port, destination_port);
+                Ok(())
+            }
             ::libc::EEXIST => Err(Error::PortAlreadyConnected(
-                source_port.to_string(),
-                destination_port.to_string(),
+                source_port.into_owned(),
+                destination_port.into_owned(),
             )),
-            _ => Err(Error::PortConnectionError(
+            code => Err(Error::PortConnectionError(
                 source_port.to_string(),
                 destination_port.to_string(),
+                PortErrorDetails {
+                    kind: self.diagnose_connection(&source_port, &destination_port),
+                    code: Some(code),
+                    message,
+                },
             )),
         }
     }
@@ -399,7 +885,9 @@ impl Client {
     /// When a connection exists, data written to the source port will be available to be read at
     /// the destination port.
     ///
-    /// On failure, either a `PortAlreadyConnected` or `PortConnectionError` is returned.
+    /// On failure, either a `PortAlreadyConnected` or `PortConnectionError` is returned. A
+    /// `PortConnectionError` tells why it failed, see `PortErrorDetails`. If a port belongs to this
+    /// client while it is inactive, `InvalidClientState` is returned without calling into JACK.
     ///
     /// # Preconditions
     /// 1. The port types must be identical
@@ -412,23 +900,121 @@ impl Client {
         destination_port: &Port<B>,
     ) -> Result<(), Error> {
         let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
-        self.connect_ports_by_name(&source_port.name()?, &destination_port.name()?)
+        self.connect_ports_cstr(source_port.name_cstr()?, destination_port.name_cstr()?)
+    }
+
+    /// Connect two ports by their full name like `connect_ports_by_name`, and disconnect them again
+    /// when the returned guard is dropped. See `ConnectionGuard`.
+    ///
+    /// Fails like `connect_ports_by_name`. This includes `PortAlreadyConnected`, so a guard never
+    /// removes a connection that it did not make.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let (client, _status) =
+    ///     jack::Client::new("tap", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let tap = client.register_port("tap", jack::AudioIn::default()).unwrap();
+    /// let client = client.activate_async((), ()).unwrap();
+    /// let guard = client
+    ///     .as_client()
+    ///     .connect_guarded("system:capture_1", &tap.name().unwrap())
+    ///     .unwrap();
+    /// std::thread::sleep(std::time::Duration::from_secs(1));
+    /// // Disconnects the ports.
+    /// drop(guard);
+    /// ```
+    pub fn connect_guarded(
+        &self,
+        source_port: &str,
+        destination_port: &str,
+    ) -> Result<ConnectionGuard, Error> {
+        let (source_port, destination_port) =
+            (to_cstring(source_port)?, to_cstring(destination_port)?);
+        self.connect_ports_cstr(&source_port, &destination_port)?;
+        Ok(ConnectionGuard::new(
+            self.downgrade(),
+            source_port,
+            destination_port,
+        ))
+    }
+
+    /// The connections of the ports of this client, as `(source_port, destination_port)` pairs of
+    /// full port names.
+    ///
+    /// JACK drops the connections of a client when it is deactivated, so they can be taken before
+    /// deactivating and given to `restore_connections` after activating again. See
+    /// `AsyncClient::deactivate_with_connections`.
+    pub fn port_connections(&self) -> PortConnections {
+        let mut connections = Vec::new();
+        for (name, port) in self.own_ports(PortFlags::empty()) {
+            let is_output = port.flags().contains(PortFlags::IS_OUTPUT);
+            let others = match port.connections() {
+                Ok(others) => others,
+                Err(_) => continue,
+            };
+            for other in others.iter() {
+                connections.push(match is_output {
+                    true => (name.clone(), other.to_string()),
+                    false => (other.to_string(), name.clone()),
+                });
+            }
+        }
+        connections
+    }
+
+    /// Connect each `(source_port, destination_port)` pair, for example the connections returned
+    /// by `port_connections` before the client was deactivated.
+    ///
+    /// All pairs are tried, connections that already exist are skipped. The first error is
+    /// returned, for example if one of the ports is gone.
+    pub fn restore_connections(&self, connections: &[(String, String)]) -> Result<(), Error> {
+        let mut res = Ok(());
+        for (source, destination) in connections {
+            match self.connect_ports_by_name(source, destination) {
+                Ok(()) | Err(Error::PortAlreadyConnected(..)) => (),
+                Err(err) => {
+                    if res.is_ok() {
+                        res = Err(err);
+                    }
+                }
+            }
+        }
+        res
     }
 
     /// Remove all connections to/from the port.
     pub fn disconnect<PS>(&self, port: &Port<PS>) -> Result<(), Error> {
-        let res = unsafe { j::jack_port_disconnect(self.raw(), port.raw()) };
+        let (res, message) =
+            logging::capture_errors(|| unsafe { j::jack_port_disconnect(self.raw(), port.raw()) });
         match res {
             0 => Ok(()),
-            _ => Err(Error::PortDisconnectionError),
+            code => Err(Error::PortDisconnectionError(PortErrorDetails {
+                kind: PortErrorKind::Unknown,
+                code: Some(code),
+                message,
+            })),
         }
     }
 
+    /// Unregister `port`. With the `metadata` feature, the properties of the port, such as its
+    /// pretty name, are removed as well.
     pub fn unregister_port<PS>(&self, port: Port<PS>) -> Result<(), Error> {
-        let res = unsafe { j::jack_port_unregister(self.raw(), port.raw()) };
+        #[cfg(feature = "metadata")]
+        let uuid = port.uuid();
+        let (res, message) =
+            logging::capture_errors(|| unsafe { j::jack_port_unregister(self.raw(), port.raw()) });
         match res {
-            0 => Ok(()),
-            _ => Err(Error::PortDisconnectionError),
+            0 => {
+                // JACK may keep the properties of a port after it is gone.
+                #[cfg(feature = "metadata")]
+                let _ = self.remove_properties(uuid);
+                Ok(())
+            }
+            code => Err(Error::PortDisconnectionError(PortErrorDetails {
+                kind: PortErrorKind::Unknown,
+                code: Some(code),
+                message,
+            })),
         }
     }
 
@@ -438,23 +1024,121 @@ impl Client {
         source: &Port<A>,
         destination: &Port<B>,
     ) -> Result<(), Error> {
-        self.disconnect_ports_by_name(&source.name()?, &destination.name()?)
+        self.disconnect_ports_cstr(source.name_cstr()?, destination.name_cstr()?)
     }
 
     /// Remove a connection between two ports.
+    ///
+    /// On failure, a `PortDisconnectionError` is returned that tells why, see `PortErrorDetails`.
+    /// Like for connecting, `InvalidClientState` is returned if a port belongs to this client while
+    /// it is inactive.
     pub fn disconnect_ports_by_name(
         &self,
         source_port: &str,
         destination_port: &str,
     ) -> Result<(), Error> {
-        let source_port = ffi::CString::new(source_port).unwrap();
-        let destination_port = ffi::CString::new(destination_port).unwrap();
-        let res = unsafe {
+        self.disconnect_ports_cstr(&to_cstring(source_port)?, &to_cstring(destination_port)?)
+    }
+
+    /// Like `disconnect_ports_by_name`, but takes the names as `CStr`s so that they don't have to
+    /// be copied. Apart from the error, disconnecting does not allocate.
+    pub fn disconnect_ports_cstr(
+        &self,
+        source_port: &ffi::CStr,
+        destination_port: &ffi::CStr,
+    ) -> Result<(), Error> {
+        self.check_connectable(source_port, destination_port)?;
+        let (res, message) = logging::capture_errors(|| unsafe {
             j::jack_disconnect(self.raw(), source_port.as_ptr(), destination_port.as_ptr())
-        };
+        });
         match res {
             0 => Ok(()),
-            _ => Err(Error::PortDisconnectionError),
+            code => {
+                let (source_port, destination_port) = (
+                    source_port.to_string_lossy(),
+                    destination_port.to_string_lossy(),
+                );
+                let kind = match self.diagnose_connection(&source_port, &destination_port) {
+                    PortErrorKind::NoSuchPort => PortErrorKind::NoSuchPort,
+                    _ => match self.port_by_name(&source_port) {
+                        Some(port) if port.is_connected_to(&destination_port) == Ok(false) => {
+                            PortErrorKind::NotConnected
+                        }
+                        _ => PortErrorKind::Unknown,
+                    },
+                };
+                Err(Error::PortDisconnectionError(PortErrorDetails {
+                    kind,
+                    code: Some(code),
+                    message,
+                }))
+            }
+        }
+    }
+
+    /// JACK only connects and disconnects the ports of active clients. Returns
+    /// `Err(Error::InvalidClientState)` if one of the ports belongs to this client while it is
+    /// inactive. Does not allocate.
+    fn check_connectable(
+        &self,
+        source_port: &ffi::CStr,
+        destination_port: &ffi::CStr,
+    ) -> Result<(), Error> {
+        if self.active {
+            return Ok(());
+        }
+        let is_mine = |name: &ffi::CStr| unsafe {
+            let port = j::jack_port_by_name(self.raw(), name.as_ptr());
+            !port.is_null() && j::jack_port_is_mine(self.raw(), port) == 1
+        };
+        if is_mine(source_port) || is_mine(destination_port) {
+            return Err(Error::InvalidClientState(
+                ClientState::Active,
+                ClientState::Inactive,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Find out why connecting `source_port` to `destination_port` failed.
+    fn diagnose_connection(&self, source_port: &str, destination_port: &str) -> PortErrorKind {
+        let (source, destination) = match (
+            self.port_by_name(source_port),
+            self.port_by_name(destination_port),
+        ) {
+            (Some(source), Some(destination)) => (source, destination),
+            _ => return PortErrorKind::NoSuchPort,
+        };
+        if source.port_type().ok() != destination.port_type().ok() {
+            return PortErrorKind::TypeMismatch;
+        }
+        if !source.flags().contains(PortFlags::IS_OUTPUT)
+            || !destination.flags().contains(PortFlags::IS_INPUT)
+        {
+            return PortErrorKind::WrongDirection;
+        }
+        if !self.active && (self.is_mine(&source) || self.is_mine(&destination)) {
+            return PortErrorKind::ClientNotActive;
+        }
+        PortErrorKind::Unknown
+    }
+
+    /// Find out why registering the port `port_name` failed.
+    fn diagnose_registration(&self, port_name: &str) -> PortErrorKind {
+        // The full name is built from the bytes of the client name, which may not be UTF-8.
+        let mut full_name = self.name_cstr().to_bytes().to_vec();
+        full_name.push(b':');
+        full_name.extend_from_slice(port_name.as_bytes());
+        if full_name.len() > *PORT_NAME_SIZE {
+            return PortErrorKind::NameTooLong;
+        }
+        let full_name = match ffi::CString::new(full_name) {
+            Ok(full_name) => full_name,
+            Err(_) => return PortErrorKind::Unknown,
+        };
+        match self.port_by_name_cstr(&full_name) {
+            Some(port) if self.is_mine(&port) => PortErrorKind::NameNotUnique,
+            _ => PortErrorKind::Unknown,
         }
     }
 
@@ -463,17 +1147,27 @@ impl Client {
     /// # Safety
     ///
     /// * This function may only be called in a buffer size callback.
+    ///
+    /// Returns `0` if `port_type` contains a NUL byte, since no port type does.
     pub unsafe fn type_buffer_size(&self, port_type: &str) -> usize {
-        let port_type = ffi::CString::new(port_type).unwrap();
+        let port_type = match to_cstring(port_type) {
+            Ok(port_type) => port_type,
+            Err(_) => return 0,
+        };
         j::jack_port_type_get_buffer_size(self.raw(), port_type.as_ptr())
     }
 
+    /// Get a non-owning handle to this client that can be stored and sent to other threads.
+    pub fn downgrade(&self) -> WeakClient {
+        WeakClient::new(self.raw(), Arc::downgrade(&self.life))
+    }
+
     /// Expose the underlying ffi pointer.
     ///
     /// This is mostly for use within the jack crate itself.
     #[inline(always)]
     pub fn raw(&self) -> *mut j::jack_client_t {
-        self.0
+        self.raw
     }
 
     /// Create a `Client` from an ffi pointer.
@@ -483,7 +1177,19 @@ impl Client {
     /// # Safety
     /// It is unsafe to create a `Client` from a raw pointer.
     pub unsafe fn from_raw(p: *mut j::jack_client_t) -> Self {
-        Client(p, Arc::default(), None)
+        Client {
+            raw: p,
+            life: Arc::default(),
+            property_change_handler: None,
+            thread_init_config: None,
+            #[cfg(feature = "tracing")]
+            notification_span: None,
+            timing_stats_enabled: false,
+            graph_tracking_enabled: false,
+            session_handler: None,
+            active: false,
+            timebase_handler: None,
+        }
     }
 
     /// Get a `Transport` object associated with this client.
@@ -492,15 +1198,118 @@ impl Client {
     /// * The transport methods will only work during this client's lifetime.
     pub fn transport(&self) -> Transport {
         Transport {
-            client_ptr: self.0,
-            client_life: Arc::downgrade(&self.1),
+            client_ptr: self.raw,
+            client_life: Arc::downgrade(&self.life),
         }
     }
 
+    /// Set the configuration to apply to the thread that runs the callbacks once the client is
+    /// activated.
+    ///
+    /// # Remarks
+    /// * The configuration is applied in the `thread_init` callback, before
+    ///   `NotificationHandler::thread_init` is called.
+    /// * Calling this method again replaces the previous configuration. It has no effect on a
+    ///   client that is already active.
+    pub fn set_thread_init_config(&mut self, config: ThreadInitConfig) {
+        self.thread_init_config = Some(config);
+    }
+
+    /// Get the configuration that is applied to the thread that runs the callbacks.
+    pub fn thread_init_config(&self) -> Option<&ThreadInitConfig> {
+        self.thread_init_config.as_ref()
+    }
+
+    /// Enter a span within `span` around every notification callback once the client is
+    /// activated, so that the events of the `NotificationHandler` have the context of `span`.
+    ///
+    /// # Remarks
+    /// * Without a span, the spans around the notification callbacks have no parent.
+    /// * The process callback, and the buffer size and xrun callbacks that may run on the
+    ///   process thread, never enter a span.
+    /// * Calling this method has no effect on a client that is already active.
+    #[cfg(feature = "tracing")]
+    pub fn set_notification_span(&mut self, span: tracing::Span) {
+        self.notification_span = Some(span);
+    }
+
+    /// Get the span that the spans around the notification callbacks are entered within, see
+    /// `set_notification_span`.
+    #[cfg(feature = "tracing")]
+    pub fn notification_span(&self) -> Option<&tracing::Span> {
+        self.notification_span.as_ref()
+    }
+
+    /// Measure how long the process callback takes once the client is activated, see
+    /// `AsyncClient::timing_stats`.
+    ///
+    /// # Remarks
+    /// * Measuring costs two reads of the JACK clock and a few atomic operations per process cycle.
+    /// * Calling this method has no effect on a client that is already active.
+    pub fn set_timing_stats_enabled(&mut self, enabled: bool) {
+        self.timing_stats_enabled = enabled;
+    }
+
+    /// Returns `true` if the process callback will be measured, see `set_timing_stats_enabled`.
+    pub fn timing_stats_enabled(&self) -> bool {
+        self.timing_stats_enabled
+    }
+
+    /// Register the client, port and connection callbacks on activation even if the
+    /// `NotificationHandler` does not select them, for `AsyncClient::watch_port`,
+    /// `AsyncClient::connect_when_available` and `AsyncClient::name_registry`.
+    ///
+    /// # Remarks
+    /// * Handlers that select `CallbackSelection::CLIENT_REGISTRATION`,
+    ///   `CallbackSelection::PORT_REGISTRATION` and `CallbackSelection::PORTS_CONNECTED`, like the
+    ///   default selection does, have the graph tracked regardless.
+    /// * Tracking the graph makes JACK call into the client for every change of the graph.
+    /// * Calling this method has no effect on a client that is already active.
+    pub fn set_graph_tracking_enabled(&mut self, enabled: bool) {
+        self.graph_tracking_enabled = enabled;
+    }
+
+    /// Returns `true` if the graph callbacks will be registered regardless of the handler, see
+    /// `set_graph_tracking_enabled`.
+    pub fn graph_tracking_enabled(&self) -> bool {
+        self.graph_tracking_enabled
+    }
+
+    /// Returns `true` if the client is active, which is the case for the client of an
+    /// `AsyncClient` and the client that is passed to the handlers.
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    /// Mark the client as active or inactive. Used by `AsyncClient` while JACK does not call into
+    /// the client.
+    pub(crate) fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// The timebase handler of the client, see `register_timebase_handler`.
+    pub(crate) fn timebase_handler_slot(&mut self) -> &mut Option<Box<dyn Any + Send>> {
+        &mut self.timebase_handler
+    }
+
+    /// The session handler of the client, see `register_session_handler`.
+    #[cfg(feature = "session")]
+    pub(crate) fn session_handler_slot(&mut self) -> &mut Option<Box<dyn Any + Send>> {
+        &mut self.session_handler
+    }
+
+    /// Returns `true` if a handler was registered with `register_property_change_handler`.
+    #[cfg(feature = "metadata")]
+    pub(crate) fn has_property_change_handler(&self) -> bool {
+        self.property_change_handler.is_some()
+    }
+
     /// Register a property change handler for this client.
     ///
     /// # Remarks
     /// * The handler isn't called until after this client is activated.
+    /// * `Err(Error::WeakFunctionNotFound)` is returned if the JACK library does not support
+    ///   Metadata.
     ///
     /// # Panics
     /// Calling this method more than once on any given client with cause a panic.
@@ -509,10 +1318,13 @@ impl Client {
         &mut self,
         handler: H,
     ) -> Result<(), Error> {
-        assert!(self.2.is_none());
+        assert!(self.property_change_handler.is_none());
+        if !crate::metadata::is_available() {
+            return Err(Error::WeakFunctionNotFound);
+        }
         let handler = Box::into_raw(Box::new(handler));
         unsafe {
-            self.2 = Some(Box::from_raw(handler));
+            self.property_change_handler = Some(Box::from_raw(handler));
             if j::jack_set_property_change_callback(
                 self.raw(),
                 Some(crate::properties::property_changed::<H>),
@@ -533,15 +1345,47 @@ impl Drop for Client {
         let _m = CREATE_OR_DESTROY_CLIENT_MUTEX.lock().unwrap();
 
         debug_assert!(!self.raw().is_null()); // Rep invariant
-                                              // Close the client
+
+        // Wait for `WeakClient` queries on other threads to finish. Once the life token is
+        // unwrapped no new queries can start.
+        let mut life = mem::take(&mut self.life);
+        while let Err(l) = Arc::try_unwrap(life) {
+            life = l;
+            thread::yield_now();
+        }
+
+        // Close the client
+        logging::log_event!(debug, "closing client {}", self.name_lossy());
         sleep_on_test();
         let res = unsafe { j::jack_client_close(self.raw()) }; // close the client
         sleep_on_test();
         assert_eq!(res, 0);
-        self.0 = ptr::null_mut();
+        self.raw = ptr::null_mut();
     }
 }
 
+/// The uuid of the client named `client_name`, if it is open. This asks the server, so it must not
+/// be called from the notification callbacks.
+///
+/// # Safety
+/// `client` must be an open client.
+pub(crate) unsafe fn uuid_by_name(
+    client: *mut j::jack_client_t,
+    client_name: &str,
+) -> Option<Uuid> {
+    let client_name = to_cstring(client_name).ok()?;
+    let uuid_s = j::jack_get_uuid_for_client_name(client, client_name.as_ptr());
+    if uuid_s.is_null() {
+        return None;
+    }
+    let uuid = ffi::CStr::from_ptr(uuid_s)
+        .to_str()
+        .ok()
+        .and_then(|s| Uuid::parse(s).ok());
+    j::jack_free(uuid_s as _);
+    uuid
+}
+
 impl fmt::Debug for Client {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         write!(f, "{:?}", ClientInfo::from(self))
@@ -556,25 +1400,53 @@ pub struct ProcessScope {
 
     // Used to allow safe access to IO port buffers
     n_frames: Frames,
+
+    // The sample rate kept by the crate, or 0 if the scope was created with `from_raw`.
+    sample_rate: usize,
+
+    // The port buffers of a `MockProcessScope`, or `null` for the process callback.
+    mock: *const MockPorts,
 }
 
 impl ProcessScope {
-    /// The number of frames in the current process cycle.
+    /// The number of frames in the current process cycle. This is at most `Client::buffer_size`,
+    /// and may be smaller on some JACK implementations, see
+    /// `ServerQuirks::buffer_size_can_change_without_callback`.
     #[inline(always)]
     pub fn n_frames(&self) -> Frames {
         self.n_frames
     }
 
+    /// The sample rate of the JACK system. Unlike `Client::sample_rate`, this does not call into
+    /// JACK. The crate keeps the sample rate up to date from the sample rate callback, which runs
+    /// before the next process cycle.
+    #[inline(always)]
+    pub fn sample_rate(&self) -> usize {
+        match self.sample_rate {
+            // Only for scopes created with `from_raw`.
+            0 if !self.client_ptr.is_null() => unsafe {
+                j::jack_get_sample_rate(self.client_ptr) as usize
+            },
+            sample_rate => sample_rate,
+        }
+    }
+
     /// The precise time at the start of the current process cycle. This function may only be used
     /// from the process callback, and can be used to interpret timestamps generated by
     /// `self.frame_time()` in other threads, with respect to the current process cycle.
     pub fn last_frame_time(&self) -> Frames {
+        if let Some(mock) = unsafe { self.mock.as_ref() } {
+            return mock.last_frame_time;
+        }
         unsafe { j::jack_last_frame_time(self.client_ptr()) }
     }
 
     /// The estimated time in frames that has passed since the JACK server began the current process
     /// cycle.
     pub fn frames_since_cycle_start(&self) -> Frames {
+        if !self.mock.is_null() {
+            return 0;
+        }
         unsafe { j::jack_frames_since_cycle_start(self.client_ptr()) }
     }
 
@@ -584,6 +1456,8 @@ impl ProcessScope {
     /// microseconds time of the start of the current cycle directly (it has to be computed
     /// otherwise).
     ///
+    /// For a `MockProcessScope`, frame `0` is at time `0` and time runs at the sample rate.
+    ///
     /// `Err(Error::TimeError)` is returned on failure.
     /// `Err(Error::WeakFunctionNotFound)` if the function does not exist.
     pub fn cycle_times(&self) -> Result<CycleTimes, Error> {
@@ -592,6 +1466,13 @@ impl ProcessScope {
         let mut next_usecs: Time = 0;
         let mut period_usecs: libc::c_float = 0.0;
 
+        if let Some(mock) = unsafe { self.mock.as_ref() } {
+            return Ok(CycleTimes::at_sample_rate(
+                mock.last_frame_time,
+                self.n_frames,
+                mock.sample_rate,
+            ));
+        }
         let jack_get_cycle_times = {
             match *j::jack_get_cycle_times {
                 Some(f) => f,
@@ -618,6 +1499,73 @@ impl ProcessScope {
         }
     }
 
+    /// The time in microseconds at which the frame at `offset` of the current process cycle is
+    /// played, where offset `0` is the start of the cycle and `n_frames()` the start of the next.
+    ///
+    /// The time is interpolated with the full precision period of `cycle_times`. If
+    /// `jack_get_cycle_times` is not available, the less precise `Client::frames_to_time` is used
+    /// instead. `Err(Error::TimeError)` is returned on failure.
+    pub fn time_at_offset(&self, offset: Frames) -> Result<Time, Error> {
+        match self.cycle_times() {
+            Ok(times) => Ok(times.time_at_offset(offset, self.n_frames)),
+            Err(Error::WeakFunctionNotFound) => Ok(unsafe {
+                j::jack_frames_to_time(
+                    self.client_ptr(),
+                    self.last_frame_time().wrapping_add(offset),
+                )
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The offset of the frame of the current process cycle that is played at `time`, in
+    /// microseconds. This is the inverse of `time_at_offset`, for example to find the frame at
+    /// which to apply an event with a timestamp.
+    ///
+    /// `None` is returned if `time` is not within the current cycle, or on failure.
+    pub fn offset_at_time(&self, time: Time) -> Option<Frames> {
+        match self.cycle_times() {
+            Ok(times) => times.offset_at_time(time, self.n_frames),
+            Err(Error::WeakFunctionNotFound) => {
+                let frames = unsafe { j::jack_time_to_frames(self.client_ptr(), time) };
+                let offset = frames.wrapping_sub(self.last_frame_time());
+                (offset < self.n_frames).then_some(offset)
+            }
+            Err(_) => None,
+        }
+    }
+
+    /// The estimated time in microseconds of the frame time `frames`, anchored at the start of the
+    /// current process cycle.
+    ///
+    /// `Client::frames_to_time` extrapolates from the clock of the server at the time of the call,
+    /// which may have moved on since the cycle started. This maps with the `cycle_times` of the
+    /// cycle instead, so all the conversions within a cycle are consistent with `time_at_offset`.
+    /// This is the way to timestamp events that are generated in the process callback and consumed
+    /// by other threads, for example `scope.frames_to_time(scope.last_frame_time() + offset)`.
+    ///
+    /// If `jack_get_cycle_times` is not available, `Client::frames_to_time` is used.
+    pub fn frames_to_time(&self, frames: impl Into<units::Frames>) -> Time {
+        let frames = frames.into();
+        match self.cycle_times().ok() {
+            Some(times) => times.frames_to_time(frames, self.n_frames),
+            None => unsafe { j::jack_frames_to_time(self.client_ptr(), frames.get()) },
+        }
+    }
+
+    /// The estimated frame time of the time `time` in microseconds, anchored at the start of the
+    /// current process cycle. This is the inverse of `frames_to_time`, for example to place an
+    /// event with a timestamp from another thread.
+    ///
+    /// If `jack_get_cycle_times` is not available, `Client::time_to_frames` is used.
+    pub fn time_to_frames(&self, time: impl Into<units::Time>) -> Frames {
+        let time = time.into();
+        match self.cycle_times().ok() {
+            Some(times) => times.time_to_frames(time, self.n_frames),
+            None => unsafe { j::jack_time_to_frames(self.client_ptr(), time.as_micros()) },
+        }
+    }
+
     /// Expose the `client_ptr` for low level purposes.
     ///
     /// This is mostly for use within the jack crate itself.
@@ -638,8 +1586,45 @@ impl ProcessScope {
         ProcessScope {
             n_frames,
             client_ptr,
+            sample_rate: 0,
+            mock: ptr::null(),
         }
     }
+
+    /// Set the sample rate that `sample_rate` returns.
+    pub(crate) fn with_sample_rate(mut self, sample_rate: usize) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    /// Create a `ProcessScope` that uses the port buffers of `mock`, which must outlive it.
+    pub(crate) fn mock(n_frames: Frames, mock: *const MockPorts) -> Self {
+        ProcessScope {
+            n_frames,
+            client_ptr: ptr::null_mut(),
+            sample_rate: unsafe { (*mock).sample_rate },
+            mock,
+        }
+    }
+
+    /// The buffer of `port` for the current process cycle.
+    ///
+    /// # Safety
+    /// `port` must belong to the client of the process cycle, or to the `MockProcessScope`.
+    #[inline(always)]
+    pub(crate) unsafe fn port_buffer<PS>(&self, port: &Port<PS>) -> *mut libc::c_void {
+        match self.mock.as_ref() {
+            None => port.buffer(self.n_frames),
+            Some(mock) => mock.audio_buffer(port.raw()) as *mut libc::c_void,
+        }
+    }
+
+    /// The MIDI buffer of `port` if the scope is a `MockProcessScope`.
+    #[inline(always)]
+    pub(crate) fn mock_midi_buffer<PS>(&self, port: &Port<PS>) -> Option<*mut MockMidiBuffer> {
+        let mock = unsafe { self.mock.as_ref() }?;
+        Some(mock.midi_buffer(port.raw()))
+    }
 }
 
 /// Internal cycle timing information.
@@ -651,6 +1636,68 @@ pub struct CycleTimes {
     pub period_usecs: libc::c_float,
 }
 
+impl CycleTimes {
+    /// The time of the frame at `offset` of a cycle with `n_frames` frames, see
+    /// `ProcessScope::time_at_offset`.
+    pub(crate) fn time_at_offset(&self, offset: Frames, n_frames: Frames) -> Time {
+        let usecs_per_frame = self.period_usecs as f64 / n_frames.max(1) as f64;
+        self.current_usecs + (offset as f64 * usecs_per_frame).round() as Time
+    }
+
+    /// The offset of the frame at `time` in a cycle with `n_frames` frames, see
+    /// `ProcessScope::offset_at_time`.
+    pub(crate) fn offset_at_time(&self, time: Time, n_frames: Frames) -> Option<Frames> {
+        // `time_at_offset` rounds to the nearest microsecond, so `time` stands for the microsecond
+        // around it.
+        let usecs = time.checked_sub(self.current_usecs)? as f64 + 0.5;
+        let offset = (usecs * n_frames as f64 / self.period_usecs as f64).floor();
+        (offset < n_frames as f64).then_some(offset as Frames)
+    }
+
+    /// The time of the frame time `frames` for a cycle with `n_frames` frames, see
+    /// `ProcessScope::frames_to_time`.
+    pub(crate) fn frames_to_time(
+        &self,
+        frames: impl Into<units::Frames>,
+        n_frames: Frames,
+    ) -> Time {
+        // Frame times wrap around, the frames up to half of the range before the cycle are earlier.
+        let offset = frames.into().since(self.current_frames.into()).get() as i32;
+        let usecs_per_frame = self.period_usecs as f64 / n_frames.max(1) as f64;
+        let usecs = (offset as f64 * usecs_per_frame).round();
+        (self.current_usecs as f64 + usecs).max(0.0) as Time
+    }
+
+    /// The frame time at `time` for a cycle with `n_frames` frames, see
+    /// `ProcessScope::time_to_frames`.
+    pub(crate) fn time_to_frames(&self, time: impl Into<units::Time>, n_frames: Frames) -> Frames {
+        if self.period_usecs <= 0.0 {
+            return self.current_frames;
+        }
+        let usecs = time.into().as_micros() as f64 - self.current_usecs as f64;
+        let offset = (usecs * n_frames as f64 / self.period_usecs as f64).round();
+        self.current_frames.wrapping_add(offset as i64 as Frames)
+    }
+
+    /// The times of a cycle that starts at `current_frames`, on a clock that starts at frame `0`
+    /// and runs at `sample_rate`.
+    pub(crate) fn at_sample_rate(
+        current_frames: Frames,
+        n_frames: Frames,
+        sample_rate: usize,
+    ) -> CycleTimes {
+        let usecs = |frames: u64| frames * 1_000_000 / sample_rate.max(1) as u64;
+        let current_usecs = usecs(current_frames as u64);
+        CycleTimes {
+            current_frames,
+            current_usecs,
+            next_usecs: usecs(current_frames as u64 + n_frames as u64),
+            period_usecs: (n_frames as f64 * 1_000_000.0 / sample_rate.max(1) as f64)
+                as libc::c_float,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct ClientInfo {
     name: String,
@@ -664,7 +1711,7 @@ struct ClientInfo {
 impl<'a> From<&'a Client> for ClientInfo {
     fn from(c: &Client) -> ClientInfo {
         ClientInfo {
-            name: c.name().into(),
+            name: c.name_lossy().into_owned(),
             sample_rate: c.sample_rate(),
             buffer_size: c.buffer_size(),
             cpu_usage: format!("{}%", c.cpu_load() / 100.0),
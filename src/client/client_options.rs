@@ -1,6 +1,8 @@
 use bitflags::bitflags;
 use jack_sys as j;
 
+use crate::jack_utils::flag_names;
+
 bitflags! {
     /// Option flags for opening a JACK client.
     pub struct ClientOptions: j::Enum_JackOptions {
@@ -32,3 +34,12 @@ bitflags! {
         const SESSION_ID      = j::JackSessionID;
     }
 }
+
+flag_names!(ClientOptions {
+    NO_START_SERVER,
+    USE_EXACT_NAME,
+    SERVER_NAME,
+    LOAD_NAME,
+    LOAD_INIT,
+    SESSION_ID
+});
@@ -1,6 +1,8 @@
 use bitflags::bitflags;
 use jack_sys as j;
 
+use crate::jack_utils::flag_names;
+
 bitflags! {
     /// Status flags for JACK clients.
     pub struct ClientStatus: j::Enum_JackStatus {
@@ -50,3 +52,135 @@ bitflags! {
         const CLIENT_ZOMBIE   = j::JackClientZombie;
     }
 }
+
+flag_names!(ClientStatus {
+    FAILURE,
+    INVALID_OPTION,
+    NAME_NOT_UNIQUE,
+    SERVER_STARTED,
+    SERVER_FAILED,
+    SERVER_ERROR,
+    NO_SUCH_CLIENT,
+    LOAD_FAILURE,
+    INIT_FAILURE,
+    SHM_FAILURE,
+    VERSION_ERROR,
+    BACKEND_ERROR,
+    CLIENT_ZOMBIE
+});
+
+impl ClientStatus {
+    /// Returns `true` if opening a client failed for a reason that may go away by trying again,
+    /// such as the JACK server not running yet. Failures like `NAME_NOT_UNIQUE` or
+    /// `VERSION_ERROR` won't improve and are not transient.
+    pub fn is_transient(&self) -> bool {
+        let transient =
+            ClientStatus::SERVER_FAILED | ClientStatus::SERVER_ERROR | ClientStatus::SHM_FAILURE;
+        let permanent = ClientStatus::INVALID_OPTION
+            | ClientStatus::NAME_NOT_UNIQUE
+            | ClientStatus::NO_SUCH_CLIENT
+            | ClientStatus::LOAD_FAILURE
+            | ClientStatus::INIT_FAILURE
+            | ClientStatus::VERSION_ERROR;
+        self.intersects(transient) && !self.intersects(permanent)
+    }
+
+    /// A summary of the set flags that can be shown to users, for example "the JACK server is not
+    /// running or could not be started". The reasons are separated by semicolons.
+    ///
+    /// `FAILURE` is only described if no other flag explains the failure.
+    pub fn describe(&self) -> String {
+        const REASONS: [(ClientStatus, &str); 12] = [
+            (
+                ClientStatus::SERVER_FAILED,
+                "the JACK server is not running or could not be started",
+            ),
+            (
+                ClientStatus::SERVER_ERROR,
+                "communication with the JACK server failed",
+            ),
+            (
+                ClientStatus::NAME_NOT_UNIQUE,
+                "the client name is already taken",
+            ),
+            (
+                ClientStatus::INVALID_OPTION,
+                "an option is invalid or unsupported",
+            ),
+            (
+                ClientStatus::NO_SUCH_CLIENT,
+                "the requested client does not exist",
+            ),
+            (
+                ClientStatus::LOAD_FAILURE,
+                "the internal client could not be loaded",
+            ),
+            (
+                ClientStatus::INIT_FAILURE,
+                "the client could not be initialized",
+            ),
+            (
+                ClientStatus::SHM_FAILURE,
+                "the shared memory of the JACK server could not be accessed",
+            ),
+            (
+                ClientStatus::VERSION_ERROR,
+                "the JACK library and server versions do not match",
+            ),
+            (ClientStatus::BACKEND_ERROR, "the JACK backend failed"),
+            (ClientStatus::CLIENT_ZOMBIE, "the client was zombified"),
+            (ClientStatus::SERVER_STARTED, "the JACK server was started"),
+        ];
+        let mut reasons: Vec<&str> = REASONS
+            .iter()
+            .filter(|(flag, _)| self.contains(*flag))
+            .map(|(_, reason)| *reason)
+            .collect();
+        // Starting the server, the last reason, does not explain a failure.
+        let explained = REASONS[..REASONS.len() - 1]
+            .iter()
+            .any(|(flag, _)| self.contains(*flag));
+        if self.contains(ClientStatus::FAILURE) && !explained {
+            reasons.insert(0, "the operation failed");
+        }
+        match reasons.is_empty() {
+            true => "no error".to_string(),
+            false => reasons.join("; "),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn client_status_describes_reasons() {
+        assert_eq!(ClientStatus::empty().describe(), "no error");
+        assert_eq!(
+            (ClientStatus::FAILURE | ClientStatus::SERVER_FAILED).describe(),
+            "the JACK server is not running or could not be started"
+        );
+        assert_eq!(
+            (ClientStatus::FAILURE | ClientStatus::NAME_NOT_UNIQUE | ClientStatus::SERVER_STARTED)
+                .describe(),
+            "the client name is already taken; the JACK server was started"
+        );
+        assert_eq!(
+            (ClientStatus::FAILURE | ClientStatus::SERVER_STARTED).describe(),
+            "the operation failed; the JACK server was started"
+        );
+    }
+
+    #[test]
+    fn client_status_tells_transient_failures() {
+        assert!((ClientStatus::FAILURE | ClientStatus::SERVER_FAILED).is_transient());
+        assert!((ClientStatus::FAILURE | ClientStatus::SERVER_ERROR).is_transient());
+        assert!(!ClientStatus::FAILURE.is_transient());
+        assert!(!(ClientStatus::FAILURE | ClientStatus::NAME_NOT_UNIQUE).is_transient());
+        assert!(!(ClientStatus::FAILURE
+            | ClientStatus::SERVER_ERROR
+            | ClientStatus::VERSION_ERROR)
+            .is_transient());
+    }
+}
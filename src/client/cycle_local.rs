@@ -0,0 +1,231 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, AtomicU8, Ordering};
+use std::thread;
+
+use crate::{BufferSizeScope, ProcessScope};
+
+/// Nobody uses the value.
+const IDLE: u8 = 0;
+/// The value is used by `CycleLocal::with`.
+const IN_PROCESS: u8 = 1;
+/// The value is being rebuilt by `CycleLocal::rebuild` or `CycleLocal::update`.
+const IN_REBUILD: u8 = 2;
+
+/// A value that is used by the process callback and rebuilt when the buffer size or the sample
+/// rate changes, for example buffers that hold one cycle of audio.
+///
+/// The value is shared with an `Arc`, or owned by the `ProcessHandler` alone. It is rebuilt with
+/// `rebuild` from `ProcessHandler::buffer_size_with_scope`, or with `update` from other threads like
+/// the one of `NotificationHandler::sample_rate`, and used with `with` from the process callback.
+///
+/// # Synchronization
+/// JACK calls the buffer size callback on the same thread as the process callback, before the
+/// first cycle with the new size. The two are never concurrent, so `rebuild` never waits for the
+/// process callback and the process callback always sees the rebuilt value in the cycle that
+/// follows. No other callback has this guarantee, so `update` may run during a cycle. It waits for
+/// the process callback to release the value, and while it runs, `with` returns `None` instead of
+/// waiting. The process callback never blocks, allocates or takes a lock, it only swaps an atomic.
+///
+/// ```
+/// struct Delay {
+///     buffer: std::sync::Arc<jack::CycleLocal<Vec<f32>>>,
+/// }
+///
+/// impl jack::ProcessHandler for Delay {
+///     fn process(&mut self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+///         self.buffer.with(ps, |buffer| buffer.fill(0.0));
+///         jack::Control::Continue
+///     }
+///
+///     fn buffer_size_with_scope(&mut self, scope: &jack::BufferSizeScope) -> jack::Control {
+///         let n_frames = scope.n_frames() as usize;
+///         self.buffer.rebuild(scope, |buffer| buffer.resize(n_frames, 0.0));
+///         jack::Control::Continue
+///     }
+/// }
+/// ```
+pub struct CycleLocal<T> {
+    value: UnsafeCell<T>,
+    state: AtomicU8,
+    epoch: AtomicU64,
+}
+
+// Access to the value is exclusive, whichever thread has it.
+unsafe impl<T: Send> Sync for CycleLocal<T> {}
+
+impl<T> CycleLocal<T> {
+    pub fn new(value: T) -> CycleLocal<T> {
+        CycleLocal {
+            value: UnsafeCell::new(value),
+            state: AtomicU8::new(IDLE),
+            epoch: AtomicU64::new(0),
+        }
+    }
+
+    /// Call `f` with the value from the process callback. Does not block or allocate.
+    ///
+    /// Returns `None` without calling `f` if `update` is running on another thread, or if `with`
+    /// is called within `f`.
+    #[inline(always)]
+    pub fn with<R, F: FnOnce(&mut T) -> R>(&self, _: &ProcessScope, f: F) -> Option<R> {
+        self.state
+            .compare_exchange(IDLE, IN_PROCESS, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        let _release = Release(&self.state);
+        Some(f(unsafe { &mut *self.value.get() }))
+    }
+
+    /// Rebuild the value from the buffer size callback, for example to resize buffers to
+    /// `scope.n_frames()`. The process callback is not running, so this only waits for an `update`
+    /// on another thread. Like `update`, it must not be called within `with`.
+    pub fn rebuild<R, F: FnOnce(&mut T) -> R>(&self, _: &BufferSizeScope, f: F) -> R {
+        self.exclusive(f)
+    }
+
+    /// Rebuild the value from any thread that is not the process thread, for example from
+    /// `NotificationHandler::sample_rate`. Waits until the process callback is done with the value,
+    /// which it can't use until `f` returns.
+    ///
+    /// Calling it within `with`, or within another `update` or `rebuild` of the same value, waits
+    /// forever.
+    pub fn update<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        self.exclusive(f)
+    }
+
+    /// How many times the value was rebuilt. The process callback can compare it with the epoch
+    /// of the last cycle to find out that the value was replaced.
+    pub fn epoch(&self) -> u64 {
+        self.epoch.load(Ordering::Acquire)
+    }
+
+    /// Get the value, which the `CycleLocal` owns alone.
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    fn exclusive<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> R {
+        while self
+            .state
+            .compare_exchange_weak(IDLE, IN_REBUILD, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            thread::yield_now();
+        }
+        let _release = Release(&self.state);
+        self.epoch.fetch_add(1, Ordering::Release);
+        f(unsafe { &mut *self.value.get() })
+    }
+}
+
+/// Releases the value when dropped, also if the function that uses it panics.
+struct Release<'a>(&'a AtomicU8);
+
+impl<'a> Drop for Release<'a> {
+    fn drop(&mut self) {
+        self.0.store(IDLE, Ordering::Release);
+    }
+}
+
+impl<T: Default> Default for CycleLocal<T> {
+    fn default() -> CycleLocal<T> {
+        CycleLocal::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for CycleLocal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CycleLocal")
+            .field("epoch", &self.epoch())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::MockProcessScope;
+    use std::sync::{mpsc, Arc};
+    use std::time::Duration;
+
+    #[test]
+    fn cycle_local_is_used_and_updated() {
+        let mock = MockProcessScope::new(4);
+        let local = CycleLocal::new(vec![0.0f32; 4]);
+        assert_eq!(local.with(mock.scope(), |v| v.len()), Some(4));
+        local.update(|v| v.resize(8, 0.0));
+        assert_eq!(local.epoch(), 1);
+        assert_eq!(local.with(mock.scope(), |v| v.len()), Some(8));
+        assert_eq!(local.into_inner().len(), 8);
+    }
+
+    #[test]
+    fn cycle_local_is_not_used_within_itself() {
+        let mock = MockProcessScope::new(4);
+        let local = CycleLocal::new(0);
+        let nested = local.with(mock.scope(), |_| local.with(mock.scope(), |_| ()));
+        assert_eq!(nested, Some(None));
+        assert_eq!(local.with(mock.scope(), |v| *v), Some(0));
+    }
+
+    #[test]
+    fn cycle_local_is_not_used_during_update() {
+        let mock = MockProcessScope::new(4);
+        let local = Arc::new(CycleLocal::new(0));
+        let (started_tx, started_rx) = mpsc::channel();
+        let (finish_tx, finish_rx) = mpsc::channel::<()>();
+        let updater = {
+            let local = local.clone();
+            thread::spawn(move || {
+                local.update(|v| {
+                    started_tx.send(()).unwrap();
+                    let _ = finish_rx.recv();
+                    *v = 1;
+                })
+            })
+        };
+        started_rx.recv().unwrap();
+        assert_eq!(local.with(mock.scope(), |v| *v), None);
+        drop(finish_tx);
+        updater.join().unwrap();
+        assert_eq!(local.with(mock.scope(), |v| *v), Some(1));
+    }
+
+    #[test]
+    fn cycle_local_update_waits_for_process() {
+        let mock = MockProcessScope::new(4);
+        let local = Arc::new(CycleLocal::new(0));
+        local
+            .with(mock.scope(), |v| {
+                let updater = {
+                    let local = local.clone();
+                    thread::spawn(move || local.update(|v| *v += 1))
+                };
+                thread::sleep(Duration::from_millis(50));
+                // The update can't start while the value is used.
+                assert_eq!(local.epoch(), 0);
+                *v = 10;
+                updater
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(local.with(mock.scope(), |v| *v), Some(11));
+    }
+
+    #[test]
+    fn cycle_local_is_released_on_panic() {
+        let mock = MockProcessScope::new(4);
+        let local = CycleLocal::new(0);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            local.with(mock.scope(), |_| panic!("induced panic"))
+        }));
+        assert!(res.is_err());
+        local.update(|v| *v = 1);
+        assert_eq!(local.with(mock.scope(), |v| *v), Some(1));
+    }
+}
@@ -1,7 +1,15 @@
-use crate::{Client, Control, NotificationHandler, ProcessHandler, ProcessScope};
+use crate::{
+    BufferSizeScope, CallbackSelection, Client, ClientStatus, Control, Frames, LatencyScope,
+    LatencyType, NotificationHandler, PortId, PortRegistrationInfo, ProcessHandler, ProcessScope,
+};
 
 /// A trivial handler that does nothing.
-impl NotificationHandler for () {}
+impl NotificationHandler for () {
+    /// Select no callbacks since none of them are handled.
+    fn callbacks(&self) -> CallbackSelection {
+        CallbackSelection::empty()
+    }
+}
 
 /// A trivial handler that does nothing.
 impl ProcessHandler for () {
@@ -13,13 +21,13 @@ impl ProcessHandler for () {
 
 /// Wrap a closure that can handle the `process` callback. This is called every time data from ports
 /// is available from JACK.
-pub struct ClosureProcessHandler<F: 'static + Send + FnMut(&Client, &ProcessScope) -> Control> {
+pub struct ClosureProcessHandler<F: Send + FnMut(&Client, &ProcessScope) -> Control> {
     pub process_fn: F,
 }
 
 impl<F> ClosureProcessHandler<F>
 where
-    F: 'static + Send + FnMut(&Client, &ProcessScope) -> Control,
+    F: Send + FnMut(&Client, &ProcessScope) -> Control,
 {
     pub fn new(f: F) -> ClosureProcessHandler<F> {
         ClosureProcessHandler { process_fn: f }
@@ -28,9 +36,140 @@ where
 
 impl<F> ProcessHandler for ClosureProcessHandler<F>
 where
-    F: 'static + Send + FnMut(&Client, &ProcessScope) -> Control,
+    F: Send + FnMut(&Client, &ProcessScope) -> Control,
 {
     fn process(&mut self, c: &Client, ps: &ProcessScope) -> Control {
         (self.process_fn)(c, ps)
     }
 }
+
+/// Forwards every callback to the boxed handler, which allows choosing the handler at runtime
+/// with `Box<dyn NotificationHandler + Sync>`.
+///
+/// Each callback costs one extra indirect call through the vtable.
+impl<T: NotificationHandler + ?Sized> NotificationHandler for Box<T> {
+    fn thread_init(&self, c: &Client) {
+        (**self).thread_init(c)
+    }
+
+    fn shutdown(&mut self, status: ClientStatus, reason: &str) {
+        (**self).shutdown(status, reason)
+    }
+
+    fn freewheel(&mut self, c: &Client, is_freewheel_enabled: bool) {
+        (**self).freewheel(c, is_freewheel_enabled)
+    }
+
+    fn sample_rate(&mut self, c: &Client, srate: Frames) -> Control {
+        (**self).sample_rate(c, srate)
+    }
+
+    fn client_registration(&mut self, c: &Client, name: &str, is_registered: bool) {
+        (**self).client_registration(c, name, is_registered)
+    }
+
+    fn port_registration(&mut self, c: &Client, port_id: PortId, is_registered: bool) {
+        (**self).port_registration(c, port_id, is_registered)
+    }
+
+    fn port_registration_with_info(
+        &mut self,
+        c: &Client,
+        info: &PortRegistrationInfo,
+        is_registered: bool,
+    ) {
+        (**self).port_registration_with_info(c, info, is_registered)
+    }
+
+    fn port_rename(
+        &mut self,
+        c: &Client,
+        port_id: PortId,
+        old_name: &str,
+        new_name: &str,
+    ) -> Control {
+        (**self).port_rename(c, port_id, old_name, new_name)
+    }
+
+    fn ports_connected(
+        &mut self,
+        c: &Client,
+        port_id_a: PortId,
+        port_id_b: PortId,
+        are_connected: bool,
+    ) {
+        (**self).ports_connected(c, port_id_a, port_id_b, are_connected)
+    }
+
+    fn ports_connected_with_names(
+        &mut self,
+        c: &Client,
+        port_id_a: PortId,
+        port_name_a: &str,
+        port_id_b: PortId,
+        port_name_b: &str,
+        are_connected: bool,
+    ) {
+        (**self).ports_connected_with_names(
+            c,
+            port_id_a,
+            port_name_a,
+            port_id_b,
+            port_name_b,
+            are_connected,
+        )
+    }
+
+    fn graph_reorder(&mut self, c: &Client) -> Control {
+        (**self).graph_reorder(c)
+    }
+
+    fn xrun(&mut self, c: &Client) -> Control {
+        (**self).xrun(c)
+    }
+
+    fn xrun_with_delay(&mut self, c: &Client, delayed_usecs: f32) -> Control {
+        (**self).xrun_with_delay(c, delayed_usecs)
+    }
+
+    fn latency(&mut self, c: &Client, mode: LatencyType) {
+        (**self).latency(c, mode)
+    }
+
+    fn latency_with_scope(&mut self, scope: &LatencyScope) {
+        (**self).latency_with_scope(scope)
+    }
+
+    #[cfg(feature = "metadata")]
+    fn property_change(
+        &mut self,
+        c: &Client,
+        subject: crate::Uuid,
+        key: Option<&str>,
+        change: crate::PropertyChangeKind,
+    ) {
+        (**self).property_change(c, subject, key, change)
+    }
+
+    fn callbacks(&self) -> CallbackSelection {
+        (**self).callbacks()
+    }
+}
+
+/// Forwards every callback to the boxed handler, which allows choosing the handler at runtime
+/// with `Box<dyn ProcessHandler>`.
+///
+/// Each callback costs one extra indirect call through the vtable.
+impl<T: ProcessHandler + ?Sized> ProcessHandler for Box<T> {
+    fn process(&mut self, c: &Client, ps: &ProcessScope) -> Control {
+        (**self).process(c, ps)
+    }
+
+    fn buffer_size(&mut self, c: &Client, size: Frames) -> Control {
+        (**self).buffer_size(c, size)
+    }
+
+    fn buffer_size_with_scope(&mut self, scope: &BufferSizeScope) -> Control {
+        (**self).buffer_size_with_scope(scope)
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{Client, Frames, LatencyType, Port, PortFlags, PortSpec, Unowned};
+
+/// Provides the information needed to compute the latencies of a client's ports within the
+/// latency callback.
+///
+/// JACK calls the latency callback once per `LatencyType`. For `LatencyType::Capture`, the latency
+/// flows from the client's input ports to its output ports; for `LatencyType::Playback` it flows
+/// from the output ports to the input ports. The ports the latency flows from are the "upstream"
+/// ports and the ports that have to be updated are returned by `ports_to_update`.
+///
+/// # Example
+/// A client whose output is delayed by `delay` frames relative to its input, where all inputs and
+/// outputs are considered to be a single signal pathway:
+/// ```
+/// struct Delay {
+///     delay: jack::Frames,
+/// }
+///
+/// impl jack::NotificationHandler for Delay {
+///     fn latency_with_scope(&mut self, scope: &jack::LatencyScope) {
+///         let (min, max) = scope.upstream_range();
+///         for port in scope.ports_to_update() {
+///             scope.set_range(&port, (min + self.delay, max + self.delay));
+///         }
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct LatencyScope<'a> {
+    client: &'a Client,
+    mode: LatencyType,
+}
+
+impl<'a> LatencyScope<'a> {
+    pub(crate) fn new(client: &'a Client, mode: LatencyType) -> LatencyScope<'a> {
+        LatencyScope { client, mode }
+    }
+
+    /// The type of latency that is being computed.
+    pub fn mode(&self) -> LatencyType {
+        self.mode
+    }
+
+    /// The client whose latencies are being computed.
+    pub fn client(&self) -> &'a Client {
+        self.client
+    }
+
+    /// The ports of this client whose latency range has to be set for this `mode`. These are the
+    /// output ports for `LatencyType::Capture` and the input ports for `LatencyType::Playback`.
+    ///
+    /// # Remarks
+    /// * Allocates, the latency callback does not run in real-time.
+    pub fn ports_to_update(&self) -> Vec<Port<Unowned>> {
+        match self.mode {
+            LatencyType::Capture => self.own_ports(PortFlags::IS_OUTPUT),
+            LatencyType::Playback => self.own_ports(PortFlags::IS_INPUT),
+        }
+    }
+
+    /// The ports of this client that the latency flows from for this `mode`. These are the input
+    /// ports for `LatencyType::Capture` and the output ports for `LatencyType::Playback`. Their
+    /// latency range is the range of the ports they are connected to.
+    ///
+    /// # Remarks
+    /// * Allocates, the latency callback does not run in real-time.
+    pub fn upstream_ports(&self) -> Vec<Port<Unowned>> {
+        match self.mode {
+            LatencyType::Capture => self.own_ports(PortFlags::IS_INPUT),
+            LatencyType::Playback => self.own_ports(PortFlags::IS_OUTPUT),
+        }
+    }
+
+    /// The smallest range that contains the ranges of all `upstream_ports`, or `(0, 0)` if there
+    /// are none.
+    pub fn upstream_range(&self) -> (Frames, Frames) {
+        self.upstream_ports()
+            .iter()
+            .map(|p| self.get_range(p))
+            .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+            .unwrap_or((0, 0))
+    }
+
+    /// Get the latency range of `port` for this `mode`.
+    pub fn get_range<PS: PortSpec>(&self, port: &Port<PS>) -> (Frames, Frames) {
+        port.get_latency_range(self.mode)
+    }
+
+    /// Set the latency range of `port` for this `mode`.
+    pub fn set_range<PS: PortSpec>(&self, port: &Port<PS>, range: (Frames, Frames)) {
+        port.set_latency_range(self.mode, range)
+    }
+
+    fn own_ports(&self, flags: PortFlags) -> Vec<Port<Unowned>> {
+        self.client
+            .ports(None, None, flags)
+            .iter()
+            .filter_map(|name| self.client.port_by_name(name))
+            .filter(|p| self.client.is_mine(p))
+            .collect()
+    }
+}
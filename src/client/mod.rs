@@ -1,8 +1,30 @@
 mod async_client;
+mod buffer_pool;
+mod buffer_size_scope;
+mod callback_selection;
 mod callbacks;
 mod client_impl;
 mod common;
+mod cycle_local;
 mod handler_impls;
+mod latency;
+mod name_registry;
+mod notifications;
+mod pending_connection;
+mod port_cache;
+mod port_watch;
+mod process_thread;
+mod resilient;
+mod retry;
+#[cfg(feature = "session")]
+mod session;
+mod shared_handler;
+mod shutdown;
+mod timebase;
+mod timing;
+mod watchdog;
+mod weak_client;
+mod xrun;
 
 /// Contains `ClientOptions` flags used when opening a client.
 mod client_options;
@@ -11,13 +33,42 @@ mod client_options;
 mod client_status;
 
 pub use self::async_client::AsyncClient;
+pub use self::buffer_pool::{BufferPool, PooledProcessHandler};
+pub use self::buffer_size_scope::BufferSizeScope;
+pub use self::callback_selection::CallbackSelection;
 pub use self::callbacks::{NotificationHandler, ProcessHandler};
-pub use self::client_impl::{Client, CycleTimes, ProcessScope};
+pub use self::client_impl::{Client, CycleTimes, ProcessScope, MAX_BUFFER_SIZE, MIN_BUFFER_SIZE};
 pub use self::client_options::ClientOptions;
 pub use self::client_status::ClientStatus;
 pub use self::common::CLIENT_NAME_SIZE;
+pub use self::cycle_local::CycleLocal;
+pub use self::shared_handler::{SharedNotificationHandler, SharedProcessHandler};
+pub use self::shutdown::{ShutdownReceiver, SHUTDOWN_REASON_SIZE};
+pub use self::timebase::TimebaseHandler;
+pub use self::timing::{TimingStats, TIMING_HISTOGRAM_BINS};
+pub use self::watchdog::{Watchdog, WatchdogEvent};
+pub use self::weak_client::WeakClient;
+pub use self::xrun::XrunStats;
 
 pub use self::handler_impls::ClosureProcessHandler;
+pub use self::latency::LatencyScope;
+pub use self::name_registry::NameRegistry;
+#[cfg(feature = "async")]
+pub use self::notifications::NotificationStream;
+pub use self::notifications::{Notification, NotificationReceiver, Notifications};
+pub use self::pending_connection::{ConnectionStatus, PendingConnection};
+pub use self::port_cache::PortRegistrationInfo;
+pub use self::port_watch::{PortEvent, PortEventReceiver};
+pub use self::process_thread::ProcessThreadContext;
+pub use self::resilient::{
+    ManifestPorts, PortManifest, ResilientClient, ResilientConfig, ResilientEvent,
+};
+pub use self::retry::wait_for_server;
+#[cfg(feature = "session")]
+pub use self::session::{
+    session_available, ClosureSessionHandler, SessionCommand, SessionEvent, SessionEventType,
+    SessionFlags, SessionHandler, SessionSupport, SESSION_DIR_PLACEHOLDER, SESSION_UUID_ARG,
+};
 
 // client.rs excluding functionality that involves ports or callbacks
 #[cfg(test)]
@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time;
+
+use crate::{Client, PortFlags, PortId, Uuid, WeakClient};
+
+/// The number of locks that the ports are spread over by id.
+const SHARDS: usize = 8;
+
+/// How long the name of a port or client is kept after it was unregistered.
+const RETENTION: time::Duration = time::Duration::from_secs(1);
+
+/// The largest port id that is looked at when the registry is filled with the existing ports.
+const MAX_SEEDED_PORT_ID: PortId = 1 << 16;
+
+/// The number of unused port ids in a row after which filling the registry stops looking for the
+/// ports that were not found yet, which were likely unregistered in the meantime.
+const MAX_MISSED_PORT_IDS: PortId = 1024;
+
+#[derive(Clone, Debug)]
+struct Entry {
+    name: String,
+    unregistered_at: Option<time::Instant>,
+}
+
+impl Entry {
+    fn registered(name: &str) -> Entry {
+        Entry {
+            name: name.to_string(),
+            unregistered_at: None,
+        }
+    }
+
+    /// Returns `true` if the entry is registered, or was unregistered within `RETENTION`.
+    fn is_current(&self, now: time::Instant) -> bool {
+        match self.unregistered_at {
+            None => true,
+            Some(t) => now.saturating_duration_since(t) <= RETENTION,
+        }
+    }
+}
+
+/// The names of the ports by id and of the clients by uuid, kept up to date from the registration
+/// notifications of an `AsyncClient`, see `AsyncClient::name_registry`.
+///
+/// Port lookups only take a read lock on a part of the registry and never call into JACK, so they
+/// may be done from any thread, including from within the notification callbacks. The registry is
+/// updated before the `NotificationHandler` is notified of a registration.
+///
+/// JACK only passes the name of a client that registers, and asking the server for its uuid from
+/// the notification thread may deadlock. So the names of new clients are kept aside, and
+/// `name_of_client` asks the server for their uuids on the next lookup. It must therefore not be
+/// called from the notification callbacks.
+///
+/// # Staleness
+/// Once a port or client is unregistered, its name is still returned by `name_of_port` and
+/// `name_of_client` for one second, so that the notifications caused by the unregistration, like
+/// the connections that it removed, can still be labeled. It is no longer listed by `all_ports`.
+/// JACK reuses the ids of ports, and a port that is registered with the id of an unregistered port
+/// replaces it right away.
+///
+/// Renames are out of scope: the crate can't register the port rename callback, so a renamed port
+/// keeps its old name in the registry until it is unregistered.
+#[derive(Debug, Default)]
+pub struct NameRegistry {
+    enabled: AtomicBool,
+    ports: [RwLock<HashMap<PortId, Entry>>; SHARDS],
+    clients: RwLock<HashMap<Uuid, Entry>>,
+    // The names of the registered clients whose uuids were not looked up yet.
+    pending_clients: Mutex<Vec<String>>,
+    client: RwLock<Option<WeakClient>>,
+}
+
+impl NameRegistry {
+    /// The full name of the port with `id`, if it is registered or was unregistered recently.
+    pub fn name_of_port(&self, id: PortId) -> Option<String> {
+        self.port_name_at(id, time::Instant::now())
+    }
+
+    /// The name of the client with `uuid`, if it is registered or was unregistered recently.
+    ///
+    /// Asks the server for the uuids of the clients that registered since the last lookup, so it
+    /// must not be called from the notification callbacks.
+    pub fn name_of_client(&self, uuid: Uuid) -> Option<String> {
+        self.resolve_pending_clients();
+        self.client_name_at(uuid, time::Instant::now())
+    }
+
+    /// The ids and full names of the registered ports, ordered by id.
+    pub fn all_ports(&self) -> Vec<(PortId, String)> {
+        let mut ports: Vec<_> = self
+            .ports
+            .iter()
+            .flat_map(|shard| {
+                read(shard)
+                    .iter()
+                    .filter(|(_, e)| e.unregistered_at.is_none())
+                    .map(|(id, e)| (*id, e.name.clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        ports.sort_unstable_by_key(|(id, _)| *id);
+        ports
+    }
+
+    /// Returns `true` once the registry is kept up to date.
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Start keeping the registry up to date, and fill it with the ports and clients that
+    /// `client` can find. Does nothing if it is enabled already.
+    ///
+    /// Clients are found through their ports, so clients without ports are only known once they
+    /// register.
+    pub(crate) fn enable(&self, client: &Client) {
+        if self.enabled.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        *write(&self.client) = Some(client.downgrade());
+        let now = time::Instant::now();
+        let missing = client
+            .ports(None, None, PortFlags::empty())
+            .into_iter()
+            .collect();
+        let client_names = self.seed_ports(missing, |id| client.port_by_id(id)?.name().ok(), now);
+        // The name of the client itself may not be UTF-8, so it is not looked up by name.
+        if let Ok(uuid) = Uuid::parse(&client.uuid_string()) {
+            self.client_seen(uuid, &client.name_lossy(), now);
+        }
+        for name in client_names {
+            if let Some(uuid) = client.uuid_by_name(&name) {
+                self.client_seen(uuid, &name, now);
+            }
+        }
+    }
+
+    /// Start keeping the registry up to date without looking for the existing ports and clients.
+    #[cfg(test)]
+    pub(crate) fn enable_unseeded(&self) {
+        self.enabled.store(true, Ordering::Release);
+    }
+
+    /// The names of the registered clients whose uuids were not looked up yet.
+    #[cfg(test)]
+    pub(crate) fn pending_clients(&self) -> Vec<String> {
+        lock(&self.pending_clients).clone()
+    }
+
+    /// Record that the port `id` named `name` was registered.
+    pub(crate) fn port_registered(&self, id: PortId, name: &str, now: time::Instant) {
+        let mut ports = write(self.shard(id));
+        ports.retain(|_, e| e.is_current(now));
+        ports.insert(id, Entry::registered(name));
+    }
+
+    /// Record that the port `id` was unregistered. `name` is used if the port is not known.
+    pub(crate) fn port_unregistered(&self, id: PortId, name: &str, now: time::Instant) {
+        let mut ports = write(self.shard(id));
+        ports.retain(|_, e| e.is_current(now));
+        let entry = ports.entry(id).or_insert_with(|| Entry::registered(name));
+        entry.unregistered_at = Some(now);
+    }
+
+    /// Record that the client named `name` was registered. Its uuid is looked up on the next call
+    /// to `name_of_client`.
+    pub(crate) fn client_registered(&self, name: &str) {
+        let mut pending = lock(&self.pending_clients);
+        if !pending.iter().any(|n| n == name) {
+            pending.push(name.to_string());
+        }
+    }
+
+    /// Record that the client named `name` was unregistered. JACK only passes the name, so the
+    /// client is looked up by it.
+    pub(crate) fn client_unregistered(&self, name: &str, now: time::Instant) {
+        lock(&self.pending_clients).retain(|n| n != name);
+        let mut clients = write(&self.clients);
+        clients.retain(|_, e| e.is_current(now));
+        if let Some(entry) = clients
+            .values_mut()
+            .find(|e| e.unregistered_at.is_none() && e.name == name)
+        {
+            entry.unregistered_at = Some(now);
+        }
+    }
+
+    // Looks up the uuids of the clients that registered since the last call. The server is asked
+    // without holding a lock that the notification callbacks take.
+    fn resolve_pending_clients(&self) {
+        let pending = std::mem::take(&mut *lock(&self.pending_clients));
+        if pending.is_empty() {
+            return;
+        }
+        let client = match read(&self.client).clone() {
+            Some(client) => client,
+            None => return,
+        };
+        let now = time::Instant::now();
+        for name in pending {
+            if let Ok(Some(uuid)) = client.uuid_by_name(&name) {
+                self.client_registered_as(uuid, &name, now);
+            }
+        }
+    }
+
+    fn client_registered_as(&self, uuid: Uuid, name: &str, now: time::Instant) {
+        let mut clients = write(&self.clients);
+        clients.retain(|_, e| e.is_current(now));
+        clients.insert(uuid, Entry::registered(name));
+    }
+
+    fn port_name_at(&self, id: PortId, now: time::Instant) -> Option<String> {
+        read(self.shard(id))
+            .get(&id)
+            .filter(|e| e.is_current(now))
+            .map(|e| e.name.clone())
+    }
+
+    fn client_name_at(&self, uuid: Uuid, now: time::Instant) -> Option<String> {
+        read(&self.clients)
+            .get(&uuid)
+            .filter(|e| e.is_current(now))
+            .map(|e| e.name.clone())
+    }
+
+    // Adds the ports named `missing`, whose ids are looked up with `name_of_id`, and returns the
+    // names of their clients. JACK has no function to get the id of a port, so the ids are searched
+    // for the ports, until all of them were found or there is a long run of unused ids.
+    fn seed_ports<F>(
+        &self,
+        mut missing: HashSet<String>,
+        name_of_id: F,
+        now: time::Instant,
+    ) -> HashSet<String>
+    where
+        F: Fn(PortId) -> Option<String>,
+    {
+        let mut client_names = HashSet::new();
+        let mut misses = 0;
+        for id in 0..MAX_SEEDED_PORT_ID {
+            if missing.is_empty() || misses >= MAX_MISSED_PORT_IDS {
+                break;
+            }
+            let name = match name_of_id(id) {
+                Some(name) => name,
+                None => {
+                    misses += 1;
+                    continue;
+                }
+            };
+            misses = 0;
+            if let Some((client_name, _)) = name.split_once(':') {
+                client_names.insert(client_name.to_string());
+            }
+            missing.remove(&name);
+            self.port_seen(id, &name, now);
+        }
+        client_names
+    }
+
+    // Adds a port found while seeding, unless a notification already recorded it.
+    fn port_seen(&self, id: PortId, name: &str, now: time::Instant) {
+        let mut ports = write(self.shard(id));
+        ports.retain(|_, e| e.is_current(now));
+        ports.entry(id).or_insert_with(|| Entry::registered(name));
+    }
+
+    // Adds a client found while seeding, unless a notification already recorded it.
+    fn client_seen(&self, uuid: Uuid, name: &str, now: time::Instant) {
+        let mut clients = write(&self.clients);
+        clients.retain(|_, e| e.is_current(now));
+        clients
+            .entry(uuid)
+            .or_insert_with(|| Entry::registered(name));
+    }
+
+    fn shard(&self, id: PortId) -> &RwLock<HashMap<PortId, Entry>> {
+        &self.ports[id as usize % SHARDS]
+    }
+}
+
+fn read<T>(lock: &RwLock<T>) -> RwLockReadGuard<'_, T> {
+    lock.read().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn lock<T>(lock: &Mutex<T>) -> MutexGuard<'_, T> {
+    lock.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+fn write<T>(lock: &RwLock<T>) -> RwLockWriteGuard<'_, T> {
+    lock.write().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    fn start_time() -> time::Instant {
+        lazy_static::lazy_static! {
+            static ref START: time::Instant = time::Instant::now();
+        }
+        *START
+    }
+
+    fn at(ms: u64) -> time::Instant {
+        start_time() + Duration::from_millis(ms)
+    }
+
+    fn n_entries(names: &NameRegistry) -> usize {
+        names.ports.iter().map(|shard| read(shard).len()).sum()
+    }
+
+    #[test]
+    fn name_registry_keeps_unregistered_ports_briefly() {
+        let names = NameRegistry::default();
+        names.port_registered(3, "a:out", at(0));
+        names.port_registered(11, "a:in", at(0));
+        assert_eq!(names.port_name_at(3, at(0)), Some("a:out".to_string()));
+        names.port_unregistered(3, "", at(100));
+        assert_eq!(names.port_name_at(3, at(100)), Some("a:out".to_string()));
+        assert_eq!(names.port_name_at(3, at(1100)), Some("a:out".to_string()));
+        assert_eq!(names.port_name_at(3, at(1101)), None);
+        assert_eq!(names.all_ports(), vec![(11, "a:in".to_string())]);
+        assert_eq!(names.port_name_at(4, at(0)), None);
+    }
+
+    #[test]
+    fn name_registry_replaces_unregistered_port_with_same_id() {
+        let names = NameRegistry::default();
+        names.port_registered(3, "a:out", at(0));
+        names.port_unregistered(3, "", at(10));
+        names.port_registered(3, "b:out", at(20));
+        assert_eq!(names.port_name_at(3, at(20)), Some("b:out".to_string()));
+        assert_eq!(names.all_ports(), vec![(3, "b:out".to_string())]);
+    }
+
+    #[test]
+    fn name_registry_labels_ports_unregistered_before_they_were_seen() {
+        let names = NameRegistry::default();
+        names.port_unregistered(5, "gone:out", at(0));
+        assert_eq!(names.port_name_at(5, at(0)), Some("gone:out".to_string()));
+        assert!(names.all_ports().is_empty());
+        // Seeding does not bring the port back.
+        names.port_seen(5, "gone:out", at(1));
+        assert!(names.all_ports().is_empty());
+    }
+
+    #[test]
+    fn name_registry_seeding_gives_up_on_vanished_ports() {
+        use std::cell::Cell;
+        let names = NameRegistry::default();
+        let missing = ["a:out", "b:in", "c:gone"]
+            .iter()
+            .map(|n| n.to_string())
+            .collect();
+        let lookups = Cell::new(0);
+        let name_of_id = |id| {
+            lookups.set(lookups.get() + 1);
+            match id {
+                0 => Some("a:out".to_string()),
+                7 => Some("b:in".to_string()),
+                _ => None,
+            }
+        };
+        let clients = names.seed_ports(missing, name_of_id, at(0));
+        assert_eq!(
+            names.all_ports(),
+            vec![(0, "a:out".to_string()), (7, "b:in".to_string())]
+        );
+        assert_eq!(clients, ["a", "b"].iter().map(|n| n.to_string()).collect());
+        assert_eq!(lookups.get(), 8 + MAX_MISSED_PORT_IDS);
+    }
+
+    #[test]
+    fn name_registry_keeps_unregistered_clients_briefly() {
+        let names = NameRegistry::default();
+        let a = Uuid::from_raw(1);
+        let b = Uuid::from_raw(2);
+        names.client_registered_as(a, "a", at(0));
+        names.client_registered_as(b, "b", at(0));
+        names.client_unregistered("a", at(10));
+        assert_eq!(names.client_name_at(a, at(500)), Some("a".to_string()));
+        assert_eq!(names.client_name_at(a, at(1011)), None);
+        assert_eq!(names.client_name_at(b, at(1011)), Some("b".to_string()));
+        // A client that reopens with the same name gets a new uuid.
+        let a2 = Uuid::from_raw(3);
+        names.client_registered_as(a2, "a", at(20));
+        names.client_unregistered("a", at(30));
+        assert_eq!(names.client_name_at(a2, at(30)), Some("a".to_string()));
+        assert_eq!(names.client_name_at(a2, at(1031)), None);
+    }
+
+    #[test]
+    fn name_registry_keeps_new_clients_aside_until_looked_up() {
+        let names = NameRegistry::default();
+        names.client_registered("a");
+        names.client_registered("b");
+        names.client_registered("a");
+        assert_eq!(names.pending_clients(), ["a", "b"]);
+        names.client_unregistered("a", at(0));
+        assert_eq!(names.pending_clients(), ["b"]);
+        // Without a client to ask, the names are dropped on lookup.
+        assert_eq!(names.name_of_client(Uuid::from_raw(1)), None);
+        assert!(names.pending_clients().is_empty());
+    }
+
+    #[test]
+    fn name_registry_stays_bounded_under_churn() {
+        let names = NameRegistry::default();
+        for round in 0..2000u64 {
+            let id = round as PortId;
+            let name = format!("churn:p{}", round);
+            let now = at(round * 10);
+            names.port_registered(id, &name, now);
+            assert_eq!(names.port_name_at(id, now), Some(name.clone()));
+            names.port_unregistered(id, "", now);
+            // Labels of the unregistration are still available shortly after.
+            let later = at(round * 10 + 5);
+            assert_eq!(names.port_name_at(id, later), Some(name));
+            if round >= 150 {
+                let old = (round - 150) as PortId;
+                assert_eq!(names.port_name_at(old, now), None);
+            }
+        }
+        assert!(names.all_ports().is_empty());
+        // Only the ports unregistered within the last second, spread over the shards, are kept.
+        assert!(n_entries(&names) <= 101 + SHARDS, "{}", n_entries(&names));
+    }
+
+    #[test]
+    fn name_registry_is_readable_while_updated() {
+        use std::sync::Arc;
+        let names = Arc::new(NameRegistry::default());
+        let writer = {
+            let names = names.clone();
+            std::thread::spawn(move || {
+                for round in 0..10_000u32 {
+                    let now = time::Instant::now();
+                    names.port_registered(round % 64, "w:p", now);
+                    names.port_unregistered(round % 64, "", now);
+                }
+            })
+        };
+        while !writer.is_finished() {
+            for id in 0..64 {
+                if let Some(name) = names.name_of_port(id) {
+                    assert_eq!(name, "w:p");
+                }
+            }
+        }
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,443 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::Waker;
+use std::time;
+
+use crate::{
+    Client, ClientStatus, Control, Frames, LatencyType, NotificationHandler, PortId,
+    PortRegistrationInfo,
+};
+
+/// A JACK notification, owning all of its data.
+///
+/// Produced by the `Notifications` handler.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Notification {
+    /// The client was shut down by the JACK server.
+    Shutdown {
+        status: ClientStatus,
+        reason: String,
+    },
+    /// "Freewheel" mode was entered or left.
+    Freewheel { enabled: bool },
+    /// The system sample rate changed.
+    SampleRateChanged(Frames),
+    /// A client was registered.
+    ClientRegistered { name: String },
+    /// A client was unregistered.
+    ClientUnregistered { name: String },
+    /// A port was registered. `name` is `None` if the port was gone before it could be resolved.
+    PortRegistered { id: PortId, name: Option<String> },
+    /// A port was unregistered. `name` is `None` if the port was never resolved.
+    PortUnregistered { id: PortId, name: Option<String> },
+    /// A port was renamed.
+    PortRenamed {
+        id: PortId,
+        old_name: String,
+        new_name: String,
+    },
+    /// Two ports were connected or disconnected. See
+    /// `NotificationHandler::ports_connected_with_names` on how the names are resolved.
+    PortsConnected {
+        a: PortId,
+        a_name: String,
+        b: PortId,
+        b_name: String,
+        connected: bool,
+    },
+    /// The processing graph was reordered.
+    GraphReordered,
+    /// An xrun occurred.
+    XRun { delayed_usecs: f32 },
+    /// The latencies of the given type have to be recomputed.
+    Latency(LatencyType),
+    /// A Metadata property changed. `key` is `None` if all the properties of the subject changed.
+    #[cfg(feature = "metadata")]
+    PropertyChanged {
+        subject: crate::Uuid,
+        key: Option<String>,
+        change: crate::PropertyChangeKind,
+    },
+}
+
+/// A `NotificationHandler` that sends every notification over a bounded channel.
+///
+/// This is convenient for applications that have an event loop, such as GUIs, that can poll the
+/// `NotificationReceiver` instead of implementing `NotificationHandler`. Strings are resolved and
+/// copied within the callback so the notifications remain valid after it returns. If the channel
+/// is full, the notification is dropped and counted, see `NotificationReceiver::dropped`.
+///
+/// # Example
+/// ```no_run
+/// let (client, _status) =
+///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let (notifications, receiver) = jack::Notifications::channel(64);
+/// let _active_client = client.activate_async(notifications, ()).unwrap();
+/// for notification in receiver.try_iter() {
+///     println!("{:?}", notification);
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Notifications {
+    // Only `None` while dropping.
+    sender: Option<SyncSender<Notification>>,
+    shared: Arc<Shared>,
+}
+
+/// State shared between `Notifications` and its `NotificationReceiver`.
+#[derive(Debug, Default)]
+struct Shared {
+    dropped: AtomicUsize,
+    // Woken whenever a notification is sent or the handler is dropped.
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Shared {
+    fn wake(&self) {
+        let waker = self
+            .waker
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl Notifications {
+    /// Create a handler and the receiver for its notifications. At most `capacity` notifications
+    /// are buffered.
+    pub fn channel(capacity: usize) -> (Notifications, NotificationReceiver) {
+        let (sender, receiver) = mpsc::sync_channel(capacity);
+        let shared = Arc::new(Shared::default());
+        let notifications = Notifications {
+            sender: Some(sender),
+            shared: shared.clone(),
+        };
+        (notifications, NotificationReceiver { receiver, shared })
+    }
+
+    fn send(&self, notification: Notification) {
+        let sender = match self.sender.as_ref() {
+            Some(sender) => sender,
+            None => return,
+        };
+        match sender.try_send(notification) {
+            Ok(()) => self.shared.wake(),
+            Err(TrySendError::Disconnected(_)) => (),
+            Err(TrySendError::Full(_)) => {
+                self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+impl Drop for Notifications {
+    /// Wake the receiver so that it can observe that no more notifications will be sent.
+    fn drop(&mut self) {
+        // Disconnect the channel before waking, otherwise the receiver may go back to sleep.
+        self.sender = None;
+        self.shared.wake();
+    }
+}
+
+impl NotificationHandler for Notifications {
+    fn shutdown(&mut self, status: ClientStatus, reason: &str) {
+        self.send(Notification::Shutdown {
+            status,
+            reason: reason.to_string(),
+        })
+    }
+
+    fn freewheel(&mut self, _: &Client, is_freewheel_enabled: bool) {
+        self.send(Notification::Freewheel {
+            enabled: is_freewheel_enabled,
+        })
+    }
+
+    fn sample_rate(&mut self, _: &Client, srate: Frames) -> Control {
+        self.send(Notification::SampleRateChanged(srate));
+        Control::Continue
+    }
+
+    fn client_registration(&mut self, _: &Client, name: &str, is_registered: bool) {
+        let name = name.to_string();
+        self.send(if is_registered {
+            Notification::ClientRegistered { name }
+        } else {
+            Notification::ClientUnregistered { name }
+        })
+    }
+
+    fn port_registration_with_info(
+        &mut self,
+        _: &Client,
+        info: &PortRegistrationInfo,
+        is_registered: bool,
+    ) {
+        let id = info.id;
+        let name = if info.name.is_empty() {
+            None
+        } else {
+            Some(info.name.clone())
+        };
+        self.send(if is_registered {
+            Notification::PortRegistered { id, name }
+        } else {
+            Notification::PortUnregistered { id, name }
+        })
+    }
+
+    fn port_rename(
+        &mut self,
+        _: &Client,
+        port_id: PortId,
+        old_name: &str,
+        new_name: &str,
+    ) -> Control {
+        self.send(Notification::PortRenamed {
+            id: port_id,
+            old_name: old_name.to_string(),
+            new_name: new_name.to_string(),
+        });
+        Control::Continue
+    }
+
+    fn ports_connected_with_names(
+        &mut self,
+        _: &Client,
+        port_id_a: PortId,
+        port_name_a: &str,
+        port_id_b: PortId,
+        port_name_b: &str,
+        are_connected: bool,
+    ) {
+        self.send(Notification::PortsConnected {
+            a: port_id_a,
+            a_name: port_name_a.to_string(),
+            b: port_id_b,
+            b_name: port_name_b.to_string(),
+            connected: are_connected,
+        })
+    }
+
+    fn graph_reorder(&mut self, _: &Client) -> Control {
+        self.send(Notification::GraphReordered);
+        Control::Continue
+    }
+
+    fn xrun_with_delay(&mut self, _: &Client, delayed_usecs: f32) -> Control {
+        self.send(Notification::XRun { delayed_usecs });
+        Control::Continue
+    }
+
+    fn latency(&mut self, _: &Client, mode: LatencyType) {
+        self.send(Notification::Latency(mode))
+    }
+
+    #[cfg(feature = "metadata")]
+    fn property_change(
+        &mut self,
+        _: &Client,
+        subject: crate::Uuid,
+        key: Option<&str>,
+        change: crate::PropertyChangeKind,
+    ) {
+        self.send(Notification::PropertyChanged {
+            subject,
+            key: key.map(str::to_string),
+            change,
+        })
+    }
+}
+
+/// Receives the notifications sent by a `Notifications` handler.
+#[derive(Debug)]
+pub struct NotificationReceiver {
+    receiver: Receiver<Notification>,
+    shared: Arc<Shared>,
+}
+
+impl NotificationReceiver {
+    /// Block until a notification is available. Returns `None` once the handler has been dropped
+    /// and all notifications have been received.
+    pub fn recv(&self) -> Option<Notification> {
+        self.receiver.recv().ok()
+    }
+
+    /// Block until a notification is available or `timeout` has passed. Returns `None` on timeout
+    /// or once the handler has been dropped and all notifications have been received.
+    pub fn recv_timeout(&self, timeout: time::Duration) -> Option<Notification> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(n) => Some(n),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Get the next notification if one is available. Does not block.
+    pub fn try_recv(&self) -> Option<Notification> {
+        match self.receiver.try_recv() {
+            Ok(n) => Some(n),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+
+    /// Iterate over the notifications that are currently available. Does not block.
+    pub fn try_iter(&self) -> impl Iterator<Item = Notification> + '_ {
+        self.receiver.try_iter()
+    }
+
+    /// The number of notifications that were dropped because the channel was full.
+    pub fn dropped(&self) -> usize {
+        self.shared.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Convert into a `Stream` of notifications for use with async executors.
+    #[cfg(feature = "async")]
+    pub fn into_stream(self) -> NotificationStream {
+        NotificationStream { receiver: self }
+    }
+}
+
+/// An asynchronous stream of the notifications sent by a `Notifications` handler.
+///
+/// The stream does not depend on any particular executor. The JACK notification thread wakes the
+/// task that is polling the stream whenever a notification is sent. The stream ends once the
+/// handler has been dropped, for example because the client was deactivated.
+///
+/// Obtained through `NotificationReceiver::into_stream`.
+#[cfg(feature = "async")]
+#[derive(Debug)]
+pub struct NotificationStream {
+    receiver: NotificationReceiver,
+}
+
+#[cfg(feature = "async")]
+impl NotificationStream {
+    /// The number of notifications that were dropped because the channel was full.
+    pub fn dropped(&self) -> usize {
+        self.receiver.dropped()
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for NotificationStream {
+    type Item = Notification;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Notification>> {
+        use std::task::Poll;
+        let receiver = &self.receiver;
+        for attempt in 0..2 {
+            match receiver.receiver.try_recv() {
+                Ok(n) => return Poll::Ready(Some(n)),
+                Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+                Err(TryRecvError::Empty) if attempt == 0 => {
+                    // Register before checking again so a notification sent in between is not
+                    // missed.
+                    *receiver
+                        .shared
+                        .waker
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner) = Some(cx.waker().clone());
+                }
+                Err(TryRecvError::Empty) => (),
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn notifications_are_received_in_order() {
+        let (n, rx) = Notifications::channel(4);
+        n.send(Notification::GraphReordered);
+        n.send(Notification::SampleRateChanged(44100));
+        assert_eq!(rx.try_recv(), Some(Notification::GraphReordered));
+        assert_eq!(rx.recv(), Some(Notification::SampleRateChanged(44100)));
+        assert_eq!(rx.try_recv(), None);
+        assert_eq!(rx.dropped(), 0);
+    }
+
+    #[test]
+    fn notifications_are_dropped_and_counted_when_full() {
+        let (n, rx) = Notifications::channel(2);
+        for _ in 0..5 {
+            n.send(Notification::GraphReordered);
+        }
+        assert_eq!(rx.try_iter().count(), 2);
+        assert_eq!(rx.dropped(), 3);
+    }
+
+    #[test]
+    fn notification_receiver_ends_when_handler_is_dropped() {
+        let (n, rx) = Notifications::channel(2);
+        n.send(Notification::Freewheel { enabled: true });
+        drop(n);
+        assert_eq!(rx.recv(), Some(Notification::Freewheel { enabled: true }));
+        assert_eq!(rx.recv(), None);
+        assert_eq!(rx.recv_timeout(time::Duration::from_millis(10)), None);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn notification_stream_yields_notifications_until_handler_is_dropped() {
+        use futures_executor::block_on_stream;
+        let (n, rx) = Notifications::channel(4);
+        let stream = rx.into_stream();
+        let sender = std::thread::spawn(move || {
+            n.send(Notification::GraphReordered);
+            std::thread::sleep(time::Duration::from_millis(50));
+            n.send(Notification::SampleRateChanged(48000));
+        });
+        let received: Vec<_> = block_on_stream(stream).collect();
+        sender.join().unwrap();
+        assert_eq!(
+            received,
+            vec![
+                Notification::GraphReordered,
+                Notification::SampleRateChanged(48000)
+            ]
+        );
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn notification_stream_wakes_local_executor() {
+        use futures_executor::LocalPool;
+        use std::future::Future;
+        use std::pin::Pin;
+        use std::task::{Context, Poll};
+
+        struct Next<'a>(&'a mut NotificationStream);
+
+        impl Future for Next<'_> {
+            type Output = Option<Notification>;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                futures_core::Stream::poll_next(Pin::new(&mut *self.0), cx)
+            }
+        }
+
+        let (n, rx) = Notifications::channel(4);
+        let mut stream = rx.into_stream();
+        let mut pool = LocalPool::new();
+        let sender = std::thread::spawn(move || {
+            std::thread::sleep(time::Duration::from_millis(50));
+            n.send(Notification::XRun { delayed_usecs: 1.0 });
+        });
+        let first = pool.run_until(Next(&mut stream));
+        sender.join().unwrap();
+        assert_eq!(first, Some(Notification::XRun { delayed_usecs: 1.0 }));
+        assert_eq!(pool.run_until(Next(&mut stream)), None);
+        assert_eq!(stream.dropped(), 0);
+    }
+}
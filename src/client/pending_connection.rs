@@ -0,0 +1,319 @@
+use jack_sys as j;
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::{ffi, thread, time};
+
+use super::weak_client::WeakClient;
+use crate::logging;
+use crate::{Error, PortErrorDetails, PortErrorKind};
+
+/// The state of a `PendingConnection`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConnectionStatus {
+    /// At least one of the ports is not registered yet.
+    Pending,
+    /// The ports are connected. This includes ports that were already connected.
+    Connected,
+    /// The ports could not be connected, or were not both registered in time. The latter fails
+    /// with `Error::PortConnectionTimeout`.
+    Failed(Error),
+}
+
+/// The threads of `PendingConnection`s, woken up by the port registration callback.
+#[derive(Debug, Default)]
+pub struct RegistrationSubscribers {
+    subscribers: Mutex<Subscribers>,
+}
+
+#[derive(Debug, Default)]
+struct Subscribers {
+    next_id: u64,
+    senders: Vec<(u64, mpsc::Sender<()>)>,
+}
+
+impl RegistrationSubscribers {
+    fn lock(&self) -> MutexGuard<'_, Subscribers> {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Send to `sender` for every registered port, until `unsubscribe` is called with the returned
+    /// id.
+    pub fn subscribe(&self, sender: mpsc::Sender<()>) -> u64 {
+        let mut subscribers = self.lock();
+        let id = subscribers.next_id;
+        subscribers.next_id += 1;
+        subscribers.senders.push((id, sender));
+        id
+    }
+
+    /// Drop the sender of the subscription `id`.
+    pub fn unsubscribe(&self, id: u64) {
+        self.lock().senders.retain(|(i, _)| *i != id);
+    }
+
+    #[cfg(test)]
+    pub fn is_empty(&self) -> bool {
+        self.lock().senders.is_empty()
+    }
+
+    /// Notify the subscribers that a port was registered. Does not call into JACK.
+    pub fn port_registered(&self) {
+        self.lock().senders.retain(|(_, s)| s.send(()).is_ok());
+    }
+}
+
+/// A connection between two ports that is made once both of them are registered, see
+/// `AsyncClient::connect_when_available`.
+///
+/// The connection is made on a thread of its own. Dropping the handle cancels the connection if it
+/// was not made yet, and waits for the thread to exit.
+#[derive(Debug)]
+pub struct PendingConnection {
+    source_port: String,
+    destination_port: String,
+    outcome: Option<Result<(), Error>>,
+    result: Option<mpsc::Receiver<Result<(), Error>>>,
+    subscription: Option<(Arc<RegistrationSubscribers>, u64)>,
+    wake: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl PendingConnection {
+    /// A connection whose outcome is already known.
+    pub(crate) fn done(
+        source_port: &str,
+        destination_port: &str,
+        outcome: Result<(), Error>,
+    ) -> PendingConnection {
+        PendingConnection {
+            source_port: source_port.to_string(),
+            destination_port: destination_port.to_string(),
+            outcome: Some(outcome),
+            result: None,
+            subscription: None,
+            wake: None,
+            thread: None,
+        }
+    }
+
+    /// Connect `source_port` to `destination_port` on a new thread once both are registered. The
+    /// thread checks the ports every time `subscribers` is notified, until `timeout` has passed. A
+    /// timeout too large to compute a deadline for waits until the connection is cancelled.
+    pub(crate) fn start(
+        client: WeakClient,
+        subscribers: Arc<RegistrationSubscribers>,
+        source_port: ffi::CString,
+        destination_port: ffi::CString,
+        timeout: time::Duration,
+    ) -> PendingConnection {
+        let deadline = time::Instant::now().checked_add(timeout);
+        let (wake_tx, wake_rx) = mpsc::channel();
+        let (result_tx, result_rx) = mpsc::channel();
+        // Subscribe before the first check so that no registration is missed in between.
+        let id = subscribers.subscribe(wake_tx.clone());
+        let (source, destination) = (source_port.clone(), destination_port.clone());
+        let thread = thread::spawn(move || {
+            connect_when_available(client, &source, &destination, deadline, wake_rx, result_tx)
+        });
+        PendingConnection {
+            source_port: source_port.to_string_lossy().into_owned(),
+            destination_port: destination_port.to_string_lossy().into_owned(),
+            outcome: None,
+            result: Some(result_rx),
+            subscription: Some((subscribers, id)),
+            wake: Some(wake_tx),
+            thread: Some(thread),
+        }
+    }
+
+    /// The full name of the source port.
+    pub fn source_port(&self) -> &str {
+        &self.source_port
+    }
+
+    /// The full name of the destination port.
+    pub fn destination_port(&self) -> &str {
+        &self.destination_port
+    }
+
+    /// Get the state of the connection. Does not block.
+    pub fn try_status(&mut self) -> ConnectionStatus {
+        if self.outcome.is_none() {
+            match self.result.as_ref().map(mpsc::Receiver::try_recv) {
+                Some(Ok(outcome)) => self.outcome = Some(outcome),
+                Some(Err(TryRecvError::Empty)) => return ConnectionStatus::Pending,
+                Some(Err(TryRecvError::Disconnected)) | None => {
+                    self.outcome = Some(Err(Error::ThreadJoinError))
+                }
+            }
+        }
+        match self.outcome.as_ref() {
+            Some(Ok(())) => ConnectionStatus::Connected,
+            Some(Err(err)) => ConnectionStatus::Failed(err.clone()),
+            None => ConnectionStatus::Pending,
+        }
+    }
+
+    /// Block until the ports are connected, the connection failed, or the timeout has passed.
+    pub fn wait(mut self) -> Result<(), Error> {
+        if let Some(outcome) = self.outcome.take() {
+            return outcome;
+        }
+        match self.result.take().map(|r| r.recv()) {
+            Some(Ok(outcome)) => outcome,
+            Some(Err(_)) | None => Err(Error::ThreadJoinError),
+        }
+    }
+}
+
+impl Drop for PendingConnection {
+    fn drop(&mut self) {
+        // Once the subscription and the sender of the handle are dropped, the thread wakes up to
+        // find the channel disconnected and exits.
+        if let Some((subscribers, id)) = self.subscription.take() {
+            subscribers.unsubscribe(id);
+        }
+        self.wake.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn connect_when_available(
+    client: WeakClient,
+    source_port: &ffi::CStr,
+    destination_port: &ffi::CStr,
+    deadline: Option<time::Instant>,
+    wake: mpsc::Receiver<()>,
+    result: mpsc::Sender<Result<(), Error>>,
+) {
+    loop {
+        if let Some(outcome) = try_connect(&client, source_port, destination_port) {
+            let _ = result.send(outcome);
+            return;
+        }
+        let woken = match deadline {
+            Some(deadline) => {
+                let remaining = deadline.saturating_duration_since(time::Instant::now());
+                if remaining.is_zero() {
+                    let _ = result.send(Err(Error::PortConnectionTimeout(
+                        source_port.to_string_lossy().into_owned(),
+                        destination_port.to_string_lossy().into_owned(),
+                    )));
+                    return;
+                }
+                wake.recv_timeout(remaining)
+            }
+            None => wake.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match woken {
+            // Several ports may have been registered since the last check.
+            Ok(()) => wake.try_iter().for_each(drop),
+            Err(RecvTimeoutError::Timeout) => (),
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// Connect the ports if both of them exist. Returns `None` if one does not exist yet.
+///
+/// The ports are connected with `jack_connect` directly since the thread has no `Client`. A port
+/// of the client can be connected since the client is active while it has subscribers.
+fn try_connect(
+    client: &WeakClient,
+    source_port: &ffi::CStr,
+    destination_port: &ffi::CStr,
+) -> Option<Result<(), Error>> {
+    let res = client.query(|c| unsafe {
+        let exists = |name: &ffi::CStr| !j::jack_port_by_name(c, name.as_ptr()).is_null();
+        if !exists(source_port) || !exists(destination_port) {
+            return None;
+        }
+        Some(logging::capture_errors(|| {
+            j::jack_connect(c, source_port.as_ptr(), destination_port.as_ptr())
+        }))
+    });
+    let (source_port, destination_port) = (
+        source_port.to_string_lossy(),
+        destination_port.to_string_lossy(),
+    );
+    match res {
+        Err(err) => Some(Err(err)),
+        Ok(None) => None,
+        Ok(Some((0, _))) | Ok(Some((::libc::EEXIST, _))) => {
+            logging::log_event!(debug, "connected {} to {}", source_port, destination_port);
+            Some(Ok(()))
+        }
+        Ok(Some((code, message))) => Some(Err(Error::PortConnectionError(
+            source_port.into_owned(),
+            destination_port.into_owned(),
+            PortErrorDetails {
+                kind: PortErrorKind::Unknown,
+                code: Some(code),
+                message,
+            },
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn registration_subscribers_are_notified_until_unsubscribed() {
+        let subscribers = RegistrationSubscribers::default();
+        let (a_tx, a_rx) = mpsc::channel();
+        let (b_tx, b_rx) = mpsc::channel();
+        let a = subscribers.subscribe(a_tx);
+        let b = subscribers.subscribe(b_tx);
+        assert_ne!(a, b);
+        subscribers.port_registered();
+        subscribers.unsubscribe(a);
+        subscribers.port_registered();
+        assert_eq!(a_rx.try_iter().count(), 1);
+        assert_eq!(b_rx.try_iter().count(), 2);
+        // The sender of an unsubscribed receiver is dropped.
+        assert_eq!(a_rx.try_recv(), Err(TryRecvError::Disconnected));
+        subscribers.unsubscribe(b);
+        assert!(subscribers.is_empty());
+    }
+
+    #[test]
+    fn registration_subscribers_forget_dropped_receivers() {
+        let subscribers = RegistrationSubscribers::default();
+        let (tx, rx) = mpsc::channel();
+        subscribers.subscribe(tx);
+        drop(rx);
+        subscribers.port_registered();
+        assert!(subscribers.is_empty());
+    }
+
+    #[test]
+    fn pending_connection_reports_known_outcome() {
+        let mut connection = PendingConnection::done("a:out", "b:in", Ok(()));
+        assert_eq!(connection.try_status(), ConnectionStatus::Connected);
+        assert_eq!(connection.source_port(), "a:out");
+        assert_eq!(connection.destination_port(), "b:in");
+        assert_eq!(connection.wait(), Ok(()));
+    }
+
+    #[test]
+    fn pending_connection_fails_for_closed_client_and_unsubscribes() {
+        // Queries of a client that is no longer alive fail, so the connection never waits.
+        let client = WeakClient::new(std::ptr::null_mut(), std::sync::Weak::new());
+        let subscribers = Arc::new(RegistrationSubscribers::default());
+        let connection = PendingConnection::start(
+            client,
+            subscribers.clone(),
+            ffi::CString::new("a:out").unwrap(),
+            ffi::CString::new("b:in").unwrap(),
+            time::Duration::MAX,
+        );
+        assert_eq!(connection.wait(), Err(Error::ClientIsNoLongerAlive));
+        assert!(subscribers.is_empty());
+    }
+}
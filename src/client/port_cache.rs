@@ -0,0 +1,96 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{Client, PortFlags, PortId};
+
+/// Information on a port that was registered or unregistered.
+///
+/// Resolved by the crate when the port registration callback is called. When a port is
+/// unregistered it may not be resolvable anymore, in which case the information that was cached
+/// when it was registered is used instead.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PortRegistrationInfo {
+    /// The id of the port.
+    pub id: PortId,
+    /// The full name of the port, or an empty string if it could not be resolved.
+    pub name: String,
+    /// The flags of the port, or empty if they could not be resolved.
+    pub flags: PortFlags,
+    /// The type of the port, or an empty string if it could not be resolved.
+    pub port_type: String,
+}
+
+impl PortRegistrationInfo {
+    /// Resolve the information of the port with `id`. Returns `None` if the port does not exist.
+    fn resolve(client: &Client, id: PortId) -> Option<PortRegistrationInfo> {
+        let port = client.port_by_id(id)?;
+        Some(PortRegistrationInfo {
+            id,
+            name: port.name().ok()?,
+            flags: port.flags(),
+            port_type: port.port_type().unwrap_or_default(),
+        })
+    }
+
+    fn unknown(id: PortId) -> PortRegistrationInfo {
+        PortRegistrationInfo {
+            id,
+            name: String::new(),
+            flags: PortFlags::empty(),
+            port_type: String::new(),
+        }
+    }
+}
+
+/// The number of unregistered ports whose information is kept around, so that connection changes
+/// caused by the unregistration can still be described.
+const UNREGISTERED_CAPACITY: usize = 64;
+
+/// Caches the information of the ports seen while the client is active by id, so that they can
+/// still be described once they are gone.
+///
+/// Only used from the notification thread.
+#[derive(Debug, Default)]
+pub struct PortCache {
+    ports: HashMap<PortId, PortRegistrationInfo>,
+    unregistered: VecDeque<PortRegistrationInfo>,
+}
+
+impl PortCache {
+    /// Resolve and cache the port that was registered.
+    pub fn register(&mut self, client: &Client, id: PortId) -> PortRegistrationInfo {
+        let info = PortRegistrationInfo::resolve(client, id)
+            .unwrap_or_else(|| PortRegistrationInfo::unknown(id));
+        self.ports.insert(id, info.clone());
+        info
+    }
+
+    /// Remove the port that was unregistered from the cache, returning its information.
+    pub fn unregister(&mut self, client: &Client, id: PortId) -> PortRegistrationInfo {
+        let info = match self.ports.remove(&id) {
+            Some(info) => info,
+            None => PortRegistrationInfo::resolve(client, id)
+                .unwrap_or_else(|| PortRegistrationInfo::unknown(id)),
+        };
+        if self.unregistered.len() == UNREGISTERED_CAPACITY {
+            self.unregistered.pop_front();
+        }
+        self.unregistered.push_back(info.clone());
+        info
+    }
+
+    /// Get the full name of the port with `id`.
+    ///
+    /// If the port is gone, the cached name is used, or `"?"` if the port was never seen.
+    pub fn name(&mut self, client: &Client, id: PortId) -> String {
+        if let Some(info) = PortRegistrationInfo::resolve(client, id) {
+            let name = info.name.clone();
+            self.ports.insert(id, info);
+            return name;
+        }
+        self.ports
+            .get(&id)
+            .or_else(|| self.unregistered.iter().rev().find(|info| info.id == id))
+            .filter(|info| !info.name.is_empty())
+            .map_or_else(|| "?".to_string(), |info| info.name.clone())
+    }
+}
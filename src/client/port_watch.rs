@@ -0,0 +1,176 @@
+use jack_sys as j;
+use std::sync::mpsc::{self, RecvTimeoutError, TryRecvError};
+use std::time;
+
+/// A connection change of a port watched with `AsyncClient::watch_port`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PortEvent {
+    /// The port was connected to the port with the full name `peer`.
+    Connected { peer: String },
+    /// The port was disconnected from the port with the full name `peer`.
+    Disconnected { peer: String },
+}
+
+/// Receives the `PortEvent`s of a port watched with `AsyncClient::watch_port`.
+///
+/// The events end once the port is unregistered, or the client is deactivated.
+#[derive(Debug)]
+pub struct PortEventReceiver {
+    receiver: mpsc::Receiver<PortEvent>,
+}
+
+impl PortEventReceiver {
+    /// Block until an event is available. Returns `None` once the events have ended and all of
+    /// them have been received.
+    pub fn recv(&self) -> Option<PortEvent> {
+        self.receiver.recv().ok()
+    }
+
+    /// Block until an event is available or `timeout` has passed. Returns `None` on timeout or
+    /// once the events have ended and all of them have been received.
+    pub fn recv_timeout(&self, timeout: time::Duration) -> Option<PortEvent> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(e) => Some(e),
+            Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+
+    /// Get the next event if one is available. Does not block.
+    pub fn try_recv(&self) -> Option<PortEvent> {
+        match self.receiver.try_recv() {
+            Ok(e) => Some(e),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None,
+        }
+    }
+
+    /// Iterate over the events that are currently available. Does not block.
+    pub fn try_iter(&self) -> impl Iterator<Item = PortEvent> + '_ {
+        self.receiver.try_iter()
+    }
+}
+
+/// A port watched with `AsyncClient::watch_port`.
+#[derive(Debug)]
+struct PortWatcher {
+    port: *mut j::jack_port_t,
+    name: String,
+    sender: mpsc::Sender<PortEvent>,
+}
+
+/// The ports watched with `AsyncClient::watch_port`. Filled from the thread of the client and read
+/// from the notification thread.
+#[derive(Debug, Default)]
+pub struct PortWatchers {
+    watchers: Vec<PortWatcher>,
+}
+
+// The port pointers are only compared, never dereferenced.
+unsafe impl Send for PortWatchers {}
+
+impl PortWatchers {
+    /// Watch the port `port` with the full name `name`.
+    pub fn add(&mut self, port: *mut j::jack_port_t, name: String) -> PortEventReceiver {
+        let (sender, receiver) = mpsc::channel();
+        self.watchers.push(PortWatcher { port, name, sender });
+        PortEventReceiver { receiver }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.watchers.is_empty()
+    }
+
+    /// Send the connection change between the ports `a` and `b`, named `a_name` and `b_name`, to
+    /// their watchers. Watchers whose receiver was dropped are removed.
+    pub fn connection_changed(
+        &mut self,
+        (a, a_name): (*mut j::jack_port_t, &str),
+        (b, b_name): (*mut j::jack_port_t, &str),
+        connected: bool,
+    ) {
+        self.watchers.retain(|w| {
+            let peer = match w.port {
+                p if p.is_null() => return true,
+                p if p == a => b_name,
+                p if p == b => a_name,
+                _ => return true,
+            };
+            let peer = peer.to_string();
+            let event = match connected {
+                true => PortEvent::Connected { peer },
+                false => PortEvent::Disconnected { peer },
+            };
+            w.sender.send(event).is_ok()
+        });
+    }
+
+    /// End the events of the port `port` with the full name `name`, which was unregistered.
+    pub fn port_unregistered(&mut self, port: *mut j::jack_port_t, name: &str) {
+        // The port may not be resolvable by its id anymore, so the name is compared as well.
+        self.watchers.retain(|w| {
+            let same_port = !port.is_null() && w.port == port;
+            let same_name = !name.is_empty() && w.name == name;
+            !(same_port || same_name)
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The pointers are only compared, so any distinct values work.
+    fn port(n: usize) -> *mut j::jack_port_t {
+        n as *mut j::jack_port_t
+    }
+
+    #[test]
+    fn port_watchers_send_connections_of_watched_port() {
+        let mut watchers = PortWatchers::default();
+        let out = watchers.add(port(8), "me:out".to_string());
+        let input = watchers.add(port(16), "me:in".to_string());
+        watchers.connection_changed((port(8), "me:out"), (port(24), "other:in"), true);
+        watchers.connection_changed((port(32), "other:out"), (port(16), "me:in"), true);
+        watchers.connection_changed((port(40), "other:out"), (port(24), "other:in"), true);
+        watchers.connection_changed((port(8), "me:out"), (port(24), "other:in"), false);
+        assert_eq!(
+            out.try_iter().collect::<Vec<_>>(),
+            vec![
+                PortEvent::Connected {
+                    peer: "other:in".to_string()
+                },
+                PortEvent::Disconnected {
+                    peer: "other:in".to_string()
+                },
+            ]
+        );
+        assert_eq!(
+            input.try_iter().collect::<Vec<_>>(),
+            vec![PortEvent::Connected {
+                peer: "other:out".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn port_watchers_forget_dropped_receivers() {
+        let mut watchers = PortWatchers::default();
+        drop(watchers.add(port(8), "me:out".to_string()));
+        assert!(!watchers.is_empty());
+        watchers.connection_changed((port(8), "me:out"), (port(24), "other:in"), true);
+        assert!(watchers.is_empty());
+    }
+
+    #[test]
+    fn port_watchers_end_events_when_port_is_unregistered() {
+        let mut watchers = PortWatchers::default();
+        let by_port = watchers.add(port(8), "me:out".to_string());
+        let by_name = watchers.add(port(16), "me:in".to_string());
+        let other = watchers.add(port(24), "me:other".to_string());
+        watchers.port_unregistered(port(8), "");
+        watchers.port_unregistered(std::ptr::null_mut(), "me:in");
+        assert_eq!(by_port.recv(), None);
+        assert_eq!(by_name.recv(), None);
+        assert_eq!(other.try_recv(), None);
+        assert!(!watchers.is_empty());
+    }
+}
@@ -0,0 +1,177 @@
+use jack_sys as j;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::{thread, time};
+
+use super::watchdog::CycleCounter;
+use crate::{Client, Control, ProcessScope};
+
+/// How long deactivation waits for the process thread function to return.
+const STOP_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// The function that runs the process thread of a client activated with `Client::activate_thread`.
+pub type ProcessThreadFn = Box<dyn FnOnce(ProcessThreadContext) + Send>;
+
+/// The process thread function and its run state.
+pub struct ProcessThread {
+    f: Mutex<Option<ProcessThreadFn>>,
+    stop: AtomicBool,
+    running: AtomicBool,
+}
+
+impl ProcessThread {
+    pub fn new(f: ProcessThreadFn) -> ProcessThread {
+        ProcessThread {
+            f: Mutex::new(Some(f)),
+            stop: AtomicBool::new(false),
+            running: AtomicBool::new(false),
+        }
+    }
+
+    /// Run the process thread function. Called on the thread created by JACK.
+    pub fn run(
+        &self,
+        client: &Client,
+        quit: &AtomicBool,
+        cycles: &CycleCounter,
+        sample_rate: &AtomicU32,
+    ) {
+        let f = match self.f.lock() {
+            Ok(mut f) => f.take(),
+            Err(_) => None,
+        };
+        self.running.store(true, Ordering::Release);
+        let _running = Running(&self.running);
+        if let Some(f) = f {
+            if !self.stop.load(Ordering::Acquire) {
+                f(ProcessThreadContext {
+                    client,
+                    thread: self,
+                    quit,
+                    cycles,
+                    sample_rate,
+                    in_cycle: false,
+                });
+            }
+        }
+        // JACK does not process the client anymore once the function returns.
+        quit.store(true, Ordering::Release);
+    }
+
+    /// Ask the process thread function to return, and wait until it does or until a timeout.
+    ///
+    /// JACK stops the process thread by cancelling it on deactivation. The process thread
+    /// function must have returned by then since unwinding through Rust frames is undefined
+    /// behavior.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Release);
+        let deadline = time::Instant::now() + STOP_TIMEOUT;
+        while self.running.load(Ordering::Acquire) && time::Instant::now() < deadline {
+            thread::sleep(time::Duration::from_millis(1));
+        }
+    }
+}
+
+/// Clears the running flag of a process thread when dropped, even if the function panics.
+struct Running<'a>(&'a AtomicBool);
+
+impl<'a> Drop for Running<'a> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Drives the processing of a client that was activated with `Client::activate_thread`.
+///
+/// Each cycle starts with `cycle_wait` and ends with `cycle_signal`:
+/// ```no_run
+/// # fn process(_: &jack::Client, _: &jack::ProcessScope) -> jack::Control {
+/// #     jack::Control::Continue
+/// # }
+/// # fn run(mut ctx: jack::ProcessThreadContext) {
+/// while let Some(ps) = ctx.cycle_wait() {
+///     let control = process(ctx.client(), &ps);
+///     ctx.cycle_signal(control);
+/// }
+/// # }
+/// ```
+///
+/// The same rules apply as for `ProcessHandler::process`, the work between `cycle_wait` and
+/// `cycle_signal` must be suitable for real-time execution.
+pub struct ProcessThreadContext<'a> {
+    client: &'a Client,
+    thread: &'a ProcessThread,
+    quit: &'a AtomicBool,
+    cycles: &'a CycleCounter,
+    sample_rate: &'a AtomicU32,
+    in_cycle: bool,
+}
+
+impl<'a> ProcessThreadContext<'a> {
+    /// The client that is being processed.
+    pub fn client(&self) -> &'a Client {
+        self.client
+    }
+
+    /// Wait until the next process cycle starts and return its scope.
+    ///
+    /// Returns `None` once the client is being deactivated, after which the process thread
+    /// function should return as soon as possible. If the previous cycle was not ended with
+    /// `cycle_signal`, it is ended with `Control::Continue`.
+    pub fn cycle_wait(&mut self) -> Option<ProcessScope> {
+        if self.in_cycle {
+            self.cycle_signal(Control::Continue);
+        }
+        if self.thread.stop.load(Ordering::Acquire) {
+            return None;
+        }
+        let n_frames = unsafe { j::jack_cycle_wait(self.client.raw()) };
+        self.cycles.record(n_frames);
+        self.in_cycle = true;
+        if self.thread.stop.load(Ordering::Acquire) {
+            self.cycle_signal(Control::Continue);
+            return None;
+        }
+        let sample_rate = self.sample_rate.load(Ordering::Relaxed) as usize;
+        Some(
+            unsafe { ProcessScope::from_raw(n_frames, self.client.raw()) }
+                .with_sample_rate(sample_rate),
+        )
+    }
+
+    /// End the current process cycle. Returning `Control::Quit` makes JACK stop processing the
+    /// client, see `AsyncClient::is_processing`.
+    ///
+    /// Does nothing if no cycle was started with `cycle_wait`.
+    pub fn cycle_signal(&mut self, control: Control) {
+        if !self.in_cycle {
+            return;
+        }
+        self.in_cycle = false;
+        if control == Control::Quit {
+            self.quit.store(true, Ordering::Release);
+        }
+        unsafe { j::jack_cycle_signal(self.client.raw(), control.to_ffi()) };
+        self.cycles.finish();
+    }
+}
+
+/// Ends the current cycle, if any, so that JACK is not left waiting. The cycle fails if the process
+/// thread function panicked.
+impl<'a> Drop for ProcessThreadContext<'a> {
+    fn drop(&mut self) {
+        match thread::panicking() {
+            true => self.cycle_signal(Control::Quit),
+            false => self.cycle_signal(Control::Continue),
+        }
+    }
+}
+
+impl<'a> std::fmt::Debug for ProcessThreadContext<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        f.debug_struct("ProcessThreadContext")
+            .field("client", self.client)
+            .field("in_cycle", &self.in_cycle)
+            .finish()
+    }
+}
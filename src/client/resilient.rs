@@ -0,0 +1,418 @@
+use std::any::Any;
+use std::ffi::CString;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::{fmt, thread, time};
+
+use crate::jack_utils::to_cstring;
+use crate::{
+    AsyncClient, Client, ClientOptions, ClientStatus, Error, NotificationHandler, Port,
+    PortConnections, PortSpec, ProcessHandler, WeakClient,
+};
+
+/// How often the supervisor of a `ResilientClient` checks for a shutdown or a stop request.
+const SUPERVISE_INTERVAL: time::Duration = time::Duration::from_millis(50);
+
+/// Registers a port of a `PortManifest` and returns it as a `Port<PS>` in a `Box<dyn Any>`.
+type RegisterFn = Box<dyn Fn(&Client, &str) -> Result<Box<dyn Any + Send>, Error> + Send>;
+
+/// The ports that a `ResilientClient` registers every time it opens its client.
+///
+/// # Example
+/// ```
+/// let manifest = jack::PortManifest::new()
+///     .with("in", jack::AudioIn::default())
+///     .with("out", jack::AudioOut::default());
+/// assert_eq!(manifest.names().collect::<Vec<_>>(), ["in", "out"]);
+/// ```
+#[derive(Default)]
+pub struct PortManifest {
+    ports: Vec<(String, RegisterFn)>,
+}
+
+impl PortManifest {
+    /// Create a manifest without ports.
+    pub fn new() -> PortManifest {
+        PortManifest::default()
+    }
+
+    /// Add a port called `name` that is registered with `spec`.
+    pub fn with<PS>(mut self, name: &str, spec: PS) -> PortManifest
+    where
+        PS: 'static + PortSpec + Clone + Send,
+    {
+        let register: RegisterFn = Box::new(move |client, name| {
+            let port = client.register_port(name, spec.clone())?;
+            Ok(Box::new(port))
+        });
+        self.ports.push((name.to_string(), register));
+        self
+    }
+
+    /// The short names of the ports, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.ports.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Register all the ports on `client`.
+    pub fn register(&self, client: &Client) -> Result<ManifestPorts, Error> {
+        let ports = self
+            .ports
+            .iter()
+            .map(|(name, register)| Ok((name.clone(), Some(register(client, name)?))))
+            .collect::<Result<_, Error>>()?;
+        Ok(ManifestPorts { ports })
+    }
+}
+
+impl fmt::Debug for PortManifest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// The ports that were registered from a `PortManifest`, handed to the handler factory of a
+/// `ResilientClient`.
+pub struct ManifestPorts {
+    ports: Vec<(String, Option<Box<dyn Any + Send>>)>,
+}
+
+impl ManifestPorts {
+    /// Take the port called `name`. Returns `None` if there is no such port, if it was already
+    /// taken, or if it was not registered with a `PS` spec.
+    pub fn take<PS: 'static + PortSpec>(&mut self, name: &str) -> Option<Port<PS>> {
+        let slot = &mut self.ports.iter_mut().find(|(n, _)| n == name)?.1;
+        match slot.take()?.downcast::<Port<PS>>() {
+            Ok(port) => Some(*port),
+            Err(port) => {
+                *slot = Some(port);
+                None
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ManifestPorts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_list()
+            .entries(
+                self.ports
+                    .iter()
+                    .filter(|(_, port)| port.is_some())
+                    .map(|(name, _)| name),
+            )
+            .finish()
+    }
+}
+
+/// How a `ResilientClient` opens its client and how hard it tries to get it back.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResilientConfig {
+    /// The name of the client.
+    pub client_name: String,
+    /// The options the client is opened with.
+    pub options: ClientOptions,
+    /// The server to open the client on, or `None` for the default server.
+    pub server_name: Option<String>,
+    /// The connections that are made after every activation, as pairs of full source and
+    /// destination port names. Connections that can't be made are reported with
+    /// `ResilientEvent::ConnectionsFailed`.
+    pub connections: PortConnections,
+    /// The number of times the client is brought back after being shut down, or `None` for no
+    /// limit.
+    pub max_restarts: Option<usize>,
+    /// How long to keep trying to open the client, each time it is opened.
+    pub open_deadline: time::Duration,
+    /// How long to wait between attempts to open the client.
+    pub retry_interval: time::Duration,
+}
+
+impl ResilientConfig {
+    /// A configuration for a client called `client_name` that is opened with
+    /// `ClientOptions::NO_START_SERVER` on the default server, without connections. It is brought
+    /// back any number of times, trying every half a second for up to 30 seconds.
+    pub fn new(client_name: &str) -> ResilientConfig {
+        ResilientConfig {
+            client_name: client_name.to_string(),
+            options: ClientOptions::NO_START_SERVER,
+            server_name: None,
+            connections: PortConnections::new(),
+            max_restarts: None,
+            open_deadline: time::Duration::from_secs(30),
+            retry_interval: time::Duration::from_millis(500),
+        }
+    }
+}
+
+/// The state transitions of a `ResilientClient`, see `ResilientClient::start`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResilientEvent {
+    /// The client was opened and activated, and its connections were made. `restarts` is `0` the
+    /// first time.
+    Activated { restarts: usize },
+    /// Restoring the connections of `ResilientConfig::connections` failed with the first error,
+    /// right after `Activated`. The client stays active.
+    ConnectionsFailed(Error),
+    /// JACK shut the client down, for example because the server exited.
+    ShutDown {
+        status: ClientStatus,
+        reason: String,
+    },
+    /// Opening the client is being tried again.
+    Reopening { restarts: usize },
+    /// The client could not be brought back, nothing more is tried.
+    GaveUp(Error),
+    /// The client was stopped with `ResilientClient::stop` or by dropping it.
+    Stopped,
+}
+
+/// A client that is brought back when the JACK server shuts it down, for example because the
+/// server was restarted.
+///
+/// A supervisor thread opens the client, registers the ports of a `PortManifest`, activates it
+/// with handlers from a factory and makes the connections of the `ResilientConfig`. When JACK shuts
+/// the client down, the old client and handlers are dropped and it all happens again with the
+/// retry policy of the configuration. The state transitions are sent over a channel as
+/// `ResilientEvent`s.
+///
+/// The handlers are created fresh each time, so state that must survive a restart should be
+/// shared with the factory, for example with an `Arc`.
+///
+/// # Example
+/// ```no_run
+/// let manifest = jack::PortManifest::new().with("out", jack::AudioOut::default());
+/// let (client, events) = jack::ResilientClient::start(
+///     jack::ResilientConfig::new("resilient"),
+///     manifest,
+///     |_client, mut ports| {
+///         let mut out = ports.take::<jack::AudioOut>("out").unwrap();
+///         let process = jack::ClosureProcessHandler::new(move |_, ps| {
+///             out.as_mut_slice(ps).fill(0.0);
+///             jack::Control::Continue
+///         });
+///         Ok(((), process))
+///     },
+/// )
+/// .unwrap();
+/// for event in events {
+///     println!("{:?}", event);
+/// }
+/// ```
+pub struct ResilientClient {
+    stop: Arc<AtomicBool>,
+    client: Arc<Mutex<Option<WeakClient>>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ResilientClient {
+    /// Open and activate the client, and keep it running on a supervisor thread.
+    ///
+    /// `factory` is called with the new client and its ports each time the client is opened, and
+    /// returns the handlers to activate it with. Its errors, like those of opening the client,
+    /// count as failed attempts.
+    ///
+    /// Returns once the client is active for the first time, or the error if it could not be
+    /// activated within `ResilientConfig::open_deadline`.
+    pub fn start<N, P, F>(
+        config: ResilientConfig,
+        manifest: PortManifest,
+        factory: F,
+    ) -> Result<(ResilientClient, mpsc::Receiver<ResilientEvent>), Error>
+    where
+        N: 'static + Send + Sync + NotificationHandler,
+        P: 'static + Send + ProcessHandler,
+        F: 'static + Send + FnMut(&Client, ManifestPorts) -> Result<(N, P), Error>,
+    {
+        let server_name = config.server_name.as_deref().map(to_cstring).transpose()?;
+        let (events_tx, events) = mpsc::channel();
+        let (started_tx, started) = mpsc::sync_channel(1);
+        let stop = Arc::new(AtomicBool::new(false));
+        let client = Arc::new(Mutex::new(None));
+        let mut supervisor = Supervisor {
+            config,
+            server_name,
+            manifest,
+            factory,
+            events: events_tx,
+            stop: stop.clone(),
+            client: client.clone(),
+        };
+        let thread = thread::Builder::new()
+            .name("jack-resilient".to_string())
+            .spawn(move || {
+                let active = match supervisor.activate() {
+                    Ok(active) => {
+                        let _ = started_tx.send(Ok(()));
+                        active
+                    }
+                    Err(err) => {
+                        let _ = started_tx.send(Err(err));
+                        return;
+                    }
+                };
+                supervisor.run(active);
+            })
+            .map_err(|_| Error::ThreadCreationError)?;
+        let res = started.recv().unwrap_or(Err(Error::ThreadCreationError));
+        let resilient = ResilientClient {
+            stop,
+            client,
+            thread: Some(thread),
+        };
+        res.map(|()| (resilient, events))
+    }
+
+    /// A weak handle to the current client, or `None` while it is being brought back.
+    pub fn client(&self) -> Option<WeakClient> {
+        self.client.lock().unwrap().clone()
+    }
+
+    /// Returns `true` if the supervisor is still running, which is until the client is stopped or
+    /// given up on.
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Deactivate and close the client and stop trying to bring it back.
+    pub fn stop(mut self) {
+        self.stop_supervisor();
+    }
+
+    fn stop_supervisor(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Stops the client, see `ResilientClient::stop`.
+impl Drop for ResilientClient {
+    fn drop(&mut self) {
+        self.stop_supervisor();
+    }
+}
+
+impl fmt::Debug for ResilientClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("ResilientClient")
+            .field("running", &self.is_running())
+            .finish()
+    }
+}
+
+/// The state of the supervisor thread of a `ResilientClient`.
+struct Supervisor<F> {
+    config: ResilientConfig,
+    server_name: Option<CString>,
+    manifest: PortManifest,
+    factory: F,
+    events: mpsc::Sender<ResilientEvent>,
+    stop: Arc<AtomicBool>,
+    client: Arc<Mutex<Option<WeakClient>>>,
+}
+
+impl<N, P, F> Supervisor<F>
+where
+    N: 'static + Send + Sync + NotificationHandler,
+    P: 'static + Send + ProcessHandler,
+    F: FnMut(&Client, ManifestPorts) -> Result<(N, P), Error>,
+{
+    fn send(&self, event: ResilientEvent) {
+        // The events are informational, nobody has to listen.
+        let _ = self.events.send(event);
+    }
+
+    fn stopping(&self) -> bool {
+        self.stop.load(Ordering::Acquire)
+    }
+
+    /// Try to activate the client until it works, the deadline passes or a stop is requested.
+    fn activate(&mut self) -> Result<AsyncClient<N, P>, Error> {
+        let start = time::Instant::now();
+        loop {
+            let err = match self.try_activate() {
+                Ok(active) => return Ok(active),
+                Err(err) => err,
+            };
+            // Only the failures to reach the server may improve, not those of the factory.
+            let transient = matches!(&err, Error::ClientError(status) if status.is_transient());
+            let deadline_passed =
+                start.elapsed() + self.config.retry_interval > self.config.open_deadline;
+            if !transient || deadline_passed || self.stopping() {
+                return Err(err);
+            }
+            self.sleep(self.config.retry_interval);
+        }
+    }
+
+    /// Sleep for `duration`, or until a stop is requested.
+    fn sleep(&self, duration: time::Duration) {
+        let start = time::Instant::now();
+        while !self.stopping() {
+            let elapsed = start.elapsed();
+            if elapsed >= duration {
+                return;
+            }
+            thread::sleep(SUPERVISE_INTERVAL.min(duration - elapsed));
+        }
+    }
+
+    fn try_activate(&mut self) -> Result<AsyncClient<N, P>, Error> {
+        let (client, _status) = Client::open(
+            &self.config.client_name,
+            self.config.options,
+            self.server_name.as_deref(),
+            None,
+        )?;
+        let ports = self.manifest.register(&client)?;
+        let (notification_handler, process_handler) = (self.factory)(&client, ports)?;
+        client.activate_async(notification_handler, process_handler)
+    }
+
+    fn run(&mut self, mut active: AsyncClient<N, P>) {
+        let mut restarts = 0;
+        loop {
+            *self.client.lock().unwrap() = Some(active.as_client().downgrade());
+            let connected = active
+                .as_client()
+                .restore_connections(&self.config.connections);
+            self.send(ResilientEvent::Activated { restarts });
+            if let Err(err) = connected {
+                self.send(ResilientEvent::ConnectionsFailed(err));
+            }
+            let shutdown = active.shutdown_receiver();
+            let (status, reason) = loop {
+                if self.stopping() {
+                    *self.client.lock().unwrap() = None;
+                    drop(active);
+                    self.send(ResilientEvent::Stopped);
+                    return;
+                }
+                if let Some(event) = shutdown.recv_timeout(SUPERVISE_INTERVAL) {
+                    break event;
+                }
+            };
+            *self.client.lock().unwrap() = None;
+            drop(active);
+            self.send(ResilientEvent::ShutDown { status, reason });
+            if self.config.max_restarts.is_some_and(|max| restarts >= max) {
+                self.send(ResilientEvent::GaveUp(Error::ClientError(status)));
+                return;
+            }
+            restarts += 1;
+            self.send(ResilientEvent::Reopening { restarts });
+            active = match self.activate() {
+                Ok(active) => active,
+                Err(_) if self.stopping() => {
+                    self.send(ResilientEvent::Stopped);
+                    return;
+                }
+                Err(err) => {
+                    self.send(ResilientEvent::GaveUp(err));
+                    return;
+                }
+            };
+        }
+    }
+}
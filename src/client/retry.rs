@@ -0,0 +1,110 @@
+use std::ffi::CStr;
+use std::{thread, time};
+
+use crate::{Client, ClientOptions, ClientStatus, Error};
+
+/// The name of the clients that `wait_for_server` opens.
+const PROBE_CLIENT_NAME: &str = "rust_jack_probe";
+
+/// How often `wait_for_server` tries to reach the server.
+const PROBE_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+/// Wait until the default JACK server accepts clients, for up to `deadline`. Returns `true` once
+/// it does, or `false` if the deadline passed.
+///
+/// Useful for applications that are started at login together with the server. The server is
+/// probed by opening and closing a client with `ClientOptions::NO_START_SERVER`, so it is never
+/// started. See `Client::open_with_retry` to open the client right away instead.
+pub fn wait_for_server(deadline: time::Duration) -> bool {
+    wait_for_server_named(None, deadline)
+}
+
+/// See `wait_for_server`.
+pub(crate) fn wait_for_server_named(server_name: Option<&CStr>, deadline: time::Duration) -> bool {
+    retry_open(deadline, PROBE_INTERVAL, || {
+        Client::open(
+            PROBE_CLIENT_NAME,
+            ClientOptions::NO_START_SERVER,
+            server_name,
+            None,
+        )
+    })
+    .is_ok()
+}
+
+/// Call `open` until it succeeds, fails for a reason that is not transient, or `deadline` has
+/// passed, sleeping `interval` in between. The result of the last call is returned.
+pub(crate) fn retry_open<F>(
+    deadline: time::Duration,
+    interval: time::Duration,
+    mut open: F,
+) -> Result<(Client, ClientStatus), Error>
+where
+    F: FnMut() -> Result<(Client, ClientStatus), Error>,
+{
+    let start = time::Instant::now();
+    loop {
+        let res = open();
+        let transient = matches!(&res, Err(Error::ClientError(status)) if status.is_transient());
+        if !transient || start.elapsed() + interval > deadline {
+            return res;
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn failure(status: ClientStatus) -> Result<(Client, ClientStatus), Error> {
+        Err(Error::ClientError(ClientStatus::FAILURE | status))
+    }
+
+    #[test]
+    fn retry_open_retries_transient_failures_until_deadline() {
+        let mut attempts = 0;
+        let res = retry_open(
+            time::Duration::from_millis(100),
+            time::Duration::from_millis(10),
+            || {
+                attempts += 1;
+                match attempts {
+                    1 => failure(ClientStatus::SERVER_FAILED),
+                    _ => failure(ClientStatus::SERVER_ERROR),
+                }
+            },
+        );
+        assert!(attempts > 2, "{} attempts", attempts);
+        // The status of the last attempt is kept.
+        assert_eq!(
+            res.err(),
+            Some(Error::ClientError(
+                ClientStatus::FAILURE | ClientStatus::SERVER_ERROR
+            ))
+        );
+    }
+
+    #[test]
+    fn retry_open_gives_up_on_permanent_failures() {
+        let mut attempts = 0;
+        let res = retry_open(
+            time::Duration::from_secs(10),
+            time::Duration::from_millis(10),
+            || {
+                attempts += 1;
+                match attempts {
+                    1 => failure(ClientStatus::SERVER_FAILED),
+                    _ => failure(ClientStatus::NAME_NOT_UNIQUE),
+                }
+            },
+        );
+        assert_eq!(attempts, 2);
+        assert_eq!(
+            res.err(),
+            Some(Error::ClientError(
+                ClientStatus::FAILURE | ClientStatus::NAME_NOT_UNIQUE
+            ))
+        );
+    }
+}
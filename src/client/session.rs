@@ -0,0 +1,567 @@
+use bitflags::bitflags;
+use jack_sys as j;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc};
+use std::{ffi, fmt, ptr, thread};
+
+use super::shutdown::{ShutdownReceiver, ShutdownSlot};
+use crate::jack_utils::to_cstring;
+use crate::logging::log_event;
+use crate::{Client, ClientOptions, ClientStatus, Error, WeakClient};
+
+/// The command line argument that passes the session uuid to a client that is restored, see
+/// `SessionSupport::uuid_from_args`.
+pub const SESSION_UUID_ARG: &str = "--jack-session-uuid";
+
+/// The shutdown reason of a client whose session manager requested it to quit.
+const SESSION_QUIT_REASON: &[u8] = b"the session manager requested the client to quit";
+
+/// The placeholder for the session directory in the command line of a session reply. The session
+/// manager replaces it with the directory of the session when the client is restored.
+pub const SESSION_DIR_PLACEHOLDER: &str = "${SESSION_DIR}";
+
+bitflags! {
+    /// Flags of a reply to a session manager, see `SessionEvent::reply`.
+    pub struct SessionFlags: j::jack_session_flags_t {
+        /// Saving the state failed, the session manager should not rely on it.
+        const SAVE_ERROR    = j::JackSessionSaveError;
+
+        /// The client needs to be run in a terminal.
+        const NEED_TERMINAL = j::JackSessionNeedTerminal;
+    }
+}
+
+/// The kind of a JACK Session request, see `SessionEvent`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SessionEventType {
+    /// Save the state of the client.
+    Save,
+    /// Save the state of the client and quit.
+    SaveAndQuit,
+    /// Save the state of the client as a template, without data such as recordings.
+    SaveTemplate,
+}
+
+impl SessionEventType {
+    pub(crate) fn from_ffi(event_type: j::jack_session_event_type_t) -> SessionEventType {
+        match event_type {
+            j::JackSessionSaveAndQuit => SessionEventType::SaveAndQuit,
+            j::JackSessionSaveTemplate => SessionEventType::SaveTemplate,
+            _ => SessionEventType::Save,
+        }
+    }
+
+    pub(crate) fn to_ffi(self) -> j::jack_session_event_type_t {
+        match self {
+            SessionEventType::Save => j::JackSessionSave,
+            SessionEventType::SaveAndQuit => j::JackSessionSaveAndQuit,
+            SessionEventType::SaveTemplate => j::JackSessionSaveTemplate,
+        }
+    }
+}
+
+/// A request from a session manager to save the state of the client, received by a
+/// `SessionHandler`.
+///
+/// The session manager waits for every client to reply with `reply`. The event may be sent to
+/// another thread, so that the state is saved without blocking the notification thread. An event
+/// that is dropped without a reply replies without a command line, so that the session manager is
+/// not left waiting, and logs a warning with the `log` feature.
+pub struct SessionEvent {
+    event_type: SessionEventType,
+    session_dir: PathBuf,
+    client_uuid: String,
+    raw: *mut j::jack_session_event_t,
+    client: WeakClient,
+    replied: bool,
+}
+
+// The event is only accessed through the owner of the `SessionEvent`.
+unsafe impl Send for SessionEvent {}
+
+impl SessionEvent {
+    /// Take ownership of an event that was passed to the session callback of `client`.
+    ///
+    /// # Safety
+    /// `raw` must be a valid event that is not owned by anything else.
+    pub(crate) unsafe fn from_raw(
+        raw: *mut j::jack_session_event_t,
+        client: WeakClient,
+    ) -> SessionEvent {
+        let event = &*raw;
+        let to_string = |s: *const libc::c_char| match s.is_null() {
+            true => String::new(),
+            false => ffi::CStr::from_ptr(s).to_string_lossy().into_owned(),
+        };
+        SessionEvent {
+            event_type: SessionEventType::from_ffi(event._type),
+            session_dir: PathBuf::from(to_string(event.session_dir)),
+            client_uuid: to_string(event.client_uuid),
+            raw,
+            client,
+            replied: false,
+        }
+    }
+
+    /// What the session manager requests.
+    pub fn event_type(&self) -> SessionEventType {
+        self.event_type
+    }
+
+    /// The directory in which the client should save its state. It ends with a path separator.
+    pub fn session_dir(&self) -> &Path {
+        &self.session_dir
+    }
+
+    /// The uuid that the session manager knows the client by. When the session is restored, the
+    /// client is started with this uuid.
+    pub fn client_uuid(&self) -> &str {
+        &self.client_uuid
+    }
+
+    /// Reply to the session manager once the state is saved, with the command line that restores
+    /// the client.
+    ///
+    /// The command line should refer to the session directory with `SESSION_DIR_PLACEHOLDER`, as
+    /// the session may be moved, and should pass the uuid of the event to the client so that it
+    /// restores the same identity. `SessionFlags::SAVE_ERROR` reports that saving failed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// # fn reply(event: jack::SessionEvent) {
+    /// let command_line = format!(
+    ///     "my_app --state {}state.txt --jack-session-uuid {}",
+    ///     jack::SESSION_DIR_PLACEHOLDER,
+    ///     event.client_uuid()
+    /// );
+    /// event
+    ///     .reply(&command_line, jack::SessionFlags::empty())
+    ///     .unwrap();
+    /// # }
+    /// ```
+    ///
+    /// `Err(Error::InvalidName)` is returned if `command_line` contains a NUL byte, in which case
+    /// the event is answered without a command line when it is dropped.
+    pub fn reply(mut self, command_line: &str, flags: SessionFlags) -> Result<(), Error> {
+        let command_line = to_cstring(command_line)?;
+        self.replied = true;
+        let res = unsafe {
+            // JACK frees the command line along with the event.
+            (*self.raw).command_line = libc::strdup(command_line.as_ptr());
+            (*self.raw).flags = flags.bits();
+            self.client
+                .query(|client| j::jack_session_reply(client, self.raw))?
+        };
+        match res {
+            0 => Ok(()),
+            _ => Err(Error::SessionReplyError),
+        }
+    }
+}
+
+/// Replies to the session manager if `reply` was not called, and releases the request.
+impl Drop for SessionEvent {
+    fn drop(&mut self) {
+        unsafe {
+            if !self.replied {
+                log_event!(
+                    warn,
+                    "the {:?} session event of client {} was dropped without a reply",
+                    self.event_type,
+                    self.client_uuid
+                );
+                (*self.raw).command_line = ptr::null_mut();
+                let _ = self
+                    .client
+                    .query(|client| j::jack_session_reply(client, self.raw));
+            }
+            j::jack_session_event_free(self.raw);
+        }
+    }
+}
+
+impl fmt::Debug for SessionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SessionEvent")
+            .field("event_type", &self.event_type)
+            .field("session_dir", &self.session_dir)
+            .field("client_uuid", &self.client_uuid)
+            .finish()
+    }
+}
+
+/// Returns `true` if the JACK library supports JACK Session, which old versions of JACK lack.
+///
+/// Without it, `Client::register_session_handler` returns `Err(Error::WeakFunctionNotFound)`,
+/// `Client::session_notify` returns no replies and `Client::has_session_callback` returns `None`.
+/// The JACK library is never called.
+pub fn session_available() -> bool {
+    *j::jack_session_available
+}
+
+/// The reply of a client to a session request, see `Client::session_notify`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SessionCommand {
+    /// The uuid of the client, which it should be restored with.
+    pub uuid: String,
+    /// The name of the client.
+    pub client_name: String,
+    /// The command line that restores the client, see `SESSION_DIR_PLACEHOLDER`.
+    pub command: String,
+    /// The flags of the reply.
+    pub flags: SessionFlags,
+}
+
+impl Client {
+    /// Open a client like `Client::new`, with the uuid that a session manager restores it with.
+    ///
+    /// The uuid is usually passed on the command line, see `SessionSupport::uuid_from_args`.
+    pub fn new_with_session_uuid(
+        client_name: &str,
+        options: ClientOptions,
+        session_uuid: &str,
+    ) -> Result<(Self, ClientStatus), Error> {
+        let session_uuid = to_cstring(session_uuid)?;
+        Client::open(client_name, options, None, Some(&session_uuid))
+    }
+
+    /// Register a handler for the requests of JACK Session managers, which ask the client to save
+    /// its state so that the session can be restored later.
+    ///
+    /// Registering a handler tells session managers that the client takes part in sessions, so
+    /// only clients that can restore their state should do so.
+    ///
+    /// # Remarks
+    /// * The handler must be registered before the client is activated.
+    /// * `Err(Error::WeakFunctionNotFound)` is returned if the JACK library does not support
+    ///   sessions, see `session_available`.
+    /// * The handler is called on the notification thread and must not block, see
+    ///   `SessionHandler::session`.
+    ///
+    /// # Panics
+    /// Calling this method more than once on any given client will cause a panic.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let (mut client, _status) =
+    ///     jack::Client::new("session_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// let (tx, rx) = std::sync::mpsc::channel();
+    /// client
+    ///     .register_session_handler(jack::ClosureSessionHandler::new(move |event| {
+    ///         // Save from another thread, the handler must not block.
+    ///         let _ = tx.send(event);
+    ///     }))
+    ///     .unwrap();
+    /// let _active_client = client.activate_async((), ()).unwrap();
+    /// for event in rx {
+    ///     std::fs::write(event.session_dir().join("state"), "state").unwrap();
+    /// }
+    /// ```
+    pub fn register_session_handler<H: SessionHandler + 'static>(
+        &mut self,
+        handler: H,
+    ) -> Result<(), Error> {
+        assert!(self.session_handler_slot().is_none());
+        if !session_available() {
+            return Err(Error::WeakFunctionNotFound);
+        }
+        let ctx = Box::into_raw(Box::new(SessionContext {
+            handler,
+            client: self.downgrade(),
+        }));
+        unsafe {
+            *self.session_handler_slot() = Some(Box::from_raw(ctx));
+            match j::jack_set_session_callback(
+                self.raw(),
+                Some(session_callback::<H>),
+                ctx as *mut libc::c_void,
+            ) {
+                0 => Ok(()),
+                _ => {
+                    *self.session_handler_slot() = None;
+                    Err(Error::CallbackRegistrationError("session".to_string()))
+                }
+            }
+        }
+    }
+
+    /// Act as a session manager: ask the client named `target`, or all the clients that take
+    /// part in sessions if `None`, to save their state into `save_path`. Returns the replies of
+    /// the clients.
+    ///
+    /// `save_path` should end with a path separator, each client saves into a directory below it.
+    /// This blocks until the clients have replied. No clients, or a request that JACK can't
+    /// deliver, result in no replies. So do arguments that contain a NUL byte.
+    ///
+    /// # Example
+    /// ```no_run
+    /// let (client, _status) =
+    ///     jack::Client::new("manager", jack::ClientOptions::NO_START_SERVER).unwrap();
+    /// for command in client.session_notify(None, jack::SessionEventType::Save, "/tmp/session/") {
+    ///     println!("{}: {}", command.client_name, command.command);
+    /// }
+    /// ```
+    pub fn session_notify(
+        &self,
+        target: Option<&str>,
+        event_type: SessionEventType,
+        save_path: &str,
+    ) -> Vec<SessionCommand> {
+        if !session_available() {
+            return Vec::new();
+        }
+        let (target, save_path) = match (target.map(to_cstring).transpose(), to_cstring(save_path))
+        {
+            (Ok(target), Ok(save_path)) => (target, save_path),
+            _ => return Vec::new(),
+        };
+        let mut replies = Vec::new();
+        unsafe {
+            let commands = j::jack_session_notify(
+                self.raw(),
+                target.as_ref().map_or(ptr::null(), |t| t.as_ptr()),
+                event_type.to_ffi(),
+                save_path.as_ptr(),
+            );
+            if commands.is_null() {
+                return replies;
+            }
+            let to_string = |s: *const libc::c_char| match s.is_null() {
+                true => String::new(),
+                false => ffi::CStr::from_ptr(s).to_string_lossy().into_owned(),
+            };
+            // The list ends with a command without a uuid.
+            let mut command = commands;
+            while !(*command).uuid.is_null() {
+                replies.push(SessionCommand {
+                    uuid: to_string((*command).uuid),
+                    client_name: to_string((*command).client_name),
+                    command: to_string((*command).command),
+                    flags: SessionFlags::from_bits_truncate((*command).flags),
+                });
+                command = command.add(1);
+            }
+            j::jack_session_commands_free(commands);
+        }
+        replies
+    }
+
+    /// Returns whether the client named `client_name` registered a session handler, and will
+    /// reply to `session_notify`.
+    ///
+    /// `None` is returned if the client does not exist, or if the server can't tell, for example
+    /// because the JACK library does not support JACK Session.
+    pub fn has_session_callback(&self, client_name: &str) -> Option<bool> {
+        if !session_available() {
+            return None;
+        }
+        let client_name = to_cstring(client_name).ok()?;
+        match unsafe { j::jack_client_has_session_callback(self.raw(), client_name.as_ptr()) } {
+            0 => Some(false),
+            1 => Some(true),
+            _ => None,
+        }
+    }
+}
+
+/// A trait for taking part in JACK Session, see `Client::register_session_handler`.
+pub trait SessionHandler: Send {
+    /// Called on the notification thread when a session manager requests the client to save its
+    /// state.
+    ///
+    /// This must not block, saving the state may be moved to another thread along with `event`.
+    fn session(&mut self, event: SessionEvent);
+}
+
+/// Wrap a closure that can handle a `session` callback.
+pub struct ClosureSessionHandler<F>
+where
+    F: 'static + Send + FnMut(SessionEvent),
+{
+    func: F,
+}
+
+impl<F> ClosureSessionHandler<F>
+where
+    F: 'static + Send + FnMut(SessionEvent),
+{
+    /// Create a new `SessionHandler` from a closure.
+    pub fn new(func: F) -> ClosureSessionHandler<F> {
+        ClosureSessionHandler { func }
+    }
+}
+
+impl<F> SessionHandler for ClosureSessionHandler<F>
+where
+    F: 'static + Send + FnMut(SessionEvent),
+{
+    fn session(&mut self, event: SessionEvent) {
+        (self.func)(event)
+    }
+}
+
+/// Takes part in JACK Session by saving the state of the client with a closure.
+///
+/// The closure is given the directory to save into and returns the command line that restores
+/// the client, see `SESSION_DIR_PLACEHOLDER`. It runs on a thread of its own, so that the
+/// notification thread is not blocked. If it fails, the session manager is told that saving
+/// failed. After a `SessionEventType::SaveAndQuit` request, the client is reported as shut down
+/// by `shutdown_receiver`, and the application should quit.
+///
+/// # Example
+/// ```no_run
+/// let session_uuid = jack::SessionSupport::uuid_from_args(std::env::args());
+/// let options = jack::ClientOptions::NO_START_SERVER;
+/// let (mut client, _status) = match &session_uuid {
+///     Some(uuid) => jack::Client::new_with_session_uuid("my_app", options, uuid),
+///     None => jack::Client::new("my_app", options),
+/// }
+/// .unwrap();
+/// let uuid = client.uuid_string();
+/// let session = jack::SessionSupport::register(&mut client, move |dir: &std::path::Path| {
+///     std::fs::write(dir.join("state.txt"), "state")?;
+///     Ok::<_, std::io::Error>(format!(
+///         "my_app --state {}state.txt {} {}",
+///         jack::SESSION_DIR_PLACEHOLDER,
+///         jack::SESSION_UUID_ARG,
+///         uuid
+///     ))
+/// })
+/// .unwrap();
+/// let _active_client = client.activate_async((), ()).unwrap();
+/// session.shutdown_receiver().recv();
+/// ```
+pub struct SessionSupport {
+    quit: Arc<ShutdownSlot>,
+}
+
+impl SessionSupport {
+    /// Register a session handler on `client` that saves with `save`. See
+    /// `Client::register_session_handler`, which must not be called on the same client.
+    pub fn register<F, E>(client: &mut Client, save: F) -> Result<SessionSupport, Error>
+    where
+        F: 'static + Send + Fn(&Path) -> Result<String, E>,
+        E: fmt::Display,
+    {
+        let quit = ShutdownSlot::new();
+        let (tx, rx) = mpsc::channel::<SessionEvent>();
+        client.register_session_handler(ClosureSessionHandler::new(move |event| {
+            // Sending does not block, the event is saved on the thread below.
+            let _ = tx.send(event);
+        }))?;
+        let worker_quit = quit.clone();
+        // The thread stops once the client, and with it the handler, is dropped.
+        thread::spawn(move || {
+            for event in rx {
+                let event_type = event.event_type();
+                let res = match save(event.session_dir()) {
+                    Ok(command_line) => event.reply(&command_line, SessionFlags::empty()),
+                    Err(e) => {
+                        log_event!(error, "saving the session failed: {}", e);
+                        event.reply("", SessionFlags::SAVE_ERROR)
+                    }
+                };
+                if event_type == SessionEventType::SaveAndQuit && res.is_ok() {
+                    worker_quit.store(ClientStatus::empty(), SESSION_QUIT_REASON);
+                }
+            }
+        });
+        Ok(SessionSupport { quit })
+    }
+
+    /// Receives the request of the session manager to quit, after the state was saved.
+    pub fn shutdown_receiver(&self) -> ShutdownReceiver {
+        ShutdownReceiver::new(self.quit.clone())
+    }
+
+    /// Find the session uuid in command line arguments, given as `--jack-session-uuid <uuid>` or
+    /// `--jack-session-uuid=<uuid>`.
+    pub fn uuid_from_args<I, S>(args: I) -> Option<String>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            if arg == SESSION_UUID_ARG {
+                return args.next().map(|uuid| uuid.as_ref().to_string());
+            }
+            if let Some(uuid) = arg
+                .strip_prefix(SESSION_UUID_ARG)
+                .and_then(|rest| rest.strip_prefix('='))
+            {
+                return Some(uuid.to_string());
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Debug for SessionSupport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SessionSupport")
+            .field("quit", &self.quit.is_stored())
+            .finish()
+    }
+}
+
+/// A session handler along with the client it is registered to.
+struct SessionContext<H> {
+    handler: H,
+    client: WeakClient,
+}
+
+unsafe extern "C" fn session_callback<H>(
+    event: *mut j::jack_session_event_t,
+    arg: *mut libc::c_void,
+) where
+    H: SessionHandler,
+{
+    let ctx: &mut SessionContext<H> = &mut *(arg as *mut SessionContext<H>);
+    let event = SessionEvent::from_raw(event, ctx.client.clone());
+    ctx.handler.session(event);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn session_uuid_with_nul_is_rejected() {
+        assert_eq!(
+            Client::new_with_session_uuid("client_suwnir", ClientOptions::NO_START_SERVER, "4\0 2")
+                .err(),
+            Some(Error::InvalidName("4\0 2".to_string()))
+        );
+    }
+
+    #[test]
+    fn session_uuid_is_found_in_args() {
+        let uuid = |args: &[&str]| SessionSupport::uuid_from_args(args);
+        assert_eq!(
+            uuid(&["my_app", "--jack-session-uuid", "42"]),
+            Some("42".into())
+        );
+        assert_eq!(
+            uuid(&["my_app", "--jack-session-uuid=43", "-v"]),
+            Some("43".into())
+        );
+        assert_eq!(uuid(&["my_app", "--jack-session-uuid"]), None);
+        assert_eq!(uuid(&["my_app", "--jack-session-uuids=44"]), None);
+        assert_eq!(uuid(&["my_app"]), None);
+    }
+
+    #[test]
+    fn session_event_type_round_trips() {
+        for event_type in [
+            SessionEventType::Save,
+            SessionEventType::SaveAndQuit,
+            SessionEventType::SaveTemplate,
+        ]
+        .iter()
+        {
+            assert_eq!(SessionEventType::from_ffi(event_type.to_ffi()), *event_type);
+        }
+    }
+}
@@ -0,0 +1,310 @@
+use std::sync::Arc;
+
+use crate::{
+    BufferSizeScope, CallbackSelection, Client, ClientStatus, Control, Frames, LatencyScope,
+    LatencyType, NotificationHandler, PortId, PortRegistrationInfo, ProcessHandler, ProcessScope,
+};
+
+/// Like `NotificationHandler`, but every callback takes `&self`, so a handler may be shared with
+/// other threads as an `Arc<T>` or a `&'static T`. Both implement `NotificationHandler` by
+/// forwarding every callback.
+///
+/// State that changes in a callback must use interior mutability, such as atomics, a `Mutex` or
+/// a channel. Notification callbacks are not real-time, so locking is fine here.
+pub trait SharedNotificationHandler: Send + Sync {
+    /// See `NotificationHandler::thread_init`.
+    fn thread_init(&self, _: &Client) {}
+
+    /// See `NotificationHandler::shutdown`.
+    fn shutdown(&self, _status: ClientStatus, _reason: &str) {}
+
+    /// See `NotificationHandler::freewheel`.
+    fn freewheel(&self, _: &Client, _is_freewheel_enabled: bool) {}
+
+    /// See `NotificationHandler::sample_rate`.
+    fn sample_rate(&self, _: &Client, _srate: Frames) -> Control {
+        Control::Continue
+    }
+
+    /// See `NotificationHandler::client_registration`.
+    fn client_registration(&self, _: &Client, _name: &str, _is_registered: bool) {}
+
+    /// See `NotificationHandler::port_registration`.
+    fn port_registration(&self, _: &Client, _port_id: PortId, _is_registered: bool) {}
+
+    /// See `NotificationHandler::port_registration_with_info`.
+    ///
+    /// The default implementation calls `port_registration`.
+    fn port_registration_with_info(
+        &self,
+        client: &Client,
+        info: &PortRegistrationInfo,
+        is_registered: bool,
+    ) {
+        self.port_registration(client, info.id, is_registered)
+    }
+
+    /// See `NotificationHandler::port_rename`.
+    fn port_rename(
+        &self,
+        _: &Client,
+        _port_id: PortId,
+        _old_name: &str,
+        _new_name: &str,
+    ) -> Control {
+        Control::Continue
+    }
+
+    /// See `NotificationHandler::ports_connected`.
+    fn ports_connected(
+        &self,
+        _: &Client,
+        _port_id_a: PortId,
+        _port_id_b: PortId,
+        _are_connected: bool,
+    ) {
+    }
+
+    /// See `NotificationHandler::ports_connected_with_names`.
+    ///
+    /// The default implementation calls `ports_connected`.
+    fn ports_connected_with_names(
+        &self,
+        client: &Client,
+        port_id_a: PortId,
+        _port_name_a: &str,
+        port_id_b: PortId,
+        _port_name_b: &str,
+        are_connected: bool,
+    ) {
+        self.ports_connected(client, port_id_a, port_id_b, are_connected)
+    }
+
+    /// See `NotificationHandler::graph_reorder`.
+    fn graph_reorder(&self, _: &Client) -> Control {
+        Control::Continue
+    }
+
+    /// See `NotificationHandler::xrun`.
+    fn xrun(&self, _: &Client) -> Control {
+        Control::Continue
+    }
+
+    /// See `NotificationHandler::xrun_with_delay`.
+    ///
+    /// The default implementation calls `xrun`.
+    fn xrun_with_delay(&self, client: &Client, _delayed_usecs: f32) -> Control {
+        self.xrun(client)
+    }
+
+    /// See `NotificationHandler::latency`.
+    fn latency(&self, _: &Client, _mode: LatencyType) {}
+
+    /// See `NotificationHandler::latency_with_scope`.
+    ///
+    /// The default implementation calls `latency`.
+    fn latency_with_scope(&self, scope: &LatencyScope) {
+        self.latency(scope.client(), scope.mode())
+    }
+
+    /// See `NotificationHandler::property_change`.
+    ///
+    /// Only available with the `metadata` feature.
+    #[cfg(feature = "metadata")]
+    fn property_change(
+        &self,
+        _: &Client,
+        _subject: crate::Uuid,
+        _key: Option<&str>,
+        _change: crate::PropertyChangeKind,
+    ) {
+    }
+
+    /// See `NotificationHandler::callbacks`.
+    fn callbacks(&self) -> CallbackSelection {
+        CallbackSelection::all()
+    }
+}
+
+/// Like `ProcessHandler`, but every callback takes `&self`, so a handler may be shared with other
+/// threads as an `Arc<T>` or a `&'static T`. Both implement `ProcessHandler` by forwarding every
+/// callback.
+///
+/// State that changes in `process` must use interior mutability that is real-time safe, such as
+/// atomics, or a `RingBuffer` that is owned by the handler. A `Mutex` that is locked outside of
+/// `process` would make `process` wait, so it should at most be tried with `try_lock`.
+///
+/// # Example
+/// ```
+/// use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+/// use std::sync::Arc;
+///
+/// #[derive(Default)]
+/// struct Engine {
+///     muted: AtomicBool,
+///     frames: AtomicUsize,
+/// }
+///
+/// impl jack::SharedProcessHandler for Engine {
+///     fn process(&self, _: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+///         if !self.muted.load(Ordering::Relaxed) {
+///             self.frames.fetch_add(ps.n_frames() as usize, Ordering::Relaxed);
+///         }
+///         jack::Control::Continue
+///     }
+/// }
+///
+/// fn use_handler<P: jack::ProcessHandler>(_: P) {}
+///
+/// let engine = Arc::new(Engine::default());
+/// // The engine is controlled with `engine` while the client processes with the clone.
+/// use_handler(engine.clone());
+/// engine.muted.store(true, Ordering::Relaxed);
+/// ```
+pub trait SharedProcessHandler: Send + Sync {
+    /// See `ProcessHandler::process`.
+    fn process(&self, _: &Client, _process_scope: &ProcessScope) -> Control;
+
+    /// See `ProcessHandler::buffer_size`.
+    fn buffer_size(&self, _: &Client, _size: Frames) -> Control {
+        Control::Continue
+    }
+
+    /// See `ProcessHandler::buffer_size_with_scope`.
+    ///
+    /// The default implementation calls `buffer_size`.
+    fn buffer_size_with_scope(&self, scope: &BufferSizeScope) -> Control {
+        self.buffer_size(scope.client(), scope.n_frames())
+    }
+}
+
+/// Implements `NotificationHandler` and `ProcessHandler` for a pointer type `$ptr` to a shared
+/// handler `T`.
+macro_rules! forward_shared_handler {
+    ($($ptr:ty),*) => {$(
+        impl<T: SharedNotificationHandler + ?Sized> NotificationHandler for $ptr {
+            fn thread_init(&self, c: &Client) {
+                (**self).thread_init(c)
+            }
+
+            fn shutdown(&mut self, status: ClientStatus, reason: &str) {
+                (**self).shutdown(status, reason)
+            }
+
+            fn freewheel(&mut self, c: &Client, is_freewheel_enabled: bool) {
+                (**self).freewheel(c, is_freewheel_enabled)
+            }
+
+            fn sample_rate(&mut self, c: &Client, srate: Frames) -> Control {
+                (**self).sample_rate(c, srate)
+            }
+
+            fn client_registration(&mut self, c: &Client, name: &str, is_registered: bool) {
+                (**self).client_registration(c, name, is_registered)
+            }
+
+            fn port_registration(&mut self, c: &Client, port_id: PortId, is_registered: bool) {
+                (**self).port_registration(c, port_id, is_registered)
+            }
+
+            fn port_registration_with_info(
+                &mut self,
+                c: &Client,
+                info: &PortRegistrationInfo,
+                is_registered: bool,
+            ) {
+                (**self).port_registration_with_info(c, info, is_registered)
+            }
+
+            fn port_rename(
+                &mut self,
+                c: &Client,
+                port_id: PortId,
+                old_name: &str,
+                new_name: &str,
+            ) -> Control {
+                (**self).port_rename(c, port_id, old_name, new_name)
+            }
+
+            fn ports_connected(
+                &mut self,
+                c: &Client,
+                port_id_a: PortId,
+                port_id_b: PortId,
+                are_connected: bool,
+            ) {
+                (**self).ports_connected(c, port_id_a, port_id_b, are_connected)
+            }
+
+            fn ports_connected_with_names(
+                &mut self,
+                c: &Client,
+                port_id_a: PortId,
+                port_name_a: &str,
+                port_id_b: PortId,
+                port_name_b: &str,
+                are_connected: bool,
+            ) {
+                (**self).ports_connected_with_names(
+                    c,
+                    port_id_a,
+                    port_name_a,
+                    port_id_b,
+                    port_name_b,
+                    are_connected,
+                )
+            }
+
+            fn graph_reorder(&mut self, c: &Client) -> Control {
+                (**self).graph_reorder(c)
+            }
+
+            fn xrun(&mut self, c: &Client) -> Control {
+                (**self).xrun(c)
+            }
+
+            fn xrun_with_delay(&mut self, c: &Client, delayed_usecs: f32) -> Control {
+                (**self).xrun_with_delay(c, delayed_usecs)
+            }
+
+            fn latency(&mut self, c: &Client, mode: LatencyType) {
+                (**self).latency(c, mode)
+            }
+
+            fn latency_with_scope(&mut self, scope: &LatencyScope) {
+                (**self).latency_with_scope(scope)
+            }
+
+            #[cfg(feature = "metadata")]
+            fn property_change(
+                &mut self,
+                c: &Client,
+                subject: crate::Uuid,
+                key: Option<&str>,
+                change: crate::PropertyChangeKind,
+            ) {
+                (**self).property_change(c, subject, key, change)
+            }
+
+            fn callbacks(&self) -> CallbackSelection {
+                (**self).callbacks()
+            }
+        }
+
+        impl<T: SharedProcessHandler + ?Sized> ProcessHandler for $ptr {
+            fn process(&mut self, c: &Client, ps: &ProcessScope) -> Control {
+                (**self).process(c, ps)
+            }
+
+            fn buffer_size(&mut self, c: &Client, size: Frames) -> Control {
+                (**self).buffer_size(c, size)
+            }
+
+            fn buffer_size_with_scope(&mut self, scope: &BufferSizeScope) -> Control {
+                (**self).buffer_size_with_scope(scope)
+            }
+        }
+    )*};
+}
+
+forward_shared_handler!(Arc<T>, &'static T);
@@ -0,0 +1,188 @@
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{fmt, thread, time};
+
+use crate::ClientStatus;
+
+/// The maximum number of bytes of the shutdown reason that are kept. Longer reasons are truncated.
+pub const SHUTDOWN_REASON_SIZE: usize = 512;
+
+const EMPTY: u8 = 0;
+const WRITING: u8 = 1;
+const READY: u8 = 2;
+
+/// How often `ShutdownReceiver::recv` checks whether the client has been shut down.
+const POLL_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+/// Preallocated storage for a single shutdown event.
+///
+/// Writing only uses atomics and copies into a fixed size buffer, so it is safe to do from JACK's
+/// shutdown callback, which must behave like an asynchronous POSIX signal handler.
+pub struct ShutdownSlot {
+    state: AtomicU8,
+    status: AtomicU32,
+    reason_len: AtomicUsize,
+    reason: UnsafeCell<[u8; SHUTDOWN_REASON_SIZE]>,
+}
+
+// The reason buffer is written at most once, before `state` is set to `READY` with release
+// ordering, and is only read after `READY` has been observed with acquire ordering.
+unsafe impl Sync for ShutdownSlot {}
+
+impl ShutdownSlot {
+    pub fn new() -> Arc<ShutdownSlot> {
+        Arc::new(ShutdownSlot {
+            state: AtomicU8::new(EMPTY),
+            status: AtomicU32::new(0),
+            reason_len: AtomicUsize::new(0),
+            reason: UnsafeCell::new([0; SHUTDOWN_REASON_SIZE]),
+        })
+    }
+
+    /// Store the shutdown event. Only the first event is kept.
+    ///
+    /// Does not allocate or block.
+    pub fn store(&self, status: ClientStatus, reason: &[u8]) {
+        if self
+            .state
+            .compare_exchange(EMPTY, WRITING, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return;
+        }
+        let len = reason.len().min(SHUTDOWN_REASON_SIZE);
+        let buf = unsafe { &mut *self.reason.get() };
+        buf[..len].copy_from_slice(&reason[..len]);
+        self.reason_len.store(len, Ordering::Relaxed);
+        self.status.store(status.bits(), Ordering::Relaxed);
+        self.state.store(READY, Ordering::Release);
+    }
+
+    /// Returns `true` if a shutdown event has been stored.
+    pub fn is_stored(&self) -> bool {
+        self.state.load(Ordering::Acquire) == READY
+    }
+
+    fn load(&self) -> Option<(ClientStatus, String)> {
+        if self.state.load(Ordering::Acquire) != READY {
+            return None;
+        }
+        let status = ClientStatus::from_bits_truncate(self.status.load(Ordering::Relaxed));
+        let len = self.reason_len.load(Ordering::Relaxed);
+        let buf = unsafe { &*self.reason.get() };
+        let reason = &buf[..len];
+        Some((status, String::from_utf8_lossy(reason).into_owned()))
+    }
+}
+
+/// Receives the shutdown event of an `AsyncClient`.
+///
+/// JACK shuts a client down when the server exits or when it decides to kick the client out of the
+/// process graph. The status and reason are captured within the shutdown callback and can be
+/// received from any thread. Once the client has been shut down, every receive returns the same
+/// event, so the receiver may be cloned and shared freely.
+///
+/// Obtained through `AsyncClient::shutdown_receiver`.
+///
+/// # Example
+/// ```no_run
+/// let (client, _status) =
+///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let active_client = client.activate_async((), ()).unwrap();
+/// let (status, reason) = active_client.shutdown_receiver().recv();
+/// println!("JACK shut down the client with {:?}: {}", status, reason);
+/// ```
+#[derive(Clone)]
+pub struct ShutdownReceiver {
+    slot: Arc<ShutdownSlot>,
+}
+
+impl ShutdownReceiver {
+    pub(crate) fn new(slot: Arc<ShutdownSlot>) -> ShutdownReceiver {
+        ShutdownReceiver { slot }
+    }
+
+    /// Returns the status and reason of the shutdown if the client has been shut down, or `None`
+    /// otherwise. Does not block.
+    pub fn try_recv(&self) -> Option<(ClientStatus, String)> {
+        self.slot.load()
+    }
+
+    /// Block until the client has been shut down and return the status and reason.
+    pub fn recv(&self) -> (ClientStatus, String) {
+        loop {
+            if let Some(event) = self.try_recv() {
+                return event;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Block until the client has been shut down or `timeout` has passed. Returns `None` on
+    /// timeout. A timeout too large to compute a deadline for blocks like `recv`.
+    pub fn recv_timeout(&self, timeout: time::Duration) -> Option<(ClientStatus, String)> {
+        let deadline = match time::Instant::now().checked_add(timeout) {
+            Some(deadline) => deadline,
+            None => return Some(self.recv()),
+        };
+        loop {
+            if let Some(event) = self.try_recv() {
+                return Some(event);
+            }
+            let now = time::Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline.saturating_duration_since(now)));
+        }
+    }
+
+    /// Returns `true` if the client has been shut down.
+    pub fn is_shutdown(&self) -> bool {
+        self.try_recv().is_some()
+    }
+}
+
+impl fmt::Debug for ShutdownReceiver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("ShutdownReceiver")
+            .field("event", &self.try_recv())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shutdown_receiver_is_empty_before_shutdown() {
+        let rx = ShutdownReceiver::new(ShutdownSlot::new());
+        assert_eq!(rx.try_recv(), None);
+        assert_eq!(rx.recv_timeout(time::Duration::from_millis(20)), None);
+        assert!(!rx.is_shutdown());
+    }
+
+    #[test]
+    fn shutdown_receiver_receives_first_event() {
+        let slot = ShutdownSlot::new();
+        let rx = ShutdownReceiver::new(slot.clone());
+        slot.store(ClientStatus::SERVER_ERROR, b"server went away");
+        slot.store(ClientStatus::FAILURE, b"ignored");
+        let expected = (ClientStatus::SERVER_ERROR, "server went away".to_string());
+        assert_eq!(rx.try_recv(), Some(expected.clone()));
+        assert_eq!(rx.clone().recv(), expected);
+        assert_eq!(rx.recv_timeout(time::Duration::MAX), Some(expected));
+        assert!(rx.is_shutdown());
+    }
+
+    #[test]
+    fn shutdown_receiver_truncates_long_reasons() {
+        let slot = ShutdownSlot::new();
+        let rx = ShutdownReceiver::new(slot.clone());
+        let reason = vec![b'x'; SHUTDOWN_REASON_SIZE * 2];
+        slot.store(ClientStatus::empty(), &reason);
+        assert_eq!(rx.recv().1.len(), SHUTDOWN_REASON_SIZE);
+    }
+}
@@ -1,5 +1,7 @@
 use crate::client::*;
 use crate::jack_enums::Error;
+use crate::test_support::{start_dummy_server, start_dummy_server_with, DummyServer};
+use crate::{AudioIn, AudioOut, Frames};
 
 fn open_test_client(name: &str) -> (Client, ClientStatus) {
     Client::new(name, ClientOptions::NO_START_SERVER).unwrap()
@@ -12,7 +14,18 @@ fn client_valid_client_name_size() {
 
 #[test]
 fn client_can_open() {
-    open_test_client("client_can_open");
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let (client, _) = Client::new_with_server_name(
+        "client_can_open",
+        server.name(),
+        ClientOptions::NO_START_SERVER,
+    )
+    .unwrap();
+    assert_eq!(client.name(), "client_can_open");
+    assert_eq!(client.name_lossy(), "client_can_open");
 }
 
 #[test]
@@ -29,6 +42,210 @@ fn client_fails_to_open_with_large_name() {
     // client_status::SERVER_ERROR)));
 }
 
+#[test]
+fn client_fails_to_open_with_nul_in_name() {
+    assert_eq!(
+        Client::new("client\0name", ClientOptions::NO_START_SERVER).err(),
+        Some(Error::InvalidName("client\0name".to_string()))
+    );
+}
+
+#[test]
+fn client_reports_name_not_unique_with_exact_name() {
+    let name = "client_rnnuwen";
+    let (_c, _) = open_test_client(name);
+    let options = ClientOptions::NO_START_SERVER | ClientOptions::USE_EXACT_NAME;
+    match Client::new(name, options) {
+        Err(Error::ClientError(status)) => {
+            assert!(status.contains(ClientStatus::NAME_NOT_UNIQUE));
+            assert!(status.describe().contains("name is already taken"));
+        }
+        res => panic!("expected a name conflict, got {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn client_reports_server_failed_without_server() {
+    let res = Client::new_with_server_name(
+        "client_rsfws",
+        "rust_jack_no_such_server",
+        ClientOptions::NO_START_SERVER,
+    );
+    match res {
+        Err(Error::ClientError(status)) => {
+            assert!(status.contains(ClientStatus::FAILURE | ClientStatus::SERVER_FAILED));
+            assert!(status.describe().contains("not running"));
+        }
+        res => panic!("expected a server failure, got {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn client_retries_until_server_starts() {
+    if std::process::Command::new("jackd")
+        .arg("--version")
+        .output()
+        .is_err()
+    {
+        eprintln!("skipping test: jackd is not installed");
+        return;
+    }
+    let name = DummyServer::unique_name();
+    let server_name = std::ffi::CString::new(name.clone()).unwrap();
+    let starter = std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        DummyServer::start_named(name, 48000, 256)
+    });
+    let res = super::retry::retry_open(
+        std::time::Duration::from_secs(20),
+        std::time::Duration::from_millis(100),
+        || {
+            Client::open(
+                "client_rust",
+                ClientOptions::NO_START_SERVER,
+                Some(&server_name),
+                None,
+            )
+        },
+    );
+    let _server = starter.join().unwrap().unwrap();
+    let (client, _) = res.unwrap();
+    assert_eq!(client.name(), "client_rust");
+}
+
+#[test]
+fn client_waits_for_server() {
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let server_name = std::ffi::CString::new(server.name()).unwrap();
+    let deadline = std::time::Duration::from_secs(1);
+    assert!(super::retry::wait_for_server_named(
+        Some(&server_name),
+        deadline
+    ));
+    drop(server);
+    let start = std::time::Instant::now();
+    assert!(!super::retry::wait_for_server_named(
+        Some(&server_name),
+        deadline
+    ));
+    assert!(start.elapsed() < 2 * deadline);
+}
+
+fn resilient_test_client(
+    server: &DummyServer,
+    max_restarts: Option<usize>,
+) -> (ResilientClient, std::sync::mpsc::Receiver<ResilientEvent>) {
+    let mut config = ResilientConfig::new("client_resilient");
+    config.server_name = Some(server.name().to_string());
+    config.max_restarts = max_restarts;
+    config.open_deadline = std::time::Duration::from_secs(20);
+    config.retry_interval = std::time::Duration::from_millis(100);
+    config.connections = vec![(
+        "client_resilient:out".to_string(),
+        "client_resilient:in".to_string(),
+    )];
+    let manifest = PortManifest::new()
+        .with("in", AudioIn::default())
+        .with("out", AudioOut::default());
+    ResilientClient::start(config, manifest, |_, mut ports| {
+        assert!(ports.take::<AudioOut>("in").is_none());
+        let input = ports.take::<AudioIn>("in").unwrap();
+        let output = ports.take::<AudioOut>("out").unwrap();
+        Ok((
+            (),
+            ClosureProcessHandler::new(move |_, _| {
+                let _ = (&input, &output);
+                crate::Control::Continue
+            }),
+        ))
+    })
+    .unwrap()
+}
+
+fn next_event(events: &std::sync::mpsc::Receiver<ResilientEvent>) -> ResilientEvent {
+    events
+        .recv_timeout(std::time::Duration::from_secs(20))
+        .unwrap()
+}
+
+#[test]
+fn client_resilient_comes_back_after_server_restart() {
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let name = server.name().to_string();
+    let (resilient, events) = resilient_test_client(&server, None);
+    assert_eq!(
+        next_event(&events),
+        ResilientEvent::Activated { restarts: 0 }
+    );
+    let connected = |server: &DummyServer| {
+        let (observer, _) = server
+            .open_client("observer", ClientOptions::empty())
+            .unwrap();
+        let out = observer.port_by_name("client_resilient:out").unwrap();
+        out.is_connected_to("client_resilient:in")
+    };
+    assert!(resilient.client().is_some());
+    assert_eq!(connected(&server), Ok(true));
+
+    drop(server);
+    assert!(matches!(
+        next_event(&events),
+        ResilientEvent::ShutDown { .. }
+    ));
+    assert_eq!(resilient.client().map(|_| ()), None);
+    assert_eq!(
+        next_event(&events),
+        ResilientEvent::Reopening { restarts: 1 }
+    );
+    let server = DummyServer::start_named(name, 48000, 256).unwrap();
+    assert_eq!(
+        next_event(&events),
+        ResilientEvent::Activated { restarts: 1 }
+    );
+    assert_eq!(connected(&server), Ok(true));
+
+    resilient.stop();
+    assert_eq!(next_event(&events), ResilientEvent::Stopped);
+}
+
+#[test]
+fn client_resilient_gives_up_after_max_restarts() {
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let (resilient, events) = resilient_test_client(&server, Some(0));
+    assert_eq!(
+        next_event(&events),
+        ResilientEvent::Activated { restarts: 0 }
+    );
+    drop(server);
+    assert!(matches!(
+        next_event(&events),
+        ResilientEvent::ShutDown { .. }
+    ));
+    assert!(matches!(
+        next_event(&events),
+        ResilientEvent::GaveUp(Error::ClientError(_))
+    ));
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    assert!(!resilient.is_running());
+}
+
+#[test]
+fn client_fails_to_open_with_nul_in_server_name() {
+    assert_eq!(
+        Client::new_with_server_name("client", "no\0server", ClientOptions::NO_START_SERVER).err(),
+        Some(Error::InvalidName("no\0server".to_string()))
+    );
+}
+
 #[test]
 fn client_can_be_named() {
     let name = "client_can_be_named";
@@ -44,7 +261,14 @@ fn client_can_activate() {
 
 #[test]
 fn client_can_set_buffer_size() {
-    let (c, _) = open_test_client("client_can_set_buffer_size");
+    // On a server of its own, so that other tests don't see the changed buffer size.
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let (c, _) = server
+        .open_client("client_can_set_buffer_size", ClientOptions::empty())
+        .unwrap();
     let initial_size = c.buffer_size();
     let new_size = 2 * initial_size;
     c.set_buffer_size(new_size).unwrap();
@@ -57,11 +281,95 @@ fn client_can_set_buffer_size() {
 fn client_detects_bad_buffer_size() {
     let (c, _) = open_test_client("client_detects_bad_buffer_size");
     let initial_size = c.buffer_size();
-    assert_eq!(c.set_buffer_size(0), Err(Error::SetBufferSizeError));
+    assert_eq!(
+        c.set_buffer_size(0),
+        Err(Error::InvalidBufferSize(
+            0,
+            MIN_BUFFER_SIZE,
+            MAX_BUFFER_SIZE
+        ))
+    );
     c.set_buffer_size(initial_size).unwrap();
     assert_eq!(c.buffer_size(), initial_size);
 }
 
+#[test]
+fn client_validates_buffer_sizes() {
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let (c, _) = server
+        .open_client("client_validates_buffer_sizes", ClientOptions::empty())
+        .unwrap();
+    let initial_size = c.buffer_size();
+    for size in [0, 3, 100, MIN_BUFFER_SIZE / 2, 2 * MAX_BUFFER_SIZE] {
+        assert_eq!(
+            c.set_buffer_size(size),
+            Err(Error::InvalidBufferSize(
+                size,
+                MIN_BUFFER_SIZE,
+                MAX_BUFFER_SIZE
+            ))
+        );
+        assert_eq!(c.buffer_size(), initial_size);
+    }
+    for size in [MIN_BUFFER_SIZE, 64, 1024, initial_size] {
+        c.set_buffer_size(size).unwrap();
+        assert_eq!(c.buffer_size(), size);
+    }
+}
+
+#[test]
+fn client_waits_for_buffer_size_callback() {
+    use std::sync::{Arc, Mutex};
+
+    struct BufferSizes(Arc<Mutex<Vec<Frames>>>);
+
+    impl ProcessHandler for BufferSizes {
+        fn process(&mut self, _: &Client, _: &ProcessScope) -> crate::Control {
+            crate::Control::Continue
+        }
+
+        fn buffer_size(&mut self, _: &Client, size: Frames) -> crate::Control {
+            self.0.lock().unwrap().push(size);
+            crate::Control::Continue
+        }
+    }
+
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let (c, _) = server
+        .open_client("client_waits_for_bs_callback", ClientOptions::empty())
+        .unwrap();
+    let initial_size = c.buffer_size();
+    let sizes = Arc::new(Mutex::new(Vec::new()));
+    let ac = c.activate_async((), BufferSizes(sizes.clone())).unwrap();
+    let timeout = std::time::Duration::from_secs(5);
+
+    for size in [2 * initial_size, initial_size] {
+        ac.set_buffer_size_sync(size, timeout).unwrap();
+        assert_eq!(sizes.lock().unwrap().last(), Some(&size));
+    }
+    // Requesting the current size does not wait for a callback that is not coming.
+    ac.set_buffer_size_sync(initial_size, timeout).unwrap();
+    // A timeout that no deadline can be computed for does not panic.
+    ac.set_buffer_size_sync(2 * initial_size, std::time::Duration::MAX)
+        .unwrap();
+    ac.set_buffer_size_sync(initial_size, std::time::Duration::MAX)
+        .unwrap();
+    assert_eq!(
+        ac.set_buffer_size_sync(3, timeout),
+        Err(Error::InvalidBufferSize(
+            3,
+            MIN_BUFFER_SIZE,
+            MAX_BUFFER_SIZE
+        ))
+    );
+}
+
 #[test]
 fn client_can_deactivate() {
     let (c, _) = open_test_client("client_can_deactivate");
@@ -71,16 +379,28 @@ fn client_can_deactivate() {
 
 #[test]
 fn client_knows_buffer_size() {
-    let (c, _) = open_test_client("client_knows_buffer_size");
-    // 1024 - As started by dummy_jack_server.sh
-    assert_eq!(c.buffer_size(), 1024);
+    let server = match start_dummy_server() {
+        Some(server) => server,
+        None => return,
+    };
+    let (c, _) = server
+        .open_client("client_knows_buffer_size", ClientOptions::empty())
+        .unwrap();
+    // 256 - As started by `DummyServer::start`
+    assert_eq!(c.buffer_size(), 256);
 }
 
 #[test]
 fn client_knows_sample_rate() {
-    let (c, _) = open_test_client("client_knows_sample_rate");
-    // 44100 - As started by dummy_jack_server.sh
+    let server = match start_dummy_server_with(44100, 64) {
+        Some(server) => server,
+        None => return,
+    };
+    let (c, _) = server
+        .open_client("client_knows_sample_rate", ClientOptions::empty())
+        .unwrap();
     assert_eq!(c.sample_rate(), 44100);
+    assert_eq!(c.buffer_size(), 64);
 }
 
 #[test]
@@ -206,15 +526,17 @@ fn client_numeric_uuid() {
     let uuid1 = c1.uuid();
     let uuid2 = c2.uuid();
     assert_ne!(uuid1, uuid2);
-    assert_ne!(0, uuid1);
-    assert_ne!(0, uuid2);
+    assert!(!uuid1.is_empty());
+    assert!(!uuid2.is_empty());
 
     let uuid1s = c1.uuid_string();
     let uuid2s = c2.uuid_string();
     assert_ne!(uuid1s, uuid2s);
+    assert_eq!(crate::Uuid::parse(&uuid1s), Ok(uuid1));
+    assert_eq!(uuid2.to_string(), uuid2s);
 
-    assert_eq!(c1.name_by_uuid(0), None);
-    assert_eq!(c2.name_by_uuid(0), None);
+    assert_eq!(c1.name_by_uuid(crate::Uuid::EMPTY), None);
+    assert_eq!(c2.name_by_uuid(crate::Uuid::EMPTY), None);
 
     assert_eq!(
         c1.name_by_uuid(uuid1),
@@ -258,3 +580,213 @@ fn client_numeric_uuid() {
     assert_eq!(c1.name_by_uuid(uuid3), None);
     assert_eq!(c2.name_by_uuid(uuid3), None);
 }
+
+#[test]
+fn client_weak_client_fails_after_close() {
+    let (c, _) = open_test_client("client_weak_client_fac");
+    let weak = c.downgrade();
+    assert!(weak.is_alive());
+    assert_eq!(weak.sample_rate(), Ok(c.sample_rate()));
+    assert_eq!(weak.buffer_size(), Ok(c.buffer_size()));
+    drop(c);
+    assert!(!weak.is_alive());
+    assert_eq!(weak.sample_rate(), Err(Error::ClientIsNoLongerAlive));
+    assert_eq!(weak.frame_time(), Err(Error::ClientIsNoLongerAlive));
+}
+
+#[test]
+fn client_spawns_rt_thread() {
+    let (c, _) = open_test_client("client_spawns_rt_thread");
+    let (tx, rx) = std::sync::mpsc::channel();
+    let state = std::sync::Arc::new(());
+    let thread = {
+        let state = state.clone();
+        c.spawn_rt_thread(None, false, move || {
+            let _state = state;
+            tx.send(unsafe { libc::pthread_self() }).unwrap();
+        })
+        .unwrap()
+    };
+    let thread_id = thread.thread_id();
+    thread.join().unwrap();
+    assert_eq!(rx.recv().unwrap(), thread_id);
+    // The function is freed once the thread exits.
+    assert_eq!(std::sync::Arc::strong_count(&state), 1);
+}
+
+#[test]
+fn client_rt_thread_reports_panic_on_join() {
+    let (c, _) = open_test_client("client_rt_thread_rpoj");
+    let thread = c
+        .spawn_rt_thread(None, false, || panic!("induced panic in rt thread"))
+        .unwrap();
+    assert_eq!(thread.join(), Err(Error::ThreadJoinError));
+}
+
+#[test]
+fn client_rt_thread_can_be_joined_after_close() {
+    let (c, _) = open_test_client("client_rt_thread_cbjac");
+    let thread = c.spawn_rt_thread(None, false, || ()).unwrap();
+    drop(c);
+    thread.join().unwrap();
+}
+
+#[test]
+fn client_lists_clients_and_their_ports() {
+    let (client, _) = open_test_client("client_lcatp");
+    let (other, _) = open_test_client("client_lcatp_other");
+    let (empty, _) = open_test_client("client_lcatp_empty");
+    let a = other.register_port("in:left", AudioIn::default()).unwrap();
+    let b = other.register_port("in:right", AudioIn::default()).unwrap();
+    let c = other.register_port("out", AudioOut::default()).unwrap();
+    let own = client.register_port("own", AudioOut::default()).unwrap();
+
+    let names = client.client_names();
+    let position = |name: &str| names.iter().position(|n| n == name);
+    assert!(position("client_lcatp_other").is_some());
+    assert!(position("client_lcatp_other:in").is_none());
+    assert!(position("client_lcatp_empty").is_none());
+    assert!(position("client_lcatp_other") < position("client_lcatp"));
+    assert_eq!(
+        names.iter().filter(|n| *n == "client_lcatp_other").count(),
+        1
+    );
+
+    let ports = client.ports_of_client("client_lcatp_other");
+    let short_names: Vec<_> = ports.iter().map(|p| p.short_name().unwrap()).collect();
+    assert_eq!(short_names, ["in:left", "in:right", "out"]);
+    assert_eq!(
+        ports,
+        [a.clone_unowned(), b.clone_unowned(), c.clone_unowned()]
+    );
+    assert_eq!(
+        client.ports_of_client("client_lcatp"),
+        [own.clone_unowned()]
+    );
+    assert!(client.ports_of_client("client_lcatp_other:in").is_empty());
+    assert!(client.ports_of_client("client_lcatp_empty").is_empty());
+
+    assert!(client.is_client_registered("client_lcatp_empty"));
+    assert!(client.is_client_registered("client_lcatp"));
+    assert!(!client.is_client_registered("client_lcatp_missing"));
+    assert!(!client.is_client_registered("client_lcatp\0"));
+    drop(empty);
+    assert!(!client.is_client_registered("client_lcatp_empty"));
+}
+
+fn cycle_times() -> CycleTimes {
+    // 256 frames at 48kHz.
+    CycleTimes {
+        current_frames: 48000,
+        current_usecs: 1_000_000,
+        next_usecs: 1_005_333,
+        period_usecs: 5333.333,
+    }
+}
+
+#[test]
+fn client_cycle_times_interpolate_time_at_offset() {
+    let times = cycle_times();
+    assert_eq!(times.time_at_offset(0, 256), 1_000_000);
+    assert_eq!(times.time_at_offset(128, 256), 1_002_667);
+    assert_eq!(times.time_at_offset(256, 256), 1_005_333);
+}
+
+#[test]
+fn client_cycle_times_interpolate_offset_at_time() {
+    let times = cycle_times();
+    assert_eq!(times.offset_at_time(1_000_000, 256), Some(0));
+    assert_eq!(times.offset_at_time(1_002_667, 256), Some(128));
+    assert_eq!(times.offset_at_time(1_005_332, 256), Some(255));
+    // The start of the next cycle.
+    assert_eq!(times.offset_at_time(1_005_333, 256), None);
+    assert_eq!(times.offset_at_time(999_999, 256), None);
+    for offset in 0..256 {
+        let time = times.time_at_offset(offset, 256);
+        assert_eq!(times.offset_at_time(time, 256), Some(offset));
+    }
+}
+
+#[test]
+fn client_cycle_times_map_frames_to_time_from_cycle_start() {
+    let times = cycle_times();
+    assert_eq!(times.frames_to_time(48000, 256), 1_000_000);
+    assert_eq!(times.frames_to_time(48128, 256), 1_002_667);
+    assert_eq!(times.frames_to_time(48256, 256), 1_005_333);
+    // Frames before the cycle and more than one cycle ahead are extrapolated.
+    assert_eq!(times.frames_to_time(47744, 256), 994_667);
+    assert_eq!(times.frames_to_time(48000 + 48000, 256), 2_000_000);
+    for offset in 0..256 {
+        assert_eq!(
+            times.frames_to_time(48000 + offset, 256),
+            times.time_at_offset(offset, 256)
+        );
+    }
+}
+
+#[test]
+fn client_cycle_times_map_time_to_frames_from_cycle_start() {
+    let times = cycle_times();
+    assert_eq!(times.time_to_frames(1_000_000, 256), 48000);
+    assert_eq!(times.time_to_frames(1_002_667, 256), 48128);
+    assert_eq!(times.time_to_frames(994_667, 256), 47744);
+    assert_eq!(times.time_to_frames(2_000_000, 256), 96000);
+    for frames in 47000..49000 {
+        let time = times.frames_to_time(frames, 256);
+        assert_eq!(times.time_to_frames(time, 256), frames);
+    }
+}
+
+#[test]
+fn client_cycle_times_map_across_frame_time_wrap_around() {
+    let times = CycleTimes {
+        current_frames: Frames::MAX - 99,
+        ..cycle_times()
+    };
+    // 100 frames after the start of the cycle, the frame time wrapped around to 0.
+    let time = times.frames_to_time(0, 256);
+    assert_eq!(time, times.time_at_offset(100, 256));
+    assert_eq!(times.time_to_frames(time, 256), 0);
+    assert_eq!(times.frames_to_time(Frames::MAX - 199, 256), 997_917);
+}
+
+#[test]
+fn client_mock_process_scope_maps_frames_at_sample_rate() {
+    let mut mock = crate::MockProcessScope::new(256);
+    mock.set_sample_rate(48000);
+    mock.set_last_frame_time(48000);
+    let scope = mock.scope();
+    assert_eq!(scope.frames_to_time(48000), 1_000_000);
+    assert_eq!(scope.frames_to_time(48024), 1_000_500);
+    assert_eq!(scope.time_to_frames(1_000_500), 48024);
+    // Typed frames and times are accepted as well.
+    let typed_frames = crate::units::Frames(48000) + crate::units::Frames(24);
+    assert_eq!(scope.frames_to_time(typed_frames), 1_000_500);
+    let typed_time = crate::units::Time::from_millis(1000) + crate::units::Time(500);
+    assert_eq!(scope.time_to_frames(typed_time), 48024);
+    // The offsets within the cycle are mapped with the same cycle times.
+    assert_eq!(scope.time_at_offset(24), Ok(1_000_500));
+    assert_eq!(scope.offset_at_time(1_000_500), Some(24));
+    assert_eq!(scope.offset_at_time(999_999), None);
+    assert_eq!(
+        scope
+            .cycle_times()
+            .map(|t| (t.current_frames, t.current_usecs)),
+        Ok((48000, 1_000_000))
+    );
+}
+
+#[cfg(feature = "metadata")]
+#[test]
+fn client_port_uuids_parse() {
+    let (client, _) = open_test_client("client_port_uuids_parse");
+    let a = client.register_port("a", AudioIn::default()).unwrap();
+    let b = client.register_port("b", AudioIn::default()).unwrap();
+    let uuids = [client.uuid(), a.uuid(), b.uuid()];
+    for uuid in uuids {
+        assert!(!uuid.is_empty());
+        assert_eq!(crate::Uuid::parse(&uuid.to_string()), Ok(uuid));
+    }
+    assert_ne!(a.uuid(), b.uuid());
+    assert_ne!(client.uuid(), a.uuid());
+}
@@ -1,4 +1,5 @@
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 use std::{mem, ptr, thread, time};
 
 use super::*;
@@ -61,7 +62,7 @@ impl ProcessHandler for Counter {
             thread::sleep(time::Duration::from_millis(400));
         }
         self.process_thread = Some(thread::current().id());
-        Control::Continue
+        self.process_return_val
     }
 
     fn buffer_size(&mut self, _: &Client, size: Frames) -> Control {
@@ -203,6 +204,69 @@ fn client_cback_reports_xruns() {
     assert!(counter.xruns_count > 0, "No xruns encountered.");
 }
 
+#[test]
+fn client_cback_counts_xruns_without_handler() {
+    let c = open_test_client("client_cback_cxwh");
+    let counter = Counter {
+        induce_xruns: true,
+        ..Counter::default()
+    };
+    let ac = c.activate_async((), counter).unwrap();
+    thread::sleep(time::Duration::from_secs(1));
+    let stats = ac.xrun_stats();
+    assert!(stats.count > 0, "No xruns encountered.");
+    assert!(stats.worst_delay_usecs >= stats.last_delay_usecs);
+}
+
+#[test]
+fn client_cback_resets_xrun_count() {
+    let c = open_test_client("client_cback_rxc");
+    let counter = Counter {
+        induce_xruns: true,
+        ..Counter::default()
+    };
+    let ac = c.activate_async((), counter).unwrap();
+    thread::sleep(time::Duration::from_secs(1));
+    assert!(ac.xruns_since_activation() > 0, "No xruns encountered.");
+    ac.reset_xrun_count();
+    // The process handler induces an xrun at most every 400ms.
+    assert!(ac.xruns_since_activation() <= 1);
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_measures_process_timing() {
+    let mut c = open_test_client("client_cback_mpt");
+    c.set_timing_stats_enabled(true);
+    let busy = time::Duration::from_millis(2);
+    let process = ClosureProcessHandler::new(move |_: &Client, _: &ProcessScope| {
+        let start = time::Instant::now();
+        while start.elapsed() < busy {}
+        Control::Continue
+    });
+    let ac = c.activate_async((), process).unwrap();
+    ac.wait_for_first_cycle(time::Duration::from_secs(5))
+        .unwrap();
+    let stats = ac.timing_stats().unwrap();
+    assert!(stats.cycles > 0);
+    assert!(stats.min_usecs >= 2000, "{:?}", stats);
+    assert!(
+        stats.avg_usecs >= 2000.0 && stats.avg_usecs < 4000.0,
+        "{:?}",
+        stats
+    );
+    assert!(stats.max_usecs as f64 >= stats.avg_usecs);
+    assert_eq!(stats.histogram.iter().sum::<u64>(), stats.cycles);
+    assert!(stats.period_usecs > 0.0);
+    assert!(stats.dsp_load() > 0.0);
+}
+
+#[test]
+fn client_cback_timing_stats_are_opt_in() {
+    let ac = active_test_client("client_cback_tsaoi");
+    assert_eq!(ac.timing_stats(), None);
+}
+
 #[test]
 fn client_cback_calls_port_registered() {
     let ac = active_test_client("client_cback_cpr");
@@ -249,3 +313,1191 @@ fn client_cback_calls_port_unregistered() {
         "Did not detect port deregistrations."
     );
 }
+
+#[test]
+fn client_cback_notification_handler_is_accessible_while_active() {
+    let ac = active_test_client("client_cback_nhiawa");
+    let _other_client = open_test_client("client_cback_nhiawa_other");
+    assert!(ac
+        .notification_handler()
+        .registered_client_history
+        .contains(&"client_cback_nhiawa_other".to_string()));
+    ac.deactivate().unwrap();
+}
+
+#[derive(Debug, Default)]
+pub struct DropCounter {
+    pub drops: Arc<AtomicUsize>,
+}
+
+impl NotificationHandler for DropCounter {}
+
+impl ProcessHandler for DropCounter {
+    fn process(&mut self, _: &Client, _: &ProcessScope) -> Control {
+        Control::Continue
+    }
+}
+
+impl Drop for DropCounter {
+    fn drop(&mut self) {
+        self.drops.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[test]
+fn client_cback_handlers_are_dropped_after_deactivation() {
+    let drops = Arc::new(AtomicUsize::new(0));
+    for i in 0..3 {
+        let c = open_test_client("client_cback_hadad");
+        let handler = || DropCounter {
+            drops: drops.clone(),
+        };
+        let ac = c.activate_async(handler(), handler()).unwrap();
+        if i % 2 == 0 {
+            let (_client, n, p) = ac.deactivate().unwrap();
+            assert_eq!(drops.load(Ordering::Relaxed), 2 * i);
+            drop((n, p));
+        } else {
+            drop(ac);
+        }
+        assert_eq!(drops.load(Ordering::Relaxed), 2 * (i + 1));
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Panicker {
+    pub panic_in_thread_init: bool,
+    pub panic_in_process: bool,
+    pub process_calls: Arc<AtomicUsize>,
+}
+
+impl NotificationHandler for Panicker {
+    fn thread_init(&self, _: &Client) {
+        if self.panic_in_thread_init {
+            panic!("induced panic in thread_init");
+        }
+    }
+}
+
+impl ProcessHandler for Panicker {
+    fn process(&mut self, _: &Client, _: &ProcessScope) -> Control {
+        self.process_calls.fetch_add(1, Ordering::Relaxed);
+        if self.panic_in_process {
+            panic!("induced panic in process");
+        }
+        Control::Continue
+    }
+}
+
+#[test]
+fn client_cback_panic_in_process_poisons_client() {
+    let c = open_test_client("client_cback_pippc");
+    let process_calls = Arc::new(AtomicUsize::new(0));
+    let handler = Panicker {
+        panic_in_process: true,
+        process_calls: process_calls.clone(),
+        ..Panicker::default()
+    };
+    let ac = c.activate_async((), handler).unwrap();
+    ac.wait_for_first_cycle(time::Duration::from_secs(5))
+        .unwrap();
+    assert!(ac.is_poisoned());
+    // The process handler is not called again after it has panicked.
+    assert_eq!(process_calls.load(Ordering::Relaxed), 1);
+    drop(ac);
+}
+
+#[test]
+fn client_cback_panic_in_notification_poisons_client() {
+    let c = open_test_client("client_cback_pinpc");
+    let handler = Panicker {
+        panic_in_thread_init: true,
+        ..Panicker::default()
+    };
+    let ac = c.activate_async(handler, ()).unwrap();
+    thread::sleep(time::Duration::from_secs(1));
+    assert!(ac.is_poisoned());
+    let (_client, n, _p) = ac.deactivate().unwrap();
+    assert!(n.panic_in_thread_init);
+}
+
+#[test]
+fn client_cback_healthy_client_is_not_poisoned() {
+    let ac = active_test_client("client_cback_hcinp");
+    assert!(!ac.is_poisoned());
+}
+
+#[test]
+fn client_cback_shutdown_receiver_is_empty_while_running() {
+    let ac = active_test_client("client_cback_sriewr");
+    let rx = ac.shutdown_receiver();
+    assert_eq!(rx.try_recv(), None);
+    ac.deactivate().unwrap();
+    assert!(!rx.is_shutdown());
+}
+
+#[test]
+fn client_cback_calls_thread_init_with_thread_init_config() {
+    let mut c = open_test_client("client_cback_ctiwtic");
+    let config = crate::ThreadInitConfig {
+        thread_name: Some("jack_test".to_string()),
+        flush_denormals: true,
+        ..crate::ThreadInitConfig::default()
+    };
+    c.set_thread_init_config(config.clone());
+    let ac = c
+        .activate_async(Counter::default(), Counter::default())
+        .unwrap();
+    let (c, counter, _) = ac.deactivate().unwrap();
+    assert!(counter.thread_init_count.load(Ordering::Relaxed) > 0);
+    assert_eq!(c.thread_init_config(), Some(&config));
+}
+
+#[test]
+fn client_cback_sends_notifications_over_channel() {
+    let c = open_test_client("client_cback_snoc");
+    let (notifications, rx) = crate::Notifications::channel(64);
+    let ac = c.activate_async(notifications, ()).unwrap();
+    let pa = ac
+        .as_client()
+        .register_port("pa", AudioIn::default())
+        .unwrap();
+    let port_name = pa.name().unwrap();
+    thread::sleep(time::Duration::from_millis(500));
+    let registered: Vec<_> = rx
+        .try_iter()
+        .filter_map(|n| match n {
+            crate::Notification::PortRegistered { name, .. } => name,
+            _ => None,
+        })
+        .collect();
+    assert!(registered.contains(&port_name));
+    assert_eq!(rx.dropped(), 0);
+    ac.deactivate().unwrap();
+}
+
+pub struct AddedLatency {
+    pub frames: Frames,
+}
+
+impl NotificationHandler for AddedLatency {
+    fn latency_with_scope(&mut self, scope: &crate::LatencyScope) {
+        let (min, max) = scope.upstream_range();
+        for port in scope.ports_to_update() {
+            scope.set_range(&port, (min + self.frames, max + self.frames));
+        }
+    }
+}
+
+#[test]
+fn client_cback_latency_scope_adds_latency_downstream() {
+    const ADDED: Frames = 1000;
+    let c = open_test_client("client_cback_lsald");
+    let in_p = c.register_port("in", AudioIn::default()).unwrap();
+    let out_p = c.register_port("out", crate::AudioOut::default()).unwrap();
+    let ac = c
+        .activate_async(AddedLatency { frames: ADDED }, ())
+        .unwrap();
+
+    let downstream = open_test_client("client_cback_lsald_ds");
+    let downstream_in = downstream.register_port("in", AudioIn::default()).unwrap();
+    let downstream = downstream.activate_async((), ()).unwrap();
+
+    let source = ac
+        .as_client()
+        .port_by_name("system:capture_1")
+        .expect("dummy server provides system:capture_1");
+    let source_range = source.get_latency_range(LatencyType::Capture);
+    ac.as_client().connect_ports(&source, &in_p).unwrap();
+    ac.as_client()
+        .connect_ports(&out_p, &downstream_in)
+        .unwrap();
+    thread::sleep(time::Duration::from_millis(500));
+
+    assert_eq!(
+        downstream_in.get_latency_range(LatencyType::Capture),
+        (source_range.0 + ADDED, source_range.1 + ADDED)
+    );
+    drop(downstream);
+    ac.deactivate().unwrap();
+}
+
+#[derive(Debug, Default)]
+pub struct SelectiveCounter {
+    pub callbacks: crate::CallbackSelection,
+    pub thread_init_count: AtomicUsize,
+    pub port_registration_count: usize,
+}
+
+impl NotificationHandler for SelectiveCounter {
+    fn thread_init(&self, _: &Client) {
+        self.thread_init_count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn port_registration(&mut self, _: &Client, _: PortId, _: bool) {
+        self.port_registration_count += 1;
+    }
+
+    fn callbacks(&self) -> crate::CallbackSelection {
+        self.callbacks
+    }
+}
+
+#[test]
+fn client_cback_does_not_call_unselected_callbacks() {
+    let c = open_test_client("client_cback_dncuc");
+    let handler = SelectiveCounter {
+        callbacks: crate::CallbackSelection::XRUN,
+        ..SelectiveCounter::default()
+    };
+    let ac = c.activate_async(handler, ()).unwrap();
+    let _p = ac
+        .as_client()
+        .register_port("p", AudioIn::default())
+        .unwrap();
+    let handler = ac.deactivate().unwrap().1;
+    assert_eq!(handler.thread_init_count.load(Ordering::Relaxed), 0);
+    assert_eq!(handler.port_registration_count, 0);
+}
+
+#[test]
+fn client_cback_calls_selected_callbacks() {
+    let c = open_test_client("client_cback_csc");
+    let handler = SelectiveCounter {
+        callbacks: crate::CallbackSelection::THREAD_INIT
+            | crate::CallbackSelection::PORT_REGISTRATION,
+        ..SelectiveCounter::default()
+    };
+    let ac = c.activate_async(handler, ()).unwrap();
+    let _p = ac
+        .as_client()
+        .register_port("p", AudioIn::default())
+        .unwrap();
+    let handler = ac.deactivate().unwrap().1;
+    assert!(handler.thread_init_count.load(Ordering::Relaxed) > 0);
+    assert_eq!(handler.port_registration_count, 1);
+}
+
+fn handlers_from_flag(
+    flag: &str,
+    cycles: Arc<AtomicUsize>,
+) -> (Box<dyn NotificationHandler + Sync>, Box<dyn ProcessHandler>) {
+    match flag {
+        "--count" => (
+            Box::new(Counter::default()),
+            Box::new(crate::ClosureProcessHandler::new(move |_, _| {
+                cycles.fetch_add(1, Ordering::Relaxed);
+                Control::Continue
+            })),
+        ),
+        _ => (Box::new(()), Box::new(())),
+    }
+}
+
+#[test]
+fn client_cback_activates_boxed_handlers_chosen_at_runtime() {
+    for (flag, counts) in &[("--count", true), ("--silent", false)] {
+        let c = open_test_client("client_cback_abhcar");
+        let cycles = Arc::new(AtomicUsize::new(0));
+        let (n, p) = handlers_from_flag(flag, cycles.clone());
+        let ac = c.activate_async(n, p).unwrap();
+        let (_c, n, _p) = ac.deactivate().unwrap();
+        assert_eq!(cycles.load(Ordering::Relaxed) > 0, *counts);
+        assert_eq!(n.callbacks().is_empty(), !*counts);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PortInfoRecorder {
+    pub registered: Vec<crate::PortRegistrationInfo>,
+    pub unregistered: Vec<crate::PortRegistrationInfo>,
+}
+
+impl NotificationHandler for PortInfoRecorder {
+    fn port_registration_with_info(
+        &mut self,
+        _: &Client,
+        info: &crate::PortRegistrationInfo,
+        is_registered: bool,
+    ) {
+        if is_registered {
+            self.registered.push(info.clone());
+        } else {
+            self.unregistered.push(info.clone());
+        }
+    }
+}
+
+#[test]
+fn client_cback_resolves_port_registration_info() {
+    let c = open_test_client("client_cback_rpri");
+    let ac = c.activate_async(PortInfoRecorder::default(), ()).unwrap();
+    let p = ac
+        .as_client()
+        .register_port("pa", AudioIn::default())
+        .unwrap();
+    let name = p.name().unwrap();
+    thread::sleep(time::Duration::from_millis(200));
+    ac.as_client().unregister_port(p).unwrap();
+    let recorder = ac.deactivate().unwrap().1;
+
+    assert_eq!(recorder.registered.len(), 1);
+    let info = &recorder.registered[0];
+    assert_eq!(info.name, name);
+    assert_eq!(info.flags, crate::PortFlags::IS_INPUT);
+    assert_eq!(
+        info.port_type,
+        crate::PortSpec::jack_port_type(&AudioIn::default())
+    );
+    // The port can't be resolved anymore, but its information was cached on registration.
+    assert_eq!(recorder.unregistered, recorder.registered);
+}
+
+#[test]
+fn client_cback_reports_quickly_unregistered_ports() {
+    let c = open_test_client("client_cback_rqup");
+    let ac = c.activate_async(PortInfoRecorder::default(), ()).unwrap();
+    let p = ac
+        .as_client()
+        .register_port("pa", AudioIn::default())
+        .unwrap();
+    ac.as_client().unregister_port(p).unwrap();
+    let recorder = ac.deactivate().unwrap().1;
+
+    assert_eq!(recorder.registered.len(), 1);
+    assert_eq!(recorder.unregistered.len(), 1);
+    assert_eq!(recorder.registered[0].id, recorder.unregistered[0].id);
+}
+
+#[derive(Debug, Default)]
+pub struct ConnectionRecorder {
+    pub connections: Vec<(String, String, bool)>,
+}
+
+impl NotificationHandler for ConnectionRecorder {
+    fn ports_connected_with_names(
+        &mut self,
+        _: &Client,
+        _: PortId,
+        port_name_a: &str,
+        _: PortId,
+        port_name_b: &str,
+        are_connected: bool,
+    ) {
+        self.connections.push((
+            port_name_a.to_string(),
+            port_name_b.to_string(),
+            are_connected,
+        ));
+    }
+}
+
+#[test]
+fn client_cback_resolves_connected_port_names() {
+    let c = open_test_client("client_cback_rcpn");
+    let out_p = c.register_port("out", crate::AudioOut::default()).unwrap();
+    let in_p = c.register_port("in", AudioIn::default()).unwrap();
+    let out_name = out_p.name().unwrap();
+    let in_name = in_p.name().unwrap();
+    let ac = c.activate_async(ConnectionRecorder::default(), ()).unwrap();
+    let sleep = || thread::sleep(time::Duration::from_millis(200));
+
+    ac.as_client().connect_ports(&out_p, &in_p).unwrap();
+    sleep();
+    ac.as_client().disconnect_ports(&out_p, &in_p).unwrap();
+    sleep();
+    ac.as_client().connect_ports(&out_p, &in_p).unwrap();
+    sleep();
+    // Unregistering the port disconnects it.
+    ac.as_client().unregister_port(out_p).unwrap();
+    sleep();
+    let recorder = ac.deactivate().unwrap().1;
+
+    let connected = (out_name.clone(), in_name.clone(), true);
+    let disconnected = (out_name, in_name, false);
+    assert_eq!(
+        recorder.connections,
+        vec![
+            connected.clone(),
+            disconnected.clone(),
+            connected,
+            disconnected
+        ]
+    );
+}
+
+#[test]
+fn client_cback_watches_connections_of_one_port() {
+    let mut c = open_test_client("client_cback_wcoop");
+    c.set_graph_tracking_enabled(true);
+    let out_p = c.register_port("out", crate::AudioOut::default()).unwrap();
+    let in_p = c.register_port("in", AudioIn::default()).unwrap();
+    let other_p = c.register_port("other", AudioIn::default()).unwrap();
+    let in_name = in_p.name().unwrap();
+    let other_name = other_p.name().unwrap();
+    let ac = c.activate_async((), ()).unwrap();
+    let events = ac.watch_port(&out_p).unwrap();
+    let in_events = ac.watch_port(&in_p).unwrap();
+    let timeout = time::Duration::from_secs(1);
+
+    ac.as_client().connect_ports(&out_p, &in_p).unwrap();
+    ac.as_client().connect_ports(&out_p, &other_p).unwrap();
+    ac.as_client().disconnect_ports(&out_p, &in_p).unwrap();
+    assert_eq!(
+        events.recv_timeout(timeout),
+        Some(crate::PortEvent::Connected {
+            peer: in_name.clone()
+        })
+    );
+    assert_eq!(
+        events.recv_timeout(timeout),
+        Some(crate::PortEvent::Connected {
+            peer: other_name.clone()
+        })
+    );
+    assert_eq!(
+        events.recv_timeout(timeout),
+        Some(crate::PortEvent::Disconnected { peer: in_name })
+    );
+    // Unregistering the port disconnects it and ends its events.
+    ac.as_client().unregister_port(out_p).unwrap();
+    assert_eq!(
+        events.recv_timeout(timeout),
+        Some(crate::PortEvent::Disconnected { peer: other_name })
+    );
+    assert_eq!(events.recv_timeout(timeout), None);
+    assert_eq!(in_events.try_iter().count(), 2);
+    ac.deactivate().unwrap();
+    assert_eq!(in_events.recv(), None);
+}
+
+#[test]
+fn client_cback_waits_for_first_cycle() {
+    let c = open_test_client("client_cback_wffc");
+    let ac = c.activate_async((), ()).unwrap();
+    ac.wait_for_first_cycle(time::Duration::from_secs(5))
+        .unwrap();
+    assert!(ac.cycles_processed() > 0);
+    // The first cycle ended already.
+    ac.wait_for_first_cycle(time::Duration::ZERO).unwrap();
+}
+
+#[test]
+fn client_cback_connects_ports_once_both_are_registered() {
+    let mut c = open_test_client("client_cback_cpobar");
+    c.set_graph_tracking_enabled(true);
+    let out_p = c.register_port("out", crate::AudioOut::default()).unwrap();
+    let out_name = out_p.name().unwrap();
+    let ac = c.activate_async((), ()).unwrap();
+    let timeout = time::Duration::from_secs(5);
+    let in_name = format!("{}:in", ac.as_client().name());
+    let mut pending = ac.connect_when_available(&out_name, &in_name, timeout);
+    assert_eq!(pending.try_status(), crate::ConnectionStatus::Pending);
+    // A timeout too large for a deadline waits until the connection is cancelled.
+    let cancelled = ac.connect_when_available(&out_name, "nobody:in", time::Duration::MAX);
+    drop(cancelled);
+
+    let in_p = ac
+        .as_client()
+        .register_port("in", AudioIn::default())
+        .unwrap();
+    assert_eq!(pending.wait(), Ok(()));
+    assert!(out_p.is_connected_to(&in_name).unwrap());
+    // Connected ports complete right away.
+    let mut connected = ac.connect_when_available(&out_name, &in_name, timeout);
+    assert_eq!(connected.try_status(), crate::ConnectionStatus::Connected);
+    let missing = ac.connect_when_available(&out_name, "nobody:in", time::Duration::ZERO);
+    assert_eq!(
+        missing.wait(),
+        Err(crate::Error::PortConnectionTimeout(
+            out_name,
+            "nobody:in".to_string()
+        ))
+    );
+    drop(in_p);
+}
+
+#[test]
+fn client_cback_does_not_track_graph_unless_asked() {
+    let c = open_test_client("client_cback_dntgua");
+    let out_p = c.register_port("out", crate::AudioOut::default()).unwrap();
+    let out_name = out_p.name().unwrap();
+    // `()` selects no callbacks, so the graph callbacks are not registered.
+    let ac = c.activate_async((), ()).unwrap();
+    assert!(matches!(
+        ac.watch_port(&out_p),
+        Err(crate::Error::GraphNotTracked)
+    ));
+    assert!(matches!(
+        ac.name_registry(),
+        Err(crate::Error::GraphNotTracked)
+    ));
+    let missing = ac.connect_when_available(&out_name, "nobody:in", time::Duration::from_secs(5));
+    assert_eq!(missing.wait(), Err(crate::Error::GraphNotTracked));
+
+    // Handlers that select the graph callbacks have the graph tracked.
+    let ac = active_test_client("client_cback_dntgua_all");
+    assert!(ac.name_registry().is_ok());
+}
+
+#[test]
+fn client_cback_names_ports_from_registration_notifications() {
+    let mut c = open_test_client("client_cback_npfrn");
+    c.set_graph_tracking_enabled(true);
+    let seeded = c.register_port("seeded", AudioIn::default()).unwrap();
+    let seeded_name = seeded.name().unwrap();
+    let ac = c.activate_async((), ()).unwrap();
+    let names = ac.name_registry().unwrap();
+    assert!(names.all_ports().iter().any(|(_, n)| *n == seeded_name));
+
+    let registered = ac
+        .as_client()
+        .register_port("registered", AudioIn::default())
+        .unwrap();
+    let registered_name = registered.name().unwrap();
+    let deadline = time::Instant::now() + time::Duration::from_secs(1);
+    while !names.all_ports().iter().any(|(_, n)| *n == registered_name) {
+        assert!(time::Instant::now() < deadline, "port was never named");
+        thread::sleep(time::Duration::from_millis(10));
+    }
+    drop(seeded);
+
+    let other = open_test_client("client_cback_npfrn_other");
+    let other_uuid = crate::Uuid::parse(&other.uuid_string()).unwrap();
+    let deadline = time::Instant::now() + time::Duration::from_secs(1);
+    while names.name_of_client(other_uuid).is_none() {
+        assert!(time::Instant::now() < deadline, "client was never named");
+        thread::sleep(time::Duration::from_millis(10));
+    }
+    assert_eq!(
+        names.name_of_client(other_uuid),
+        Some(other.name().to_string())
+    );
+}
+
+#[test]
+fn client_cback_reports_callback_registration_failure() {
+    let ac = active_test_client("client_cback_rcrf");
+    // Callbacks can't be registered on a client that is already active.
+    let client = unsafe { Client::from_raw(ac.as_client().raw()) };
+    let mut ctx = Box::new(super::callbacks::CallbackContext::new(client, (), ()));
+    let res = unsafe { super::callbacks::CallbackContext::register_callbacks(&mut ctx) };
+    assert_eq!(
+        res,
+        Err(crate::Error::CallbackRegistrationError(
+            "process".to_string()
+        ))
+    );
+    // The client is still owned by `ac`.
+    let (client, _, _) = ctx.into_parts();
+    mem::forget(client);
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_reports_unavailable_callbacks() {
+    let ac = active_test_client("client_cback_ruc");
+    let unavailable = ac.unavailable_callbacks();
+    // The port rename callback is not supported yet.
+    assert_eq!(unavailable, crate::CallbackSelection::PORT_RENAME);
+    ac.deactivate().unwrap();
+
+    let ac = open_test_client("client_cback_ruc")
+        .activate_async((), ())
+        .unwrap();
+    assert!(ac.unavailable_callbacks().is_empty());
+}
+
+/// Counts process cycles into a counter that lives on the test's stack.
+struct BorrowingCounter<'a> {
+    cycles: &'a AtomicUsize,
+}
+
+impl<'a> ProcessHandler for BorrowingCounter<'a> {
+    fn process(&mut self, _: &Client, _: &ProcessScope) -> Control {
+        self.cycles.fetch_add(1, Ordering::Relaxed);
+        Control::Continue
+    }
+}
+
+#[test]
+fn client_cback_scoped_handler_borrows_stack_data() {
+    let cycles = AtomicUsize::new(0);
+    let handler = BorrowingCounter { cycles: &cycles };
+    let (client, name) = open_test_client("client_cback_shbsd")
+        .activate_scoped((), handler, |ac| {
+            thread::sleep(time::Duration::from_secs(1));
+            ac.as_client().name().to_string()
+        })
+        .unwrap();
+    assert_eq!(name, client.name());
+    let processed = cycles.load(Ordering::Relaxed);
+    assert!(processed > 0);
+    // The client is no longer active once `activate_scoped` returns.
+    thread::sleep(time::Duration::from_millis(500));
+    assert_eq!(cycles.load(Ordering::Relaxed), processed);
+    // The client can be activated again.
+    client.activate_async((), ()).unwrap().deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_scoped_deactivates_on_panic() {
+    let cycles = AtomicUsize::new(0);
+    let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let handler = BorrowingCounter { cycles: &cycles };
+        open_test_client("client_cback_sdop").activate_scoped((), handler, |_| {
+            thread::sleep(time::Duration::from_secs(1));
+            panic!("induced panic in scope");
+        })
+    }));
+    assert!(res.is_err());
+    let processed = cycles.load(Ordering::Relaxed);
+    assert!(processed > 0);
+    thread::sleep(time::Duration::from_millis(500));
+    assert_eq!(cycles.load(Ordering::Relaxed), processed);
+}
+
+#[test]
+fn client_cback_is_processing_until_quit() {
+    let ac = active_test_client("client_cback_ipuq");
+    assert!(ac.is_processing());
+    assert!(!ac.wait_for_quit(time::Duration::from_millis(500)));
+
+    let counter = Counter {
+        process_return_val: Control::Quit,
+        ..Counter::default()
+    };
+    let ac = open_test_client("client_cback_ipuq")
+        .activate_async(Counter::default(), counter)
+        .unwrap();
+    assert!(ac.wait_for_quit(time::Duration::from_secs(2)));
+    assert!(!ac.is_processing());
+    // A timeout that no deadline can be computed for does not panic.
+    assert!(ac.wait_for_quit(time::Duration::MAX));
+    // JACK stops calling the client after the first cycle.
+    thread::sleep(time::Duration::from_millis(500));
+    let buffer_size = ac.as_client().buffer_size() as usize;
+    let (_, _, p) = ac.deactivate().unwrap();
+    assert_eq!(p.frames_processed, buffer_size);
+}
+
+/// Counts the allocations made by the wrapped process handler.
+struct AllocationCounter<P> {
+    inner: P,
+    process_allocations: Arc<AtomicUsize>,
+}
+
+impl<P: ProcessHandler> ProcessHandler for AllocationCounter<P> {
+    fn process(&mut self, c: &Client, ps: &ProcessScope) -> Control {
+        let before = crate::test_alloc::allocations();
+        let res = self.inner.process(c, ps);
+        let allocations = crate::test_alloc::allocations() - before;
+        self.process_allocations
+            .fetch_add(allocations, Ordering::Relaxed);
+        res
+    }
+
+    fn buffer_size(&mut self, c: &Client, size: Frames) -> Control {
+        self.inner.buffer_size(c, size)
+    }
+}
+
+#[test]
+fn client_cback_resizes_buffer_pool_outside_of_process() {
+    let c = open_test_client("client_cback_rbpoop");
+    let initial = c.buffer_size();
+    let mismatches = Arc::new(AtomicUsize::new(0));
+    let process_allocations = Arc::new(AtomicUsize::new(0));
+    let handler = AllocationCounter {
+        inner: crate::PooledProcessHandler::new(crate::BufferPool::new(2, 2), {
+            let mismatches = mismatches.clone();
+            move |_: &Client, ps: &ProcessScope, pool: &mut crate::BufferPool| {
+                for channel in pool.channels_mut(1) {
+                    if channel.len() != ps.n_frames() as usize {
+                        mismatches.fetch_add(1, Ordering::Relaxed);
+                    }
+                    channel.fill(1.0);
+                }
+                Control::Continue
+            }
+        }),
+        process_allocations: process_allocations.clone(),
+    };
+    let ac = c.activate_async((), handler).unwrap();
+    thread::sleep(time::Duration::from_millis(200));
+    for &size in &[initial / 2, initial * 2, initial] {
+        ac.as_client().set_buffer_size(size).unwrap();
+        thread::sleep(time::Duration::from_millis(200));
+    }
+    let (_, _, mut handler) = ac.deactivate().unwrap();
+    assert_eq!(mismatches.load(Ordering::Relaxed), 0);
+    assert_eq!(process_allocations.load(Ordering::Relaxed), 0);
+    let pool = &mut handler.inner.pool;
+    assert_eq!(pool.buffer_size(), initial);
+    assert!(pool.buffer_mut(1).iter().all(|&s| s == 1.0));
+}
+
+/// Records the MIDI buffer size, and fills a MIDI port with 3 byte events once.
+struct MidiFiller {
+    port: crate::Port<crate::MidiOut>,
+    midi_buffer_size: usize,
+    audio_buffer_size: usize,
+    written: Option<usize>,
+}
+
+impl ProcessHandler for MidiFiller {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        if self.written.is_none() {
+            let mut writer = self.port.writer(ps);
+            let mut written = 0;
+            while writer
+                .write(&crate::RawMidi {
+                    time: 0,
+                    bytes: &[0x90, 60, 100],
+                })
+                .is_ok()
+            {
+                written += 1;
+            }
+            self.written = Some(written);
+        }
+        Control::Continue
+    }
+
+    fn buffer_size_with_scope(&mut self, scope: &crate::BufferSizeScope) -> Control {
+        self.midi_buffer_size = scope.midi_buffer_size();
+        self.audio_buffer_size = scope.audio_buffer_size();
+        Control::Continue
+    }
+}
+
+struct CycleLocalResizer {
+    buffer: Arc<crate::CycleLocal<Vec<f32>>>,
+    mismatches: Arc<AtomicUsize>,
+    unavailable: Arc<AtomicUsize>,
+}
+
+impl ProcessHandler for CycleLocalResizer {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        let n_frames = ps.n_frames() as usize;
+        let used = self.buffer.with(ps, |buffer| {
+            if buffer.len() != n_frames {
+                self.mismatches.fetch_add(1, Ordering::Relaxed);
+            }
+            buffer.fill(1.0);
+        });
+        if used.is_none() {
+            self.unavailable.fetch_add(1, Ordering::Relaxed);
+        }
+        Control::Continue
+    }
+
+    fn buffer_size_with_scope(&mut self, scope: &crate::BufferSizeScope) -> Control {
+        let n_frames = scope.n_frames() as usize;
+        self.buffer
+            .rebuild(scope, |buffer| buffer.resize(n_frames, 0.0));
+        Control::Continue
+    }
+}
+
+#[test]
+fn client_cback_rebuilds_cycle_local_on_buffer_size_change() {
+    let c = open_test_client("client_cback_rclobsc");
+    let initial = c.buffer_size();
+    let buffer = Arc::new(crate::CycleLocal::default());
+    let mismatches = Arc::new(AtomicUsize::new(0));
+    let unavailable = Arc::new(AtomicUsize::new(0));
+    let handler = CycleLocalResizer {
+        buffer: buffer.clone(),
+        mismatches: mismatches.clone(),
+        unavailable: unavailable.clone(),
+    };
+    let ac = c.activate_async((), handler).unwrap();
+    let timeout = time::Duration::from_secs(5);
+    ac.wait_for_first_cycle(timeout).unwrap();
+    for &size in &[initial / 2, initial * 2, initial] {
+        ac.set_buffer_size_sync(size, timeout).unwrap();
+        // Run a few cycles with the new size.
+        let cycles = ac.cycles_processed();
+        while ac.cycles_processed() < cycles + 4 {
+            thread::sleep(time::Duration::from_millis(10));
+        }
+    }
+    ac.deactivate().unwrap();
+    assert_eq!(mismatches.load(Ordering::Relaxed), 0);
+    // Rebuilding in the buffer size callback never overlaps with a cycle.
+    assert_eq!(unavailable.load(Ordering::Relaxed), 0);
+    assert!(buffer.epoch() >= 4, "epoch {}", buffer.epoch());
+    buffer.update(|b| assert_eq!(b.len(), initial as usize));
+}
+
+#[test]
+fn client_cback_reports_port_buffer_sizes_in_buffer_size_callback() {
+    let c = open_test_client("client_cback_rpbsibsc");
+    let handler = MidiFiller {
+        port: c.register_port("midi", crate::MidiOut::default()).unwrap(),
+        midi_buffer_size: 0,
+        audio_buffer_size: 0,
+        written: None,
+    };
+    let ac = c.activate_async((), handler).unwrap();
+    ac.wait_for_first_cycle(time::Duration::from_secs(5))
+        .unwrap();
+    let buffer_size = ac.as_client().buffer_size() as usize;
+    let (_, _, handler) = ac.deactivate().unwrap();
+
+    assert_eq!(
+        handler.audio_buffer_size,
+        buffer_size * std::mem::size_of::<f32>()
+    );
+    // Each event takes its bytes and some bookkeeping from the MIDI buffer.
+    let written = handler.written.unwrap();
+    assert!(written > 0);
+    assert!(written * 3 <= handler.midi_buffer_size);
+    assert!(written * 32 >= handler.midi_buffer_size);
+}
+
+#[test]
+fn client_cback_weak_client_queries_from_other_thread() {
+    let (tx, rx) = std::sync::mpsc::sync_channel(1);
+    let handler = crate::ClosureProcessHandler::new(move |c: &Client, _: &ProcessScope| {
+        // Hand a weak client to the worker thread on the first cycle.
+        let _ = tx.try_send(c.downgrade());
+        Control::Continue
+    });
+    let ac = open_test_client("client_cback_wcqfot")
+        .activate_async((), handler)
+        .unwrap();
+    let weak = rx.recv_timeout(time::Duration::from_secs(1)).unwrap();
+    let expected = ac.as_client().sample_rate();
+    let worker = thread::spawn(move || {
+        let sample_rate = weak.sample_rate();
+        let frame_time = weak.frame_time();
+        (weak, sample_rate, frame_time)
+    });
+    let (weak, sample_rate, frame_time) = worker.join().unwrap();
+    assert_eq!(sample_rate, Ok(expected));
+    assert!(frame_time.unwrap() > 0);
+    ac.deactivate().unwrap();
+    assert!(!weak.is_alive());
+}
+
+#[test]
+fn client_cback_reports_process_thread_id() {
+    let process_thread = Arc::new(std::sync::Mutex::new(None));
+    let handler = crate::ClosureProcessHandler::new({
+        let process_thread = process_thread.clone();
+        move |_: &Client, _: &ProcessScope| {
+            if let Ok(mut t) = process_thread.try_lock() {
+                *t = Some(unsafe { libc::pthread_self() });
+            }
+            Control::Continue
+        }
+    });
+    let c = open_test_client("client_cback_rpti");
+    assert_eq!(c.thread_id(), None);
+    let ac = c.activate_async((), handler).unwrap();
+    ac.wait_for_first_cycle(time::Duration::from_secs(5))
+        .unwrap();
+    let process_thread = *process_thread.lock().unwrap();
+    assert!(process_thread.is_some());
+    assert_eq!(ac.as_client().thread_id(), process_thread);
+}
+
+static CREATED_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe extern "C" fn counting_thread_creator(
+    thread: *mut libc::pthread_t,
+    attr: *const libc::pthread_attr_t,
+    function: Option<extern "C" fn(*mut libc::c_void) -> *mut libc::c_void>,
+    arg: *mut libc::c_void,
+) -> libc::c_int {
+    CREATED_THREADS.fetch_add(1, Ordering::Relaxed);
+    match function {
+        Some(function) => libc::pthread_create(thread, attr, function, arg),
+        None => libc::EINVAL,
+    }
+}
+
+#[test]
+fn client_cback_creates_threads_with_thread_creator() {
+    unsafe { crate::set_thread_creator(counting_thread_creator) };
+    let ac = active_test_client("client_cback_ctwtc");
+    thread::sleep(time::Duration::from_millis(200));
+    unsafe { crate::reset_thread_creator() };
+    assert!(CREATED_THREADS.load(Ordering::Relaxed) > 0);
+    ac.deactivate().unwrap();
+}
+
+#[test]
+fn client_cback_process_thread_runs_loopback_cycles() {
+    let c = open_test_client("client_cback_ptrlc");
+    let in_port = c.register_port("in", AudioIn::default()).unwrap();
+    let mut out_port = c.register_port("out", crate::AudioOut::default()).unwrap();
+    let cycles = Arc::new(AtomicUsize::new(0));
+    let looped_back = Arc::new(AtomicUsize::new(0));
+    let f = {
+        let cycles = cycles.clone();
+        let looped_back = looped_back.clone();
+        move |mut ctx: crate::ProcessThreadContext| {
+            while let Some(ps) = ctx.cycle_wait() {
+                out_port.as_mut_slice(&ps).fill(1.0);
+                if in_port.as_slice(&ps).iter().all(|&s| s == 1.0) {
+                    looped_back.fetch_add(1, Ordering::Relaxed);
+                }
+                cycles.fetch_add(1, Ordering::Relaxed);
+                ctx.cycle_signal(Control::Continue);
+            }
+        }
+    };
+    let ac = c.activate_thread((), f).unwrap();
+    ac.as_client()
+        .connect_ports_by_name("client_cback_ptrlc:out", "client_cback_ptrlc:in")
+        .unwrap();
+    thread::sleep(time::Duration::from_millis(500));
+    assert!(ac.is_processing());
+    ac.deactivate().unwrap();
+    assert!(cycles.load(Ordering::Relaxed) > 0);
+    assert!(looped_back.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn client_cback_process_thread_can_quit() {
+    let ac = open_test_client("client_cback_ptcq")
+        .activate_thread((), |mut ctx: crate::ProcessThreadContext| {
+            if ctx.cycle_wait().is_some() {
+                ctx.cycle_signal(Control::Quit);
+            }
+        })
+        .unwrap();
+    assert!(ac.wait_for_quit(time::Duration::from_secs(1)));
+    ac.deactivate().unwrap();
+}
+
+#[cfg(feature = "session")]
+#[test]
+fn client_cback_receives_session_events() {
+    let mut c = open_test_client("client_cback_rse");
+    let (tx, rx) = std::sync::mpsc::channel();
+    c.register_session_handler(crate::ClosureSessionHandler::new(move |event| {
+        let _ = tx.send((
+            event.event_type(),
+            event.session_dir().to_path_buf(),
+            event.client_uuid().to_string(),
+        ));
+    }))
+    .unwrap();
+    let ac = c.activate_async((), ()).unwrap();
+    let uuid = ac.as_client().uuid_string();
+
+    // Act as a session manager, the request returns once the client has replied.
+    let manager = open_test_client("client_cback_rse_manager");
+    manager.session_notify(
+        Some("client_cback_rse"),
+        crate::SessionEventType::Save,
+        "/tmp/rust_jack_session/",
+    );
+
+    let (event_type, session_dir, client_uuid) =
+        rx.recv_timeout(time::Duration::from_secs(1)).unwrap();
+    assert_eq!(event_type, crate::SessionEventType::Save);
+    assert!(session_dir.starts_with("/tmp/rust_jack_session/"));
+    assert_eq!(client_uuid, uuid);
+    ac.deactivate().unwrap();
+}
+
+#[cfg(feature = "session")]
+#[test]
+fn client_cback_replies_to_session_events() {
+    let mut c = open_test_client("client_cback_rtse");
+    c.register_session_handler(crate::ClosureSessionHandler::new(|event| {
+        let command_line = format!("restore ${{SESSION_DIR}} {}", event.client_uuid());
+        event
+            .reply(&command_line, crate::SessionFlags::NEED_TERMINAL)
+            .unwrap();
+    }))
+    .unwrap();
+    let ac = c.activate_async((), ()).unwrap();
+    let uuid = ac.as_client().uuid_string();
+
+    let manager = open_test_client("client_cback_rtse_manager");
+    let replies = manager.session_notify(
+        Some("client_cback_rtse"),
+        crate::SessionEventType::SaveTemplate,
+        "/tmp/rust_jack_session/",
+    );
+    assert_eq!(
+        replies,
+        [crate::SessionCommand {
+            uuid: uuid.clone(),
+            client_name: "client_cback_rtse".to_string(),
+            command: format!("restore ${{SESSION_DIR}} {}", uuid),
+            flags: crate::SessionFlags::NEED_TERMINAL,
+        }]
+    );
+    ac.deactivate().unwrap();
+}
+
+#[cfg(feature = "session")]
+#[test]
+fn client_cback_session_notify_without_responders_is_empty() {
+    let manager = open_test_client("client_cback_snwr");
+    let replies = manager.session_notify(
+        Some("client_cback_snwr_missing"),
+        crate::SessionEventType::Save,
+        "/tmp/rust_jack_session/",
+    );
+    assert!(replies.is_empty());
+}
+
+#[cfg(feature = "session")]
+#[test]
+fn client_cback_reports_session_callbacks() {
+    let mut c = open_test_client("client_cback_rsc");
+    let registered = c.register_session_handler(crate::ClosureSessionHandler::new(|_| {}));
+    let ac = c.activate_async((), ()).unwrap();
+    let other = open_test_client("client_cback_rsc_other");
+    if !crate::session_available() {
+        assert_eq!(registered, Err(crate::Error::WeakFunctionNotFound));
+        assert_eq!(other.has_session_callback("client_cback_rsc"), None);
+        return;
+    }
+    assert_eq!(registered, Ok(()));
+    assert_eq!(other.has_session_callback("client_cback_rsc"), Some(true));
+    assert_eq!(
+        ac.as_client()
+            .has_session_callback("client_cback_rsc_other"),
+        Some(false)
+    );
+    assert_eq!(other.has_session_callback("client_cback_rsc_missing"), None);
+    assert_eq!(other.has_session_callback("client_cback_rsc\0"), None);
+    assert!(other
+        .session_notify(
+            Some("client_cback_rsc\0"),
+            crate::SessionEventType::Save,
+            "/tmp/rust_jack_session/"
+        )
+        .is_empty());
+    ac.deactivate().unwrap();
+}
+
+#[cfg(feature = "session")]
+#[test]
+fn client_cback_session_support_saves_and_quits() {
+    let mut c = open_test_client("client_cback_sssq");
+    let uuid = c.uuid_string();
+    let session = crate::SessionSupport::register(&mut c, |dir: &std::path::Path| {
+        std::fs::write(dir.join("state.txt"), "saved")?;
+        Ok::<_, std::io::Error>(format!(
+            "restore --state {}state.txt",
+            crate::SESSION_DIR_PLACEHOLDER
+        ))
+    })
+    .unwrap();
+    let ac = c.activate_async((), ()).unwrap();
+
+    let dir = std::env::temp_dir().join("rust_jack_session_sssq/");
+    std::fs::create_dir_all(&dir).unwrap();
+    let manager = open_test_client("client_cback_sssq_manager");
+    let replies = manager.session_notify(
+        Some("client_cback_sssq"),
+        crate::SessionEventType::SaveAndQuit,
+        &format!("{}/", dir.display()),
+    );
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].uuid, uuid);
+    assert_eq!(
+        replies[0].command,
+        "restore --state ${SESSION_DIR}state.txt"
+    );
+    assert_eq!(replies[0].flags, crate::SessionFlags::empty());
+
+    let (_, reason) = session
+        .shutdown_receiver()
+        .recv_timeout(time::Duration::from_secs(1))
+        .unwrap();
+    assert!(reason.contains("quit"));
+    let saved = std::fs::read_to_string(dir.join("client_cback_sssq").join("state.txt"));
+    assert_eq!(saved.unwrap(), "saved");
+    ac.deactivate().unwrap();
+}
+
+#[cfg(feature = "session")]
+#[test]
+fn client_cback_session_support_reports_save_errors() {
+    let mut c = open_test_client("client_cback_ssrse");
+    let session = crate::SessionSupport::register(&mut c, |_: &std::path::Path| {
+        Err::<String, _>("disk full")
+    })
+    .unwrap();
+    let ac = c.activate_async((), ()).unwrap();
+    let manager = open_test_client("client_cback_ssrse_manager");
+    let replies = manager.session_notify(
+        Some("client_cback_ssrse"),
+        crate::SessionEventType::SaveAndQuit,
+        "/tmp/rust_jack_session/",
+    );
+    assert_eq!(replies.len(), 1);
+    assert_eq!(replies[0].flags, crate::SessionFlags::SAVE_ERROR);
+    // The application keeps running when saving failed.
+    assert!(session
+        .shutdown_receiver()
+        .recv_timeout(time::Duration::from_millis(100))
+        .is_none());
+    ac.deactivate().unwrap();
+}
+
+#[derive(Default)]
+struct SharedCounter {
+    frames_processed: AtomicUsize,
+    buffer_size: AtomicUsize,
+}
+
+impl crate::SharedNotificationHandler for SharedCounter {}
+
+impl crate::SharedProcessHandler for SharedCounter {
+    fn process(&self, _: &Client, ps: &ProcessScope) -> Control {
+        self.frames_processed
+            .fetch_add(ps.n_frames() as usize, Ordering::Relaxed);
+        Control::Continue
+    }
+
+    fn buffer_size(&self, _: &Client, size: Frames) -> Control {
+        self.buffer_size.store(size as usize, Ordering::Relaxed);
+        Control::Continue
+    }
+}
+
+#[test]
+fn client_cback_shares_handler_through_arc() {
+    let counter = Arc::new(SharedCounter::default());
+    let ac = open_test_client("client_cback_shares_handler_through_arc")
+        .activate_async(counter.clone(), counter.clone())
+        .unwrap();
+    let expected_buffer_size = ac.as_client().buffer_size() as usize;
+    ac.wait_for_first_cycle(time::Duration::from_secs(5))
+        .unwrap();
+    // The state is observed while the client is still processing.
+    assert!(counter.frames_processed.load(Ordering::Relaxed) > 0);
+    assert_eq!(
+        counter.buffer_size.load(Ordering::Relaxed),
+        expected_buffer_size
+    );
+    ac.deactivate().unwrap();
+    assert_eq!(Arc::strong_count(&counter), 1);
+}
+
+#[test]
+fn client_cback_shares_static_handler() {
+    static COUNTER: SharedCounter = SharedCounter {
+        frames_processed: AtomicUsize::new(0),
+        buffer_size: AtomicUsize::new(0),
+    };
+    let ac = open_test_client("client_cback_shares_static_handler")
+        .activate_async(&COUNTER, &COUNTER)
+        .unwrap();
+    ac.wait_for_first_cycle(time::Duration::from_secs(5))
+        .unwrap();
+    assert!(COUNTER.frames_processed.load(Ordering::Relaxed) > 0);
+    ac.deactivate().unwrap();
+}
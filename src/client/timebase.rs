@@ -0,0 +1,84 @@
+use jack_sys as j;
+
+use super::callbacks::forbid_alloc;
+use crate::{Client, Error, Frames, Transport, TransportPosition, TransportState};
+
+/// A trait for providing the position of the JACK transport in bars, beats and ticks, see
+/// `Client::register_timebase_handler`. `TimebaseMaster` implements it for a tempo map.
+pub trait TimebaseHandler: Send {
+    /// Called in the process thread, after the process callbacks of the cycle, to fill in the
+    /// musical fields of `pos` for the next cycle. `pos.frame()` is the frame the cycle starts at.
+    /// `new_pos` is `true` for the first cycle after the transport was relocated, and for the
+    /// first call after the client became the timebase master.
+    ///
+    /// This is called from the real-time thread, so it must not block or allocate.
+    fn timebase(
+        &mut self,
+        state: TransportState,
+        n_frames: Frames,
+        pos: &mut TransportPosition,
+        new_pos: bool,
+    );
+}
+
+impl Client {
+    /// Make the client the timebase master, which provides the bar, beat and tick position of the
+    /// transport to all clients with `handler`. If `conditional` is `true`, this fails when
+    /// another client is the timebase master already. Otherwise the other client is replaced.
+    ///
+    /// The handler is called once the client is activated, and is kept until the client is
+    /// closed. Being the timebase master ends when the client is deactivated or closed, another
+    /// client takes over, or `release_timebase` is called.
+    ///
+    /// # Panics
+    /// Calling this method more than once on any given client will cause a panic.
+    pub fn register_timebase_handler<H: TimebaseHandler + 'static>(
+        &mut self,
+        handler: H,
+        conditional: bool,
+    ) -> Result<(), Error> {
+        assert!(self.timebase_handler_slot().is_none());
+        let mut handler = Box::new(handler);
+        let arg = &mut *handler as *mut H as *mut libc::c_void;
+        *self.timebase_handler_slot() = Some(handler);
+        let res = unsafe {
+            j::jack_set_timebase_callback(
+                self.raw(),
+                conditional as libc::c_int,
+                Some(timebase_callback::<H>),
+                arg,
+            )
+        };
+        match res {
+            0 => Ok(()),
+            _ => {
+                *self.timebase_handler_slot() = None;
+                Err(Error::CallbackRegistrationError("timebase".to_string()))
+            }
+        }
+    }
+
+    /// Stop being the timebase master. The handler passed to `register_timebase_handler` is not
+    /// called anymore, but it is kept until the client is closed.
+    pub fn release_timebase(&self) -> Result<(), Error> {
+        match unsafe { j::jack_release_timebase(self.raw()) } {
+            0 => Ok(()),
+            _ => Err(Error::UnknownError),
+        }
+    }
+}
+
+unsafe extern "C" fn timebase_callback<H>(
+    state: j::jack_transport_state_t,
+    n_frames: Frames,
+    pos: *mut j::jack_position_t,
+    new_pos: libc::c_int,
+    arg: *mut libc::c_void,
+) where
+    H: TimebaseHandler,
+{
+    let handler = &mut *(arg as *mut H);
+    let pos = &mut *(pos as *mut TransportPosition);
+    let state = Transport::state_from_ffi(state);
+    forbid_alloc(|| handler.timebase(state, n_frames, pos, new_pos != 0));
+}
@@ -0,0 +1,139 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::Time;
+
+/// The number of bins of `TimingStats::histogram`.
+pub const TIMING_HISTOGRAM_BINS: usize = 16;
+
+/// Statistics on how long the process callback of a client takes.
+///
+/// Obtained through `AsyncClient::timing_stats` on clients that enabled them with
+/// `Client::set_timing_stats_enabled`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimingStats {
+    /// The number of process cycles that were measured.
+    pub cycles: u64,
+    /// The shortest time, in microseconds, that a process cycle took.
+    pub min_usecs: Time,
+    /// The average time, in microseconds, that a process cycle took.
+    pub avg_usecs: f64,
+    /// The longest time, in microseconds, that a process cycle took.
+    pub max_usecs: Time,
+    /// The length of a period, in microseconds, at the current buffer size and sample rate.
+    pub period_usecs: f64,
+    /// The number of cycles by duration. Bin `0` counts the cycles that took less than 1
+    /// microsecond and bin `i` the cycles that took from `2^(i - 1)` up to `2^i` microseconds. The
+    /// last bin also counts all longer cycles.
+    pub histogram: [u64; TIMING_HISTOGRAM_BINS],
+}
+
+impl TimingStats {
+    /// The share of the period that the process callback takes on average, from `0.0` to `1.0`.
+    ///
+    /// Unlike `Client::cpu_load`, which is the load of the whole JACK server, this only covers the
+    /// process callback of this client.
+    pub fn dsp_load(&self) -> f64 {
+        if self.period_usecs > 0.0 {
+            self.avg_usecs / self.period_usecs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Lock-free process cycle timings, updated from the process callback.
+#[derive(Debug)]
+pub struct TimingCounter {
+    cycles: AtomicU64,
+    total: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+    histogram: [AtomicU64; TIMING_HISTOGRAM_BINS],
+}
+
+impl Default for TimingCounter {
+    fn default() -> TimingCounter {
+        TimingCounter {
+            cycles: AtomicU64::new(0),
+            total: AtomicU64::new(0),
+            min: AtomicU64::new(Time::MAX),
+            max: AtomicU64::new(0),
+            histogram: Default::default(),
+        }
+    }
+}
+
+impl TimingCounter {
+    /// Record a process cycle that started at `start` and ended at `end`, in microseconds. Does not
+    /// allocate or block.
+    pub fn record(&self, start: Time, end: Time) {
+        let usecs = end.saturating_sub(start);
+        let bin = (Time::BITS - usecs.leading_zeros()) as usize;
+        self.histogram[bin.min(TIMING_HISTOGRAM_BINS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.min.fetch_min(usecs, Ordering::Relaxed);
+        self.max.fetch_max(usecs, Ordering::Relaxed);
+        self.total.fetch_add(usecs, Ordering::Relaxed);
+        self.cycles.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self, period_usecs: f64) -> TimingStats {
+        let cycles = self.cycles.load(Ordering::Relaxed);
+        let mut histogram = [0; TIMING_HISTOGRAM_BINS];
+        for (count, bin) in histogram.iter_mut().zip(self.histogram.iter()) {
+            *count = bin.load(Ordering::Relaxed);
+        }
+        let (min_usecs, avg_usecs) = match cycles {
+            0 => (0, 0.0),
+            _ => (
+                self.min.load(Ordering::Relaxed),
+                self.total.load(Ordering::Relaxed) as f64 / cycles as f64,
+            ),
+        };
+        TimingStats {
+            cycles,
+            min_usecs,
+            avg_usecs,
+            max_usecs: self.max.load(Ordering::Relaxed),
+            period_usecs,
+            histogram,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn timing_counter_starts_empty() {
+        assert_eq!(TimingCounter::default().stats(0.0), TimingStats::default());
+    }
+
+    #[test]
+    fn timing_counter_tracks_min_avg_and_max() {
+        let counter = TimingCounter::default();
+        counter.record(100, 110);
+        counter.record(200, 230);
+        counter.record(300, 320);
+        let stats = counter.stats(40.0);
+        assert_eq!(stats.cycles, 3);
+        assert_eq!(stats.min_usecs, 10);
+        assert_eq!(stats.avg_usecs, 20.0);
+        assert_eq!(stats.max_usecs, 30);
+        assert_eq!(stats.dsp_load(), 0.5);
+    }
+
+    #[test]
+    fn timing_counter_bins_by_power_of_two() {
+        let counter = TimingCounter::default();
+        counter.record(0, 0);
+        counter.record(0, 1);
+        counter.record(0, 3);
+        counter.record(0, 4);
+        counter.record(0, Time::MAX);
+        let histogram = counter.stats(0.0).histogram;
+        assert_eq!(histogram[..4], [1, 1, 1, 1]);
+        assert_eq!(histogram[TIMING_HISTOGRAM_BINS - 1], 1);
+        assert_eq!(histogram.iter().sum::<u64>(), 5);
+    }
+}
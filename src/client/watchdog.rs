@@ -0,0 +1,403 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc, Mutex, PoisonError};
+use std::{thread, time};
+
+use super::async_client::AsyncClient;
+use crate::Frames;
+
+/// The shortest time between two checks of a `Watchdog`.
+const MIN_POLL_INTERVAL: time::Duration = time::Duration::from_millis(1);
+
+/// The longest time that `CycleCounter::wait_for_first` sleeps before it checks the count again,
+/// in case a wake up was missed.
+const MAX_PARK_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+/// Counts the process cycles of an active client, updated from the process callback.
+#[derive(Debug, Default)]
+pub struct CycleCounter {
+    cycles: AtomicU64,
+    processed: AtomicU64,
+    n_frames: AtomicU32,
+    freewheeling: AtomicBool,
+    /// The threads in `wait_for_first`.
+    waiters: Mutex<Vec<thread::Thread>>,
+}
+
+impl CycleCounter {
+    /// Record the start of a process cycle. Does not allocate or block.
+    #[inline(always)]
+    pub fn record(&self, n_frames: Frames) {
+        self.n_frames.store(n_frames, Ordering::Relaxed);
+        self.cycles.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record the end of a process cycle. Does not allocate or block.
+    #[inline(always)]
+    pub fn finish(&self) {
+        if self.processed.fetch_add(1, Ordering::SeqCst) == 0 {
+            self.wake_waiters();
+        }
+    }
+
+    // The process thread never waits for the lock. A waiter that holds it checks the count after
+    // releasing it, or at the latest after `MAX_PARK_INTERVAL`.
+    #[cold]
+    fn wake_waiters(&self) {
+        if let Ok(waiters) = self.waiters.try_lock() {
+            waiters.iter().for_each(thread::Thread::unpark);
+        }
+    }
+
+    /// The number of process cycles that have ended.
+    pub fn processed(&self) -> u64 {
+        self.processed.load(Ordering::SeqCst)
+    }
+
+    /// Block until the first process cycle has ended. Returns `false` if it did not end within
+    /// `timeout`. A timeout too large to compute a deadline for waits without one.
+    pub fn wait_for_first(&self, timeout: time::Duration) -> bool {
+        let deadline = time::Instant::now().checked_add(timeout);
+        let current = thread::current();
+        self.lock_waiters().push(current.clone());
+        let processed = loop {
+            if self.processed() > 0 {
+                break true;
+            }
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(time::Instant::now()),
+                None => MAX_PARK_INTERVAL,
+            };
+            if remaining.is_zero() {
+                break false;
+            }
+            thread::park_timeout(remaining.min(MAX_PARK_INTERVAL));
+        };
+        self.lock_waiters().retain(|t| t.id() != current.id());
+        processed
+    }
+
+    fn lock_waiters(&self) -> std::sync::MutexGuard<'_, Vec<thread::Thread>> {
+        self.waiters.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn set_freewheeling(&self, freewheeling: bool) {
+        self.freewheeling.store(freewheeling, Ordering::Relaxed);
+    }
+}
+
+/// A change in the progress of the process cycles, as seen by a `Watchdog`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchdogEvent {
+    /// No process cycle started for longer than one period plus the tolerance. `missed_ms` is the
+    /// time since the next cycle was due, in milliseconds.
+    Stalled { missed_ms: u64 },
+    /// Process cycles started again after a stall.
+    Resumed,
+}
+
+/// Reports when the process callback of an `AsyncClient` stops being called.
+///
+/// A client stops making sound without any error when its process handler blocks, when the JACK
+/// server dies without notifying it, or when JACK stops calling it after it returned
+/// `Control::Quit`. The crate counts the process cycles of every active client, and a `Watchdog`
+/// checks on its own thread that the count advances about once per period of
+/// `buffer_size / sample_rate`.
+///
+/// A stall is reported once, with `WatchdogEvent::Stalled`, when no cycle started for longer than
+/// a period plus the tolerance, and `WatchdogEvent::Resumed` follows once cycles start again.
+/// While the server is in freewheel mode, the cycles don't follow the clock, so no stalls are
+/// reported. The thread exits when the watchdog or the receiver of the events is dropped.
+///
+/// ```no_run
+/// let (client, _status) =
+///     jack::Client::new("watched", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let active_client = client.activate_async((), ()).unwrap();
+/// let (_watchdog, events) =
+///     jack::Watchdog::start(&active_client, std::time::Duration::from_millis(100));
+/// for event in events {
+///     println!("{:?}", event);
+/// }
+/// ```
+pub struct Watchdog {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl Watchdog {
+    /// Start watching the process cycles of `client`. A stall is reported when no cycle started
+    /// for `tolerance` past the time the next one was due. The cycles are checked every quarter of
+    /// `tolerance`.
+    pub fn start<N, P>(
+        client: &AsyncClient<N, P>,
+        tolerance: time::Duration,
+    ) -> (Watchdog, mpsc::Receiver<WatchdogEvent>) {
+        let cycles = client.cycle_counter();
+        let c = client.as_client();
+        let monitor = Monitor::new(
+            tolerance,
+            c.sample_rate() as Frames,
+            c.buffer_size(),
+            time::Instant::now(),
+        );
+        let interval = (tolerance / 4).max(MIN_POLL_INTERVAL);
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let (events_tx, events_rx) = mpsc::channel();
+        let thread = thread::spawn(move || watch(cycles, monitor, interval, stop_rx, events_tx));
+        let watchdog = Watchdog {
+            stop: Some(stop_tx),
+            thread: Some(thread),
+        };
+        (watchdog, events_rx)
+    }
+
+    /// Returns `true` while the watchdog thread runs.
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Stop watching and wait for the thread to exit. Same as dropping the watchdog.
+    pub fn stop(self) {}
+}
+
+impl Drop for Watchdog {
+    fn drop(&mut self) {
+        // Wakes up the thread, even in the middle of its interval.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for Watchdog {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Watchdog")
+            .field("is_running", &self.is_running())
+            .finish()
+    }
+}
+
+fn watch(
+    cycles: Arc<CycleCounter>,
+    mut monitor: Monitor,
+    interval: time::Duration,
+    stop: mpsc::Receiver<()>,
+    events: mpsc::Sender<WatchdogEvent>,
+) {
+    while let Err(mpsc::RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        let event = monitor.check(
+            cycles.cycles.load(Ordering::Relaxed),
+            cycles.n_frames.load(Ordering::Relaxed),
+            cycles.freewheeling.load(Ordering::Relaxed),
+            time::Instant::now(),
+        );
+        if let Some(event) = event {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Decides from successive reads of a `CycleCounter` whether the cycles stalled.
+#[derive(Debug)]
+struct Monitor {
+    tolerance: time::Duration,
+    sample_rate: Frames,
+    n_frames: Frames,
+    cycles: u64,
+    last_advance: time::Instant,
+    stalled: bool,
+}
+
+impl Monitor {
+    fn new(
+        tolerance: time::Duration,
+        sample_rate: Frames,
+        n_frames: Frames,
+        now: time::Instant,
+    ) -> Monitor {
+        Monitor {
+            tolerance,
+            sample_rate,
+            n_frames,
+            cycles: 0,
+            last_advance: now,
+            stalled: false,
+        }
+    }
+
+    /// The time between two cycles.
+    fn period(&self) -> time::Duration {
+        match self.sample_rate {
+            0 => time::Duration::ZERO,
+            rate => time::Duration::from_secs_f64(f64::from(self.n_frames) / f64::from(rate)),
+        }
+    }
+
+    fn check(
+        &mut self,
+        cycles: u64,
+        n_frames: Frames,
+        freewheeling: bool,
+        now: time::Instant,
+    ) -> Option<WatchdogEvent> {
+        // `n_frames` is 0 until the first cycle, keep the buffer size until then.
+        if n_frames != 0 {
+            self.n_frames = n_frames;
+        }
+        if cycles != self.cycles {
+            self.cycles = cycles;
+            self.last_advance = now;
+            if self.stalled {
+                self.stalled = false;
+                return Some(WatchdogEvent::Resumed);
+            }
+            return None;
+        }
+        if freewheeling {
+            // The time spent in freewheel mode doesn't count towards a stall.
+            self.last_advance = now;
+            return None;
+        }
+        let due = self.last_advance + self.period();
+        let missed = now.saturating_duration_since(due);
+        if self.stalled || missed <= self.tolerance {
+            return None;
+        }
+        self.stalled = true;
+        Some(WatchdogEvent::Stalled {
+            missed_ms: missed.as_millis() as u64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    const RATE: Frames = 48000;
+
+    fn start_time() -> time::Instant {
+        lazy_static::lazy_static! {
+            static ref START: time::Instant = time::Instant::now();
+        }
+        *START
+    }
+
+    fn at(ms: u64) -> time::Instant {
+        start_time() + Duration::from_millis(ms)
+    }
+
+    // A period of 1024 frames at 48kHz is about 21ms.
+    fn monitor() -> Monitor {
+        Monitor::new(Duration::from_millis(50), RATE, 1024, at(0))
+    }
+
+    #[test]
+    fn watchdog_accepts_advancing_cycles() {
+        let mut m = monitor();
+        for i in 1..100 {
+            assert_eq!(m.check(i, 1024, false, at(i * 21)), None);
+        }
+    }
+
+    #[test]
+    fn watchdog_reports_stall_once_and_resume() {
+        let mut m = monitor();
+        assert_eq!(m.check(1, 1024, false, at(0)), None);
+        assert_eq!(m.check(1, 1024, false, at(60)), None);
+        assert_eq!(
+            m.check(1, 1024, false, at(100)),
+            Some(WatchdogEvent::Stalled { missed_ms: 78 })
+        );
+        assert_eq!(m.check(1, 1024, false, at(500)), None);
+        assert_eq!(
+            m.check(2, 1024, false, at(520)),
+            Some(WatchdogEvent::Resumed)
+        );
+        assert_eq!(m.check(3, 1024, false, at(541)), None);
+    }
+
+    #[test]
+    fn watchdog_reports_absent_process_callback() {
+        let mut m = monitor();
+        assert_eq!(m.check(0, 0, false, at(50)), None);
+        assert_eq!(
+            m.check(0, 0, false, at(80)),
+            Some(WatchdogEvent::Stalled { missed_ms: 58 })
+        );
+    }
+
+    #[test]
+    fn watchdog_follows_buffer_size() {
+        let mut m = monitor();
+        assert_eq!(m.check(1, 8192, false, at(0)), None);
+        // 8192 frames are about 170ms, so the next cycle isn't due yet.
+        assert_eq!(m.check(1, 8192, false, at(200)), None);
+        assert!(m.check(1, 8192, false, at(250)).is_some());
+    }
+
+    #[test]
+    fn watchdog_ignores_stalls_while_freewheeling() {
+        let mut m = monitor();
+        assert_eq!(m.check(1, 1024, true, at(0)), None);
+        assert_eq!(m.check(1, 1024, true, at(1000)), None);
+        // The stall is counted from the end of freewheel mode.
+        assert_eq!(m.check(1, 1024, false, at(1050)), None);
+        assert!(m.check(1, 1024, false, at(1100)).is_some());
+    }
+
+    #[test]
+    fn cycle_counter_wakes_waiters_on_first_processed_cycle() {
+        let counter = Arc::new(CycleCounter::default());
+        assert!(!counter.wait_for_first(Duration::from_millis(20)));
+        let waiter = {
+            let counter = counter.clone();
+            // Too large for a deadline, so this waits until the first cycle ends.
+            thread::spawn(move || counter.wait_for_first(Duration::MAX))
+        };
+        counter.record(1024);
+        assert_eq!(counter.processed(), 0);
+        counter.finish();
+        assert!(waiter.join().unwrap());
+        assert!(counter.lock_waiters().is_empty());
+        counter.finish();
+        assert_eq!(counter.processed(), 2);
+        assert!(counter.wait_for_first(Duration::ZERO));
+    }
+
+    #[test]
+    fn watchdog_detects_process_handler_that_sleeps_once() {
+        use crate::test_support::start_dummy_server;
+        use crate::{ClientOptions, ClosureProcessHandler, Control};
+
+        let server = match start_dummy_server() {
+            Some(server) => server,
+            None => return,
+        };
+        let (client, _) = server
+            .open_client("watched", ClientOptions::empty())
+            .unwrap();
+        let mut cycle = 0;
+        let handler = ClosureProcessHandler::new(move |_, _| {
+            cycle += 1;
+            if cycle == 10 {
+                thread::sleep(Duration::from_millis(300));
+            }
+            Control::Continue
+        });
+        let active_client = client.activate_async((), handler).unwrap();
+        let (watchdog, events) = Watchdog::start(&active_client, Duration::from_millis(50));
+        let timeout = Duration::from_secs(5);
+        assert!(matches!(
+            events.recv_timeout(timeout),
+            Ok(WatchdogEvent::Stalled { .. })
+        ));
+        assert_eq!(events.recv_timeout(timeout), Ok(WatchdogEvent::Resumed));
+        assert!(watchdog.is_running());
+        watchdog.stop();
+        assert!(events.recv().is_err());
+    }
+}
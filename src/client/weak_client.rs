@@ -0,0 +1,95 @@
+use jack_sys as j;
+use std::fmt;
+use std::sync::Weak;
+
+use crate::client::client_impl::uuid_by_name;
+use crate::{Error, Frames, Time, Uuid};
+
+/// A non-owning handle to a `Client` that can be stored and sent to other threads, for example to
+/// query the frame time from a worker thread. Created with `Client::downgrade`.
+///
+/// The handle does not keep the client open. Every query fails with
+/// `Err(Error::ClientIsNoLongerAlive)` once the client has been closed. A client that is being
+/// closed waits for the queries that are in progress to finish, so a query never uses a closed
+/// client.
+///
+/// Cloning the handle does not allocate.
+#[derive(Clone)]
+pub struct WeakClient {
+    client_ptr: *mut j::jack_client_t,
+    client_life: Weak<()>,
+}
+
+// The query functions of libjack may be called from any thread, and the client is kept open while
+// they run.
+unsafe impl Send for WeakClient {}
+unsafe impl Sync for WeakClient {}
+
+impl WeakClient {
+    pub(crate) fn new(client_ptr: *mut j::jack_client_t, client_life: Weak<()>) -> WeakClient {
+        WeakClient {
+            client_ptr,
+            client_life,
+        }
+    }
+
+    /// Returns `true` if the client is still open.
+    pub fn is_alive(&self) -> bool {
+        self.client_life.strong_count() > 0
+    }
+
+    /// The sample rate of the JACK system, see `Client::sample_rate`.
+    pub fn sample_rate(&self) -> Result<usize, Error> {
+        self.query(|c| unsafe { j::jack_get_sample_rate(c) } as usize)
+    }
+
+    /// The current maximum size that will be passed to the process callback, see
+    /// `Client::buffer_size`.
+    pub fn buffer_size(&self) -> Result<Frames, Error> {
+        self.query(|c| unsafe { j::jack_get_buffer_size(c) })
+    }
+
+    /// The current CPU load estimated by JACK, see `Client::cpu_load`.
+    pub fn cpu_load(&self) -> Result<f32, Error> {
+        self.query(|c| unsafe { j::jack_cpu_load(c) } as f32)
+    }
+
+    /// The estimated current time in frames, see `Client::frame_time`.
+    pub fn frame_time(&self) -> Result<Frames, Error> {
+        self.query(|c| unsafe { j::jack_frame_time(c) })
+    }
+
+    /// The estimated time in microseconds of the specified frame time, see
+    /// `Client::frames_to_time`.
+    pub fn frames_to_time(&self, n_frames: Frames) -> Result<Time, Error> {
+        self.query(|c| unsafe { j::jack_frames_to_time(c, n_frames) })
+    }
+
+    /// The estimated time in frames for the specified system time, see `Client::time_to_frames`.
+    pub fn time_to_frames(&self, t: Time) -> Result<Frames, Error> {
+        self.query(|c| unsafe { j::jack_time_to_frames(c, t) })
+    }
+
+    /// The uuid of the client named `client_name`, if it is open.
+    pub(crate) fn uuid_by_name(&self, client_name: &str) -> Result<Option<Uuid>, Error> {
+        self.query(|c| unsafe { uuid_by_name(c, client_name) })
+    }
+
+    // Keeps the client open while `f` runs.
+    pub(crate) fn query<R, F: FnOnce(*mut j::jack_client_t) -> R>(&self, f: F) -> Result<R, Error> {
+        let _life = self
+            .client_life
+            .upgrade()
+            .ok_or(Error::ClientIsNoLongerAlive)?;
+        Ok(f(self.client_ptr))
+    }
+}
+
+impl fmt::Debug for WeakClient {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("WeakClient")
+            .field("client_ptr", &self.client_ptr)
+            .field("is_alive", &self.is_alive())
+            .finish()
+    }
+}
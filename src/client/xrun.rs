@@ -0,0 +1,91 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// Statistics on the xruns that occurred while a client was active.
+///
+/// Obtained through `AsyncClient::xrun_stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct XrunStats {
+    /// The number of xruns that have occurred.
+    pub count: u64,
+    /// The delay, in microseconds, reported for the most recent xrun.
+    pub last_delay_usecs: f32,
+    /// The largest delay, in microseconds, reported for any xrun.
+    pub worst_delay_usecs: f32,
+}
+
+/// Lock-free xrun statistics, updated from the xrun callback.
+#[derive(Debug, Default)]
+pub struct XrunCounter {
+    count: AtomicU64,
+    // `f32` bits. Delays are never negative, so comparing the bits orders them like floats.
+    last_delay: AtomicU32,
+    worst_delay: AtomicU32,
+}
+
+impl XrunCounter {
+    /// Record an xrun. Does not allocate or block.
+    pub fn record(&self, delay_usecs: f32) {
+        let delay = delay_usecs.max(0.0).to_bits();
+        self.last_delay.store(delay, Ordering::Relaxed);
+        self.worst_delay.fetch_max(delay, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Clear the statistics. An xrun that is recorded concurrently may be partially cleared.
+    pub fn reset(&self) {
+        self.count.store(0, Ordering::Relaxed);
+        self.last_delay.store(0, Ordering::Relaxed);
+        self.worst_delay.store(0, Ordering::Relaxed);
+    }
+
+    pub fn stats(&self) -> XrunStats {
+        XrunStats {
+            count: self.count.load(Ordering::Relaxed),
+            last_delay_usecs: f32::from_bits(self.last_delay.load(Ordering::Relaxed)),
+            worst_delay_usecs: f32::from_bits(self.worst_delay.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn xrun_counter_starts_empty() {
+        assert_eq!(XrunCounter::default().stats(), XrunStats::default());
+    }
+
+    #[test]
+    fn xrun_counter_tracks_last_and_worst_delay() {
+        let counter = XrunCounter::default();
+        counter.record(10.0);
+        counter.record(250.5);
+        counter.record(3.0);
+        assert_eq!(
+            counter.stats(),
+            XrunStats {
+                count: 3,
+                last_delay_usecs: 3.0,
+                worst_delay_usecs: 250.5,
+            }
+        );
+    }
+
+    #[test]
+    fn xrun_counter_can_be_reset() {
+        let counter = XrunCounter::default();
+        counter.record(10.0);
+        counter.record(20.0);
+        assert_eq!(counter.count(), 2);
+        counter.reset();
+        assert_eq!(counter.stats(), XrunStats::default());
+        counter.record(5.0);
+        assert_eq!(counter.count(), 1);
+        assert_eq!(counter.stats().worst_delay_usecs, 5.0);
+    }
+}
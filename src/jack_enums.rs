@@ -1,41 +1,205 @@
-use crate::ClientStatus;
+use crate::{ClientStatus, Frames};
 
 /// An error that can occur in JACK.
+///
+/// The port errors keep why the call failed in their last field, see `PortErrorDetails`.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Error {
     CallbackDeregistrationError,
-    CallbackRegistrationError,
+    CallbackRegistrationError(String),
     ClientActivationError,
     ClientDeactivationError,
     ClientError(ClientStatus),
     FreewheelError,
     InvalidDeactivation,
+    /// A name or other string argument contains a NUL byte, which JACK can't represent.
+    InvalidName(String),
+    /// A string is not the textual form of a uuid, see `Uuid::parse`.
+    InvalidUuid(String),
+    /// A name is not one of the flags of a flags type, see `PortFlags::from_names`.
+    UnknownFlagName(String),
+    /// A name is not one of the parameters of a `ParamMailbox`, see `ParamMailbox::set`.
+    UnknownParameter(String),
+    /// Writing a recording to its output failed, see `CaptureWriter::stop`. Holds the message of
+    /// the `std::io::Error`.
+    CaptureIoError(String),
+    /// A `PatchProfile` could not be parsed. Holds the line and the reason.
+    InvalidProfile(String),
+    /// The segments of a `TempoMap` are invalid. Holds the reason.
+    InvalidTempoMap(String),
     NotEnoughSpace,
     PortAliasError,
     PortAlreadyConnected(String, String),
-    PortConnectionError(String, String),
-    PortDisconnectionError,
+    PortConnectionError(String, String, PortErrorDetails),
+    PortDisconnectionError(PortErrorDetails),
     PortMonitorError,
+    /// Monitoring could not be changed for the named ports, see `Client::set_monitoring_all`.
+    PortMonitorErrors(Vec<String>),
     PortNamingError,
-    PortRegistrationError(String),
+    PortRegistrationError(String, PortErrorDetails),
     SetBufferSizeError,
+    /// A buffer size that is not a power of two from the smallest to the largest allowed size, see
+    /// `Client::set_buffer_size`. Holds the requested, smallest and largest sizes.
+    InvalidBufferSize(Frames, Frames, Frames),
+    /// The buffer size callback for the held size was not called in time, see
+    /// `AsyncClient::set_buffer_size_sync`.
+    BufferSizeChangeTimeout(Frames),
+    /// The ports were not both registered in time to be connected, see
+    /// `AsyncClient::connect_when_available`. Holds the source and destination.
+    PortConnectionTimeout(String, String),
+    /// No process cycle ended in time, see `AsyncClient::wait_for_first_cycle`.
+    FirstCycleTimeout,
+    /// The graph callbacks were not registered on activation, see
+    /// `Client::set_graph_tracking_enabled`.
+    GraphNotTracked,
     TimeError,
     WeakFunctionNotFound,
     ClientIsNoLongerAlive,
+    /// The operation needs the client to be in the first state, but it is in the second. For
+    /// example, the ports of a client can only be connected while it is active.
+    InvalidClientState(ClientState, ClientState),
     RingbufferCreateFailed,
+    RingbufferMlockFailed,
+    MetadataValueTooLarge,
+    SessionReplyError,
+    NetSlaveError,
+    NetMasterError,
+    NetLinkError(NetLinkErrorKind),
+    /// A JACK server for tests could not be started, see `DummyServer`.
+    ServerStartError(String),
+    /// No impulse arrived during a latency measurement, see `measure_latency`.
+    LatencyTestTimeout,
+    SchedulingError,
+    ThreadCreationError,
+    ThreadJoinError,
     UnknownError,
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "JackError: {:?}", &self) // FIXME
+        match self {
+            Error::InvalidClientState(needed, actual) => write!(
+                f,
+                "JackError: the client must be {} for this operation, but it is {}",
+                needed, actual
+            ),
+            Error::InvalidBufferSize(requested, min, max) => write!(
+                f,
+                "JackError: the buffer size must be a power of two from {} to {}, not {}",
+                min, max, requested
+            ),
+            _ => write!(f, "JackError: {:?}", &self), // FIXME
+        }
     }
 }
 
 impl std::error::Error for Error {}
 
+/// Why registering, connecting or disconnecting ports failed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PortErrorDetails {
+    /// The cause of the failure, as far as it can be determined.
+    pub kind: PortErrorKind,
+    /// The code that the JACK function returned. `None` for functions that don't return a code,
+    /// like `jack_port_register`.
+    pub code: Option<i32>,
+    /// The messages that JACK reported while the call ran, if any.
+    pub message: Option<String>,
+}
+
+/// The cause of a port error, see `PortErrorDetails`.
+///
+/// JACK does not report the cause itself, so it is determined by the crate after the call failed.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PortErrorKind {
+    /// A port with the given name does not exist.
+    NoSuchPort,
+    /// The ports have different types, for example audio and MIDI.
+    TypeMismatch,
+    /// The source is not an output, or the destination is not an input.
+    WrongDirection,
+    /// A port belongs to this client, which is not active.
+    ClientNotActive,
+    /// The ports are not connected.
+    NotConnected,
+    /// The full name of the port is longer than `PORT_NAME_SIZE`.
+    NameTooLong,
+    /// The client already has a port with the name.
+    NameNotUnique,
+    /// The cause could not be determined.
+    Unknown,
+}
+
+/// A flag name that could not be parsed, returned by the `FromStr` implementations of `PortFlags`,
+/// `ClientOptions` and `ClientStatus`.
+///
+/// Names are matched ignoring case, and the `IS_` prefix of the port flags may be left out.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ParseFlagsError {
+    /// The name that is not a flag.
+    pub name: String,
+    /// The names of the flags of the type.
+    pub valid_names: &'static [&'static str],
+}
+
+impl std::fmt::Display for ParseFlagsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown flag {:?}, expected one of: {}",
+            self.name,
+            self.valid_names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ParseFlagsError {}
+
+/// Why exchanging a cycle with a NetJACK2 slave failed, see `Error::NetLinkError`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NetLinkErrorKind {
+    /// The slave did not answer in time, or the socket failed.
+    SocketError,
+    /// The packet that synchronizes the cycle was lost or corrupted.
+    SyncPacketLost,
+    /// A packet with audio or MIDI data was lost or corrupted.
+    DataPacketLost,
+    /// libjacknet returned an unknown error code.
+    Unknown(i32),
+}
+
+impl NetLinkErrorKind {
+    pub fn from_ffi(code: libc::c_int) -> NetLinkErrorKind {
+        match code {
+            jack_sys::NET_SOCKET_ERROR => NetLinkErrorKind::SocketError,
+            jack_sys::NET_SYNC_PACKET_ERROR => NetLinkErrorKind::SyncPacketLost,
+            jack_sys::NET_DATA_PACKET_ERROR => NetLinkErrorKind::DataPacketLost,
+            code => NetLinkErrorKind::Unknown(code),
+        }
+    }
+}
+
+/// Whether a client is processing, see `Client::is_active` and `Error::InvalidClientState`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ClientState {
+    /// The client was not activated yet, or was deactivated.
+    Inactive,
+    /// The client was activated, JACK calls its callbacks.
+    Active,
+}
+
+impl std::fmt::Display for ClientState {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ClientState::Inactive => write!(f, "inactive"),
+            ClientState::Active => write!(f, "active"),
+        }
+    }
+}
+
 /// Used by `NotificationHandler::latency()`.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LatencyType {
     Capture,
     Playback,
@@ -1,6 +1,14 @@
 use jack_sys as j;
 use std::ffi;
 
+use crate::{Error, ParseFlagsError};
+
+/// Convert `s` into a c-string for JACK. Returns `Err(Error::InvalidName)` if `s` contains a NUL
+/// byte.
+pub fn to_cstring(s: &str) -> Result<ffi::CString, Error> {
+    ffi::CString::new(s).map_err(|_| Error::InvalidName(s.to_string()))
+}
+
 /// Collects strings from an array of c-strings into a Rust vector of strings
 /// and frees the memory pointed to by `ptr`. The end of the array is marked by
 /// the value of the c-string being the null pointer. `ptr` may be `null`, in
@@ -25,3 +33,360 @@ pub unsafe fn collect_strs(ptr: *const *const libc::c_char) -> Vec<String> {
     j::jack_free(ptr as *mut ::libc::c_void);
     strs
 }
+
+/// Implement `names` and `from_names` for a bitflags type, to store flags by the names of their
+/// constants, for example `["IS_INPUT", "IS_TERMINAL"]`. Names stay readable and keep their meaning
+/// if JACK adds flags, unlike bits.
+///
+/// Also implements `Display` as the comma separated names, and `FromStr` for the more lenient
+/// syntax of command line arguments, see `parse_flag_name`.
+///
+/// With the `serde` feature, the flags are serialized as the list of their names for human readable
+/// formats like JSON or TOML, where unknown names fail to deserialize. Compact formats get the bits
+/// instead, which keep unknown bits.
+macro_rules! flag_names {
+    ($flags:ident { $($flag:ident),+ $(,)? }) => {
+        impl $flags {
+            const NAMES: &'static [&'static str] = &[$(stringify!($flag)),+];
+            const FLAGS: &'static [$flags] = &[$($flags::$flag),+];
+
+            /// The names of the set flags, in the order they are declared. Bits that don't belong
+            /// to a known flag are left out.
+            pub fn iter_names(&self) -> impl Iterator<Item = &'static str> {
+                let flags = *self;
+                $flags::NAMES
+                    .iter()
+                    .zip($flags::FLAGS)
+                    .filter(move |(_, flag)| flags.contains(**flag))
+                    .map(|(name, _)| *name)
+            }
+
+            /// The names of the set flags, see `iter_names`.
+            pub fn names(&self) -> Vec<&'static str> {
+                self.iter_names().collect()
+            }
+
+            /// The flags with the given names, see `names`. Returns `Err(Error::UnknownFlagName)`
+            /// for a name that is not a flag.
+            pub fn from_names<I, S>(names: I) -> Result<$flags, crate::Error>
+            where
+                I: IntoIterator<Item = S>,
+                S: AsRef<str>,
+            {
+                let mut flags = $flags::empty();
+                for name in names {
+                    flags |= match name.as_ref() {
+                        $(stringify!($flag) => $flags::$flag,)+
+                        name => return Err(crate::Error::UnknownFlagName(name.to_string())),
+                    };
+                }
+                Ok(flags)
+            }
+
+            /// Returns `true` if any of the flags in `other` is set. The same as `intersects`.
+            pub fn contains_any(&self, other: $flags) -> bool {
+                self.intersects(other)
+            }
+        }
+
+        /// The names of the set flags separated by commas, for example `IS_INPUT, IS_TERMINAL`.
+        /// Empty if no flag is set.
+        impl std::fmt::Display for $flags {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                for (i, name) in self.iter_names().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    f.write_str(name)?;
+                }
+                Ok(())
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $flags {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                if serializer.is_human_readable() {
+                    serializer.collect_seq(self.iter_names())
+                } else {
+                    serializer.serialize_u32(self.bits())
+                }
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $flags {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<$flags, D::Error> {
+                if deserializer.is_human_readable() {
+                    let names = <Vec<String> as serde::Deserialize>::deserialize(deserializer)?;
+                    $flags::from_names(names).map_err(|err| match err {
+                        crate::Error::UnknownFlagName(name) => {
+                            serde::de::Error::unknown_variant(&name, $flags::NAMES)
+                        }
+                        err => serde::de::Error::custom(err),
+                    })
+                } else {
+                    let bits = <u32 as serde::Deserialize>::deserialize(deserializer)?;
+                    // The flags are plain bits, unknown bits are kept like JACK may set them.
+                    Ok(unsafe { $flags::from_bits_unchecked(bits) })
+                }
+            }
+        }
+
+        /// Parse names separated by `,` or `|`, see `ParseFlagsError` for the accepted names. An
+        /// empty string is parsed as no flags.
+        impl std::str::FromStr for $flags {
+            type Err = crate::ParseFlagsError;
+
+            fn from_str(s: &str) -> Result<$flags, crate::ParseFlagsError> {
+                let mut flags = $flags::empty();
+                for name in s.split(|c| c == ',' || c == '|').map(str::trim) {
+                    if name.is_empty() {
+                        continue;
+                    }
+                    let index = crate::jack_utils::parse_flag_name($flags::NAMES, name)?;
+                    flags |= $flags::FLAGS[index];
+                }
+                Ok(flags)
+            }
+        }
+    };
+}
+
+pub(crate) use flag_names;
+
+/// The index of `name` in `names`, ignoring case and an optional `IS_` prefix, so that `physical`
+/// matches `IS_PHYSICAL`.
+pub fn parse_flag_name(
+    names: &'static [&'static str],
+    name: &str,
+) -> Result<usize, ParseFlagsError> {
+    names
+        .iter()
+        .position(|valid| {
+            valid.eq_ignore_ascii_case(name)
+                || valid
+                    .strip_prefix("IS_")
+                    .is_some_and(|short| short.eq_ignore_ascii_case(name))
+        })
+        .ok_or_else(|| ParseFlagsError {
+            name: name.to_string(),
+            valid_names: names,
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_cstring_rejects_nul_bytes() {
+        assert_eq!(
+            to_cstring("system:playback_1").unwrap().as_bytes(),
+            b"system:playback_1"
+        );
+        assert_eq!(
+            to_cstring("system:\0playback_1"),
+            Err(Error::InvalidName("system:\0playback_1".to_string()))
+        );
+    }
+
+    #[test]
+    fn flag_names_round_trip() {
+        use crate::{ClientOptions, ClientStatus, PortFlags};
+
+        let flags = PortFlags::IS_OUTPUT | PortFlags::IS_PHYSICAL;
+        assert_eq!(flags.names(), ["IS_OUTPUT", "IS_PHYSICAL"]);
+        assert_eq!(PortFlags::from_names(flags.names()), Ok(flags));
+        assert_eq!(
+            PortFlags::from_names(Vec::<String>::new()),
+            Ok(PortFlags::empty())
+        );
+
+        let options = ClientOptions::all();
+        assert_eq!(ClientOptions::from_names(options.names()), Ok(options));
+        let status = ClientStatus::FAILURE | ClientStatus::SERVER_FAILED;
+        assert_eq!(status.names(), ["FAILURE", "SERVER_FAILED"]);
+        assert_eq!(ClientStatus::from_names(status.names()), Ok(status));
+    }
+
+    #[test]
+    fn flag_names_reject_unknown_names() {
+        use crate::PortFlags;
+
+        assert_eq!(
+            PortFlags::from_names(["IS_INPUT", "is_output"]),
+            Err(Error::UnknownFlagName("is_output".to_string()))
+        );
+        assert_eq!(
+            PortFlags::from_bits_truncate(PortFlags::IS_INPUT.bits() | 1 << 30).names(),
+            ["IS_INPUT"]
+        );
+    }
+
+    #[test]
+    fn flags_display_as_comma_separated_names() {
+        use crate::{ClientOptions, ClientStatus, PortFlags};
+
+        assert_eq!(PortFlags::empty().to_string(), "");
+        assert_eq!(PortFlags::IS_TERMINAL.to_string(), "IS_TERMINAL");
+        assert_eq!(
+            (PortFlags::IS_INPUT | PortFlags::IS_PHYSICAL).to_string(),
+            "IS_INPUT, IS_PHYSICAL"
+        );
+        assert_eq!(
+            (ClientOptions::NO_START_SERVER | ClientOptions::USE_EXACT_NAME).to_string(),
+            "NO_START_SERVER, USE_EXACT_NAME"
+        );
+        assert_eq!(
+            (ClientStatus::FAILURE | ClientStatus::SERVER_FAILED).to_string(),
+            "FAILURE, SERVER_FAILED"
+        );
+    }
+
+    #[test]
+    fn flags_parse_from_str() {
+        use crate::{ClientOptions, ClientStatus, PortFlags};
+
+        let physical_input = PortFlags::IS_PHYSICAL | PortFlags::IS_INPUT;
+        for s in [
+            "physical,input",
+            "IS_PHYSICAL | IS_INPUT",
+            " is_physical , Input ",
+            "input,physical,input",
+        ] {
+            assert_eq!(s.parse::<PortFlags>(), Ok(physical_input), "{:?}", s);
+        }
+        assert_eq!("".parse::<PortFlags>(), Ok(PortFlags::empty()));
+        assert_eq!(" , ".parse::<PortFlags>(), Ok(PortFlags::empty()));
+        assert_eq!(
+            "no_start_server|use_exact_name".parse::<ClientOptions>(),
+            Ok(ClientOptions::NO_START_SERVER | ClientOptions::USE_EXACT_NAME)
+        );
+        assert_eq!(
+            "server_failed".parse::<ClientStatus>(),
+            Ok(ClientStatus::SERVER_FAILED)
+        );
+        for flags in [PortFlags::empty(), physical_input, PortFlags::all()] {
+            assert_eq!(flags.to_string().parse::<PortFlags>(), Ok(flags));
+        }
+        let status = ClientStatus::all();
+        assert_eq!(status.to_string().parse::<ClientStatus>(), Ok(status));
+    }
+
+    #[test]
+    fn flags_parse_error_lists_valid_names() {
+        use crate::{ClientOptions, PortFlags};
+
+        let err = "input,speaker".parse::<PortFlags>().unwrap_err();
+        assert_eq!(err.name, "speaker");
+        assert_eq!(
+            err.to_string(),
+            "unknown flag \"speaker\", expected one of: IS_INPUT, IS_OUTPUT, IS_PHYSICAL, \
+             CAN_MONITOR, IS_TERMINAL"
+        );
+        // The `IS_` prefix is optional for flags that have it, other prefixes are not.
+        assert!("monitor".parse::<PortFlags>().is_err());
+        assert!("start_server".parse::<ClientOptions>().is_err());
+    }
+
+    #[test]
+    fn flags_iterate_names_and_check_any() {
+        use crate::PortFlags;
+
+        let flags = PortFlags::IS_OUTPUT | PortFlags::IS_TERMINAL;
+        assert_eq!(
+            flags.iter_names().collect::<Vec<_>>(),
+            ["IS_OUTPUT", "IS_TERMINAL"]
+        );
+        assert_eq!(PortFlags::empty().iter_names().count(), 0);
+        assert!(flags.contains_any(PortFlags::IS_INPUT | PortFlags::IS_OUTPUT));
+        assert!(!flags.contains_any(PortFlags::IS_INPUT | PortFlags::IS_PHYSICAL));
+        assert!(!flags.contains_any(PortFlags::empty()));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn flags_serialize_as_names_when_readable() {
+        use crate::{ClientOptions, ClientStatus, PortFlags};
+
+        let flags = PortFlags::IS_INPUT | PortFlags::IS_PHYSICAL;
+        let json = serde_json::to_string(&flags).unwrap();
+        assert_eq!(json, r#"["IS_INPUT","IS_PHYSICAL"]"#);
+        assert_eq!(serde_json::from_str::<PortFlags>(&json).unwrap(), flags);
+        assert_eq!(
+            serde_json::from_str::<PortFlags>("[]").unwrap(),
+            PortFlags::empty()
+        );
+        let options = ClientOptions::NO_START_SERVER | ClientOptions::USE_EXACT_NAME;
+        let json = serde_json::to_string(&options).unwrap();
+        assert_eq!(
+            serde_json::from_str::<ClientOptions>(&json).unwrap(),
+            options
+        );
+        let status = ClientStatus::FAILURE | ClientStatus::SERVER_FAILED;
+        let json = serde_json::to_string(&status).unwrap();
+        assert_eq!(json, r#"["FAILURE","SERVER_FAILED"]"#);
+        assert_eq!(serde_json::from_str::<ClientStatus>(&json).unwrap(), status);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn flags_reject_unknown_names_when_readable() {
+        use crate::PortFlags;
+
+        let err = serde_json::from_str::<PortFlags>(r#"["IS_INPUT","IS_SPEAKER"]"#).unwrap_err();
+        assert!(
+            err.to_string().starts_with(
+                "unknown variant `IS_SPEAKER`, expected one of `IS_INPUT`, `IS_OUTPUT`"
+            ),
+            "{}",
+            err
+        );
+        assert!(serde_json::from_str::<PortFlags>("1").is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn flags_serialize_as_bits_when_compact() {
+        use crate::{ClientStatus, PortFlags};
+        use serde_test::{assert_tokens, Configure, Token};
+
+        let flags = PortFlags::IS_OUTPUT | PortFlags::IS_TERMINAL;
+        assert_tokens(&flags.compact(), &[Token::U32(flags.bits())]);
+        assert_tokens(
+            &flags.readable(),
+            &[
+                Token::Seq { len: None },
+                Token::Str("IS_OUTPUT"),
+                Token::Str("IS_TERMINAL"),
+                Token::SeqEnd,
+            ],
+        );
+        // Bits that are not known flags are kept.
+        let unknown =
+            unsafe { ClientStatus::from_bits_unchecked(ClientStatus::FAILURE.bits() | 1 << 30) };
+        assert_tokens(&unknown.compact(), &[Token::U32(unknown.bits())]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn enums_round_trip_through_serde() {
+        use crate::{LatencyType, TransportState};
+        use serde_test::{assert_tokens, Token};
+
+        let state = TransportState::Rolling;
+        assert_eq!(serde_json::to_string(&state).unwrap(), r#""Rolling""#);
+        assert_tokens(
+            &state,
+            &[Token::UnitVariant {
+                name: "TransportState",
+                variant: "Rolling",
+            }],
+        );
+        let latency = LatencyType::Playback;
+        let json = serde_json::to_string(&latency).unwrap();
+        assert_eq!(serde_json::from_str::<LatencyType>(&json).unwrap(), latency);
+        assert!(serde_json::from_str::<LatencyType>(r#""Monitor""#).is_err());
+    }
+}
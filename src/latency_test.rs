@@ -0,0 +1,284 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    channel, AudioIn, AudioOut, Client, Control, Error, Frames, LatencyType, Port, ProcessHandler,
+    ProcessScope, Sender,
+};
+
+/// The amplitude of the impulses.
+const IMPULSE: f32 = 0.5;
+
+/// Input peaks below this level are not counted as the impulse, about -40dB.
+const THRESHOLD: f32 = 0.01;
+
+/// The number of impulses that are measured.
+const MEASUREMENTS: usize = 5;
+
+/// The result of `measure_latency`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatencyReport {
+    /// The measured round trip delay, in frames.
+    pub delay: Frames,
+    /// The round trip latency reported by JACK, as a `(min, max)` range in frames. It is the
+    /// playback latency of the output port plus the capture latency of the input port.
+    pub expected: (Frames, Frames),
+    /// The number of impulses that the delay was measured with.
+    pub measurements: usize,
+    /// The sample rate of the JACK system.
+    pub sample_rate: usize,
+}
+
+impl LatencyReport {
+    /// The measured round trip delay, in milliseconds.
+    pub fn delay_ms(&self) -> f64 {
+        self.delay as f64 * 1000.0 / self.sample_rate as f64
+    }
+
+    /// The delay that JACK does not know about, in frames. This is the latency that should be
+    /// configured as extra input and output latency of the driver, it is negative if JACK
+    /// overestimates the latency.
+    pub fn extra(&self) -> i64 {
+        self.delay as i64 - self.expected.1 as i64
+    }
+
+    /// The delay that JACK does not know about, in milliseconds, see `LatencyReport::extra`.
+    pub fn extra_ms(&self) -> f64 {
+        self.extra() as f64 * 1000.0 / self.sample_rate as f64
+    }
+}
+
+/// Measure the round trip latency from `out_port_name` to `in_port_name`, like `jack_iodelay`.
+///
+/// The client registers two ports of its own, connects them to the given ports and activates. It
+/// then sends impulses to `out_port_name` twice a second, and measures when they arrive at
+/// `in_port_name`. Usually the ports are a hardware playback port and capture port, with a loopback
+/// cable between them. The measurement takes about three seconds. Delays of half a second or more
+/// can't be measured.
+///
+/// Returns the client, deactivated and without the ports, along with the report.
+/// `Err(Error::LatencyTestTimeout)` is returned if no impulse arrived within `timeout`, and the
+/// errors of `Client::connect_ports_by_name` if the ports can't be connected.
+///
+/// # Example
+/// ```no_run
+/// let (client, _status) =
+///     jack::Client::new("iodelay", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let timeout = std::time::Duration::from_secs(5);
+/// let (_client, report) =
+///     jack::measure_latency(client, "system:playback_1", "system:capture_1", timeout).unwrap();
+/// println!(
+///     "{:.2}ms round trip, {} frames not reported by JACK",
+///     report.delay_ms(),
+///     report.extra()
+/// );
+/// ```
+pub fn measure_latency(
+    client: Client,
+    out_port_name: &str,
+    in_port_name: &str,
+    timeout: Duration,
+) -> Result<(Client, LatencyReport), Error> {
+    let output = client.register_port("latency_out", AudioOut)?;
+    let input = match client.register_port("latency_in", AudioIn) {
+        Ok(input) => input,
+        Err(err) => {
+            let _ = client.unregister_port(output);
+            return Err(err);
+        }
+    };
+    let ports = (output.clone_unowned(), input.clone_unowned());
+    let res = run_measurement(client, output, input, out_port_name, in_port_name, timeout);
+    let (client, report) = res?;
+    let _ = client.unregister_port(ports.0);
+    let _ = client.unregister_port(ports.1);
+    Ok((client, report?))
+}
+
+fn run_measurement(
+    client: Client,
+    output: Port<AudioOut>,
+    input: Port<AudioIn>,
+    out_port_name: &str,
+    in_port_name: &str,
+    timeout: Duration,
+) -> Result<(Client, Result<LatencyReport, Error>), Error> {
+    let (sender, mut receiver) = channel::<Frames>(MEASUREMENTS * 2)?;
+    let sample_rate = client.sample_rate();
+    let output_name = output.name()?;
+    let input_name = input.name()?;
+    let handler = Measurement {
+        output,
+        input,
+        detector: ImpulseDetector::new(sample_rate as Frames / 2),
+        sender,
+    };
+    client.activate_scoped((), handler, |active| {
+        let client = active.as_client();
+        client.connect_ports_by_name(&output_name, out_port_name)?;
+        client.connect_ports_by_name(in_port_name, &input_name)?;
+
+        let start = Instant::now();
+        let mut delays = Vec::with_capacity(MEASUREMENTS);
+        while delays.len() < MEASUREMENTS && start.elapsed() < timeout {
+            match receiver.try_recv() {
+                Some(delay) => delays.push(delay),
+                None => std::thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        if delays.is_empty() {
+            return Err(Error::LatencyTestTimeout);
+        }
+        delays.sort_unstable();
+
+        // The latency ranges are updated by JACK once the ports are connected.
+        let range = |name: &str, mode: LatencyType| {
+            client
+                .port_by_name(name)
+                .map_or((0, 0), |port| port.get_latency_range(mode))
+        };
+        let playback = range(out_port_name, LatencyType::Playback);
+        let capture = range(in_port_name, LatencyType::Capture);
+        Ok(LatencyReport {
+            delay: delays[delays.len() / 2],
+            expected: (playback.0 + capture.0, playback.1 + capture.1),
+            measurements: delays.len(),
+            sample_rate,
+        })
+    })
+}
+
+struct Measurement {
+    output: Port<AudioOut>,
+    input: Port<AudioIn>,
+    detector: ImpulseDetector,
+    sender: Sender<Frames>,
+}
+
+impl ProcessHandler for Measurement {
+    fn process(&mut self, _: &Client, ps: &ProcessScope) -> Control {
+        let sender = &mut self.sender;
+        self.detector.process(
+            self.input.as_slice(ps),
+            self.output.as_mut_slice(ps),
+            |delay| {
+                // The measurement only needs a few of the delays.
+                let _ = sender.try_send(delay);
+            },
+        );
+        Control::Continue
+    }
+}
+
+/// Sends an impulse at the start of every period, and finds the loudest input frame of the period.
+struct ImpulseDetector {
+    period: Frames,
+    position: Frames,
+    peak: f32,
+    peak_position: Frames,
+}
+
+impl ImpulseDetector {
+    fn new(period: Frames) -> ImpulseDetector {
+        ImpulseDetector {
+            period,
+            position: 0,
+            peak: 0.0,
+            peak_position: 0,
+        }
+    }
+
+    /// Process a cycle. `on_delay` is called with the position of the peak at the end of each
+    /// period in which the input was loud enough.
+    fn process<F: FnMut(Frames)>(&mut self, input: &[f32], output: &mut [f32], mut on_delay: F) {
+        for (i, o) in input.iter().zip(output.iter_mut()) {
+            *o = if self.position == 0 { IMPULSE } else { 0.0 };
+            if i.abs() > self.peak {
+                self.peak = i.abs();
+                self.peak_position = self.position;
+            }
+            self.position += 1;
+            if self.position == self.period {
+                if self.peak >= THRESHOLD {
+                    on_delay(self.peak_position);
+                }
+                self.position = 0;
+                self.peak = 0.0;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ClientOptions, ClosureProcessHandler};
+
+    // Runs the detector with its output fed back to its input, `delay` frames later.
+    fn detect(delay: usize, gain: f32, n_frames: usize, cycles: usize) -> Vec<Frames> {
+        let mut detector = ImpulseDetector::new(1000);
+        let mut sent = Vec::new();
+        let mut output = vec![0.0; n_frames];
+        let mut delays = Vec::new();
+        for cycle in 0..cycles {
+            let start = cycle * n_frames;
+            // Like in JACK, the delay is at least a cycle so only earlier cycles arrive.
+            let input: Vec<f32> = (start..start + n_frames)
+                .map(|t| t.checked_sub(delay).map_or(0.0, |t| sent[t] * gain))
+                .collect();
+            detector.process(&input, &mut output, |d| delays.push(d));
+            sent.extend_from_slice(&output);
+        }
+        delays
+    }
+
+    #[test]
+    fn latency_detector_finds_delay() {
+        assert_eq!(detect(300, 1.0, 64, 50), [300, 300, 300]);
+        assert_eq!(detect(150, 0.1, 128, 24), [150, 150, 150]);
+    }
+
+    #[test]
+    fn latency_detector_ignores_silence() {
+        assert_eq!(detect(300, 0.01, 64, 50), Vec::<Frames>::new());
+    }
+
+    #[test]
+    fn latency_report_computes_extra_latency() {
+        let report = LatencyReport {
+            delay: 480,
+            expected: (384, 432),
+            measurements: 5,
+            sample_rate: 48000,
+        };
+        assert_eq!(report.delay_ms(), 10.0);
+        assert_eq!(report.extra(), 48);
+        assert_eq!(report.extra_ms(), 1.0);
+    }
+
+    #[test]
+    fn latency_measures_loopback_through_client() {
+        let (loopback, _) =
+            Client::new("latency_loopback", ClientOptions::NO_START_SERVER).unwrap();
+        let input = loopback.register_port("in", AudioIn::default()).unwrap();
+        let mut output = loopback.register_port("out", AudioOut::default()).unwrap();
+        let process = ClosureProcessHandler::new(move |_: &Client, ps: &ProcessScope| {
+            output.as_mut_slice(ps).copy_from_slice(input.as_slice(ps));
+            Control::Continue
+        });
+        let _loopback = loopback.activate_async((), process).unwrap();
+
+        let (client, _) = Client::new("latency_measure", ClientOptions::NO_START_SERVER).unwrap();
+        let buffer_size = client.buffer_size();
+        let (client, report) = measure_latency(
+            client,
+            "latency_loopback:in",
+            "latency_loopback:out",
+            Duration::from_secs(10),
+        )
+        .unwrap();
+        assert!(report.delay <= 2 * buffer_size, "{:?}", report);
+        assert_eq!(report.expected, (0, 0));
+        assert_eq!(report.measurements, MEASUREMENTS);
+        assert!(client.port_by_name("latency_measure:latency_out").is_none());
+    }
+}
@@ -33,39 +33,123 @@
 //! callback. For example, `Port<AudioIn>::as_mut_slice` returns a audio buffer that can be written
 //! to.
 
+pub use crate::audio_stream::AudioStream;
+pub use crate::capture::{CaptureInput, CaptureStats, CaptureWriter};
+pub use crate::channel::{channel, Receiver, Sender};
+#[cfg(feature = "async")]
+pub use crate::client::NotificationStream;
+#[cfg(feature = "session")]
 pub use crate::client::{
-    AsyncClient, Client, ClientOptions, ClientStatus, ClosureProcessHandler, CycleTimes,
-    NotificationHandler, ProcessHandler, ProcessScope, CLIENT_NAME_SIZE,
+    session_available, ClosureSessionHandler, SessionCommand, SessionEvent, SessionEventType,
+    SessionFlags, SessionHandler, SessionSupport, SESSION_DIR_PLACEHOLDER, SESSION_UUID_ARG,
 };
-pub use crate::jack_enums::{Control, Error, LatencyType};
+pub use crate::client::{
+    wait_for_server, AsyncClient, BufferPool, BufferSizeScope, CallbackSelection, Client,
+    ClientOptions, ClientStatus, ClosureProcessHandler, ConnectionStatus, CycleLocal, CycleTimes,
+    LatencyScope, ManifestPorts, NameRegistry, Notification, NotificationHandler,
+    NotificationReceiver, Notifications, PendingConnection, PooledProcessHandler, PortEvent,
+    PortEventReceiver, PortManifest, PortRegistrationInfo, ProcessHandler, ProcessScope,
+    ProcessThreadContext, ResilientClient, ResilientConfig, ResilientEvent,
+    SharedNotificationHandler, SharedProcessHandler, ShutdownReceiver, TimebaseHandler,
+    TimingStats, Watchdog, WatchdogEvent, WeakClient, XrunStats, CLIENT_NAME_SIZE, MAX_BUFFER_SIZE,
+    MIN_BUFFER_SIZE, SHUTDOWN_REASON_SIZE, TIMING_HISTOGRAM_BINS,
+};
+pub use crate::jack_enums::{
+    ClientState, Control, Error, LatencyType, NetLinkErrorKind, ParseFlagsError, PortErrorDetails,
+    PortErrorKind,
+};
+pub use crate::latency_test::{measure_latency, LatencyReport};
 pub use crate::logging::{
     error_callback, info_callback, reset_error_callback, reset_info_callback, set_error_callback,
-    set_info_callback,
+    set_info_callback, set_quiet, LogCallback,
+};
+pub use crate::meter::{meter, meter_bank, Meter, MeterBank, MeterBankReader, MeterReader};
+#[cfg(feature = "net")]
+pub use crate::net::{
+    net_available, ActiveNetSlave, ClosureNetSlaveHandler, NetEncoder, NetLinkStats, NetMaster,
+    NetMasterConfig, NetMasterInfo, NetProcessScope, NetSlave, NetSlaveConfig, NetSlaveHandler,
+    NET_DEFAULT_MULTICAST_IP, NET_DEFAULT_PORT,
+};
+#[cfg(feature = "assert-no-alloc")]
+pub use crate::no_alloc::{assert_no_alloc, permit_alloc, NoAllocChecker};
+pub use crate::param::{ParamCell, ParamMailbox};
+pub use crate::patchbay::{
+    PatchAction, PatchOutcome, PatchProfile, PatchReport, PatchStep, PortPattern, SkipReason,
 };
 pub use crate::port::{
-    AudioIn, AudioOut, MidiIn, MidiIter, MidiOut, MidiWriter, Port, PortFlags, PortSpec, RawMidi,
-    Unowned, PORT_NAME_SIZE, PORT_TYPE_SIZE,
+    zip_frames, AudioIn, AudioOut, ConnectionGuard, ConnectionsBuf, MidiIn, MidiIter, MidiOut,
+    MidiWriter, MixBuffer, OwnedPort, Port, PortFlags, PortNames, PortNamesIter, PortSpec, RawMidi,
+    Unowned, ZipFrames, PORT_NAME_SIZE, PORT_TYPE_SIZE,
+};
+pub use crate::primitive_types::{Frames, PortConnections, PortId, Time};
+pub use crate::ringbuffer::{
+    ReadVector, RingBuffer, RingBufferReader, RingBufferWriter, WriteVector,
+};
+#[cfg(feature = "log")]
+pub use crate::rt_log::{RtLogReader, RtLogThread, RtLogger, RT_LOG_MESSAGE_SIZE};
+pub use crate::server_quirks::{server_quirks, ServerFlavor, ServerQuirks};
+pub use crate::tempo_map::{TempoMap, TempoSegment, TimebaseMaster, TEMPO_MAP_TICKS_PER_BEAT};
+#[cfg(feature = "dummy-server")]
+pub use crate::test_support::DummyServer;
+pub use crate::test_support::{
+    ClientQuery, DriverConfig, MockClient, MockProcessScope, OfflineDriver, OfflineHandler,
+};
+#[cfg(not(target_os = "windows"))]
+pub use crate::thread::{
+    acquire_current_thread_real_time_scheduling, acquire_real_time_scheduling,
+    drop_current_thread_real_time_scheduling, drop_real_time_scheduling, reset_thread_creator,
+    set_thread_creator, RtThreadHandle, ThreadCreator,
+};
+pub use crate::thread::{
+    denormal_flushing_enabled, enable_denormal_flushing, DenormalFlushGuard, ThreadInitConfig,
 };
-pub use crate::primitive_types::{Frames, PortId, Time};
-pub use crate::ringbuffer::{RingBuffer, RingBufferReader, RingBufferWriter};
 pub use crate::transport::{
     Transport, TransportBBT, TransportBBTValidationError, TransportPosition, TransportState,
     TransportStatePosition,
 };
+pub use crate::transport_observer::{TransportEvent, TransportObserver};
+pub use crate::uuid::Uuid;
 
 //only expose metadata if enabled
 #[cfg(feature = "metadata")]
 pub use crate::properties::*;
 
+mod audio_stream;
+
 /// Create and manage client connections to a JACK server.
 mod client;
 
 /// Create and manage JACK ring buffers.
 mod ringbuffer;
 
+/// Send `Copy` values between threads over a ring buffer.
+mod channel;
+
+/// Record audio from the process callback to disk.
+mod capture;
+
+#[cfg(feature = "log")]
+mod rt_log;
+
+/// Check that the process callback does not allocate.
+#[cfg(feature = "assert-no-alloc")]
+mod no_alloc;
+
+/// Measure the round trip latency of audio hardware.
+mod latency_test;
+
 /// Control error and info logging from JACK.
 mod logging;
 
+/// Peak and RMS meters for the process callback.
+mod meter;
+
+/// Share parameters with the process callback without locking.
+mod param;
+
+/// Routing presets that are applied to the connections of a JACK graph.
+mod patchbay;
+
 /// Enum types in jack.
 mod jack_enums;
 
@@ -77,12 +161,40 @@ mod port;
 /// Platform independent types.
 mod primitive_types;
 
+/// Differences between the implementations of JACK.
+mod server_quirks;
+
+/// Configuration of the threads that run JACK callbacks.
+mod thread;
+
+/// Bar, beat and tick positions from a tempo map, for timebase masters.
+mod tempo_map;
+
 /// Transport.
 mod transport;
 
+/// Events for changes of the transport, for clients that don't take part in it.
+mod transport_observer;
+
+/// Test process handlers without a JACK server.
+mod test_support;
+
+/// Typed frame counts and times.
+pub mod units;
+
+/// Identifiers of clients and ports.
+mod uuid;
+
+/// Exchange audio and MIDI with JACK servers on other machines over NetJACK2.
+#[cfg(feature = "net")]
+mod net;
+
 /// Properties
 mod properties;
 
+#[cfg(test)]
+mod test_alloc;
+
 /// Return JACK's current system time in microseconds, using the JACK clock
 /// source.
 pub fn get_time() -> primitive_types::Time {
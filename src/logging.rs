@@ -1,77 +1,225 @@
 use jack_sys as j;
 use lazy_static::lazy_static;
+use std::cell::RefCell;
 use std::ffi;
+#[cfg(not(feature = "log"))]
 use std::io::{stderr, Write};
-use std::sync::{Mutex, Once};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, MutexGuard, Once, PoisonError};
+
+/// A callback for the messages that JACK logs, see `set_error_callback` and `set_info_callback`.
+pub type LogCallback = Arc<dyn Fn(&str) + Send + Sync>;
+
+type RawLogFn = unsafe extern "C" fn(msg: *const libc::c_char);
 
 lazy_static! {
-    static ref INFO_FN: Mutex<Option<fn(&str)>> = Mutex::new(None);
-    static ref ERROR_FN: Mutex<Option<fn(&str)>> = Mutex::new(None);
+    static ref INFO_FN: Mutex<Option<LogCallback>> = Mutex::new(None);
+    static ref ERROR_FN: Mutex<Option<LogCallback>> = Mutex::new(None);
+    // The functions that were set in JACK before rust-jack replaced them.
+    static ref PREVIOUS_INFO_FN: Mutex<Option<RawLogFn>> = Mutex::new(None);
+    static ref PREVIOUS_ERROR_FN: Mutex<Option<RawLogFn>> = Mutex::new(None);
 }
 
-unsafe extern "C" fn error_wrapper(msg: *const libc::c_char) {
-    let msg = ffi::CStr::from_ptr(msg)
-        .to_str()
-        .unwrap_or("rust failed to interpret error message");
-    let f = ERROR_FN.lock().unwrap();
-    match *f {
-        Some(f) => f(msg),
-        None => writeln!(&mut stderr(), "{}", msg).unwrap(),
+thread_local! {
+    // The error messages that JACK reported on this thread during `capture_errors`.
+    static CAPTURED_ERRORS: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// Pass `msg` to `callback`, or to the function JACK used before if no callback is set.
+unsafe fn forward(
+    msg: *const libc::c_char,
+    text: &str,
+    callback: &Mutex<Option<LogCallback>>,
+    previous: &Mutex<Option<RawLogFn>>,
+    default: fn(&str),
+) {
+    // The lock is not held while calling the callback, so that it may replace itself.
+    let f = lock(callback).clone();
+    match f {
+        Some(f) => f(text),
+        None => match *lock(previous) {
+            Some(previous) => previous(msg),
+            None => default(text),
+        },
     }
 }
 
+// Panics must not unwind into libjack, so a callback that panics is ignored.
+unsafe extern "C" fn error_wrapper(msg: *const libc::c_char) {
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let text = ffi::CStr::from_ptr(msg).to_string_lossy();
+        let _ = CAPTURED_ERRORS.try_with(|captured| {
+            if let Some(captured) = captured.borrow_mut().as_mut() {
+                captured.push(text.to_string());
+            }
+        });
+        forward(msg, &text, &ERROR_FN, &PREVIOUS_ERROR_FN, default_error);
+    }));
+}
+
 unsafe extern "C" fn info_wrapper(msg: *const libc::c_char) {
-    let msg = ffi::CStr::from_ptr(msg)
-        .to_str()
-        .unwrap_or("rust failed to interpret info message");
-    let f = INFO_FN.lock().unwrap();
-    match *f {
-        Some(f) => f(msg),
-        None => println!("{}", msg),
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        let text = ffi::CStr::from_ptr(msg).to_string_lossy();
+        forward(msg, &text, &INFO_FN, &PREVIOUS_INFO_FN, default_info);
+    }));
+}
+
+// The callbacks are only swapped under the locks, so a lock poisoned by a panic is still usable.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(feature = "log")]
+fn default_error(text: &str) {
+    log::error!(target: "jack", "{}", text);
+}
+
+#[cfg(not(feature = "log"))]
+fn default_error(text: &str) {
+    let _ = writeln!(&mut stderr(), "{}", text);
+}
+
+#[cfg(feature = "log")]
+fn default_info(text: &str) {
+    log::info!(target: "jack", "{}", text);
+}
+
+#[cfg(not(feature = "log"))]
+fn default_info(text: &str) {
+    println!("{}", text)
+}
+
+/// Log an event of the crate with the `log` crate and as a `tracing` event, with the target `jack`.
+/// Each is only done with its feature, so nothing is done without the `log` and `tracing`
+/// features.
+///
+/// This must not be used on a real-time thread, see `RtLogger` instead.
+macro_rules! log_event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::$level!(target: "jack", $($arg)+);
+        #[cfg(feature = "tracing")]
+        tracing::$level!(target: "jack", $($arg)+);
+        #[cfg(not(any(feature = "log", feature = "tracing")))]
+        let _ = || format!($($arg)+);
+    }};
+}
+pub(crate) use log_event;
+
+/// Route the JACK messages to the `log` crate, unless a callback was set. Called before opening a
+/// client so that the reason a client can't be opened is logged as well.
+pub(crate) fn init() {
+    #[cfg(feature = "log")]
+    {
+        set_info_wrapper();
+        set_error_wrapper();
     }
 }
 
 static IS_INFO_CALLBACK_SET: Once = Once::new();
 /// Set the global JACK info callback. It is recommended to specify a callback that uses the [log
-/// crate](https://cratse.io/crates/log).
-pub fn set_info_callback(info: fn(&str)) {
-    *INFO_FN.lock().unwrap() = Some(info);
-    IS_INFO_CALLBACK_SET.call_once(|| unsafe { j::jack_set_info_function(Some(info_wrapper)) })
+/// crate](https://crates.io/crates/log).
+///
+/// The callback is shared by the whole process: it receives the messages of every client and
+/// replaces the callback that was set before. JACK may call it from any thread, including the
+/// threads of the clients, so it should return quickly. A panic in the callback is caught and the
+/// message is dropped, since it must not unwind into JACK.
+pub fn set_info_callback<F: Fn(&str) + Send + Sync + 'static>(info: F) {
+    *lock(&INFO_FN) = Some(Arc::new(info));
+    set_info_wrapper();
+}
+
+/// Route the JACK info messages through rust-jack, see `set_error_wrapper`.
+fn set_info_wrapper() {
+    IS_INFO_CALLBACK_SET.call_once(|| unsafe {
+        *lock(&PREVIOUS_INFO_FN) = previous_fn(j::jack_info_callback);
+        j::jack_set_info_function(Some(info_wrapper))
+    })
 }
 
-/// Resets the JACK info callback to use stdio.
+/// The function to fall back to when no callback is set. With the `log` feature the messages go to
+/// the `log` crate instead of to the function that JACK used before.
+#[cfg(feature = "log")]
+fn previous_fn(_: Option<RawLogFn>) -> Option<RawLogFn> {
+    None
+}
+
+#[cfg(not(feature = "log"))]
+fn previous_fn(previous: Option<RawLogFn>) -> Option<RawLogFn> {
+    previous
+}
 
 /// Get the info callback that was set using `set_info_callback`. This corresponds to the one set
 /// using rust-jack, not JACK itself. `None` is returned if rust-jack hasn't set a callback or has
 /// reset it to use stdout.
-pub fn info_callback() -> Option<fn(&str)> {
-    *INFO_FN.lock().unwrap()
+pub fn info_callback() -> Option<LogCallback> {
+    lock(&INFO_FN).clone()
 }
 
-/// Restores the JACK info callback to the JACK default, which is to write to
-/// stdout.
+/// Restores the JACK info callback to the one that was used before `set_info_callback`, by default
+/// JACK writes to stdout. With the `log` feature, the messages are logged with the `log` crate
+/// instead, at the info level and with the target `jack`.
 pub fn reset_info_callback() {
-    *INFO_FN.lock().unwrap() = None;
+    *lock(&INFO_FN) = None;
 }
 
 static IS_ERROR_CALLBACK_SET: Once = Once::new();
-/// Set the global JACK info callback. It is recommended to specify a callback that uses the [log
-/// crate](https://cratse.io/crates/log).
-pub fn set_error_callback(error: fn(&str)) {
-    *ERROR_FN.lock().unwrap() = Some(error);
-    IS_ERROR_CALLBACK_SET.call_once(|| unsafe { j::jack_set_error_function(Some(error_wrapper)) })
+/// Set the global JACK error callback. It is recommended to specify a callback that uses the [log
+/// crate](https://crates.io/crates/log).
+///
+/// Like with `set_info_callback`, the callback is shared by all the clients of the process. The
+/// messages are still attached to the errors of the calls that failed, see
+/// `PortErrorDetails::message`. A panic in the callback is caught and the message is dropped.
+pub fn set_error_callback<F: Fn(&str) + Send + Sync + 'static>(error: F) {
+    *lock(&ERROR_FN) = Some(Arc::new(error));
+    set_error_wrapper();
+}
+
+/// Route the JACK error messages through rust-jack. Messages keep going to the error function that
+/// was set before, unless a callback is set with `set_error_callback`.
+fn set_error_wrapper() {
+    IS_ERROR_CALLBACK_SET.call_once(|| unsafe {
+        *lock(&PREVIOUS_ERROR_FN) = previous_fn(j::jack_error_callback);
+        j::jack_set_error_function(Some(error_wrapper))
+    })
+}
+
+/// Call `f` and return its result with the error messages that JACK reported while it ran, joined
+/// by newlines, or `None` if there were none.
+///
+/// JACK explains why a call failed, for example that a port name is too long, only through its
+/// error function. Only messages reported on the calling thread are captured.
+pub(crate) fn capture_errors<T, F: FnOnce() -> T>(f: F) -> (T, Option<String>) {
+    set_error_wrapper();
+    let outer = CAPTURED_ERRORS.with(|captured| captured.replace(Some(Vec::new())));
+    let res = f();
+    let captured = CAPTURED_ERRORS.with(|captured| captured.replace(outer));
+    let details = captured
+        .filter(|messages| !messages.is_empty())
+        .map(|messages| messages.join("\n"));
+    (res, details)
 }
 
 /// Get the error callback that was set using `set_error_callback`. This corresponds to the one set
 /// using rust-jack, not JACK itself. `None` is returned if rust-jack hasn't set a callback or has
 /// reset it to use stderr.
-pub fn error_callback() -> Option<fn(&str)> {
-    *ERROR_FN.lock().unwrap()
+pub fn error_callback() -> Option<LogCallback> {
+    lock(&ERROR_FN).clone()
 }
-/// Restores the JACK info callback to the JACK default, which is to write to
-/// stderr.
+
+/// Restores the JACK error callback to the one that was used before `set_error_callback`, by
+/// default JACK writes to stderr. With the `log` feature, the messages are logged with the `log`
+/// crate instead, at the error level and with the target `jack`.
 pub fn reset_error_callback() {
-    *ERROR_FN.lock().unwrap() = None;
+    *lock(&ERROR_FN) = None;
+}
+
+/// Discard the error and info messages of JACK, for example to keep them from being written over
+/// a terminal user interface. Use `reset_error_callback` and `reset_info_callback` to print them
+/// again.
+pub fn set_quiet() {
+    set_info_callback(|_: &str| {});
+    set_error_callback(|_: &str| {});
 }
 
 #[cfg(test)]
@@ -91,6 +239,18 @@ mod test {
         assert!(info_callback().is_some());
         info_callback().unwrap()("Using info callback!.");
 
+        // set a closure
+        let (tx, rx) = std::sync::mpsc::channel();
+        let tx = Mutex::new(tx);
+        set_info_callback(move |msg: &str| tx.lock().unwrap().send(msg.to_string()).unwrap());
+        info_callback().unwrap()("Using closure callback!.");
+        assert_eq!(rx.try_recv(), Ok("Using closure callback!.".to_string()));
+
+        // a closure that panics once the receiver is gone does not unwind into JACK
+        drop(rx);
+        let msg = ffi::CString::new("receiver is gone").unwrap();
+        unsafe { info_wrapper(msg.as_ptr()) };
+
         // reset
         reset_info_callback();
         assert!(info_callback().is_none());
@@ -107,8 +267,94 @@ mod test {
         assert!(error_callback().is_some());
         error_callback().unwrap()("Using error callback!.");
 
+        // a panicking callback does not unwind into JACK, nor does it poison the callback
+        set_error_callback(|_: &str| panic!("induced panic in error callback"));
+        report_error("panicking callback");
+        let _ = std::thread::spawn(|| {
+            let _callback = ERROR_FN.lock();
+            panic!("induced panic while holding the callback");
+        })
+        .join();
+        assert!(ERROR_FN.is_poisoned());
+        report_error("poisoned callback");
+        assert!(error_callback().is_some());
+
         // reset
         reset_error_callback();
         assert!(error_callback().is_none());
     }
+
+    #[cfg(feature = "log")]
+    struct CapturingLogger(Mutex<Vec<(log::Level, String)>>);
+
+    #[cfg(feature = "log")]
+    impl log::Log for CapturingLogger {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.target() == "jack"
+        }
+
+        fn log(&self, record: &log::Record) {
+            if self.enabled(record.metadata()) {
+                let message = record.args().to_string();
+                self.0.lock().unwrap().push((record.level(), message));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[cfg(feature = "log")]
+    #[test]
+    fn logging_forwards_jack_messages_to_log() {
+        static LOGGER: CapturingLogger = CapturingLogger(Mutex::new(Vec::new()));
+        log::set_logger(&LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Info);
+        init();
+        let options = crate::ClientOptions::NO_START_SERVER | crate::ClientOptions::SERVER_NAME;
+        let client_name = ffi::CString::new("logging_fjmtl").unwrap();
+        let server_name = ffi::CString::new("rust_jack_no_such_server").unwrap();
+        let mut status = 0;
+        let client = unsafe {
+            j::jack_client_open(
+                client_name.as_ptr(),
+                options.bits(),
+                &mut status,
+                server_name.as_ptr(),
+            )
+        };
+        assert!(client.is_null());
+        let records = LOGGER.0.lock().unwrap();
+        assert!(records.iter().any(|(level, _)| *level == log::Level::Error));
+    }
+
+    fn report_error(msg: &str) {
+        let msg = ffi::CString::new(msg).unwrap();
+        unsafe { error_wrapper(msg.as_ptr()) };
+    }
+
+    #[test]
+    fn logging_captures_errors_during_call() {
+        report_error("not captured");
+        let (res, details) = capture_errors(|| {
+            report_error("port name too long");
+            report_error("cannot register port");
+            42
+        });
+        assert_eq!(res, 42);
+        assert_eq!(
+            details.as_deref(),
+            Some("port name too long\ncannot register port")
+        );
+        assert_eq!(capture_errors(|| ()).1, None);
+    }
+
+    #[test]
+    fn logging_captures_errors_only_on_calling_thread() {
+        let (_, details) = capture_errors(|| {
+            std::thread::spawn(|| report_error("other thread"))
+                .join()
+                .unwrap();
+        });
+        assert_eq!(details, None);
+    }
 }
@@ -0,0 +1,352 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::Frames;
+
+/// Create a peak and RMS meter. The `Meter` is fed with samples from the process callback, and the
+/// `MeterReader` reads the levels from another thread, such as the GUI thread.
+///
+/// The RMS level is the average over `window` frames, for example `sample_rate * 3 / 10` for the
+/// common 300ms. The peak level is the loudest sample since it was last read, with the hold and
+/// decay of the reader applied.
+///
+/// # Example
+/// ```
+/// let (mut meter, mut reader) = jack::meter(4);
+/// // In the process callback.
+/// meter.feed(&[1.0, -1.0, 0.0, 0.0]);
+/// // In the GUI thread.
+/// assert_eq!(reader.peak_db(), 0.0);
+/// assert!((reader.rms_db() - -3.01).abs() < 0.01);
+/// ```
+pub fn meter(window: Frames) -> (Meter, MeterReader) {
+    let levels = Arc::new(Levels::default());
+    let meter = Meter {
+        levels: levels.clone(),
+        window: window.max(1),
+        sum: 0.0,
+        count: 0,
+    };
+    (meter, MeterReader::new(levels))
+}
+
+/// Create a `MeterBank` and `MeterBankReader` with `channels` meters, see `meter`.
+pub fn meter_bank(channels: usize, window: Frames) -> (MeterBank, MeterBankReader) {
+    let (meters, readers) = (0..channels).map(|_| meter(window)).unzip();
+    (MeterBank { meters }, MeterBankReader { readers })
+}
+
+/// The levels shared by a `Meter` and its `MeterReader`, as the bits of `f32` values.
+#[derive(Debug, Default)]
+struct Levels {
+    // The loudest absolute sample since the last read. The bits of non-negative floats have the same
+    // order as the floats, so `fetch_max` works on them.
+    peak: AtomicU32,
+    // The mean square of the last complete window.
+    mean_square: AtomicU32,
+}
+
+/// The process callback side of a meter, see `meter`.
+#[derive(Debug)]
+pub struct Meter {
+    levels: Arc<Levels>,
+    window: Frames,
+    sum: f64,
+    count: Frames,
+}
+
+impl Meter {
+    /// Meter `samples`, usually the buffer of a port in a process cycle. Does not block or
+    /// allocate. `NaN` samples are ignored for the peak level.
+    pub fn feed(&mut self, samples: &[f32]) {
+        let peak = samples.iter().fold(0.0f32, |peak, s| peak.max(s.abs()));
+        self.levels
+            .peak
+            .fetch_max(peak.to_bits(), Ordering::Relaxed);
+        for chunk in samples.chunks(self.window as usize) {
+            let room = (self.window - self.count) as usize;
+            let (now, rest) = chunk.split_at(room.min(chunk.len()));
+            self.accumulate(now);
+            self.accumulate(rest);
+        }
+    }
+
+    fn accumulate(&mut self, samples: &[f32]) {
+        if samples.is_empty() {
+            return;
+        }
+        self.sum += samples.iter().map(|s| (s * s) as f64).sum::<f64>();
+        self.count += samples.len() as Frames;
+        if self.count == self.window {
+            let mean_square = (self.sum / self.window as f64) as f32;
+            self.levels
+                .mean_square
+                .store(mean_square.to_bits(), Ordering::Relaxed);
+            self.sum = 0.0;
+            self.count = 0;
+        }
+    }
+}
+
+/// The reading side of a meter, see `meter`.
+#[derive(Debug)]
+pub struct MeterReader {
+    levels: Arc<Levels>,
+    hold: Duration,
+    decay: f32,
+    // The displayed peak level in dB, and when it was last raised.
+    peak_db: f32,
+    peak_at: Option<Instant>,
+    read_at: Option<Instant>,
+}
+
+impl MeterReader {
+    /// The default of `MeterReader::set_peak_hold`.
+    pub const DEFAULT_PEAK_HOLD: Duration = Duration::from_millis(500);
+
+    /// The default of `MeterReader::set_peak_decay`, in dB per second.
+    pub const DEFAULT_PEAK_DECAY: f32 = 20.0;
+
+    fn new(levels: Arc<Levels>) -> MeterReader {
+        MeterReader {
+            levels,
+            hold: MeterReader::DEFAULT_PEAK_HOLD,
+            decay: MeterReader::DEFAULT_PEAK_DECAY,
+            peak_db: f32::NEG_INFINITY,
+            peak_at: None,
+            read_at: None,
+        }
+    }
+
+    /// Set how long a peak is shown before it decays.
+    pub fn set_peak_hold(&mut self, hold: Duration) {
+        self.hold = hold;
+    }
+
+    /// Set how fast the peak level falls after the hold time, in dB per second. With
+    /// `f32::INFINITY` the peak level is the loudest sample since the last read.
+    pub fn set_peak_decay(&mut self, db_per_second: f32) {
+        self.decay = db_per_second;
+    }
+
+    /// The peak level in dBFS, `f32::NEG_INFINITY` for silence.
+    pub fn peak_db(&mut self) -> f32 {
+        self.peak_db_at(Instant::now())
+    }
+
+    fn peak_db_at(&mut self, now: Instant) -> f32 {
+        let peak = f32::from_bits(self.levels.peak.swap(0, Ordering::Relaxed));
+        let peak_db = to_db(peak);
+        if let (Some(peak_at), Some(read_at)) = (self.peak_at, self.read_at) {
+            // Only the time after the hold time counts towards the decay.
+            let decay_from = read_at.max(peak_at + self.hold);
+            let decaying = now.saturating_duration_since(decay_from).as_secs_f32();
+            if decaying > 0.0 {
+                self.peak_db -= self.decay * decaying;
+            }
+        }
+        if peak_db >= self.peak_db || self.peak_at.is_none() {
+            self.peak_db = peak_db;
+            self.peak_at = Some(now);
+        }
+        self.read_at = Some(now);
+        self.peak_db
+    }
+
+    /// The RMS level of the last complete window in dBFS, `f32::NEG_INFINITY` for silence.
+    pub fn rms_db(&self) -> f32 {
+        let mean_square = f32::from_bits(self.levels.mean_square.load(Ordering::Relaxed));
+        to_db(mean_square.sqrt())
+    }
+
+    /// Forget the held peak level.
+    pub fn reset(&mut self) {
+        self.levels.peak.store(0, Ordering::Relaxed);
+        self.peak_db = f32::NEG_INFINITY;
+        self.peak_at = None;
+        self.read_at = None;
+    }
+}
+
+/// The process callback side of multiple meters, for example one for each channel. See
+/// `meter_bank`.
+#[derive(Debug)]
+pub struct MeterBank {
+    meters: Vec<Meter>,
+}
+
+impl MeterBank {
+    /// The number of meters.
+    pub fn channels(&self) -> usize {
+        self.meters.len()
+    }
+
+    /// Meter `samples` with the meter of `channel`, see `Meter::feed`.
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn feed(&mut self, channel: usize, samples: &[f32]) {
+        self.meters[channel].feed(samples);
+    }
+
+    /// The meters, one for each channel.
+    pub fn meters_mut(&mut self) -> &mut [Meter] {
+        &mut self.meters
+    }
+}
+
+/// The reading side of multiple meters, see `meter_bank`.
+#[derive(Debug)]
+pub struct MeterBankReader {
+    readers: Vec<MeterReader>,
+}
+
+impl MeterBankReader {
+    /// The number of meters.
+    pub fn channels(&self) -> usize {
+        self.readers.len()
+    }
+
+    /// Set the peak hold of all meters, see `MeterReader::set_peak_hold`.
+    pub fn set_peak_hold(&mut self, hold: Duration) {
+        for reader in self.readers.iter_mut() {
+            reader.set_peak_hold(hold);
+        }
+    }
+
+    /// Set the peak decay of all meters, see `MeterReader::set_peak_decay`.
+    pub fn set_peak_decay(&mut self, db_per_second: f32) {
+        for reader in self.readers.iter_mut() {
+            reader.set_peak_decay(db_per_second);
+        }
+    }
+
+    /// The peak level of `channel`, see `MeterReader::peak_db`.
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn peak_db(&mut self, channel: usize) -> f32 {
+        self.readers[channel].peak_db()
+    }
+
+    /// The RMS level of `channel`, see `MeterReader::rms_db`.
+    ///
+    /// Panics if `channel` is out of range.
+    pub fn rms_db(&self, channel: usize) -> f32 {
+        self.readers[channel].rms_db()
+    }
+
+    /// The readers, one for each channel.
+    pub fn readers_mut(&mut self) -> &mut [MeterReader] {
+        &mut self.readers
+    }
+}
+
+fn to_db(level: f32) -> f32 {
+    20.0 * level.log10()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sine(frequency: f32, amplitude: f32, frames: usize) -> Vec<f32> {
+        let step = 2.0 * std::f32::consts::PI * frequency / 48000.0;
+        (0..frames)
+            .map(|i| amplitude * (i as f32 * step).sin())
+            .collect()
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 0.01,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn meter_measures_full_scale_sine() {
+        let (mut meter, mut reader) = meter(4800);
+        for cycle in sine(1000.0, 1.0, 48000).chunks(256) {
+            meter.feed(cycle);
+        }
+        assert_close(reader.peak_db(), 0.0);
+        assert_close(reader.rms_db(), -3.01);
+    }
+
+    #[test]
+    fn meter_measures_quiet_sine() {
+        let (mut meter, mut reader) = meter(480);
+        meter.feed(&sine(100.0, 0.1, 480));
+        assert_close(reader.peak_db(), -20.0);
+        assert_close(reader.rms_db(), -23.01);
+    }
+
+    #[test]
+    fn meter_reports_silence() {
+        let (mut meter, mut reader) = meter(64);
+        assert_eq!(reader.peak_db(), f32::NEG_INFINITY);
+        assert_eq!(reader.rms_db(), f32::NEG_INFINITY);
+        meter.feed(&[0.0; 64]);
+        assert_eq!(reader.rms_db(), f32::NEG_INFINITY);
+        // A window is only reported once it is complete.
+        meter.feed(&[1.0; 63]);
+        assert_eq!(reader.rms_db(), f32::NEG_INFINITY);
+        meter.feed(&[1.0; 2]);
+        assert_eq!(reader.rms_db(), 0.0);
+    }
+
+    #[test]
+    fn meter_ignores_nan_peaks() {
+        let (mut meter, mut reader) = meter(4);
+        meter.feed(&[0.5, f32::NAN, -0.25]);
+        assert_close(reader.peak_db(), -6.02);
+    }
+
+    #[test]
+    fn meter_holds_and_decays_peaks() {
+        let (mut meter, mut reader) = meter(64);
+        reader.set_peak_hold(Duration::from_secs(1));
+        reader.set_peak_decay(10.0);
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+
+        meter.feed(&[1.0]);
+        assert_eq!(reader.peak_db_at(at(0)), 0.0);
+        meter.feed(&[0.1]);
+        assert_eq!(reader.peak_db_at(at(500)), 0.0);
+        assert_close(reader.peak_db_at(at(1500)), -5.0);
+        assert_close(reader.peak_db_at(at(2000)), -10.0);
+        // A louder peak is held again.
+        meter.feed(&[0.5]);
+        assert_close(reader.peak_db_at(at(2100)), -6.02);
+        assert_close(reader.peak_db_at(at(3100)), -6.02);
+        assert_close(reader.peak_db_at(at(3600)), -11.02);
+        reader.reset();
+        assert_eq!(reader.peak_db_at(at(3700)), f32::NEG_INFINITY);
+    }
+
+    #[test]
+    fn meter_bank_meters_channels_separately() {
+        let (mut bank, mut reader) = meter_bank(2, 4);
+        assert_eq!((bank.channels(), reader.channels()), (2, 2));
+        bank.feed(0, &[1.0; 4]);
+        bank.meters_mut()[1].feed(&[0.5; 4]);
+        reader.set_peak_decay(f32::INFINITY);
+        assert_eq!(reader.peak_db(0), 0.0);
+        assert_close(reader.rms_db(1), -6.02);
+        assert_close(reader.readers_mut()[1].peak_db(), -6.02);
+    }
+
+    #[test]
+    fn meter_feed_does_not_allocate() {
+        let (mut meter, _reader) = meter(100);
+        let samples = sine(440.0, 0.8, 1024);
+        let before = crate::test_alloc::allocations();
+        for cycle in samples.chunks(64) {
+            meter.feed(cycle);
+        }
+        assert_eq!(crate::test_alloc::allocations(), before);
+    }
+}
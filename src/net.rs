@@ -0,0 +1,1139 @@
+use jack_sys as j;
+use std::cell::UnsafeCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::{fmt, mem, ptr, slice};
+
+use crate::jack_utils::to_cstring;
+use crate::{Control, Error, Frames, MidiIter, MidiWriter, NetLinkErrorKind};
+
+/// The multicast address that NetJACK2 masters listen on by default.
+pub const NET_DEFAULT_MULTICAST_IP: &str = j::DEFAULT_MULTICAST_IP;
+
+/// The port that NetJACK2 masters listen on by default.
+pub const NET_DEFAULT_PORT: u16 = j::DEFAULT_PORT as u16;
+
+/// Returns `true` if the NetJACK2 library, libjacknet, is installed. It comes with JACK2.
+pub fn net_available() -> bool {
+    j::jack_net.is_some()
+}
+
+fn net() -> Result<&'static j::jack_net_t, Error> {
+    j::jack_net.as_ref().ok_or(Error::WeakFunctionNotFound)
+}
+
+/// How a NetJACK2 master encodes the audio sent over the network.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NetEncoder {
+    /// Uncompressed 32 bit floats.
+    Float,
+    /// Uncompressed 16 bit integers.
+    Integer,
+    /// Compressed with CELT, at `NetSlaveConfig::kbps`.
+    Celt,
+    /// Compressed with Opus, at `NetSlaveConfig::kbps`.
+    Opus,
+}
+
+impl NetEncoder {
+    fn to_ffi(self) -> j::JackNetEncoder {
+        match self {
+            NetEncoder::Float => j::JackFloatEncoder,
+            NetEncoder::Integer => j::JackIntEncoder,
+            NetEncoder::Celt => j::JackCeltEncoder,
+            NetEncoder::Opus => j::JackOpusEncoder,
+        }
+    }
+
+    fn from_ffi(encoder: j::JackNetEncoder) -> NetEncoder {
+        match encoder {
+            j::JackIntEncoder => NetEncoder::Integer,
+            j::JackCeltEncoder => NetEncoder::Celt,
+            j::JackOpusEncoder => NetEncoder::Opus,
+            _ => NetEncoder::Float,
+        }
+    }
+}
+
+/// The stream that a NetJACK2 slave requests from the master, see `NetSlave::open`.
+///
+/// Inputs go from the master to the slave and outputs from the slave to the master. A channel
+/// count of `-1` takes as many channels as the master has physical ports.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NetSlaveConfig {
+    /// The number of audio channels from the master.
+    pub audio_input: i32,
+    /// The number of audio channels to the master.
+    pub audio_output: i32,
+    /// The number of MIDI channels from the master.
+    pub midi_input: i32,
+    /// The number of MIDI channels to the master.
+    pub midi_output: i32,
+    /// The maximum size of a network packet, in bytes.
+    pub mtu: i32,
+    /// How long to wait for a master, in seconds. `-1` waits forever.
+    pub time_out: i32,
+    /// How the audio is encoded.
+    pub encoder: NetEncoder,
+    /// The bitrate of the CELT and Opus encoders, in kilobytes per second.
+    pub kbps: i32,
+    /// The latency of the network, in periods.
+    pub latency: i32,
+}
+
+/// Two audio channels each way, without MIDI, uncompressed.
+impl Default for NetSlaveConfig {
+    fn default() -> NetSlaveConfig {
+        NetSlaveConfig {
+            audio_input: 2,
+            audio_output: 2,
+            midi_input: 0,
+            midi_output: 0,
+            mtu: j::DEFAULT_MTU,
+            time_out: -1,
+            encoder: NetEncoder::Float,
+            kbps: 0,
+            latency: 2,
+        }
+    }
+}
+
+impl NetSlaveConfig {
+    fn to_ffi(self) -> j::jack_slave_t {
+        j::jack_slave_t {
+            audio_input: self.audio_input,
+            audio_output: self.audio_output,
+            midi_input: self.midi_input,
+            midi_output: self.midi_output,
+            mtu: self.mtu,
+            time_out: self.time_out,
+            encoder: self.encoder.to_ffi(),
+            kbps: self.kbps,
+            latency: self.latency,
+        }
+    }
+
+    fn from_ffi(slave: &j::jack_slave_t) -> NetSlaveConfig {
+        NetSlaveConfig {
+            audio_input: slave.audio_input,
+            audio_output: slave.audio_output,
+            midi_input: slave.midi_input,
+            midi_output: slave.midi_output,
+            mtu: slave.mtu,
+            time_out: slave.time_out,
+            encoder: NetEncoder::from_ffi(slave.encoder),
+            kbps: slave.kbps,
+            latency: slave.latency,
+        }
+    }
+}
+
+/// The stream that the NetJACK2 master agreed to, see `NetSlave::master`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetMasterInfo {
+    /// The number of audio channels from the master.
+    pub audio_input: usize,
+    /// The number of audio channels to the master.
+    pub audio_output: usize,
+    /// The number of MIDI channels from the master.
+    pub midi_input: usize,
+    /// The number of MIDI channels to the master.
+    pub midi_output: usize,
+    /// The buffer size of the master.
+    pub buffer_size: Frames,
+    /// The sample rate of the master.
+    pub sample_rate: Frames,
+    /// The name of the master.
+    pub master_name: String,
+    /// How long the master waits for the slave, in seconds.
+    pub time_out: i32,
+    /// `true` if the master processes partial cycles.
+    pub partial_cycle: bool,
+}
+
+impl NetMasterInfo {
+    fn from_ffi(master: &j::jack_master_t) -> NetMasterInfo {
+        let name: Vec<u8> = master
+            .master_name
+            .iter()
+            .take_while(|c| **c != 0)
+            .map(|c| *c as u8)
+            .collect();
+        NetMasterInfo {
+            audio_input: master.audio_input.max(0) as usize,
+            audio_output: master.audio_output.max(0) as usize,
+            midi_input: master.midi_input.max(0) as usize,
+            midi_output: master.midi_output.max(0) as usize,
+            buffer_size: master.buffer_size,
+            sample_rate: master.sample_rate,
+            master_name: String::from_utf8_lossy(&name).into_owned(),
+            time_out: master.time_out,
+            partial_cycle: master.partial_cycle != 0,
+        }
+    }
+}
+
+/// A NetJACK2 slave, which processes audio and MIDI for a JACK server on another machine without
+/// a local JACK server.
+///
+/// # Example
+/// ```no_run
+/// let slave = jack::NetSlave::open(
+///     jack::NET_DEFAULT_MULTICAST_IP,
+///     jack::NET_DEFAULT_PORT,
+///     "rust_slave",
+///     jack::NetSlaveConfig::default(),
+/// )
+/// .unwrap();
+/// let handler = jack::ClosureNetSlaveHandler::new(|scope: &mut jack::NetProcessScope| {
+///     for channel in 0..scope.audio_out_count().min(scope.audio_in_count()) {
+///         let input = scope.audio_in(channel).to_vec();
+///         scope.audio_out_mut(channel).copy_from_slice(&input);
+///     }
+///     jack::Control::Continue
+/// });
+/// let active = slave.activate(handler).unwrap();
+/// // ...
+/// let (slave, _handler) = active.deactivate().unwrap();
+/// slave.close().unwrap();
+/// ```
+pub struct NetSlave {
+    raw: *mut j::jack_net_slave_t,
+    master: NetMasterInfo,
+}
+
+unsafe impl Send for NetSlave {}
+unsafe impl Sync for NetSlave {}
+
+impl NetSlave {
+    /// Connect to the NetJACK2 master at `ip` and `port`, as a slave named `name`.
+    ///
+    /// This blocks until a master accepts the slave, or until `config.time_out` seconds passed.
+    /// `Err(Error::WeakFunctionNotFound)` is returned if libjacknet is not installed, and
+    /// `Err(Error::NetSlaveError)` if no master accepted the slave.
+    pub fn open(
+        ip: &str,
+        port: u16,
+        name: &str,
+        config: NetSlaveConfig,
+    ) -> Result<NetSlave, Error> {
+        let net = net()?;
+        let ip = to_cstring(ip)?;
+        let name = to_cstring(name)?;
+        let mut request = config.to_ffi();
+        let mut result: j::jack_master_t = unsafe { mem::zeroed() };
+        let raw = unsafe {
+            (net.jack_net_slave_open)(
+                ip.as_ptr(),
+                libc::c_int::from(port),
+                name.as_ptr(),
+                &mut request,
+                &mut result,
+            )
+        };
+        if raw.is_null() {
+            return Err(Error::NetSlaveError);
+        }
+        Ok(NetSlave {
+            raw,
+            master: NetMasterInfo::from_ffi(&result),
+        })
+    }
+
+    /// The stream that the master agreed to.
+    pub fn master(&self) -> &NetMasterInfo {
+        &self.master
+    }
+
+    /// Start processing with `handler`. The slave is returned by `ActiveNetSlave::deactivate`.
+    pub fn activate<H: 'static + NetSlaveHandler>(
+        self,
+        handler: H,
+    ) -> Result<ActiveNetSlave<H>, Error> {
+        let net = net()?;
+        let mut context = Box::new(NetContext {
+            handler: UnsafeCell::new(handler),
+            poisoned: AtomicBool::new(false),
+        });
+        let data = context.as_mut() as *mut NetContext<H> as *mut libc::c_void;
+        unsafe {
+            let registered = [
+                (
+                    "net process",
+                    (net.jack_set_net_slave_process_callback)(
+                        self.raw,
+                        Some(net_process::<H>),
+                        data,
+                    ),
+                ),
+                (
+                    "net buffer size",
+                    (net.jack_set_net_slave_buffer_size_callback)(
+                        self.raw,
+                        Some(net_buffer_size::<H>),
+                        data,
+                    ),
+                ),
+                (
+                    "net sample rate",
+                    (net.jack_set_net_slave_sample_rate_callback)(
+                        self.raw,
+                        Some(net_sample_rate::<H>),
+                        data,
+                    ),
+                ),
+                (
+                    "net shutdown",
+                    (net.jack_set_net_slave_shutdown_callback)(
+                        self.raw,
+                        Some(net_shutdown::<H>),
+                        data,
+                    ),
+                ),
+            ];
+            if let Some((name, _)) = registered.iter().find(|(_, res)| *res != 0) {
+                self.clear_callbacks(net);
+                return Err(Error::CallbackRegistrationError(name.to_string()));
+            }
+            if (net.jack_net_slave_activate)(self.raw) != 0 {
+                self.clear_callbacks(net);
+                return Err(Error::ClientActivationError);
+            }
+        }
+        Ok(ActiveNetSlave {
+            slave: Some(self),
+            context: Some(context),
+        })
+    }
+
+    /// Disconnect from the master.
+    pub fn close(mut self) -> Result<(), Error> {
+        let raw = mem::replace(&mut self.raw, ptr::null_mut());
+        match unsafe { (net()?.jack_net_slave_close)(raw) } {
+            0 => Ok(()),
+            _ => Err(Error::NetSlaveError),
+        }
+    }
+
+    /// Stop the callbacks from referring to a context that is about to be dropped.
+    ///
+    /// # Safety
+    /// The slave must not be active.
+    unsafe fn clear_callbacks(&self, net: &j::jack_net_t) {
+        let data = ptr::null_mut();
+        (net.jack_set_net_slave_process_callback)(self.raw, None, data);
+        (net.jack_set_net_slave_buffer_size_callback)(self.raw, None, data);
+        (net.jack_set_net_slave_sample_rate_callback)(self.raw, None, data);
+        (net.jack_set_net_slave_shutdown_callback)(self.raw, None, data);
+    }
+}
+
+/// Disconnects from the master.
+impl Drop for NetSlave {
+    fn drop(&mut self) {
+        if let (false, Ok(net)) = (self.raw.is_null(), net()) {
+            unsafe { (net.jack_net_slave_close)(self.raw) };
+        }
+    }
+}
+
+impl fmt::Debug for NetSlave {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NetSlave")
+            .field("master", &self.master)
+            .finish()
+    }
+}
+
+/// A NetJACK2 slave that is processing, see `NetSlave::activate`.
+///
+/// Dropping it deactivates and closes the slave.
+pub struct ActiveNetSlave<H> {
+    slave: Option<NetSlave>,
+    context: Option<Box<NetContext<H>>>,
+}
+
+impl<H> ActiveNetSlave<H> {
+    /// The stream that the master agreed to.
+    pub fn master(&self) -> &NetMasterInfo {
+        self.slave.as_ref().unwrap().master()
+    }
+
+    /// Returns `true` if a method of the handler panicked, after which it is not called again.
+    pub fn is_poisoned(&self) -> bool {
+        self.context
+            .as_ref()
+            .unwrap()
+            .poisoned
+            .load(Ordering::Acquire)
+    }
+
+    /// Stop processing and return the slave and the handler.
+    ///
+    /// If deactivation fails, the handler is leaked since the slave may still call it.
+    pub fn deactivate(mut self) -> Result<(NetSlave, H), Error> {
+        let context = self.stop()?;
+        let slave = self.slave.take().unwrap();
+        Ok((slave, context.handler.into_inner()))
+    }
+
+    fn stop(&mut self) -> Result<Box<NetContext<H>>, Error> {
+        let net = net()?;
+        let slave = self.slave.as_ref().unwrap();
+        let context = self.context.take().unwrap();
+        unsafe {
+            if (net.jack_net_slave_deactivate)(slave.raw) != 0 {
+                mem::forget(context);
+                return Err(Error::ClientDeactivationError);
+            }
+            slave.clear_callbacks(net);
+        }
+        Ok(context)
+    }
+}
+
+/// Deactivates the slave, and closes it.
+impl<H> Drop for ActiveNetSlave<H> {
+    fn drop(&mut self) {
+        if self.context.is_some() {
+            let _ = self.stop();
+        }
+    }
+}
+
+impl<H> fmt::Debug for ActiveNetSlave<H> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ActiveNetSlave")
+            .field("slave", &self.slave)
+            .finish()
+    }
+}
+
+/// The handler of a NetJACK2 slave, see `NetSlave::activate`.
+pub trait NetSlaveHandler: Send {
+    /// Called on the real-time thread of the slave for every cycle, with the buffers received
+    /// from and sent to the master.
+    ///
+    /// The same rules apply as for `ProcessHandler::process`. Returning `Control::Quit` stops the
+    /// slave.
+    fn process(&mut self, scope: &mut NetProcessScope) -> Control;
+
+    /// Called when the buffer size of the master changes.
+    fn buffer_size(&mut self, _size: Frames) -> Control {
+        Control::Continue
+    }
+
+    /// Called when the sample rate of the master changes.
+    fn sample_rate(&mut self, _srate: Frames) -> Control {
+        Control::Continue
+    }
+
+    /// Called when the connection to the master is lost.
+    fn shutdown(&mut self) {}
+}
+
+/// Wrap a closure that handles the `process` callback of a NetJACK2 slave.
+pub struct ClosureNetSlaveHandler<F: Send + FnMut(&mut NetProcessScope) -> Control> {
+    pub process_fn: F,
+}
+
+impl<F> ClosureNetSlaveHandler<F>
+where
+    F: Send + FnMut(&mut NetProcessScope) -> Control,
+{
+    pub fn new(f: F) -> ClosureNetSlaveHandler<F> {
+        ClosureNetSlaveHandler { process_fn: f }
+    }
+}
+
+impl<F> NetSlaveHandler for ClosureNetSlaveHandler<F>
+where
+    F: Send + FnMut(&mut NetProcessScope) -> Control,
+{
+    fn process(&mut self, scope: &mut NetProcessScope) -> Control {
+        (self.process_fn)(scope)
+    }
+}
+
+/// The buffers of a cycle of a NetJACK2 slave, one per channel.
+///
+/// Inputs are received from the master and outputs are sent to the master. A channel index that
+/// is out of range panics, like indexing a slice.
+pub struct NetProcessScope<'a> {
+    n_frames: Frames,
+    audio_in: &'a [*mut f32],
+    audio_out: &'a [*mut f32],
+    midi_in: &'a [*mut libc::c_void],
+    midi_out: &'a [*mut libc::c_void],
+}
+
+impl<'a> NetProcessScope<'a> {
+    /// The number of frames in the buffers.
+    pub fn n_frames(&self) -> Frames {
+        self.n_frames
+    }
+
+    /// The number of audio channels from the master.
+    pub fn audio_in_count(&self) -> usize {
+        self.audio_in.len()
+    }
+
+    /// The number of audio channels to the master.
+    pub fn audio_out_count(&self) -> usize {
+        self.audio_out.len()
+    }
+
+    /// The number of MIDI channels from the master.
+    pub fn midi_in_count(&self) -> usize {
+        self.midi_in.len()
+    }
+
+    /// The number of MIDI channels to the master.
+    pub fn midi_out_count(&self) -> usize {
+        self.midi_out.len()
+    }
+
+    /// The audio received from the master on `channel`.
+    pub fn audio_in(&self, channel: usize) -> &[f32] {
+        unsafe { slice::from_raw_parts(self.audio_in[channel], self.n_frames as usize) }
+    }
+
+    /// The audio to send to the master on `channel`.
+    pub fn audio_out_mut(&mut self, channel: usize) -> &mut [f32] {
+        unsafe { slice::from_raw_parts_mut(self.audio_out[channel], self.n_frames as usize) }
+    }
+
+    /// The MIDI events received from the master on `channel`.
+    pub fn midi_in(&self, channel: usize) -> MidiIter<'_> {
+        unsafe { MidiIter::from_buffer(self.midi_in[channel]) }
+    }
+
+    /// Write the MIDI events to send to the master on `channel`. This clears the events that were
+    /// written before.
+    pub fn midi_out(&mut self, channel: usize) -> MidiWriter<'_> {
+        unsafe { MidiWriter::from_buffer(self.midi_out[channel]) }
+    }
+}
+
+impl<'a> fmt::Debug for NetProcessScope<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NetProcessScope")
+            .field("n_frames", &self.n_frames)
+            .field("audio_in", &self.audio_in.len())
+            .field("audio_out", &self.audio_out.len())
+            .field("midi_in", &self.midi_in.len())
+            .field("midi_out", &self.midi_out.len())
+            .finish()
+    }
+}
+
+/// The handler of an active slave. The slave calls all the callbacks from its own thread, so the
+/// handler is never accessed concurrently.
+struct NetContext<H> {
+    handler: UnsafeCell<H>,
+    poisoned: AtomicBool,
+}
+
+impl<H> NetContext<H> {
+    unsafe fn from_raw<'a>(data: *mut libc::c_void) -> &'a NetContext<H> {
+        &*(data as *const NetContext<H>)
+    }
+
+    /// Run `f` with the handler, returning `default` instead if a handler panicked. Unwinding into
+    /// JACK is undefined behavior so panics must not escape a callback.
+    unsafe fn guard<R, F: FnOnce(&mut H) -> R>(&self, default: R, f: F) -> R {
+        if self.poisoned.load(Ordering::Acquire) {
+            return default;
+        }
+        let handler = &mut *self.handler.get();
+        match panic::catch_unwind(AssertUnwindSafe(|| f(handler))) {
+            Ok(r) => r,
+            Err(_) => {
+                self.poisoned.store(true, Ordering::Release);
+                default
+            }
+        }
+    }
+}
+
+/// The buffers of the slave as slices, empty for channels that are not used.
+unsafe fn channels<'a, T>(count: libc::c_int, buffers: *mut *mut T) -> &'a [*mut T] {
+    match (count, buffers.is_null()) {
+        (count, false) if count > 0 => slice::from_raw_parts(buffers, count as usize),
+        _ => &[],
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe extern "C" fn net_process<H: NetSlaveHandler>(
+    buffer_size: Frames,
+    audio_input: libc::c_int,
+    audio_input_buffer: *mut *mut f32,
+    midi_input: libc::c_int,
+    midi_input_buffer: *mut *mut libc::c_void,
+    audio_output: libc::c_int,
+    audio_output_buffer: *mut *mut f32,
+    midi_output: libc::c_int,
+    midi_output_buffer: *mut *mut libc::c_void,
+    data: *mut libc::c_void,
+) -> libc::c_int {
+    let ctx = NetContext::<H>::from_raw(data);
+    let mut scope = NetProcessScope {
+        n_frames: buffer_size,
+        audio_in: channels(audio_input, audio_input_buffer),
+        audio_out: channels(audio_output, audio_output_buffer),
+        midi_in: channels(midi_input, midi_input_buffer),
+        midi_out: channels(midi_output, midi_output_buffer),
+    };
+    ctx.guard(Control::Quit, |handler| handler.process(&mut scope))
+        .to_ffi()
+}
+
+unsafe extern "C" fn net_buffer_size<H: NetSlaveHandler>(
+    size: Frames,
+    data: *mut libc::c_void,
+) -> libc::c_int {
+    let ctx = NetContext::<H>::from_raw(data);
+    ctx.guard(Control::Quit, |handler| handler.buffer_size(size))
+        .to_ffi()
+}
+
+unsafe extern "C" fn net_sample_rate<H: NetSlaveHandler>(
+    srate: Frames,
+    data: *mut libc::c_void,
+) -> libc::c_int {
+    let ctx = NetContext::<H>::from_raw(data);
+    ctx.guard(Control::Quit, |handler| handler.sample_rate(srate))
+        .to_ffi()
+}
+
+unsafe extern "C" fn net_shutdown<H: NetSlaveHandler>(data: *mut libc::c_void) {
+    let ctx = NetContext::<H>::from_raw(data);
+    ctx.guard((), |handler| handler.shutdown())
+}
+
+/// The stream that a NetJACK2 master offers, see `NetMaster::open`.
+///
+/// Inputs go from the master to the slave and outputs from the slave to the master, like in
+/// `NetSlaveConfig`. A channel count of `-1` takes as many channels as the slave asks for. MIDI is
+/// not exchanged.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NetMasterConfig {
+    /// The number of audio channels to the slave.
+    pub audio_input: i32,
+    /// The number of audio channels from the slave.
+    pub audio_output: i32,
+    /// The number of frames exchanged every cycle.
+    pub buffer_size: Frames,
+    /// The sample rate of the audio.
+    pub sample_rate: Frames,
+    /// The name of the master, as shown to the slave. Names longer than 255 bytes are
+    /// truncated.
+    pub name: String,
+    /// How long to wait for the slave, in seconds.
+    pub time_out: i32,
+}
+
+impl NetMasterConfig {
+    /// A master named `name` that takes the channels that the slave asks for.
+    pub fn new(name: &str, buffer_size: Frames, sample_rate: Frames) -> NetMasterConfig {
+        NetMasterConfig {
+            audio_input: -1,
+            audio_output: -1,
+            buffer_size,
+            sample_rate,
+            name: name.to_string(),
+            time_out: 5,
+        }
+    }
+
+    fn to_ffi(&self) -> Result<j::jack_master_t, Error> {
+        let name = to_cstring(&self.name)?;
+        let mut master = j::jack_master_t {
+            audio_input: self.audio_input,
+            audio_output: self.audio_output,
+            midi_input: 0,
+            midi_output: 0,
+            buffer_size: self.buffer_size,
+            sample_rate: self.sample_rate,
+            master_name: [0; j::MASTER_NAME_SIZE],
+            time_out: self.time_out,
+            partial_cycle: 0,
+        };
+        // The last byte stays NUL.
+        let bytes = name.as_bytes();
+        let len = bytes.len().min(j::MASTER_NAME_SIZE - 1);
+        for (dst, src) in master.master_name.iter_mut().zip(&bytes[..len]) {
+            *dst = *src as libc::c_char;
+        }
+        Ok(master)
+    }
+}
+
+/// Counts of the cycles that a `NetMaster` exchanged with its slave, see `NetMaster::stats`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NetLinkStats {
+    /// The number of cycles that were received.
+    pub received: u64,
+    /// The number of cycles that were sent.
+    pub sent: u64,
+    /// The number of times the slave did not answer in time or the socket failed.
+    pub socket_errors: u64,
+    /// The number of lost or corrupted synchronization packets.
+    pub lost_sync_packets: u64,
+    /// The number of lost or corrupted data packets.
+    pub lost_data_packets: u64,
+    /// The number of failures with an unknown cause.
+    pub other_errors: u64,
+}
+
+impl NetLinkStats {
+    fn record(&mut self, kind: NetLinkErrorKind) {
+        match kind {
+            NetLinkErrorKind::SocketError => self.socket_errors += 1,
+            NetLinkErrorKind::SyncPacketLost => self.lost_sync_packets += 1,
+            NetLinkErrorKind::DataPacketLost => self.lost_data_packets += 1,
+            NetLinkErrorKind::Unknown(_) => self.other_errors += 1,
+        }
+    }
+}
+
+/// A NetJACK2 master, which exchanges audio with a slave on another machine every period.
+///
+/// The master owns a buffer per channel. Every period, `recv` fills the buffers from the slave and
+/// `send` sends the buffers to the slave. Failures are returned as `Error::NetLinkError` and
+/// counted in `stats`.
+///
+/// # Example
+/// ```no_run
+/// let config = jack::NetMasterConfig::new("rust_master", 512, 48000);
+/// let mut master =
+///     jack::NetMaster::open(jack::NET_DEFAULT_MULTICAST_IP, jack::NET_DEFAULT_PORT, &config)
+///         .unwrap();
+/// loop {
+///     if let Err(e) = master.recv() {
+///         eprintln!("lost a cycle: {:?}", e);
+///     }
+///     for channel in master.audio_out_channels_mut() {
+///         channel.iter_mut().for_each(|x| *x = 0.0);
+///     }
+///     master.send().unwrap();
+/// #   break;
+/// }
+/// ```
+pub struct NetMaster {
+    raw: *mut j::jack_net_master_t,
+    slave: NetSlaveConfig,
+    n_frames: usize,
+    // The channels are stored one after the other.
+    audio_in: Vec<f32>,
+    audio_out: Vec<f32>,
+    // Preallocated so that exchanging a cycle does not allocate.
+    in_ptrs: Vec<*mut f32>,
+    out_ptrs: Vec<*mut f32>,
+    stats: NetLinkStats,
+}
+
+unsafe impl Send for NetMaster {}
+
+impl NetMaster {
+    /// Wait for a slave on `ip` and `port`, and agree on the stream with it.
+    ///
+    /// `Err(Error::WeakFunctionNotFound)` is returned if libjacknet is not installed, and
+    /// `Err(Error::NetMasterError)` if no slave connected.
+    pub fn open(ip: &str, port: u16, config: &NetMasterConfig) -> Result<NetMaster, Error> {
+        let net = net()?;
+        if config.buffer_size == 0 {
+            return Err(Error::NetMasterError);
+        }
+        let ip = to_cstring(ip)?;
+        let mut request = config.to_ffi()?;
+        let mut result: j::jack_slave_t = unsafe { mem::zeroed() };
+        let raw = unsafe {
+            (net.jack_net_master_open)(
+                ip.as_ptr(),
+                libc::c_int::from(port),
+                &mut request,
+                &mut result,
+            )
+        };
+        if raw.is_null() {
+            return Err(Error::NetMasterError);
+        }
+        let slave = NetSlaveConfig::from_ffi(&result);
+        Ok(NetMaster::with_buffers(raw, slave, config.buffer_size))
+    }
+
+    fn with_buffers(
+        raw: *mut j::jack_net_master_t,
+        slave: NetSlaveConfig,
+        n_frames: Frames,
+    ) -> NetMaster {
+        // The master receives what the slave outputs, and sends what the slave inputs.
+        let n_in = slave.audio_output.max(0) as usize;
+        let n_out = slave.audio_input.max(0) as usize;
+        let n_frames = n_frames as usize;
+        NetMaster {
+            raw,
+            slave,
+            n_frames,
+            audio_in: vec![0.0; n_in * n_frames],
+            audio_out: vec![0.0; n_out * n_frames],
+            in_ptrs: Vec::with_capacity(n_in),
+            out_ptrs: Vec::with_capacity(n_out),
+            stats: NetLinkStats::default(),
+        }
+    }
+
+    /// The stream that the slave agreed to.
+    pub fn slave(&self) -> &NetSlaveConfig {
+        &self.slave
+    }
+
+    /// The number of frames in the buffers.
+    pub fn n_frames(&self) -> Frames {
+        self.n_frames as Frames
+    }
+
+    /// The number of audio channels from the slave.
+    pub fn audio_in_count(&self) -> usize {
+        self.audio_in.len() / self.n_frames
+    }
+
+    /// The number of audio channels to the slave.
+    pub fn audio_out_count(&self) -> usize {
+        self.audio_out.len() / self.n_frames
+    }
+
+    /// The audio received from the slave on `channel`. Panics if `channel` is out of range.
+    pub fn audio_in(&self, channel: usize) -> &[f32] {
+        self.audio_in_channels().nth(channel).unwrap()
+    }
+
+    /// The audio to send to the slave on `channel`. Panics if `channel` is out of range.
+    pub fn audio_out_mut(&mut self, channel: usize) -> &mut [f32] {
+        self.audio_out_channels_mut().nth(channel).unwrap()
+    }
+
+    /// The audio received from the slave, a slice per channel.
+    pub fn audio_in_channels(&self) -> impl Iterator<Item = &[f32]> {
+        self.audio_in.chunks_exact(self.n_frames)
+    }
+
+    /// The audio to send to the slave, a slice per channel.
+    pub fn audio_out_channels_mut(&mut self) -> impl Iterator<Item = &mut [f32]> {
+        self.audio_out.chunks_exact_mut(self.n_frames)
+    }
+
+    /// Receive a cycle from the slave into the input buffers. Blocks until the cycle arrived or the
+    /// time out passed.
+    ///
+    /// On failure, the input buffers are silenced so that stale audio is not played again.
+    pub fn recv(&mut self) -> Result<(), Error> {
+        let net = net()?;
+        let n_frames = self.n_frames;
+        self.in_ptrs.clear();
+        self.in_ptrs.extend(
+            self.audio_in
+                .chunks_exact_mut(n_frames)
+                .map(|c| c.as_mut_ptr()),
+        );
+        let code = unsafe {
+            (net.jack_net_master_recv)(
+                self.raw,
+                self.in_ptrs.len() as libc::c_int,
+                self.in_ptrs.as_mut_ptr(),
+                0,
+                ptr::null_mut(),
+            )
+        };
+        self.received(code)
+    }
+
+    fn received(&mut self, code: libc::c_int) -> Result<(), Error> {
+        if code >= 0 {
+            self.stats.received += 1;
+            return Ok(());
+        }
+        self.audio_in.iter_mut().for_each(|x| *x = 0.0);
+        let kind = NetLinkErrorKind::from_ffi(code);
+        self.stats.record(kind);
+        Err(Error::NetLinkError(kind))
+    }
+
+    /// Send the output buffers to the slave.
+    pub fn send(&mut self) -> Result<(), Error> {
+        let net = net()?;
+        let n_frames = self.n_frames;
+        self.out_ptrs.clear();
+        self.out_ptrs.extend(
+            self.audio_out
+                .chunks_exact_mut(n_frames)
+                .map(|c| c.as_mut_ptr()),
+        );
+        let code = unsafe {
+            (net.jack_net_master_send)(
+                self.raw,
+                self.out_ptrs.len() as libc::c_int,
+                self.out_ptrs.as_mut_ptr(),
+                0,
+                ptr::null_mut(),
+            )
+        };
+        self.sent(code)
+    }
+
+    fn sent(&mut self, code: libc::c_int) -> Result<(), Error> {
+        if code >= 0 {
+            self.stats.sent += 1;
+            return Ok(());
+        }
+        let kind = NetLinkErrorKind::from_ffi(code);
+        self.stats.record(kind);
+        Err(Error::NetLinkError(kind))
+    }
+
+    /// The cycles that were exchanged and the failures, since the master was opened.
+    pub fn stats(&self) -> NetLinkStats {
+        self.stats
+    }
+
+    /// Disconnect from the slave.
+    pub fn close(mut self) -> Result<(), Error> {
+        let raw = mem::replace(&mut self.raw, ptr::null_mut());
+        match unsafe { (net()?.jack_net_master_close)(raw) } {
+            0 => Ok(()),
+            _ => Err(Error::NetMasterError),
+        }
+    }
+}
+
+/// Disconnects from the slave.
+impl Drop for NetMaster {
+    fn drop(&mut self) {
+        if let (false, Ok(net)) = (self.raw.is_null(), net()) {
+            unsafe { (net.jack_net_master_close)(self.raw) };
+        }
+    }
+}
+
+impl fmt::Debug for NetMaster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NetMaster")
+            .field("slave", &self.slave)
+            .field("n_frames", &self.n_frames)
+            .field("stats", &self.stats)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn net_slave_config_converts_to_ffi() {
+        let config = NetSlaveConfig {
+            midi_input: 1,
+            encoder: NetEncoder::Opus,
+            kbps: 64,
+            ..NetSlaveConfig::default()
+        };
+        let raw = config.to_ffi();
+        assert_eq!(
+            (
+                raw.audio_input,
+                raw.audio_output,
+                raw.midi_input,
+                raw.midi_output
+            ),
+            (2, 2, 1, 0)
+        );
+        assert_eq!(
+            (raw.mtu, raw.time_out, raw.latency),
+            (j::DEFAULT_MTU, -1, 2)
+        );
+        assert_eq!((raw.encoder, raw.kbps), (j::JackOpusEncoder, 64));
+    }
+
+    #[test]
+    fn net_master_info_converts_from_ffi() {
+        let mut raw: j::jack_master_t = unsafe { mem::zeroed() };
+        raw.audio_input = 4;
+        raw.audio_output = 2;
+        raw.buffer_size = 256;
+        raw.sample_rate = 48000;
+        raw.partial_cycle = 1;
+        for (dst, src) in raw.master_name.iter_mut().zip(b"studio".iter()) {
+            *dst = *src as libc::c_char;
+        }
+        let master = NetMasterInfo::from_ffi(&raw);
+        assert_eq!(master.master_name, "studio");
+        assert_eq!((master.audio_input, master.audio_output), (4, 2));
+        assert_eq!((master.buffer_size, master.sample_rate), (256, 48000));
+        assert!(master.partial_cycle);
+    }
+
+    struct Doubler;
+
+    impl NetSlaveHandler for Doubler {
+        fn process(&mut self, scope: &mut NetProcessScope) -> Control {
+            assert_eq!((scope.midi_in_count(), scope.midi_out_count()), (0, 0));
+            for channel in 0..scope.audio_out_count() {
+                let input: Vec<f32> = scope.audio_in(channel).iter().map(|x| x * 2.0).collect();
+                scope.audio_out_mut(channel).copy_from_slice(&input);
+            }
+            Control::Continue
+        }
+
+        fn buffer_size(&mut self, _: Frames) -> Control {
+            panic!("buffer size changed")
+        }
+    }
+
+    fn process<H: NetSlaveHandler>(
+        context: &NetContext<H>,
+        inputs: &mut [[f32; 2]],
+        outputs: &mut [[f32; 2]],
+    ) -> libc::c_int {
+        let mut input_ptrs: Vec<*mut f32> = inputs.iter_mut().map(|c| c.as_mut_ptr()).collect();
+        let mut output_ptrs: Vec<*mut f32> = outputs.iter_mut().map(|c| c.as_mut_ptr()).collect();
+        unsafe {
+            net_process::<H>(
+                2,
+                input_ptrs.len() as libc::c_int,
+                input_ptrs.as_mut_ptr(),
+                0,
+                ptr::null_mut(),
+                output_ptrs.len() as libc::c_int,
+                output_ptrs.as_mut_ptr(),
+                0,
+                ptr::null_mut(),
+                context as *const _ as *mut libc::c_void,
+            )
+        }
+    }
+
+    #[test]
+    fn net_process_passes_audio_channels() {
+        let context = NetContext {
+            handler: UnsafeCell::new(Doubler),
+            poisoned: AtomicBool::new(false),
+        };
+        let mut outputs = [[0.0; 2]; 2];
+        let res = process(&context, &mut [[1.0, 2.0], [3.0, 4.0]], &mut outputs);
+        assert_eq!(res, Control::Continue.to_ffi());
+        assert_eq!(outputs, [[2.0, 4.0], [6.0, 8.0]]);
+    }
+
+    #[test]
+    fn net_handler_panic_poisons_the_slave() {
+        let context = NetContext {
+            handler: UnsafeCell::new(Doubler),
+            poisoned: AtomicBool::new(false),
+        };
+        let data = &context as *const _ as *mut libc::c_void;
+        let res = unsafe { net_buffer_size::<Doubler>(128, data) };
+        assert_eq!(res, Control::Quit.to_ffi());
+        assert!(context.poisoned.load(Ordering::Acquire));
+        let mut outputs = [[0.0; 2]; 2];
+        let res = process(&context, &mut [[1.0, 2.0], [3.0, 4.0]], &mut outputs);
+        assert_eq!(res, Control::Quit.to_ffi());
+        assert_eq!(outputs, [[0.0; 2]; 2]);
+    }
+
+    #[test]
+    fn net_slave_open_fails_without_master() {
+        let config = NetSlaveConfig {
+            time_out: 1,
+            ..NetSlaveConfig::default()
+        };
+        let res = NetSlave::open("127.0.0.1", 1, "rust_jack_net_test", config);
+        match net_available() {
+            true => assert_eq!(res.err(), Some(Error::NetSlaveError)),
+            false => assert_eq!(res.err(), Some(Error::WeakFunctionNotFound)),
+        }
+    }
+
+    #[test]
+    fn net_master_config_converts_to_ffi() {
+        let mut config = NetMasterConfig::new("hub", 256, 44100);
+        config.audio_output = 1;
+        let raw = config.to_ffi().unwrap();
+        assert_eq!((raw.audio_input, raw.audio_output), (-1, 1));
+        assert_eq!((raw.midi_input, raw.midi_output), (0, 0));
+        assert_eq!((raw.buffer_size, raw.sample_rate), (256, 44100));
+        assert_eq!(NetMasterInfo::from_ffi(&raw).master_name, "hub");
+
+        config.name = "a".repeat(j::MASTER_NAME_SIZE * 2);
+        let raw = config.to_ffi().unwrap();
+        assert_eq!(raw.master_name[j::MASTER_NAME_SIZE - 1], 0);
+        config.name = "h\0b".to_string();
+        assert!(matches!(config.to_ffi(), Err(Error::InvalidName(_))));
+    }
+
+    fn fake_master(audio_input: i32, audio_output: i32) -> NetMaster {
+        let slave = NetSlaveConfig {
+            audio_input,
+            audio_output,
+            ..NetSlaveConfig::default()
+        };
+        NetMaster::with_buffers(ptr::null_mut(), slave, 4)
+    }
+
+    #[test]
+    fn net_master_has_a_buffer_per_channel() {
+        let mut master = fake_master(3, 1);
+        assert_eq!((master.audio_in_count(), master.audio_out_count()), (1, 3));
+        assert_eq!(master.audio_in(0), [0.0; 4]);
+        master.audio_out_mut(2)[3] = 1.0;
+        let outputs: Vec<&mut [f32]> = master.audio_out_channels_mut().collect();
+        assert_eq!(outputs.len(), 3);
+        assert_eq!(outputs[2], [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(fake_master(-1, 0).audio_out_channels_mut().count(), 0);
+    }
+
+    #[test]
+    fn net_master_reports_link_errors() {
+        let mut master = fake_master(2, 2);
+        master.audio_in.iter_mut().for_each(|x| *x = 1.0);
+        assert_eq!(master.received(0), Ok(()));
+        assert_eq!(master.audio_in(1), [1.0; 4]);
+        assert_eq!(
+            master.received(j::NET_DATA_PACKET_ERROR),
+            Err(Error::NetLinkError(NetLinkErrorKind::DataPacketLost))
+        );
+        assert_eq!(master.audio_in(1), [0.0; 4]);
+        assert_eq!(
+            master.sent(j::NET_SOCKET_ERROR),
+            Err(Error::NetLinkError(NetLinkErrorKind::SocketError))
+        );
+        assert_eq!(
+            master.sent(-7),
+            Err(Error::NetLinkError(NetLinkErrorKind::Unknown(-7)))
+        );
+        assert_eq!(master.sent(0), Ok(()));
+        assert_eq!(
+            master.stats(),
+            NetLinkStats {
+                received: 1,
+                sent: 1,
+                socket_errors: 1,
+                lost_sync_packets: 0,
+                lost_data_packets: 1,
+                other_errors: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn net_master_open_fails_without_slave() {
+        let mut config = NetMasterConfig::new("rust_jack_net_test", 128, 48000);
+        config.time_out = 1;
+        let res = NetMaster::open("127.0.0.1", 1, &config);
+        match net_available() {
+            true => assert_eq!(res.err(), Some(Error::NetMasterError)),
+            false => assert_eq!(res.err(), Some(Error::WeakFunctionNotFound)),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+
+thread_local! {
+    // The number of `assert_no_alloc` sections the thread is in, `0` if it may allocate.
+    static FORBIDDEN: Cell<usize> = const { Cell::new(0) };
+    // The first allocation that was made in the outermost section.
+    static VIOLATION: RefCell<Option<Violation>> = const { RefCell::new(None) };
+}
+
+struct Violation {
+    size: usize,
+    backtrace: Backtrace,
+}
+
+/// A global allocator that reports allocations made within `assert_no_alloc`, such as in the
+/// process callback. Allocations are passed on to the wrapped allocator, by default the system
+/// allocator.
+///
+/// Allocating in the process callback may block and cause xruns, which only happen now and then.
+/// With the `assert-no-alloc` feature, the process callback of every client runs within
+/// `assert_no_alloc`. Once the callback returns, a process handler that allocated panics with the
+/// size and the backtrace of the first allocation, and the client stops processing like for other
+/// panics. Checking only happens in debug builds, in release builds the checks compile to nothing.
+///
+/// The checks only work if the application uses this allocator, and are typically enabled for
+/// tests or debug builds only:
+///
+/// ```toml
+/// [features]
+/// check-rt = ["jack/assert-no-alloc"]
+/// ```
+///
+/// ```no_run
+/// #[cfg(feature = "check-rt")]
+/// #[global_allocator]
+/// static ALLOCATOR: jack::NoAllocChecker = jack::NoAllocChecker::new(std::alloc::System);
+/// ```
+#[derive(Debug, Default)]
+pub struct NoAllocChecker<A = System> {
+    inner: A,
+}
+
+impl<A> NoAllocChecker<A> {
+    /// Check the allocations made with `inner`.
+    pub const fn new(inner: A) -> NoAllocChecker<A> {
+        NoAllocChecker { inner }
+    }
+}
+
+unsafe impl<A: GlobalAlloc> GlobalAlloc for NoAllocChecker<A> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        check(layout);
+        self.inner.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        check(layout);
+        self.inner.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        check(layout);
+        self.inner.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.inner.dealloc(ptr, layout)
+    }
+}
+
+/// Remember the allocation if the thread is within `assert_no_alloc`. Panicking is left to
+/// `assert_no_alloc`, since a global allocator must not unwind.
+#[inline]
+fn check(layout: Layout) {
+    if !cfg!(debug_assertions) {
+        return;
+    }
+    // The thread locals may already be destroyed while the thread exits.
+    let _ = FORBIDDEN.try_with(|forbidden| {
+        if forbidden.get() == 0 {
+            return;
+        }
+        // Capturing the backtrace allocates as well.
+        let depth = forbidden.replace(0);
+        let _ = VIOLATION.try_with(|violation| {
+            violation.borrow_mut().get_or_insert_with(|| Violation {
+                size: layout.size(),
+                backtrace: Backtrace::force_capture(),
+            });
+        });
+        forbidden.set(depth);
+    });
+}
+
+/// Run `f`, and panic afterwards if it allocated with `NoAllocChecker`. The panic message has the
+/// backtrace of the first allocation.
+///
+/// In release builds, this only calls `f`.
+pub fn assert_no_alloc<T, F: FnOnce() -> T>(f: F) -> T {
+    if !cfg!(debug_assertions) {
+        return f();
+    }
+    struct Section;
+    impl Drop for Section {
+        fn drop(&mut self) {
+            FORBIDDEN.with(|forbidden| forbidden.set(forbidden.get() - 1));
+        }
+    }
+    let outermost = FORBIDDEN.with(|forbidden| forbidden.replace(forbidden.get() + 1)) == 0;
+    if outermost {
+        VIOLATION.with(|violation| violation.take());
+    }
+    let res = {
+        let _section = Section;
+        f()
+    };
+    if !outermost {
+        return res;
+    }
+    if let Some(violation) = VIOLATION.with(RefCell::take) {
+        panic!(
+            "allocated {} bytes where allocating is not allowed, at:\n{}",
+            violation.size, violation.backtrace
+        );
+    }
+    res
+}
+
+/// Run `f` with allocations allowed, within `assert_no_alloc`. For code that is known to allocate
+/// rarely, for example to grow a buffer the first time it is used.
+pub fn permit_alloc<T, F: FnOnce() -> T>(f: F) -> T {
+    struct Restore(usize);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            FORBIDDEN.with(|forbidden| forbidden.set(self.0));
+        }
+    }
+    let _restore = Restore(FORBIDDEN.with(|forbidden| forbidden.replace(0)));
+    f()
+}
+
+#[cfg(all(test, debug_assertions))]
+mod test {
+    use super::*;
+    use std::panic;
+
+    static CHECKER: NoAllocChecker = NoAllocChecker::new(System);
+
+    // The allocator is not the global allocator of the tests, so it is called directly.
+    fn allocate(size: usize) {
+        let layout = Layout::from_size_align(size, 8).unwrap();
+        unsafe { CHECKER.dealloc(CHECKER.alloc(layout), layout) };
+    }
+
+    fn panic_message(f: impl FnOnce() + panic::UnwindSafe) -> Option<String> {
+        let payload = panic::catch_unwind(f).err()?;
+        payload.downcast_ref::<String>().cloned()
+    }
+
+    #[test]
+    fn no_alloc_allows_allocations_outside_of_sections() {
+        allocate(16);
+        assert_eq!(assert_no_alloc(|| 1 + 1), 2);
+        allocate(16);
+    }
+
+    #[test]
+    fn no_alloc_panics_after_section_that_allocated() {
+        let message = panic_message(|| assert_no_alloc(|| allocate(24))).unwrap();
+        assert!(
+            message.starts_with("allocated 24 bytes where allocating is not allowed"),
+            "{}",
+            message
+        );
+        // The violation does not carry over to the next section.
+        assert_no_alloc(|| ());
+    }
+
+    #[test]
+    fn no_alloc_reports_the_first_allocation_of_nested_sections() {
+        let message = panic_message(|| {
+            assert_no_alloc(|| {
+                assert_no_alloc(|| allocate(8));
+                allocate(32);
+            })
+        })
+        .unwrap();
+        assert!(message.starts_with("allocated 8 bytes"), "{}", message);
+    }
+
+    #[test]
+    fn no_alloc_can_permit_allocations() {
+        assert_no_alloc(|| {
+            permit_alloc(|| allocate(16));
+            assert_eq!(permit_alloc(|| 3), 3);
+        });
+    }
+
+    #[test]
+    fn no_alloc_recovers_from_panics() {
+        assert!(panic::catch_unwind(|| assert_no_alloc(|| panic!("process failed"))).is_err());
+        assert_eq!(FORBIDDEN.with(Cell::get), 0);
+        allocate(16);
+        assert_no_alloc(|| ());
+    }
+}
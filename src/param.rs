@@ -0,0 +1,375 @@
+use std::cell::UnsafeCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::Error;
+
+/// A `Copy` value, such as a gain or a filter cutoff, that is changed by a control thread and read
+/// by the process callback. Share it with an `Arc` or put it in a `ParamMailbox`.
+///
+/// `get` is wait-free and never returns a value that is partly old and partly new, no matter how
+/// large `T` is. Any number of threads may read at the same time. `set` is not real-time safe: it
+/// waits for readers of the previous value to finish and only one `set` runs at a time.
+///
+/// The cell keeps two copies of the value and readers are directed to the one that is not being
+/// written, following the Left-Right algorithm.
+///
+/// # Example
+/// ```
+/// use std::sync::Arc;
+///
+/// let gain = Arc::new(jack::ParamCell::new(1.0f32));
+/// let control = gain.clone();
+/// std::thread::spawn(move || control.set(0.5)).join().unwrap();
+/// // In the process callback.
+/// assert_eq!(gain.get_if_changed(), Some(0.5));
+/// assert_eq!(gain.get_if_changed(), None);
+/// assert_eq!(gain.get(), 0.5);
+/// ```
+pub struct ParamCell<T> {
+    values: [UnsafeCell<T>; 2],
+    // The copy of the value that new readers read.
+    read_index: AtomicUsize,
+    // Which of `readers` new readers register in.
+    version: AtomicUsize,
+    readers: [AtomicUsize; 2],
+    changed: AtomicBool,
+    writer: Mutex<()>,
+}
+
+// Readers and the writer never access the same copy of the value at the same time, so sharing the
+// cell only requires that the values may be sent between threads.
+unsafe impl<T: Send> Sync for ParamCell<T> {}
+
+impl<T: Copy> ParamCell<T> {
+    /// Create a cell that holds `value`. The change flag starts cleared.
+    pub fn new(value: T) -> ParamCell<T> {
+        ParamCell {
+            values: [UnsafeCell::new(value), UnsafeCell::new(value)],
+            read_index: AtomicUsize::new(0),
+            version: AtomicUsize::new(0),
+            readers: [AtomicUsize::new(0), AtomicUsize::new(0)],
+            changed: AtomicBool::new(false),
+            writer: Mutex::new(()),
+        }
+    }
+
+    /// Get the current value. Wait-free and does not allocate, so it may be called from the
+    /// process callback.
+    pub fn get(&self) -> T {
+        let version = self.version.load(Ordering::SeqCst);
+        self.readers[version].fetch_add(1, Ordering::SeqCst);
+        let index = self.read_index.load(Ordering::SeqCst);
+        let value = unsafe { *self.values[index].get() };
+        self.readers[version].fetch_sub(1, Ordering::SeqCst);
+        value
+    }
+
+    /// Get the current value if it was set since the change flag was last cleared, and clear the
+    /// flag. Lets the process callback only do work, such as recomputing filter coefficients,
+    /// when something changed.
+    ///
+    /// The flag is shared by all readers, so with several readers only one of them sees a change.
+    pub fn get_if_changed(&self) -> Option<T> {
+        if self.changed.swap(false, Ordering::Acquire) {
+            Some(self.get())
+        } else {
+            None
+        }
+    }
+
+    /// Returns `true` if the value was set since the change flag was last cleared.
+    pub fn is_changed(&self) -> bool {
+        self.changed.load(Ordering::Acquire)
+    }
+
+    /// Set the value and the change flag. Readers see either the old or the new value until
+    /// `set` returns, and the new value after.
+    ///
+    /// Not real-time safe, it may wait for readers and other calls to `set`.
+    pub fn set(&self, value: T) {
+        let _writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let index = self.read_index.load(Ordering::SeqCst);
+        // Nobody reads the other copy, readers that started on it finished before the previous
+        // `set` returned.
+        unsafe { *self.values[1 - index].get() = value };
+        self.read_index.store(1 - index, Ordering::SeqCst);
+        // Wait until no reader can still be reading the old copy.
+        let version = self.version.load(Ordering::SeqCst);
+        Self::wait_for_readers(&self.readers[1 - version]);
+        self.version.store(1 - version, Ordering::SeqCst);
+        Self::wait_for_readers(&self.readers[version]);
+        unsafe { *self.values[index].get() = value };
+        self.changed.store(true, Ordering::Release);
+    }
+
+    fn wait_for_readers(readers: &AtomicUsize) {
+        while readers.load(Ordering::SeqCst) != 0 {
+            thread::yield_now();
+        }
+    }
+}
+
+impl<T: Copy + Default> Default for ParamCell<T> {
+    fn default() -> ParamCell<T> {
+        ParamCell::new(T::default())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for ParamCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("ParamCell")
+            .field("value", &self.get())
+            .field("changed", &self.is_changed())
+            .finish()
+    }
+}
+
+/// A fixed set of named `ParamCell`s, for example all the parameters of a plugin. The control
+/// thread sets parameters by name and the process callback reads them by index, which is
+/// wait-free and does not compare strings.
+///
+/// Besides the change flag of every cell, the mailbox has a change flag of its own so the process
+/// callback can skip looking at the cells when nothing changed.
+///
+/// # Example
+/// ```
+/// use std::sync::Arc;
+///
+/// let params = Arc::new(jack::ParamMailbox::new(vec![("gain", 1.0f32), ("cutoff", 1000.0)]));
+/// let cutoff = params.index("cutoff").unwrap();
+///
+/// params.set("cutoff", 440.0).unwrap();
+/// assert!(params.set("resonance", 0.5).is_err());
+///
+/// // In the process callback.
+/// if params.take_changed() {
+///     if let Some(hz) = params.cell(cutoff).get_if_changed() {
+///         assert_eq!(hz, 440.0);
+///     }
+/// }
+/// assert_eq!(params.get(0), 1.0);
+/// ```
+pub struct ParamMailbox<T> {
+    names: Vec<String>,
+    cells: Box<[ParamCell<T>]>,
+    changed: AtomicBool,
+}
+
+impl<T: Copy> ParamMailbox<T> {
+    /// Create a mailbox with a cell for each name and initial value. If a name appears more than
+    /// once, lookups by name find the first cell.
+    pub fn new<I, S>(params: I) -> ParamMailbox<T>
+    where
+        I: IntoIterator<Item = (S, T)>,
+        S: Into<String>,
+    {
+        let (names, cells): (Vec<String>, Vec<ParamCell<T>>) = params
+            .into_iter()
+            .map(|(name, value)| (name.into(), ParamCell::new(value)))
+            .unzip();
+        ParamMailbox {
+            names,
+            cells: cells.into_boxed_slice(),
+            changed: AtomicBool::new(false),
+        }
+    }
+
+    /// The number of parameters.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns `true` if there are no parameters.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// The index of the parameter called `name`. Look indices up before activating the client,
+    /// since searching compares strings.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// The name of the parameter at `index`.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index).map(String::as_str)
+    }
+
+    /// The cell of the parameter at `index`.
+    ///
+    /// # Panics
+    /// Panics if `index` is not less than `len()`.
+    pub fn cell(&self, index: usize) -> &ParamCell<T> {
+        &self.cells[index]
+    }
+
+    /// Get the value of the parameter at `index`, see `ParamCell::get`.
+    ///
+    /// # Panics
+    /// Panics if `index` is not less than `len()`.
+    pub fn get(&self, index: usize) -> T {
+        self.cells[index].get()
+    }
+
+    /// Set the parameter called `name`, see `ParamCell::set`. Sets the change flag of the cell
+    /// and of the mailbox.
+    ///
+    /// `Err(Error::UnknownParameter(name))` is returned if there is no such parameter.
+    pub fn set(&self, name: &str, value: T) -> Result<(), Error> {
+        let index = self
+            .index(name)
+            .ok_or_else(|| Error::UnknownParameter(name.to_string()))?;
+        self.set_index(index, value);
+        Ok(())
+    }
+
+    /// Set the parameter at `index`, see `ParamCell::set`. Sets the change flag of the cell and of
+    /// the mailbox.
+    ///
+    /// # Panics
+    /// Panics if `index` is not less than `len()`.
+    pub fn set_index(&self, index: usize, value: T) {
+        self.cells[index].set(value);
+        self.changed.store(true, Ordering::Release);
+    }
+
+    /// Returns `true` if any parameter was set since the last call, and clears the mailbox's
+    /// change flag. The change flags of the cells are left alone. Wait-free.
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::Acquire)
+    }
+
+    /// Iterate over the names and cells of the parameters.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ParamCell<T>)> {
+        self.names.iter().map(String::as_str).zip(self.cells.iter())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for ParamMailbox<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_map()
+            .entries(self.iter().map(|(name, cell)| (name, cell.get())))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn param_cell_gets_what_was_set() {
+        let cell = ParamCell::new(1);
+        assert_eq!(cell.get(), 1);
+        assert!(!cell.is_changed());
+        cell.set(2);
+        cell.set(3);
+        assert!(cell.is_changed());
+        assert_eq!(cell.get(), 3);
+        assert_eq!(cell.get_if_changed(), Some(3));
+        assert_eq!(cell.get_if_changed(), None);
+        assert_eq!(cell.get(), 3);
+    }
+
+    #[test]
+    fn param_cell_is_never_torn() {
+        // Each value repeats a single number, so a torn read mixes two different numbers.
+        let cell = Arc::new(ParamCell::new([0u64; 16]));
+        let writers: Vec<_> = (0..2)
+            .map(|w| {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    for i in 1..=20_000u64 {
+                        cell.set([i * 2 + w; 16]);
+                    }
+                })
+            })
+            .collect();
+        let readers: Vec<_> = (0..3)
+            .map(|_| {
+                let cell = cell.clone();
+                thread::spawn(move || {
+                    for _ in 0..200_000 {
+                        let value = cell.get();
+                        assert!(value.iter().all(|&v| v == value[0]), "{:?}", value);
+                    }
+                })
+            })
+            .collect();
+        for handle in writers.into_iter().chain(readers) {
+            handle.join().unwrap();
+        }
+        let last = cell.get()[0];
+        assert!(last == 40_000 || last == 40_001);
+    }
+
+    #[test]
+    fn param_cell_reader_sees_values_in_order() {
+        let cell = Arc::new(ParamCell::new(0u64));
+        let writer = {
+            let cell = cell.clone();
+            thread::spawn(move || {
+                for i in 1..=100_000 {
+                    cell.set(i);
+                }
+            })
+        };
+        let mut last = 0;
+        while last < 100_000 {
+            let value = cell.get();
+            assert!(value >= last, "{} after {}", value, last);
+            last = value;
+        }
+        writer.join().unwrap();
+    }
+
+    #[test]
+    fn param_mailbox_sets_by_name_and_gets_by_index() {
+        let params = ParamMailbox::new(vec![("gain", 1.0), ("cutoff", 1000.0)]);
+        assert_eq!(params.len(), 2);
+        assert_eq!(params.index("cutoff"), Some(1));
+        assert_eq!(params.index("resonance"), None);
+        assert_eq!(params.name(0), Some("gain"));
+        assert!(!params.take_changed());
+
+        params.set("cutoff", 440.0).unwrap();
+        assert_eq!(
+            params.set("resonance", 0.5),
+            Err(Error::UnknownParameter("resonance".to_string()))
+        );
+        assert!(params.take_changed());
+        assert!(!params.take_changed());
+        assert_eq!(params.cell(0).get_if_changed(), None);
+        assert_eq!(params.cell(1).get_if_changed(), Some(440.0));
+        assert_eq!(params.get(1), 440.0);
+
+        params.set_index(0, 0.5);
+        assert!(params.take_changed());
+        let values: Vec<_> = params.iter().map(|(n, c)| (n, c.get())).collect();
+        assert_eq!(values, [("gain", 0.5), ("cutoff", 440.0)]);
+    }
+
+    #[test]
+    fn param_mailbox_change_flag_is_seen_across_threads() {
+        let params = Arc::new(ParamMailbox::new(vec![("a", 0u32), ("b", 0)]));
+        let writer = {
+            let params = params.clone();
+            thread::spawn(move || {
+                for i in 1..=10_000 {
+                    params.set_index(i as usize % 2, i);
+                }
+            })
+        };
+        let mut seen = 0;
+        while seen < 10_000 {
+            if params.take_changed() {
+                seen = seen.max(params.get(0)).max(params.get(1));
+            }
+        }
+        writer.join().unwrap();
+    }
+}
@@ -0,0 +1,486 @@
+use std::collections::HashSet;
+use std::{fmt, str};
+
+use crate::{Client, Error, PortFlags};
+
+/// Selects ports by their full name, see `PatchProfile`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PortPattern {
+    /// The port with exactly this name.
+    Exact(String),
+    /// The ports whose names match this regular expression, as selected by `Client::port_names`.
+    /// The expression may match anywhere in the name unless it is anchored with `^` and `$`.
+    Regex(String),
+}
+
+impl PortPattern {
+    /// The full names of the ports with all of `flags` that match.
+    fn resolve(&self, client: &Client, flags: PortFlags) -> Vec<String> {
+        match self {
+            PortPattern::Exact(name) => client
+                .port_by_name(name)
+                .filter(|port| port.flags().contains(flags))
+                .map(|_| vec![name.clone()])
+                .unwrap_or_default(),
+            PortPattern::Regex(regex) => client
+                .port_names(Some(regex), None, flags)
+                .iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+/// Regular expressions are written between slashes, like `/^system:capture_.*/`, and exact names
+/// as they are.
+impl fmt::Display for PortPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PortPattern::Exact(name) => f.write_str(name),
+            PortPattern::Regex(regex) => write!(f, "/{}/", regex),
+        }
+    }
+}
+
+impl str::FromStr for PortPattern {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PortPattern, Error> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(Error::InvalidProfile("empty port pattern".to_string()));
+        }
+        match s.strip_prefix('/').and_then(|s| s.strip_suffix('/')) {
+            Some(regex) => Ok(PortPattern::Regex(regex.to_string())),
+            None => Ok(PortPattern::Exact(s.to_string())),
+        }
+    }
+}
+
+/// A routing preset, such as "rehearsal" or "show": connections that must exist and connections
+/// that must not.
+///
+/// Each rule pairs a pattern for source (output) ports with a pattern for destination (input)
+/// ports and covers every matching source with every matching destination. A pair that is both
+/// ensured and forbidden is ensured.
+///
+/// Profiles are stored as text with `to_string` and read back with `parse`. Each line is a rule,
+/// `ensure` or `forbid` followed by the source and destination patterns separated by `->`.
+/// Regular expressions are written between slashes. Empty lines and lines that start with `#` are
+/// ignored.
+///
+/// # Example
+/// ```
+/// let profile = jack::PatchProfile::parse(
+///     "# show\n\
+///      ensure system:capture_1 -> synth:in\n\
+///      forbid /^system:capture_/ -> /^recorder:/\n",
+/// )
+/// .unwrap();
+/// assert_eq!(profile.ensure.len(), 1);
+/// assert_eq!(
+///     profile.forbid[0].0,
+///     jack::PortPattern::Regex("^system:capture_".to_string())
+/// );
+/// assert_eq!(jack::PatchProfile::parse(&profile.to_string()), Ok(profile));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchProfile {
+    /// The connections to make, as source and destination patterns.
+    pub ensure: Vec<(PortPattern, PortPattern)>,
+    /// The connections to break, as source and destination patterns.
+    pub forbid: Vec<(PortPattern, PortPattern)>,
+}
+
+impl PatchProfile {
+    /// Read a profile from its text form, see `PatchProfile`.
+    ///
+    /// `Err(Error::InvalidProfile(message))` is returned for the first line that is not a rule.
+    pub fn parse(text: &str) -> Result<PatchProfile, Error> {
+        let mut profile = PatchProfile::default();
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |reason: &str| {
+                Error::InvalidProfile(format!("line {}: {}: {}", number + 1, reason, line))
+            };
+            let (kind, rule) = line
+                .split_once(char::is_whitespace)
+                .ok_or_else(|| invalid("expected a rule"))?;
+            let rules = match kind {
+                "ensure" => &mut profile.ensure,
+                "forbid" => &mut profile.forbid,
+                _ => return Err(invalid("expected ensure or forbid")),
+            };
+            let (source, destination) = rule
+                .split_once("->")
+                .ok_or_else(|| invalid("expected source -> destination"))?;
+            let source = source.parse().map_err(|_| invalid("missing source"))?;
+            let destination = destination
+                .parse()
+                .map_err(|_| invalid("missing destination"))?;
+            rules.push((source, destination));
+        }
+        Ok(profile)
+    }
+
+    /// The steps that `apply` would take on the current graph, without taking them. Steps that
+    /// would be taken have the outcome `PatchOutcome::Pending`.
+    pub fn diff(&self, client: &Client) -> PatchReport {
+        let ensured: Vec<_> = self
+            .ensure
+            .iter()
+            .map(|(source, destination)| {
+                let sources = source.resolve(client, PortFlags::IS_OUTPUT);
+                let destinations = destination.resolve(client, PortFlags::IS_INPUT);
+                (source, sources, destination, destinations)
+            })
+            .collect();
+        let is_ensured = |source: &str, destination: &str| {
+            ensured.iter().any(|(_, sources, _, destinations)| {
+                sources.iter().any(|s| s == source) && destinations.iter().any(|d| d == destination)
+            })
+        };
+        let mut steps = Vec::new();
+        let mut seen = HashSet::new();
+        for (source, destination) in &self.forbid {
+            let destinations: HashSet<String> = destination
+                .resolve(client, PortFlags::IS_INPUT)
+                .into_iter()
+                .collect();
+            for source in source.resolve(client, PortFlags::IS_OUTPUT) {
+                let connections = match client.port_by_name(&source).map(|p| p.connections()) {
+                    Some(Ok(connections)) => connections,
+                    _ => continue,
+                };
+                for destination in connections.iter() {
+                    if destinations.contains(destination)
+                        && !is_ensured(&source, destination)
+                        && seen.insert((
+                            PatchAction::Disconnect,
+                            source.clone(),
+                            destination.to_string(),
+                        ))
+                    {
+                        steps.push(PatchStep {
+                            action: PatchAction::Disconnect,
+                            source: source.clone(),
+                            destination: destination.to_string(),
+                            outcome: PatchOutcome::Pending,
+                        });
+                    }
+                }
+            }
+        }
+        for (source, sources, destination, destinations) in &ensured {
+            let unmatched = match (sources.is_empty(), destinations.is_empty()) {
+                (true, _) => Some(source),
+                (_, true) => Some(destination),
+                _ => None,
+            };
+            if let Some(pattern) = unmatched {
+                steps.push(PatchStep {
+                    action: PatchAction::Connect,
+                    source: source.to_string(),
+                    destination: destination.to_string(),
+                    outcome: PatchOutcome::Skipped(SkipReason::NoMatchingPort(pattern.to_string())),
+                });
+                continue;
+            }
+            for source in sources {
+                let port = client.port_by_name(source);
+                for destination in destinations {
+                    if !seen.insert((PatchAction::Connect, source.clone(), destination.clone())) {
+                        continue;
+                    }
+                    let connected = port
+                        .as_ref()
+                        .and_then(|p| p.is_connected_to(destination).ok())
+                        .unwrap_or(false);
+                    let outcome = match connected {
+                        true => PatchOutcome::Skipped(SkipReason::AlreadyConnected),
+                        false => PatchOutcome::Pending,
+                    };
+                    steps.push(PatchStep {
+                        action: PatchAction::Connect,
+                        source: source.clone(),
+                        destination: destination.clone(),
+                        outcome,
+                    });
+                }
+            }
+        }
+        PatchReport { steps }
+    }
+
+    /// Break the forbidden connections that exist, then make the ensured connections that are
+    /// missing. Every step is tried, even after a failure, and reported with its outcome.
+    pub fn apply(&self, client: &Client) -> PatchReport {
+        let mut report = self.diff(client);
+        for step in report.steps.iter_mut() {
+            if step.outcome != PatchOutcome::Pending {
+                continue;
+            }
+            let res = match step.action {
+                PatchAction::Connect => {
+                    client.connect_ports_by_name(&step.source, &step.destination)
+                }
+                PatchAction::Disconnect => {
+                    client.disconnect_ports_by_name(&step.source, &step.destination)
+                }
+            };
+            step.outcome = match res {
+                Ok(()) => PatchOutcome::Done,
+                // Something else made the connection in the meantime.
+                Err(Error::PortAlreadyConnected(..)) => {
+                    PatchOutcome::Skipped(SkipReason::AlreadyConnected)
+                }
+                Err(err) => PatchOutcome::Failed(err),
+            };
+        }
+        report
+    }
+}
+
+impl fmt::Display for PatchProfile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (source, destination) in &self.ensure {
+            writeln!(f, "ensure {} -> {}", source, destination)?;
+        }
+        for (source, destination) in &self.forbid {
+            writeln!(f, "forbid {} -> {}", source, destination)?;
+        }
+        Ok(())
+    }
+}
+
+impl str::FromStr for PatchProfile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PatchProfile, Error> {
+        PatchProfile::parse(s)
+    }
+}
+
+/// What a `PatchStep` does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum PatchAction {
+    Connect,
+    Disconnect,
+}
+
+/// Why a `PatchStep` was skipped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The ports of an ensured connection are connected already.
+    AlreadyConnected,
+    /// No port matches the pattern, so the rule covers no connection.
+    NoMatchingPort(String),
+}
+
+/// What came of a `PatchStep`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchOutcome {
+    /// The step would be taken, see `PatchProfile::diff`.
+    Pending,
+    /// The step was taken.
+    Done,
+    /// The step was not needed.
+    Skipped(SkipReason),
+    /// Taking the step failed.
+    Failed(Error),
+}
+
+/// A connection that a `PatchProfile` makes or breaks. For a rule that matched no ports, the
+/// source and destination are the patterns of the rule.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatchStep {
+    pub action: PatchAction,
+    pub source: String,
+    pub destination: String,
+    pub outcome: PatchOutcome,
+}
+
+/// The steps of `PatchProfile::apply` or `PatchProfile::diff`, with disconnections first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PatchReport {
+    pub steps: Vec<PatchStep>,
+}
+
+impl PatchReport {
+    /// Returns `true` if no step failed.
+    pub fn is_ok(&self) -> bool {
+        !self.failures().any(|_| true)
+    }
+
+    /// Returns `true` if nothing needs to be done, which is the case after a profile was applied
+    /// successfully.
+    pub fn is_satisfied(&self) -> bool {
+        self.steps
+            .iter()
+            .all(|step| matches!(step.outcome, PatchOutcome::Skipped(_)))
+    }
+
+    /// The steps that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &PatchStep> {
+        self.steps
+            .iter()
+            .filter(|step| matches!(step.outcome, PatchOutcome::Failed(_)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn patch_profile_parses_rules() {
+        let profile = PatchProfile::parse(
+            "\n\
+             # rehearsal\n\
+             ensure system:capture_1 -> synth:in\n  \
+             forbid  /^system:.*/   ->   /rec:in_[12]/  \n",
+        )
+        .unwrap();
+        assert_eq!(
+            profile,
+            PatchProfile {
+                ensure: vec![(
+                    PortPattern::Exact("system:capture_1".to_string()),
+                    PortPattern::Exact("synth:in".to_string())
+                )],
+                forbid: vec![(
+                    PortPattern::Regex("^system:.*".to_string()),
+                    PortPattern::Regex("rec:in_[12]".to_string())
+                )],
+            }
+        );
+        assert_eq!(
+            profile.to_string(),
+            "ensure system:capture_1 -> synth:in\nforbid /^system:.*/ -> /rec:in_[12]/\n"
+        );
+        assert_eq!(profile.to_string().parse(), Ok(profile));
+    }
+
+    #[test]
+    fn patch_profile_rejects_invalid_lines() {
+        for (text, reason) in [
+            ("connect a -> b", "line 1: expected ensure or forbid"),
+            ("ensure", "line 1: expected a rule"),
+            ("# ok\nensure a b", "line 2: expected source -> destination"),
+            ("forbid -> b", "line 1: missing source"),
+            ("forbid a ->", "line 1: missing destination"),
+        ] {
+            match PatchProfile::parse(text) {
+                Err(Error::InvalidProfile(message)) => {
+                    assert!(message.starts_with(reason), "{}", message)
+                }
+                res => panic!("{:?} parsed as {:?}", text, res),
+            }
+        }
+    }
+
+    #[test]
+    fn patch_report_summarizes_outcomes() {
+        let step = |outcome| PatchStep {
+            action: PatchAction::Connect,
+            source: "a:out".to_string(),
+            destination: "b:in".to_string(),
+            outcome,
+        };
+        let mut report = PatchReport {
+            steps: vec![step(PatchOutcome::Skipped(SkipReason::AlreadyConnected))],
+        };
+        assert!(report.is_ok());
+        assert!(report.is_satisfied());
+        report.steps.push(step(PatchOutcome::Pending));
+        assert!(report.is_ok());
+        assert!(!report.is_satisfied());
+        report
+            .steps
+            .push(step(PatchOutcome::Failed(Error::UnknownError)));
+        assert!(!report.is_ok());
+        assert_eq!(report.failures().count(), 1);
+    }
+
+    #[test]
+    fn patch_profile_applies_to_dummy_server_graph() {
+        use crate::test_support::start_dummy_server;
+        use crate::{AudioIn, AudioOut, ClientOptions};
+
+        let server = match start_dummy_server() {
+            Some(server) => server,
+            None => return,
+        };
+        let (client, _) = server.open_client("pb", ClientOptions::empty()).unwrap();
+        for i in 1..=2 {
+            client
+                .register_port(&format!("out_{}", i), AudioOut::default())
+                .unwrap();
+            client
+                .register_port(&format!("in_{}", i), AudioIn::default())
+                .unwrap();
+        }
+        let active = client.activate_async((), ()).unwrap();
+        let client = active.as_client();
+        client.connect_ports_by_name("pb:out_1", "pb:in_2").unwrap();
+        client.connect_ports_by_name("pb:out_2", "pb:in_2").unwrap();
+
+        let profile = PatchProfile::parse(
+            "ensure pb:out_1 -> pb:in_1\n\
+             ensure /^pb:out_2$/ -> pb:in_2\n\
+             ensure pb:out_1 -> pb:no_such_port\n\
+             forbid /^pb:out_/ -> pb:in_2\n",
+        )
+        .unwrap();
+        let step = |action, source: &str, destination: &str, outcome| PatchStep {
+            action,
+            source: source.to_string(),
+            destination: destination.to_string(),
+            outcome,
+        };
+        let expected = |outcome: PatchOutcome| {
+            vec![
+                step(
+                    PatchAction::Disconnect,
+                    "pb:out_1",
+                    "pb:in_2",
+                    outcome.clone(),
+                ),
+                step(PatchAction::Connect, "pb:out_1", "pb:in_1", outcome),
+                step(
+                    PatchAction::Connect,
+                    "pb:out_2",
+                    "pb:in_2",
+                    PatchOutcome::Skipped(SkipReason::AlreadyConnected),
+                ),
+                step(
+                    PatchAction::Connect,
+                    "pb:out_1",
+                    "pb:no_such_port",
+                    PatchOutcome::Skipped(SkipReason::NoMatchingPort(
+                        "pb:no_such_port".to_string(),
+                    )),
+                ),
+            ]
+        };
+        assert_eq!(profile.diff(client).steps, expected(PatchOutcome::Pending));
+        // Diffing does not change the graph.
+        assert_eq!(profile.diff(client).steps, expected(PatchOutcome::Pending));
+
+        let report = profile.apply(client);
+        assert_eq!(report.steps, expected(PatchOutcome::Done));
+        assert!(report.is_ok());
+
+        let connections = |name: &str| {
+            let port = client.port_by_name(name).unwrap();
+            let names = port.connections().unwrap();
+            names.iter().map(String::from).collect::<Vec<_>>()
+        };
+        assert_eq!(connections("pb:out_1"), ["pb:in_1"]);
+        assert_eq!(connections("pb:out_2"), ["pb:in_2"]);
+        assert!(profile.diff(client).is_satisfied());
+    }
+}
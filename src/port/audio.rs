@@ -71,12 +71,7 @@ impl Port<AudioIn> {
     /// Read the received audio data.
     pub fn as_slice<'a>(&'a self, ps: &'a ProcessScope) -> &'a [f32] {
         assert_eq!(self.client_ptr(), ps.client_ptr());
-        unsafe {
-            slice::from_raw_parts(
-                self.buffer(ps.n_frames()) as *const f32,
-                ps.n_frames() as usize,
-            )
-        }
+        unsafe { slice::from_raw_parts(ps.port_buffer(self) as *const f32, ps.n_frames() as usize) }
     }
 }
 
@@ -85,10 +80,7 @@ impl Port<AudioOut> {
     pub fn as_mut_slice<'a>(&'a mut self, ps: &'a ProcessScope) -> &'a mut [f32] {
         assert_eq!(self.client_ptr(), ps.client_ptr());
         unsafe {
-            slice::from_raw_parts_mut(
-                self.buffer(ps.n_frames()) as *mut f32,
-                ps.n_frames() as usize,
-            )
+            slice::from_raw_parts_mut(ps.port_buffer(self) as *mut f32, ps.n_frames() as usize)
         }
     }
 }
@@ -148,4 +140,65 @@ mod test {
         );
         ac.deactivate().unwrap();
     }
+
+    #[test]
+    fn port_audio_can_read_from_port_registered_while_active() {
+        let c = open_test_client("port_audio_crfprwa");
+        let mut out = c.register_port("o", AudioOut::default()).unwrap();
+        let (port_tx, port_rx) = bounded::<Port<AudioIn>>(1);
+        let (signal_succeed, did_succeed) = bounded(1_000);
+        let mut in_port = None;
+        let process_callback = move |_: &Client, ps: &ProcessScope| -> Control {
+            let exp = 0.312_443;
+            for v in out.as_mut_slice(ps).iter_mut() {
+                *v = exp;
+            }
+            if in_port.is_none() {
+                in_port = port_rx.try_recv().ok();
+            }
+            if let Some(in_port) = in_port.as_ref() {
+                if in_port.as_slice(ps).iter().all(|v| (*v - exp).abs() < 1E-5) {
+                    let _ = signal_succeed.try_send(true);
+                }
+            }
+            Control::Continue
+        };
+        let ac = c
+            .activate_async((), ClosureProcessHandler::new(process_callback))
+            .unwrap();
+        let in_port = ac
+            .as_client()
+            .register_port("i", AudioIn::default())
+            .unwrap();
+        port_tx.send(in_port).unwrap();
+        ac.as_client()
+            .connect_ports_by_name("port_audio_crfprwa:o", "port_audio_crfprwa:i")
+            .unwrap();
+        assert!(
+            did_succeed.iter().any(|b| b),
+            "input port does not have expected data"
+        );
+        ac.deactivate().unwrap();
+    }
+
+    #[test]
+    fn port_audio_accessors_do_not_allocate() {
+        let c = open_test_client("port_audio_adna");
+        let in_p = c.register_port("i", AudioIn::default()).unwrap();
+        let mut out_p = c.register_port("o", AudioOut::default()).unwrap();
+        let (allocations_tx, allocations_rx) = bounded(1_000);
+        let process_callback = move |_: &Client, ps: &ProcessScope| -> Control {
+            let before = crate::test_alloc::allocations();
+            let peak = in_p.as_slice(ps).iter().fold(0.0f32, |m, v| m.max(v.abs()));
+            out_p.as_mut_slice(ps).fill(peak);
+            let _ = allocations_tx.try_send(crate::test_alloc::allocations() - before);
+            Control::Continue
+        };
+        let ac = c
+            .activate_async((), ClosureProcessHandler::new(process_callback))
+            .unwrap();
+        let allocations: Vec<usize> = allocations_rx.iter().take(10).collect();
+        assert_eq!(allocations, [0; 10]);
+        ac.deactivate().unwrap();
+    }
 }
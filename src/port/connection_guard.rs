@@ -0,0 +1,71 @@
+use jack_sys as j;
+use std::ffi::CString;
+use std::fmt;
+
+use crate::WeakClient;
+
+/// A connection between two ports that is removed when the guard is dropped, see
+/// `Client::connect_guarded`. Useful for tests and temporary taps, such as a monitor that listens
+/// to a port for a while.
+///
+/// Failing to disconnect when dropped is ignored, for example if one of the ports or the client is
+/// already gone. The guard may be sent to and dropped on another thread.
+#[must_use = "the ports are disconnected again when the ConnectionGuard is dropped"]
+pub struct ConnectionGuard {
+    client: WeakClient,
+    source_port: CString,
+    destination_port: CString,
+    connected: bool,
+}
+
+impl ConnectionGuard {
+    pub(crate) fn new(
+        client: WeakClient,
+        source_port: CString,
+        destination_port: CString,
+    ) -> ConnectionGuard {
+        ConnectionGuard {
+            client,
+            source_port,
+            destination_port,
+            connected: true,
+        }
+    }
+
+    /// The full name of the source port.
+    pub fn source_port(&self) -> &str {
+        self.source_port.to_str().unwrap_or_default()
+    }
+
+    /// The full name of the destination port.
+    pub fn destination_port(&self) -> &str {
+        self.destination_port.to_str().unwrap_or_default()
+    }
+
+    /// Keep the connection instead of removing it.
+    pub fn forget(mut self) {
+        self.connected = false;
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if !self.connected {
+            return;
+        }
+        let (source_port, destination_port) = (&self.source_port, &self.destination_port);
+        let _ = self.client.query(|c| unsafe {
+            j::jack_disconnect(c, source_port.as_ptr(), destination_port.as_ptr())
+        });
+    }
+}
+
+impl fmt::Debug for ConnectionGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("ConnectionGuard")
+            .field("source_port", &self.source_port())
+            .field("destination_port", &self.destination_port())
+            .field("connected", &self.connected)
+            .finish()
+    }
+}
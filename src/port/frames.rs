@@ -0,0 +1,159 @@
+use std::marker::PhantomData;
+
+/// Iterate over the frames of several audio buffers at once, for example the buffers of the audio
+/// ports of a process cycle. Each item holds the input samples of a frame, and references to the
+/// output samples of the frame.
+///
+/// The lengths are checked once up front, so per-frame processing needs no indexing and no bounds
+/// checks. Buffers of the same `ProcessScope` always have the same length.
+///
+/// Panics if the buffers don't all have the same length.
+///
+/// # Example
+/// ```
+/// fn process(
+///     in_l: &jack::Port<jack::AudioIn>,
+///     in_r: &jack::Port<jack::AudioIn>,
+///     out_mid: &mut jack::Port<jack::AudioOut>,
+///     out_side: &mut jack::Port<jack::AudioOut>,
+///     ps: &jack::ProcessScope,
+/// ) {
+///     let inputs = [in_l.as_slice(ps), in_r.as_slice(ps)];
+///     let outputs = [out_mid.as_mut_slice(ps), out_side.as_mut_slice(ps)];
+///     for ([l, r], [mid, side]) in jack::zip_frames(inputs, outputs) {
+///         *mid = (l + r) * 0.5;
+///         *side = (l - r) * 0.5;
+///     }
+/// }
+/// ```
+pub fn zip_frames<'a, const I: usize, const O: usize>(
+    inputs: [&'a [f32]; I],
+    outputs: [&'a mut [f32]; O],
+) -> ZipFrames<'a, I, O> {
+    let len = match (inputs.first(), outputs.first()) {
+        (Some(input), _) => input.len(),
+        (None, Some(output)) => output.len(),
+        (None, None) => 0,
+    };
+    let lens = inputs.iter().map(|i| i.len());
+    if let Some(other) = lens
+        .chain(outputs.iter().map(|o| o.len()))
+        .find(|l| *l != len)
+    {
+        panic!(
+            "zip_frames got buffers of {} and {} frames, they must have the same length",
+            len, other
+        );
+    }
+    ZipFrames {
+        inputs: inputs.map(<[f32]>::as_ptr),
+        outputs: outputs.map(<[f32]>::as_mut_ptr),
+        index: 0,
+        len,
+        _phantom: PhantomData,
+    }
+}
+
+/// Iterates over the frames of audio buffers, see `zip_frames`.
+#[derive(Debug)]
+pub struct ZipFrames<'a, const I: usize, const O: usize> {
+    inputs: [*const f32; I],
+    outputs: [*mut f32; O],
+    index: usize,
+    len: usize,
+    _phantom: PhantomData<(&'a [f32], &'a mut [f32])>,
+}
+
+unsafe impl<'a, const I: usize, const O: usize> Send for ZipFrames<'a, I, O> {}
+
+impl<'a, const I: usize, const O: usize> Iterator for ZipFrames<'a, I, O> {
+    type Item = ([f32; I], [&'a mut f32; O]);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index == self.len {
+            return None;
+        }
+        let index = self.index;
+        self.index += 1;
+        // All buffers have `len` frames, and each output frame is only handed out once.
+        unsafe {
+            Some((
+                self.inputs.map(|input| *input.add(index)),
+                self.outputs.map(|output| &mut *output.add(index)),
+            ))
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.len - self.index;
+        (len, Some(len))
+    }
+}
+
+impl<'a, const I: usize, const O: usize> ExactSizeIterator for ZipFrames<'a, I, O> {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zip_frames_pairs_frames_of_buffers() {
+        let a = [1.0, 2.0, 3.0];
+        let b = [10.0, 20.0, 30.0];
+        let mut sum = [0.0; 3];
+        let mut diff = [0.0; 3];
+        let frames = zip_frames([&a[..], &b[..]], [&mut sum[..], &mut diff[..]]);
+        assert_eq!(frames.len(), 3);
+        for ([a, b], [sum, diff]) in frames {
+            *sum = a + b;
+            *diff = b - a;
+        }
+        assert_eq!(sum, [11.0, 22.0, 33.0]);
+        assert_eq!(diff, [9.0, 18.0, 27.0]);
+    }
+
+    #[test]
+    fn zip_frames_allows_only_inputs_or_outputs() {
+        let a = [1.0, 2.0];
+        let frames: Vec<_> = zip_frames([&a[..]], []).map(|([a], [])| a).collect();
+        assert_eq!(frames, [1.0, 2.0]);
+        let mut out = [0.0; 2];
+        for ([], [o]) in zip_frames([], [&mut out[..]]) {
+            *o = 0.5;
+        }
+        assert_eq!(out, [0.5, 0.5]);
+        assert_eq!(zip_frames::<0, 0>([], []).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffers of 3 and 2 frames")]
+    fn zip_frames_rejects_mismatched_lengths() {
+        let a = [0.0; 3];
+        let mut out = [0.0; 2];
+        zip_frames([&a[..]], [&mut out[..]]);
+    }
+
+    fn mix_indexed(a: &[f32], b: &[f32], out: &mut [f32]) {
+        for i in 0..out.len() {
+            out[i] = a[i] * 0.5 + b[i] * 0.25;
+        }
+    }
+
+    fn mix_zipped(a: &[f32], b: &[f32], out: &mut [f32]) {
+        for ([a, b], [o]) in zip_frames([a, b], [out]) {
+            *o = a * 0.5 + b * 0.25;
+        }
+    }
+
+    #[test]
+    fn zip_frames_matches_indexing() {
+        let a: Vec<f32> = (0..100).map(|i| i as f32).collect();
+        let b: Vec<f32> = a.iter().map(|x| x * 3.0).collect();
+        let (mut indexed, mut zipped) = (vec![0.0; 100], vec![0.0; 100]);
+        mix_indexed(&a, &b, &mut indexed);
+        mix_zipped(&a, &b, &mut zipped);
+        assert_eq!(indexed, zipped);
+    }
+}
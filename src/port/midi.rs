@@ -2,6 +2,7 @@ use jack_sys as j;
 use std::marker::PhantomData;
 use std::{mem, slice};
 
+use crate::test_support::MockMidiBuffer;
 use crate::{Error, Frames, Port, PortFlags, PortSpec, ProcessScope};
 
 /// Contains 8bit raw midi information along with a timestamp relative to the
@@ -54,23 +55,59 @@ impl Port<MidiIn> {
     /// Get an iterator over midi events.
     pub fn iter<'a>(&'a self, ps: &'a ProcessScope) -> MidiIter<'a> {
         assert_eq!(self.client_ptr(), ps.client_ptr());
+        let buffer = match ps.mock_midi_buffer(self) {
+            Some(buffer) => MidiBuffer::Mock(buffer),
+            None => MidiBuffer::Jack(unsafe { self.buffer(ps.n_frames()) }),
+        };
         MidiIter {
-            buffer: unsafe { self.buffer(ps.n_frames()) },
+            buffer,
             index: 0,
             _phantom: PhantomData,
         }
     }
 }
 
+/// The buffer of a MIDI port, from JACK or from a `MockProcessScope`.
+#[derive(Debug, Clone, Copy)]
+enum MidiBuffer {
+    Jack(*mut ::libc::c_void),
+    Mock(*mut MockMidiBuffer),
+}
+
 /// Iterate through Midi Messages within a `Port<MidiIn>`.
 #[derive(Debug, Clone)]
 pub struct MidiIter<'a> {
-    buffer: *mut ::libc::c_void,
+    buffer: MidiBuffer,
     index: usize,
     _phantom: PhantomData<&'a ()>,
 }
 
 impl<'a> MidiIter<'a> {
+    /// Iterate over the events of a JACK MIDI buffer.
+    ///
+    /// # Safety
+    /// `buffer` must be a JACK MIDI buffer that stays valid for `'a`.
+    #[cfg_attr(not(feature = "net"), allow(dead_code))]
+    pub(crate) unsafe fn from_buffer(buffer: *mut ::libc::c_void) -> MidiIter<'a> {
+        MidiIter {
+            buffer: MidiBuffer::Jack(buffer),
+            index: 0,
+            _phantom: PhantomData,
+        }
+    }
+
+    /// Iterate over the events of a `MockProcessScope` buffer.
+    ///
+    /// # Safety
+    /// `buffer` must stay valid and unchanged for `'a`.
+    pub(crate) unsafe fn from_mock(buffer: *mut MockMidiBuffer) -> MidiIter<'a> {
+        MidiIter {
+            buffer: MidiBuffer::Mock(buffer),
+            index: 0,
+            _phantom: PhantomData,
+        }
+    }
+
     /// Return the next element without advancing the iterator.
     pub fn peek(&self) -> Option<RawMidi<'a>> {
         self.absolute_nth(self.index as u32)
@@ -89,8 +126,12 @@ impl<'a> MidiIter<'a> {
     }
 
     fn absolute_nth(&self, n: u32) -> Option<RawMidi<'a>> {
+        let buffer = match self.buffer {
+            MidiBuffer::Jack(buffer) => buffer,
+            MidiBuffer::Mock(buffer) => return unsafe { &*buffer }.get(n as usize),
+        };
         let mut ev = mem::MaybeUninit::<j::jack_midi_event_t>::uninit();
-        let res = unsafe { j::jack_midi_event_get(ev.as_mut_ptr(), self.buffer, n) };
+        let res = unsafe { j::jack_midi_event_get(ev.as_mut_ptr(), buffer, n) };
         if res != 0 {
             return None;
         }
@@ -101,10 +142,10 @@ impl<'a> MidiIter<'a> {
     }
 
     fn absolute_len(&self) -> usize {
-        if self.buffer.is_null() {
-            0
-        } else {
-            unsafe { j::jack_midi_get_event_count(self.buffer) as usize }
+        match self.buffer {
+            MidiBuffer::Jack(buffer) if buffer.is_null() => 0,
+            MidiBuffer::Jack(buffer) => unsafe { j::jack_midi_get_event_count(buffer) as usize },
+            MidiBuffer::Mock(buffer) => unsafe { (*buffer).len() },
         }
     }
 }
@@ -162,35 +203,53 @@ impl Port<MidiOut> {
     /// clears the midi buffer.
     pub fn writer<'a>(&'a mut self, ps: &'a ProcessScope) -> MidiWriter<'a> {
         assert_eq!(self.client_ptr(), ps.client_ptr());
-        let buffer = unsafe { self.buffer(ps.n_frames()) };
-        unsafe { j::jack_midi_clear_buffer(buffer) };
-        MidiWriter {
-            buffer,
-            _phantom: PhantomData,
+        if let Some(buffer) = ps.mock_midi_buffer(self) {
+            unsafe { (*buffer).clear() };
+            return MidiWriter {
+                buffer: MidiBuffer::Mock(buffer),
+                _phantom: PhantomData,
+            };
         }
+        unsafe { MidiWriter::from_buffer(self.buffer(ps.n_frames())) }
     }
 }
 
 /// Write midi events to an output midi port.
 #[derive(Debug)]
 pub struct MidiWriter<'a> {
-    buffer: *mut ::libc::c_void,
+    buffer: MidiBuffer,
     _phantom: PhantomData<&'a ()>,
 }
 
 impl<'a> MidiWriter<'a> {
+    /// Clear a JACK MIDI buffer and create a writer for it.
+    ///
+    /// # Safety
+    /// `buffer` must be a JACK MIDI buffer that stays valid for `'a`.
+    pub(crate) unsafe fn from_buffer(buffer: *mut ::libc::c_void) -> MidiWriter<'a> {
+        j::jack_midi_clear_buffer(buffer);
+        MidiWriter {
+            buffer: MidiBuffer::Jack(buffer),
+            _phantom: PhantomData,
+        }
+    }
+
     /// Write an event into an event port buffer.
     ///
     /// Clients must write normalised MIDI data to the port - no running status and no (1-byte)
     /// realtime messages interspersed with other messagse (realtime messages are fine when they
     /// occur on their own, like other messages).
     pub fn write(&mut self, message: &RawMidi) -> Result<(), Error> {
+        let buffer = match self.buffer {
+            MidiBuffer::Jack(buffer) => buffer,
+            MidiBuffer::Mock(buffer) => return unsafe { (*buffer).write(message) },
+        };
         let ev = j::jack_midi_event_t {
             time: message.time,
             size: message.bytes.len(),
             buffer: message.bytes.as_ptr() as *mut u8,
         };
-        let res = unsafe { j::jack_midi_event_write(self.buffer, ev.time, ev.buffer, ev.size) };
+        let res = unsafe { j::jack_midi_event_write(buffer, ev.time, ev.buffer, ev.size) };
         match res {
             0 => Ok(()),
             _ => Err(Error::NotEnoughSpace),
@@ -202,8 +261,12 @@ impl<'a> MidiWriter<'a> {
     /// If the return value is greater than 0, than the buffer is full.  Currently, the only way
     /// this can happen is if events are lost on port mixdown.
     pub fn lost_count(&self) -> usize {
-        let n = unsafe { j::jack_midi_get_lost_event_count(self.buffer) };
-        n as usize
+        match self.buffer {
+            MidiBuffer::Jack(buffer) => unsafe {
+                j::jack_midi_get_lost_event_count(buffer) as usize
+            },
+            MidiBuffer::Mock(buffer) => unsafe { (*buffer).lost_count() },
+        }
     }
 
     /// Get the size of the largest event that can be stored by the port.
@@ -211,8 +274,10 @@ impl<'a> MidiWriter<'a> {
     /// This function returns the current space available, taking into account events already stored
     /// in the port.
     pub fn max_event_size(&self) -> usize {
-        let n = unsafe { j::jack_midi_max_event_size(self.buffer) };
-        n as usize
+        match self.buffer {
+            MidiBuffer::Jack(buffer) => unsafe { j::jack_midi_max_event_size(buffer) },
+            MidiBuffer::Mock(buffer) => unsafe { (*buffer).max_event_size() },
+        }
     }
 }
 
@@ -530,4 +595,35 @@ mod test {
         let got: &[OwnedRawMidi] = &processor.collected;
         assert_eq!(expected, got);
     }
+
+    #[test]
+    fn port_midi_iter_and_writer_do_not_allocate() {
+        let c = open_test_client("port_midi_iwdna");
+        let in_p = c.register_port("i", MidiIn::default()).unwrap();
+        let mut out_p = c.register_port("o", MidiOut::default()).unwrap();
+        let (allocations_tx, allocations_rx) = bounded(1_000);
+        let process_callback = move |_: &Client, ps: &ProcessScope| -> Control {
+            let before = crate::test_alloc::allocations();
+            let mut writer = out_p.writer(ps);
+            writer
+                .write(&RawMidi {
+                    time: 0,
+                    bytes: &[0b1001_0000, 0b0100_0000, 0b0111_1111],
+                })
+                .unwrap();
+            let notes = in_p.iter(ps).filter(|m| m.bytes.len() == 3).count();
+            let _ = allocations_tx.try_send((notes, crate::test_alloc::allocations() - before));
+            Control::Continue
+        };
+        let ac = c
+            .activate_async((), ClosureProcessHandler::new(process_callback))
+            .unwrap();
+        ac.as_client()
+            .connect_ports_by_name("port_midi_iwdna:o", "port_midi_iwdna:i")
+            .unwrap();
+        let cycles: Vec<(usize, usize)> = allocations_rx.iter().take(20).collect();
+        assert!(cycles.iter().all(|(_, allocations)| *allocations == 0));
+        assert!(cycles.iter().any(|(notes, _)| *notes == 1));
+        ac.deactivate().unwrap();
+    }
 }
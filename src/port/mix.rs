@@ -0,0 +1,183 @@
+/// Mixing and gain operations on audio buffers, such as the slices of `Port::as_mut_slice`.
+///
+/// The operations don't allocate and are written so that the compiler vectorizes them. Buffers of
+/// different lengths are processed up to the length of the shortest one, and the number of
+/// frames that were processed is returned. Buffers of the same `ProcessScope` always have the
+/// same length.
+///
+/// # Example
+/// ```
+/// use jack::MixBuffer;
+///
+/// fn mix_to_stereo(
+///     inputs: &[(&[f32], f32)],
+///     out_l: &mut [f32],
+///     out_r: &mut [f32],
+///     pan: f32,
+/// ) {
+///     out_l.fill(0.0);
+///     out_r.fill(0.0);
+///     for (input, gain) in inputs {
+///         out_l.mix_from(input, gain * (1.0 - pan));
+///         out_r.mix_from(input, gain * pan);
+///     }
+/// }
+///
+/// let mut out_l = [0.0; 4];
+/// let mut out_r = [0.0; 4];
+/// mix_to_stereo(&[(&[1.0; 4], 0.5), (&[0.5; 4], 1.0)], &mut out_l, &mut out_r, 0.25);
+/// assert_eq!(out_l, [0.75; 4]);
+/// assert_eq!(out_r, [0.25; 4]);
+/// ```
+pub trait MixBuffer {
+    /// Add `src` to the buffer.
+    fn add_from(&mut self, src: &[f32]) -> usize;
+
+    /// Add `src`, multiplied by `gain`, to the buffer.
+    fn mix_from(&mut self, src: &[f32], gain: f32) -> usize;
+
+    /// Add `a` multiplied by `gain_a` and `b` multiplied by `gain_b` to the buffer, in a single
+    /// pass. Useful for crossfades and for panning a stereo pair.
+    fn add_scaled_pair(&mut self, a: &[f32], gain_a: f32, b: &[f32], gain_b: f32) -> usize;
+
+    /// Multiply the buffer by `gain`.
+    fn scale(&mut self, gain: f32);
+
+    /// Replace the buffer with `src` multiplied by `gain`.
+    fn copy_scaled(&mut self, src: &[f32], gain: f32) -> usize;
+}
+
+impl MixBuffer for [f32] {
+    #[inline]
+    fn add_from(&mut self, src: &[f32]) -> usize {
+        let n = self.len().min(src.len());
+        // Slicing both to `n` up front removes the bounds checks from the loop.
+        for (d, s) in self[..n].iter_mut().zip(&src[..n]) {
+            *d += s;
+        }
+        n
+    }
+
+    #[inline]
+    fn mix_from(&mut self, src: &[f32], gain: f32) -> usize {
+        let n = self.len().min(src.len());
+        for (d, s) in self[..n].iter_mut().zip(&src[..n]) {
+            *d += s * gain;
+        }
+        n
+    }
+
+    #[inline]
+    fn add_scaled_pair(&mut self, a: &[f32], gain_a: f32, b: &[f32], gain_b: f32) -> usize {
+        let n = self.len().min(a.len()).min(b.len());
+        for ((d, a), b) in self[..n].iter_mut().zip(&a[..n]).zip(&b[..n]) {
+            *d += a * gain_a + b * gain_b;
+        }
+        n
+    }
+
+    #[inline]
+    fn scale(&mut self, gain: f32) {
+        for d in self.iter_mut() {
+            *d *= gain;
+        }
+    }
+
+    #[inline]
+    fn copy_scaled(&mut self, src: &[f32], gain: f32) -> usize {
+        let n = self.len().min(src.len());
+        for (d, s) in self[..n].iter_mut().zip(&src[..n]) {
+            *d = s * gain;
+        }
+        n
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Pseudo random samples in `[-1, 1)`.
+    fn noise(len: usize, seed: u32) -> Vec<f32> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (state >> 8) as f32 / (1 << 23) as f32 - 1.0
+            })
+            .collect()
+    }
+
+    // Lengths around the vector widths, and buffers of different lengths.
+    const LENGTHS: [(usize, usize); 7] = [
+        (0, 0),
+        (1, 1),
+        (7, 7),
+        (64, 64),
+        (1027, 1027),
+        (16, 9),
+        (9, 16),
+    ];
+
+    #[test]
+    fn mix_buffer_adds_like_reference() {
+        for (dst_len, src_len) in LENGTHS {
+            let src = noise(src_len, 1);
+            let mut dst = noise(dst_len, 2);
+            let mut expected = dst.clone();
+            for i in 0..dst_len.min(src_len) {
+                expected[i] += src[i];
+            }
+            assert_eq!(dst.add_from(&src), dst_len.min(src_len));
+            assert_eq!(dst, expected);
+        }
+    }
+
+    #[test]
+    fn mix_buffer_mixes_like_reference() {
+        for (dst_len, src_len) in LENGTHS {
+            let src = noise(src_len, 3);
+            let mut dst = noise(dst_len, 4);
+            let mut expected = dst.clone();
+            for i in 0..dst_len.min(src_len) {
+                expected[i] += src[i] * 0.3;
+            }
+            assert_eq!(dst.mix_from(&src, 0.3), dst_len.min(src_len));
+            assert_eq!(dst, expected);
+
+            let mut copied = noise(dst_len, 4);
+            let mut expected = copied.clone();
+            for i in 0..dst_len.min(src_len) {
+                expected[i] = src[i] * -2.0;
+            }
+            assert_eq!(copied.copy_scaled(&src, -2.0), dst_len.min(src_len));
+            assert_eq!(copied, expected);
+        }
+    }
+
+    #[test]
+    fn mix_buffer_adds_scaled_pairs_like_reference() {
+        for (dst_len, src_len) in LENGTHS {
+            let a = noise(src_len, 5);
+            let b = noise(src_len + 3, 6);
+            let mut dst = noise(dst_len, 7);
+            let mut expected = dst.clone();
+            let n = dst_len.min(src_len);
+            for i in 0..n {
+                expected[i] += a[i] * 0.25 + b[i] * 0.75;
+            }
+            assert_eq!(dst.add_scaled_pair(&a, 0.25, &b, 0.75), n);
+            assert_eq!(dst, expected);
+        }
+    }
+
+    #[test]
+    fn mix_buffer_scales_like_reference() {
+        for (len, _) in LENGTHS {
+            let mut dst = noise(len, 8);
+            let expected: Vec<f32> = dst.iter().map(|s| s * 0.5).collect();
+            dst.scale(0.5);
+            assert_eq!(dst, expected);
+        }
+    }
+}
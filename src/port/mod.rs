@@ -1,14 +1,24 @@
 mod audio;
+mod connection_guard;
+mod frames;
 mod midi;
+mod mix;
+mod owned_port;
 mod port_impl;
+mod port_names;
 
 /// Contains flag constants that may be used to create `PortFlags`.
 mod port_flags;
 
 pub use self::audio::{AudioIn, AudioOut};
+pub use self::connection_guard::ConnectionGuard;
+pub use self::frames::{zip_frames, ZipFrames};
 pub use self::midi::{MidiIn, MidiIter, MidiOut, MidiWriter, RawMidi};
+pub use self::mix::MixBuffer;
+pub use self::owned_port::OwnedPort;
 pub use self::port_flags::PortFlags;
 pub use self::port_impl::{Port, PortSpec, Unowned, PORT_NAME_SIZE, PORT_TYPE_SIZE};
+pub use self::port_names::{ConnectionsBuf, PortNames, PortNamesIter};
 
 #[cfg(test)]
 mod test_client;
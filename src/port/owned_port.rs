@@ -0,0 +1,79 @@
+use jack_sys as j;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+
+use crate::logging::log_event;
+use crate::{Port, PortSpec};
+
+/// A port that is unregistered when it is dropped, see `Client::register_port_owned`. It
+/// dereferences to the `Port`, so it is used like one.
+///
+/// A `Port` is a handle that may be copied with `Port::clone_unowned`, so it leaves the port
+/// registered when dropped. `OwnedPort` is the only handle to unregister the port with, which
+/// suits ports that belong to objects that come and go with a long lived client, such as plugins.
+///
+/// If the client is already closed when the `OwnedPort` is dropped, for example because it is held
+/// by the process handler of an `AsyncClient` that is being dropped, nothing is done since JACK
+/// removes the ports of a client when it is closed. Unregistering is not real-time safe, so an
+/// `OwnedPort` should not be dropped in the process callback.
+pub struct OwnedPort<PS> {
+    port: Option<Port<PS>>,
+}
+
+impl<PS> OwnedPort<PS> {
+    pub(crate) fn new(port: Port<PS>) -> OwnedPort<PS> {
+        OwnedPort { port: Some(port) }
+    }
+
+    /// Keep the port registered, and return it as a plain `Port`.
+    pub fn into_port(mut self) -> Port<PS> {
+        self.port.take().unwrap()
+    }
+}
+
+impl<PS> Deref for OwnedPort<PS> {
+    type Target = Port<PS>;
+
+    fn deref(&self) -> &Port<PS> {
+        self.port.as_ref().unwrap()
+    }
+}
+
+impl<PS> DerefMut for OwnedPort<PS> {
+    fn deref_mut(&mut self) -> &mut Port<PS> {
+        self.port.as_mut().unwrap()
+    }
+}
+
+impl<PS> Drop for OwnedPort<PS> {
+    fn drop(&mut self) {
+        let port = match self.port.take() {
+            Some(port) => port,
+            None => return,
+        };
+        let name = port.name().unwrap_or_default();
+        // Keeps the client open while the port is unregistered.
+        let res = port.weak_client().query(|client| unsafe {
+            // JACK may keep the properties of a port after it is gone.
+            #[cfg(feature = "metadata")]
+            if crate::metadata::is_available() {
+                j::jack_remove_properties(client, j::jack_port_uuid(port.raw()));
+            }
+            j::jack_port_unregister(client, port.raw())
+        });
+        match res {
+            Ok(0) => log_event!(debug, "unregistered port {}", name),
+            Ok(code) => log_event!(warn, "failed to unregister port {}: {}", name, code),
+            Err(_) => log_event!(
+                debug,
+                "the client of an owned port was closed before the port"
+            ),
+        }
+    }
+}
+
+impl<PS: PortSpec> fmt::Debug for OwnedPort<PS> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_tuple("OwnedPort").field(&**self).finish()
+    }
+}
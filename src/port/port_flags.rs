@@ -1,6 +1,8 @@
 use bitflags::bitflags;
 use jack_sys as j;
 
+use crate::jack_utils::flag_names;
+
 bitflags! {
     /// Flags for specifying port options.
     pub struct PortFlags: j::Enum_JackPortFlags {
@@ -30,3 +32,11 @@ bitflags! {
         const IS_TERMINAL = j::JackPortIsTerminal;
     }
 }
+
+flag_names!(PortFlags {
+    IS_INPUT,
+    IS_OUTPUT,
+    IS_PHYSICAL,
+    CAN_MONITOR,
+    IS_TERMINAL
+});
@@ -2,11 +2,11 @@ use jack_sys as j;
 use lazy_static::lazy_static;
 use std::cmp::{Eq, Ord, Ordering, PartialEq, PartialOrd};
 use std::hash::{Hash, Hasher};
-use std::marker::Sized;
 use std::sync::Weak;
 use std::{ffi, fmt, iter};
 
-use crate::{Error, Frames, LatencyType, PortFlags};
+use crate::jack_utils::to_cstring;
+use crate::{ConnectionsBuf, Error, Frames, LatencyType, PortFlags, PortNames, Uuid, WeakClient};
 
 lazy_static! {
     /// The maximum string length for port names.
@@ -18,7 +18,11 @@ lazy_static! {
 
 /// Defines the configuration for a certain port to JACK, ie 32 bit floating audio input, 8 bit raw
 /// midi output, etc...
-pub unsafe trait PortSpec: Sized {
+///
+/// `PortSpec` is object safe, and `Box<dyn PortSpec>` is a `PortSpec` too. Ports of different
+/// types may then be kept together as `Port<Box<dyn PortSpec + Send + Sync>>`, see
+/// `Port::into_dyn`.
+pub unsafe trait PortSpec {
     /// String used by JACK upon port creation to identify the port
     /// type.
     fn jack_port_type(&self) -> &str;
@@ -30,6 +34,20 @@ pub unsafe trait PortSpec: Sized {
     fn jack_buffer_size(&self) -> libc::c_ulong;
 }
 
+unsafe impl<PS: PortSpec + ?Sized> PortSpec for Box<PS> {
+    fn jack_port_type(&self) -> &str {
+        (**self).jack_port_type()
+    }
+
+    fn jack_flags(&self) -> PortFlags {
+        (**self).jack_flags()
+    }
+
+    fn jack_buffer_size(&self) -> libc::c_ulong {
+        (**self).jack_buffer_size()
+    }
+}
+
 /// An endpoint to interact with JACK data streams, for audio, midi, etc...
 ///
 /// The `Port` struct contains mostly metadata and exposes data as raw pointers. For a better data
@@ -78,6 +96,13 @@ impl<PS> Port<PS> {
         Ok(s)
     }
 
+    /// Returns the full name of the port like `name`, borrowed from JACK instead of copied into a
+    /// `String`. It reflects renames, since JACK keeps the name in the port itself.
+    pub fn name_cstr(&self) -> Result<&ffi::CStr, Error> {
+        self.check_client_life()?;
+        Ok(unsafe { ffi::CStr::from_ptr(j::jack_port_name(self.raw())) })
+    }
+
     /// Returns the short name of the port, it excludes the "client_name:"
     /// prefix.
     pub fn short_name(&self) -> Result<String, Error> {
@@ -90,9 +115,24 @@ impl<PS> Port<PS> {
         Ok(s)
     }
 
+    /// The `uuid` of the port, which identifies it as the subject of Metadata. `Uuid::EMPTY` if
+    /// the client is no longer alive.
+    ///
+    /// The uuid is assigned on registration and does not require the `metadata` feature or a JACK
+    /// library with Metadata support, so it can be stored before Metadata is first used.
+    pub fn uuid(&self) -> Uuid {
+        if self.check_client_life().is_err() {
+            return Uuid::EMPTY;
+        }
+        Uuid::from_raw(unsafe { j::jack_port_uuid(self.raw()) })
+    }
+
     /// The flags for the port. These are set when the port is registered with
-    /// its client.
+    /// its client. Empty if the client is no longer alive.
     pub fn flags(&self) -> PortFlags {
+        if self.check_client_life().is_err() {
+            return PortFlags::empty();
+        }
         let bits = unsafe { j::jack_port_flags(self.raw()) };
         PortFlags::from_bits(bits as j::Enum_JackPortFlags).unwrap()
     }
@@ -116,14 +156,49 @@ impl<PS> Port<PS> {
         Ok(n as usize)
     }
 
+    /// The names of the ports that are connected to/from `&self`. Works for ports of any client.
+    ///
+    /// The names are borrowed from JACK's array instead of being copied into `String`s, see
+    /// `PortNames`.
+    pub fn connections(&self) -> Result<PortNames, Error> {
+        self.check_client_life()?;
+        unsafe {
+            let ptr = j::jack_port_get_all_connections(self.client_ptr(), self.raw());
+            Ok(PortNames::from_raw(ptr))
+        }
+    }
+
+    /// Like `connections`, but the names are copied into `buf`, which is reused instead of
+    /// allocating. If there are more connections than `buf` holds, the first ones are returned
+    /// and `buf.is_truncated()` is `true`.
+    ///
+    /// For ports of this client, the connections are read with `jack_port_get_connections`,
+    /// which does not ask the server, so this may be called from notification callbacks such as
+    /// `NotificationHandler::graph_reorder`. JACK still allocates the array it returns with
+    /// `malloc`, so this is not strictly real-time safe. For other ports the server is asked,
+    /// which must not be done from a callback.
+    pub fn connections_into<'a>(
+        &self,
+        buf: &'a mut ConnectionsBuf,
+    ) -> Result<impl Iterator<Item = &'a str>, Error> {
+        self.check_client_life()?;
+        let names = unsafe {
+            let ptr = match j::jack_port_is_mine(self.client_ptr(), self.raw()) {
+                0 => j::jack_port_get_all_connections(self.client_ptr(), self.raw()),
+                _ => j::jack_port_get_connections(self.raw()),
+            };
+            PortNames::from_raw(ptr)
+        };
+        buf.fill(&names);
+        Ok(buf.iter())
+    }
+
     /// Returns `true` if the port is directly connected to a port with the
     /// name `port_name`.
     pub fn is_connected_to(&self, port_name: &str) -> Result<bool, Error> {
         self.check_client_life()?;
-        let res = unsafe {
-            let port_name = ffi::CString::new(port_name).unwrap();
-            j::jack_port_connected_to(self.raw(), port_name.as_ptr())
-        };
+        let port_name = to_cstring(port_name)?;
+        let res = unsafe { j::jack_port_connected_to(self.raw(), port_name.as_ptr()) };
         match res {
             0 => Ok(false),
             _ => Ok(true),
@@ -188,7 +263,7 @@ impl<PS> Port<PS> {
     /// will be truncated.
     pub fn set_name(&mut self, short_name: &str) -> Result<(), Error> {
         self.check_client_life()?;
-        let short_name = ffi::CString::new(short_name).unwrap();
+        let short_name = to_cstring(short_name)?;
         let res = unsafe { j::jack_port_set_name(self.raw(), short_name.as_ptr()) };
         match res {
             0 => Ok(()),
@@ -208,7 +283,7 @@ impl<PS> Port<PS> {
     /// error.
     pub fn set_alias(&mut self, alias: &str) -> Result<(), Error> {
         self.check_client_life()?;
-        let alias = ffi::CString::new(alias).unwrap();
+        let alias = to_cstring(alias)?;
         let res = unsafe { j::jack_port_set_alias(self.raw(), alias.as_ptr()) };
         match res {
             0 => Ok(()),
@@ -221,7 +296,7 @@ impl<PS> Port<PS> {
     /// After a successful call, `alias` can no longer be used as an alternate name for `self`.
     pub fn unset_alias(&mut self, alias: &str) -> Result<(), Error> {
         self.check_client_life()?;
-        let alias = ffi::CString::new(alias).unwrap();
+        let alias = to_cstring(alias)?;
         let res = unsafe { j::jack_port_unset_alias(self.raw(), alias.as_ptr()) };
         match res {
             0 => Ok(()),
@@ -292,6 +367,9 @@ impl<PS> Port<PS> {
             min: range.0,
             max: range.1,
         };
+        if self.check_client_life().is_err() {
+            return;
+        }
         unsafe { j::jack_port_set_latency_range(self.port_ptr, mode.to_ffi(), &mut ffi_range) };
     }
 
@@ -303,11 +381,19 @@ impl<PS> Port<PS> {
     #[inline(always)]
     pub fn get_latency_range(&self, mode: LatencyType) -> (Frames, Frames) {
         let mut ffi_range = j::Struct__jack_latency_range { min: 0, max: 0 };
+        if self.check_client_life().is_err() {
+            return (0, 0);
+        }
         unsafe { j::jack_port_get_latency_range(self.port_ptr, mode.to_ffi(), &mut ffi_range) };
         (ffi_range.min, ffi_range.max)
     }
 
-    fn check_client_life(&self) -> Result<(), Error> {
+    /// A handle to the client of the port that keeps it open while it is used.
+    pub(crate) fn weak_client(&self) -> WeakClient {
+        WeakClient::new(self.client_ptr, self.client_life.clone())
+    }
+
+    pub(crate) fn check_client_life(&self) -> Result<(), Error> {
         self.client_life
             .upgrade()
             .map(|_| ())
@@ -315,6 +401,21 @@ impl<PS> Port<PS> {
     }
 }
 
+impl<PS: PortSpec + Send + Sync + 'static> Port<PS> {
+    /// Erase the type of the port spec, so ports of different types can be kept in one
+    /// collection. Names, flags and connections work the same as for the typed port. The buffer
+    /// adapters, such as `AudioIn::as_slice`, need the typed port, so keep a typed handle for the
+    /// process callback.
+    pub fn into_dyn(self) -> Port<Box<dyn PortSpec + Send + Sync>> {
+        Port {
+            spec: Box::new(self.spec),
+            client_ptr: self.client_ptr,
+            port_ptr: self.port_ptr,
+            client_life: self.client_life,
+        }
+    }
+}
+
 /// `PortSpec` for a port that holds has no readable or writeable data from JACK on the created
 /// client. It can be used to connect ports or to obtain metadata.
 #[derive(Debug, Default)]
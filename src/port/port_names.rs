@@ -0,0 +1,282 @@
+use jack_sys as j;
+use std::ffi::CStr;
+use std::fmt;
+
+use crate::PORT_NAME_SIZE;
+
+/// Port names returned by JACK, see `Client::port_names` and `Port::connections`.
+///
+/// The names are borrowed from the array that JACK allocated, which is freed when `PortNames` is
+/// dropped. Unlike `Client::ports`, no `String` is allocated for each name.
+pub struct PortNames {
+    ptr: *mut *const libc::c_char,
+}
+
+unsafe impl Send for PortNames {}
+unsafe impl Sync for PortNames {}
+
+impl PortNames {
+    /// Take ownership of a NULL terminated array of port names allocated by JACK. `ptr` may be
+    /// `null`, which JACK returns when there are no names.
+    ///
+    /// # Safety
+    /// `ptr` must be `null` or an array that may be freed with `jack_free`.
+    pub(crate) unsafe fn from_raw(ptr: *mut *const libc::c_char) -> PortNames {
+        PortNames { ptr }
+    }
+
+    /// The port names. Names that are not valid UTF-8 are skipped.
+    pub fn iter(&self) -> PortNamesIter<'_> {
+        PortNamesIter {
+            names: self,
+            index: 0,
+        }
+    }
+
+    /// Returns `true` if there are no names.
+    pub fn is_empty(&self) -> bool {
+        self.ptr.is_null() || unsafe { (*self.ptr).is_null() }
+    }
+
+    /// Returns `true` if `name` is one of the names.
+    pub fn contains(&self, name: &str) -> bool {
+        self.iter().any(|n| n == name)
+    }
+
+    /// Copy the names into a `Vec`, like `Client::ports` returns them.
+    pub fn to_vec(&self) -> Vec<String> {
+        self.iter().map(String::from).collect()
+    }
+}
+
+/// Frees the array with `jack_free`.
+impl Drop for PortNames {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { j::jack_free(self.ptr as *mut libc::c_void) };
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a PortNames {
+    type Item = &'a str;
+    type IntoIter = PortNamesIter<'a>;
+
+    fn into_iter(self) -> PortNamesIter<'a> {
+        self.iter()
+    }
+}
+
+impl fmt::Debug for PortNames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Iterates over `PortNames`, see `PortNames::iter`.
+#[derive(Clone)]
+pub struct PortNamesIter<'a> {
+    names: &'a PortNames,
+    index: usize,
+}
+
+impl<'a> Iterator for PortNamesIter<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        if self.names.ptr.is_null() {
+            return None;
+        }
+        loop {
+            let name = unsafe { *self.names.ptr.add(self.index) };
+            if name.is_null() {
+                return None;
+            }
+            self.index += 1;
+            if let Ok(name) = unsafe { CStr::from_ptr(name) }.to_str() {
+                return Some(name);
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Debug for PortNamesIter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PortNamesIter")
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// Reusable storage for the connections of a port, see `Port::connections_into`.
+///
+/// Room for `capacity` names of up to `PORT_NAME_SIZE` bytes is allocated up front, and refilling
+/// the buffer reuses it, so checking connections repeatedly does not allocate. When a port has
+/// more connections than fit, the first `capacity` are kept and `is_truncated` returns `true`.
+pub struct ConnectionsBuf {
+    names: Vec<String>,
+    len: usize,
+    total: usize,
+}
+
+impl ConnectionsBuf {
+    /// Create a buffer for up to `capacity` names.
+    pub fn new(capacity: usize) -> ConnectionsBuf {
+        ConnectionsBuf {
+            names: (0..capacity)
+                .map(|_| String::with_capacity(*PORT_NAME_SIZE))
+                .collect(),
+            len: 0,
+            total: 0,
+        }
+    }
+
+    /// The number of names the buffer holds at most.
+    pub fn capacity(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The number of names in the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if the buffer holds no names.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of connections when the buffer was last filled, including the ones that did
+    /// not fit.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// Returns `true` if there were more connections than fit in the buffer when it was last
+    /// filled.
+    pub fn is_truncated(&self) -> bool {
+        self.total > self.len
+    }
+
+    /// The names in the buffer, in the order JACK reported them.
+    pub fn iter(&self) -> impl Iterator<Item = &str> + '_ {
+        self.names[..self.len].iter().map(String::as_str)
+    }
+
+    /// Returns `true` if `name` is one of the names in the buffer.
+    pub fn contains(&self, name: &str) -> bool {
+        self.iter().any(|n| n == name)
+    }
+
+    /// Replace the contents of the buffer with `names`.
+    pub(crate) fn fill<'a, I: IntoIterator<Item = &'a str>>(&mut self, names: I) {
+        self.len = 0;
+        self.total = 0;
+        for name in names {
+            self.total += 1;
+            if let Some(slot) = self.names.get_mut(self.len) {
+                // Port names are at most `PORT_NAME_SIZE` bytes, so this does not allocate.
+                slot.clear();
+                slot.push_str(name);
+                self.len += 1;
+            }
+        }
+    }
+}
+
+impl fmt::Debug for ConnectionsBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConnectionsBuf")
+            .field("names", &self.names[..self.len].to_vec())
+            .field("total", &self.total)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Names in static memory, so that the array is not freed by `jack_free`.
+    struct StaticNames(Vec<*const libc::c_char>);
+
+    impl StaticNames {
+        fn new(names: &[&'static [u8]]) -> StaticNames {
+            let mut ptrs: Vec<*const libc::c_char> =
+                names.iter().map(|n| n.as_ptr() as *const _).collect();
+            ptrs.push(std::ptr::null());
+            StaticNames(ptrs)
+        }
+
+        fn view(&mut self) -> std::mem::ManuallyDrop<PortNames> {
+            std::mem::ManuallyDrop::new(unsafe { PortNames::from_raw(self.0.as_mut_ptr()) })
+        }
+    }
+
+    #[test]
+    fn port_names_iterates_borrowed_names() {
+        let mut raw = StaticNames::new(&[b"system:capture_1\0", b"\xff\0", b"system:capture_2\0"]);
+        let names = raw.view();
+        assert!(!names.is_empty());
+        assert_eq!(
+            names.iter().collect::<Vec<_>>(),
+            ["system:capture_1", "system:capture_2"]
+        );
+        assert!(names.contains("system:capture_2"));
+        assert!(!names.contains("system:capture_3"));
+        assert_eq!(names.to_vec(), ["system:capture_1", "system:capture_2"]);
+    }
+
+    #[test]
+    fn port_names_handles_empty_arrays() {
+        let names = unsafe { PortNames::from_raw(std::ptr::null_mut()) };
+        assert!(names.is_empty());
+        assert_eq!(names.iter().count(), 0);
+        let mut raw = StaticNames::new(&[]);
+        let names = raw.view();
+        assert!(names.is_empty());
+        assert_eq!(names.iter().count(), 0);
+    }
+
+    #[test]
+    fn port_names_iteration_does_not_allocate() {
+        let mut raw = StaticNames::new(&[b"a:1\0", b"a:2\0", b"b:1\0"]);
+        let names = raw.view();
+        let before = crate::test_alloc::allocations();
+        let clients = names.iter().filter(|n| n.starts_with("a:")).count();
+        assert!(names.contains("b:1"));
+        assert_eq!(crate::test_alloc::allocations(), before);
+        assert_eq!(clients, 2);
+    }
+
+    #[test]
+    fn connections_buf_reports_truncation() {
+        let mut buf = ConnectionsBuf::new(2);
+        assert!(buf.is_empty());
+        buf.fill(["a:1", "a:2", "b:1"]);
+        assert_eq!(buf.iter().collect::<Vec<_>>(), ["a:1", "a:2"]);
+        assert_eq!((buf.len(), buf.total(), buf.capacity()), (2, 3, 2));
+        assert!(buf.is_truncated());
+        assert!(!buf.contains("b:1"));
+
+        buf.fill(["b:1"]);
+        assert_eq!(buf.iter().collect::<Vec<_>>(), ["b:1"]);
+        assert!(!buf.is_truncated());
+        buf.fill([]);
+        assert!(buf.is_empty());
+        assert_eq!(buf.total(), 0);
+    }
+
+    #[test]
+    fn connections_buf_refills_without_allocating() {
+        let mut raw = StaticNames::new(&[b"system:capture_1\0", b"system:capture_2\0"]);
+        let names = raw.view();
+        let mut buf = ConnectionsBuf::new(4);
+        let before = crate::test_alloc::allocations();
+        for _ in 0..100 {
+            buf.fill(names.iter());
+            assert!(buf.contains("system:capture_2"));
+        }
+        assert_eq!(crate::test_alloc::allocations(), before);
+    }
+}
@@ -4,8 +4,10 @@ use std::sync::Mutex;
 use super::*;
 use crate::Client;
 use crate::ClientOptions;
+use crate::ClientState;
 use crate::Error;
 use crate::NotificationHandler;
+use crate::PortErrorKind;
 use crate::PortId;
 use crate::PORT_NAME_SIZE;
 
@@ -24,10 +26,13 @@ fn client_port_register_port_enforces_unique_names() {
     let pname = "cprpeun_a";
     let c = open_test_client("cp_can_register_port");
     c.register_port(pname, AudioIn::default()).unwrap();
-    assert_eq!(
+    assert!(matches!(
         c.register_port(pname, AudioIn::default()).err(),
-        Some(Error::PortRegistrationError(pname.to_string()))
-    );
+        Some(Error::PortRegistrationError(name, details))
+            if name == pname
+                && details.kind == PortErrorKind::NameNotUnique
+                && details.code.is_none()
+    ));
 }
 
 #[test]
@@ -37,10 +42,11 @@ fn client_port_register_port_enforces_name_length() {
         .map(|_| "a")
         .collect::<Vec<&str>>()
         .join("_");
-    assert_eq!(
+    assert!(matches!(
         c.register_port(&pname, AudioIn::default()).err(),
-        Some(Error::PortRegistrationError(pname.to_string()))
-    );
+        Some(Error::PortRegistrationError(name, details))
+            if name == pname && details.kind == PortErrorKind::NameTooLong
+    ));
 }
 
 #[test]
@@ -205,10 +211,10 @@ fn client_port_cant_connect_inactive_client() {
     // let client = client.activate_async((), ()).unwrap();
 
     // connect them
-    assert_eq!(
+    assert!(matches!(
         client.connect_ports_by_name(&in_p, &out_p).err(),
-        Some(Error::PortConnectionError(in_p, out_p))
-    );
+        Some(Error::PortConnectionError(src, dst, _)) if src == in_p && dst == out_p
+    ));
 }
 
 #[test]
@@ -238,15 +244,72 @@ fn client_port_fails_to_connect_nonexistant_ports() {
     let client = open_test_client("client_port_ftcnp")
         .activate_async((), ())
         .unwrap();
-    assert_eq!(
+    assert!(matches!(
         client
             .as_client()
             .connect_ports_by_name("doesnt_exist", "also_no_exist"),
-        Err(Error::PortConnectionError(
-            "doesnt_exist".to_string(),
-            "also_no_exist".to_string(),
+        Err(Error::PortConnectionError(src, dst, details))
+            if src == "doesnt_exist"
+                && dst == "also_no_exist"
+                && details.kind == PortErrorKind::NoSuchPort
+                && details.code.is_some_and(|code| code != 0)
+    ));
+}
+
+/// Connect `source` to `destination` and return the kind of the error.
+fn connection_error_kind(client: &Client, source: &str, destination: &str) -> PortErrorKind {
+    match client.connect_ports_by_name(source, destination) {
+        Err(Error::PortConnectionError(_, _, details)) => details.kind,
+        res => panic!("expected a connection error, got {:?}", res),
+    }
+}
+
+#[test]
+fn client_port_connection_errors_are_distinguishable() {
+    let client = open_test_client("client_port_cead");
+    let audio_in = client
+        .register_port("audio_in", AudioIn::default())
+        .unwrap();
+    let audio_out = client
+        .register_port("audio_out", AudioOut::default())
+        .unwrap();
+    let midi_in = client.register_port("midi_in", MidiIn::default()).unwrap();
+    let audio_in = audio_in.name().unwrap();
+    let audio_out = audio_out.name().unwrap();
+    let midi_in = midi_in.name().unwrap();
+
+    assert_eq!(
+        client.connect_ports_by_name(&audio_out, &audio_in),
+        Err(Error::InvalidClientState(
+            ClientState::Active,
+            ClientState::Inactive
         ))
     );
+
+    let client = client.activate_async((), ()).unwrap();
+    let client = client.as_client();
+    assert_eq!(
+        connection_error_kind(client, &audio_out, "client_port_cead:missing"),
+        PortErrorKind::NoSuchPort
+    );
+    assert_eq!(
+        connection_error_kind(client, &audio_out, &midi_in),
+        PortErrorKind::TypeMismatch
+    );
+    assert_eq!(
+        connection_error_kind(client, &audio_in, &audio_out),
+        PortErrorKind::WrongDirection
+    );
+    assert!(matches!(
+        client.disconnect_ports_by_name(&audio_out, &audio_in),
+        Err(Error::PortDisconnectionError(details))
+            if details.kind == PortErrorKind::NotConnected
+    ));
+    assert!(matches!(
+        client.disconnect_ports_by_name(&audio_out, "client_port_cead:missing"),
+        Err(Error::PortDisconnectionError(details))
+            if details.kind == PortErrorKind::NoSuchPort
+    ));
 }
 
 #[test]
@@ -392,3 +455,498 @@ fn client_port_can_get_port_by_type_pattern() {
         &full_name
     );
 }
+
+#[test]
+fn client_port_names_match_ports() {
+    let client = open_test_client("client_port_pnmp");
+    for pattern in [None, Some("2"), Some("no_such_port")] {
+        let names = client.port_names(pattern, None, PortFlags::empty());
+        assert_eq!(
+            names.to_vec(),
+            client.ports(pattern, None, PortFlags::empty())
+        );
+        assert_eq!(names.is_empty(), names.to_vec().is_empty());
+    }
+    let inputs = client.port_names(None, None, PortFlags::IS_INPUT);
+    assert!(inputs.contains("system:playback_1"));
+    assert!(!inputs.contains("system:capture_1"));
+    assert!(client
+        .port_names(Some("system:\0"), None, PortFlags::empty())
+        .is_empty());
+}
+
+#[test]
+fn client_port_lists_connections() {
+    let client = open_test_client("client_port_plc");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = client.register_port("outp", AudioOut::default()).unwrap();
+    let client = client.activate_async((), ()).unwrap();
+    assert!(in_p.connections().unwrap().is_empty());
+
+    client.as_client().connect_ports(&out_p, &in_p).unwrap();
+    client
+        .as_client()
+        .connect_ports_by_name("system:capture_1", &in_p.name().unwrap())
+        .unwrap();
+    let mut connections = in_p.connections().unwrap().to_vec();
+    connections.sort();
+    assert_eq!(
+        connections,
+        [out_p.name().unwrap(), "system:capture_1".to_string()]
+    );
+    let system = client.as_client().port_by_name("system:capture_1").unwrap();
+    assert!(system
+        .connections()
+        .unwrap()
+        .contains(&in_p.name().unwrap()));
+}
+
+#[test]
+fn client_port_connects_without_allocating() {
+    let client = open_test_client("client_port_cwa");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = client.register_port("outp", AudioOut::default()).unwrap();
+    let client = client.activate_async((), ()).unwrap();
+    let client = client.as_client();
+    let before = crate::test_alloc::allocations();
+    for _ in 0..1000 {
+        client.connect_ports(&out_p, &in_p).unwrap();
+        client.disconnect_ports(&out_p, &in_p).unwrap();
+    }
+    assert_eq!(crate::test_alloc::allocations(), before);
+    assert!(!out_p.is_connected_to(&in_p.name().unwrap()).unwrap());
+}
+
+#[test]
+fn client_port_name_cstr_follows_renames() {
+    let client = open_test_client("client_port_ncfr");
+    let mut port = client.register_port("before", AudioIn::default()).unwrap();
+    assert_eq!(
+        port.name_cstr().unwrap().to_str(),
+        Ok("client_port_ncfr:before")
+    );
+    port.set_name("after").unwrap();
+    assert_eq!(
+        port.name_cstr().unwrap().to_str(),
+        Ok("client_port_ncfr:after")
+    );
+    let client = client.activate_async((), ()).unwrap();
+    let system = std::ffi::CString::new("system:capture_1").unwrap();
+    client
+        .as_client()
+        .connect_ports_cstr(&system, port.name_cstr().unwrap())
+        .unwrap();
+    assert!(port.is_connected_to("system:capture_1").unwrap());
+    client
+        .as_client()
+        .disconnect_ports_cstr(&system, port.name_cstr().unwrap())
+        .unwrap();
+}
+
+#[test]
+fn client_port_rejects_nul_in_names() {
+    fn invalid<T>(name: &str) -> Result<T, Error> {
+        Err(Error::InvalidName(name.to_string()))
+    }
+    let client = open_test_client("client_port_rnin")
+        .activate_async((), ())
+        .unwrap();
+    let client = client.as_client();
+    assert!(matches!(
+        client.register_port("in\0put", AudioIn::default()),
+        Err(Error::InvalidName(name)) if name == "in\0put"
+    ));
+    let mut port = client.register_port("input", AudioIn::default()).unwrap();
+    let name = port.name().unwrap();
+
+    assert!(client.port_by_name("client_port_rnin:\0input").is_none());
+    assert!(client
+        .ports(Some("in\0put"), None, PortFlags::empty())
+        .is_empty());
+    assert!(client
+        .ports(None, Some("audio\0"), PortFlags::empty())
+        .is_empty());
+    assert_eq!(client.name_by_uuid_str("\0"), None);
+    assert_eq!(
+        client.connect_ports_by_name("out\0", &name),
+        invalid("out\0")
+    );
+    assert_eq!(
+        client.disconnect_ports_by_name(&name, "out\0"),
+        invalid("out\0")
+    );
+    assert_eq!(
+        client.request_monitor_by_name("in\0put", true),
+        invalid("in\0put")
+    );
+    assert_eq!(port.is_connected_to("out\0"), invalid("out\0"));
+    assert_eq!(port.set_name("in\0put"), invalid("in\0put"));
+    assert_eq!(port.set_alias("al\0ias"), invalid("al\0ias"));
+    assert_eq!(port.unset_alias("al\0ias"), invalid("al\0ias"));
+    assert_eq!(port.name().unwrap(), name);
+}
+
+#[test]
+fn client_port_restores_connections_after_reactivation() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let client = open_test_client("client_port_rcar");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = client.register_port("outp", AudioOut::default()).unwrap();
+    let cycles = Arc::new(AtomicUsize::new(0));
+    let counter = cycles.clone();
+    let process = crate::ClosureProcessHandler::new(move |_: &Client, _: &crate::ProcessScope| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        crate::Control::Continue
+    });
+    let active = client.activate_async((), process).unwrap();
+    active.as_client().connect_ports(&out_p, &in_p).unwrap();
+    active
+        .as_client()
+        .connect_ports_by_name("system:capture_1", &in_p.name().unwrap())
+        .unwrap();
+
+    let (client, (), process, mut connections) = active.deactivate_with_connections().unwrap();
+    connections.sort();
+    assert_eq!(
+        connections,
+        [
+            (out_p.name().unwrap(), in_p.name().unwrap()),
+            ("system:capture_1".to_string(), in_p.name().unwrap()),
+        ]
+    );
+    assert!(in_p.connections().unwrap().is_empty());
+
+    // The ports survive, and new ones may be registered while inactive.
+    assert!(client.is_mine(&in_p) && client.is_mine(&out_p));
+    client.register_port("extra", AudioOut::default()).unwrap();
+    let active = client.activate_async((), process).unwrap();
+    active
+        .as_client()
+        .restore_connections(&connections)
+        .unwrap();
+    // Restoring again skips the existing connections.
+    active
+        .as_client()
+        .restore_connections(&connections)
+        .unwrap();
+    let mut restored = active.as_client().port_connections();
+    restored.sort();
+    assert_eq!(restored, connections);
+
+    // The process callback is called again after reactivation.
+    let before = cycles.load(Ordering::Relaxed);
+    active
+        .wait_for_first_cycle(std::time::Duration::from_secs(5))
+        .unwrap();
+    assert!(cycles.load(Ordering::Relaxed) > before);
+    active.deactivate().unwrap();
+}
+
+#[test]
+fn client_port_restore_connections_reports_missing_ports() {
+    let client = open_test_client("client_port_rcrmp");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let client = client.activate_async((), ()).unwrap();
+    let connections = [
+        ("client_port_rcrmp:gone".to_string(), in_p.name().unwrap()),
+        ("system:capture_1".to_string(), in_p.name().unwrap()),
+    ];
+    assert!(client
+        .as_client()
+        .restore_connections(&connections)
+        .is_err());
+    // The other connections are still made.
+    assert!(in_p.is_connected_to("system:capture_1").unwrap());
+}
+
+#[test]
+fn client_port_connecting_own_ports_needs_active_client() {
+    let client = open_test_client("client_port_coprac");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = client.register_port("outp", AudioOut::default()).unwrap();
+    let (in_name, out_name) = (in_p.name().unwrap(), out_p.name().unwrap());
+    let (in_c, out_c) = (in_p.name_cstr().unwrap(), out_p.name_cstr().unwrap());
+    let inactive = Err(Error::InvalidClientState(
+        ClientState::Active,
+        ClientState::Inactive,
+    ));
+
+    assert!(!client.is_active());
+    assert_eq!(client.connect_ports(&out_p, &in_p), inactive);
+    assert_eq!(client.connect_ports_by_name(&out_name, &in_name), inactive);
+    assert_eq!(client.connect_ports_cstr(out_c, in_c), inactive);
+    assert_eq!(
+        client.connect_ports_by_name("system:capture_1", &in_name),
+        inactive
+    );
+    assert_eq!(client.disconnect_ports(&out_p, &in_p), inactive);
+    assert_eq!(
+        client.disconnect_ports_by_name(&out_name, &in_name),
+        inactive
+    );
+    assert_eq!(client.disconnect_ports_cstr(out_c, in_c), inactive);
+    assert_eq!(
+        inactive.clone().unwrap_err().to_string(),
+        "JackError: the client must be active for this operation, but it is inactive"
+    );
+
+    let active = client.activate_async((), ()).unwrap();
+    let client = active.as_client();
+    assert!(client.is_active());
+    client.connect_ports(&out_p, &in_p).unwrap();
+    client.disconnect_ports(&out_p, &in_p).unwrap();
+    client.connect_ports_by_name(&out_name, &in_name).unwrap();
+    client
+        .disconnect_ports_by_name(&out_name, &in_name)
+        .unwrap();
+    client.connect_ports_cstr(out_c, in_c).unwrap();
+    client.disconnect_ports_cstr(out_c, in_c).unwrap();
+
+    let (client, (), ()) = active.deactivate().unwrap();
+    assert!(!client.is_active());
+    assert_eq!(client.connect_ports(&out_p, &in_p), inactive);
+}
+
+#[test]
+fn client_port_inactive_client_can_connect_other_ports() {
+    let client = open_test_client("client_port_iccop");
+    let other = open_test_client("client_port_iccop_other");
+    let in_p = other.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = other.register_port("outp", AudioOut::default()).unwrap();
+    let _other = other.activate_async((), ()).unwrap();
+    assert!(!client.is_active());
+    client.connect_ports(&out_p, &in_p).unwrap();
+    client.disconnect_ports(&out_p, &in_p).unwrap();
+}
+
+#[test]
+fn client_port_connection_guard_disconnects_when_dropped() {
+    let client = open_test_client("client_port_cgdwd");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = client.register_port("outp", AudioOut::default()).unwrap();
+    let client = client.activate_async((), ()).unwrap();
+    let (in_name, out_name) = (in_p.name().unwrap(), out_p.name().unwrap());
+
+    let guard = client
+        .as_client()
+        .connect_guarded(&out_name, &in_name)
+        .unwrap();
+    assert_eq!(
+        (guard.source_port(), guard.destination_port()),
+        (out_name.as_str(), in_name.as_str())
+    );
+    assert!(out_p.is_connected_to(&in_name).unwrap());
+    assert_eq!(
+        client
+            .as_client()
+            .connect_guarded(&out_name, &in_name)
+            .err(),
+        Some(Error::PortAlreadyConnected(
+            out_name.clone(),
+            in_name.clone()
+        ))
+    );
+    // Guards may be dropped on another thread.
+    std::thread::spawn(move || drop(guard)).join().unwrap();
+    assert!(!out_p.is_connected_to(&in_name).unwrap());
+}
+
+#[test]
+fn client_port_connection_guard_can_be_forgotten() {
+    let client = open_test_client("client_port_cgcbf");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = client.register_port("outp", AudioOut::default()).unwrap();
+    let client = client.activate_async((), ()).unwrap();
+    let in_name = in_p.name().unwrap();
+    client
+        .as_client()
+        .connect_guarded(&out_p.name().unwrap(), &in_name)
+        .unwrap()
+        .forget();
+    assert!(out_p.is_connected_to(&in_name).unwrap());
+}
+
+#[test]
+fn client_port_connection_guard_ignores_vanished_ports() {
+    let client = open_test_client("client_port_cgivp");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_p = client.register_port("outp", AudioOut::default()).unwrap();
+    let client = client.activate_async((), ()).unwrap();
+    let guard = client
+        .as_client()
+        .connect_guarded(&out_p.name().unwrap(), &in_p.name().unwrap())
+        .unwrap();
+    client.as_client().unregister_port(in_p).unwrap();
+    drop(guard);
+    // The client may be closed before the guard is dropped too.
+    let in_p = client
+        .as_client()
+        .register_port("inp2", AudioIn::default())
+        .unwrap();
+    let guard = client
+        .as_client()
+        .connect_guarded(&out_p.name().unwrap(), &in_p.name().unwrap())
+        .unwrap();
+    drop(client);
+    drop(guard);
+}
+
+#[test]
+fn client_port_owned_port_is_unregistered_when_dropped() {
+    let client = open_test_client("client_port_opiuwd");
+    let mut port = client
+        .register_port_owned("owned", AudioOut::default())
+        .unwrap();
+    assert_eq!(port.name().unwrap(), "client_port_opiuwd:owned");
+    let ports = || client.ports(Some("client_port_opiuwd:"), None, PortFlags::empty());
+    assert_eq!(ports(), ["client_port_opiuwd:owned"]);
+    // The port is used like a `Port`.
+    port.set_name("renamed").unwrap();
+    drop(port);
+    assert!(ports().is_empty());
+
+    let port = client
+        .register_port_owned("kept", AudioOut::default())
+        .unwrap()
+        .into_port();
+    assert_eq!(ports(), ["client_port_opiuwd:kept"]);
+    client.unregister_port(port).unwrap();
+}
+
+#[test]
+fn client_port_owned_port_may_outlive_its_client() {
+    let client = open_test_client("client_port_opmoic");
+    let port = client
+        .register_port_owned("owned", AudioIn::default())
+        .unwrap();
+    let process = crate::ClosureProcessHandler::new(move |_: &Client, ps: &crate::ProcessScope| {
+        let _ = port.as_slice(ps);
+        crate::Control::Continue
+    });
+    // The client is closed before the process handler, and with it the port, is dropped.
+    let active = client.activate_async((), process).unwrap();
+    drop(active);
+
+    let client = open_test_client("client_port_opmoic");
+    let port = client
+        .register_port_owned("owned", AudioIn::default())
+        .unwrap();
+    drop(client);
+    assert_eq!(port.name(), Err(Error::ClientIsNoLongerAlive));
+    drop(port);
+}
+
+/// An audio input that may be monitored, like the capture ports of a sound card.
+struct MonitoredAudioIn;
+
+unsafe impl PortSpec for MonitoredAudioIn {
+    fn jack_port_type(&self) -> &str {
+        jack_sys::FLOAT_MONO_AUDIO
+    }
+
+    fn jack_flags(&self) -> PortFlags {
+        PortFlags::IS_INPUT | PortFlags::CAN_MONITOR
+    }
+
+    fn jack_buffer_size(&self) -> libc::c_ulong {
+        0
+    }
+}
+
+#[test]
+fn client_port_can_set_monitoring_of_all_ports() {
+    let client = open_test_client("client_port_csmoap");
+    let a = client.register_port("a", MonitoredAudioIn).unwrap();
+    let b = client.register_port("b", MonitoredAudioIn).unwrap();
+    let plain = client.register_port("plain", AudioIn::default()).unwrap();
+    assert!(!a.is_monitoring_input().unwrap());
+
+    assert_eq!(client.set_monitoring_all(true), Ok(2));
+    assert!(a.is_monitoring_input().unwrap());
+    assert!(b.is_monitoring_input().unwrap());
+    assert!(!plain.is_monitoring_input().unwrap());
+    // Monitoring is only turned on once.
+    assert_eq!(client.set_monitoring_all(true), Ok(2));
+
+    assert_eq!(client.set_monitoring_all(false), Ok(2));
+    assert!(!a.is_monitoring_input().unwrap());
+    assert!(!b.is_monitoring_input().unwrap());
+}
+
+#[test]
+fn client_port_keeps_mixed_ports_in_one_collection() {
+    let client = open_test_client("client_port_kmpioc");
+    let ports: Vec<Port<Box<dyn PortSpec + Send + Sync>>> = vec![
+        client
+            .register_port("audio_out", AudioOut)
+            .unwrap()
+            .into_dyn(),
+        client
+            .register_port("midi_out", MidiOut)
+            .unwrap()
+            .into_dyn(),
+        client
+            .register_port(
+                "audio_in",
+                Box::new(AudioIn) as Box<dyn PortSpec + Send + Sync>,
+            )
+            .unwrap(),
+        client.register_port("midi_in", MidiIn).unwrap().into_dyn(),
+    ];
+    assert_eq!(ports[1].spec().jack_port_type(), "8 bit raw midi");
+    assert_eq!(ports[2].flags(), PortFlags::IS_INPUT);
+    let client = client.activate_async((), ()).unwrap();
+
+    let (outputs, inputs) = ports.split_at(2);
+    for (out_p, in_p) in outputs.iter().zip(inputs) {
+        client.as_client().connect_ports(out_p, in_p).unwrap();
+        assert!(out_p.is_connected_to(&in_p.name().unwrap()).unwrap());
+    }
+    for (out_p, in_p) in outputs.iter().zip(inputs) {
+        client.as_client().disconnect_ports(out_p, in_p).unwrap();
+        assert_eq!(out_p.connected_count(), Ok(0));
+    }
+}
+
+#[test]
+fn client_port_lists_connections_into_buffer() {
+    let client = open_test_client("client_port_lcib");
+    let in_p = client.register_port("inp", AudioIn::default()).unwrap();
+    let out_a = client.register_port("out_a", AudioOut::default()).unwrap();
+    let out_b = client.register_port("out_b", AudioOut::default()).unwrap();
+    let client = client.activate_async((), ()).unwrap();
+    let client = client.as_client();
+    let mut buf = ConnectionsBuf::new(1);
+    assert_eq!(in_p.connections_into(&mut buf).unwrap().count(), 0);
+
+    client.connect_ports(&out_a, &in_p).unwrap();
+    let names: Vec<String> = in_p
+        .connections_into(&mut buf)
+        .unwrap()
+        .map(String::from)
+        .collect();
+    assert_eq!(names, [out_a.name().unwrap()]);
+    assert!(!buf.is_truncated());
+
+    // The buffer only has room for one of the connections.
+    client.connect_ports(&out_b, &in_p).unwrap();
+    assert_eq!(in_p.connections_into(&mut buf).unwrap().count(), 1);
+    assert!(buf.is_truncated());
+    assert_eq!(buf.total(), 2);
+
+    // Ports of other clients are asked from the server.
+    let system = client.port_by_name("system:capture_1").unwrap();
+    client.connect_ports(&system, &in_p).unwrap();
+    let mut buf = ConnectionsBuf::new(4);
+    let before = crate::test_alloc::allocations();
+    for _ in 0..10 {
+        assert_eq!(in_p.connections_into(&mut buf).unwrap().count(), 3);
+        assert!(system
+            .connections_into(&mut buf)
+            .unwrap()
+            .any(|n| n == "client_port_lcib:inp"));
+    }
+    assert_eq!(crate::test_alloc::allocations(), before);
+}
@@ -11,3 +11,7 @@ pub type Time = j::jack_time_t;
 /// ever need to know
 /// their value.
 pub type PortId = j::jack_port_id_t;
+
+/// Connections between ports, as `(source_port, destination_port)` pairs of full port names. See
+/// `Client::port_connections`.
+pub type PortConnections = Vec<(String, String)>;
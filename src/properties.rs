@@ -1,15 +1,34 @@
 //! Properties, AKA [Meta Data](https://jackaudio.org/api/group__Metadata.html)
 //!
-use j::jack_uuid_t as uuid;
 use jack_sys as j;
 
+use crate::Uuid;
+
 /// A description of a Metadata change describint a creation, change or deletion, its owner
-/// `subject` and `key`.
+/// `subject` and `key`. The `key` is empty if all the properties of the subject changed.
 #[derive(Debug, PartialEq)]
 pub enum PropertyChange<'a> {
-    Created { subject: uuid, key: &'a str },
-    Changed { subject: uuid, key: &'a str },
-    Deleted { subject: uuid, key: &'a str },
+    Created { subject: Uuid, key: &'a str },
+    Changed { subject: Uuid, key: &'a str },
+    Deleted { subject: Uuid, key: &'a str },
+}
+
+/// The kind of a property change, see `NotificationHandler::property_change`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PropertyChangeKind {
+    Created,
+    Changed,
+    Deleted,
+}
+
+impl PropertyChangeKind {
+    pub(crate) fn from_ffi(change: j::jack_property_change_t) -> PropertyChangeKind {
+        match change {
+            j::PropertyCreated => PropertyChangeKind::Created,
+            j::PropertyDeleted => PropertyChangeKind::Deleted,
+            _ => PropertyChangeKind::Changed,
+        }
+    }
 }
 
 /// A trait for reacting to property changes.
@@ -31,33 +50,83 @@ pub(crate) unsafe extern "C" fn property_changed<P>(
     P: PropertyChangeHandler,
 {
     let h: &mut P = &mut *(arg as *mut P);
-    let key_c = std::ffi::CStr::from_ptr(key);
-    let key = key_c.to_str().expect("to convert key to valid str");
-    let c = match change {
-        j::PropertyCreated => PropertyChange::Created { subject, key },
-        j::PropertyDeleted => PropertyChange::Deleted { subject, key },
-        _ => PropertyChange::Changed { subject, key },
+    // A null key stands for all the properties of the subject, it is passed on as "".
+    let key_c = match key.is_null() {
+        true => "".into(),
+        false => std::ffi::CStr::from_ptr(key).to_string_lossy(),
+    };
+    let key = &*key_c;
+    let subject = Uuid::from_raw(subject);
+    let c = match PropertyChangeKind::from_ffi(change) {
+        PropertyChangeKind::Created => PropertyChange::Created { subject, key },
+        PropertyChangeKind::Deleted => PropertyChange::Deleted { subject, key },
+        PropertyChangeKind::Changed => PropertyChange::Changed { subject, key },
     };
     h.property_changed(&c);
 }
 
 #[cfg(feature = "metadata")]
-pub use metadata::*;
+pub use metadata::{
+    all_properties, properties_of, ClosurePropertyChangeHandler, IconSize, Property,
+    PropertyChangeOwned, PropertyMap, SignalType, ICON_PNG_MAX_SIZE,
+};
 
+/// Metadata on clients and ports, see `Client::set_property` and `Port::set_pretty_name`.
+///
+/// # Availability
+///
+/// The module requires the `metadata` feature. Whether the JACK library supports Metadata is
+/// checked at runtime with `is_available`, as it is missing from old versions of JACK:
+///
+/// | JACK library            | Metadata        |
+/// |-------------------------|-----------------|
+/// | JACK1                   | since 0.125.0   |
+/// | JACK2                   | since 1.9.13    |
+/// | PipeWire (pipewire-jack)| yes             |
+///
+/// Without Metadata support, functions that change properties return
+/// `Err(Error::WeakFunctionNotFound)`, and functions that read them return nothing. The JACK
+/// library is never called.
 #[cfg(feature = "metadata")]
-mod metadata {
+pub mod metadata {
     use super::*;
+    use crate::jack_utils::to_cstring;
     use crate::Error;
     use std::{collections::HashMap, ffi, mem::MaybeUninit, ptr};
 
-    use crate::Client;
+    use crate::{Client, Port};
+
+    /// The well-known Metadata keys defined by JACK, see
+    /// [metadata.h](https://jackaudio.org/api/group__Metadata.html).
+    pub mod keys {
+        /// A value that is `"1"` or `"true"` if the port is physically connected to something.
+        pub const CONNECTED: &str = "http://jackaudio.org/metadata/connected";
+        /// A comma separated list of the kinds of events a MIDI port carries.
+        pub const EVENT_TYPES: &str = "http://jackaudio.org/metadata/event-types";
+        /// A unique name of the hardware behind the port or client.
+        pub const HARDWARE: &str = "http://jackaudio.org/metadata/hardware";
+        /// A base64 encoded 96x96 PNG icon for the client.
+        pub const ICON_LARGE: &str = "http://jackaudio.org/metadata/icon-large";
+        /// The name of a freedesktop.org icon for the client.
+        pub const ICON_NAME: &str = "http://jackaudio.org/metadata/icon-name";
+        /// A base64 encoded 32x32 PNG icon for the client.
+        pub const ICON_SMALL: &str = "http://jackaudio.org/metadata/icon-small";
+        /// An integer that gives the order in which the ports of a client are displayed.
+        pub const ORDER: &str = "http://jackaudio.org/metadata/order";
+        /// A human readable name for a port or client, displayed instead of its name.
+        pub const PRETTY_NAME: &str = "http://jackaudio.org/metadata/pretty-name";
+        /// The name of the group, such as a stereo pair, that a port belongs to.
+        pub const PORT_GROUP: &str = "http://jackaudio.org/metadata/port-group";
+        /// The kind of signal of an audio port, either `"AUDIO"` or `"CV"`.
+        pub const SIGNAL_TYPE: &str = "http://jackaudio.org/metadata/signal-type";
+    }
 
     /// A helper enum, allowing for sending changes between threads.
     #[derive(Debug, Clone, PartialEq)]
     pub enum PropertyChangeOwned {
-        Created { subject: uuid, key: String },
-        Changed { subject: uuid, key: String },
-        Deleted { subject: uuid, key: String },
+        Created { subject: Uuid, key: String },
+        Changed { subject: Uuid, key: String },
+        Deleted { subject: Uuid, key: String },
     }
 
     /// A piece of Metadata on a Jack `subject`: either a port or a client.
@@ -99,6 +168,25 @@ mod metadata {
         }
     }
 
+    /// Returns `true` if the JACK library supports Metadata. See the module documentation.
+    ///
+    /// The library is probed on the first call from anywhere, including the Metadata functions of
+    /// `Client` and `Port` and `server_quirks`, and the result is kept for the life of the process.
+    /// So the result does not depend on the order of the calls, or on whether clients and ports
+    /// were created before.
+    pub fn is_available() -> bool {
+        *j::jack_metadata_available
+    }
+
+    //helper to fail with `Error::WeakFunctionNotFound` on versions of JACK without Metadata
+    fn check_available() -> Result<(), Error> {
+        if is_available() {
+            Ok(())
+        } else {
+            Err(Error::WeakFunctionNotFound)
+        }
+    }
+
     //helper to map 0 return to Ok
     fn map_error<F: FnOnce() -> ::libc::c_int>(func: F) -> Result<(), Error> {
         if func() == 0 {
@@ -108,44 +196,281 @@ mod metadata {
         }
     }
 
-    //helper to convert to an Option<PropertyMap> and free
-    unsafe fn description_to_map_free(
+    /// The types of the values of the well-known Metadata keys, see `Property::typ`.
+    pub mod types {
+        /// Plain text, such as `keys::PRETTY_NAME`.
+        pub const TEXT: &str = "text/plain";
+        /// A decimal integer, such as `keys::ORDER`.
+        pub const INTEGER: &str = "http://www.w3.org/2001/XMLSchema#integer";
+        /// A base64 encoded PNG image, such as `keys::ICON_SMALL`.
+        pub const PNG_BASE64: &str = "image/png;base64";
+    }
+
+    /// The kind of signal that an audio port carries, see `keys::SIGNAL_TYPE`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum SignalType {
+        /// An audio signal, the default for audio ports.
+        Audio,
+        /// A control voltage signal, which may contain DC and is not meant to be listened to.
+        Cv,
+    }
+
+    impl SignalType {
+        /// The value of `keys::SIGNAL_TYPE` for this signal type.
+        pub fn as_str(self) -> &'static str {
+            match self {
+                SignalType::Audio => "AUDIO",
+                SignalType::Cv => "CV",
+            }
+        }
+
+        /// The signal type of a value of `keys::SIGNAL_TYPE`, or `None` if it is not known.
+        pub fn from_value(value: &str) -> Option<SignalType> {
+            match value {
+                "AUDIO" => Some(SignalType::Audio),
+                "CV" => Some(SignalType::Cv),
+                _ => None,
+            }
+        }
+    }
+
+    /// The maximum size, in bytes, of the PNG data of a client icon.
+    pub const ICON_PNG_MAX_SIZE: usize = 64 * 1024;
+
+    /// The size of a client icon, see `Client::set_icon_png`.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum IconSize {
+        /// A 32x32 icon, see `keys::ICON_SMALL`.
+        Small,
+        /// A 96x96 icon, see `keys::ICON_LARGE`.
+        Large,
+    }
+
+    impl IconSize {
+        /// The Metadata key of icons of this size.
+        pub fn key(self) -> &'static str {
+            match self {
+                IconSize::Small => keys::ICON_SMALL,
+                IconSize::Large => keys::ICON_LARGE,
+            }
+        }
+    }
+
+    const BASE64_ALPHABET: &[u8; 64] =
+        b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    //helper to encode `data` as padded standard base64
+    fn base64_encode(data: &[u8]) -> String {
+        let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+        for chunk in data.chunks(3) {
+            let bytes = [
+                chunk[0],
+                *chunk.get(1).unwrap_or(&0),
+                *chunk.get(2).unwrap_or(&0),
+            ];
+            let bits = u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]]);
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    let index = (bits >> (18 - 6 * i)) & 0x3f;
+                    encoded.push(BASE64_ALPHABET[index as usize] as char);
+                } else {
+                    encoded.push('=');
+                }
+            }
+        }
+        encoded
+    }
+
+    //helper to decode standard base64, ignoring whitespace, or `None` if it is not valid
+    fn base64_decode(encoded: &str) -> Option<Vec<u8>> {
+        let mut decoded = Vec::with_capacity(encoded.len() / 4 * 3);
+        let (mut bits, mut n_bits) = (0u32, 0);
+        let mut padding = 0;
+        for c in encoded.bytes().filter(|c| !c.is_ascii_whitespace()) {
+            if c == b'=' {
+                padding += 1;
+                continue;
+            }
+            if padding > 0 {
+                return None;
+            }
+            let value = BASE64_ALPHABET.iter().position(|&a| a == c)? as u32;
+            bits = (bits << 6) | value;
+            n_bits += 6;
+            if n_bits >= 8 {
+                n_bits -= 8;
+                decoded.push((bits >> n_bits) as u8);
+                bits &= (1 << n_bits) - 1;
+            }
+        }
+        if padding > 2 || bits != 0 {
+            return None;
+        }
+        Some(decoded)
+    }
+
+    //helper to get a property, shared by `Client` and `Port`
+    fn get_raw(subject: Uuid, key: &str) -> Option<Property> {
+        check_available().ok()?;
+        let key = to_cstring(key).ok()?;
+        let mut value: MaybeUninit<*mut ::libc::c_char> = MaybeUninit::uninit();
+        let mut typ: MaybeUninit<*mut ::libc::c_char> = MaybeUninit::uninit();
+
+        unsafe {
+            if j::jack_get_property(
+                subject.raw(),
+                key.as_ptr(),
+                value.as_mut_ptr(),
+                typ.as_mut_ptr(),
+            ) == 0
+            {
+                let value = value.assume_init();
+                let typ = typ.assume_init();
+                let r = Some(Property::new(
+                    ffi::CStr::from_ptr(value).to_string_lossy(),
+                    if typ.is_null() {
+                        None
+                    } else {
+                        Some(ffi::CStr::from_ptr(typ).to_string_lossy().into_owned())
+                    },
+                ));
+                j::jack_free(value as _);
+                if !typ.is_null() {
+                    j::jack_free(typ as _)
+                }
+                r
+            } else {
+                None
+            }
+        }
+    }
+
+    //helper to set a property on behalf of `client`, shared by `Client` and `Port`
+    fn set_raw(
+        client: *mut j::jack_client_t,
+        subject: Uuid,
+        key: &str,
+        property: &Property,
+    ) -> Result<(), Error> {
+        check_available()?;
+        let key = to_cstring(key)?;
+        let value = to_cstring(property.value.as_str())?;
+        let typ = property.typ().map(to_cstring).transpose()?;
+        map_error(|| unsafe {
+            let typ = typ.as_ref().map_or(ptr::null(), |t| t.as_ptr());
+            j::jack_set_property(client, subject.raw(), key.as_ptr(), value.as_ptr(), typ)
+        })
+    }
+
+    //helper to remove a property on behalf of `client`, shared by `Client` and `Port`
+    fn remove_raw(client: *mut j::jack_client_t, subject: Uuid, key: &str) -> Result<(), Error> {
+        check_available()?;
+        let key = to_cstring(key)?;
+        map_error(|| unsafe { j::jack_remove_property(client, subject.raw(), key.as_ptr()) })
+    }
+
+    //helper to set or remove the pretty name of a subject
+    fn set_pretty_name_raw(
+        client: *mut j::jack_client_t,
+        subject: Uuid,
+        name: Option<&str>,
+    ) -> Result<(), Error> {
+        match name {
+            Some(name) => set_raw(client, subject, keys::PRETTY_NAME, &Property::text(name)),
+            // Removing a pretty name that is not set is not an error.
+            None => match remove_raw(client, subject, keys::PRETTY_NAME) {
+                Err(Error::UnknownError) => Ok(()),
+                res => res,
+            },
+        }
+    }
+
+    //helper to convert to a list of keys and `Property`s, in the order of JACK, and free
+    unsafe fn description_to_vec_free(
         description: *mut j::jack_description_t,
-    ) -> Option<PropertyMap> {
+    ) -> Option<Vec<(String, Property)>> {
         if description.is_null() {
             None
         } else {
             let des = &*description;
-            let mut properties = HashMap::new();
-            for prop in std::slice::from_raw_parts(des.properties, des.property_cnt as usize) {
-                let typ = if prop._type.is_null() {
-                    None
-                } else {
-                    Some(
-                        ffi::CStr::from_ptr(prop._type)
-                            .to_str()
-                            .expect("to convert type to str")
-                            .to_string(),
-                    )
-                };
-                properties.insert(
-                    ffi::CStr::from_ptr(prop.key)
-                        .to_str()
-                        .expect("to turn key to str")
-                        .to_string(),
-                    Property::new(
-                        ffi::CStr::from_ptr(prop.data)
-                            .to_str()
-                            .expect("to turn data to str"),
-                        typ,
-                    ),
-                );
+            let mut properties = Vec::with_capacity(des.property_cnt as usize);
+            if !des.properties.is_null() {
+                for prop in std::slice::from_raw_parts(des.properties, des.property_cnt as usize) {
+                    let typ = if prop._type.is_null() {
+                        None
+                    } else {
+                        Some(
+                            ffi::CStr::from_ptr(prop._type)
+                                .to_string_lossy()
+                                .into_owned(),
+                        )
+                    };
+                    properties.push((
+                        ffi::CStr::from_ptr(prop.key).to_string_lossy().into_owned(),
+                        Property::new(ffi::CStr::from_ptr(prop.data).to_string_lossy(), typ),
+                    ));
+                }
             }
             j::jack_free_description(description, 0);
             Some(properties)
         }
     }
 
+    //helper to get the description of a subject, or `None` if it can't be read
+    fn subject_properties(subject: Uuid) -> Option<Vec<(String, Property)>> {
+        check_available().ok()?;
+        let mut description: MaybeUninit<j::jack_description_t> = MaybeUninit::uninit();
+        unsafe {
+            if j::jack_get_properties(subject.raw(), description.as_mut_ptr()) < 0 {
+                return None;
+            }
+            description_to_vec_free(description.as_mut_ptr())
+        }
+    }
+
+    //helper to get the descriptions of all subjects
+    fn all_subject_properties() -> Vec<(Uuid, Vec<(String, Property)>)> {
+        let mut all = Vec::new();
+        if check_available().is_err() {
+            return all;
+        }
+        let mut descriptions: MaybeUninit<*mut j::jack_description_t> = MaybeUninit::uninit();
+        unsafe {
+            let cnt = j::jack_get_all_properties(descriptions.as_mut_ptr());
+            if cnt > 0 {
+                let descriptions = descriptions.assume_init();
+                all.reserve(cnt as usize);
+                for des in std::slice::from_raw_parts_mut(descriptions, cnt as usize) {
+                    let uuid = Uuid::from_raw(des.subject);
+                    if let Some(properties) = description_to_vec_free(des) {
+                        all.push((uuid, properties));
+                    }
+                }
+                j::jack_free(descriptions as _);
+            }
+        }
+        all
+    }
+
+    /// Get all the properties of a subject, as pairs of keys and `Property`s.
+    ///
+    /// The list is empty if the subject has no properties, if it can't be read or if the JACK
+    /// library does not support Metadata. Unlike `Client::property_get_subject`, the properties are
+    /// listed in the order that JACK reports them.
+    pub fn properties_of(subject: Uuid) -> Vec<(String, Property)> {
+        subject_properties(subject).unwrap_or_default()
+    }
+
+    /// Get all the properties of all the subjects that have Metadata on the JACK server.
+    ///
+    /// # Remarks
+    ///
+    /// * The list is empty if the JACK library does not support Metadata.
+    /// * The result may be large, it is copied before the memory allocated by JACK is freed.
+    pub fn all_properties() -> Vec<(Uuid, Vec<(String, Property)>)> {
+        all_subject_properties()
+    }
+
     impl Property {
         /// Create a property.
         ///
@@ -170,6 +495,46 @@ mod metadata {
         pub fn typ(&self) -> Option<&str> {
             self.typ.as_ref().map(|t| t.as_str())
         }
+
+        /// Create a plain text property, with the type `types::TEXT`.
+        pub fn text(value: &str) -> Self {
+            Self::new(value, Some(types::TEXT.into()))
+        }
+
+        /// Create an integer property, with the type `types::INTEGER`.
+        pub fn integer(value: i64) -> Self {
+            Self::new(value, Some(types::INTEGER.into()))
+        }
+
+        /// Create a property that holds a PNG image, base64 encoded with the type
+        /// `types::PNG_BASE64`.
+        ///
+        /// `Err(Error::MetadataValueTooLarge)` is returned if `png` is larger than
+        /// `ICON_PNG_MAX_SIZE`.
+        pub fn png(png: &[u8]) -> Result<Self, Error> {
+            if png.len() > ICON_PNG_MAX_SIZE {
+                return Err(Error::MetadataValueTooLarge);
+            }
+            Ok(Self::new(
+                base64_encode(png),
+                Some(types::PNG_BASE64.into()),
+            ))
+        }
+
+        /// Create a signal type property, see `keys::SIGNAL_TYPE`.
+        pub fn signal_type(signal_type: SignalType) -> Self {
+            Self::text(signal_type.as_str())
+        }
+
+        /// The value as an integer, or `None` if it is not one.
+        pub fn as_integer(&self) -> Option<i64> {
+            self.value.trim().parse().ok()
+        }
+
+        /// The decoded PNG image, or `None` if the value is not valid base64.
+        pub fn as_png(&self) -> Option<Vec<u8>> {
+            base64_decode(&self.value)
+        }
     }
 
     #[cfg(feature = "metadata")]
@@ -180,34 +545,8 @@ mod metadata {
         ///
         /// * `subject` - The subject of the property.
         /// * `key` - The key of the property, a URI String.
-        pub fn property_get(&self, subject: uuid, key: &str) -> Option<Property> {
-            let key = ffi::CString::new(key).expect("key to be convert to CString");
-            let mut value: MaybeUninit<*mut ::libc::c_char> = MaybeUninit::uninit();
-            let mut typ: MaybeUninit<*mut ::libc::c_char> = MaybeUninit::uninit();
-
-            unsafe {
-                if j::jack_get_property(subject, key.as_ptr(), value.as_mut_ptr(), typ.as_mut_ptr())
-                    == 0
-                {
-                    let value = value.assume_init();
-                    let typ = typ.assume_init();
-                    let r = Some(Property::new(
-                        ffi::CStr::from_ptr(value).to_str().unwrap(),
-                        if typ.is_null() {
-                            None
-                        } else {
-                            Some(ffi::CStr::from_ptr(typ).to_str().unwrap().to_string())
-                        },
-                    ));
-                    j::jack_free(value as _);
-                    if !typ.is_null() {
-                        j::jack_free(typ as _)
-                    }
-                    r
-                } else {
-                    None
-                }
-            }
+        pub fn property_get(&self, subject: Uuid, key: &str) -> Option<Property> {
+            get_raw(subject, key)
         }
 
         /// Get all the properties from a subject.
@@ -219,12 +558,8 @@ mod metadata {
         /// # Remarks
         ///
         /// * The Jack API calls this data a 'description'.
-        pub fn property_get_subject(&self, subject: uuid) -> Option<PropertyMap> {
-            let mut description: MaybeUninit<j::jack_description_t> = MaybeUninit::uninit();
-            unsafe {
-                let _ = j::jack_get_properties(subject, description.as_mut_ptr());
-                description_to_map_free(description.as_mut_ptr())
-            }
+        pub fn property_get_subject(&self, subject: Uuid) -> Option<PropertyMap> {
+            subject_properties(subject).map(|properties| properties.into_iter().collect())
         }
 
         /// Get all the properties from all the subjects with Metadata.
@@ -232,23 +567,11 @@ mod metadata {
         /// # Remarks
         ///
         /// * The Jack API calls these maps 'descriptions'.
-        pub fn property_get_all(&self) -> HashMap<uuid, PropertyMap> {
-            let mut map = HashMap::new();
-            let mut descriptions: MaybeUninit<*mut j::jack_description_t> = MaybeUninit::uninit();
-            unsafe {
-                let cnt = j::jack_get_all_properties(descriptions.as_mut_ptr());
-                if cnt > 0 {
-                    let descriptions = descriptions.assume_init();
-                    for des in std::slice::from_raw_parts_mut(descriptions, cnt as usize) {
-                        let uuid = (*des).subject;
-                        if let Some(dmap) = description_to_map_free(des) {
-                            map.insert(uuid, dmap);
-                        }
-                    }
-                    j::jack_free(descriptions as _);
-                }
-            }
-            map
+        pub fn property_get_all(&self) -> HashMap<Uuid, PropertyMap> {
+            all_subject_properties()
+                .into_iter()
+                .map(|(subject, properties)| (subject, properties.into_iter().collect()))
+                .collect()
         }
 
         /// Set a property.
@@ -259,33 +582,11 @@ mod metadata {
         /// * `key` - The key of the property. A URI string.
         pub fn property_set(
             &self,
-            subject: uuid,
+            subject: Uuid,
             key: &str,
             property: &Property,
         ) -> Result<(), Error> {
-            let key = ffi::CString::new(key).expect("to create cstring from key");
-            let value =
-                ffi::CString::new(property.value.as_str()).expect("to create cstring from value");
-            map_error(|| unsafe {
-                if let Some(t) = property.typ() {
-                    let t = ffi::CString::new(t).unwrap();
-                    j::jack_set_property(
-                        self.raw(),
-                        subject,
-                        key.as_ptr(),
-                        value.as_ptr(),
-                        t.as_ptr(),
-                    )
-                } else {
-                    j::jack_set_property(
-                        self.raw(),
-                        subject,
-                        key.as_ptr(),
-                        value.as_ptr(),
-                        ptr::null(),
-                    )
-                }
-            })
+            set_raw(self.raw(), subject, key, property)
         }
 
         /// Remove a single property from a subject.
@@ -294,9 +595,8 @@ mod metadata {
         ///
         /// * `subject` - The subject to remove all properties from.
         /// * `key` - The key of the property to be removed. A URI string.
-        pub fn property_remove(&self, subject: uuid, key: &str) -> Result<(), Error> {
-            let key = ffi::CString::new(key).expect("to create cstring from key");
-            map_error(|| unsafe { j::jack_remove_property(self.raw(), subject, key.as_ptr()) })
+        pub fn property_remove(&self, subject: Uuid, key: &str) -> Result<(), Error> {
+            remove_raw(self.raw(), subject, key)
         }
 
         /// Remove all properties from a subject.
@@ -304,9 +604,10 @@ mod metadata {
         /// # Arguments
         ///
         /// * `subject` - The subject to remove all properties from.
-        pub fn property_remove_subject(&self, subject: uuid) -> Result<(), Error> {
+        pub fn property_remove_subject(&self, subject: Uuid) -> Result<(), Error> {
+            check_available()?;
             unsafe {
-                if j::jack_remove_properties(self.raw(), subject) == -1 {
+                if j::jack_remove_properties(self.raw(), subject.raw()) == -1 {
                     Err(Error::UnknownError)
                 } else {
                     Ok(())
@@ -320,10 +621,216 @@ mod metadata {
         ///
         /// * **WARNING!!** This deletes all Metadata managed by a running JACK server.
         pub fn property_remove_all(&self) -> Result<(), Error> {
+            check_available()?;
             map_error(|| unsafe { j::jack_remove_all_properties(self.raw()) })
         }
     }
 
+    #[cfg(feature = "metadata")]
+    impl Client {
+        /// Set the property `key` of `subject` to `value`, with an optional type, see
+        /// `property_set`.
+        ///
+        /// `Err(Error::WeakFunctionNotFound)` is returned if the JACK library does not support
+        /// Metadata.
+        ///
+        /// # Example
+        /// ```no_run
+        /// let (client, _status) =
+        ///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+        /// let key = "http://jackaudio.org/metadata/pretty-name";
+        /// client.set_property(client.uuid(), key, "My Client", None).unwrap();
+        /// assert_eq!(
+        ///     client.get_property(client.uuid(), key),
+        ///     Some(("My Client".to_string(), None))
+        /// );
+        /// client.remove_property(client.uuid(), key).unwrap();
+        /// ```
+        pub fn set_property(
+            &self,
+            subject: Uuid,
+            key: &str,
+            value: &str,
+            typ: Option<&str>,
+        ) -> Result<(), Error> {
+            let property = Property::new(value, typ.map(str::to_string));
+            self.property_set(subject, key, &property)
+        }
+
+        /// Get the value and optional type of the property `key` of `subject`, see
+        /// `property_get`.
+        ///
+        /// `None` is returned if the property is not set or if the JACK library does not support
+        /// Metadata.
+        pub fn get_property(&self, subject: Uuid, key: &str) -> Option<(String, Option<String>)> {
+            self.property_get(subject, key)
+                .map(|property| (property.value, property.typ))
+        }
+
+        /// Remove the property `key` of `subject`, see `property_remove`.
+        ///
+        /// `Err(Error::WeakFunctionNotFound)` is returned if the JACK library does not support
+        /// Metadata.
+        pub fn remove_property(&self, subject: Uuid, key: &str) -> Result<(), Error> {
+            self.property_remove(subject, key)
+        }
+
+        /// Remove all the properties of `subject` and return how many were removed.
+        ///
+        /// This is useful to clean up after a port whose properties should not outlive it.
+        /// `Err(Error::WeakFunctionNotFound)` is returned if the JACK library does not support
+        /// Metadata.
+        pub fn remove_properties(&self, subject: Uuid) -> Result<usize, Error> {
+            check_available()?;
+            match unsafe { j::jack_remove_properties(self.raw(), subject.raw()) } {
+                -1 => Err(Error::UnknownError),
+                n => Ok(n as usize),
+            }
+        }
+
+        /// Remove all the properties of all the subjects on the JACK server.
+        ///
+        /// # Remarks
+        ///
+        /// * **WARNING!!** This also deletes the Metadata of other clients. To clean up after this
+        ///   client, use `remove_properties` on its subjects instead.
+        pub fn remove_all_properties_dangerous(&self) -> Result<(), Error> {
+            self.property_remove_all()
+        }
+
+        /// Set the pretty name of this client, the name that patchbays display instead of its
+        /// actual name, or remove it with `None`. See `keys::PRETTY_NAME`.
+        pub fn set_pretty_name(&self, name: Option<&str>) -> Result<(), Error> {
+            set_pretty_name_raw(self.raw(), self.uuid(), name)
+        }
+
+        /// The pretty name of this client, if it has one.
+        pub fn pretty_name(&self) -> Option<String> {
+            get_raw(self.uuid(), keys::PRETTY_NAME).map(|property| property.value)
+        }
+
+        /// Set the name of the freedesktop.org icon that session managers display for this
+        /// client. See `keys::ICON_NAME`.
+        pub fn set_icon_name(&self, name: &str) -> Result<(), Error> {
+            self.property_set(self.uuid(), keys::ICON_NAME, &Property::text(name))
+        }
+
+        /// The name of the icon of this client, if it has one.
+        pub fn icon_name(&self) -> Option<String> {
+            get_raw(self.uuid(), keys::ICON_NAME).map(|property| property.value)
+        }
+
+        /// Set the icon of this client from the contents of a PNG file, which is base64 encoded as
+        /// JACK expects. The image should be 32x32 for `IconSize::Small` and 96x96 for
+        /// `IconSize::Large`.
+        ///
+        /// `Err(Error::MetadataValueTooLarge)` is returned if `png` is larger than
+        /// `ICON_PNG_MAX_SIZE`.
+        ///
+        /// # Example
+        /// ```no_run
+        /// let (client, _status) =
+        ///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+        /// let png = std::fs::read("icon-32.png").unwrap();
+        /// client.set_icon_png(&png, jack::IconSize::Small).unwrap();
+        /// client.set_icon_name("audio-x-generic").unwrap();
+        /// ```
+        pub fn set_icon_png(&self, png: &[u8], size: IconSize) -> Result<(), Error> {
+            self.property_set(self.uuid(), size.key(), &Property::png(png)?)
+        }
+
+        /// The decoded PNG icon of the given size of this client, if it has a valid one.
+        pub fn icon_png(&self, size: IconSize) -> Option<Vec<u8>> {
+            get_raw(self.uuid(), size.key())?.as_png()
+        }
+    }
+
+    impl<PS> Port<PS> {
+        /// Set the pretty name of this port, the name that patchbays display instead of its actual
+        /// name, or remove it with `None`. See `keys::PRETTY_NAME`.
+        ///
+        /// The properties of the port are removed when it is unregistered with
+        /// `Client::unregister_port`.
+        ///
+        /// # Example
+        /// ```no_run
+        /// let (client, _status) =
+        ///     jack::Client::new("my_client", jack::ClientOptions::NO_START_SERVER).unwrap();
+        /// let port = client
+        ///     .register_port("out_1", jack::AudioOut::default())
+        ///     .unwrap();
+        /// port.set_pretty_name(Some("Left")).unwrap();
+        /// assert_eq!(port.pretty_name(), Some("Left".to_string()));
+        /// ```
+        pub fn set_pretty_name(&self, name: Option<&str>) -> Result<(), Error> {
+            self.check_client_life()?;
+            set_pretty_name_raw(self.client_ptr(), self.uuid(), name)
+        }
+
+        /// The pretty name of this port, if it has one.
+        pub fn pretty_name(&self) -> Option<String> {
+            self.check_client_life().ok()?;
+            get_raw(self.uuid(), keys::PRETTY_NAME).map(|property| property.value)
+        }
+
+        /// Set the position of this port among the ports of its client, so that patchbays list
+        /// them in a logical order instead of alphabetically. See `keys::ORDER`.
+        ///
+        /// Like its other properties, the order of the port is removed when it is unregistered
+        /// with `Client::unregister_port`.
+        pub fn set_order(&self, index: u32) -> Result<(), Error> {
+            self.check_client_life()?;
+            set_raw(
+                self.client_ptr(),
+                self.uuid(),
+                keys::ORDER,
+                &Property::integer(index.into()),
+            )
+        }
+
+        /// The position of this port among the ports of its client, if it is set to an integer.
+        pub fn order(&self) -> Option<u32> {
+            self.check_client_life().ok()?;
+            let order = get_raw(self.uuid(), keys::ORDER)?.as_integer()?;
+            std::convert::TryFrom::try_from(order).ok()
+        }
+
+        /// Set the kind of signal of this audio port. See `keys::SIGNAL_TYPE`.
+        pub fn set_signal_type(&self, signal_type: SignalType) -> Result<(), Error> {
+            self.check_client_life()?;
+            set_raw(
+                self.client_ptr(),
+                self.uuid(),
+                keys::SIGNAL_TYPE,
+                &Property::signal_type(signal_type),
+            )
+        }
+
+        /// The kind of signal of this port, if it is set to a known signal type.
+        pub fn signal_type(&self) -> Option<SignalType> {
+            self.check_client_life().ok()?;
+            SignalType::from_value(get_raw(self.uuid(), keys::SIGNAL_TYPE)?.value())
+        }
+
+        /// Set the name of the group, such as a stereo pair, that this port belongs to. See
+        /// `keys::PORT_GROUP`.
+        pub fn set_port_group(&self, group: &str) -> Result<(), Error> {
+            self.check_client_life()?;
+            set_raw(
+                self.client_ptr(),
+                self.uuid(),
+                keys::PORT_GROUP,
+                &Property::text(group),
+            )
+        }
+
+        /// The name of the group that this port belongs to, if it has one.
+        pub fn port_group(&self) -> Option<String> {
+            self.check_client_life().ok()?;
+            get_raw(self.uuid(), keys::PORT_GROUP).map(|property| property.value)
+        }
+    }
+
     impl<'a> From<&PropertyChange<'a>> for PropertyChangeOwned {
         fn from(foo: &PropertyChange<'a>) -> Self {
             match foo {
@@ -347,8 +854,61 @@ mod metadata {
     mod tests {
         use super::*;
         use crate::client::*;
+        use crate::Uuid;
         use std::sync::mpsc::{channel, Sender};
 
+        #[test]
+        fn property_strings_with_nul_are_rejected() {
+            let (c, _) = Client::new("client_pswnar", ClientOptions::NO_START_SERVER).unwrap();
+            if !is_available() {
+                return;
+            }
+            let invalid = |s: &str| Err(Error::InvalidName(s.to_string()));
+            let key = keys::PRETTY_NAME;
+            assert_eq!(c.set_property(c.uuid(), "k\0", "v", None), invalid("k\0"));
+            assert_eq!(c.set_property(c.uuid(), key, "v\0", None), invalid("v\0"));
+            assert_eq!(
+                c.set_property(c.uuid(), key, "v", Some("t\0")),
+                invalid("t\0")
+            );
+            assert_eq!(c.set_pretty_name(Some("Pretty\0")), invalid("Pretty\0"));
+            assert_eq!(c.get_property(c.uuid(), "k\0"), None);
+            assert_eq!(c.remove_property(c.uuid(), "k\0"), invalid("k\0"));
+            assert!(properties_of(c.uuid()).is_empty());
+        }
+
+        #[test]
+        fn metadata_calls_match_availability() {
+            let (mut c, _) = Client::new("client_mcma", ClientOptions::NO_START_SERVER).unwrap();
+            let key = keys::PRETTY_NAME;
+            if is_available() {
+                assert_eq!(c.set_property(c.uuid(), key, "Pretty", None), Ok(()));
+                assert!(c.get_property(c.uuid(), key).is_some());
+                assert_eq!(properties_of(c.uuid()).len(), 1);
+                assert_eq!(c.remove_properties(c.uuid()), Ok(1));
+                return;
+            }
+            let unavailable = Err(Error::WeakFunctionNotFound);
+            assert_eq!(c.set_property(c.uuid(), key, "Pretty", None), unavailable);
+            assert_eq!(c.get_property(c.uuid(), key), None);
+            assert_eq!(c.remove_property(c.uuid(), key), unavailable);
+            assert_eq!(c.remove_properties(c.uuid()).map(|_| ()), unavailable);
+            assert_eq!(c.set_pretty_name(Some("Pretty")), unavailable);
+            assert!(properties_of(c.uuid()).is_empty());
+            assert!(all_properties().is_empty());
+            assert_eq!(
+                c.register_property_change_handler(ClosurePropertyChangeHandler::new(|_| {})),
+                unavailable
+            );
+            let (sender, _receiver) = channel();
+            let ac = c
+                .activate_async(PropertyChangeForwarder(sender), ())
+                .unwrap();
+            assert!(ac
+                .unavailable_callbacks()
+                .contains(CallbackSelection::PROPERTY_CHANGE));
+        }
+
         #[test]
         fn can_set_and_get() {
             let (c, _) = Client::new("dummy", ClientOptions::NO_START_SERVER).unwrap();
@@ -390,6 +950,270 @@ mod metadata {
             assert_eq!(sub.get(&"asdf".to_string()), None);
         }
 
+        #[test]
+        fn can_round_trip_own_property() {
+            let (c, _) = Client::new("client_rtop", ClientOptions::NO_START_SERVER).unwrap();
+            let key = "http://jackaudio.org/metadata/pretty-name";
+            assert_eq!(c.get_property(c.uuid(), key), None);
+
+            assert_eq!(c.set_property(c.uuid(), key, "Pretty", None), Ok(()));
+            assert_eq!(
+                c.get_property(c.uuid(), key),
+                Some(("Pretty".to_string(), None))
+            );
+
+            assert_eq!(
+                c.set_property(c.uuid(), key, "Prettier", Some("text/plain")),
+                Ok(())
+            );
+            assert_eq!(
+                c.get_property(c.uuid(), key),
+                Some(("Prettier".to_string(), Some("text/plain".to_string())))
+            );
+
+            assert_eq!(c.remove_property(c.uuid(), key), Ok(()));
+            assert_eq!(c.get_property(c.uuid(), key), None);
+            assert_eq!(c.remove_property(c.uuid(), key), Err(Error::UnknownError));
+        }
+
+        #[test]
+        fn can_enumerate_properties() {
+            let (c1, _) = Client::new("client_cep1", ClientOptions::NO_START_SERVER).unwrap();
+            let (c2, _) = Client::new("client_cep2", ClientOptions::NO_START_SERVER).unwrap();
+            let props: Vec<(String, Property)> = (0..5)
+                .map(|i| {
+                    let typ = if i % 2 == 0 {
+                        Some("text/plain".into())
+                    } else {
+                        None
+                    };
+                    (
+                        format!("key{}", i),
+                        Property::new(format!("value{}", i), typ),
+                    )
+                })
+                .collect();
+            for (key, prop) in props.iter() {
+                assert_eq!(c1.property_set(c1.uuid(), key, prop), Ok(()));
+            }
+            let other = Property::new("other", None);
+            assert_eq!(c1.property_set(c2.uuid(), "key0", &other), Ok(()));
+
+            let mut of_c1 = properties_of(c1.uuid());
+            of_c1.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(of_c1, props);
+            assert_eq!(properties_of(c2.uuid()), [("key0".to_string(), other)]);
+
+            let all = all_properties();
+            let mut all_of_c1 = all
+                .iter()
+                .find(|(subject, _)| *subject == c1.uuid())
+                .unwrap()
+                .1
+                .clone();
+            all_of_c1.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(all_of_c1, props);
+            assert!(all.iter().any(|(subject, _)| *subject == c2.uuid()));
+
+            assert_eq!(Ok(()), c1.property_remove_subject(c1.uuid()));
+            assert_eq!(Ok(()), c1.property_remove_subject(c2.uuid()));
+            assert!(properties_of(c1.uuid()).is_empty());
+        }
+
+        #[test]
+        fn can_count_removed_properties() {
+            let (c, _) = Client::new("client_ccrp", ClientOptions::NO_START_SERVER).unwrap();
+            for key in ["a", "b", "c"].iter() {
+                assert_eq!(c.set_property(c.uuid(), key, "value", None), Ok(()));
+            }
+            assert_eq!(c.remove_properties(c.uuid()), Ok(3));
+            assert_eq!(c.remove_properties(c.uuid()), Ok(0));
+            assert!(properties_of(c.uuid()).is_empty());
+        }
+
+        #[test]
+        fn can_set_pretty_names() {
+            let (c, _) = Client::new("client_cspn", ClientOptions::NO_START_SERVER).unwrap();
+            assert_eq!(c.pretty_name(), None);
+            assert_eq!(c.set_pretty_name(Some("Pretty Client")), Ok(()));
+            assert_eq!(c.pretty_name(), Some("Pretty Client".to_string()));
+            assert_eq!(c.set_pretty_name(None), Ok(()));
+            assert_eq!(c.set_pretty_name(None), Ok(()));
+            assert_eq!(c.pretty_name(), None);
+
+            let port = c.register_port("out", crate::AudioOut::default()).unwrap();
+            let uuid = port.uuid();
+            assert_eq!(port.pretty_name(), None);
+            assert_eq!(port.set_pretty_name(Some("Left")), Ok(()));
+            assert_eq!(port.pretty_name(), Some("Left".to_string()));
+            assert_eq!(
+                c.get_property(uuid, keys::PRETTY_NAME),
+                Some(("Left".to_string(), Some("text/plain".to_string())))
+            );
+            assert_eq!(port.set_pretty_name(None), Ok(()));
+            assert_eq!(port.pretty_name(), None);
+
+            assert_eq!(port.set_pretty_name(Some("Right")), Ok(()));
+            assert_eq!(c.unregister_port(port), Ok(()));
+            assert!(properties_of(uuid).is_empty());
+        }
+
+        #[test]
+        fn can_set_pretty_name_of_port_registered_before_metadata_is_used() {
+            let (c, _) = Client::new("client_cspnoprbmiu", ClientOptions::NO_START_SERVER).unwrap();
+            let port = c
+                .register_port("early", crate::AudioOut::default())
+                .unwrap();
+            // The first Metadata call of the client is on the port.
+            let res = port.set_pretty_name(Some("Early"));
+            if !is_available() {
+                assert_eq!(res, Err(Error::WeakFunctionNotFound));
+                return;
+            }
+            assert_eq!(res, Ok(()));
+            assert_eq!(port.pretty_name(), Some("Early".to_string()));
+            assert_eq!(c.unregister_port(port), Ok(()));
+        }
+
+        #[test]
+        fn metadata_availability_does_not_depend_on_call_order() {
+            let (c, _) = Client::new("client_madndoco", ClientOptions::NO_START_SERVER).unwrap();
+            let before = c
+                .register_port("before", crate::AudioOut::default())
+                .unwrap();
+            let first = before.set_pretty_name(Some("Before"));
+            let available = is_available();
+            let after = c
+                .register_port("after", crate::AudioOut::default())
+                .unwrap();
+            let second = after.set_pretty_name(Some("After"));
+            assert_eq!(first, second);
+            assert_eq!(first.is_ok(), available);
+            assert_eq!(is_available(), available);
+            assert_eq!(crate::server_quirks().metadata_available, available);
+            if !available {
+                assert_eq!(first, Err(Error::WeakFunctionNotFound));
+                assert_eq!(c.set_pretty_name(Some("Client")), first);
+            }
+        }
+
+        #[test]
+        fn can_set_port_order() {
+            let (c, _) = Client::new("client_cspo", ClientOptions::NO_START_SERVER).unwrap();
+            let ports: Vec<_> = (0..4)
+                .map(|i| {
+                    c.register_port(&format!("out_{}", i), crate::AudioOut::default())
+                        .unwrap()
+                })
+                .collect();
+            for (i, port) in ports.iter().enumerate().rev() {
+                assert_eq!(port.order(), None);
+                assert_eq!(port.set_order(i as u32), Ok(()));
+            }
+            for (i, port) in ports.iter().enumerate() {
+                assert_eq!(port.order(), Some(i as u32));
+                assert_eq!(
+                    properties_of(port.uuid()),
+                    [(keys::ORDER.to_string(), Property::integer(i as i64))]
+                );
+            }
+
+            let uuid = ports[0].uuid();
+            for port in ports {
+                assert_eq!(c.unregister_port(port), Ok(()));
+            }
+            assert!(properties_of(uuid).is_empty());
+        }
+
+        #[test]
+        fn base64_round_trips() {
+            let cases: [(&[u8], &str); 5] = [
+                (b"", ""),
+                (b"f", "Zg=="),
+                (b"fo", "Zm8="),
+                (b"foo", "Zm9v"),
+                (b"\x89PNG\r\n\x1a\n\xff", "iVBORw0KGgr/"),
+            ];
+            for (data, encoded) in cases.iter() {
+                assert_eq!(base64_encode(data), *encoded);
+                assert_eq!(base64_decode(encoded).as_deref(), Some(*data));
+            }
+            assert_eq!(base64_decode("Zm9v\nYmFy"), Some(b"foobar".to_vec()));
+            assert_eq!(base64_decode("Zg=a"), None);
+            assert_eq!(base64_decode("Z!=="), None);
+        }
+
+        #[test]
+        fn typed_properties_have_types() {
+            assert_eq!(Property::text("Left").typ(), Some("text/plain"));
+            assert_eq!(Property::text("Left").value(), "Left");
+
+            let order = Property::integer(3);
+            assert_eq!(
+                order.typ(),
+                Some("http://www.w3.org/2001/XMLSchema#integer")
+            );
+            assert_eq!(order.value(), "3");
+            assert_eq!(order.as_integer(), Some(3));
+            assert_eq!(Property::text("three").as_integer(), None);
+
+            let png = Property::png(b"\x89PNG").unwrap();
+            assert_eq!(png.typ(), Some("image/png;base64"));
+            assert_eq!(png.value(), "iVBORw==");
+            assert_eq!(png.as_png(), Some(b"\x89PNG".to_vec()));
+            assert_eq!(
+                Property::png(&vec![0; ICON_PNG_MAX_SIZE + 1]),
+                Err(Error::MetadataValueTooLarge)
+            );
+
+            for signal_type in [SignalType::Audio, SignalType::Cv].iter() {
+                let property = Property::signal_type(*signal_type);
+                assert_eq!(property.typ(), Some("text/plain"));
+                assert_eq!(SignalType::from_value(property.value()), Some(*signal_type));
+            }
+            assert_eq!(Property::signal_type(SignalType::Cv).value(), "CV");
+            assert_eq!(SignalType::from_value("MIDI"), None);
+        }
+
+        #[test]
+        fn can_set_typed_port_properties() {
+            let (c, _) = Client::new("client_cstpp", ClientOptions::NO_START_SERVER).unwrap();
+            let port = c.register_port("cv", crate::AudioOut::default()).unwrap();
+            assert_eq!(port.signal_type(), None);
+            assert_eq!(port.set_signal_type(SignalType::Cv), Ok(()));
+            assert_eq!(port.signal_type(), Some(SignalType::Cv));
+            assert_eq!(port.set_port_group("modulation"), Ok(()));
+            assert_eq!(port.port_group(), Some("modulation".to_string()));
+
+            // Custom keys remain available through the raw API.
+            let key = "urn:example:custom";
+            assert_eq!(c.set_property(port.uuid(), key, "value", None), Ok(()));
+            assert_eq!(properties_of(port.uuid()).len(), 3);
+            assert_eq!(c.unregister_port(port), Ok(()));
+        }
+
+        #[test]
+        fn can_set_icons() {
+            let (c, _) = Client::new("client_csi", ClientOptions::NO_START_SERVER).unwrap();
+            let png = b"\x89PNG\r\n\x1a\nnot really an image";
+            assert_eq!(c.icon_png(IconSize::Small), None);
+            assert_eq!(c.set_icon_png(png, IconSize::Small), Ok(()));
+            assert_eq!(c.icon_png(IconSize::Small), Some(png.to_vec()));
+            assert_eq!(c.icon_png(IconSize::Large), None);
+            assert_eq!(
+                c.get_property(c.uuid(), keys::ICON_SMALL).unwrap().1,
+                Some("image/png;base64".to_string())
+            );
+            assert_eq!(
+                c.set_icon_png(&vec![0; ICON_PNG_MAX_SIZE + 1], IconSize::Large),
+                Err(Error::MetadataValueTooLarge)
+            );
+
+            assert_eq!(c.set_icon_name("audio-x-generic"), Ok(()));
+            assert_eq!(c.icon_name(), Some("audio-x-generic".to_string()));
+            assert_eq!(c.remove_properties(c.uuid()), Ok(2));
+        }
+
         #[test]
         fn can_remove() {
             let (c1, _) = Client::new("client1", ClientOptions::NO_START_SERVER).unwrap();
@@ -547,6 +1371,63 @@ mod metadata {
             );
         }
 
+        struct PropertyChangeForwarder(Sender<(Uuid, Option<String>, PropertyChangeKind)>);
+
+        impl NotificationHandler for PropertyChangeForwarder {
+            fn property_change(
+                &mut self,
+                _: &Client,
+                subject: Uuid,
+                key: Option<&str>,
+                change: PropertyChangeKind,
+            ) {
+                let _ = self.0.send((subject, key.map(str::to_string), change));
+            }
+        }
+
+        #[test]
+        fn notification_handler_gets_property_changes() {
+            let timeout = std::time::Duration::from_millis(100);
+            let (c1, _) = Client::new("client_nhpc1", ClientOptions::NO_START_SERVER).unwrap();
+            let (c2, _) = Client::new("client_nhpc2", ClientOptions::NO_START_SERVER).unwrap();
+            let (sender, receiver) = channel();
+            let ac = c1
+                .activate_async(PropertyChangeForwarder(sender), ())
+                .unwrap();
+            assert!(!ac
+                .unavailable_callbacks()
+                .contains(CallbackSelection::PROPERTY_CHANGE));
+
+            assert_eq!(c2.set_property(c2.uuid(), "blah", "foo", None), Ok(()));
+            assert_eq!(
+                receiver.recv_timeout(timeout),
+                Ok((
+                    c2.uuid(),
+                    Some("blah".to_string()),
+                    PropertyChangeKind::Created
+                ))
+            );
+            assert_eq!(c2.set_property(c2.uuid(), "blah", "bar", None), Ok(()));
+            assert_eq!(
+                receiver.recv_timeout(timeout),
+                Ok((
+                    c2.uuid(),
+                    Some("blah".to_string()),
+                    PropertyChangeKind::Changed
+                ))
+            );
+            assert_eq!(c2.remove_property(c2.uuid(), "blah"), Ok(()));
+            assert_eq!(
+                receiver.recv_timeout(timeout),
+                Ok((
+                    c2.uuid(),
+                    Some("blah".to_string()),
+                    PropertyChangeKind::Deleted
+                ))
+            );
+            ac.deactivate().unwrap();
+        }
+
         #[test]
         #[should_panic]
         fn double_register() {
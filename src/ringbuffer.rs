@@ -1,6 +1,5 @@
 use jack_sys as j;
-use std::mem;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 /// A lock-free ringbuffer. The key attribute of a ringbuffer is that it can be safely accessed by
 /// two threads simultaneously, one reading from the buffer and the other writing to it - without
@@ -24,6 +23,9 @@ pub struct RingBuffer(*mut j::jack_ringbuffer_t);
 
 impl RingBuffer {
     /// Allocates a ringbuffer of a specified size.
+    ///
+    /// Like the C library, the size is rounded up to the next power of two, see `size`. One byte
+    /// is always kept free, so the ringbuffer holds at most `size() - 1` bytes.
     pub fn new(size: usize) -> Result<Self, crate::Error> {
         let insize = size as libc::size_t;
         let handle = unsafe { j::jack_ringbuffer_create(insize) };
@@ -35,9 +37,18 @@ impl RingBuffer {
         Ok(RingBuffer(handle))
     }
 
-    /// Lock a ringbuffer data block into memory.
-    pub fn mlock(&mut self) {
-        unsafe { j::jack_ringbuffer_mlock(self.0) };
+    /// Lock the data block of the ringbuffer into memory, so that it is never swapped out. This
+    /// should be done before the ringbuffer is used from a real-time thread.
+    ///
+    /// Returns `Err(Error::RingbufferMlockFailed)` if the memory can't be locked, usually because
+    /// it would exceed the limit on locked memory of the process. The limit is shown by `ulimit -l`
+    /// and is typically raised for the members of the `audio` group, see
+    /// `/etc/security/limits.conf`.
+    pub fn mlock(&mut self) -> Result<(), crate::Error> {
+        match unsafe { j::jack_ringbuffer_mlock(self.0) } {
+            0 => Ok(()),
+            _ => Err(crate::Error::RingbufferMlockFailed),
+        }
     }
 
     /// Resets the ring buffer, making an empty buffer.
@@ -45,11 +56,65 @@ impl RingBuffer {
         unsafe { j::jack_ringbuffer_reset(self.0) };
     }
 
+    /// The size of the ringbuffer in bytes, which is the size it was created with rounded up to a
+    /// power of two.
+    pub fn size(&self) -> usize {
+        unsafe { raw_size(self.0) }
+    }
+
+    /// Return the number of bytes available for reading.
+    pub fn read_space(&self) -> usize {
+        unsafe { j::jack_ringbuffer_read_space(self.0) as usize }
+    }
+
+    /// Return the number of bytes available for writing.
+    pub fn write_space(&self) -> usize {
+        unsafe { j::jack_ringbuffer_write_space(self.0) as usize }
+    }
+
+    /// Read data from the ringbuffer. Returns the number of bytes read, which may range from 0 to
+    /// `buf.len()`.
+    ///
+    /// To read from another thread than the one that writes, split the ringbuffer with
+    /// `into_reader_writer`.
+    pub fn read(&mut self, buf: &mut [u8]) -> usize {
+        unsafe { raw_read(self.0, buf) }
+    }
+
+    /// Write data into the ringbuffer. Returns the number of bytes written, which may range from 0
+    /// to `buf.len()`.
+    pub fn write(&mut self, buf: &[u8]) -> usize {
+        unsafe { raw_write(self.0, buf) }
+    }
+
     /// Create a reader and writer, to use the ring buffer.
     pub fn into_reader_writer(self) -> (RingBufferReader, RingBufferWriter) {
-        let out = unsafe { (RingBufferReader::new(self.0), RingBufferWriter::new(self.0)) };
-        mem::forget(self);
-        out
+        let ringbuffer = Arc::new(self);
+        let reader = RingBufferReader {
+            ringbuffer: ringbuffer.clone(),
+        };
+        (reader, RingBufferWriter { ringbuffer })
+    }
+
+    /// Split the ring buffer into its writing and reading halves, to hand them to the threads that
+    /// write and read. The halves can't be cloned, so there is only ever a single writer and a
+    /// single reader.
+    ///
+    /// The ring buffer is freed once both halves are dropped. Freeing is not a real-time
+    /// operation, so the half that is used by a real-time thread should not be the last to go.
+    ///
+    /// # Example
+    /// ```
+    /// let (mut writer, mut reader) = jack::RingBuffer::new(1024).unwrap().into_halves();
+    /// std::thread::spawn(move || writer.write_buffer(&[1, 2, 3]))
+    ///     .join()
+    ///     .unwrap();
+    /// let mut buf = [0; 3];
+    /// assert_eq!(reader.read_buffer(&mut buf), 3);
+    /// ```
+    pub fn into_halves(self) -> (RingBufferWriter, RingBufferReader) {
+        let (reader, writer) = self.into_reader_writer();
+        (writer, reader)
     }
 
     /// Re-create the ring buffer object from reader and writer. useful if you need to call reset.
@@ -59,19 +124,18 @@ impl RingBuffer {
     ///
     /// panics if the reader and the writer were created from different RingBuffer objects.
     pub fn from_reader_writer(r: RingBufferReader, w: RingBufferWriter) -> Self {
-        if r.ringbuffer_handle != w.ringbuffer_handle {
-            // drops will be valid during unwinding - assuming that all reader/writer pairs are
-            // consisitent.
+        if !Arc::ptr_eq(&r.ringbuffer, &w.ringbuffer) {
+            // drops will be valid during unwinding, each half frees its own ringbuffer.
             panic!("mismatching read and write handles!")
         }
 
-        // The next 3 lines transfer ownership of the ringbuffer from both reader and writer to the
-        // new buffer object.
-        let handle = RingBuffer(r.ringbuffer_handle);
-        mem::forget(r);
-        mem::forget(w);
-
-        handle
+        // The reader and writer are the only owners, so the ringbuffer is unwrapped once the
+        // writer is gone.
+        drop(w);
+        match Arc::try_unwrap(r.ringbuffer) {
+            Ok(ringbuffer) => ringbuffer,
+            Err(_) => unreachable!("the ringbuffer is shared by more than a reader and a writer"),
+        }
     }
 }
 
@@ -89,34 +153,25 @@ unsafe impl Sync for RingBuffer {}
 
 /// Read end of the ring buffer. Can only be used from one thread (can be different from the write
 /// thread).
+///
+/// It only exposes the reading side of the ring buffer, and can't be cloned.
 pub struct RingBufferReader {
-    ringbuffer_handle: *mut j::jack_ringbuffer_t,
-    /// A marker to check if both halves of the ringbuffer are live. Destroying a ringbuffer is not
-    /// a realtime operation.
-    both_live: AtomicBool,
+    /// Shared with the writer, the ringbuffer is freed once both halves are dropped. Destroying a
+    /// ringbuffer is not a realtime operation.
+    ringbuffer: Arc<RingBuffer>,
 }
 
-unsafe impl Send for RingBufferReader {}
-unsafe impl Sync for RingBufferReader {}
-
 /// Write end of the ring buffer. Can only be used from one thread (can be a different from the read
 /// thread).
+///
+/// It only exposes the writing side of the ring buffer, and can't be cloned.
 pub struct RingBufferWriter {
-    ringbuffer_handle: *mut j::jack_ringbuffer_t,
-    both_live: AtomicBool,
+    ringbuffer: Arc<RingBuffer>,
 }
 
-unsafe impl Send for RingBufferWriter {}
-unsafe impl Sync for RingBufferWriter {}
-
 impl RingBufferReader {
-    // safety: this method must be called as part of the splitting of the ringbuffer into 2
-    // channels.
-    unsafe fn new(raw: *mut j::jack_ringbuffer_t) -> Self {
-        RingBufferReader {
-            ringbuffer_handle: raw,
-            both_live: AtomicBool::new(true),
-        }
+    pub(crate) fn ringbuffer_handle(&self) -> *mut j::jack_ringbuffer_t {
+        self.ringbuffer.0
     }
 
     /// Fill a data structure with a description of the current readable data held in the
@@ -131,7 +186,7 @@ impl RingBufferReader {
         ];
         let vecstart = &mut vec[0] as *mut j::jack_ringbuffer_data_t;
 
-        unsafe { j::jack_ringbuffer_get_read_vector(self.ringbuffer_handle, vecstart) };
+        unsafe { j::jack_ringbuffer_get_read_vector(self.ringbuffer_handle(), vecstart) };
 
         let view1 = vec[0];
         let view2 = vec[1];
@@ -152,18 +207,44 @@ impl RingBufferReader {
         (view1, view2)
     }
 
+    /// Borrow the data that is ready to be read without copying it. The data is consumed when the
+    /// returned guard is dropped, up to the amount passed to `ReadVector::consume`.
+    ///
+    /// Dropping the guard without consuming anything peeks at the data.
+    ///
+    /// # Example
+    /// ```
+    /// use std::io::Write;
+    ///
+    /// let (mut reader, mut writer) = jack::RingBuffer::new(1024).unwrap().into_reader_writer();
+    /// writer.write_buffer(&[1, 2, 3]);
+    ///
+    /// let mut file = Vec::new();
+    /// let mut data = reader.read_vector();
+    /// let (first, second) = data.as_slices();
+    /// file.write_all(first).unwrap();
+    /// file.write_all(second).unwrap();
+    /// data.consume(data.len());
+    /// drop(data);
+    /// assert_eq!(file, [1, 2, 3]);
+    /// assert_eq!(reader.space(), 0);
+    /// ```
+    pub fn read_vector(&mut self) -> ReadVector<'_> {
+        let mut vec = [j::jack_ringbuffer_data_t::default(); 2];
+        unsafe { j::jack_ringbuffer_get_read_vector(self.ringbuffer_handle(), vec.as_mut_ptr()) };
+        // The data stays valid until the read pointer is advanced, which requires the reader.
+        let slices = unsafe { (data_slice(vec[0]), data_slice(vec[1])) };
+        ReadVector {
+            reader: self,
+            slices,
+            consumed: 0,
+        }
+    }
+
     /// Read data from the ringbuffer.  Returns: the number of bytes read, which may range from 0 to
     /// buf.len().
     pub fn read_buffer(&mut self, buf: &mut [u8]) -> usize {
-        if buf.is_empty() {
-            return 0;
-        }
-
-        let insize: libc::size_t = buf.len() as libc::size_t;
-        let bufstart = &mut buf[0] as *mut _ as *mut libc::c_char;
-
-        let read = unsafe { j::jack_ringbuffer_read(self.ringbuffer_handle, bufstart, insize) };
-        read as usize
+        unsafe { raw_read(self.ringbuffer_handle(), buf) }
     }
 
     /// Read data from the ringbuffer. Opposed to read_buffer() this function does not move the read
@@ -179,7 +260,7 @@ impl RingBufferReader {
         let insize: libc::size_t = buf.len() as libc::size_t;
         let bufstart = &mut buf[0] as *mut _ as *mut libc::c_char;
 
-        let read = unsafe { j::jack_ringbuffer_peek(self.ringbuffer_handle, bufstart, insize) };
+        let read = unsafe { j::jack_ringbuffer_peek(self.ringbuffer_handle(), bufstart, insize) };
         read as usize
     }
 
@@ -187,12 +268,17 @@ impl RingBufferReader {
     /// pointer.
     pub fn advance(&mut self, cnt: usize) {
         let incnt = cnt as libc::size_t;
-        unsafe { j::jack_ringbuffer_read_advance(self.ringbuffer_handle, incnt) };
+        unsafe { j::jack_ringbuffer_read_advance(self.ringbuffer_handle(), incnt) };
     }
 
     /// Return the number of bytes available for reading.
     pub fn space(&self) -> usize {
-        unsafe { j::jack_ringbuffer_read_space(self.ringbuffer_handle) as usize }
+        unsafe { j::jack_ringbuffer_read_space(self.ringbuffer_handle()) as usize }
+    }
+
+    /// The size of the ringbuffer in bytes, see `RingBuffer::size`.
+    pub fn size(&self) -> usize {
+        unsafe { raw_size(self.ringbuffer_handle()) }
     }
 
     /// Iterator that goes over all the data available to read.
@@ -211,54 +297,32 @@ impl std::io::Read for RingBufferReader {
     }
 }
 
-impl Drop for RingBufferReader {
-    fn drop(&mut self) {
-        match self
-            .both_live
-            .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
-        {
-            Ok(false) | Err(false) => {
-                drop(RingBuffer(self.ringbuffer_handle));
-            }
-            _ => (),
-        }
-    }
-}
-
 impl RingBufferWriter {
-    // safety: this method must be called as part of the splitting of the ringbuffer into 2
-    // channels.
-    unsafe fn new(raw: *mut j::jack_ringbuffer_t) -> Self {
-        RingBufferWriter {
-            ringbuffer_handle: raw,
-            both_live: AtomicBool::new(true),
-        }
+    pub(crate) fn ringbuffer_handle(&self) -> *mut j::jack_ringbuffer_t {
+        self.ringbuffer.0
     }
 
     /// Write data into the ringbuffer.  Returns: The number of bytes written, which may range from
     /// 0 to buf.len()
     pub fn write_buffer(&mut self, buf: &[u8]) -> usize {
-        if buf.is_empty() {
-            return 0;
-        }
-
-        let insize: libc::size_t = buf.len() as libc::size_t;
-        let bufstart = &buf[0] as *const _ as *const libc::c_char;
-
-        let read = unsafe { j::jack_ringbuffer_write(self.ringbuffer_handle, bufstart, insize) };
-        read as usize
+        unsafe { raw_write(self.ringbuffer_handle(), buf) }
     }
 
     /// Advance the write pointer. use this after peek_iter or get_vector to advance the buffer
     /// pointer.
     pub fn advance(&mut self, cnt: usize) {
         let incnt = cnt as libc::size_t;
-        unsafe { j::jack_ringbuffer_write_advance(self.ringbuffer_handle, incnt) };
+        unsafe { j::jack_ringbuffer_write_advance(self.ringbuffer_handle(), incnt) };
     }
 
     /// Return the number of bytes available for writing.
     pub fn space(&mut self) -> usize {
-        unsafe { j::jack_ringbuffer_write_space(self.ringbuffer_handle) as usize }
+        unsafe { j::jack_ringbuffer_write_space(self.ringbuffer_handle()) as usize }
+    }
+
+    /// The size of the ringbuffer in bytes, see `RingBuffer::size`.
+    pub fn size(&self) -> usize {
+        unsafe { raw_size(self.ringbuffer_handle()) }
     }
 
     /// Return a pair of slices of the current writable space in the ringbuffer. two slices are
@@ -271,7 +335,7 @@ impl RingBufferWriter {
         ];
         let vecstart = &mut vec[0] as *mut j::jack_ringbuffer_data_t;
 
-        unsafe { j::jack_ringbuffer_get_write_vector(self.ringbuffer_handle, vecstart) };
+        unsafe { j::jack_ringbuffer_get_write_vector(self.ringbuffer_handle(), vecstart) };
 
         let view1 = vec[0];
         let view2 = vec[1];
@@ -292,6 +356,33 @@ impl RingBufferWriter {
         (view1, view2)
     }
 
+    /// Borrow the space that is available for writing, to write into it without an intermediate
+    /// copy. The data is made available to the reader when the returned guard is dropped, up to the
+    /// amount passed to `WriteVector::commit`.
+    ///
+    /// # Example
+    /// ```
+    /// let (mut reader, mut writer) = jack::RingBuffer::new(1024).unwrap().into_reader_writer();
+    /// let mut space = writer.write_vector();
+    /// let (first, _second) = space.as_mut_slices();
+    /// first[..3].copy_from_slice(&[1, 2, 3]);
+    /// space.commit(3);
+    /// drop(space);
+    /// assert_eq!(reader.space(), 3);
+    /// ```
+    pub fn write_vector(&mut self) -> WriteVector<'_> {
+        let mut vec = [j::jack_ringbuffer_data_t::default(); 2];
+        unsafe { j::jack_ringbuffer_get_write_vector(self.ringbuffer_handle(), vec.as_mut_ptr()) };
+        // The space is only written to through the guard, and is not read until the write pointer
+        // is advanced, which requires the writer.
+        let slices = unsafe { (data_slice_mut(vec[0]), data_slice_mut(vec[1])) };
+        WriteVector {
+            writer: self,
+            slices,
+            committed: 0,
+        }
+    }
+
     /// Iterator that goes over all the data available to write.
     pub fn peek_iter(
         &'_ mut self,
@@ -312,20 +403,159 @@ impl std::io::Write for RingBufferWriter {
     }
 }
 
-impl Drop for RingBufferWriter {
+/// The data that is ready to be read from a ring buffer, see `RingBufferReader::read_vector`.
+///
+/// The data may be split across the end of the ring buffer, in which case the second slice is the
+/// continuation of the first.
+pub struct ReadVector<'a> {
+    reader: &'a mut RingBufferReader,
+    slices: (&'a [u8], &'a [u8]),
+    consumed: usize,
+}
+
+impl<'a> ReadVector<'a> {
+    /// The data as two slices, the second slice is empty unless the data wraps around the end of
+    /// the ring buffer.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        self.slices
+    }
+
+    /// The number of bytes that are ready to be read.
+    pub fn len(&self) -> usize {
+        self.slices.0.len() + self.slices.1.len()
+    }
+
+    /// Returns `true` if there is no data to read.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Mark `cnt` more bytes as read, they are consumed when the guard is dropped.
+    ///
+    /// # Panics
+    /// Panics if more bytes are consumed than there are in total.
+    pub fn consume(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.len() - self.consumed,
+            "consumed more bytes than are available"
+        );
+        self.consumed += cnt;
+    }
+}
+
+/// Advances the read pointer by the consumed bytes.
+impl<'a> Drop for ReadVector<'a> {
     fn drop(&mut self) {
-        match self
-            .both_live
-            .compare_exchange(true, false, Ordering::SeqCst, Ordering::SeqCst)
-        {
-            Ok(false) | Err(false) => {
-                drop(RingBuffer(self.ringbuffer_handle));
-            }
-            _ => (),
+        if self.consumed > 0 {
+            self.reader.advance(self.consumed);
+        }
+    }
+}
+
+impl<'a> std::fmt::Debug for ReadVector<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ReadVector")
+            .field("len", &self.len())
+            .field("consumed", &self.consumed)
+            .finish()
+    }
+}
+
+/// The space that is available for writing to a ring buffer, see `RingBufferWriter::write_vector`.
+///
+/// The space may be split across the end of the ring buffer, in which case the second slice is the
+/// continuation of the first.
+pub struct WriteVector<'a> {
+    writer: &'a mut RingBufferWriter,
+    slices: (&'a mut [u8], &'a mut [u8]),
+    committed: usize,
+}
+
+impl<'a> WriteVector<'a> {
+    /// The space as two slices, the second slice is empty unless the space wraps around the end of
+    /// the ring buffer.
+    pub fn as_mut_slices(&mut self) -> (&mut [u8], &mut [u8]) {
+        (self.slices.0, self.slices.1)
+    }
+
+    /// The number of bytes that are available for writing.
+    pub fn len(&self) -> usize {
+        self.slices.0.len() + self.slices.1.len()
+    }
+
+    /// Returns `true` if there is no space to write to.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Mark `cnt` more bytes, from the start of the first slice, as written. They are made
+    /// available to the reader when the guard is dropped.
+    ///
+    /// # Panics
+    /// Panics if more bytes are committed than there are in total.
+    pub fn commit(&mut self, cnt: usize) {
+        assert!(
+            cnt <= self.len() - self.committed,
+            "committed more bytes than are available"
+        );
+        self.committed += cnt;
+    }
+}
+
+/// Advances the write pointer by the committed bytes.
+impl<'a> Drop for WriteVector<'a> {
+    fn drop(&mut self) {
+        if self.committed > 0 {
+            self.writer.advance(self.committed);
         }
     }
 }
 
+impl<'a> std::fmt::Debug for WriteVector<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("WriteVector")
+            .field("len", &self.len())
+            .field("committed", &self.committed)
+            .finish()
+    }
+}
+
+// The buffer may be null when the length is zero.
+unsafe fn data_slice<'a>(data: j::jack_ringbuffer_data_t) -> &'a [u8] {
+    match data.len {
+        0 => &[],
+        len => std::slice::from_raw_parts(data.buf as *const u8, len),
+    }
+}
+
+unsafe fn data_slice_mut<'a>(data: j::jack_ringbuffer_data_t) -> &'a mut [u8] {
+    match data.len {
+        0 => &mut [],
+        len => std::slice::from_raw_parts_mut(data.buf as *mut u8, len),
+    }
+}
+
+// The size is set on creation and never changes, so it may be read from any thread.
+unsafe fn raw_size(rb: *const j::jack_ringbuffer_t) -> usize {
+    std::ptr::addr_of!((*rb).size).read()
+}
+
+unsafe fn raw_read(rb: *mut j::jack_ringbuffer_t, buf: &mut [u8]) -> usize {
+    if buf.is_empty() {
+        return 0;
+    }
+    let bufstart = buf.as_mut_ptr() as *mut libc::c_char;
+    j::jack_ringbuffer_read(rb, bufstart, buf.len() as libc::size_t) as usize
+}
+
+unsafe fn raw_write(rb: *mut j::jack_ringbuffer_t, buf: &[u8]) -> usize {
+    if buf.is_empty() {
+        return 0;
+    }
+    let bufstart = buf.as_ptr() as *const libc::c_char;
+    j::jack_ringbuffer_write(rb, bufstart, buf.len() as libc::size_t) as usize
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -459,4 +689,176 @@ mod test {
 
         assert_eq!(outbuf[..num], buf[..]);
     }
+
+    #[test]
+    fn ringbuffer_mlock_does_not_panic() {
+        let mut ringbuf = RingBuffer::new(1024).unwrap();
+        let res = ringbuf.mlock();
+        assert!(matches!(
+            res,
+            Ok(()) | Err(crate::Error::RingbufferMlockFailed)
+        ));
+    }
+
+    #[test]
+    fn ringbuffer_size_is_rounded_up_to_power_of_two() {
+        let ringbuf = RingBuffer::new(1000).unwrap();
+        assert_eq!(ringbuf.size(), 1024);
+        assert_eq!(ringbuf.write_space(), 1023);
+        assert_eq!(ringbuf.read_space(), 0);
+        let (reader, writer) = ringbuf.into_reader_writer();
+        assert_eq!(reader.size(), 1024);
+        assert_eq!(writer.size(), 1024);
+    }
+
+    #[test]
+    fn ringbuffer_read_write_and_reset() {
+        let mut ringbuf = RingBuffer::new(8).unwrap();
+        assert_eq!(ringbuf.write(&[1, 2, 3, 4, 5, 6, 7, 8, 9]), 7);
+        assert_eq!(ringbuf.read_space(), 7);
+        assert_eq!(ringbuf.write_space(), 0);
+
+        let mut outbuf = [0u8; 4];
+        assert_eq!(ringbuf.read(&mut outbuf), 4);
+        assert_eq!(outbuf, [1, 2, 3, 4]);
+        assert_eq!(ringbuf.write(&[10, 11]), 2);
+        let mut outbuf = [0u8; 8];
+        assert_eq!(ringbuf.read(&mut outbuf), 5);
+        assert_eq!(outbuf[..5], [5, 6, 7, 10, 11]);
+
+        ringbuf.write(&[1, 2, 3]);
+        ringbuf.reset();
+        assert_eq!(ringbuf.read_space(), 0);
+        assert_eq!(ringbuf.write_space(), 7);
+        assert_eq!(ringbuf.read(&mut outbuf), 0);
+    }
+
+    #[test]
+    fn ringbuffer_round_trips_across_threads_with_wrap_around() {
+        use std::thread;
+
+        const LEN: usize = 10_000;
+        // A small ringbuffer and an odd chunk size make the pointers wrap around at every offset.
+        let ringbuf = RingBuffer::new(64).unwrap();
+        let (mut reader, mut writer) = ringbuf.into_reader_writer();
+        let expected = |i: usize| (i % 251) as u8;
+
+        let writer_thread = thread::spawn(move || {
+            let data: Vec<u8> = (0..LEN).map(expected).collect();
+            let mut written = 0;
+            while written < LEN {
+                let end = (written + 7).min(LEN);
+                match writer.write_buffer(&data[written..end]) {
+                    0 => thread::yield_now(),
+                    n => written += n,
+                }
+            }
+        });
+
+        let mut read = 0;
+        let mut outbuf = [0u8; 13];
+        while read < LEN {
+            let n = reader.read_buffer(&mut outbuf);
+            if n == 0 {
+                thread::yield_now();
+            }
+            for (i, byte) in outbuf[..n].iter().enumerate() {
+                assert_eq!(*byte, expected(read + i));
+            }
+            read += n;
+        }
+        writer_thread.join().unwrap();
+        assert_eq!(reader.space(), 0);
+    }
+
+    #[test]
+    fn ringbuffer_halves_can_be_sent_and_dropped_in_any_order() {
+        use std::thread;
+
+        let (mut writer, reader) = RingBuffer::new(64).unwrap().into_halves();
+        writer.write_buffer(&[1, 2, 3]);
+        let mut reader = thread::spawn(move || reader).join().unwrap();
+        thread::spawn(move || drop(writer)).join().unwrap();
+        let mut outbuf = [0u8; 4];
+        assert_eq!(reader.read_buffer(&mut outbuf), 3);
+        drop(reader);
+
+        let (writer, reader) = RingBuffer::new(64).unwrap().into_halves();
+        drop(reader);
+        drop(writer);
+    }
+
+    #[test]
+    fn ringbuffer_can_be_rejoined() {
+        let (mut writer, reader) = RingBuffer::new(64).unwrap().into_halves();
+        writer.write_buffer(&[1, 2, 3]);
+        let mut ringbuf = RingBuffer::from_reader_writer(reader, writer);
+        assert_eq!(ringbuf.read_space(), 3);
+        ringbuf.reset();
+        assert_eq!(ringbuf.read_space(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn ringbuffer_rejoining_mismatched_halves_panics() {
+        let (_, reader) = RingBuffer::new(64).unwrap().into_halves();
+        let (writer, _) = RingBuffer::new(64).unwrap().into_halves();
+        RingBuffer::from_reader_writer(reader, writer);
+    }
+
+    #[test]
+    fn ringbuffer_read_vector_covers_split_data() {
+        let (mut reader, mut writer) = RingBuffer::new(16).unwrap().into_reader_writer();
+        writer.advance(12);
+        reader.advance(12);
+        assert_eq!(writer.write_buffer(&[0, 1, 2, 3, 4, 5, 6, 7]), 8);
+
+        {
+            let data = reader.read_vector();
+            assert_eq!(data.as_slices(), (&[0, 1, 2, 3][..], &[4, 5, 6, 7][..]));
+            assert_eq!(data.len(), 8);
+        }
+        // Dropping without consuming only peeks.
+        assert_eq!(reader.space(), 8);
+
+        let mut data = reader.read_vector();
+        data.consume(5);
+        drop(data);
+        assert_eq!(reader.space(), 3);
+        let data = reader.read_vector();
+        assert_eq!(data.as_slices(), (&[5, 6, 7][..], &[][..]));
+    }
+
+    #[test]
+    fn ringbuffer_write_vector_covers_split_space() {
+        let (mut reader, mut writer) = RingBuffer::new(16).unwrap().into_reader_writer();
+        writer.advance(12);
+        reader.advance(12);
+
+        let mut space = writer.write_vector();
+        assert_eq!(space.len(), 15);
+        let (first, second) = space.as_mut_slices();
+        assert_eq!((first.len(), second.len()), (4, 11));
+        first.copy_from_slice(&[0, 1, 2, 3]);
+        second[..2].copy_from_slice(&[4, 5]);
+        space.commit(6);
+        drop(space);
+        assert_eq!(writer.space(), 9);
+
+        let mut outbuf = [0u8; 8];
+        assert_eq!(reader.read_buffer(&mut outbuf), 6);
+        assert_eq!(outbuf[..6], [0, 1, 2, 3, 4, 5]);
+
+        // Dropping without committing writes nothing.
+        drop(writer.write_vector());
+        assert_eq!(reader.space(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn ringbuffer_read_vector_can_not_consume_more_than_available() {
+        let (mut reader, mut writer) = RingBuffer::new(16).unwrap().into_reader_writer();
+        writer.write_buffer(&[1, 2]);
+        reader.read_vector().consume(3);
+    }
 }
@@ -0,0 +1,302 @@
+use std::fmt::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{str, thread, time};
+
+use log::Level;
+
+use crate::{Client, Error, Frames, RingBuffer, RingBufferReader, RingBufferWriter, WeakClient};
+
+/// The maximum number of bytes of a message logged with `rt_log!`. Longer messages are truncated.
+pub const RT_LOG_MESSAGE_SIZE: usize = 128;
+
+// A record is the level, the frame time, the message length and the message bytes.
+const RECORD_SIZE: usize = 1 + 4 + 1 + RT_LOG_MESSAGE_SIZE;
+
+/// How often `RtLogThread` forwards the records that were logged.
+const FORWARD_INTERVAL: time::Duration = time::Duration::from_millis(10);
+
+/// Log a message from a real-time thread through an `RtLogger`.
+///
+/// The message is formatted like with `format!`, into a fixed size buffer, and is truncated to
+/// `RT_LOG_MESSAGE_SIZE` bytes. Formatting does not allocate for the standard types, but `Display`
+/// implementations of other types may.
+///
+/// # Example
+/// ```no_run
+/// let (client, _status) =
+///     jack::Client::new("rt_log", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let (mut logger, reader) = jack::RtLogger::new(&client, 256).unwrap();
+/// let _forwarder = reader.spawn();
+/// let process = jack::ClosureProcessHandler::new(
+///     move |_: &jack::Client, ps: &jack::ProcessScope| -> jack::Control {
+///         jack::rt_log!(logger, log::Level::Debug, "processing {} frames", ps.n_frames());
+///         jack::Control::Continue
+///     },
+/// );
+/// let active_client = client.activate_async((), process).unwrap();
+/// ```
+#[macro_export]
+macro_rules! rt_log {
+    ($logger:expr, $level:expr, $($arg:tt)+) => {
+        $logger.log($level, format_args!($($arg)+))
+    };
+}
+
+/// The real-time side of a log that is forwarded to the `log` crate, written to with `rt_log!`.
+///
+/// Messages are stored in a preallocated lock-free ring buffer. Logging does not allocate, block or
+/// make system calls, so it is suitable for the process callback. Messages that don't fit in the
+/// ring buffer are dropped and counted.
+pub struct RtLogger {
+    writer: RingBufferWriter,
+    // The ring buffer may be larger than requested, this is the space that is not used.
+    reserved: usize,
+    client: Option<WeakClient>,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl RtLogger {
+    /// Create a log that holds up to `capacity` messages until they are forwarded. The messages
+    /// are timestamped with the frame time of `client`.
+    pub fn new(client: &Client, capacity: usize) -> Result<(RtLogger, RtLogReader), Error> {
+        RtLogger::with_clock(Some(client.downgrade()), capacity)
+    }
+
+    fn with_clock(
+        client: Option<WeakClient>,
+        capacity: usize,
+    ) -> Result<(RtLogger, RtLogReader), Error> {
+        // The ring buffer keeps a byte free to tell a full buffer from an empty one.
+        let mut ring = RingBuffer::new(capacity * RECORD_SIZE + 1)?;
+        // Logging still works if the memory can't be locked, it may just be slower.
+        let _ = ring.mlock();
+        let (reader, mut writer) = ring.into_reader_writer();
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let logger = RtLogger {
+            reserved: writer.space().saturating_sub(capacity * RECORD_SIZE),
+            writer,
+            client,
+            dropped: dropped.clone(),
+        };
+        Ok((logger, RtLogReader { reader, dropped }))
+    }
+
+    /// Log a message, see `rt_log!`.
+    pub fn log(&mut self, level: Level, args: fmt::Arguments) {
+        if self.writer.space() < self.reserved + RECORD_SIZE {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        let frame = self
+            .client
+            .as_ref()
+            .and_then(|c| c.frame_time().ok())
+            .unwrap_or(0);
+        let mut message = MessageBuffer::default();
+        // Errors only come from `Display` implementations, log what was written so far.
+        let _ = message.write_fmt(args);
+        let mut record = [0; RECORD_SIZE];
+        record[0] = level as u8;
+        record[1..5].copy_from_slice(&frame.to_ne_bytes());
+        record[5] = message.len as u8;
+        record[6..].copy_from_slice(&message.bytes);
+        self.writer.write_buffer(&record);
+    }
+}
+
+impl fmt::Debug for RtLogger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("RtLogger")
+            .field("dropped", &self.dropped.load(Ordering::Relaxed))
+            .finish()
+    }
+}
+
+/// The reading side of an `RtLogger`.
+pub struct RtLogReader {
+    reader: RingBufferReader,
+    dropped: Arc<AtomicUsize>,
+}
+
+impl RtLogReader {
+    /// The number of messages that were dropped because the log was full.
+    pub fn dropped(&self) -> usize {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Call `f` with the level, frame time and message of every message that was logged since the
+    /// last call.
+    pub fn drain<F: FnMut(Level, Frames, &str)>(&mut self, mut f: F) {
+        let mut record = [0; RECORD_SIZE];
+        while self.reader.space() >= RECORD_SIZE {
+            self.reader.read_buffer(&mut record);
+            let level = match record[0] {
+                1 => Level::Error,
+                2 => Level::Warn,
+                3 => Level::Info,
+                4 => Level::Debug,
+                _ => Level::Trace,
+            };
+            let mut frame = [0; 4];
+            frame.copy_from_slice(&record[1..5]);
+            let len = record[5] as usize;
+            // The message is truncated at a char boundary when it is logged.
+            let message = str::from_utf8(&record[6..6 + len]).unwrap_or_default();
+            f(level, Frames::from_ne_bytes(frame), message);
+        }
+    }
+
+    /// Forward the logged messages to the `log` crate from a background thread, with the target
+    /// `jack::rt`. Dropped messages are reported with a warning.
+    ///
+    /// The thread stops once the returned handle is dropped, after forwarding the remaining
+    /// messages.
+    pub fn spawn(self) -> RtLogThread {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = thread::spawn({
+            let stop = stop.clone();
+            move || forward(self, &stop)
+        });
+        RtLogThread {
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl fmt::Debug for RtLogReader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("RtLogReader")
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+
+fn forward(mut reader: RtLogReader, stop: &AtomicBool) {
+    let mut reported_dropped = 0;
+    loop {
+        // Check before draining so that nothing is left behind after stopping.
+        let stopping = stop.load(Ordering::Acquire);
+        reader.drain(|level, frame, message| {
+            log::log!(target: "jack::rt", level, "[frame {}] {}", frame, message)
+        });
+        let dropped = reader.dropped();
+        if dropped > reported_dropped {
+            log::warn!(
+                target: "jack::rt",
+                "dropped {} messages, the real-time log is full",
+                dropped - reported_dropped
+            );
+            reported_dropped = dropped;
+        }
+        if stopping {
+            return;
+        }
+        thread::sleep(FORWARD_INTERVAL);
+    }
+}
+
+/// The thread that forwards the messages of an `RtLogger`, see `RtLogReader::spawn`.
+#[derive(Debug)]
+pub struct RtLogThread {
+    stop: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+/// Stops the thread after it has forwarded the remaining messages.
+impl Drop for RtLogThread {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Formats a message into a fixed size buffer, truncating it at a char boundary.
+struct MessageBuffer {
+    bytes: [u8; RT_LOG_MESSAGE_SIZE],
+    len: usize,
+}
+
+impl Default for MessageBuffer {
+    fn default() -> MessageBuffer {
+        MessageBuffer {
+            bytes: [0; RT_LOG_MESSAGE_SIZE],
+            len: 0,
+        }
+    }
+}
+
+impl fmt::Write for MessageBuffer {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut len = s.len().min(RT_LOG_MESSAGE_SIZE - self.len);
+        while !s.is_char_boundary(len) {
+            len -= 1;
+        }
+        self.bytes[self.len..self.len + len].copy_from_slice(&s.as_bytes()[..len]);
+        self.len += len;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn drain_all(reader: &mut RtLogReader) -> Vec<(Level, String)> {
+        let mut messages = Vec::new();
+        reader.drain(|level, _, message| messages.push((level, message.to_string())));
+        messages
+    }
+
+    #[test]
+    fn rt_log_forwards_messages_in_order() {
+        let (mut logger, mut reader) = RtLogger::with_clock(None, 4).unwrap();
+        crate::rt_log!(logger, Level::Info, "cycle {}", 1);
+        crate::rt_log!(logger, Level::Error, "cycle {} failed", 2);
+        assert_eq!(
+            drain_all(&mut reader),
+            [
+                (Level::Info, "cycle 1".to_string()),
+                (Level::Error, "cycle 2 failed".to_string())
+            ]
+        );
+        assert!(drain_all(&mut reader).is_empty());
+    }
+
+    #[test]
+    fn rt_log_drops_messages_when_full() {
+        let (mut logger, mut reader) = RtLogger::with_clock(None, 2).unwrap();
+        for i in 0..5 {
+            crate::rt_log!(logger, Level::Debug, "{}", i);
+        }
+        assert_eq!(reader.dropped(), 3);
+        let messages = drain_all(&mut reader);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[1], (Level::Debug, "1".to_string()));
+        crate::rt_log!(logger, Level::Debug, "after");
+        assert_eq!(
+            drain_all(&mut reader),
+            [(Level::Debug, "after".to_string())]
+        );
+    }
+
+    #[test]
+    fn rt_log_truncates_long_messages_at_char_boundary() {
+        let (mut logger, mut reader) = RtLogger::with_clock(None, 1).unwrap();
+        let long = "é".repeat(RT_LOG_MESSAGE_SIZE);
+        crate::rt_log!(logger, Level::Warn, "{}", long);
+        let messages = drain_all(&mut reader);
+        assert_eq!(messages[0].1, "é".repeat(RT_LOG_MESSAGE_SIZE / 2));
+    }
+
+    #[test]
+    fn rt_log_does_not_allocate() {
+        let (mut logger, _reader) = RtLogger::with_clock(None, 4).unwrap();
+        let before = crate::test_alloc::allocations();
+        crate::rt_log!(logger, Level::Info, "{} {} {:.2}", "value", 42, 0.5);
+        assert_eq!(crate::test_alloc::allocations(), before);
+    }
+}
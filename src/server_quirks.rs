@@ -0,0 +1,231 @@
+use jack_sys as j;
+use lazy_static::lazy_static;
+use std::ffi;
+
+// A version as `(major, minor, micro)`.
+type Version = (u32, u32, u32);
+
+/// The implementation of JACK that the client library belongs to, see `server_quirks`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ServerFlavor {
+    /// JACK1, the original C implementation. Its versions are `0.x.y`.
+    Jack1,
+    /// JACK2, also known as jackdmp. Its versions are `1.9.x`.
+    Jack2,
+    /// The JACK library of PipeWire, pipewire-jack.
+    PipeWire,
+    /// The version string of the library was not recognized.
+    Unknown,
+}
+
+/// How the JACK implementation in use differs from the behavior that the JACK API documents, see
+/// `server_quirks`.
+///
+/// The quirks are detected from the version string of the JACK library, and from the functions
+/// that it exports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ServerQuirks {
+    /// The implementation of JACK.
+    pub flavor: ServerFlavor,
+    /// The version of the implementation as `(major, minor, micro)`. For PipeWire, this is the
+    /// version of PipeWire and not the JACK version it reports.
+    pub version: Option<(u32, u32, u32)>,
+    /// The version string reported by the JACK library.
+    pub version_string: String,
+    /// `true` if the number of frames of a process cycle may be smaller than
+    /// `Client::buffer_size` without a buffer size callback. PipeWire runs clients with the
+    /// quantum of the graph, which changes as clients come and go, so `Client::buffer_size` is
+    /// only an upper bound and `ProcessScope::n_frames` must be used for each cycle.
+    pub buffer_size_can_change_without_callback: bool,
+    /// `true` if `Client::set_freewheel` is supported. Older versions of PipeWire ignore it.
+    pub supports_freewheel: bool,
+    /// `true` if the JACK library supports Metadata, see `metadata::is_available`.
+    pub metadata_available: bool,
+}
+
+/// The quirks of a flavor, from a version of it on.
+struct FlavorQuirks {
+    flavor: ServerFlavor,
+    since: Version,
+    buffer_size_can_change_without_callback: bool,
+    supports_freewheel: bool,
+}
+
+// For each flavor, the last entry whose version is not newer than the library applies.
+const FLAVOR_QUIRKS: [FlavorQuirks; 4] = [
+    FlavorQuirks {
+        flavor: ServerFlavor::Jack1,
+        since: (0, 0, 0),
+        buffer_size_can_change_without_callback: false,
+        supports_freewheel: true,
+    },
+    FlavorQuirks {
+        flavor: ServerFlavor::Jack2,
+        since: (0, 0, 0),
+        buffer_size_can_change_without_callback: false,
+        supports_freewheel: true,
+    },
+    FlavorQuirks {
+        flavor: ServerFlavor::PipeWire,
+        since: (0, 0, 0),
+        buffer_size_can_change_without_callback: true,
+        supports_freewheel: false,
+    },
+    FlavorQuirks {
+        flavor: ServerFlavor::PipeWire,
+        since: (0, 3, 44),
+        buffer_size_can_change_without_callback: true,
+        supports_freewheel: true,
+    },
+];
+
+lazy_static! {
+    static ref SERVER_QUIRKS: ServerQuirks = {
+        let version_string = unsafe {
+            let ptr = j::jack_get_version_string();
+            if ptr.is_null() {
+                String::new()
+            } else {
+                ffi::CStr::from_ptr(ptr).to_string_lossy().into_owned()
+            }
+        };
+        ServerQuirks::from_version_string(&version_string, *j::jack_metadata_available)
+    };
+}
+
+/// Get the quirks of the JACK implementation in use. The JACK library is only queried on the first
+/// call.
+///
+/// Many systems run PipeWire in place of a JACK server. Its JACK library works with the same
+/// applications, but differs in some behavior that applications may have to account for.
+///
+/// ```
+/// let quirks = jack::server_quirks();
+/// if quirks.buffer_size_can_change_without_callback {
+///     println!("{:?} may run cycles smaller than the buffer size", quirks.flavor);
+/// }
+/// ```
+pub fn server_quirks() -> ServerQuirks {
+    SERVER_QUIRKS.clone()
+}
+
+impl ServerQuirks {
+    fn from_version_string(version_string: &str, metadata_available: bool) -> ServerQuirks {
+        let (flavor, version) = parse_version_string(version_string);
+        let quirks = FLAVOR_QUIRKS
+            .iter()
+            .rev()
+            .find(|q| q.flavor == flavor && version.unwrap_or_default() >= q.since);
+        ServerQuirks {
+            flavor,
+            version,
+            version_string: version_string.to_string(),
+            buffer_size_can_change_without_callback: quirks
+                .map(|q| q.buffer_size_can_change_without_callback)
+                .unwrap_or(false),
+            supports_freewheel: quirks.map(|q| q.supports_freewheel).unwrap_or(true),
+            metadata_available,
+        }
+    }
+}
+
+// PipeWire reports a JACK version followed by its own, like "3.0.0.0 (using PipeWire 0.3.48)".
+// JACK1 and JACK2 report only their version, like "0.126.0" and "1.9.22".
+fn parse_version_string(s: &str) -> (ServerFlavor, Option<Version>) {
+    if let Some(idx) = s.to_ascii_lowercase().find("pipewire") {
+        return (
+            ServerFlavor::PipeWire,
+            parse_version(&s[idx + "pipewire".len()..]),
+        );
+    }
+    match parse_version(s) {
+        Some(v @ (0, _, _)) => (ServerFlavor::Jack1, Some(v)),
+        Some(v @ (1, _, _)) => (ServerFlavor::Jack2, Some(v)),
+        v => (ServerFlavor::Unknown, v),
+    }
+}
+
+// Parses the first version number in `s`. The minor and micro versions default to 0.
+fn parse_version(s: &str) -> Option<Version> {
+    let start = s.find(|c: char| c.is_ascii_digit())?;
+    let end = s[start..]
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .map_or(s.len(), |end| start + end);
+    let mut parts = s[start..end].split('.').map(|p| p.parse::<u32>().ok());
+    let major = parts.next().flatten()?;
+    let minor = parts.next().flatten().unwrap_or(0);
+    let micro = parts.next().flatten().unwrap_or(0);
+    Some((major, minor, micro))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn server_quirks_parses_version_strings() {
+        let table: &[(&str, ServerFlavor, Option<Version>)] = &[
+            ("0.126.0", ServerFlavor::Jack1, Some((0, 126, 0))),
+            ("0.125.0rc1", ServerFlavor::Jack1, Some((0, 125, 0))),
+            ("1.9.22", ServerFlavor::Jack2, Some((1, 9, 22))),
+            (
+                "1.9.12 (git 5ba8f7f)",
+                ServerFlavor::Jack2,
+                Some((1, 9, 12)),
+            ),
+            ("1.9", ServerFlavor::Jack2, Some((1, 9, 0))),
+            (
+                "3.0.0.0 (using PipeWire 0.3.48)",
+                ServerFlavor::PipeWire,
+                Some((0, 3, 48)),
+            ),
+            (
+                "3.0.0.0 (using PipeWire 1.2.7)",
+                ServerFlavor::PipeWire,
+                Some((1, 2, 7)),
+            ),
+            (
+                "pipewire-jack 1.0.5",
+                ServerFlavor::PipeWire,
+                Some((1, 0, 5)),
+            ),
+            ("PipeWire", ServerFlavor::PipeWire, None),
+            ("2.0.0", ServerFlavor::Unknown, Some((2, 0, 0))),
+            ("", ServerFlavor::Unknown, None),
+            ("unknown", ServerFlavor::Unknown, None),
+        ];
+        for (s, flavor, version) in table {
+            assert_eq!(parse_version_string(s), (*flavor, *version), "{:?}", s);
+        }
+    }
+
+    #[test]
+    fn server_quirks_depend_on_flavor_and_version() {
+        // Version string, buffer_size_can_change_without_callback, supports_freewheel.
+        let table: &[(&str, bool, bool)] = &[
+            ("0.126.0", false, true),
+            ("1.9.22", false, true),
+            ("3.0.0.0 (using PipeWire 0.3.30)", true, false),
+            ("3.0.0.0 (using PipeWire 0.3.44)", true, true),
+            ("3.0.0.0 (using PipeWire 1.2.7)", true, true),
+            ("PipeWire", true, false),
+            ("", false, true),
+        ];
+        for (s, can_change, freewheel) in table {
+            let quirks = ServerQuirks::from_version_string(s, true);
+            assert_eq!(
+                quirks.buffer_size_can_change_without_callback, *can_change,
+                "{:?}",
+                s
+            );
+            assert_eq!(quirks.supports_freewheel, *freewheel, "{:?}", s);
+            assert!(quirks.metadata_available);
+            assert_eq!(quirks.version_string, *s);
+        }
+    }
+
+    #[test]
+    fn server_quirks_are_cached() {
+        assert_eq!(server_quirks(), server_quirks());
+    }
+}
@@ -0,0 +1,449 @@
+use std::fmt;
+use std::sync::Arc;
+
+use crate::{
+    Error, Frames, ParamCell, TimebaseHandler, TransportBBT, TransportPosition, TransportState,
+};
+
+/// The number of ticks in a beat of the positions computed by a `TempoMap`.
+pub const TEMPO_MAP_TICKS_PER_BEAT: f64 = 1920.0;
+
+/// Tolerance for rounding errors when positions fall exactly on a beat or bar.
+const EPSILON: f64 = 1e-9;
+
+/// A part of a `TempoMap` with a constant tempo and time signature, from `frame` until the start
+/// of the next segment.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TempoSegment {
+    /// The transport frame the segment starts at.
+    pub frame: Frames,
+    /// Beats per minute, where a beat is a `1 / sig_denom` note as in JACK. A 6/8 meter at 120
+    /// bpm has 120 eighth notes per minute.
+    pub bpm: f64,
+    /// Time signature "numerator", the beats in a bar.
+    pub sig_num: f32,
+    /// Time signature "denominator", the note value of a beat.
+    pub sig_denom: f32,
+}
+
+impl TempoSegment {
+    /// Create a segment that starts at `frame`.
+    pub fn new(frame: Frames, bpm: f64, sig_num: f32, sig_denom: f32) -> TempoSegment {
+        TempoSegment {
+            frame,
+            bpm,
+            sig_num,
+            sig_denom,
+        }
+    }
+}
+
+/// The tempo and time signature of a song over time, as a list of `TempoSegment`s. Computes the
+/// bar, beat and tick for any transport frame, see `TimebaseMaster` to provide them to JACK.
+///
+/// Bars and beats continue across a change of tempo, even one in the middle of a bar. A change of
+/// time signature starts a new bar, so a bar that is cut short by it ends early.
+///
+/// The map holds up to `TempoMap::MAX_SEGMENTS` segments without allocating, so it is `Copy` and
+/// may be replaced while the process thread uses it.
+///
+/// # Example
+/// ```
+/// use jack::{TempoMap, TempoSegment};
+///
+/// let map = TempoMap::new(&[
+///     TempoSegment::new(0, 120.0, 4.0, 4.0),
+///     // After 2 bars of 4/4 at 120 bpm and 48 kHz, switch to 6/8.
+///     TempoSegment::new(192000, 180.0, 6.0, 8.0),
+/// ])
+/// .unwrap();
+/// let bbt = map.bbt(192000 + 16000, 48000);
+/// assert_eq!((bbt.bar, bbt.beat, bbt.tick), (3, 2, 0));
+/// ```
+#[derive(Clone, Copy)]
+pub struct TempoMap {
+    segments: [TempoSegment; TempoMap::MAX_SEGMENTS],
+    len: usize,
+}
+
+impl TempoMap {
+    /// The maximum number of segments in a map.
+    pub const MAX_SEGMENTS: usize = 32;
+
+    /// Create a map from `segments`. The first segment must start at frame 0 and the segments
+    /// must be sorted by their frame, with a positive tempo and time signature.
+    pub fn new(segments: &[TempoSegment]) -> Result<TempoMap, Error> {
+        let invalid = |reason: String| Err(Error::InvalidTempoMap(reason));
+        match segments.first() {
+            None => return invalid("no segments".to_string()),
+            Some(first) if first.frame != 0 => {
+                return invalid(format!("the first segment starts at {}", first.frame))
+            }
+            _ => {}
+        }
+        if segments.len() > TempoMap::MAX_SEGMENTS {
+            return invalid(format!(
+                "{} segments, at most {} are supported",
+                segments.len(),
+                TempoMap::MAX_SEGMENTS
+            ));
+        }
+        for (i, segment) in segments.iter().enumerate() {
+            if !(segment.bpm > 0.0 && segment.bpm.is_finite()) {
+                return invalid(format!("segment {}: invalid tempo {}", i, segment.bpm));
+            }
+            if !(segment.sig_num > 0.0 && segment.sig_denom > 0.0) {
+                return invalid(format!(
+                    "segment {}: invalid time signature {}/{}",
+                    i, segment.sig_num, segment.sig_denom
+                ));
+            }
+            if i > 0 && segment.frame <= segments[i - 1].frame {
+                return invalid(format!(
+                    "segment {}: starts at {}, which is not after the previous segment",
+                    i, segment.frame
+                ));
+            }
+        }
+        let mut map = TempoMap {
+            segments: [TempoSegment::default(); TempoMap::MAX_SEGMENTS],
+            len: segments.len(),
+        };
+        map.segments[..segments.len()].copy_from_slice(segments);
+        Ok(map)
+    }
+
+    /// A map with a single tempo and time signature.
+    pub fn constant(bpm: f64, sig_num: f32, sig_denom: f32) -> Result<TempoMap, Error> {
+        TempoMap::new(&[TempoSegment::new(0, bpm, sig_num, sig_denom)])
+    }
+
+    /// The segments of the map, sorted by their frame.
+    pub fn segments(&self) -> &[TempoSegment] {
+        &self.segments[..self.len]
+    }
+
+    /// The segment that `frame` is in.
+    pub fn segment_at(&self, frame: Frames) -> &TempoSegment {
+        let i = self.segments().partition_point(|s| s.frame <= frame);
+        &self.segments[i.max(1) - 1]
+    }
+
+    /// The bar, beat and tick at `frame`, with `frame_rate` frames per second. Bars and beats
+    /// start at 1 and ticks at 0, with `TEMPO_MAP_TICKS_PER_BEAT` ticks in a beat.
+    ///
+    /// Does not allocate, so it may be called from the process thread.
+    pub fn bbt(&self, frame: Frames, frame_rate: Frames) -> TransportBBT {
+        let seconds_per_frame = 1.0 / f64::from(frame_rate.max(1));
+        // Full bars before the current bar, beats into the current bar, and all beats since
+        // frame 0.
+        let mut bars = 0.0;
+        let mut beat = 0.0;
+        let mut beats = 0.0;
+        let mut current = &self.segments[0];
+        for (i, segment) in self.segments().iter().enumerate() {
+            if segment.frame > frame {
+                break;
+            }
+            let new_meter =
+                current.sig_num != segment.sig_num || current.sig_denom != segment.sig_denom;
+            if new_meter && beat > EPSILON {
+                bars += 1.0;
+                beat = 0.0;
+            }
+            current = segment;
+            let end = match self.segments().get(i + 1) {
+                Some(next) if next.frame <= frame => next.frame,
+                _ => frame,
+            };
+            let elapsed = f64::from(end - segment.frame) * seconds_per_frame * segment.bpm / 60.0;
+            let sig_num = f64::from(segment.sig_num);
+            let in_bars = ((beat + elapsed) / sig_num + EPSILON).floor();
+            bars += in_bars;
+            beat = (beat + elapsed - in_bars * sig_num).max(0.0);
+            beats += elapsed;
+        }
+        let whole_beats = (beat + EPSILON).floor();
+        let tick = ((beat - whole_beats).max(0.0) * TEMPO_MAP_TICKS_PER_BEAT + EPSILON) as usize;
+        TransportBBT {
+            bar: bars as usize + 1,
+            beat: (whole_beats as usize + 1).min(current.sig_num.ceil() as usize),
+            tick: tick.min(TEMPO_MAP_TICKS_PER_BEAT as usize - 1),
+            sig_num: current.sig_num,
+            sig_denom: current.sig_denom,
+            ticks_per_beat: TEMPO_MAP_TICKS_PER_BEAT,
+            bpm: current.bpm,
+            bar_start_tick: ((beats - beat).max(0.0) * TEMPO_MAP_TICKS_PER_BEAT).round(),
+        }
+    }
+}
+
+impl PartialEq for TempoMap {
+    fn eq(&self, other: &TempoMap) -> bool {
+        self.segments() == other.segments()
+    }
+}
+
+impl Default for TempoMap {
+    /// 120 bpm in 4/4.
+    fn default() -> TempoMap {
+        TempoMap::constant(120.0, 4.0, 4.0).unwrap()
+    }
+}
+
+impl fmt::Debug for TempoMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.segments()).finish()
+    }
+}
+
+/// A `TimebaseHandler` that provides the position of the transport from a `TempoMap`.
+///
+/// The position is computed from the frame of the transport alone, so it is right after the
+/// transport is relocated, including into the middle of a beat. Clones share the tempo map, so
+/// keep one to change it with `set_tempo_map` after registering another with
+/// `Client::register_timebase_handler`.
+///
+/// # Example
+/// ```no_run
+/// use jack::{TempoMap, TempoSegment, TimebaseMaster};
+///
+/// let (mut client, _status) =
+///     jack::Client::new("conductor", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let master = TimebaseMaster::new(TempoMap::constant(120.0, 4.0, 4.0).unwrap());
+/// client.register_timebase_handler(master.clone(), false).unwrap();
+/// let _active_client = client.activate_async((), ()).unwrap();
+/// // Speed up after 8 bars.
+/// master.set_tempo_map(
+///     TempoMap::new(&[
+///         TempoSegment::new(0, 120.0, 4.0, 4.0),
+///         TempoSegment::new(768000, 140.0, 4.0, 4.0),
+///     ])
+///     .unwrap(),
+/// );
+/// ```
+#[derive(Clone, Default)]
+pub struct TimebaseMaster {
+    tempo_map: Arc<ParamCell<TempoMap>>,
+}
+
+impl TimebaseMaster {
+    /// Create a timebase master that follows `tempo_map`.
+    pub fn new(tempo_map: TempoMap) -> TimebaseMaster {
+        TimebaseMaster {
+            tempo_map: Arc::new(ParamCell::new(tempo_map)),
+        }
+    }
+
+    /// The current tempo map.
+    pub fn tempo_map(&self) -> TempoMap {
+        self.tempo_map.get()
+    }
+
+    /// Replace the tempo map, it is used from the next cycle on. Not real-time safe, see
+    /// `ParamCell::set`.
+    pub fn set_tempo_map(&self, tempo_map: TempoMap) {
+        self.tempo_map.set(tempo_map);
+    }
+}
+
+impl TimebaseHandler for TimebaseMaster {
+    fn timebase(&mut self, _: TransportState, _: Frames, pos: &mut TransportPosition, _: bool) {
+        let frame_rate = match pos.frame_rate() {
+            Some(frame_rate) => frame_rate,
+            None => return,
+        };
+        let bbt = self.tempo_map.get().bbt(pos.frame(), frame_rate);
+        // The map only contains valid segments, so the position is valid too.
+        let _ = pos.set_bbt(Some(bbt));
+        let _ = pos.set_bbt_offset(None);
+    }
+}
+
+impl fmt::Debug for TimebaseMaster {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TimebaseMaster")
+            .field("tempo_map", &self.tempo_map())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const RATE: Frames = 48000;
+
+    fn bbt_at(map: &TempoMap, frame: Frames) -> (usize, usize, usize) {
+        let bbt = map.bbt(frame, RATE);
+        assert!(bbt.valid(), "{:?}", bbt);
+        (bbt.bar, bbt.beat, bbt.tick)
+    }
+
+    #[test]
+    fn tempo_map_rejects_invalid_segments() {
+        let segment = |frame, bpm, sig_num| TempoSegment::new(frame, bpm, sig_num, 4.0);
+        for (segments, reason) in [
+            (vec![], "no segments"),
+            (
+                vec![segment(10, 120.0, 4.0)],
+                "the first segment starts at 10",
+            ),
+            (vec![segment(0, 0.0, 4.0)], "segment 0: invalid tempo 0"),
+            (
+                vec![segment(0, 120.0, 0.0)],
+                "segment 0: invalid time signature 0/4",
+            ),
+            (
+                vec![segment(0, 120.0, 4.0), segment(0, 90.0, 4.0)],
+                "segment 1: starts at 0, which is not after the previous segment",
+            ),
+        ] {
+            assert_eq!(
+                TempoMap::new(&segments),
+                Err(Error::InvalidTempoMap(reason.to_string()))
+            );
+        }
+        let too_many: Vec<_> = (0..=TempoMap::MAX_SEGMENTS as Frames)
+            .map(|i| segment(i, 120.0, 4.0))
+            .collect();
+        assert!(TempoMap::new(&too_many).is_err());
+    }
+
+    #[test]
+    fn tempo_map_counts_bars_beats_and_ticks() {
+        // 120 bpm is 24000 frames per beat.
+        let map = TempoMap::constant(120.0, 4.0, 4.0).unwrap();
+        assert_eq!(bbt_at(&map, 0), (1, 1, 0));
+        assert_eq!(bbt_at(&map, 23999), (1, 1, 1919));
+        assert_eq!(bbt_at(&map, 24000), (1, 2, 0));
+        assert_eq!(bbt_at(&map, 4 * 24000), (2, 1, 0));
+        // The middle of beat 3 of bar 11, as after relocating there.
+        assert_eq!(bbt_at(&map, 42 * 24000 + 12000), (11, 3, 960));
+
+        let bbt = map.bbt(42 * 24000 + 12000, RATE);
+        assert_eq!(bbt.bar_start_tick, 40.0 * TEMPO_MAP_TICKS_PER_BEAT);
+        assert_eq!(bbt.bpm, 120.0);
+    }
+
+    #[test]
+    fn tempo_map_counts_compound_meters() {
+        // 6/8 at 180 eighth notes per minute is 16000 frames per beat, and 96000 per bar.
+        let map = TempoMap::constant(180.0, 6.0, 8.0).unwrap();
+        assert_eq!(bbt_at(&map, 5 * 16000), (1, 6, 0));
+        assert_eq!(bbt_at(&map, 96000), (2, 1, 0));
+        assert_eq!(bbt_at(&map, 96000 + 5 * 16000 + 4000), (2, 6, 480));
+        let bbt = map.bbt(96000, RATE);
+        assert_eq!((bbt.sig_num, bbt.sig_denom), (6.0, 8.0));
+    }
+
+    #[test]
+    fn tempo_map_continues_bars_across_tempo_changes() {
+        // 2.5 beats at 120 bpm, then 60 bpm, which is 48000 frames per beat, in the same bar.
+        let change = 60000;
+        let map = TempoMap::new(&[
+            TempoSegment::new(0, 120.0, 4.0, 4.0),
+            TempoSegment::new(change, 60.0, 4.0, 4.0),
+        ])
+        .unwrap();
+        assert_eq!(bbt_at(&map, change - 1), (1, 3, 959));
+        assert_eq!(bbt_at(&map, change), (1, 3, 960));
+        assert_eq!(bbt_at(&map, change + 24000), (1, 4, 0));
+        assert_eq!(bbt_at(&map, change + 72000), (2, 1, 0));
+        assert_eq!(bbt_at(&map, change + 72000 + 4 * 48000), (3, 1, 0));
+        assert_eq!(map.bbt(change, RATE).bpm, 60.0);
+        assert_eq!(map.bbt(change - 1, RATE).bpm, 120.0);
+        assert_eq!(map.segment_at(change - 1).bpm, 120.0);
+        assert_eq!(map.segment_at(change).bpm, 60.0);
+    }
+
+    #[test]
+    fn tempo_map_starts_a_bar_when_the_meter_changes() {
+        // 1.5 bars of 4/4 at 120 bpm, then 3/4. The bar that is cut short ends early.
+        let change = 6 * 24000;
+        let map = TempoMap::new(&[
+            TempoSegment::new(0, 120.0, 4.0, 4.0),
+            TempoSegment::new(change, 120.0, 3.0, 4.0),
+        ])
+        .unwrap();
+        assert_eq!(bbt_at(&map, change - 1), (2, 2, 1919));
+        assert_eq!(bbt_at(&map, change), (3, 1, 0));
+        assert_eq!(bbt_at(&map, change + 3 * 24000), (4, 1, 0));
+        let bbt = map.bbt(change + 3 * 24000, RATE);
+        assert_eq!(bbt.bar_start_tick, 9.0 * TEMPO_MAP_TICKS_PER_BEAT);
+
+        // Changing the meter on a bar line does not add a bar.
+        let change = 8 * 24000;
+        let map = TempoMap::new(&[
+            TempoSegment::new(0, 120.0, 4.0, 4.0),
+            TempoSegment::new(change, 180.0, 6.0, 8.0),
+        ])
+        .unwrap();
+        assert_eq!(bbt_at(&map, change), (3, 1, 0));
+        assert_eq!(bbt_at(&map, change + 6 * 16000), (4, 1, 0));
+    }
+
+    #[test]
+    fn timebase_master_fills_in_the_position() {
+        let master = TimebaseMaster::new(TempoMap::constant(120.0, 4.0, 4.0).unwrap());
+        let mut handler = master.clone();
+        let mut pos = TransportPosition::default();
+        pos.set_frame(4 * 24000);
+
+        // The frame rate is needed to compute the position.
+        handler.timebase(TransportState::Rolling, 256, &mut pos, true);
+        assert_eq!(pos.bbt(), None);
+
+        pos.0.frame_rate = RATE;
+        handler.timebase(TransportState::Rolling, 256, &mut pos, true);
+        let bbt = pos.bbt().unwrap();
+        assert_eq!((bbt.bar, bbt.beat, bbt.tick, bbt.bpm), (2, 1, 0, 120.0));
+
+        master.set_tempo_map(TempoMap::constant(60.0, 4.0, 4.0).unwrap());
+        handler.timebase(TransportState::Rolling, 256, &mut pos, false);
+        let bbt = pos.bbt().unwrap();
+        assert_eq!((bbt.bar, bbt.beat, bbt.tick, bbt.bpm), (1, 3, 0, 60.0));
+    }
+
+    #[test]
+    fn timebase_master_provides_bbt_to_other_clients() {
+        use crate::test_support::start_dummy_server_with;
+        use crate::ClientOptions;
+        use std::{thread, time};
+
+        let server = match start_dummy_server_with(RATE as usize, 256) {
+            Some(server) => server,
+            None => return,
+        };
+        let (mut conductor, _) = server
+            .open_client("conductor", ClientOptions::empty())
+            .unwrap();
+        let (listener, _) = server
+            .open_client("listener", ClientOptions::empty())
+            .unwrap();
+        let map = TempoMap::new(&[
+            TempoSegment::new(0, 120.0, 4.0, 4.0),
+            TempoSegment::new(4 * 24000, 180.0, 6.0, 8.0),
+        ])
+        .unwrap();
+        conductor
+            .register_timebase_handler(TimebaseMaster::new(map), false)
+            .unwrap();
+        let _conductor = conductor.activate_async((), ()).unwrap();
+        let transport = listener.transport();
+
+        for frame in [4 * 24000 + 8000, 1000, 4 * 24000 + 6 * 16000] {
+            transport.locate(frame).unwrap();
+            let deadline = time::Instant::now() + time::Duration::from_secs(5);
+            let pos = loop {
+                let pos = transport.query().unwrap().pos;
+                if pos.frame() == frame && pos.bbt().is_some() {
+                    break pos;
+                }
+                assert!(time::Instant::now() < deadline, "{:?}", pos);
+                thread::sleep(time::Duration::from_millis(10));
+            };
+            assert_eq!(pos.bbt(), Some(map.bbt(frame, RATE)));
+        }
+    }
+}
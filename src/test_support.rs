@@ -0,0 +1,918 @@
+use jack_sys as j;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Weak;
+
+#[cfg(any(test, feature = "dummy-server"))]
+use crate::{ClientOptions, ClientStatus};
+
+use crate::{
+    Client, Control, Error, Frames, MidiIter, Port, PortErrorDetails, PortErrorKind, PortSpec,
+    ProcessScope, RawMidi, TransportPosition, TransportState, TransportStatePosition,
+};
+
+/// The queries that process handlers typically make on their client. Implemented by `Client`, and
+/// by `MockClient` to test handlers without a JACK server.
+///
+/// Handlers that should be testable can do their work in a function that is generic over
+/// `ClientQuery`, and call it from `ProcessHandler::process`, see `MockProcessScope`.
+pub trait ClientQuery {
+    /// The sample rate of the JACK system, see `Client::sample_rate`.
+    fn sample_rate(&self) -> usize;
+
+    /// The current maximum size of the process buffers, see `Client::buffer_size`.
+    fn buffer_size(&self) -> Frames;
+
+    /// The estimated current time in frames, see `Client::frame_time`.
+    fn frame_time(&self) -> Frames;
+
+    /// The state and position of the transport, see `Transport::query`.
+    fn transport_query(&self) -> Result<TransportStatePosition, Error>;
+}
+
+impl ClientQuery for Client {
+    fn sample_rate(&self) -> usize {
+        Client::sample_rate(self)
+    }
+
+    fn buffer_size(&self) -> Frames {
+        Client::buffer_size(self)
+    }
+
+    fn frame_time(&self) -> Frames {
+        Client::frame_time(self)
+    }
+
+    fn transport_query(&self) -> Result<TransportStatePosition, Error> {
+        self.transport().query()
+    }
+}
+
+/// A client with fixed values, to test process handlers without a JACK server. See
+/// `MockProcessScope`.
+#[derive(Clone, Debug)]
+pub struct MockClient {
+    pub sample_rate: usize,
+    pub buffer_size: Frames,
+    pub frame_time: Frames,
+    pub transport_state: TransportState,
+    pub transport_position: TransportPosition,
+}
+
+impl MockClient {
+    /// A client at frame time `0` with a stopped transport.
+    pub fn new(sample_rate: usize, buffer_size: Frames) -> MockClient {
+        MockClient {
+            sample_rate,
+            buffer_size,
+            frame_time: 0,
+            transport_state: TransportState::Stopped,
+            transport_position: TransportPosition::default(),
+        }
+    }
+}
+
+impl ClientQuery for MockClient {
+    fn sample_rate(&self) -> usize {
+        self.sample_rate
+    }
+
+    fn buffer_size(&self) -> Frames {
+        self.buffer_size
+    }
+
+    fn frame_time(&self) -> Frames {
+        self.frame_time
+    }
+
+    fn transport_query(&self) -> Result<TransportStatePosition, Error> {
+        Ok(TransportStatePosition {
+            pos: self.transport_position,
+            state: self.transport_state,
+        })
+    }
+}
+
+/// A `ProcessScope` with synthetic port buffers, to test process handlers without a JACK server.
+///
+/// Audio and MIDI ports are registered with the scope instead of a client. Their buffers are
+/// accessed with `Port::as_slice`, `Port::as_mut_slice`, `Port::iter` and `Port::writer` like in a
+/// real process callback, and filled or read with `set_audio`, `audio`, `set_midi` and `midi`
+/// between cycles. Other port methods return `Err(Error::ClientIsNoLongerAlive)` or empty values.
+///
+/// To run a handler for many cycles, see `OfflineDriver`.
+///
+/// # Example
+/// ```
+/// struct Gain {
+///     input: jack::Port<jack::AudioIn>,
+///     output: jack::Port<jack::AudioOut>,
+///     gain: f32,
+/// }
+///
+/// impl Gain {
+///     fn run<C: jack::ClientQuery>(&mut self, _: &C, ps: &jack::ProcessScope) -> jack::Control {
+///         let input = self.input.as_slice(ps);
+///         for (o, i) in self.output.as_mut_slice(ps).iter_mut().zip(input) {
+///             *o = i * self.gain;
+///         }
+///         jack::Control::Continue
+///     }
+/// }
+///
+/// impl jack::ProcessHandler for Gain {
+///     fn process(&mut self, client: &jack::Client, ps: &jack::ProcessScope) -> jack::Control {
+///         self.run(client, ps)
+///     }
+/// }
+///
+/// let mut scope = jack::MockProcessScope::new(4);
+/// let mut gain = Gain {
+///     input: scope.register_port("in", jack::AudioIn::default()).unwrap(),
+///     output: scope.register_port("out", jack::AudioOut::default()).unwrap(),
+///     gain: 0.5,
+/// };
+/// scope.set_audio(&gain.input, &[1.0, -1.0, 0.5, 0.0]);
+/// gain.run(&jack::MockClient::new(48000, 4), scope.scope());
+/// assert_eq!(scope.audio(&gain.output), [0.5, -0.5, 0.25, 0.0]);
+/// ```
+#[derive(Debug)]
+pub struct MockProcessScope {
+    // Boxed so that `scope` can point to it.
+    ports: Box<MockPorts>,
+    scope: ProcessScope,
+}
+
+impl MockProcessScope {
+    /// A scope with cycles of `n_frames` frames.
+    pub fn new(n_frames: Frames) -> MockProcessScope {
+        let ports = Box::new(MockPorts {
+            buffers: Vec::new(),
+            n_frames,
+            sample_rate: MOCK_SAMPLE_RATE,
+            last_frame_time: 0,
+        });
+        let scope = ProcessScope::mock(n_frames, &*ports);
+        MockProcessScope { ports, scope }
+    }
+
+    /// Register a port with a silent buffer. Only audio and MIDI ports can be mocked, for other
+    /// types `Err(Error::PortRegistrationError)` is returned.
+    pub fn register_port<PS: PortSpec>(
+        &mut self,
+        port_name: &str,
+        port_spec: PS,
+    ) -> Result<Port<PS>, Error> {
+        let n_frames = self.ports.n_frames;
+        let port_type = port_spec.jack_port_type();
+        let buffer = if port_type == j::FLOAT_MONO_AUDIO {
+            MockBuffer::Audio(vec![0.0; n_frames as usize])
+        } else if port_type == j::RAW_MIDI_TYPE {
+            MockBuffer::Midi(MockMidiBuffer::new(n_frames))
+        } else {
+            return Err(Error::PortRegistrationError(
+                port_name.to_string(),
+                PortErrorDetails {
+                    kind: PortErrorKind::Unknown,
+                    code: None,
+                    message: Some("only audio and MIDI ports can be mocked".to_string()),
+                },
+            ));
+        };
+        // Ids are unique across scopes, so that ports of other scopes are detected.
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+        let port_ptr = NEXT_ID.fetch_add(1, Ordering::Relaxed) as *mut j::jack_port_t;
+        self.ports.buffers.push((port_ptr, UnsafeCell::new(buffer)));
+        // The port has no client, so its methods that query JACK fail.
+        Ok(unsafe { Port::from_raw(port_spec, std::ptr::null_mut(), port_ptr, Weak::new()) })
+    }
+
+    /// The scope to pass to the process handler.
+    pub fn scope(&self) -> &ProcessScope {
+        &self.scope
+    }
+
+    /// The number of frames in each cycle.
+    pub fn n_frames(&self) -> Frames {
+        self.ports.n_frames
+    }
+
+    /// The sample rate that `ProcessScope::sample_rate` returns, 48000 unless changed.
+    pub fn sample_rate(&self) -> usize {
+        self.ports.sample_rate
+    }
+
+    /// Change the sample rate that `ProcessScope::sample_rate` returns.
+    pub fn set_sample_rate(&mut self, sample_rate: usize) {
+        self.ports.sample_rate = sample_rate;
+        self.scope = ProcessScope::mock(self.ports.n_frames, &*self.ports);
+    }
+
+    /// Change the number of frames in each cycle. Like in JACK, the contents of all buffers are
+    /// lost.
+    pub fn set_n_frames(&mut self, n_frames: Frames) {
+        self.ports.n_frames = n_frames;
+        for (_, buffer) in self.ports.buffers.iter_mut() {
+            match buffer.get_mut() {
+                MockBuffer::Audio(audio) => *audio = vec![0.0; n_frames as usize],
+                MockBuffer::Midi(midi) => *midi = MockMidiBuffer::new(n_frames),
+            }
+        }
+        self.scope = ProcessScope::mock(n_frames, &*self.ports);
+    }
+
+    /// Copy `audio` into the buffer of `port`, for example to provide the input of the next cycle.
+    /// Extra samples are ignored, and missing samples are left as they are.
+    ///
+    /// Panics if `port` is not an audio port of this scope.
+    pub fn set_audio<PS>(&mut self, port: &Port<PS>, audio: &[f32]) {
+        let buffer = self.ports.audio_mut(port.raw());
+        let len = buffer.len().min(audio.len());
+        buffer[..len].copy_from_slice(&audio[..len]);
+    }
+
+    /// The buffer of `port`, for example to check the output of the last cycle.
+    ///
+    /// Panics if `port` is not an audio port of this scope.
+    pub fn audio<PS>(&mut self, port: &Port<PS>) -> &[f32] {
+        self.ports.audio_mut(port.raw())
+    }
+
+    /// Replace the events in the buffer of `port`, for example to provide the input of the next
+    /// cycle. Like `MidiWriter::write`, `Err(Error::NotEnoughSpace)` is returned if the events are
+    /// not in order or not within the cycle, the events before are kept in that case.
+    ///
+    /// Panics if `port` is not a MIDI port of this scope.
+    pub fn set_midi<PS>(&mut self, port: &Port<PS>, events: &[RawMidi]) -> Result<(), Error> {
+        let buffer = self.ports.midi_mut(port.raw());
+        buffer.clear();
+        events.iter().try_for_each(|event| buffer.write(event))
+    }
+
+    /// The events in the buffer of `port`, for example to check the output of the last cycle.
+    ///
+    /// Panics if `port` is not a MIDI port of this scope.
+    pub fn midi<PS>(&mut self, port: &Port<PS>) -> MidiIter<'_> {
+        unsafe { MidiIter::from_mock(self.ports.midi_mut(port.raw())) }
+    }
+
+    /// Set the value of `ProcessScope::last_frame_time`.
+    pub fn set_last_frame_time(&mut self, frame_time: Frames) {
+        self.ports.last_frame_time = frame_time;
+    }
+}
+
+/// The sample rate and buffer size of an `OfflineDriver`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DriverConfig {
+    pub sample_rate: usize,
+    pub buffer_size: Frames,
+}
+
+/// The callbacks of a process handler that is run by an `OfflineDriver`. They match the callbacks
+/// of `ProcessHandler` and `NotificationHandler::sample_rate`, with a `MockClient` instead of a
+/// `Client`.
+pub trait OfflineHandler {
+    /// Called for each cycle, see `ProcessHandler::process`.
+    fn process(&mut self, _: &MockClient, _process_scope: &ProcessScope) -> Control;
+
+    /// Called when the buffer size changes, see `ProcessHandler::buffer_size`.
+    fn buffer_size(&mut self, _: &MockClient, _size: Frames) -> Control {
+        Control::Continue
+    }
+
+    /// Called when the sample rate changes, see `NotificationHandler::sample_rate`.
+    fn sample_rate(&mut self, _: &MockClient, _srate: Frames) -> Control {
+        Control::Continue
+    }
+}
+
+/// Runs a process handler for a number of cycles with synthetic buffers, to test it without a JACK
+/// server.
+///
+/// Each cycle advances the frame time of the client and of the `ProcessScope` by the buffer size,
+/// and the transport position if the transport is rolling. Changing the configuration between
+/// cycles calls the `sample_rate` and `buffer_size` callbacks of the handler.
+///
+/// # Example
+/// ```
+/// struct Counter {
+///     output: jack::Port<jack::AudioOut>,
+///     count: f32,
+/// }
+///
+/// impl jack::OfflineHandler for Counter {
+///     fn process(&mut self, _: &jack::MockClient, ps: &jack::ProcessScope) -> jack::Control {
+///         for o in self.output.as_mut_slice(ps) {
+///             *o = self.count;
+///             self.count += 1.0;
+///         }
+///         jack::Control::Continue
+///     }
+/// }
+///
+/// let config = jack::DriverConfig {
+///     sample_rate: 48000,
+///     buffer_size: 2,
+/// };
+/// let mut driver = jack::OfflineDriver::new(config);
+/// let output = driver.register_port("out", jack::AudioOut::default()).unwrap();
+/// let recorded_port = output.clone_unowned();
+/// let mut counter = Counter { output, count: 0.0 };
+/// let mut recorded = Vec::new();
+/// driver.run(&mut counter, 3, |_, _| {}, |_, scope| {
+///     recorded.extend_from_slice(scope.audio(&recorded_port))
+/// });
+/// assert_eq!(recorded, [0.0, 1.0, 2.0, 3.0, 4.0, 5.0]);
+/// ```
+#[derive(Debug)]
+pub struct OfflineDriver {
+    client: MockClient,
+    scope: MockProcessScope,
+}
+
+impl OfflineDriver {
+    /// A driver at frame time `0` with a stopped transport.
+    pub fn new(config: DriverConfig) -> OfflineDriver {
+        let mut scope = MockProcessScope::new(config.buffer_size);
+        scope.set_sample_rate(config.sample_rate);
+        OfflineDriver {
+            client: MockClient::new(config.sample_rate, config.buffer_size),
+            scope,
+        }
+    }
+
+    /// The current sample rate and buffer size.
+    pub fn config(&self) -> DriverConfig {
+        DriverConfig {
+            sample_rate: self.client.sample_rate,
+            buffer_size: self.client.buffer_size,
+        }
+    }
+
+    /// Register a port, see `MockProcessScope::register_port`.
+    pub fn register_port<PS: PortSpec>(
+        &mut self,
+        port_name: &str,
+        port_spec: PS,
+    ) -> Result<Port<PS>, Error> {
+        self.scope.register_port(port_name, port_spec)
+    }
+
+    /// The client that is passed to the handler.
+    pub fn client(&self) -> &MockClient {
+        &self.client
+    }
+
+    /// The client that is passed to the handler, for example to start the transport.
+    pub fn client_mut(&mut self) -> &mut MockClient {
+        &mut self.client
+    }
+
+    /// The port buffers, to provide input to and check the output of the handler between cycles.
+    pub fn scope_mut(&mut self) -> &mut MockProcessScope {
+        &mut self.scope
+    }
+
+    /// Change the sample rate and buffer size, and call the callbacks of `handler` for the values
+    /// that changed. Returns `Control::Quit` if any callback did.
+    pub fn set_config<H: OfflineHandler>(
+        &mut self,
+        handler: &mut H,
+        config: DriverConfig,
+    ) -> Control {
+        let mut control = Control::Continue;
+        if config.sample_rate != self.client.sample_rate {
+            self.client.sample_rate = config.sample_rate;
+            self.scope.set_sample_rate(config.sample_rate);
+            if handler.sample_rate(&self.client, config.sample_rate as Frames) == Control::Quit {
+                control = Control::Quit;
+            }
+        }
+        if config.buffer_size != self.client.buffer_size {
+            self.client.buffer_size = config.buffer_size;
+            self.scope.set_n_frames(config.buffer_size);
+            if handler.buffer_size(&self.client, config.buffer_size) == Control::Quit {
+                control = Control::Quit;
+            }
+        }
+        control
+    }
+
+    /// Run a single cycle.
+    pub fn cycle<H: OfflineHandler>(&mut self, handler: &mut H) -> Control {
+        let n_frames = self.client.buffer_size;
+        self.scope.set_last_frame_time(self.client.frame_time);
+        let control = handler.process(&self.client, self.scope.scope());
+        self.client.frame_time = self.client.frame_time.wrapping_add(n_frames);
+        if self.client.transport_state == TransportState::Rolling {
+            let frame = self.client.transport_position.frame();
+            self.client.transport_position.set_frame(frame + n_frames);
+        }
+        control
+    }
+
+    /// Run `cycles` cycles, or until the handler returns `Control::Quit`. `inputs` is called with
+    /// the index of the cycle before each cycle, and `outputs` after it.
+    pub fn run<H, I, O>(
+        &mut self,
+        handler: &mut H,
+        cycles: usize,
+        mut inputs: I,
+        mut outputs: O,
+    ) -> Control
+    where
+        H: OfflineHandler,
+        I: FnMut(usize, &mut MockProcessScope),
+        O: FnMut(usize, &mut MockProcessScope),
+    {
+        for index in 0..cycles {
+            inputs(index, &mut self.scope);
+            let control = self.cycle(handler);
+            outputs(index, &mut self.scope);
+            if control == Control::Quit {
+                return Control::Quit;
+            }
+        }
+        Control::Continue
+    }
+}
+
+/// A `jackd` server with the dummy driver, for tests that need a JACK server. The server is killed
+/// when `DummyServer` is dropped.
+///
+/// Each server has a unique name, so tests that run in parallel don't share a server. Clients are
+/// opened on it with `DummyServer::open_client` or `Client::new_with_server_name`.
+///
+/// `Err(Error::ServerStartError)` is returned if `jackd` is not installed or does not start, so
+/// that tests can be skipped on machines without JACK:
+///
+/// ```no_run
+/// let server = match jack::DummyServer::start() {
+///     Ok(server) => server,
+///     Err(err) => {
+///         eprintln!("skipping test: {}", err);
+///         return;
+///     }
+/// };
+/// let (client, _status) = server.open_client("my_test", jack::ClientOptions::empty()).unwrap();
+/// assert_eq!(client.buffer_size(), 256);
+/// ```
+#[cfg(any(test, feature = "dummy-server"))]
+#[derive(Debug)]
+pub struct DummyServer {
+    name: String,
+    child: std::process::Child,
+}
+
+#[cfg(any(test, feature = "dummy-server"))]
+impl DummyServer {
+    /// How long to wait for the server to accept clients.
+    const START_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+    /// Start a server with a sample rate of `48000` and a buffer size of `256`.
+    pub fn start() -> Result<DummyServer, Error> {
+        DummyServer::start_with(48000, 256)
+    }
+
+    /// Start a server with the given sample rate and buffer size, and wait until it accepts
+    /// clients.
+    pub fn start_with(sample_rate: usize, buffer_size: Frames) -> Result<DummyServer, Error> {
+        DummyServer::start_named(DummyServer::unique_name(), sample_rate, buffer_size)
+    }
+
+    /// A server name that no other `DummyServer` uses.
+    pub(crate) fn unique_name() -> String {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        format!(
+            "rust_jack_dummy_{}_{}",
+            std::process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+
+    /// Start a server called `name`, see `start_with`.
+    pub(crate) fn start_named(
+        name: String,
+        sample_rate: usize,
+        buffer_size: Frames,
+    ) -> Result<DummyServer, Error> {
+        use std::process::{Command, Stdio};
+
+        let child = Command::new("jackd")
+            .arg("-r")
+            .args(["-n", &name])
+            .args(["-d", "dummy"])
+            .arg(format!("-r{}", sample_rate))
+            .arg(format!("-p{}", buffer_size))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|err| Error::ServerStartError(format!("failed to run jackd: {}", err)))?;
+        let mut server = DummyServer { name, child };
+        server.wait_until_ready()?;
+        Ok(server)
+    }
+
+    fn wait_until_ready(&mut self) -> Result<(), Error> {
+        let start = std::time::Instant::now();
+        loop {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return Err(Error::ServerStartError(format!(
+                    "jackd exited with {}",
+                    status
+                )));
+            }
+            if self
+                .open_client("rust_jack_probe", ClientOptions::empty())
+                .is_ok()
+            {
+                return Ok(());
+            }
+            if start.elapsed() > DummyServer::START_TIMEOUT {
+                return Err(Error::ServerStartError(format!(
+                    "jackd did not accept clients within {:?}",
+                    DummyServer::START_TIMEOUT
+                )));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(50));
+        }
+    }
+
+    /// The name of the server.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Open a client on the server, see `Client::new_with_server_name`. The server is never
+    /// started by JACK, `ClientOptions::NO_START_SERVER` is always set.
+    pub fn open_client(
+        &self,
+        client_name: &str,
+        options: ClientOptions,
+    ) -> Result<(Client, ClientStatus), Error> {
+        let options = options | ClientOptions::NO_START_SERVER;
+        Client::new_with_server_name(client_name, &self.name, options)
+    }
+}
+
+/// Kills the server.
+#[cfg(any(test, feature = "dummy-server"))]
+impl Drop for DummyServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Start a server of its own for a test, or `None` to skip the test if `jackd` can't be started.
+#[cfg(test)]
+pub(crate) fn start_dummy_server() -> Option<DummyServer> {
+    skip_on_error(DummyServer::start())
+}
+
+/// Like `start_dummy_server`, with the given sample rate and buffer size.
+#[cfg(test)]
+pub(crate) fn start_dummy_server_with(
+    sample_rate: usize,
+    buffer_size: Frames,
+) -> Option<DummyServer> {
+    skip_on_error(DummyServer::start_with(sample_rate, buffer_size))
+}
+
+#[cfg(test)]
+fn skip_on_error(server: Result<DummyServer, Error>) -> Option<DummyServer> {
+    match server {
+        Ok(server) => Some(server),
+        Err(err) => {
+            eprintln!("skipping test: {}", err);
+            None
+        }
+    }
+}
+
+/// The buffers of the ports of a `MockProcessScope`, see `ProcessScope::port_buffer`.
+#[derive(Debug)]
+pub(crate) struct MockPorts {
+    // The buffers by the pointers of their ports, which are only used as ids.
+    buffers: Vec<(*mut j::jack_port_t, UnsafeCell<MockBuffer>)>,
+    n_frames: Frames,
+    pub(crate) sample_rate: usize,
+    pub(crate) last_frame_time: Frames,
+}
+
+#[derive(Debug)]
+enum MockBuffer {
+    Audio(Vec<f32>),
+    Midi(MockMidiBuffer),
+}
+
+impl MockPorts {
+    fn buffer(&self, port_ptr: *mut j::jack_port_t) -> *mut MockBuffer {
+        let (_, buffer) = self
+            .buffers
+            .iter()
+            .find(|(ptr, _)| *ptr == port_ptr)
+            .expect("the port was not registered with this MockProcessScope");
+        buffer.get()
+    }
+
+    /// The audio buffer of the port with the pointer `port_ptr`.
+    pub(crate) fn audio_buffer(&self, port_ptr: *mut j::jack_port_t) -> *mut f32 {
+        match unsafe { &mut *self.buffer(port_ptr) } {
+            MockBuffer::Audio(audio) => audio.as_mut_ptr(),
+            MockBuffer::Midi(_) => panic!("the port is not an audio port"),
+        }
+    }
+
+    /// The MIDI buffer of the port with the pointer `port_ptr`.
+    pub(crate) fn midi_buffer(&self, port_ptr: *mut j::jack_port_t) -> *mut MockMidiBuffer {
+        match unsafe { &mut *self.buffer(port_ptr) } {
+            MockBuffer::Midi(midi) => midi,
+            MockBuffer::Audio(_) => panic!("the port is not a MIDI port"),
+        }
+    }
+
+    fn audio_mut(&mut self, port_ptr: *mut j::jack_port_t) -> &mut [f32] {
+        let buffer = self.audio_buffer(port_ptr);
+        unsafe { std::slice::from_raw_parts_mut(buffer, self.n_frames as usize) }
+    }
+
+    fn midi_mut(&mut self, port_ptr: *mut j::jack_port_t) -> &mut MockMidiBuffer {
+        unsafe { &mut *self.midi_buffer(port_ptr) }
+    }
+}
+
+/// The size of the MIDI buffers of a `MockProcessScope`, which limits the size of the events of a
+/// cycle like in JACK.
+const MOCK_MIDI_BUFFER_SIZE: usize = 32768;
+
+/// The sample rate of a `MockProcessScope` until it is changed.
+const MOCK_SAMPLE_RATE: usize = 48000;
+
+/// A MIDI buffer of a `MockProcessScope`, that follows the rules of JACK MIDI buffers. See
+/// `MidiIter` and `MidiWriter`.
+#[derive(Debug)]
+pub(crate) struct MockMidiBuffer {
+    events: Vec<(Frames, Vec<u8>)>,
+    n_frames: Frames,
+    size: usize,
+    lost: usize,
+}
+
+impl MockMidiBuffer {
+    fn new(n_frames: Frames) -> MockMidiBuffer {
+        MockMidiBuffer {
+            events: Vec::new(),
+            n_frames,
+            size: 0,
+            lost: 0,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.events.clear();
+        self.size = 0;
+        self.lost = 0;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub(crate) fn get(&self, n: usize) -> Option<RawMidi<'_>> {
+        self.events
+            .get(n)
+            .map(|(time, bytes)| RawMidi { time: *time, bytes })
+    }
+
+    /// Append an event. Like JACK, events must be in order and within the cycle, and events that
+    /// don't fit are counted as lost.
+    pub(crate) fn write(&mut self, message: &RawMidi) -> Result<(), Error> {
+        let out_of_order = self.events.last().is_some_and(|(t, _)| *t > message.time);
+        if out_of_order || message.time >= self.n_frames {
+            return Err(Error::NotEnoughSpace);
+        }
+        if message.bytes.len() > self.max_event_size() {
+            self.lost += 1;
+            return Err(Error::NotEnoughSpace);
+        }
+        self.size += message.bytes.len();
+        self.events.push((message.time, message.bytes.to_vec()));
+        Ok(())
+    }
+
+    pub(crate) fn lost_count(&self) -> usize {
+        self.lost
+    }
+
+    pub(crate) fn max_event_size(&self) -> usize {
+        MOCK_MIDI_BUFFER_SIZE - self.size
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{AudioIn, AudioOut, MidiIn, MidiOut, Unowned};
+
+    struct Delay {
+        input: Port<AudioIn>,
+        output: Port<AudioOut>,
+        last: f32,
+        cycles: Vec<(Frames, usize, TransportState)>,
+    }
+
+    impl Delay {
+        fn run<C: ClientQuery>(&mut self, client: &C, ps: &ProcessScope) -> Control {
+            let state = client.transport_query().unwrap().state;
+            self.cycles
+                .push((ps.last_frame_time(), client.sample_rate(), state));
+            let input = self.input.as_slice(ps);
+            for (o, i) in self.output.as_mut_slice(ps).iter_mut().zip(input) {
+                *o = self.last;
+                self.last = *i;
+            }
+            Control::Continue
+        }
+    }
+
+    #[test]
+    fn mock_scope_runs_handler_without_server() {
+        let mut scope = MockProcessScope::new(3);
+        let mut delay = Delay {
+            input: scope.register_port("in", AudioIn::default()).unwrap(),
+            output: scope.register_port("out", AudioOut::default()).unwrap(),
+            last: 0.0,
+            cycles: Vec::new(),
+        };
+        let mut client = MockClient::new(44100, 3);
+        scope.set_audio(&delay.input, &[1.0, 2.0, 3.0]);
+        assert_eq!(delay.run(&client, scope.scope()), Control::Continue);
+        assert_eq!(scope.audio(&delay.output), [0.0, 1.0, 2.0]);
+
+        client.transport_state = TransportState::Rolling;
+        scope.set_last_frame_time(3);
+        scope.set_audio(&delay.input, &[4.0, 5.0]);
+        delay.run(&client, scope.scope());
+        assert_eq!(scope.audio(&delay.output), [3.0, 4.0, 5.0]);
+        assert_eq!(
+            delay.cycles,
+            [
+                (0, 44100, TransportState::Stopped),
+                (3, 44100, TransportState::Rolling)
+            ]
+        );
+    }
+
+    #[test]
+    fn mock_ports_have_no_client() {
+        let mut scope = MockProcessScope::new(8);
+        let port = scope.register_port("in", AudioIn::default()).unwrap();
+        assert_eq!(port.name(), Err(Error::ClientIsNoLongerAlive));
+        assert!(port.flags().is_empty());
+        assert_eq!(port.as_slice(scope.scope()), [0.0; 8]);
+        assert!(matches!(
+            scope.register_port("other", Unowned),
+            Err(Error::PortRegistrationError(..))
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "not registered with this MockProcessScope")]
+    fn mock_scope_rejects_ports_of_other_scopes() {
+        let mut other = MockProcessScope::new(8);
+        let port = other.register_port("in", AudioIn::default()).unwrap();
+        let scope = MockProcessScope::new(8);
+        port.as_slice(scope.scope());
+    }
+
+    #[test]
+    fn mock_scope_exchanges_midi() {
+        let mut scope = MockProcessScope::new(16);
+        let input = scope.register_port("in", MidiIn::default()).unwrap();
+        let mut output = scope.register_port("out", MidiOut::default()).unwrap();
+        let note_on = [0x90, 60, 100];
+        let note_off = [0x80, 60, 0];
+        let events = [
+            RawMidi {
+                time: 2,
+                bytes: &note_on,
+            },
+            RawMidi {
+                time: 9,
+                bytes: &note_off,
+            },
+        ];
+        scope.set_midi(&input, &events).unwrap();
+
+        let mut writer = output.writer(scope.scope());
+        for event in input.iter(scope.scope()) {
+            let mut bytes = [0; 3];
+            bytes.copy_from_slice(event.bytes);
+            bytes[1] += 12;
+            writer
+                .write(&RawMidi {
+                    time: event.time,
+                    bytes: &bytes,
+                })
+                .unwrap();
+        }
+        let late = RawMidi {
+            time: 16,
+            bytes: &note_on,
+        };
+        assert_eq!(writer.write(&late), Err(Error::NotEnoughSpace));
+        assert_eq!(writer.write(&events[0]), Err(Error::NotEnoughSpace));
+        assert_eq!(writer.lost_count(), 0);
+
+        let written: Vec<_> = scope.midi(&output).map(|e| (e.time, e.bytes[1])).collect();
+        assert_eq!(written, [(2, 72), (9, 72)]);
+        assert_eq!(scope.midi(&input).count(), 2);
+        assert_eq!(
+            scope.set_midi(&input, &[events[1], events[0]]),
+            Err(Error::NotEnoughSpace)
+        );
+    }
+
+    // Ramps the gain to its target over one buffer, to avoid clicks.
+    struct SmoothGain {
+        input: Port<AudioIn>,
+        output: Port<AudioOut>,
+        gain: f32,
+        target: f32,
+        callbacks: Vec<String>,
+    }
+
+    impl OfflineHandler for SmoothGain {
+        fn process(&mut self, _: &MockClient, ps: &ProcessScope) -> Control {
+            let step = (self.target - self.gain) / ps.n_frames() as f32;
+            let input = self.input.as_slice(ps);
+            for (o, i) in self.output.as_mut_slice(ps).iter_mut().zip(input) {
+                self.gain += step;
+                *o = i * self.gain;
+            }
+            self.gain = self.target;
+            Control::Continue
+        }
+
+        fn buffer_size(&mut self, _: &MockClient, size: Frames) -> Control {
+            self.callbacks.push(format!("buffer_size {}", size));
+            Control::Continue
+        }
+
+        fn sample_rate(&mut self, client: &MockClient, srate: Frames) -> Control {
+            assert_eq!(client.sample_rate, srate as usize);
+            self.callbacks.push(format!("sample_rate {}", srate));
+            Control::Quit
+        }
+    }
+
+    #[test]
+    fn offline_driver_runs_handler_for_cycles() {
+        let config = DriverConfig {
+            sample_rate: 48000,
+            buffer_size: 4,
+        };
+        let mut driver = OfflineDriver::new(config);
+        let input = driver.register_port("in", AudioIn::default()).unwrap();
+        let output = driver.register_port("out", AudioOut::default()).unwrap();
+        let (input_id, output_id) = (input.clone_unowned(), output.clone_unowned());
+        let mut gain = SmoothGain {
+            input,
+            output,
+            gain: 1.0,
+            target: 0.0,
+            callbacks: Vec::new(),
+        };
+        let mut recorded = Vec::new();
+        let control = driver.run(
+            &mut gain,
+            2,
+            |_, scope| scope.set_audio(&input_id, &[1.0; 4]),
+            |_, scope| recorded.extend_from_slice(scope.audio(&output_id)),
+        );
+        assert_eq!(control, Control::Continue);
+        assert_eq!(recorded, [0.75, 0.5, 0.25, 0.0, 0.0, 0.0, 0.0, 0.0]);
+        assert_eq!(driver.client().frame_time, 8);
+
+        let config = DriverConfig {
+            sample_rate: 44100,
+            buffer_size: 2,
+        };
+        assert_eq!(driver.set_config(&mut gain, config), Control::Quit);
+        assert_eq!(driver.config(), config);
+        assert_eq!(gain.callbacks, ["sample_rate 44100", "buffer_size 2"]);
+        assert_eq!(driver.scope_mut().audio(&output_id), [0.0; 2]);
+        assert_eq!(driver.scope_mut().scope().sample_rate(), 44100);
+
+        gain.target = 1.0;
+        driver.client_mut().transport_state = TransportState::Rolling;
+        driver.scope_mut().set_audio(&input_id, &[1.0; 2]);
+        driver.cycle(&mut gain);
+        assert_eq!(driver.scope_mut().audio(&output_id), [0.5, 1.0]);
+        assert_eq!(driver.scope_mut().scope().last_frame_time(), 8);
+        assert_eq!(driver.client().transport_position.frame(), 2);
+    }
+}
@@ -0,0 +1,554 @@
+use jack_sys as j;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Weak};
+use std::{ffi, fmt, mem, ptr};
+
+use crate::{Client, Error};
+
+/// Configuration that is applied to JACK's callback thread from within the `thread_init`
+/// callback.
+///
+/// Attach it to a client with `Client::set_thread_init_config` before activating. Each setting is
+/// applied on a best effort basis; settings that are not supported on the current platform are
+/// skipped. The `thread_init` method of the `NotificationHandler` is called after the settings have
+/// been applied.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ThreadInitConfig {
+    /// The name to give the thread, as seen by debuggers and profilers.
+    ///
+    /// Some platforms limit the length of thread names, on Linux the name is truncated to 15
+    /// bytes.
+    pub thread_name: Option<String>,
+
+    /// Pin the thread to the CPU with the given index.
+    ///
+    /// Only supported on Linux.
+    pub cpu_affinity: Option<usize>,
+
+    /// Flush denormal floats to zero. Computing with denormals is very slow on most processors,
+    /// which can cause xruns in filters and reverbs whose state decays towards zero.
+    ///
+    /// Only supported on x86_64 and aarch64, see `enable_denormal_flushing`.
+    pub flush_denormals: bool,
+}
+
+impl ThreadInitConfig {
+    /// Apply the configuration to the current thread.
+    pub fn apply(&self) {
+        if let Some(name) = self.thread_name.as_ref() {
+            set_current_thread_name(name);
+        }
+        if let Some(cpu) = self.cpu_affinity {
+            set_current_thread_affinity(cpu);
+        }
+        if self.flush_denormals {
+            enable_denormal_flushing();
+        }
+    }
+}
+
+/// A function that creates threads for JACK, with the same contract as `pthread_create`.
+///
+/// It must start a thread that calls `function` with `arg`, store the id of the new thread in the
+/// first argument and return `0`, or return an error number without starting a thread.
+#[cfg(not(target_os = "windows"))]
+pub type ThreadCreator = unsafe extern "C" fn(
+    *mut libc::pthread_t,
+    *const libc::pthread_attr_t,
+    Option<extern "C" fn(*mut libc::c_void) -> *mut libc::c_void>,
+    *mut libc::c_void,
+) -> libc::c_int;
+
+/// Make JACK create its threads, including the real-time process threads of clients, with
+/// `creator` instead of `pthread_create`. This is needed in environments where real-time threads
+/// have to be created through a broker.
+///
+/// # Safety
+/// `creator` must uphold the contract of `ThreadCreator`. The setting is global to the process, so
+/// it applies to all clients, and it must not be changed while any client is being opened or
+/// activated.
+#[cfg(not(target_os = "windows"))]
+pub unsafe fn set_thread_creator(creator: ThreadCreator) {
+    j::jack_set_thread_creator(Some(creator))
+}
+
+/// Make JACK create its threads with `pthread_create` again.
+///
+/// # Safety
+/// Must not be called while any client is being opened or activated, see `set_thread_creator`.
+#[cfg(not(target_os = "windows"))]
+pub unsafe fn reset_thread_creator() {
+    j::jack_set_thread_creator(None)
+}
+
+/// Give `thread` real-time scheduling at `priority`, the way JACK does for its own threads.
+///
+/// This requires the privileges to use real-time scheduling, `Err(Error::SchedulingError)` is
+/// returned without them. It does not depend on the server running in real-time, see
+/// `Client::is_realtime`, so threads of a server without real-time scheduling may end up with a
+/// higher priority than the process thread. Use `Client::real_time_priority` to pick a priority
+/// relative to the process thread.
+///
+/// # Safety
+/// `thread` must be a thread that has not been joined or detached and exited.
+#[cfg(not(target_os = "windows"))]
+pub unsafe fn acquire_real_time_scheduling(
+    thread: libc::pthread_t,
+    priority: i32,
+) -> Result<(), Error> {
+    match j::jack_acquire_real_time_scheduling(thread, priority) {
+        0 => Ok(()),
+        _ => Err(Error::SchedulingError),
+    }
+}
+
+/// Give `thread` the default, non real-time, scheduling.
+///
+/// # Safety
+/// `thread` must be a thread that has not been joined or detached and exited.
+#[cfg(not(target_os = "windows"))]
+pub unsafe fn drop_real_time_scheduling(thread: libc::pthread_t) -> Result<(), Error> {
+    match j::jack_drop_real_time_scheduling(thread) {
+        0 => Ok(()),
+        _ => Err(Error::SchedulingError),
+    }
+}
+
+/// Give the current thread real-time scheduling at `priority`, see
+/// `acquire_real_time_scheduling`.
+#[cfg(not(target_os = "windows"))]
+pub fn acquire_current_thread_real_time_scheduling(priority: i32) -> Result<(), Error> {
+    unsafe { acquire_real_time_scheduling(libc::pthread_self(), priority) }
+}
+
+/// Give the current thread the default, non real-time, scheduling.
+#[cfg(not(target_os = "windows"))]
+pub fn drop_current_thread_real_time_scheduling() -> Result<(), Error> {
+    unsafe { drop_real_time_scheduling(libc::pthread_self()) }
+}
+
+/// A thread created by JACK for a client with `Client::spawn_rt_thread`.
+///
+/// The thread is detached when the handle is dropped, like with `std::thread::JoinHandle`.
+#[cfg(not(target_os = "windows"))]
+pub struct RtThreadHandle {
+    client_ptr: *mut j::jack_client_t,
+    client_life: Weak<()>,
+    thread: Option<libc::pthread_t>,
+    panicked: Arc<AtomicBool>,
+}
+
+#[cfg(not(target_os = "windows"))]
+unsafe impl Send for RtThreadHandle {}
+
+#[cfg(not(target_os = "windows"))]
+impl RtThreadHandle {
+    /// The id of the thread.
+    pub fn thread_id(&self) -> libc::pthread_t {
+        self.thread.unwrap()
+    }
+
+    /// Wait for the thread to finish.
+    ///
+    /// `Err(Error::ThreadJoinError)` is returned if the thread could not be joined or if its
+    /// function panicked.
+    pub fn join(mut self) -> Result<(), Error> {
+        let thread = self.thread.take().unwrap();
+        let res = match self.client_life.upgrade() {
+            Some(_life) => unsafe { j::jack_client_stop_thread(self.client_ptr, thread) },
+            // JACK only joins the thread, which is still possible without the client.
+            None => unsafe { libc::pthread_join(thread, ptr::null_mut()) },
+        };
+        if res != 0 || self.panicked.load(Ordering::Acquire) {
+            return Err(Error::ThreadJoinError);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl Drop for RtThreadHandle {
+    fn drop(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            unsafe { libc::pthread_detach(thread) };
+        }
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+impl fmt::Debug for RtThreadHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.debug_struct("RtThreadHandle")
+            .field("thread", &self.thread)
+            .finish()
+    }
+}
+
+/// The function and state handed to a thread created by `spawn_rt_thread`.
+#[cfg(not(target_os = "windows"))]
+struct RtThreadStart<F> {
+    f: F,
+    panicked: Arc<AtomicBool>,
+}
+
+#[cfg(not(target_os = "windows"))]
+unsafe extern "C" fn rt_thread_main<F: FnOnce() + Send>(
+    arg: *mut libc::c_void,
+) -> *mut libc::c_void {
+    // The thread owns the start state from here on, so it is freed however the function exits.
+    let start = Box::from_raw(arg as *mut RtThreadStart<F>);
+    let RtThreadStart { f, panicked } = *start;
+    // Unwinding into JACK is undefined behavior.
+    if panic::catch_unwind(AssertUnwindSafe(f)).is_err() {
+        panicked.store(true, Ordering::Release);
+    }
+    ptr::null_mut()
+}
+
+/// See `Client::spawn_rt_thread`.
+#[cfg(not(target_os = "windows"))]
+pub(crate) fn spawn_rt_thread<F>(
+    client: &Client,
+    client_life: Weak<()>,
+    priority: i32,
+    realtime: bool,
+    f: F,
+) -> Result<RtThreadHandle, Error>
+where
+    F: 'static + Send + FnOnce(),
+{
+    let panicked = Arc::new(AtomicBool::new(false));
+    let start = Box::into_raw(Box::new(RtThreadStart {
+        f,
+        panicked: panicked.clone(),
+    }));
+    let mut thread: libc::pthread_t = unsafe { mem::zeroed() };
+    let res = unsafe {
+        j::jack_client_create_thread(
+            client.raw(),
+            &mut thread,
+            priority,
+            realtime as libc::c_int,
+            Some(rt_thread_main::<F>),
+            start as *mut libc::c_void,
+        )
+    };
+    if res != 0 {
+        // The thread was not started, so the start state is still owned here.
+        drop(unsafe { Box::from_raw(start) });
+        return Err(Error::ThreadCreationError);
+    }
+    Ok(RtThreadHandle {
+        client_ptr: client.raw(),
+        client_life,
+        thread: Some(thread),
+        panicked,
+    })
+}
+
+/// Set the name of the current thread. Returns `true` on success.
+#[cfg(target_os = "linux")]
+fn set_current_thread_name(name: &str) -> bool {
+    // Linux limits names to 16 bytes, including the nul terminator.
+    let mut len = name.len().min(15);
+    while !name.is_char_boundary(len) {
+        len -= 1;
+    }
+    match ffi::CString::new(&name[..len]) {
+        Ok(name) => unsafe { libc::pthread_setname_np(libc::pthread_self(), name.as_ptr()) == 0 },
+        Err(_) => false,
+    }
+}
+
+/// Set the name of the current thread. Returns `true` on success.
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+fn set_current_thread_name(name: &str) -> bool {
+    match ffi::CString::new(name) {
+        Ok(name) => unsafe { libc::pthread_setname_np(name.as_ptr()) == 0 },
+        Err(_) => false,
+    }
+}
+
+/// Set the name of the current thread. Returns `true` on success.
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "ios")))]
+fn set_current_thread_name(_name: &str) -> bool {
+    false
+}
+
+/// Pin the current thread to `cpu`. Returns `true` on success.
+#[cfg(target_os = "linux")]
+fn set_current_thread_affinity(cpu: usize) -> bool {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return false;
+    }
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0
+    }
+}
+
+/// Pin the current thread to `cpu`. Returns `true` on success.
+#[cfg(not(target_os = "linux"))]
+fn set_current_thread_affinity(_cpu: usize) -> bool {
+    false
+}
+
+/// Make the current thread flush denormal floats to zero. Returns `true` on success, or `false` if
+/// this is not supported on the current platform.
+///
+/// Computing with denormals is very slow on most processors, which can cause xruns in filters and
+/// reverbs whose state decays towards zero. This is meant to be called on the process thread, for
+/// example from `NotificationHandler::thread_init`, or through `ThreadInitConfig::flush_denormals`.
+/// See `DenormalFlushGuard` to only flush within a scope.
+///
+/// On x86_64 this sets the flush to zero (FTZ) and denormals are zero (DAZ) bits of MXCSR, and on
+/// aarch64 the flush to zero (FZ) bit of FPCR. It does nothing on other platforms.
+pub fn enable_denormal_flushing() -> bool {
+    match fp_control() {
+        Some(control) => {
+            set_fp_control(control | FLUSH_DENORMALS);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns `true` if the current thread flushes denormal floats to zero, see
+/// `enable_denormal_flushing`.
+pub fn denormal_flushing_enabled() -> bool {
+    match fp_control() {
+        Some(control) => control & FLUSH_DENORMALS == FLUSH_DENORMALS,
+        None => false,
+    }
+}
+
+/// Flushes denormal floats to zero on the current thread until it is dropped, after which the
+/// previous setting is restored. See `enable_denormal_flushing`.
+///
+/// # Example
+/// ```
+/// {
+///     let _guard = jack::DenormalFlushGuard::new();
+///     // Denormals are flushed to zero here, on supported platforms.
+/// }
+/// // The previous setting is restored here.
+/// ```
+#[derive(Debug)]
+pub struct DenormalFlushGuard {
+    previous: Option<u64>,
+    // The setting is per thread, so it must be restored on the same thread.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+impl DenormalFlushGuard {
+    /// Start flushing denormal floats to zero on the current thread.
+    pub fn new() -> DenormalFlushGuard {
+        let previous = fp_control();
+        enable_denormal_flushing();
+        DenormalFlushGuard {
+            previous,
+            _not_send: std::marker::PhantomData,
+        }
+    }
+}
+
+impl Default for DenormalFlushGuard {
+    fn default() -> DenormalFlushGuard {
+        DenormalFlushGuard::new()
+    }
+}
+
+/// Restores the previous setting.
+impl Drop for DenormalFlushGuard {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous {
+            let control = fp_control().unwrap_or(previous);
+            set_fp_control((control & !FLUSH_DENORMALS) | (previous & FLUSH_DENORMALS));
+        }
+    }
+}
+
+/// The bits of the floating point control register that flush denormals to zero.
+#[cfg(target_arch = "x86_64")]
+const FLUSH_DENORMALS: u64 = (1 << 15) | (1 << 6);
+#[cfg(target_arch = "aarch64")]
+const FLUSH_DENORMALS: u64 = 1 << 24;
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+const FLUSH_DENORMALS: u64 = 0;
+
+/// Read MXCSR of the current thread.
+#[cfg(target_arch = "x86_64")]
+fn fp_control() -> Option<u64> {
+    let mut mxcsr: u32 = 0;
+    unsafe {
+        std::arch::asm!("stmxcsr [{}]", in(reg) &mut mxcsr, options(nostack, preserves_flags));
+    }
+    Some(mxcsr as u64)
+}
+
+/// Write MXCSR of the current thread.
+#[cfg(target_arch = "x86_64")]
+fn set_fp_control(control: u64) {
+    let mxcsr = control as u32;
+    unsafe {
+        std::arch::asm!("ldmxcsr [{}]", in(reg) &mxcsr, options(nostack, preserves_flags));
+    }
+}
+
+/// Read FPCR of the current thread.
+#[cfg(target_arch = "aarch64")]
+fn fp_control() -> Option<u64> {
+    let fpcr: u64;
+    unsafe {
+        std::arch::asm!("mrs {}, fpcr", out(reg) fpcr, options(nomem, nostack, preserves_flags));
+    }
+    Some(fpcr)
+}
+
+/// Write FPCR of the current thread.
+#[cfg(target_arch = "aarch64")]
+fn set_fp_control(fpcr: u64) {
+    unsafe {
+        std::arch::asm!("msr fpcr, {}", in(reg) fpcr, options(nomem, nostack, preserves_flags));
+    }
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn fp_control() -> Option<u64> {
+    None
+}
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+fn set_fp_control(_control: u64) {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn thread_init_config_default_does_nothing() {
+        let config = ThreadInitConfig::default();
+        assert_eq!(config.thread_name, None);
+        assert_eq!(config.cpu_affinity, None);
+        assert!(!config.flush_denormals);
+        thread::spawn(move || config.apply()).join().unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn thread_init_config_sets_truncated_name() {
+        let config = ThreadInitConfig {
+            thread_name: Some("a_very_long_thread_name".to_string()),
+            ..ThreadInitConfig::default()
+        };
+        let name = thread::spawn(move || {
+            config.apply();
+            let mut buf = [0 as libc::c_char; 16];
+            let res =
+                unsafe { libc::pthread_getname_np(libc::pthread_self(), buf.as_mut_ptr(), 16) };
+            assert_eq!(res, 0);
+            unsafe { ffi::CStr::from_ptr(buf.as_ptr()) }
+                .to_string_lossy()
+                .into_owned()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(name, "a_very_long_thr");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn thread_init_config_ignores_invalid_cpu() {
+        let config = ThreadInitConfig {
+            cpu_affinity: Some(usize::MAX),
+            ..ThreadInitConfig::default()
+        };
+        thread::spawn(move || config.apply()).join().unwrap();
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn thread_init_config_flushes_denormals() {
+        let config = ThreadInitConfig {
+            flush_denormals: true,
+            ..ThreadInitConfig::default()
+        };
+        let flushed = thread::spawn(move || {
+            config.apply();
+            std::hint::black_box(f32::MIN_POSITIVE) / std::hint::black_box(2.0)
+        })
+        .join()
+        .unwrap();
+        assert_eq!(flushed, 0.0);
+        let denormal = std::hint::black_box(f32::MIN_POSITIVE) / std::hint::black_box(2.0);
+        assert!(denormal > 0.0);
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn enable_denormal_flushing_sets_control_bits() {
+        let control = thread::spawn(|| {
+            assert!(!denormal_flushing_enabled());
+            assert!(enable_denormal_flushing());
+            assert!(denormal_flushing_enabled());
+            fp_control().unwrap()
+        })
+        .join()
+        .unwrap();
+        #[cfg(target_arch = "x86_64")]
+        assert_eq!(control & (1 << 15 | 1 << 6), 1 << 15 | 1 << 6);
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(control & 1 << 24, 1 << 24);
+    }
+
+    #[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+    #[test]
+    fn denormal_flush_guard_restores_previous_state() {
+        thread::spawn(|| {
+            let before = fp_control();
+            {
+                let _guard = DenormalFlushGuard::new();
+                assert!(denormal_flushing_enabled());
+                let flushed = std::hint::black_box(f32::MIN_POSITIVE) / std::hint::black_box(2.0);
+                assert_eq!(flushed, 0.0);
+            }
+            assert!(!denormal_flushing_enabled());
+            // Only the flushing bits are restored, the status flags are left as they are.
+            assert_eq!(
+                fp_control().unwrap() & FLUSH_DENORMALS,
+                before.unwrap() & FLUSH_DENORMALS
+            );
+
+            // A guard on a thread that already flushes keeps it flushing.
+            enable_denormal_flushing();
+            drop(DenormalFlushGuard::new());
+            assert!(denormal_flushing_enabled());
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    #[test]
+    fn enable_denormal_flushing_is_unsupported() {
+        assert!(!enable_denormal_flushing());
+        assert!(!denormal_flushing_enabled());
+        drop(DenormalFlushGuard::new());
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    #[test]
+    fn thread_real_time_scheduling_can_be_acquired_and_dropped() {
+        thread::spawn(|| {
+            // Acquiring fails without the privileges to use real-time scheduling, but dropping
+            // works regardless.
+            let _ = acquire_current_thread_real_time_scheduling(10);
+            assert_eq!(drop_current_thread_real_time_scheduling(), Ok(()));
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -13,11 +13,13 @@ pub struct Transport {
 }
 
 /// A structure representing the transport position.
+#[derive(Clone, Copy)]
 #[repr(transparent)]
-pub struct TransportPosition(j::jack_position_t);
+pub struct TransportPosition(pub(crate) j::jack_position_t);
 
 /// A representation of transport state.
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TransportState {
     Stopped,
     Rolling,
@@ -165,7 +167,7 @@ impl Transport {
     }
 
     //helper to convert to TransportState
-    fn state_from_ffi(state: j::jack_transport_state_t) -> TransportState {
+    pub(crate) fn state_from_ffi(state: j::jack_transport_state_t) -> TransportState {
         match state {
             j::JackTransportStopped => TransportState::Stopped,
             j::JackTransportStarting => TransportState::Starting,
@@ -390,10 +392,10 @@ impl std::fmt::Debug for TransportPosition {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut s = f.debug_struct("TransportPosition");
         let mut d = s
-            .field("unique_1", unsafe { &self.0.unique_1 })
-            .field("usecs", unsafe { &self.0.usecs })
-            .field("frame", unsafe { &self.0.frame })
-            .field("frame_rate", unsafe { &self.0.frame_rate })
+            .field("unique_1", &{ self.0.unique_1 })
+            .field("usecs", &{ self.0.usecs })
+            .field("frame", &{ self.0.frame })
+            .field("frame_rate", &{ self.0.frame_rate })
             .field("valid", &format!("{:#b}", { self.0.valid }));
         if let Some(bbt) = self.bbt() {
             d = d.field("bbt", &bbt);
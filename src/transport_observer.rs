@@ -0,0 +1,361 @@
+use std::sync::mpsc;
+use std::{thread, time};
+
+use crate::{Frames, Transport, TransportState, TransportStatePosition};
+
+/// How far, in seconds of audio, the transport may run ahead of the time between two polls
+/// before the difference counts as a relocation. Covers the position only moving once per
+/// process cycle.
+const RELOCATE_SLACK_SECS: f64 = 0.25;
+
+/// A change of the JACK transport, as seen by a `TransportObserver`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransportEvent {
+    /// The transport left the stopped state, it is rolling or waiting for slow-sync clients.
+    Started,
+    /// The transport stopped.
+    Stopped,
+    /// The position jumped from `from` to `to` instead of moving along with time.
+    Relocated { from: Frames, to: Frames },
+    /// The tempo changed. The tempo is only known while the timebase master provides the BBT
+    /// position.
+    TempoChanged { bpm: f64 },
+}
+
+/// Reports changes of the JACK transport as `TransportEvent`s.
+///
+/// JACK has no notification for transport changes, clients either poll or become the timebase
+/// or sync client. A `TransportObserver` polls `Transport::query` on its own thread and compares
+/// each result with the previous one, which suits GUIs that show the state of the transport.
+/// Changes that are undone between two polls, such as a quick stop and start, are missed.
+///
+/// The first poll is only used as a reference, use `Transport::query` for the state the
+/// observer starts from. The thread exits when the observer is dropped, the receiver of the
+/// events is dropped, or the client of the `Transport` is closed.
+///
+/// ```no_run
+/// let (client, _status) =
+///     jack::Client::new("observer", jack::ClientOptions::NO_START_SERVER).unwrap();
+/// let (_observer, events) = jack::TransportObserver::start(
+///     client.transport(),
+///     std::time::Duration::from_millis(50),
+/// );
+/// for event in events {
+///     println!("{:?}", event);
+/// }
+/// ```
+pub struct TransportObserver {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl TransportObserver {
+    /// Start polling `transport` every `interval`. The events are sent to the returned receiver in
+    /// the order they are detected. Within one poll, a relocation is reported before a change of
+    /// state, which is reported before a change of tempo.
+    pub fn start(
+        transport: Transport,
+        interval: time::Duration,
+    ) -> (TransportObserver, mpsc::Receiver<TransportEvent>) {
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let (events_tx, events_rx) = mpsc::channel();
+        let thread = thread::spawn(move || observe(transport, interval, stop_rx, events_tx));
+        let observer = TransportObserver {
+            stop: Some(stop_tx),
+            thread: Some(thread),
+        };
+        (observer, events_rx)
+    }
+
+    /// Returns `true` while the polling thread runs.
+    pub fn is_running(&self) -> bool {
+        self.thread.as_ref().is_some_and(|t| !t.is_finished())
+    }
+
+    /// Stop polling and wait for the thread to exit. Same as dropping the observer.
+    pub fn stop(self) {}
+}
+
+impl Drop for TransportObserver {
+    fn drop(&mut self) {
+        // Wakes up the thread, even in the middle of its interval.
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for TransportObserver {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TransportObserver")
+            .field("is_running", &self.is_running())
+            .finish()
+    }
+}
+
+fn observe(
+    transport: Transport,
+    interval: time::Duration,
+    stop: mpsc::Receiver<()>,
+    events: mpsc::Sender<TransportEvent>,
+) {
+    let mut prev = match transport.query() {
+        Ok(tsp) => Snapshot::new(&tsp, time::Instant::now()),
+        Err(_) => return,
+    };
+    while let Err(mpsc::RecvTimeoutError::Timeout) = stop.recv_timeout(interval) {
+        let mut next = match transport.query() {
+            Ok(tsp) => Snapshot::new(&tsp, time::Instant::now()),
+            Err(_) => return,
+        };
+        // Keep the last known tempo while the BBT position is missing.
+        next.bpm = next.bpm.or(prev.bpm);
+        for event in changes(&prev, &next) {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+        prev = next;
+    }
+}
+
+/// The parts of the transport that are compared between polls.
+#[derive(Clone, Copy, Debug)]
+struct Snapshot {
+    state: TransportState,
+    frame: Frames,
+    frame_rate: Option<Frames>,
+    bpm: Option<f64>,
+    time: time::Instant,
+}
+
+impl Snapshot {
+    fn new(tsp: &TransportStatePosition, time: time::Instant) -> Snapshot {
+        Snapshot {
+            state: tsp.state,
+            frame: tsp.pos.frame(),
+            frame_rate: tsp.pos.frame_rate(),
+            bpm: tsp.pos.bbt().map(|bbt| bbt.bpm),
+            time,
+        }
+    }
+}
+
+/// The events that lead from `prev` to `next`.
+fn changes(prev: &Snapshot, next: &Snapshot) -> Vec<TransportEvent> {
+    let mut events = Vec::new();
+    if relocated(prev, next) {
+        events.push(TransportEvent::Relocated {
+            from: prev.frame,
+            to: next.frame,
+        });
+    }
+    let was_stopped = prev.state == TransportState::Stopped;
+    let is_stopped = next.state == TransportState::Stopped;
+    if was_stopped && !is_stopped {
+        events.push(TransportEvent::Started);
+    } else if !was_stopped && is_stopped {
+        events.push(TransportEvent::Stopped);
+    }
+    if let Some(bpm) = next.bpm {
+        if prev.bpm != Some(bpm) {
+            events.push(TransportEvent::TempoChanged { bpm });
+        }
+    }
+    events
+}
+
+/// Returns `true` if the position between `prev` and `next` did not come from the transport
+/// rolling.
+fn relocated(prev: &Snapshot, next: &Snapshot) -> bool {
+    let rolled = prev.state == TransportState::Rolling || next.state == TransportState::Rolling;
+    if !rolled {
+        return prev.frame != next.frame;
+    }
+    // The position only moves forward while rolling, so a backwards jump is a relocation. The
+    // difference wraps, so a position that wrapped past `Frames::MAX` is still moving forward.
+    let advanced = next.frame.wrapping_sub(prev.frame);
+    if advanced > Frames::MAX / 2 {
+        return true;
+    }
+    match next.frame_rate.or(prev.frame_rate) {
+        Some(rate) => {
+            let elapsed = next.time.saturating_duration_since(prev.time).as_secs_f64();
+            let limit = (elapsed + RELOCATE_SLACK_SECS) * f64::from(rate);
+            f64::from(advanced) > limit
+        }
+        // Without the frame rate, only backwards jumps can be told apart.
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    const RATE: Frames = 48000;
+
+    fn snapshot(state: TransportState, frame: Frames, ms: u64) -> Snapshot {
+        Snapshot {
+            state,
+            frame,
+            frame_rate: Some(RATE),
+            bpm: None,
+            time: start_time() + Duration::from_millis(ms),
+        }
+    }
+
+    fn start_time() -> time::Instant {
+        lazy_static::lazy_static! {
+            static ref START: time::Instant = time::Instant::now();
+        }
+        *START
+    }
+
+    #[test]
+    fn transport_observer_ignores_rolling_transport() {
+        let prev = snapshot(TransportState::Rolling, 1000, 0);
+        let next = snapshot(TransportState::Rolling, 1000 + RATE / 10, 100);
+        assert_eq!(changes(&prev, &next), vec![]);
+        let still = snapshot(TransportState::Stopped, 1000, 0);
+        assert_eq!(changes(&still, &still), vec![]);
+    }
+
+    #[test]
+    fn transport_observer_reports_state_changes() {
+        let stopped = snapshot(TransportState::Stopped, 0, 0);
+        let starting = snapshot(TransportState::Starting, 0, 100);
+        let rolling = snapshot(TransportState::Rolling, RATE / 10, 200);
+        assert_eq!(changes(&stopped, &starting), vec![TransportEvent::Started]);
+        assert_eq!(changes(&starting, &rolling), vec![]);
+        assert_eq!(changes(&stopped, &rolling), vec![TransportEvent::Started]);
+        let stopped_again = snapshot(TransportState::Stopped, RATE / 5, 300);
+        assert_eq!(
+            changes(&rolling, &stopped_again),
+            vec![TransportEvent::Stopped]
+        );
+    }
+
+    #[test]
+    fn transport_observer_reports_relocations() {
+        // While stopped, any movement is a relocation.
+        let prev = snapshot(TransportState::Stopped, 0, 0);
+        let next = snapshot(TransportState::Stopped, 10, 100);
+        assert_eq!(
+            changes(&prev, &next),
+            vec![TransportEvent::Relocated { from: 0, to: 10 }]
+        );
+
+        // While rolling, jumping further than time allows is a relocation.
+        let prev = snapshot(TransportState::Rolling, 0, 0);
+        let next = snapshot(TransportState::Rolling, 10 * RATE, 100);
+        assert_eq!(
+            changes(&prev, &next),
+            vec![TransportEvent::Relocated {
+                from: 0,
+                to: 10 * RATE
+            }]
+        );
+
+        // Relocating and starting within one poll reports both.
+        let prev = snapshot(TransportState::Stopped, 5 * RATE, 0);
+        let next = snapshot(TransportState::Rolling, 64, 100);
+        assert_eq!(
+            changes(&prev, &next),
+            vec![
+                TransportEvent::Relocated {
+                    from: 5 * RATE,
+                    to: 64
+                },
+                TransportEvent::Started
+            ]
+        );
+    }
+
+    #[test]
+    fn transport_observer_treats_backwards_jumps_as_relocations() {
+        let prev = snapshot(TransportState::Rolling, 1000, 0);
+        let next = snapshot(TransportState::Rolling, 999, 100);
+        assert_eq!(
+            changes(&prev, &next),
+            vec![TransportEvent::Relocated {
+                from: 1000,
+                to: 999
+            }]
+        );
+
+        // Wrapping past the largest position is not a jump backwards.
+        let prev = snapshot(TransportState::Rolling, Frames::MAX - 100, 0);
+        let next = snapshot(TransportState::Rolling, RATE / 20, 100);
+        assert_eq!(changes(&prev, &next), vec![]);
+
+        // Without the frame rate, forward jumps can't be detected.
+        let mut prev = snapshot(TransportState::Rolling, 1000, 0);
+        let mut next = snapshot(TransportState::Rolling, 1000 + 100 * RATE, 100);
+        prev.frame_rate = None;
+        next.frame_rate = None;
+        assert_eq!(changes(&prev, &next), vec![]);
+    }
+
+    #[test]
+    fn transport_observer_reports_tempo_changes() {
+        let mut prev = snapshot(TransportState::Rolling, 0, 0);
+        let mut next = snapshot(TransportState::Rolling, RATE / 10, 100);
+        next.bpm = Some(120.0);
+        assert_eq!(
+            changes(&prev, &next),
+            vec![TransportEvent::TempoChanged { bpm: 120.0 }]
+        );
+        prev.bpm = Some(120.0);
+        assert_eq!(changes(&prev, &next), vec![]);
+    }
+
+    #[test]
+    fn transport_observer_follows_transport_of_other_client() {
+        use crate::test_support::start_dummy_server;
+        use crate::ClientOptions;
+
+        let server = match start_dummy_server() {
+            Some(server) => server,
+            None => return,
+        };
+        let (observed, _) = server
+            .open_client("observed", ClientOptions::empty())
+            .unwrap();
+        let (driver, _) = server
+            .open_client("driver", ClientOptions::empty())
+            .unwrap();
+        let transport = driver.transport();
+        let (observer, events) =
+            TransportObserver::start(observed.transport(), Duration::from_millis(10));
+        let next_event = || events.recv_timeout(Duration::from_secs(5)).unwrap();
+
+        transport.start().unwrap();
+        assert_eq!(next_event(), TransportEvent::Started);
+        thread::sleep(Duration::from_millis(500));
+
+        transport.locate(0).unwrap();
+        match next_event() {
+            TransportEvent::Relocated { from, to } => assert!(to < from, "{} -> {}", from, to),
+            event => panic!("expected a relocation, got {:?}", event),
+        }
+
+        transport.stop().unwrap();
+        assert_eq!(next_event(), TransportEvent::Stopped);
+        thread::sleep(Duration::from_millis(100));
+        transport.locate(RATE).unwrap();
+        match next_event() {
+            TransportEvent::Relocated { to, .. } => assert_eq!(to, RATE),
+            event => panic!("expected a relocation, got {:?}", event),
+        }
+
+        assert!(observer.is_running());
+        observer.stop();
+        assert_eq!(
+            events.recv_timeout(Duration::from_secs(1)),
+            Err(mpsc::RecvTimeoutError::Disconnected)
+        );
+    }
+}
@@ -0,0 +1,396 @@
+//! Typed frame counts and times, which keep frames and microseconds from being mixed up.
+//!
+//! Most of the crate uses the plain aliases `jack::Frames` (`u32`) and `jack::Time` (`u64`), which
+//! are kept for compatibility and at the FFI boundary. `units::Frames` and `units::Time` wrap the
+//! same values and only provide the arithmetic that makes sense for them. Adding a
+//! `units::Time` to a `units::Frames` does not compile, converting between them needs a sample
+//! rate.
+//!
+//! # Migrating
+//!
+//! The types convert from and into the aliases with `From`, so they can be adopted one function at
+//! a time:
+//!
+//! ```
+//! use jack::units::{Frames, Time};
+//!
+//! fn fade_length(sample_rate: usize) -> Frames {
+//!     Frames::from_time(Time::from_millis(10), sample_rate)
+//! }
+//!
+//! // Where the crate takes or returns a `jack::Frames`, convert with `into`.
+//! let raw: jack::Frames = fade_length(48000).into();
+//! assert_eq!(raw, 480);
+//! let typed = Frames::from(raw) + Frames(32);
+//! assert_eq!(typed, Frames(512));
+//! ```
+//!
+//! New functions that take frame counts or times accept `impl Into<units::Frames>` or
+//! `impl Into<units::Time>`, which works with both types, like `ProcessScope::frames_to_time`
+//! and `ProcessScope::time_to_frames`.
+use jack_sys as j;
+use std::{fmt, ops, time};
+
+/// A number of sample frames, or a position in frames such as the frame time of JACK.
+///
+/// Positions wrap around after `u32::MAX`, like the frame time of JACK does after about a day at
+/// 48kHz. The arithmetic operators therefore wrap around as well, use `checked_add` and
+/// `checked_sub` where an overflow is an error. For the same reason `Frames` does not implement
+/// `Ord`, positions are compared with `is_before` and `is_after`, which treat the positions as
+/// being less than half the range apart.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Frames(pub j::jack_nframes_t);
+
+impl Frames {
+    /// No frames.
+    pub const ZERO: Frames = Frames(0);
+
+    /// The number of frames as the raw type.
+    pub fn get(self) -> j::jack_nframes_t {
+        self.0
+    }
+
+    /// Add `rhs`, or `None` if the result overflows.
+    pub fn checked_add(self, rhs: Frames) -> Option<Frames> {
+        self.0.checked_add(rhs.0).map(Frames)
+    }
+
+    /// Subtract `rhs`, or `None` if the result would be negative.
+    pub fn checked_sub(self, rhs: Frames) -> Option<Frames> {
+        self.0.checked_sub(rhs.0).map(Frames)
+    }
+
+    /// Multiply by `factor`, rounded to the nearest frame. Results outside of the range of
+    /// `Frames` saturate, and `NaN` results in `Frames::ZERO`. Useful to convert between sample
+    /// rates, or to take a fraction of a buffer.
+    pub fn scale(self, factor: f64) -> Frames {
+        Frames((f64::from(self.0) * factor).round() as j::jack_nframes_t)
+    }
+
+    /// The number of frames from `earlier` to `self`, taking wrap around into account.
+    pub fn since(self, earlier: Frames) -> Frames {
+        Frames(self.0.wrapping_sub(earlier.0))
+    }
+
+    /// Returns `true` if the position `self` comes before `other`, taking wrap around into
+    /// account.
+    pub fn is_before(self, other: Frames) -> bool {
+        let ahead = other.since(self).0;
+        ahead != 0 && ahead <= j::jack_nframes_t::MAX / 2
+    }
+
+    /// Returns `true` if the position `self` comes after `other`, taking wrap around into account.
+    pub fn is_after(self, other: Frames) -> bool {
+        other.is_before(self)
+    }
+
+    /// The duration of the frames at `sample_rate`. Returns `Time::ZERO` if `sample_rate` is 0.
+    pub fn to_time(self, sample_rate: usize) -> Time {
+        match sample_rate {
+            0 => Time::ZERO,
+            rate => Time((u64::from(self.0) * 1_000_000 + rate as u64 / 2) / rate as u64),
+        }
+    }
+
+    /// The number of frames that last `time` at `sample_rate`, rounded to the nearest frame.
+    /// Saturates at the largest number of frames.
+    pub fn from_time(time: Time, sample_rate: usize) -> Frames {
+        let frames = (u128::from(time.0) * sample_rate as u128 + 500_000) / 1_000_000;
+        Frames(frames.min(u128::from(j::jack_nframes_t::MAX)) as j::jack_nframes_t)
+    }
+}
+
+impl From<j::jack_nframes_t> for Frames {
+    fn from(n: j::jack_nframes_t) -> Frames {
+        Frames(n)
+    }
+}
+
+impl From<Frames> for j::jack_nframes_t {
+    fn from(n: Frames) -> j::jack_nframes_t {
+        n.0
+    }
+}
+
+impl fmt::Display for Frames {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} frames", self.0)
+    }
+}
+
+/// Wraps around, see `Frames`.
+impl ops::Add for Frames {
+    type Output = Frames;
+
+    fn add(self, rhs: Frames) -> Frames {
+        Frames(self.0.wrapping_add(rhs.0))
+    }
+}
+
+/// Wraps around, see `Frames`.
+impl ops::Sub for Frames {
+    type Output = Frames;
+
+    fn sub(self, rhs: Frames) -> Frames {
+        Frames(self.0.wrapping_sub(rhs.0))
+    }
+}
+
+impl ops::AddAssign for Frames {
+    fn add_assign(&mut self, rhs: Frames) {
+        *self = *self + rhs;
+    }
+}
+
+impl ops::SubAssign for Frames {
+    fn sub_assign(&mut self, rhs: Frames) {
+        *self = *self - rhs;
+    }
+}
+
+/// Same as `Frames::scale`.
+impl ops::Mul<f64> for Frames {
+    type Output = Frames;
+
+    fn mul(self, factor: f64) -> Frames {
+        self.scale(factor)
+    }
+}
+
+/// A time in microseconds, such as the value of `jack::get_time`, or the difference between two
+/// of them.
+///
+/// Times don't wrap around in practice, so they are ordered like numbers. Differences saturate at
+/// zero, since a time can't be before another one by a negative amount.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time(pub j::jack_time_t);
+
+impl Time {
+    /// No time.
+    pub const ZERO: Time = Time(0);
+
+    /// A time of `millis` milliseconds.
+    pub fn from_millis(millis: u64) -> Time {
+        Time(millis.saturating_mul(1000))
+    }
+
+    /// The number of microseconds as the raw type.
+    pub fn as_micros(self) -> j::jack_time_t {
+        self.0
+    }
+
+    /// Add `rhs`, or `None` if the result overflows.
+    pub fn checked_add(self, rhs: Time) -> Option<Time> {
+        self.0.checked_add(rhs.0).map(Time)
+    }
+
+    /// Subtract `rhs`, or `None` if `rhs` is later than `self`.
+    pub fn checked_sub(self, rhs: Time) -> Option<Time> {
+        self.0.checked_sub(rhs.0).map(Time)
+    }
+
+    /// The time from `earlier` to `self`, or `Time::ZERO` if `earlier` is later than `self`.
+    pub fn since(self, earlier: Time) -> Time {
+        Time(self.0.saturating_sub(earlier.0))
+    }
+
+    /// The time as a `Duration`.
+    pub fn to_duration(self) -> time::Duration {
+        time::Duration::from_micros(self.0)
+    }
+
+    /// The number of frames that pass in this time at `sample_rate`, see `Frames::from_time`.
+    pub fn to_frames(self, sample_rate: usize) -> Frames {
+        Frames::from_time(self, sample_rate)
+    }
+}
+
+impl From<j::jack_time_t> for Time {
+    fn from(t: j::jack_time_t) -> Time {
+        Time(t)
+    }
+}
+
+impl From<Time> for j::jack_time_t {
+    fn from(t: Time) -> j::jack_time_t {
+        t.0
+    }
+}
+
+/// Saturates at the largest time.
+impl From<time::Duration> for Time {
+    fn from(d: time::Duration) -> Time {
+        Time(d.as_micros().min(u128::from(j::jack_time_t::MAX)) as j::jack_time_t)
+    }
+}
+
+impl From<Time> for time::Duration {
+    fn from(t: Time) -> time::Duration {
+        t.to_duration()
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}us", self.0)
+    }
+}
+
+/// Saturates at the largest time.
+impl ops::Add for Time {
+    type Output = Time;
+
+    fn add(self, rhs: Time) -> Time {
+        Time(self.0.saturating_add(rhs.0))
+    }
+}
+
+/// Same as `Time::since`.
+impl ops::Sub for Time {
+    type Output = Time;
+
+    fn sub(self, rhs: Time) -> Time {
+        self.since(rhs)
+    }
+}
+
+impl ops::AddAssign for Time {
+    fn add_assign(&mut self, rhs: Time) {
+        *self = *self + rhs;
+    }
+}
+
+impl ops::SubAssign for Time {
+    fn sub_assign(&mut self, rhs: Time) {
+        *self = *self - rhs;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MAX: j::jack_nframes_t = j::jack_nframes_t::MAX;
+
+    #[test]
+    fn frames_add_and_subtract() {
+        assert_eq!(Frames(3) + Frames(4), Frames(7));
+        assert_eq!(Frames(7) - Frames(4), Frames(3));
+        let mut n = Frames(10);
+        n += Frames(5);
+        n -= Frames(3);
+        assert_eq!(n, Frames(12));
+    }
+
+    #[test]
+    fn frames_wrap_around() {
+        assert_eq!(Frames(MAX) + Frames(2), Frames(1));
+        assert_eq!(Frames(1) - Frames(2), Frames(MAX));
+        assert_eq!(Frames(5).since(Frames(MAX - 4)), Frames(10));
+    }
+
+    #[test]
+    fn frames_check_for_overflow() {
+        assert_eq!(Frames(MAX).checked_add(Frames(1)), None);
+        assert_eq!(Frames(MAX - 1).checked_add(Frames(1)), Some(Frames(MAX)));
+        assert_eq!(Frames(1).checked_sub(Frames(2)), None);
+        assert_eq!(Frames(2).checked_sub(Frames(2)), Some(Frames::ZERO));
+    }
+
+    #[test]
+    fn frames_scale() {
+        assert_eq!(Frames(1024) * 0.5, Frames(512));
+        assert_eq!(Frames(3).scale(0.5), Frames(2));
+        // 44.1kHz to 48kHz.
+        assert_eq!(Frames(44100).scale(48000.0 / 44100.0), Frames(48000));
+        assert_eq!(Frames(10).scale(-1.0), Frames::ZERO);
+        assert_eq!(Frames(MAX).scale(2.0), Frames(MAX));
+        assert_eq!(Frames(10).scale(f64::NAN), Frames::ZERO);
+    }
+
+    #[test]
+    fn frames_compare_positions_across_wrap_around() {
+        assert!(Frames(1).is_before(Frames(2)));
+        assert!(Frames(2).is_after(Frames(1)));
+        assert!(!Frames(2).is_before(Frames(2)));
+        assert!(!Frames(2).is_after(Frames(2)));
+        assert!(Frames(MAX - 10).is_before(Frames(5)));
+        assert!(Frames(5).is_after(Frames(MAX - 10)));
+        // Half the range apart is the furthest a position can be ahead.
+        assert!(Frames(0).is_before(Frames(MAX / 2)));
+        assert!(!Frames(0).is_before(Frames(MAX / 2 + 1)));
+        assert!(!Frames(0).is_after(Frames(MAX / 2 + 1)));
+        assert!(Frames(0).is_after(Frames(MAX / 2 + 2)));
+    }
+
+    #[test]
+    fn frames_convert_to_and_from_time() {
+        assert_eq!(Frames(48000).to_time(48000), Time(1_000_000));
+        assert_eq!(Frames(1024).to_time(48000), Time(21_333));
+        assert_eq!(Frames(1).to_time(44100), Time(23));
+        assert_eq!(Frames(MAX).to_time(1), Time(u64::from(MAX) * 1_000_000));
+        assert_eq!(Frames(10).to_time(0), Time::ZERO);
+        assert_eq!(Frames::from_time(Time(21_333), 48000), Frames(1024));
+        assert_eq!(Frames::from_time(Time::from_millis(10), 44100), Frames(441));
+        assert_eq!(Time(u64::MAX).to_frames(48000), Frames(MAX));
+        for n in [0, 1, 64, 1000, 48000, 1 << 24] {
+            assert_eq!(Frames(n).to_time(48000).to_frames(48000), Frames(n));
+        }
+    }
+
+    #[test]
+    fn frames_convert_to_and_from_raw() {
+        let raw: crate::Frames = Frames(12).into();
+        assert_eq!(raw, 12);
+        assert_eq!(Frames::from(12), Frames(12));
+        assert_eq!(Frames(12).get(), 12);
+        assert_eq!(Frames(12).to_string(), "12 frames");
+    }
+
+    #[test]
+    fn time_differences_saturate() {
+        assert_eq!(Time(10) - Time(4), Time(6));
+        assert_eq!(Time(4) - Time(10), Time::ZERO);
+        assert_eq!(Time(10).since(Time(4)), Time(6));
+        assert_eq!(Time(4).checked_sub(Time(10)), None);
+        assert_eq!(Time(10).checked_sub(Time(4)), Some(Time(6)));
+        let mut t = Time(10);
+        t -= Time(20);
+        assert_eq!(t, Time::ZERO);
+    }
+
+    #[test]
+    fn time_addition_saturates() {
+        assert_eq!(Time(3) + Time(4), Time(7));
+        assert_eq!(Time(u64::MAX) + Time(1), Time(u64::MAX));
+        assert_eq!(Time(u64::MAX).checked_add(Time(1)), None);
+        let mut t = Time(1);
+        t += Time(2);
+        assert_eq!(t, Time(3));
+    }
+
+    #[test]
+    fn time_is_ordered() {
+        assert!(Time(1) < Time(2));
+        assert_eq!(Time(5).max(Time(3)), Time(5));
+    }
+
+    #[test]
+    fn time_converts_to_and_from_duration_and_raw() {
+        assert_eq!(
+            Time::from_millis(3).to_duration(),
+            time::Duration::from_millis(3)
+        );
+        assert_eq!(Time::from(time::Duration::from_micros(1500)), Time(1500));
+        assert_eq!(
+            time::Duration::from(Time(7)),
+            time::Duration::from_micros(7)
+        );
+        assert_eq!(Time::from(time::Duration::MAX), Time(u64::MAX), "saturates");
+        let raw: crate::Time = Time(9).into();
+        assert_eq!(raw, 9);
+        assert_eq!(Time::from(9), Time(9));
+        assert_eq!(Time(9).as_micros(), 9);
+        assert_eq!(Time(9).to_string(), "9us");
+    }
+}
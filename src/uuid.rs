@@ -0,0 +1,126 @@
+use jack_sys as j;
+use std::{ffi, fmt, str};
+
+use crate::jack_utils::to_cstring;
+use crate::Error;
+
+/// The size of the buffer that `jack_uuid_unparse` writes to, including the NUL byte.
+const UUID_STRING_SIZE: usize = 37;
+
+/// The unique identifier of a client or port, the subject of Metadata properties. See
+/// `Client::uuid` and `Port::uuid`.
+///
+/// The textual form, as used by `Client::uuid_string` and the session API, is converted with
+/// `Uuid::parse` and `to_string`.
+///
+/// # Example
+/// ```
+/// let uuid = jack::Uuid::parse("8589934593").unwrap();
+/// assert_eq!(uuid.to_string(), "8589934593");
+/// assert_eq!(uuid, jack::Uuid::from_raw(8589934593));
+/// assert!(!uuid.is_empty());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Uuid(j::jack_uuid_t);
+
+impl Uuid {
+    /// The empty uuid, which does not identify anything.
+    pub const EMPTY: Uuid = Uuid(0);
+
+    /// Create a uuid from the integer used by JACK.
+    pub const fn from_raw(raw: j::jack_uuid_t) -> Uuid {
+        Uuid(raw)
+    }
+
+    /// The integer used by JACK.
+    pub const fn raw(self) -> j::jack_uuid_t {
+        self.0
+    }
+
+    /// Parse the textual form of a uuid, as returned by `to_string`.
+    ///
+    /// `Err(Error::InvalidUuid(s))` is returned if JACK does not accept `s` as a uuid.
+    pub fn parse(s: &str) -> Result<Uuid, Error> {
+        let invalid = || Error::InvalidUuid(s.to_string());
+        let s_c = to_cstring(s).map_err(|_| invalid())?;
+        let mut raw = 0;
+        match unsafe { j::jack_uuid_parse(s_c.as_ptr(), &mut raw) } {
+            0 => Ok(Uuid(raw)),
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Returns `true` if this is the empty uuid.
+    pub fn is_empty(self) -> bool {
+        unsafe { j::jack_uuid_empty(self.0) != 0 }
+    }
+}
+
+impl From<j::jack_uuid_t> for Uuid {
+    fn from(raw: j::jack_uuid_t) -> Uuid {
+        Uuid(raw)
+    }
+}
+
+impl From<Uuid> for j::jack_uuid_t {
+    fn from(uuid: Uuid) -> j::jack_uuid_t {
+        uuid.0
+    }
+}
+
+impl str::FromStr for Uuid {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Uuid, Error> {
+        Uuid::parse(s)
+    }
+}
+
+impl fmt::Display for Uuid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = [0 as libc::c_char; UUID_STRING_SIZE];
+        let s = unsafe {
+            j::jack_uuid_unparse(self.0, buf.as_mut_ptr());
+            ffi::CStr::from_ptr(buf.as_ptr())
+        };
+        f.write_str(&s.to_string_lossy())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn uuid_round_trips_through_text() {
+        for raw in [0x1_0000_0000, 0x2_0000_0001, u64::MAX] {
+            let uuid = Uuid::from_raw(raw);
+            assert_eq!(uuid.to_string(), raw.to_string());
+            assert_eq!(Uuid::parse(&uuid.to_string()), Ok(uuid));
+            assert_eq!(uuid.to_string().parse(), Ok(uuid));
+            assert_eq!(j::jack_uuid_t::from(uuid), raw);
+            assert_eq!(Uuid::from(raw), uuid);
+        }
+    }
+
+    #[test]
+    fn uuid_rejects_invalid_text() {
+        for s in ["", "not a uuid", "12\u{0}3"] {
+            assert_eq!(Uuid::parse(s), Err(Error::InvalidUuid(s.to_string())));
+        }
+    }
+
+    #[test]
+    fn uuid_knows_empty_uuid() {
+        assert!(Uuid::EMPTY.is_empty());
+        assert!(Uuid::default().is_empty());
+        assert!(!Uuid::from_raw(0x2_0000_0001).is_empty());
+    }
+
+    #[test]
+    fn uuid_orders_by_integer() {
+        let mut uuids = vec![Uuid::from_raw(3), Uuid::EMPTY, Uuid::from_raw(2)];
+        uuids.sort();
+        assert_eq!(uuids, [Uuid::EMPTY, Uuid::from_raw(2), Uuid::from_raw(3)]);
+    }
+}